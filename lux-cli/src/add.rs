@@ -1,17 +1,22 @@
-use eyre::{OptionExt, Result};
+use eyre::{eyre, OptionExt, Result};
+use inquire::Select;
 use itertools::{Either, Itertools};
 use lux_lib::{
     config::Config,
+    git::shorthand::GitUrlShorthand,
+    lockfile::{OptState, PinnedState},
+    package::{PackageName, PackageReq},
     progress::{MultiProgress, Progress, ProgressBar},
-    project::Project,
+    project::{DependencyConflict, DependencyConflictResolution, Project},
     remote_package_db::RemotePackageDB,
-    rockspec::lua_dependency::{self},
+    rockspec::lua_dependency::{DependencyType, LuaDependencySpec, LuaDependencyType},
 };
 
 use crate::utils::project::{
     sync_build_dependencies_if_locked, sync_dependencies_if_locked,
     sync_test_dependencies_if_locked, PackageReqOrGitShorthand,
 };
+use crate::utils::summary::Summary;
 
 #[derive(clap::Args)]
 pub struct Add {
@@ -31,73 +36,214 @@ pub struct Add {
     force: bool,
 
     /// Install the package as a development dependency. {n}
-    /// Also called `dev`.
-    #[arg(short, long, alias = "dev", visible_short_aliases = ['d', 'b'])]
+    /// Also called `dev`. {n}
+    /// If no packages are specified, adds the other packages as development dependencies.
+    #[arg(short, long, alias = "dev", visible_short_aliases = ['d', 'b'], num_args = 0..)]
     build: Option<Vec<PackageReqOrGitShorthand>>,
 
-    /// Install the package as a test dependency.
-    #[arg(short, long, visible_short_alias = 't')]
+    /// Install the package as a test dependency. {n}
+    /// If no packages are specified, adds the other packages as test dependencies.
+    #[arg(short, long, visible_short_alias = 't', num_args = 0..)]
     test: Option<Vec<PackageReqOrGitShorthand>>,
+
+    /// Pin the added packages, so that they are not upgraded. {n}
+    /// Not supported for git dependencies.
+    #[arg(long)]
+    pin: bool,
+
+    /// Add the packages as optional dependencies. {n}
+    /// Not supported for git dependencies.
+    #[arg(long, alias = "opt")]
+    optional: bool,
+
+    /// Sort the dependency tables of the lux.toml alphabetically.
+    #[arg(long)]
+    sorted: bool,
+
+    /// Do not prompt if a package is already a dependency with a different version requirement. {n}
+    /// Widens the existing version requirement if possible, and replaces it otherwise. {n}
+    /// Also required to add packages with a name similar to a much more popular package.
+    #[arg(long, short = 'y')]
+    yes: bool,
 }
 
 pub async fn add(data: Add, config: Config) -> Result<()> {
     let mut project = Project::current()?.ok_or_eyre("No project found")?;
 
-    let db = RemotePackageDB::from_config(&config, &Progress::Progress(ProgressBar::new())).await?;
+    // `--build` and `--test` without packages apply to the other packages.
+    let add_to_build = data.build.as_ref().is_some_and(Vec::is_empty);
+    let add_to_test = data.test.as_ref().is_some_and(Vec::is_empty);
+    let mut build_packages = data.build.unwrap_or_default();
+    let mut test_packages = data.test.unwrap_or_default();
+    let regular_packages = if add_to_build || add_to_test {
+        if add_to_build {
+            build_packages.extend(data.package_req.iter().cloned());
+        }
+        if add_to_test {
+            test_packages.extend(data.package_req.iter().cloned());
+        }
+        Vec::new()
+    } else {
+        data.package_req
+    };
 
-    let progress = MultiProgress::new_arc();
+    let (regular, regular_git) = partition_packages(regular_packages);
+    let (build, build_git) = partition_packages(build_packages);
+    let (test, test_git) = partition_packages(test_packages);
 
-    let (dependencies, git_dependencies): (Vec<_>, Vec<_>) =
-        data.package_req.iter().partition_map(|req| match req {
-            PackageReqOrGitShorthand::PackageReq(req) => Either::Left(req.clone()),
-            PackageReqOrGitShorthand::GitShorthand(url) => Either::Right(url.clone()),
-        });
+    if (data.pin || data.optional)
+        && !(regular_git.is_empty() && build_git.is_empty() && test_git.is_empty())
+    {
+        return Err(eyre!(
+            "`--pin` and `--optional` are not supported for git dependencies"
+        ));
+    }
 
-    if !data.package_req.is_empty() {
-        project
-            .add(lua_dependency::DependencyType::Regular(dependencies), &db)
-            .await?;
-        project
-            .add_git(lua_dependency::LuaDependencyType::Regular(git_dependencies))
-            .await?;
-        sync_dependencies_if_locked(&project, progress.clone(), &config).await?;
+    let package_names = regular
+        .iter()
+        .chain(&build)
+        .chain(&test)
+        .map(|req| req.name().clone())
+        .collect_vec();
+
+    let to_spec = |req: PackageReq| {
+        LuaDependencySpec::from(req)
+            .with_pin(PinnedState::from(data.pin))
+            .with_opt(OptState::from(data.optional))
+    };
+    let sync_regular = !(regular.is_empty() && regular_git.is_empty());
+    let sync_build = !(build.is_empty() && build_git.is_empty());
+    let sync_test = !(test.is_empty() && test_git.is_empty());
+
+    let mut dependencies = Vec::new();
+    if !regular.is_empty() {
+        dependencies.push(DependencyType::Regular(
+            regular.into_iter().map(to_spec).collect(),
+        ));
+    }
+    if !build.is_empty() {
+        dependencies.push(DependencyType::Build(
+            build.into_iter().map(to_spec).collect(),
+        ));
+    }
+    if !test.is_empty() {
+        dependencies.push(DependencyType::Test(
+            test.into_iter().map(to_spec).collect(),
+        ));
     }
 
-    let build_packages = data.build.unwrap_or_default();
-    if !build_packages.is_empty() {
-        let (dependencies, git_dependencies): (Vec<_>, Vec<_>) =
-            build_packages.iter().partition_map(|req| match req {
-                PackageReqOrGitShorthand::PackageReq(req) => Either::Left(req.clone()),
-                PackageReqOrGitShorthand::GitShorthand(url) => Either::Right(url.clone()),
-            });
+    if !dependencies.is_empty() {
+        let db =
+            RemotePackageDB::from_config(&config, &Progress::Progress(ProgressBar::new())).await?;
+        check_typosquatting(&db, &package_names, data.yes)?;
         project
-            .add(lua_dependency::DependencyType::Build(dependencies), &db)
+            .add_all(dependencies, &db, |conflict| {
+                resolve_conflict(conflict, data.yes)
+            })
             .await?;
+    }
+    if !regular_git.is_empty() {
         project
-            .add_git(lua_dependency::LuaDependencyType::Build(git_dependencies))
+            .add_git(LuaDependencyType::Regular(regular_git))
             .await?;
-        sync_build_dependencies_if_locked(&project, progress.clone(), &config).await?;
+    }
+    if !build_git.is_empty() {
+        project.add_git(LuaDependencyType::Build(build_git)).await?;
+    }
+    if !test_git.is_empty() {
+        project.add_git(LuaDependencyType::Test(test_git)).await?;
     }
 
-    let test_packages = data.test.unwrap_or_default();
-    if !test_packages.is_empty() {
-        let (dependencies, git_dependencies): (Vec<_>, Vec<_>) =
-            test_packages.iter().partition_map(|req| match req {
-                PackageReqOrGitShorthand::PackageReq(req) => Either::Left(req.clone()),
-                PackageReqOrGitShorthand::GitShorthand(url) => Either::Right(url.clone()),
-            });
-        project
-            .add(lua_dependency::DependencyType::Test(dependencies), &db)
-            .await?;
-        project
-            .add_git(lua_dependency::LuaDependencyType::Test(git_dependencies))
+    if data.sorted {
+        project.sort_dependencies().await?;
+    }
+
+    let progress = MultiProgress::new_arc();
+    let mut summary = Summary::default();
+    if sync_regular {
+        sync_dependencies_if_locked(&project, progress.clone(), &config, &mut summary).await?;
+    }
+    if sync_build {
+        sync_build_dependencies_if_locked(&project, progress.clone(), &config, &mut summary)
             .await?;
-        sync_test_dependencies_if_locked(&project, progress.clone(), &config).await?;
     }
+    if sync_test {
+        sync_test_dependencies_if_locked(&project, progress.clone(), &config, &mut summary).await?;
+    }
+    summary.print();
 
     Ok(())
 }
 
+fn partition_packages(
+    packages: Vec<PackageReqOrGitShorthand>,
+) -> (Vec<PackageReq>, Vec<GitUrlShorthand>) {
+    packages.into_iter().partition_map(|req| match req {
+        PackageReqOrGitShorthand::PackageReq(req) => Either::Left(req),
+        PackageReqOrGitShorthand::GitShorthand(url) => Either::Right(url),
+    })
+}
+
+/// Warns about packages with a name similar to that of a much more popular package,
+/// and refuses to add them without `--yes`.
+fn check_typosquatting(db: &RemotePackageDB, packages: &[PackageName], yes: bool) -> Result<()> {
+    let suspicious = packages
+        .iter()
+        .filter_map(|package| {
+            let similar = db.similar_popular_packages(package);
+            (!similar.is_empty()).then_some((package, similar))
+        })
+        .collect_vec();
+    for (package, similar) in &suspicious {
+        eprintln!(
+            "⚠️ WARNING: '{}' has a name similar to the much more popular {}.
+Make sure it is the package you meant to add.",
+            package,
+            similar.iter().map(|name| format!("'{name}'")).join(", ")
+        );
+    }
+    if !suspicious.is_empty() && !yes {
+        return Err(eyre!(
+            "not adding {} without `--yes`",
+            suspicious
+                .iter()
+                .map(|(package, _)| format!("'{package}'"))
+                .join(", ")
+        ));
+    }
+    Ok(())
+}
+
+fn resolve_conflict(conflict: &DependencyConflict, yes: bool) -> DependencyConflictResolution {
+    let widened = conflict.widened();
+    if yes {
+        return if widened.is_some() {
+            DependencyConflictResolution::Widen
+        } else {
+            DependencyConflictResolution::Replace
+        };
+    }
+    let mut options = Vec::new();
+    if let Some(widened) = widened {
+        options.push((
+            format!("Widen to `{widened}`"),
+            DependencyConflictResolution::Widen,
+        ));
+    }
+    options.push((
+        format!("Replace with `{}`", conflict.requested()),
+        DependencyConflictResolution::Replace,
+    ));
+    options.push(("Abort".into(), DependencyConflictResolution::Abort));
+    let (labels, resolutions): (Vec<_>, Vec<_>) = options.into_iter().unzip();
+    Select::new(&format!("{conflict}. What should be done?"), labels)
+        .without_filtering()
+        .raw_prompt()
+        .map(|answer| resolutions[answer.index])
+        // Cancelling the prompt, or not being able to prompt, aborts.
+        .unwrap_or(DependencyConflictResolution::Abort)
+}
+
 #[cfg(test)]
 mod test {
     use assert_fs::{prelude::PathCopy, TempDir};
@@ -125,6 +271,10 @@ mod test {
             force: false,
             build: Option::None,
             test: Option::None,
+            pin: false,
+            optional: false,
+            sorted: false,
+            yes: false,
         };
         add(args, config.clone()).await.unwrap();
         let lockfile_path = project_root.join("lux.lock");
@@ -138,6 +288,10 @@ mod test {
             force: false,
             build: Option::None,
             test: Option::None,
+            pin: false,
+            optional: false,
+            sorted: false,
+            yes: false,
         };
         add(args, config.clone()).await.unwrap();
         let lockfile_path = project_root.join("lux.lock");
@@ -168,6 +322,10 @@ mod test {
             force: false,
             build: Option::Some(vec!["penlight@1.5".parse().unwrap()]),
             test: Option::None,
+            pin: false,
+            optional: false,
+            sorted: false,
+            yes: false,
         };
         add(args, config.clone()).await.unwrap();
         let lockfile_path = project_root.join("lux.lock");
@@ -181,6 +339,10 @@ mod test {
             force: false,
             build: Option::Some(vec!["md5".parse().unwrap()]),
             test: Option::None,
+            pin: false,
+            optional: false,
+            sorted: false,
+            yes: false,
         };
         add(args, config.clone()).await.unwrap();
         let lockfile_path = project_root.join("lux.lock");
@@ -211,6 +373,10 @@ mod test {
             force: false,
             build: Option::None,
             test: Option::Some(vec!["penlight@1.5".parse().unwrap()]),
+            pin: false,
+            optional: false,
+            sorted: false,
+            yes: false,
         };
         add(args, config.clone()).await.unwrap();
         let lockfile_path = project_root.join("lux.lock");
@@ -224,6 +390,10 @@ mod test {
             force: false,
             build: Option::None,
             test: Option::Some(vec!["md5".parse().unwrap()]),
+            pin: false,
+            optional: false,
+            sorted: false,
+            yes: false,
         };
         add(args, config.clone()).await.unwrap();
         let lockfile_path = project_root.join("lux.lock");
@@ -1,57 +1,48 @@
-use std::time::Duration;
+use std::process::ExitCode;
 
 use clap::Parser;
 use eyre::Result;
 use lux_cli::{
-    add, build, completion, config,
+    add, binaries, build, bundle, check, completion, config,
     debug::Debug,
-    doc, download, exec, fetch, format, generate_rockspec, info, install, install_lua,
-    install_rockspec, lint, list, outdated, pack, path, pin, project, purge, remove, run, run_lua,
-    search, shell, test, uninstall, unpack, update,
+    diagnostic, direnv, doc, download, exec, explain, external_deps, fetch, format, gen_man,
+    generate, generate_rockspec, hash, index, info, install, install_lua, install_rockspec, lint,
+    list, lock, nix_prefetch, nvim, outdated, pack, path, pin, project, purge, remove, run,
+    run_lua, search, shell, test, tree, uninstall, unpack, update,
     upload::{self},
-    which, Cli, Commands,
+    verify, watch, which, Cli, Commands,
 };
 use lux_lib::{
-    config::{tree::RockLayoutConfig, ConfigBuilder},
+    config::Config,
     lockfile::PinnedState::{Pinned, Unpinned},
+    progress::ProgressMode,
 };
 
 #[tokio::main(flavor = "multi_thread")]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
-
-    let mut config_builder = ConfigBuilder::new()
-        .unwrap()
-        .dev(Some(cli.dev))
-        .extra_servers(cli.extra_servers)
-        .generate_luarc(Some(!cli.no_luarc))
-        .lua_dir(cli.lua_dir)
-        .lua_version(cli.lua_version)
-        .namespace(cli.namespace)
-        .no_project(Some(cli.no_project))
-        .only_sources(cli.only_sources)
-        .server(cli.server)
-        .timeout(
-            cli.timeout
-                .map(|duration| Duration::from_secs(duration as u64)),
-        )
-        .user_tree(cli.tree)
-        .variables(
-            cli.variables
-                .map(|variables| variables.into_iter().collect()),
-        )
-        .verbose(Some(cli.verbose));
-
-    if cli.nvim {
-        config_builder = config_builder.entrypoint_layout(RockLayoutConfig::new_nvim_layout());
+    let error_format = cli.error_format;
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => diagnostic::report(&err, error_format),
     }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    diagnostic::install_error_hook()?;
+    ProgressMode::detect(cli.quiet).init();
 
-    let config = config_builder.build()?;
+    let config = match &cli.config {
+        Some(config_file) => Config::from_file(config_file, cli.config_overrides())?,
+        None => Config::from_overrides(cli.config_overrides())?,
+    };
 
     if config.verbose() {
         std::env::set_var("CC_ENABLE_DEBUG_OUTPUT", "1");
     }
 
+    project::check_unknown_fields(&config)?;
+
     match cli.command {
         Commands::Completion(completion_args) => completion::completion(completion_args).await?,
         Commands::Search(search_data) => search::search(search_data, config).await?,
@@ -61,41 +52,65 @@ async fn main() -> Result<()> {
             Debug::Unpack(unpack_data) => unpack::unpack(unpack_data).await?,
             Debug::UnpackRemote(unpack_data) => unpack::unpack_remote(unpack_data, config).await?,
             Debug::Project(debug_project) => project::debug_project(debug_project)?,
+            Debug::Schema => project::debug_schema()?,
+            Debug::GenMan(gen_man_args) => gen_man::gen_man(gen_man_args)?,
+            Debug::Lua => install_lua::debug_lua(config)?,
+            Debug::ExternalDeps(external_deps_args) => {
+                external_deps::debug_external_deps(external_deps_args, config).await?
+            }
+            Debug::NixPrefetch(nix_prefetch_args) => {
+                nix_prefetch::nix_prefetch(nix_prefetch_args, config).await?
+            }
         },
         Commands::New(project_data) => project::write_project_rockspec(project_data).await?,
-        Commands::Build(build_data) => {
-            build::build(build_data, config).await?;
-        }
-        Commands::List(list_data) => list::list_installed(list_data, config)?,
+        Commands::Init(init_args) => project::init_project(init_args).await?,
+        Commands::Bin(bin_cmd) => binaries::bin(bin_cmd, config)?,
+        Commands::Build(build_data) => build::build_command(build_data, config).await?,
+        Commands::Bundle(bundle_args) => bundle::bundle(bundle_args, config).await?,
+        Commands::Check(check_args) => check::check(check_args, config).await?,
+        Commands::List(list_data) => list::list_installed(list_data, config).await?,
         Commands::Lua(run_lua) => run_lua::run_lua(run_lua, config).await?,
         Commands::Install(install_data) => install::install(install_data, config).await?,
         Commands::InstallRockspec(install_data) => {
             install_rockspec::install_rockspec(install_data, config).await?
         }
         Commands::Outdated(outdated) => outdated::outdated(outdated, config).await?,
-        Commands::InstallLua => install_lua::install_lua(config).await?,
-        Commands::Fmt(fmt_args) => format::format(fmt_args)?,
-        Commands::Purge => purge::purge(config).await?,
+        Commands::InstallLua(install_lua_args) => {
+            install_lua::install_lua(install_lua_args, config).await?
+        }
+        Commands::Fmt(fmt_args) => format::format(fmt_args, config).await?,
+        Commands::Purge(purge_args) => purge::purge(purge_args, config).await?,
         Commands::Remove(remove_args) => remove::remove(remove_args, config).await?,
         Commands::Exec(run_args) => exec::exec(run_args, config).await?,
+        Commands::Explain(explain_args) => explain::explain(explain_args)?,
         Commands::Test(test) => test::test(test, config).await?,
+        Commands::Tree(tree_cmd) => tree::tree(tree_cmd, config)?,
         Commands::Update(update_args) => update::update(update_args, config).await?,
+        Commands::Index(index_data) => index::index(index_data).await?,
         Commands::Info(info_data) => info::info(info_data, config).await?,
         Commands::Lint(lint_args) => lint::lint(lint_args, config).await?,
+        Commands::Lock(lock_cmd) => lock::lock(lock_cmd, config)?,
         Commands::Path(path_data) => path::path(path_data, config).await?,
         Commands::Pin(pin_data) => pin::set_pinned_state(pin_data, config, Pinned).await?,
         Commands::Unpin(pin_data) => pin::set_pinned_state(pin_data, config, Unpinned).await?,
+        Commands::Unpack(unpack_data) => unpack::unpack(unpack_data).await?,
         Commands::Upload(upload_data) => upload::upload(upload_data, config).await?,
         Commands::Add(add_data) => add::add(add_data, config).await?,
         Commands::Config(config_cmd) => config::config(config_cmd, config)?,
+        Commands::Nvim(nvim_cmd) => nvim::nvim(nvim_cmd, config)?,
+        Commands::Direnv(direnv_cmd) => direnv::direnv(direnv_cmd, config)?,
         Commands::Doc(doc_args) => doc::doc(doc_args, config).await?,
         Commands::Pack(pack_args) => pack::pack(pack_args, config).await?,
         Commands::Uninstall(uninstall_data) => {
             uninstall::uninstall(uninstall_data, config).await.unwrap()
         }
+        Commands::Verify(verify_args) => verify::verify(verify_args, config).await?,
+        Commands::Watch(watch_cmd) => watch::watch(watch_cmd, config).await?,
         Commands::Which(which_args) => which::which(which_args, config)?,
         Commands::Run(run_args) => run::run(run_args, config).await?,
+        Commands::Generate(cmd) => generate::generate(cmd, config)?,
         Commands::GenerateRockspec(data) => generate_rockspec::generate_rockspec(data)?,
+        Commands::Hash(hash_args) => hash::hash(hash_args, config).await?,
         Commands::Shell(data) => shell::shell(data, config).await?,
     }
     Ok(())
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use eyre::{eyre, Result};
+use lux_lib::{config::Config, operations::Binaries, project::Project};
+
+#[derive(Subcommand)]
+pub enum BinCmd {
+    /// List the executables installed in the project and user trees,{n}
+    /// with their packages and whether they are wrapped Lua scripts.
+    List(ListBinaries),
+    /// Write a shim for each installed executable to a directory,{n}
+    /// which can be added to the PATH, e.g. with direnv's `PATH_add`.{n}
+    /// Executables in the project tree shadow those in the user tree.
+    Shims(Shims),
+}
+
+#[derive(Args)]
+pub struct ListBinaries {
+    /// Print the executables as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct Shims {
+    /// The directory to write the shims to.{n}
+    /// Defaults to `.lux/shims` in the project root, if in a project.
+    dir: Option<PathBuf>,
+}
+
+pub fn bin(cmd: BinCmd, config: Config) -> Result<()> {
+    let project = Project::current()?;
+    let binaries = Binaries::new(&config).maybe_project(project.as_ref());
+    match cmd {
+        BinCmd::List(args) => {
+            let binaries = binaries.list()?;
+            if args.json {
+                println!("{}", serde_json::to_string(&binaries)?);
+            } else {
+                for binary in binaries {
+                    println!(
+                        "{} ({}@{}, {}{})",
+                        binary.path.display(),
+                        binary.package,
+                        binary.version,
+                        binary.location,
+                        if binary.is_wrapped() { ", wrapped" } else { "" }
+                    );
+                }
+            }
+        }
+        BinCmd::Shims(args) => {
+            let dir = match (args.dir, &project) {
+                (Some(dir), _) => dir,
+                (None, Some(project)) => project.root().join(".lux").join("shims"),
+                (None, None) => {
+                    return Err(eyre!(
+                        "Not in a project. Please specify the directory to write the shims to."
+                    ))
+                }
+            };
+            let shims = binaries.write_shims(&dir)?;
+            println!("Wrote {} shims to {}", shims.len(), dir.display());
+        }
+    }
+    Ok(())
+}
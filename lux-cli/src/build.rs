@@ -1,12 +1,32 @@
+use std::path::PathBuf;
+
 use clap::Args;
-use eyre::Result;
+use eyre::{eyre, Result};
+use inquire::Confirm;
+use itertools::Itertools;
 use lux_lib::{
-    config::Config,
-    lockfile::LocalPackage,
-    operations::{self},
+    build::{
+        compile_commands::CompileCommands, sanitizer::Sanitizer,
+        system_package::SystemPackageManager,
+    },
+    config::{
+        profile::{DEV_PROFILE, RELEASE_PROFILE},
+        Config, LuaVersion,
+    },
+    lockfile::{signature::verify_lockfile_signature, LocalPackage, PinnedState},
+    operations::{self, BuildCheckIssue, GitPackageUrl},
+    progress::MultiProgress,
     project::Project,
+    timings::Timings,
 };
 
+use crate::{diagnostic::CheckFailed, install::fetch_git_packages};
+
+const TIMINGS_FILE: &str = "timings.json";
+const COMPILE_COMMANDS_FILE: &str = "compile_commands.json";
+const DEPENDENCY_SOURCES_DIR: &str = "sources";
+const LOCKFILE_NAME: &str = "lux.lock";
+
 #[derive(Args, Default)]
 pub struct Build {
     /// Ignore the project's lockfile and don't create one.
@@ -16,15 +36,302 @@ pub struct Build {
     /// Build only the dependencies
     #[arg(long)]
     only_deps: bool,
+
+    /// Offer to install missing external dependencies
+    /// using the system package manager.
+    #[arg(long)]
+    install_system_deps: bool,
+
+    /// Record the time spent in each build phase,{n}
+    /// write a report to `.lux/timings.json`{n}
+    /// and print a summary of the slowest packages{n}
+    /// and of the compiler cache hits, if a `compiler_cache` is configured.
+    #[arg(long)]
+    timings: bool,
+
+    /// Rebuild all project sources, even if they haven't changed
+    /// since the last build.
+    #[arg(long)]
+    force: bool,
+
+    /// Symlink the project's Lua sources into the project tree{n}
+    /// instead of copying them, so that edits are picked up without rebuilding.{n}
+    /// C modules are still compiled.
+    #[arg(long)]
+    dev_link: bool,
+
+    /// The compiler toolchain preset to build C modules with,{n}
+    /// e.g. `clang` or `gcc`, or a preset from the `[toolchains]` in the config.{n}
+    /// Takes precedence over the project's `[build.toolchain]`.
+    #[arg(long)]
+    toolchain: Option<String>,
+
+    /// The build profile to build C modules with,{n}
+    /// e.g. `dev` (`-O0 -g`) or `release` (`-O2`),{n}
+    /// or a profile from the project's `[profile.<name>]` sections.{n}
+    /// Each profile is built to a separate project tree.
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Build with the `release` profile.{n}
+    /// Shorthand for `--profile release`.
+    #[arg(long, conflicts_with = "profile")]
+    release: bool,
+
+    /// Instrument the C modules built by the builtin backend with sanitizers,{n}
+    /// e.g. `--sanitize address,undefined`, to debug crashes in native modules.{n}
+    /// `lx test` sets up the sanitizers' runtime environment for instrumented modules.{n}
+    /// Implies `--force`. Dependencies that are already installed are not rebuilt.
+    #[arg(long, value_delimiter = ',')]
+    sanitize: Vec<Sanitizer>,
+
+    /// Write a `compile_commands.json` for the project's C sources{n}
+    /// to the project root, for clangd-based editing.{n}
+    /// Existing entries for other source files are kept.
+    #[arg(long)]
+    compile_commands: bool,
+
+    /// Also add the C sources of dependencies that are built from source{n}
+    /// to the `compile_commands.json`.{n}
+    /// Their sources are kept in `.lux/sources`.
+    #[arg(long, requires = "compile_commands")]
+    compile_commands_deps: bool,
+
+    /// Build and install the project and its dependencies into a tree{n}
+    /// at this directory instead of the project's `.lux` directory,{n}
+    /// e.g. a staging directory for packaging.{n}
+    /// Unless `--no-lock` is set, the project's lockfile is copied to the directory.
+    #[arg(long, value_name = "DIR")]
+    out_tree: Option<PathBuf>,
+}
+
+impl Build {
+    /// Apply the selected toolchain preset, the project's `debug_symbols`,
+    /// the sanitizers and the selected build profile to the config.
+    pub(crate) fn config(&self, project: &Project, config: Config) -> Result<Config> {
+        let config = match &self.toolchain {
+            Some(toolchain) => config.with_toolchain(toolchain.clone())?,
+            None => config,
+        };
+        let config = match project.toml().debug_symbols() {
+            Some(debug_symbols) => config.with_debug_symbols(debug_symbols),
+            None => config,
+        };
+        let config = if self.sanitize.is_empty() {
+            config
+        } else {
+            config.with_sanitizers(self.sanitize.clone())
+        };
+        let config = match &self.out_tree {
+            // Resolve relative paths now, as build steps may run in other directories.
+            Some(out_tree) => config.with_project_tree_root(std::path::absolute(out_tree)?),
+            None => config,
+        };
+        let profile = if self.release {
+            Some(RELEASE_PROFILE)
+        } else {
+            self.profile.as_deref()
+        };
+        match profile {
+            Some(name) => {
+                let profile = project.toml().profile(name).ok_or_else(|| {
+                    eyre!(
+                        "unknown build profile '{name}'. Built-in profiles: '{DEV_PROFILE}', '{RELEASE_PROFILE}'."
+                    )
+                })?;
+                Ok(config.with_profile(name.to_string(), &profile))
+            }
+            None => Ok(config),
+        }
+    }
+}
+
+/// The arguments of `lx build`.
+#[derive(Args)]
+pub struct BuildCommand {
+    #[clap(flatten)]
+    build: Build,
+
+    /// Don't build anything. Instead, resolve the dependencies that would be built,{n}
+    /// fetch their rockspecs and validate the build specs of the dependencies{n}
+    /// and the project: the build backends' programs, external dependencies{n}
+    /// and variable substitutions.{n}
+    /// Reports what would be built and any missing prerequisites.
+    #[arg(long, conflicts_with_all = ["only_deps", "install_system_deps", "timings", "force", "dev_link", "compile_commands"])]
+    check: bool,
+
+    /// Instead of building the current project, build a package{n}
+    /// from a git repository containing a lux.toml or a rockspec{n}
+    /// and install it into the user tree, optionally at a revision,{n}
+    /// e.g. "git+https://github.com/owner/repo#v1.0.0".{n}
+    /// The commit it is built from is recorded in the lockfile.
+    #[arg(value_name = "git-url", conflicts_with_all = ["check", "only_deps", "install_system_deps", "dev_link", "compile_commands"])]
+    git: Option<GitPackageUrl>,
+}
+
+pub async fn build_command(data: BuildCommand, config: Config) -> Result<()> {
+    if let Some(url) = data.git {
+        build_git_package(url, config).await
+    } else if data.check {
+        let project = Project::current_or_err()?;
+        let config = data.build.config(&project, config)?;
+        check_build(&project, &config).await
+    } else {
+        build(data.build, config).await?;
+        Ok(())
+    }
+}
+
+async fn build_git_package(url: GitPackageUrl, config: Config) -> Result<()> {
+    let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
+    if config.require_signed_lockfile() {
+        verify_lockfile_signature(&tree.lockfile_path(), &config)?;
+    }
+    let progress = MultiProgress::new_arc();
+    let packages = fetch_git_packages(vec![url], PinnedState::Unpinned, &progress)?;
+    operations::Install::new(&config)
+        .packages(packages)
+        .tree(tree)
+        .progress(progress)
+        .install()
+        .await?;
+    Ok(())
 }
 
 /// Returns `Some` if the `only_deps` arg is set to `false`.
 pub async fn build(data: Build, config: Config) -> Result<Option<LocalPackage>> {
     let project = Project::current_or_err()?;
+    let config = data.config(&project, config)?;
+    if data.install_system_deps {
+        install_system_deps(&project, &config).await?;
+    }
+    let timings = data.timings.then(Timings::new);
+    let compiler_cache_stats = match (&timings, config.compiler_cache()) {
+        (Some(_), Some(compiler_cache)) => compiler_cache.stats().await,
+        _ => None,
+    };
+    let compile_commands = data.compile_commands.then(|| {
+        let compile_commands = CompileCommands::new();
+        if data.compile_commands_deps {
+            compile_commands
+                .with_dependency_sources(project.root().join(".lux").join(DEPENDENCY_SOURCES_DIR))
+        } else {
+            compile_commands
+        }
+    });
     let result = operations::BuildProject::new(&project, &config)
         .no_lock(data.no_lock)
         .only_deps(data.only_deps)
+        .maybe_timings(timings.clone())
+        .maybe_compile_commands(compile_commands.clone())
+        .force(data.force || !data.sanitize.is_empty())
+        .dev_link(data.dev_link)
         .build()
         .await?;
+    if let Some(compile_commands) = compile_commands {
+        let path = project.root().join(COMPILE_COMMANDS_FILE);
+        compile_commands.write(&path)?;
+        println!("Wrote compile commands to {}", path.display());
+    }
+    if let Some(timings) = timings {
+        if let Some(before) = compiler_cache_stats {
+            if let Some(after) = before.cache.stats().await {
+                timings.set_compiler_cache_stats(after.since(&before));
+            }
+        }
+        let report = timings.report();
+        let report_path = project.root().join(".lux").join(TIMINGS_FILE);
+        report.write(&report_path)?;
+        print!("{report}");
+        println!("Wrote timings report to {}", report_path.display());
+    }
+    if let Some(out_tree) = config.project_tree_root() {
+        let lockfile_path = project.lockfile_path();
+        if !data.no_lock && lockfile_path.is_file() {
+            let out_lockfile_path = out_tree.join(LOCKFILE_NAME);
+            std::fs::copy(&lockfile_path, &out_lockfile_path)?;
+            println!("Wrote lockfile to {}", out_lockfile_path.display());
+        }
+    }
     Ok(result)
 }
+
+async fn check_build(project: &Project, config: &Config) -> Result<()> {
+    let report = operations::CheckBuild::new(project, config).check().await?;
+    if let Some(lua_version) = &report.install_lua {
+        println!("Would install Lua {lua_version}");
+    }
+    println!("Would build:");
+    for planned in &report.planned {
+        println!("  {planned}");
+    }
+    if report.issues.is_empty() {
+        return Ok(());
+    }
+    for issue in &report.issues {
+        println!("{issue}");
+    }
+    Err(CheckFailed(format!("{} build problem(s) found", report.issues.len())).into())
+}
+
+async fn install_system_deps(project: &Project, config: &Config) -> Result<()> {
+    // Probes the external dependencies of the project and of the dependencies it would build.
+    let report = operations::CheckBuild::new(project, config).check().await?;
+    let hints = report
+        .issues
+        .into_iter()
+        .filter_map(|issue| match issue {
+            BuildCheckIssue::MissingExternalDependency {
+                hint: Some(hint), ..
+            } => Some(hint),
+            BuildCheckIssue::MissingExternalDependency {
+                package,
+                error,
+                hint: None,
+            } => {
+                eprintln!(
+                    "⚠️ WARNING: no known system package provides a missing external dependency of {package}: {error}"
+                );
+                None
+            }
+            _ => None,
+        })
+        .unique_by(|hint| hint.dependency().to_string())
+        .collect_vec();
+    if hints.is_empty() {
+        return Ok(());
+    }
+    let manager = SystemPackageManager::detect()
+        .ok_or_else(|| eyre!("could not detect a supported system package manager"))?;
+    // The build reports the dependencies we can't install with its usual lookup error.
+    let packages = hints
+        .iter()
+        .filter_map(|hint| {
+            let package = hint.package(&manager).cloned();
+            if package.is_none() {
+                eprintln!(
+                    "⚠️ WARNING: no known {} package provides the external dependency {}",
+                    manager,
+                    hint.dependency()
+                );
+            }
+            package
+        })
+        .collect_vec();
+    if packages.is_empty() {
+        return Ok(());
+    }
+    let command = manager.install_command(&packages).join(" ");
+    if Confirm::new(&format!(
+        "Install missing system packages with `{command}`?"
+    ))
+    .with_default(false)
+    .prompt()?
+    {
+        let status = manager.install(&packages)?;
+        if !status.success() {
+            return Err(eyre!("`{command}` exited with {status}"));
+        }
+    }
+    Ok(())
+}
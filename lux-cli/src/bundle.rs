@@ -0,0 +1,56 @@
+use clap::{Args, ValueEnum};
+use eyre::Result;
+use lux_lib::{
+    config::Config,
+    operations::{self, BundleFormat},
+    project::Project,
+};
+
+use crate::build;
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum BundleFormatArg {
+    /// A single Lua file, which registers the modules in `package.preload`.
+    #[default]
+    Lua,
+    /// A zip archive with the Lua modules and an `init.lua` loader,{n}
+    /// which adds them to the `package.path`.
+    Zip,
+}
+
+impl From<BundleFormatArg> for BundleFormat {
+    fn from(value: BundleFormatArg) -> Self {
+        match value {
+            BundleFormatArg::Lua => Self::Lua,
+            BundleFormatArg::Zip => Self::Zip,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct Bundle {
+    /// The format to bundle to.
+    #[arg(long, value_enum, default_value_t)]
+    format: BundleFormatArg,
+
+    /// The module to `require` when the bundle is loaded.{n}
+    /// If set, loading the bundle returns the module.{n}
+    /// Otherwise, it only makes the bundled modules available to `require`.
+    #[arg(long)]
+    entrypoint: Option<String>,
+}
+
+/// Build the current project and bundle it with its dependencies.
+pub async fn bundle(args: Bundle, config: Config) -> Result<()> {
+    let project = Project::current_or_err()?;
+    let package = build::build(build::Build::default(), config.clone())
+        .await?
+        .expect("exptected a `LocalPackage`");
+    let bundle_path = operations::Bundle::new(std::env::current_dir()?, &project, package)
+        .config(&config)
+        .format(args.format.into())
+        .maybe_entrypoint(args.entrypoint)
+        .bundle()?;
+    print!("bundle created at {}", bundle_path.display());
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use clap::Args;
+use eyre::Result;
+use lux_lib::{config::Config, operations::ProjectCheck, project::Project};
+
+use crate::diagnostic::CheckFailed;
+
+#[derive(Args)]
+pub struct Check {
+    /// Check the current project's dependencies:{n}
+    /// undeclared and unused dependencies, and packages{n}
+    /// declared in more than one dependency section.{n}
+    /// Allowlists can be configured in the lux.toml's `[check]` section.
+    #[arg(long, required = true)]
+    project: bool,
+}
+
+pub async fn check(_check_args: Check, config: Config) -> Result<()> {
+    let project = Project::current_or_err()?;
+
+    let issues = ProjectCheck::new(&project, &config).check()?;
+
+    if issues.is_empty() {
+        return Ok(());
+    }
+
+    for issue in &issues {
+        println!("{issue}");
+    }
+
+    Err(CheckFailed(format!("{} dependency issue(s) found", issues.len())).into())
+}
@@ -1,6 +1,8 @@
+use std::path::PathBuf;
+
 use eyre::{eyre, Result};
 use inquire::Confirm;
-use lux_lib::config::{Config, ConfigBuilder};
+use lux_lib::config::{luarocks::LuarocksConfig, Config, ConfigBuilder};
 
 #[derive(clap::Subcommand)]
 pub enum ConfigCmd {
@@ -11,6 +13,9 @@ pub enum ConfigCmd {
     /// Show the current config.
     /// This includes options picked up from CLI flags.
     Show,
+    /// Import the `rocks_servers`, `rocks_trees` and `variables`{n}
+    /// of a luarocks config file into the lux config file.
+    ImportLuarocks(ImportLuarocks),
 }
 
 #[derive(clap::Args)]
@@ -26,6 +31,17 @@ pub struct Init {
     current: bool,
 }
 
+#[derive(clap::Args)]
+pub struct ImportLuarocks {
+    /// The luarocks config file to import.{n}
+    /// Defaults to the first one found in `~/.luarocks`.
+    path: Option<PathBuf>,
+
+    /// Print the resulting config instead of writing it to the config file.
+    #[arg(long)]
+    print: bool,
+}
+
 pub fn config(cmd: ConfigCmd, config: Config) -> Result<()> {
     match cmd {
         ConfigCmd::Init(init) => {
@@ -67,6 +83,32 @@ to initialise a config file.
             let cfg: ConfigBuilder = config.into();
             print!("{}", toml::to_string(&cfg)?);
         }
+        ConfigCmd::ImportLuarocks(import) => {
+            let luarocks_config_file = match import.path {
+                Some(path) => path,
+                None => LuarocksConfig::default_config_files()
+                    .into_iter()
+                    .find(|path| path.is_file())
+                    .ok_or_else(|| eyre!("No luarocks config file found in ~/.luarocks."))?,
+            };
+            let luarocks = LuarocksConfig::from_file(&luarocks_config_file)?;
+            let cfg = ConfigBuilder::new()?.with_luarocks_config(luarocks);
+            // Make sure the resulting config is valid before writing it
+            cfg.clone().build()?;
+            let content = toml::to_string(&cfg)?;
+            if import.print {
+                print!("{content}");
+            } else {
+                let config_file = ConfigBuilder::config_file()?;
+                std::fs::create_dir_all(config_file.parent().unwrap())?;
+                std::fs::write(&config_file, content)?;
+                println!(
+                    "Imported {} into {}",
+                    luarocks_config_file.display(),
+                    config_file.display()
+                );
+            }
+        }
     }
     Ok(())
 }
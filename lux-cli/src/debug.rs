@@ -1,4 +1,7 @@
 use crate::{
+    external_deps::DebugExternalDeps,
+    gen_man::GenMan,
+    nix_prefetch::NixPrefetch,
     project::DebugProject,
     unpack::{Unpack, UnpackRemote},
 };
@@ -14,4 +17,24 @@ pub enum Debug {
     UnpackRemote(UnpackRemote),
     /// View information about the current project.
     Project(DebugProject),
+    /// Print the JSON schema of the lux.toml format,{n}
+    /// which editors can use for completion and validation.{n}
+    /// Example: `lx debug schema > lux.schema.json`, then add{n}
+    /// `#:schema ./lux.schema.json` to the top of the lux.toml{n}
+    /// for taplo / Even Better TOML to pick it up.
+    Schema,
+    /// View information about the Lua installation that C rocks are built against,{n}
+    /// including the Lua headers that are used.
+    Lua,
+    /// Probe the external dependencies of the current project or a package{n}
+    /// and print where their headers and libraries were found,{n}
+    /// which fallbacks were used and the resulting compiler and linker flags.
+    ExternalDeps(DebugExternalDeps),
+    /// Generate a man page for lx and each of its subcommands.{n}
+    /// Example: `lx debug gen-man ./man` followed by `man ./man/lx-build.1`
+    GenMan(GenMan),
+    /// Fetch a package's source and print its hashes{n}
+    /// along with a Nix fetcher expression.{n}
+    /// Example: `lx debug nix-prefetch say@1.4.1-3`
+    NixPrefetch(NixPrefetch),
 }
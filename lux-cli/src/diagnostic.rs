@@ -0,0 +1,173 @@
+use std::{error::Error, fmt, process::ExitCode};
+
+use clap::ValueEnum;
+use eyre::EyreHandler;
+use lux_lib::diagnostic::{error_class, error_code, ErrorClass};
+use serde_json::json;
+
+/// The format in which to print the error that `lx` fails with.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum ErrorFormat {
+    /// A human readable message, with the error's causes and a hint.
+    #[default]
+    Human,
+    /// A JSON object with the error's `class`, `exit_code`, `code`,{n}
+    /// `message`, `causes` and `hint`.
+    Json,
+}
+
+/// A check that a command performed failed, e.g. `lx verify` found metadata issues.
+#[derive(Debug)]
+pub struct CheckFailed(pub String);
+
+impl fmt::Display for CheckFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for CheckFailed {}
+
+/// The exit code of `lx outdated --lockfile` if `lx update` would change the lockfile.
+pub const UPDATES_AVAILABLE: u8 = 9;
+
+/// The exit code that `lx` fails with for each class of failure.
+/// These are stable, so that scripts and CI systems can rely on them.
+/// Exit code 2 is reserved for invalid command line arguments,
+/// and [`UPDATES_AVAILABLE`] for `lx outdated --lockfile`.
+pub fn exit_code(class: ErrorClass) -> u8 {
+    match class {
+        ErrorClass::Other => 1,
+        ErrorClass::Resolution => 3,
+        ErrorClass::Build => 4,
+        ErrorClass::Network => 5,
+        ErrorClass::Verification => 6,
+        ErrorClass::Test => 7,
+        ErrorClass::Config => 8,
+    }
+}
+
+fn report_class(err: &eyre::Report) -> ErrorClass {
+    if err.chain().any(|err| err.is::<CheckFailed>()) {
+        ErrorClass::Verification
+    } else {
+        error_class(err.as_ref())
+    }
+}
+
+/// Print the error that `lx` fails with to stderr,
+/// and return the exit code for its class of failure.
+pub fn report(err: &eyre::Report, format: ErrorFormat) -> ExitCode {
+    let class = report_class(err);
+    match format {
+        ErrorFormat::Human => eprintln!("Error: {err:?}"),
+        ErrorFormat::Json => {
+            let code = error_code(err.as_ref());
+            eprintln!(
+                "{}",
+                json!({
+                    "class": class.to_string(),
+                    "exit_code": exit_code(class),
+                    "code": code.map(|code| code.to_string()),
+                    "message": err.to_string(),
+                    "causes": err.chain().skip(1).map(|cause| cause.to_string()).collect::<Vec<_>>(),
+                    "hint": code.map(|code| code.hint()),
+                })
+            );
+        }
+    }
+    ExitCode::from(exit_code(class))
+}
+
+/// Renders errors with their causes and, for errors with a stable error code,
+/// a hint and a pointer to `lx explain`.
+struct DiagnosticHandler;
+
+impl EyreHandler for DiagnosticHandler {
+    fn debug(&self, error: &(dyn Error + 'static), f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return fmt::Debug::fmt(error, f);
+        }
+        match error_code(error) {
+            Some(code) => write!(f, "[{code}] {error}")?,
+            None => write!(f, "{error}")?,
+        }
+        let causes = std::iter::successors(error.source(), |&err| err.source())
+            // Many errors include their source in their message
+            .filter(|cause| !error.to_string().contains(&cause.to_string()))
+            .collect::<Vec<_>>();
+        if !causes.is_empty() {
+            write!(f, "\n\nCaused by:")?;
+            for (i, cause) in causes.iter().enumerate() {
+                write!(f, "\n    {i}: {cause}")?;
+            }
+        }
+        if let Some(code) = error_code(error) {
+            write!(f, "\n\nhint: {}", code.hint())?;
+            write!(
+                f,
+                "\nFor more information about this error, run `lx explain {code}`."
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Install the error renderer for the errors returned by `lx`.
+pub fn install_error_hook() -> eyre::Result<()> {
+    eyre::set_hook(Box::new(|_| Box::new(DiagnosticHandler)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use lux_lib::operations::RunError;
+
+    use super::*;
+
+    struct Rendered<'a>(&'a (dyn Error + 'static));
+
+    impl fmt::Debug for Rendered<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            DiagnosticHandler.debug(self.0, f)
+        }
+    }
+
+    #[test]
+    fn render_error_code() {
+        let rendered = format!("{:?}", Rendered(&RunError::NoRunField));
+        assert_eq!(
+            rendered,
+            "[LUX0009] No `run` field found in `lux.toml`\n\n\
+            hint: add a `[run]` section with the `args` to run to your lux.toml\n\
+            For more information about this error, run `lx explain LUX0009`."
+        );
+        let err = std::io::Error::other("oops");
+        assert_eq!(format!("{:?}", Rendered(&err)), "oops");
+    }
+
+    #[test]
+    fn classify_report() {
+        let err = eyre::Report::new(CheckFailed("1 metadata issue(s) found".into()));
+        assert_eq!(report_class(&err), ErrorClass::Verification);
+        assert_eq!(exit_code(report_class(&err)), 6);
+        let err = eyre::Report::new(RunError::NoRunField).wrap_err("failed to run project");
+        assert_eq!(report_class(&err), ErrorClass::Config);
+        assert_eq!(report_class(&eyre::eyre!("oops")), ErrorClass::Other);
+    }
+
+    #[test]
+    fn updates_available_is_not_a_failure_exit_code() {
+        for class in [
+            ErrorClass::Resolution,
+            ErrorClass::Build,
+            ErrorClass::Network,
+            ErrorClass::Verification,
+            ErrorClass::Test,
+            ErrorClass::Config,
+            ErrorClass::Other,
+        ] {
+            assert_ne!(exit_code(class), UPDATES_AVAILABLE);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use clap::{Args, Subcommand};
+use eyre::Result;
+use lux_lib::{config::Config, path::Paths, project::Project};
+
+use crate::{
+    path::{mk_bin_path, mk_package_cpath, mk_package_path},
+    utils::project::current_project_or_user_tree,
+};
+
+/// A `use_lux` function for direnv's stdlib,
+/// which can be added to `~/.config/direnv/lib/lux.sh`.
+const USE_LUX: &str = r#"# Load the environment of the lux project in the current directory.
+# Usage: `use lux` in an `.envrc`, with optional arguments for `lx direnv export`.
+use_lux() {
+  eval "$(lx direnv export "$@")"
+}"#;
+
+#[derive(Subcommand)]
+pub enum DirenvCmd {
+    /// Print an `.envrc`-compatible block that exports the `PATH`,{n}
+    /// `LUA_PATH`, `LUA_CPATH` and `LUA_INIT` for the current project,{n}
+    /// and the variables from the config.{n}
+    /// In a project, direnv is told to watch the `lux.toml` and `lux.lock`,{n}
+    /// so that the environment is reloaded when the dependencies change.{n}
+    /// Use it in an `.envrc` with `eval "$(lx direnv export)"`.
+    Export(Export),
+    /// Print a `use_lux` function for direnv's stdlib,{n}
+    /// so that an `.envrc` can contain `use lux`.{n}
+    /// Add it with `lx direnv stdlib > ~/.config/direnv/lib/lux.sh`.
+    Stdlib,
+}
+
+#[derive(Args)]
+pub struct Export {
+    /// Do not add `require('lux').loader()` to `LUA_INIT`.
+    #[arg(long)]
+    no_loader: bool,
+
+    /// Do not export the variables from the config.
+    #[arg(long)]
+    no_variables: bool,
+}
+
+pub fn direnv(cmd: DirenvCmd, config: Config) -> Result<()> {
+    match cmd {
+        DirenvCmd::Export(args) => print!("{}", export(args, &config)?),
+        DirenvCmd::Stdlib => println!("{USE_LUX}"),
+    }
+    Ok(())
+}
+
+fn export(args: Export, config: &Config) -> Result<String> {
+    let tree = current_project_or_user_tree(config)?;
+    let paths = Paths::new(&tree)?;
+    let mut result = String::new();
+    if let Some(project) = Project::current()? {
+        result.push_str(&format!(
+            "watch_file {} {}\n",
+            quote(&project.toml_path()),
+            quote(&project.lockfile_path())
+        ));
+    }
+    let package_path = mk_package_path(&paths, true);
+    if !package_path.is_empty() {
+        result.push_str(&format_export("LUA_PATH", &package_path.to_string()));
+    }
+    let package_cpath = mk_package_cpath(&paths, true);
+    if !package_cpath.is_empty() {
+        result.push_str(&format_export("LUA_CPATH", &package_cpath.to_string()));
+    }
+    let path = mk_bin_path(&paths, true)?;
+    if !path.is_empty() {
+        result.push_str(&format_export("PATH", &path.to_string()));
+    }
+    if !args.no_loader {
+        if tree.version().lux_lib_dir().is_some() {
+            result.push_str(&format_export("LUA_INIT", &paths.init()));
+        } else {
+            eprintln!("⚠️ WARNING: lux-lua library not found. Cannot use the `lux.loader`.");
+        }
+    }
+    if !args.no_variables {
+        for (name, value) in config.custom_variables() {
+            result.push_str(&format_export(name, value));
+        }
+    }
+    Ok(result)
+}
+
+fn format_export(var_name: &str, value: &str) -> String {
+    format!("export {var_name}={}\n", quote_str(value))
+}
+
+fn quote(path: &Path) -> String {
+    quote_str(&path.to_string_lossy())
+}
+
+/// Quote a value for a POSIX shell, which is what direnv evaluates `.envrc` files in.
+fn quote_str(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_exports() {
+        assert_eq!(format_export("FOO", "bar baz"), "export FOO='bar baz'\n");
+        assert_eq!(
+            format_export("LUA_INIT", "require('lux').loader()"),
+            r"export LUA_INIT='require('\''lux'\'').loader()'".to_string() + "\n"
+        );
+    }
+}
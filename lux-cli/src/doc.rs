@@ -10,16 +10,30 @@ use lux_lib::{
     rockspec::Rockspec,
     tree::{RockMatches, Tree},
 };
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
 use url::Url;
 use walkdir::WalkDir;
+use which::which;
+
+use crate::utils::markdown::render_markdown;
+
+/// Name of the Vim help tags file, which is not a documentation file itself.
+const HELP_TAGS_FILE: &str = "tags";
 
 #[derive(Args)]
 pub struct Doc {
     package: PackageReq,
 
     /// Ignore local docs and open the package's homepage in a browser.
-    #[arg(long)]
+    #[arg(long, conflicts_with = "path")]
     online: bool,
+
+    /// Print the path to the package's doc directory instead of opening the docs.
+    #[arg(long)]
+    path: bool,
 }
 
 pub async fn doc(args: Doc, config: Config) -> Result<()> {
@@ -37,15 +51,19 @@ Please specify an exact package (<name>@<version>) or narrow the version require
         )),
         RockMatches::Single(package_id) => Ok(package_id),
     }?;
+    let tree = tree.tree_for(&package_id)?;
     let lockfile = tree.lockfile()?;
     let pkg = lockfile
         .get(&package_id)
         .expect("malformed lockfile")
         .clone();
-    if args.online {
-        open_homepage(pkg, &tree).await
+    if args.path {
+        println!("{}", tree.installed_rock_layout(&pkg)?.doc.display());
+        Ok(())
+    } else if args.online {
+        open_homepage(pkg, tree).await
     } else {
-        open_local_docs(pkg, &tree).await
+        open_local_docs(pkg, tree).await
     }
 }
 
@@ -74,7 +92,7 @@ async fn open_local_docs(pkg: LocalPackage, tree: &Tree) -> Result<()> {
         .into_iter()
         .filter_map_ok(|file| {
             let path = file.into_path();
-            if path.is_file() {
+            if path.is_file() && path.file_name().is_some_and(|name| name != HELP_TAGS_FILE) {
                 Some(
                     path.file_name()
                         .expect("no file name")
@@ -104,8 +122,7 @@ async fn open_local_docs(pkg: LocalPackage, tree: &Tree) -> Result<()> {
             }
         }
     } else if files.len() == 1 {
-        edit::edit_file(layout.doc.join(files.first().unwrap()))?;
-        Ok(())
+        open_doc_file(&layout.doc, files.first().unwrap()).await
     } else {
         let file = Select::new(
             "Multiple documentation files found. Please select one to open.",
@@ -113,7 +130,63 @@ async fn open_local_docs(pkg: LocalPackage, tree: &Tree) -> Result<()> {
         )
         .prompt()
         .expect("error selecting from multiple files");
-        edit::edit_file(layout.doc.join(file))?;
-        Ok(())
+        open_doc_file(&layout.doc, &file).await
+    }
+}
+
+/// Opens Vim help files (`*.txt` files with a `tags` file) in Neovim or Vim if available,
+/// so that help tags can be followed. Markdown files are rendered in a pager.
+/// Other files are opened in the default editor.
+async fn open_doc_file(doc_dir: &Path, file: &str) -> Result<()> {
+    let path = doc_dir.join(file);
+    if path
+        .extension()
+        .is_some_and(|ext| ext == "md" || ext == "markdown")
+    {
+        let content = std::fs::read_to_string(&path)?;
+        return page(&render_markdown(&content)?);
+    }
+    let is_help_file =
+        path.extension().is_some_and(|ext| ext == "txt") && doc_dir.join(HELP_TAGS_FILE).is_file();
+    match ["nvim", "vim"].into_iter().find_map(|vim| which(vim).ok()) {
+        Some(vim) if is_help_file => {
+            Command::new(vim)
+                .arg("-R")
+                .arg("-c")
+                .arg(format!(
+                    "set tags={}",
+                    doc_dir.join(HELP_TAGS_FILE).display()
+                ))
+                .arg("-c")
+                .arg("setlocal filetype=help")
+                .arg(&path)
+                .status()
+                .await?;
+        }
+        _ => edit::edit_file(path)?,
+    }
+    Ok(())
+}
+
+/// Display the content in the user's `PAGER` (or `less`),
+/// falling back to printing it if stdout is not a terminal or no pager is available.
+fn page(content: &str) -> Result<()> {
+    if std::io::stdout().is_terminal() {
+        let pager = std::env::var("PAGER").unwrap_or("less -R".into());
+        let mut pager_args = pager.split_whitespace();
+        if let Some(pager_cmd) = pager_args.next().and_then(|cmd| which(cmd).ok()) {
+            let mut child = std::process::Command::new(pager_cmd)
+                .args(pager_args)
+                .stdin(Stdio::piped())
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                // The user may quit the pager before all content has been written.
+                let _ = stdin.write_all(content.as_bytes());
+            }
+            child.wait()?;
+            return Ok(());
+        }
     }
+    print!("{content}");
+    Ok(())
 }
@@ -1,31 +1,109 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
 use clap::Args;
 use eyre::Result;
 use lux_lib::{
     config::Config,
+    hash::HasIntegrity,
     operations,
-    package::PackageReq,
-    progress::{MultiProgress, Progress},
+    package::{PackageName, PackageReq, PackageVersion},
+    progress::{MultiProgress, Progress, ProgressBar},
+    rockspec::Rockspec,
 };
 
 #[derive(Args)]
 pub struct Download {
     package_req: PackageReq,
+
+    /// The directory to download to.
+    /// Defaults to the current working directory.
+    #[arg(long, short)]
+    outdir: Option<PathBuf>,
+
+    /// Unpack the downloaded rock into `<outdir>/<name>-<version>`.
+    #[arg(long, conflicts_with = "rockspec_only")]
+    unpack: bool,
+
+    /// Only download the rockspec.
+    #[arg(long)]
+    rockspec_only: bool,
+
+    /// Print the SHA256 integrity of the downloaded file.
+    #[arg(long)]
+    checksum: bool,
+
+    /// Don't show progress or status messages.
+    #[arg(long, short)]
+    quiet: bool,
 }
 
 pub async fn download(dl_data: Download, config: Config) -> Result<()> {
     let progress = MultiProgress::new();
-    let bar = Progress::Progress(progress.new_bar());
+    let bar = if dl_data.quiet {
+        Progress::NoProgress
+    } else {
+        Progress::Progress(progress.new_bar())
+    };
 
-    let rock = operations::Download::new(&dl_data.package_req, &config, &bar)
-        .download_src_rock_to_file(None)
-        .await?;
+    let outdir = dl_data.outdir.unwrap_or_default();
+    if !outdir.as_os_str().is_empty() {
+        tokio::fs::create_dir_all(&outdir).await?;
+    }
 
-    bar.map(|b| {
-        b.finish_with_message(format!(
-            "Succesfully downloaded {}@{}",
-            rock.name, rock.version
-        ))
-    });
+    let downloaded = if dl_data.rockspec_only {
+        download_rockspec(&dl_data.package_req, &outdir, &config, &bar).await
+    } else {
+        download_src_rock(&dl_data.package_req, &outdir, dl_data.unpack, &config, &bar).await
+    };
+    let (name, version, path) = downloaded.inspect_err(|_| {
+        bar.map(|b| b.finish_and_clear());
+    })?;
+
+    bar.map(|b| b.finish_with_message(format!("Succesfully downloaded {name}@{version}")));
+
+    if dl_data.checksum {
+        println!("{}  {}", path.hash()?, path.display());
+    }
 
     Ok(())
 }
+
+async fn download_rockspec(
+    package_req: &PackageReq,
+    outdir: &Path,
+    config: &Config,
+    bar: &Progress<ProgressBar>,
+) -> Result<(PackageName, PackageVersion, PathBuf)> {
+    let rockspec = operations::Download::new(package_req, config, bar)
+        .download_rockspec()
+        .await?
+        .rockspec;
+    let name = rockspec.package().clone();
+    let version = rockspec.version().clone();
+    let path = outdir.join(format!("{name}-{version}.rockspec"));
+    tokio::fs::write(&path, rockspec.to_lua_remote_rockspec_string()?).await?;
+    Ok((name, version, path))
+}
+
+async fn download_src_rock(
+    package_req: &PackageReq,
+    outdir: &Path,
+    unpack: bool,
+    config: &Config,
+    bar: &Progress<ProgressBar>,
+) -> Result<(PackageName, PackageVersion, PathBuf)> {
+    let rock = operations::Download::new(package_req, config, bar)
+        .download_src_rock_to_file(Some(outdir.to_path_buf()))
+        .await?;
+    if rock.cached {
+        bar.map(|b| b.println(format!("{} already downloaded", rock.path.display())));
+    }
+    if unpack {
+        let destination = outdir.join(format!("{}-{}", rock.name, rock.version));
+        operations::unpack_src_rock(File::open(&rock.path)?, destination, bar).await?;
+    }
+    Ok((rock.name, rock.version, rock.path))
+}
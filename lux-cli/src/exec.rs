@@ -51,10 +51,14 @@ pub async fn exec(run: Exec, config: Config) -> Result<()> {
             None => install_command(&run.command, &config).await?,
         }
     };
-    operations::Exec::new(&run.command, project.as_ref(), &config)
+    let result = operations::Exec::new(&run.command, project.as_ref(), &config)
         .args(run.args.unwrap_or_default())
         .disable_loader(run.no_loader)
         .exec()
-        .await?;
+        .await;
+    if let Some(exit_code) = result.as_ref().err().and_then(|err| err.exit_code()) {
+        std::process::exit(exit_code);
+    }
+    result?;
     Ok(())
 }
@@ -0,0 +1,24 @@
+use clap::Args;
+use eyre::Result;
+use lux_lib::diagnostic::ErrorCode;
+
+use crate::utils::markdown::render_markdown;
+
+#[derive(Args)]
+pub struct Explain {
+    /// The error code to explain, e.g. `LUX0003`.{n}
+    /// If not set, lists all error codes.
+    code: Option<ErrorCode>,
+}
+
+pub fn explain(args: Explain) -> Result<()> {
+    match args.code {
+        Some(code) => print!("{}", render_markdown(code.explanation())?),
+        None => {
+            for code in ErrorCode::all() {
+                println!("{code}: {}", code.summary());
+            }
+        }
+    }
+    Ok(())
+}
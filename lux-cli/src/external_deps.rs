@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use clap::Args;
+use eyre::{OptionExt, Result};
+use itertools::Itertools;
+use lux_lib::{
+    build::external_dependency::ExternalDependencyInfo,
+    config::Config,
+    lua_rockspec::ExternalDependencySpec,
+    operations::Download,
+    package::PackageReq,
+    progress::{MultiProgress, Progress},
+    project::Project,
+    rockspec::Rockspec,
+};
+
+#[derive(Args)]
+pub struct DebugExternalDeps {
+    /// Probe the external dependencies of a package, e.g. `luasec@1.3.2`,{n}
+    /// instead of the ones declared in the current project.
+    package: Option<PackageReq>,
+}
+
+/// Probe the external dependencies of the current project or a package
+/// and print where they were found.
+pub async fn debug_external_deps(args: DebugExternalDeps, config: Config) -> Result<()> {
+    let external_dependencies: HashMap<String, ExternalDependencySpec> = match args.package {
+        Some(package) => {
+            let progress = MultiProgress::new();
+            let bar = Progress::Progress(progress.new_bar());
+            let rockspec = Download::new(&package, &config, &bar)
+                .download_rockspec()
+                .await?
+                .rockspec;
+            bar.map(|b| b.finish_and_clear());
+            rockspec.external_dependencies().current_platform().clone()
+        }
+        None => Project::current()?
+            .ok_or_eyre("not in a project! Specify a package to probe its external dependencies.")?
+            .local_rockspec()?
+            .external_dependencies()
+            .current_platform()
+            .clone(),
+    };
+    if external_dependencies.is_empty() {
+        println!("No external dependencies declared.");
+        return Ok(());
+    }
+    let probes = external_dependencies
+        .iter()
+        .sorted_by_key(|(name, _)| *name)
+        .map(|(name, dependency)| {
+            ExternalDependencyInfo::probe_with_details(name, dependency, config.external_deps())
+        })
+        .collect_vec();
+    println!("{}", probes.iter().join("\n\n"));
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Args, CommandFactory};
+use eyre::Result;
+
+use crate::Cli;
+
+#[derive(Args)]
+pub struct GenMan {
+    /// The directory to write the man pages to.
+    dir: PathBuf,
+}
+
+pub fn gen_man(args: GenMan) -> Result<()> {
+    generate_man_pages(&args.dir)?;
+    println!("Wrote man pages to {}", args.dir.display());
+    Ok(())
+}
+
+/// Generate a man page for `lx` and one for each (non-hidden) subcommand,
+/// e.g. `lx.1`, `lx-build.1` and `lx-debug-gen-man.1`.
+pub fn generate_man_pages(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    clap_mangen::generate_to(Cli::command().name("lx"), dir)
+}
@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use lux_lib::{config::Config, operations, project::Project};
+
+#[derive(clap::Subcommand)]
+pub enum GenerateCmd {
+    /// Generate a `lux-loader.lua`, which adds the project's dependencies{n}
+    /// to `package.path` and `package.cpath` at runtime.{n}
+    /// It can be required, or set via `LUA_INIT`, by programs that aren't run with `lx run`.{n}
+    /// Example: `LUA_INIT=@lux-loader.lua lua main.lua`
+    Loader(Loader),
+    /// Generate a `default.nix` that builds the project with its locked dependencies,{n}
+    /// fetching each dependency with the hashes from the project's lockfile.
+    Nix(Nix),
+    /// Generate a `.luacheckrc` and/or a selene config (`selene.toml`){n}
+    /// that declare the globals the project's Lua files may use,{n}
+    /// based on the supported Lua versions and the dependencies:{n}
+    /// busted's globals in test files, Neovim's `vim` global for Neovim plugins{n}
+    /// and OpenResty's globals for `lua-resty-*` dependencies.{n}
+    /// Regenerate the configs after changing the dependencies to keep them in sync.
+    Luacheckrc(Luacheckrc),
+}
+
+#[derive(Args)]
+pub struct Loader {
+    /// Where to write the loader.{n}
+    /// Defaults to `lux-loader.lua` in the project root.
+    #[arg(long, value_name = "path")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct Luacheckrc {
+    /// The linter to generate a config for. Can be specified multiple times.{n}
+    /// Defaults to the linters in the `[lint]` section of the lux.toml.
+    #[arg(long = "linter", value_enum)]
+    linters: Option<Vec<operations::Linter>>,
+
+    /// Allow Neovim's `vim` global in all files,{n}
+    /// even if no dependency indicates that the project is a Neovim plugin.
+    #[arg(long)]
+    nvim: bool,
+
+    /// Overwrite existing configs that were not generated by lux.
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+pub struct Nix {
+    /// Also generate a `flake.nix` that exposes the derivation as the default package.
+    #[arg(long)]
+    flake: bool,
+}
+
+pub fn generate(cmd: GenerateCmd, config: Config) -> Result<()> {
+    match cmd {
+        GenerateCmd::Loader(loader) => {
+            let project = Project::current_or_err()?;
+            let path = operations::GenLoader::new(&project)
+                .maybe_output(loader.output)
+                .relocatable(config.relocatable())
+                .generate()?;
+            println!("Wrote loader to {}", path.display());
+        }
+        GenerateCmd::Nix(nix) => {
+            let project = Project::current_or_err()?;
+            let paths = operations::GenNix::new(&project, &config)
+                .flake(nix.flake)
+                .generate()?;
+            for path in paths {
+                println!("Wrote {}", path.display());
+            }
+        }
+        GenerateCmd::Luacheckrc(luacheckrc) => {
+            let project = Project::current_or_err()?;
+            let paths = operations::GenLintConfig::new(&project)
+                .maybe_linters(luacheckrc.linters)
+                .nvim(luacheckrc.nvim)
+                .force(luacheckrc.force)
+                .generate()?;
+            for path in paths {
+                println!("Wrote {}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,19 +1,70 @@
+use std::path::PathBuf;
+
 use clap::Args;
-use eyre::Result;
-use lux_lib::{project::Project, rockspec::Rockspec};
+use eyre::{eyre, Context, Result};
+use lux_lib::{
+    lua_rockspec::RemoteLuaRockspec,
+    project::{
+        rockspec_template::{RockspecTemplate, ROCKSPEC_TEMPLATE},
+        Project,
+    },
+    rockspec::Rockspec,
+};
 
 #[derive(Args)]
-pub struct GenerateRockspec {}
+pub struct GenerateRockspec {
+    /// Print the rockspec to stdout instead of writing it to a file.
+    #[arg(long, conflicts_with = "output")]
+    stdout: bool,
+
+    /// The path to write the rockspec to.{n}
+    /// Defaults to `<package>-<version>.rockspec` in the project root.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// A template that controls the order of the rockspec's fields and its comments.{n}
+    /// Fields are inserted with placeholders, e.g. `{{dependencies}}`.{n}
+    /// Fields that are not in the template are appended to the end.{n}
+    /// Defaults to the project's `rockspec.template`, if it exists.
+    #[arg(long)]
+    template: Option<PathBuf>,
+}
 
-pub fn generate_rockspec(_data: GenerateRockspec) -> Result<()> {
+pub fn generate_rockspec(data: GenerateRockspec) -> Result<()> {
     let project = Project::current()?.unwrap();
 
     let toml = project.toml().into_remote()?;
-    let rockspec = toml.to_lua_remote_rockspec_string()?;
+    let template_path = data
+        .template
+        .or_else(|| Some(project.root().join(ROCKSPEC_TEMPLATE)).filter(|path| path.is_file()));
+    let rockspec = match template_path {
+        Some(template_path) => {
+            let template: RockspecTemplate = std::fs::read_to_string(&template_path)
+                .wrap_err_with(|| {
+                    format!(
+                        "failed to read rockspec template {}",
+                        template_path.display()
+                    )
+                })?
+                .parse()?;
+            toml.to_lua_remote_rockspec_string_with_template(&template)?
+        }
+        None => toml.to_lua_remote_rockspec_string()?,
+    };
+
+    RemoteLuaRockspec::new(&rockspec)
+        .map_err(|err| eyre!("the generated rockspec is invalid:\n{err}"))?;
+
+    if data.stdout {
+        print!("{rockspec}");
+        return Ok(());
+    }
 
-    let path = project
-        .root()
-        .join(format!("{}-{}.rockspec", toml.package(), toml.version()));
+    let path = data.output.unwrap_or_else(|| {
+        project
+            .root()
+            .join(format!("{}-{}.rockspec", toml.package(), toml.version()))
+    });
 
     std::fs::write(&path, rockspec)?;
 
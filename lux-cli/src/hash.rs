@@ -0,0 +1,116 @@
+use std::{path::PathBuf, str::FromStr};
+
+use clap::{Args, ValueEnum};
+use eyre::{eyre, Result};
+use lux_lib::{
+    config::Config,
+    hash::{to_nix_base32, HasIntegrity},
+    operations,
+    package::PackageReq,
+    progress::{MultiProgress, Progress},
+};
+use url::Url;
+
+#[derive(Debug, Clone)]
+pub enum HashTarget {
+    Path(PathBuf),
+    Url(Url),
+    Package(PackageReq),
+}
+
+impl FromStr for HashTarget {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let path = PathBuf::from(s);
+        if path.exists() {
+            return Ok(Self::Path(path));
+        }
+        match Url::parse(s) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(Self::Url(url)),
+            _ => {
+                let pkg = PackageReq::from_str(s).map_err(|err| {
+                    eyre!(
+                        "No file {0} found and cannot parse URL or package query: {1}",
+                        s,
+                        err
+                    )
+                })?;
+                Ok(Self::Package(pkg))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum HashFormat {
+    /// The SRI hash, e.g. `sha256-...`, as used for `source.hash`{n}
+    /// in `lux.toml` and for `hash` in Nix fetchers.
+    Sri,
+    /// The sha256 in Nix's base-32 encoding, as used by `nix-prefetch-url`.
+    Nix,
+}
+
+#[derive(Args)]
+pub struct Hash {
+    /// What to hash.{n}
+    ///{n}
+    /// Examples:{n}
+    ///     - "/path/to/file.tar.gz"{n}
+    ///     - "/path/to/directory" (hashed in the Nix archive format){n}
+    ///     - "https://example.com/foo-1.0.0.tar.gz"{n}
+    ///     - "pkg@1.0.0" (hashes the package's source){n}
+    #[clap(value_parser)]
+    target: HashTarget,
+
+    /// Only print the hash in this format.{n}
+    /// If not set, all formats are printed.
+    #[arg(long, value_enum)]
+    format: Option<HashFormat>,
+}
+
+/// Compute the hashes of a file, directory, URL or package source
+pub async fn hash(args: Hash, config: Config) -> Result<()> {
+    let integrity = match args.target {
+        HashTarget::Path(path) => path.hash()?,
+        HashTarget::Url(url) => config
+            .http_client()?
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .hash()?,
+        HashTarget::Package(package_req) => {
+            let progress = MultiProgress::new();
+            let bar = Progress::Progress(progress.new_bar());
+            let data = operations::NixPrefetch::new(&package_req, &config, &bar)
+                .prefetch()
+                .await?;
+            bar.map(|b| b.finish_and_clear());
+            // Without a format, the package and a Nix fetcher expression are printed, too.
+            match args.format {
+                Some(format) => print_hash(format, &data.hash, &data.sha256),
+                None => println!("{data}"),
+            }
+            return Ok(());
+        }
+    };
+    let (hash, sha256) = (integrity.to_string(), to_nix_base32(&integrity));
+    match args.format {
+        Some(format) => print_hash(format, &hash, &sha256),
+        None => {
+            println!("hash: {hash}");
+            println!("sha256: {sha256}");
+        }
+    }
+    Ok(())
+}
+
+fn print_hash(format: HashFormat, hash: &str, sha256: &str) {
+    match format {
+        HashFormat::Sri => println!("{hash}"),
+        HashFormat::Nix => println!("{sha256}"),
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use lux_lib::{
+    operations,
+    progress::{MultiProgress, Progress},
+};
+
+#[derive(Args)]
+pub struct Index {
+    /// The directory containing the rockspecs and rocks to index.{n}
+    /// Defaults to the current working directory.
+    dir: Option<PathBuf>,
+
+    /// Re-read all rockspecs and rocks, instead of only the new or modified ones.
+    #[arg(long)]
+    force: bool,
+}
+
+pub async fn index(data: Index) -> Result<()> {
+    let dir = match data.dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let progress = MultiProgress::new();
+    let bar = Progress::Progress(progress.new_bar());
+
+    let report = operations::Index::new(&dir, &bar)
+        .force(data.force)
+        .index()
+        .await?;
+
+    bar.map(|b| {
+        b.finish_with_message(if report.written().is_empty() {
+            format!(
+                "Manifests for {} rocks in {} are up to date.",
+                report.rocks(),
+                dir.display()
+            )
+        } else {
+            format!(
+                "📚 Indexed {} rocks ({} new or modified) in {}.",
+                report.rocks(),
+                report.reindexed().len(),
+                dir.display()
+            )
+        })
+    });
+
+    Ok(())
+}
@@ -2,7 +2,7 @@ use clap::Args;
 use eyre::Result;
 use lux_lib::{
     config::Config,
-    operations::Download,
+    operations::{Download, RemoteMetadata},
     package::PackageReq,
     progress::{MultiProgress, Progress},
     rockspec::Rockspec,
@@ -13,9 +13,43 @@ use crate::utils::project::current_project_or_user_tree;
 #[derive(Args)]
 pub struct Info {
     package: PackageReq,
+
+    /// Print the package's rockspec, without installing it.
+    #[arg(long, conflicts_with_all = ["json", "resolve"])]
+    rockspec: bool,
+
+    /// Print the package's rockspec as JSON, without installing it.
+    #[arg(long, conflicts_with = "resolve")]
+    json: bool,
+
+    /// Print the package's resolved dependencies for the current platform,
+    /// without installing them.
+    #[arg(long)]
+    resolve: bool,
 }
 
 pub async fn info(data: Info, config: Config) -> Result<()> {
+    if data.rockspec || data.json {
+        let rockspec = RemoteMetadata::new(&data.package, &config)
+            .rockspec()
+            .await?;
+        if data.json {
+            println!("{}", serde_json::to_string_pretty(&rockspec.to_json()?)?);
+        } else {
+            print!("{}", rockspec.to_lua_remote_rockspec_string()?);
+        }
+        return Ok(());
+    }
+    if data.resolve {
+        for package in RemoteMetadata::new(&data.package, &config)
+            .resolve()
+            .await?
+        {
+            println!("{} {}", package.name(), package.version());
+        }
+        return Ok(());
+    }
+
     let tree = current_project_or_user_tree(&config)?;
 
     let progress = MultiProgress::new();
@@ -1,18 +1,49 @@
+use std::{str::FromStr, time::Instant};
+
 use eyre::Result;
+use itertools::{Either, Itertools};
 use lux_lib::{
     config::{Config, LuaVersion},
-    lockfile::PinnedState,
-    operations,
+    lockfile::{signature::verify_lockfile_signature, PinnedState},
+    operations::{self, FetchGitPackage, GitPackageUrl, PackageInstallSpec},
     package::PackageReq,
-    progress::MultiProgress,
+    progress::{MultiProgress, Progress},
+    tree::ModulePrecedence,
+};
+
+use crate::utils::{
+    install::apply_build_behaviour,
+    summary::{Summary, SummaryRow},
 };
 
-use crate::utils::install::apply_build_behaviour;
+#[derive(Debug, Clone)]
+pub enum InstallTarget {
+    Package(PackageReq),
+    Git(GitPackageUrl),
+}
+
+impl FromStr for InstallTarget {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("git+") {
+            Ok(Self::Git(s.parse()?))
+        } else {
+            Ok(Self::Package(s.parse()?))
+        }
+    }
+}
 
 #[derive(clap::Args)]
 pub struct Install {
-    /// Package or list of packages to install.
-    package_req: Vec<PackageReq>,
+    /// Package or list of packages to install.{n}
+    /// Packages can also be installed from a git repository{n}
+    /// containing a lux.toml or a rockspec, optionally at a revision:{n}
+    ///     - "git+https://github.com/owner/repo"{n}
+    ///     - "git+https://github.com/owner/repo#v1.0.0"{n}
+    /// The commit they are built from is recorded in the lockfile.
+    #[clap(value_parser)]
+    package_req: Vec<InstallTarget>,
 
     /// Pin the packages so that they don't get updated.
     #[arg(long)]
@@ -21,24 +52,106 @@ pub struct Install {
     /// Reinstall without prompt if a package is already installed.
     #[arg(long)]
     force: bool,
+
+    /// Install packages even if they provide modules or binaries{n}
+    /// that are already provided by other installed packages.
+    #[arg(long)]
+    allow_overwrite: bool,
+
+    /// Install packages that provide the same modules as other installed packages{n}
+    /// side by side, like luarocks' versioned deploys.{n}
+    /// `require` loads the module of the package that takes precedence:{n}
+    /// the newly installed package (`new`, the default) or the installed one (`installed`).{n}
+    /// The other package's module remains available under a versioned name,{n}
+    /// e.g. `require("foo_1_0_0_1-bar")` for the module `bar` of `foo 1.0.0-1`.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "PRECEDENCE",
+        num_args = 0..=1,
+        default_missing_value = "new",
+        conflicts_with = "allow_overwrite"
+    )]
+    versioned: Option<ModulePrecedence>,
+
+    /// Install Lua bin scripts as-is, instead of wrapping them{n}
+    /// so that they are run with the tree's Lua interpreter.{n}
+    /// Takes precedence over the rockspecs' `deploy.wrap_bin_scripts`.
+    #[arg(long)]
+    no_wrap_bin: bool,
 }
 
 /// Install a rock into the user tree.
 pub async fn install(data: Install, config: Config) -> Result<()> {
     let pin = PinnedState::from(data.pin);
+    let config = if data.no_wrap_bin {
+        config.with_wrap_bin_scripts(false)
+    } else {
+        config
+    };
 
     let lua_version = LuaVersion::from(&config)?.clone();
     let tree = config.user_tree(lua_version)?;
+    if config.require_signed_lockfile() {
+        verify_lockfile_signature(&tree.lockfile_path(), &config)?;
+    }
 
-    let packages = apply_build_behaviour(data.package_req, pin, data.force, &tree)?;
+    let (package_reqs, git_urls): (Vec<_>, Vec<_>) =
+        data.package_req
+            .into_iter()
+            .partition_map(|target| match target {
+                InstallTarget::Package(package_req) => Either::Left(package_req),
+                InstallTarget::Git(url) => Either::Right(url),
+            });
+    let progress = MultiProgress::new_arc();
+    let packages = apply_build_behaviour(package_reqs, pin, data.force, &tree)?
+        .into_iter()
+        .chain(fetch_git_packages(git_urls, pin, &progress)?)
+        .collect_vec();
 
+    let requested = packages.len();
+    let start = Instant::now();
     // TODO(vhyrro): If the tree doesn't exist then error out.
-    operations::Install::new(&config)
+    let result = operations::Install::new(&config)
         .packages(packages)
         .tree(tree)
-        .progress(MultiProgress::new_arc())
+        .progress(progress)
+        .maybe_versioned(data.versioned)
+        .check_conflicts(!data.allow_overwrite)
         .install()
-        .await?;
+        .await;
+
+    let mut summary = Summary::default();
+    summary.push(SummaryRow {
+        operation: "install",
+        installed: result.as_ref().map_or(0, Vec::len),
+        failed: if result.is_err() { requested } else { 0 },
+        duration: start.elapsed(),
+        ..SummaryRow::default()
+    });
+    summary.print();
+    result?;
 
     Ok(())
 }
+
+/// Clone the git repositories and read their packages, to install them as entrypoints.
+pub(crate) fn fetch_git_packages(
+    urls: Vec<GitPackageUrl>,
+    pin: PinnedState,
+    progress: &Progress<MultiProgress>,
+) -> Result<Vec<PackageInstallSpec>> {
+    urls.into_iter()
+        .map(|url| {
+            let bar = progress.map(|p| p.new_bar());
+            let git_package = FetchGitPackage::new(&url, &bar).fetch()?;
+            bar.map(|b| b.finish_and_clear());
+            println!(
+                "Installing {} from {url} at commit {}",
+                git_package.package(),
+                git_package.commit()
+            );
+            Ok(git_package.into_install_spec(pin))
+        })
+        .try_collect()
+}
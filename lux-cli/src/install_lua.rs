@@ -1,21 +1,41 @@
-use eyre::Result;
+use clap::Args;
+use eyre::{eyre, Result};
+use itertools::Itertools;
 use lux_lib::{
     config::{Config, LuaVersion},
     lua_installation::LuaInstallation,
     progress::{MultiProgress, Progress, ProgressBar},
 };
 
-pub async fn install_lua(config: Config) -> Result<()> {
+#[derive(Args)]
+pub struct InstallLua {
+    /// Only install the C headers (lua.h, luaconf.h, lauxlib.h, ...).{n}
+    /// This is much faster than building Lua, and is enough to build C rocks{n}
+    /// against a Lua interpreter that already exists.
+    #[arg(long)]
+    headers_only: bool,
+}
+
+pub async fn install_lua(args: InstallLua, config: Config) -> Result<()> {
     let version_stringified = &LuaVersion::from(&config)?;
 
+    let what = if args.headers_only {
+        "Lua headers"
+    } else {
+        "Lua"
+    };
     let progress = MultiProgress::new();
     let bar = Progress::Progress(progress.add(ProgressBar::from(format!(
-        "🌔 Installing Lua ({version_stringified})",
+        "🌔 Installing {what} ({version_stringified})",
     ))));
 
     // TODO: Detect when path already exists by checking `Lua::path()` and prompt the user
     // whether they'd like to forcefully reinstall.
-    let lua = LuaInstallation::install(version_stringified, &config, &bar).await?;
+    let lua = if args.headers_only {
+        LuaInstallation::install_headers(version_stringified, &config, &bar).await?
+    } else {
+        LuaInstallation::install(version_stringified, &config, &bar).await?
+    };
     let lua_root = lua
         .includes()
         .first()
@@ -24,7 +44,8 @@ pub async fn install_lua(config: Config) -> Result<()> {
 
     bar.map(|bar| {
         bar.finish_with_message(format!(
-            "🌔 Installed Lua ({}) to {}",
+            "🌔 Installed {} ({}) to {}",
+            what,
             version_stringified,
             lua_root.display()
         ))
@@ -32,3 +53,38 @@ pub async fn install_lua(config: Config) -> Result<()> {
 
     Ok(())
 }
+
+/// Print information about the Lua installation that Lux builds C rocks against.
+pub fn debug_lua(config: Config) -> Result<()> {
+    let lua_version = LuaVersion::from(&config)?;
+    let lua = LuaInstallation::find(lua_version, &config).ok_or(eyre!(
+        "no Lua {} installation found. Run `lx install-lua` to install one.",
+        lua_version
+    ))?;
+
+    println!("Lua version: {}", lua.version);
+    let kind = if lua.is_headers_only() {
+        "headers only"
+    } else {
+        "full"
+    };
+    println!("Installation: {kind}");
+    if let Some(bin) = lua.bin() {
+        println!("Lua binary: {}", bin.display());
+    }
+    println!(
+        "Include directories: {}",
+        lua.includes().iter().map(|dir| dir.display()).join(", ")
+    );
+    let headers = lua.headers();
+    if headers.is_empty() {
+        println!("Headers: none found");
+    } else {
+        println!("Headers:");
+        for header in headers {
+            println!("  {}", header.display());
+        }
+    }
+
+    Ok(())
+}
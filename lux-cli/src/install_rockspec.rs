@@ -76,6 +76,7 @@ pub async fn install_rockspec(data: InstallRockspec, config: Config) -> Result<(
     Install::new(&config)
         .packages(build_dependencies_to_install)
         .tree(tree.build_tree(&config)?)
+        .check_conflicts(false)
         .progress(progress_arc.clone())
         .install()
         .await?;
@@ -101,6 +102,7 @@ pub async fn install_rockspec(data: InstallRockspec, config: Config) -> Result<(
     Install::new(&config)
         .packages(dependencies_to_install)
         .tree(tree.clone())
+        .check_conflicts(false)
         .progress(progress_arc.clone())
         .install()
         .await?;
@@ -1,55 +1,94 @@
-use crate::{completion::Completion, format::Fmt, project::NewProject};
+use crate::{
+    completion::Completion,
+    format::Fmt,
+    project::{InitProject, NewProject},
+};
 use std::error::Error;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use add::Add;
-use build::Build;
+use binaries::BinCmd;
+use build::BuildCommand;
+use bundle::Bundle;
+use check::Check;
 use clap::{Parser, Subcommand};
 use config::ConfigCmd;
 use debug::Debug;
+use diagnostic::ErrorFormat;
+use direnv::DirenvCmd;
 use doc::Doc;
 use download::Download;
 use exec::Exec;
+use explain::Explain;
+use generate::GenerateCmd;
 use generate_rockspec::GenerateRockspec;
+use hash::Hash;
+use index::Index;
 use info::Info;
 use install::Install;
+use install_lua::InstallLua;
 use install_rockspec::InstallRockspec;
 use lint::Lint;
 use list::ListCmd;
-use lux_lib::config::LuaVersion;
+use lock::LockCmd;
+use lux_lib::{
+    config::{ConfigOverrides, LuaVersion},
+    project::Project,
+};
+use nvim::NvimCmd;
 use outdated::Outdated;
 use pack::Pack;
 use path::Path;
 use pin::ChangePin;
+use purge::Purge;
 use remove::Remove;
 use run::Run;
 use run_lua::RunLua;
 use search::Search;
 use shell::Shell;
 use test::Test;
+use tree::TreeCmd;
 use uninstall::Uninstall;
+use unpack::Unpack;
 use update::Update;
 use upload::Upload;
 use url::Url;
+use verify::Verify;
+use watch::WatchCmd;
 use which::Which;
 
 pub mod add;
+pub mod binaries;
 pub mod build;
+pub mod bundle;
+pub mod check;
 pub mod completion;
 pub mod config;
 pub mod debug;
+pub mod diagnostic;
+pub mod direnv;
 pub mod doc;
 pub mod download;
 pub mod exec;
+pub mod explain;
+pub mod external_deps;
 pub mod fetch;
 pub mod format;
+pub mod gen_man;
+pub mod generate;
 pub mod generate_rockspec;
+pub mod hash;
+pub mod index;
 pub mod info;
 pub mod install;
 pub mod install_lua;
 pub mod install_rockspec;
 pub mod lint;
 pub mod list;
+pub mod lock;
+pub mod nix_prefetch;
+pub mod nvim;
 pub mod outdated;
 pub mod pack;
 pub mod path;
@@ -62,11 +101,14 @@ pub mod run_lua;
 pub mod search;
 pub mod shell;
 pub mod test;
+pub mod tree;
 pub mod uninstall;
 pub mod unpack;
 pub mod update;
 pub mod upload;
 pub mod utils;
+pub mod verify;
+pub mod watch;
 pub mod which;
 
 /// A luxurious package manager for Lua.
@@ -100,14 +142,36 @@ pub struct Cli {
     pub lua_dir: Option<PathBuf>,
 
     /// Which Lua installation to use.{n}
-    /// Valid versions are: '5.1', '5.2', '5.3', '5.4', 'jit' and 'jit52'.
+    /// Valid versions are: '5.1', '5.2', '5.3', '5.4', 'jit' and 'jit52'.{n}
+    /// If neither this nor the config sets it, the Lua version is taken from{n}
+    /// the project's exact `lua` version in the lux.toml, if any,{n}
+    /// or from a `.lua-version` file in the project root.
     #[arg(long, value_name = "ver")]
     pub lua_version: Option<LuaVersion>,
 
     /// Which tree to operate on.
-    #[arg(long, value_name = "tree")]
+    #[arg(long, value_name = "tree", conflicts_with = "tree_name")]
     pub tree: Option<PathBuf>,
 
+    /// Which named tree to operate on.{n}
+    /// Named trees are defined in the `[trees]` section of the config,{n}
+    /// or with `lx tree create`.
+    #[arg(long, value_name = "name")]
+    pub tree_name: Option<String>,
+
+    /// A read-only tree, e.g. provisioned by a distribution{n}
+    /// package or a container image.{n}
+    /// Its packages are used to satisfy dependencies,{n}
+    /// but packages are only ever installed into the writable tree.
+    #[arg(long, value_name = "tree")]
+    pub system_tree: Option<PathBuf>,
+
+    /// Load this config file on top of the default config file.{n}
+    /// Its settings take precedence over the default config file's,{n}
+    /// and CLI flags take precedence over both.
+    #[arg(long, value_name = "path")]
+    pub config: Option<PathBuf>,
+
     /// Specifies the cache directory for e.g. luarocks manifests.
     #[arg(long, value_name = "path")]
     pub cache_path: Option<PathBuf>,
@@ -125,6 +189,20 @@ pub struct Cli {
     #[arg(long)]
     pub verbose: bool,
 
+    /// Don't display progress or status messages.{n}
+    /// Progress bars are replaced by plain log lines{n}
+    /// if the output is not a terminal.
+    #[arg(long, short, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// The format in which to print the error that lx fails with.{n}
+    /// The exit code depends on the class of the failure:{n}
+    /// 1: other, 2: invalid arguments, 3: resolution, 4: build,{n}
+    /// 5: network, 6: verification, 7: test, 8: configuration.{n}
+    /// `lx outdated --lockfile` exits with 9 if updates are available.
+    #[arg(long, value_enum, default_value_t)]
+    pub error_format: ErrorFormat,
+
     /// Configure lux for installing Neovim packages.
     #[arg(long)]
     pub nvim: bool,
@@ -134,76 +212,226 @@ pub struct Cli {
     #[arg(long, value_name = "seconds")]
     pub timeout: Option<usize>,
 
+    /// How long to wait for another lux process to release{n}
+    /// its lock on an install tree, in seconds.{n}
+    /// 0 means no timeout (wait forever). Default is 300.
+    #[arg(long, value_name = "seconds")]
+    pub lock_timeout: Option<u64>,
+
     /// Do not generate or update a `.luarc.json` file when building{n}
     /// a project.
     #[arg(long)]
     pub no_luarc: bool,
 
+    /// Refuse to install packages from a lockfile that is not signed{n}
+    /// by one of the `trusted_lockfile_keys` in the config.{n}
+    /// Lockfiles can be signed with `lx lock sign`.
+    #[arg(long)]
+    pub require_signed_lockfile: bool,
+
+    /// Allow package servers to be accessed over plain HTTP.{n}
+    /// To only allow specific servers, e.g. intranet registries,{n}
+    /// add their hosts to `insecure_servers` in the config instead.
+    #[arg(long)]
+    pub allow_insecure_servers: bool,
+
+    /// Fail if the project's lux.toml contains unknown fields,{n}
+    /// e.g. misspelled ones, instead of warning about them.
+    #[arg(long)]
+    pub deny_unknown_fields: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+impl Cli {
+    /// The overrides the global flags apply on top of the lux config file.
+    pub fn config_overrides(&self) -> ConfigOverrides {
+        ConfigOverrides::builder()
+            .dev(self.dev)
+            .maybe_server(self.server.clone())
+            .maybe_extra_servers(self.extra_servers.clone())
+            .maybe_only_sources(self.only_sources.clone())
+            .maybe_namespace(self.namespace.clone())
+            .maybe_lua_dir(self.lua_dir.clone())
+            .maybe_lua_version(self.lua_version.clone())
+            .maybe_default_lua_version(self.default_lua_version())
+            .maybe_user_tree(self.tree.clone())
+            .maybe_system_tree(self.system_tree.clone())
+            .maybe_tree_name(self.tree_name.clone())
+            .maybe_cache_dir(self.cache_path.clone())
+            .no_project(self.no_project)
+            .variables(self.variables.iter().flatten().cloned().collect())
+            .verbose(self.verbose)
+            .nvim(self.nvim)
+            .maybe_timeout(
+                self.timeout
+                    .map(|duration| Duration::from_secs(duration as u64)),
+            )
+            .maybe_lock_timeout(self.lock_timeout.map(Duration::from_secs))
+            .no_luarc(self.no_luarc)
+            .require_signed_lockfile(self.require_signed_lockfile)
+            .allow_insecure_servers(self.allow_insecure_servers)
+            .deny_unknown_fields(self.deny_unknown_fields)
+            .build()
+    }
+
+    /// The current project's default Lua version, e.g. from its `.lua-version` file.
+    /// Errors loading the project are reported by the commands that use it.
+    fn default_lua_version(&self) -> Option<LuaVersion> {
+        if self.no_project || self.lua_version.is_some() {
+            return None;
+        }
+        let project = Project::current().ok().flatten()?;
+        project
+            .default_lua_version()
+            .inspect_err(|err| eprintln!("⚠️ WARNING: {err}"))
+            .ok()
+            .flatten()
+    }
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Add a dependency to the current project.
+    /// Add a dependency to the current project.{n}
+    /// Example: `lx add lpeg@1.1.0 busted --test`
     Add(Add),
-    /// Build/compile a project.
-    Build(Build),
-    /// Interact with the lux configuration.
+    /// List installed executables and generate PATH shims for them.{n}
+    /// Example: `lx bin shims ~/.local/bin`
+    #[command(subcommand, arg_required_else_help = true)]
+    Bin(BinCmd),
+    /// Build/compile a project.{n}
+    /// Example: `lx build --only-deps`
+    Build(BuildCommand),
+    /// Bundle the current project and its pure Lua dependencies{n}
+    /// into a single Lua file or zip archive, e.g. for OpenResty{n}
+    /// or applications that embed Lua.{n}
+    /// Example: `lx bundle --entrypoint main`
+    Bundle(Bundle),
+    /// Check the current project for dependency hygiene issues.{n}
+    /// Example: `lx check --project`
+    Check(Check),
+    /// Interact with the lux configuration.{n}
+    /// Example: `lx config show`
     #[command(subcommand, arg_required_else_help = true)]
     Config(ConfigCmd),
     /// Generate autocompletion scripts for the shell.{n}
     /// Example: `lx completion zsh > ~/.zsh/completions/_lx`
     Completion(Completion),
-    /// Internal commands for debugging Lux itself.
+    /// Internal commands for debugging Lux itself.{n}
+    /// Example: `lx debug project`
     #[command(subcommand, arg_required_else_help = true)]
     Debug(Debug),
-    /// Show documentation for an installed rock.
+    /// Integrate with direnv, to load the environment of the current project{n}
+    /// when entering its directory.{n}
+    /// Example: `lx direnv stdlib > ~/.config/direnv/lib/lux.sh`
+    #[command(subcommand, arg_required_else_help = true)]
+    Direnv(DirenvCmd),
+    /// Show documentation for an installed rock.{n}
+    /// Example: `lx doc luasocket --online`
     Doc(Doc),
-    /// Download a specific rock file from a luarocks server.
+    /// Download a specific rock file from a luarocks server.{n}
+    /// Exits with code 3, like other resolution failures,{n}
+    /// if no rock matches the package requirement.{n}
+    /// Example: `lx download say@1.4.1 --unpack`
     #[command(arg_required_else_help = true)]
     Download(Download),
-    /// Formats the codebase with stylua.
+    /// Formats the codebase with the formatter configured{n}
+    /// in the lux.toml's `[format]` section (stylua by default).{n}
+    /// Example: `lx fmt`
     Fmt(Fmt),
-    /// Generate a rockspec file from a project.
+    /// Generate files for a project.{n}
+    /// Example: `lx generate loader`
+    #[command(subcommand, arg_required_else_help = true)]
+    Generate(GenerateCmd),
+    /// Generate a rockspec file from a project.{n}
+    /// Example: `lx generate-rockspec`
     GenerateRockspec(GenerateRockspec),
-    /// Show metadata for any rock.
+    /// Compute the hashes of a file, directory, URL or package source,{n}
+    /// e.g. for `source.hash` in `lux.toml`, or for Nix expressions.{n}
+    /// Example: `lx hash ./src`
+    #[command(arg_required_else_help = true)]
+    Hash(Hash),
+    /// Generate luarocks-compatible manifests for a directory of rockspecs and rocks,{n}
+    /// so that it can be hosted as a static server on any web server or S3 bucket{n}
+    /// and used with `--server`.{n}
+    /// Writes a `manifest` and a `manifest-<lua version>` for each Lua version,{n}
+    /// along with zipped variants.{n}
+    /// Only new or modified rockspecs and rocks are read when regenerating the manifests.{n}
+    /// Example: `lx index ./rocks`
+    Index(Index),
+    /// Show metadata for any rock.{n}
+    /// Example: `lx info penlight`
     Info(Info),
-    /// Install a rock for use on the system.
+    /// Turn the current directory into a lux project,{n}
+    /// converting an existing rockspec if there is one.{n}
+    /// Existing files are never overwritten.{n}
+    /// Example: `lx init --name my-project`
+    Init(InitProject),
+    /// Install a rock for use on the system.{n}
+    /// Example: `lx install penlight@1.14.0`
     #[command(arg_required_else_help = true)]
     Install(Install),
-    /// Install a local rockspec for use on the system.
+    /// Install a local rockspec for use on the system.{n}
+    /// Example: `lx install-rockspec ./foo-1.0.0-1.rockspec`
     #[command(arg_required_else_help = true)]
     InstallRockspec(InstallRockspec),
-    /// Manually install and manage Lua headers for various Lua versions.
-    InstallLua,
-    /// Lints the current project using `luacheck`.
+    /// Manually install and manage Lua headers for various Lua versions.{n}
+    /// Example: `lx --lua-version 5.4 install-lua`
+    InstallLua(InstallLua),
+    /// Lints the current project using `luacheck` and/or `selene`.{n}
+    /// Example: `lx lint --linter selene`
     Lint(Lint),
-    /// List currently installed rocks.
+    /// Sign or verify the lockfile of the current project.{n}
+    /// Outside of a project, the user tree's lockfile is used.{n}
+    /// Example: `lx lock verify`
+    #[command(subcommand, arg_required_else_help = true)]
+    Lock(LockCmd),
+    /// List currently installed rocks.{n}
+    /// Example: `lx list --outdated`
     List(ListCmd),
-    /// Run lua, with the `LUA_PATH` and `LUA_CPATH` set to the specified lux tree.
+    /// Run lua, with the `LUA_PATH` and `LUA_CPATH` set to the specified lux tree.{n}
+    /// Example: `lx lua -e 'print(require("pl.pretty"))'`
     Lua(RunLua),
-    /// Create a new Lua project.
+    /// Create a new Lua project.{n}
+    /// Example: `lx new ./my-project`
     New(NewProject),
-    /// List outdated rocks.
+    /// Manage installed rocks for use as Neovim plugins.{n}
+    /// Example: `lx nvim link`
+    #[command(subcommand, arg_required_else_help = true)]
+    Nvim(NvimCmd),
+    /// List outdated rocks.{n}
+    /// Example: `lx outdated --porcelain`
     Outdated(Outdated),
-    /// Create a packed rock for distribution, packing sources or binaries.
+    /// Create a packed rock for distribution, packing sources or binaries.{n}
+    /// Example: `lx pack penlight`
     Pack(Pack),
-    /// Return the currently configured package path.
+    /// Return the currently configured package path.{n}
+    /// Example: `eval "$(lx path)"`
     Path(Path),
-    /// Pin an existing rock, preventing any updates to the package.
+    /// Pin an existing rock, preventing any updates to the package.{n}
+    /// Example: `lx pin penlight`
     Pin(ChangePin),
-    /// Remove all installed rocks from a tree.
-    Purge,
-    /// Remove a rock from the current project's lux.toml dependencies.
+    /// Remove all installed rocks from a tree,{n}
+    /// or from its test or build dependency trees.{n}
+    /// Reports the disk space that was reclaimed.{n}
+    /// Example: `lx purge --except busted`
+    Purge(Purge),
+    /// Remove a rock from the current project's lux.toml dependencies.{n}
+    /// Example: `lx remove penlight`
     Remove(Remove),
-    /// Run the current project with the provided arguments.
+    /// Run the current project with the provided arguments.{n}
+    /// Example: `lx run -- --verbose`
     Run(Run),
     /// Execute a command that has been installed with lux.
     /// If the command is not found, a package named after the command
-    /// will be installed.
+    /// will be installed.{n}
+    /// Example: `lx exec busted --version`
     Exec(Exec),
-    /// Query the luarocks servers.
+    /// Print extended documentation for an error code, e.g. `lx explain LUX0003`.
+    Explain(Explain),
+    /// Query the luarocks servers.{n}
+    /// Example: `lx search --module lpeg`
     #[command(arg_required_else_help = true)]
     Search(Search),
     /// Run the test suite in the current project directory.{n}
@@ -227,9 +455,10 @@ pub enum Commands {
     /// {n}
     ///   - busted-nlua:{n}:
     ///     {n}
-    ///     [currently broken on macOS and Windows]
     ///     A build backend for running busted tests with Neovim as the Lua interpreter.
     ///     Used for testing Neovim plugins.
+    ///     Requires Neovim (`nvim`) on the PATH, or the path to it in the `NVIM` config variable.
+    ///     The tests run with `NVIM_APPNAME=lux-nlua`, so they don't load your Neovim config.
     ///     {n}
     ///     Example:{n}
     ///     {n}
@@ -267,11 +496,26 @@ pub enum Commands {
     ///     flags = [ ] # Optional arguments passed to the test script{n}
     ///     ```{n}
     Test(Test),
-    /// Uninstall a rock from the system.
+    /// Manage the default user tree and named trees.{n}
+    /// Named trees are defined in the `[trees]` section of the config{n}
+    /// and can be selected with `--tree-name`.{n}
+    /// Example: `lx tree create lua51 --root ~/.lua51`
+    #[command(subcommand, arg_required_else_help = true)]
+    Tree(TreeCmd),
+    /// Uninstall a rock from the system.{n}
+    /// Example: `lx uninstall penlight`
     Uninstall(Uninstall),
-    /// Unpins an existing rock, allowing updates to alter the package.
+    /// Unpins an existing rock, allowing updates to alter the package.{n}
+    /// Example: `lx unpin penlight`
     Unpin(ChangePin),
-    /// Updates all rocks in a project.
+    /// Unpack a packed rock, validating the hashes in its rock_manifest.{n}
+    /// Use `--list` to list its contents, or `--diff` to compare it{n}
+    /// with another packed rock, without extracting it.{n}
+    /// Example: `lx unpack foo-1.0.0-1.src.rock --list`
+    #[command(arg_required_else_help = true)]
+    Unpack(Unpack),
+    /// Updates all rocks in a project.{n}
+    /// Example: `lx update penlight`
     Update(Update),
     /// Generate a Lua rockspec for a Lux project and upload it to the public luarocks repository.{n}
     /// You can specify a source template for release and dev packages in the lux.toml.{n}
@@ -294,9 +538,26 @@ pub enum Commands {
     /// If the `version` is not set in the lux.toml, lux will search the current
     /// commit for SemVer tags and if found, will use it to generate the package version.
     Upload(Upload),
-    /// Tell which file corresponds to a given module name.
+    /// Check that the current project's lux.toml, generated rockspec{n}
+    /// and lockfile are consistent: declared modules and installed files exist,{n}
+    /// the rockspec can be generated, the source URL can be fetched{n}
+    /// and the lockfile is in sync with the dependencies.{n}
+    /// Exits with a non-zero status if any issues are found.{n}
+    /// Example: `lx verify --no-fetch`
+    Verify(Verify),
+    /// Watch the current project's files and rerun a command when they change.{n}
+    /// Example: `lx watch test --changed-only`
+    #[command(subcommand, arg_required_else_help = true)]
+    Watch(WatchCmd),
+    /// Tell which file corresponds to a given module name,{n}
+    /// or to an installed executable with `--bin`.{n}
+    /// Use `--edit` to open the file in your editor.{n}
+    /// Example: `lx which pl.pretty --edit`
     Which(Which),
-    /// Spawns an interactive shell with PATH, LUA_PATH, LUA_CPATH and LUA_INIT set.
+    /// Spawns an interactive shell with PATH, LUA_PATH, LUA_CPATH and LUA_INIT set.{n}
+    /// The shell's prompt is prefixed with the active project's name.{n}
+    /// Use `--command` to run a single command in the shell's environment instead.{n}
+    /// Example: `lx shell --command busted`
     Shell(Shell),
 }
 
@@ -1,90 +1,60 @@
-use std::collections::HashSet;
-
 use clap::Args;
-use eyre::Result;
+use eyre::{eyre, Result};
 use itertools::Itertools;
 use lux_lib::{
     config::Config,
-    operations::{Exec, Install, PackageInstallSpec},
+    operations::{self, Linter, Severity},
     progress::MultiProgress,
     project::Project,
-    tree,
 };
-use path_slash::PathBufExt;
-use walkdir::WalkDir;
 
 #[derive(Args)]
 pub struct Lint {
-    /// Arguments to pass to the luacheck command.{n}
-    /// If you pass arguments to luacheck, Lux will not pass any default arguments.
+    /// Arguments to pass to the linters, after the project's Lua files.
     args: Option<Vec<String>>,
-    /// By default, Lux will add top-level ignored files and directories{n}
-    /// (like those in .gitignore) to luacheck's exclude files.{n}
+    /// By default, Lux only lints Lua files that are not ignored{n}
+    /// (like those in .gitignore).{n}
     /// This flag disables that behaviour.{n}
     #[arg(long)]
     no_ignore: bool,
+    /// The linters to run.{n}
+    /// Overrides the `linters` in the lux.toml's `[lint]` section.{n}
+    /// Defaults to `luacheck`, which is installed to the project's build tree if missing.
+    #[arg(long = "linter", value_enum)]
+    linters: Option<Vec<Linter>>,
 }
 
 pub async fn lint(lint_args: Lint, config: Config) -> Result<()> {
     let project = Project::current_or_err()?;
 
-    let luacheck =
-        PackageInstallSpec::new("luacheck".parse()?, tree::EntryType::Entrypoint).build();
-
-    Install::new(&config)
-        .package(luacheck)
-        .project(&project)?
+    let diagnostics = operations::Lint::new(&project, &config)
+        .args(lint_args.args.unwrap_or_default())
+        .maybe_linters(lint_args.linters)
+        .no_ignore(lint_args.no_ignore)
         .progress(MultiProgress::new_arc())
-        .install()
-        .await?;
-
-    let check_args: Vec<String> = match lint_args.args {
-        Some(args) => args,
-        None if lint_args.no_ignore => Vec::new(),
-        None => {
-            let top_level_project_files = ignore::WalkBuilder::new(project.root())
-                .max_depth(Some(1))
-                .build()
-                .filter_map(Result::ok)
-                .filter_map(|entry| {
-                    let file = entry.into_path();
-                    if file.is_dir() || file.extension().is_some_and(|ext| ext == "lua") {
-                        Some(file)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<HashSet<_>>();
-
-            let top_level_files = WalkDir::new(project.root())
-                .max_depth(1)
-                .into_iter()
-                .filter_map(Result::ok)
-                .filter_map(|entry| {
-                    let file = entry.into_path();
-                    if file.is_dir() || file.extension().is_some_and(|ext| ext == "lua") {
-                        Some(file)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<HashSet<_>>();
-
-            let ignored_files = top_level_files
-                .difference(&top_level_project_files)
-                .map(|file| file.to_slash_lossy().to_string());
-
-            std::iter::once("--exclude-files".into())
-                .chain(ignored_files)
-                .collect_vec()
-        }
-    };
-
-    Exec::new("luacheck", Some(&project), &config)
-        .arg(project.root().to_slash_lossy())
-        .args(check_args)
-        .exec()
+        .lint()
         .await?;
 
-    Ok(())
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
+    }
+
+    let (errors, warnings): (Vec<_>, Vec<_>) = diagnostics
+        .iter()
+        .partition(|diagnostic| diagnostic.severity == Severity::Error);
+    let files = diagnostics
+        .iter()
+        .map(|diagnostic| &diagnostic.file)
+        .unique()
+        .count();
+    Err(eyre!(
+        "{} warning(s) / {} error(s) in {} file(s)",
+        warnings.len(),
+        errors.len(),
+        files
+    ))
 }
@@ -1,45 +1,229 @@
-use clap::Args;
+use std::{
+    collections::HashMap,
+    io::{IsTerminal, Write},
+};
+
+use clap::{Args, ValueEnum};
 use eyre::Result;
 use itertools::Itertools as _;
 use lux_lib::{
     config::{Config, LuaVersion},
-    lockfile::PinnedState,
+    lockfile::{LocalPackage, LocalPackageId, OptState, PinnedState},
+    package::PackageVersion,
+    progress::{MultiProgress, Progress},
+    project::Project,
+    remote_package_db::RemotePackageDB,
+    tree::Tree,
 };
-use text_trees::{FormatCharacters, StringTreeNode, TreeFormatting};
+use serde_json::json;
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum ListTree {
+    /// The user tree.
+    #[default]
+    User,
+    /// The current project's tree.
+    Project,
+    /// The current project's test dependencies tree.
+    Test,
+    /// The current project's build dependencies tree.
+    Build,
+}
 
 #[derive(Args)]
 pub struct ListCmd {
+    /// Return a machine readable format.{n}
+    /// Prints a JSON array with an object for each installed rock, with the fields{n}
+    /// `name`, `version`, `pinned`, `optional`, `entrypoint`{n}
+    /// and, with `--outdated`, `latest`, which is `null` if the rock is up to date.
     #[arg(long)]
     porcelain: bool,
+
+    /// Only list pinned rocks.
+    #[arg(long)]
+    pinned: bool,
+
+    /// The tree to list the rocks of.
+    #[arg(long, value_enum, default_value_t)]
+    tree: ListTree,
+
+    /// Show the latest available version of outdated rocks.
+    #[arg(long)]
+    outdated: bool,
+}
+
+/// A row of the list of installed rocks.
+struct ListEntry {
+    package: LocalPackage,
+    entrypoint: bool,
+    latest: Option<PackageVersion>,
 }
 
-/// List rocks that are installed in the user tree
-pub fn list_installed(list_data: ListCmd, config: Config) -> Result<()> {
-    let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
-    let available_rocks = tree.list()?;
+/// List rocks that are installed in the user tree,
+/// or in one of the current project's trees
+pub async fn list_installed(list_data: ListCmd, config: Config) -> Result<()> {
+    let tree = list_tree(list_data.tree, &config)?;
+    let lockfile = tree.lockfile()?;
+    let packages = tree
+        .list()?
+        .into_values()
+        .flatten()
+        .filter(|package| !list_data.pinned || package.pinned() == PinnedState::Pinned)
+        .sorted_by(|a, b| {
+            a.name()
+                .cmp(b.name())
+                .then_with(|| a.version().cmp(b.version()))
+        })
+        .collect_vec();
+
+    let latest_versions = if list_data.outdated && !packages.is_empty() {
+        latest_versions(&packages, &config).await?
+    } else {
+        HashMap::new()
+    };
+
+    let entries = packages
+        .into_iter()
+        .map(|package| ListEntry {
+            entrypoint: lockfile.is_entrypoint(&package.id()),
+            latest: latest_versions.get(&package.id()).cloned(),
+            package,
+        })
+        .collect_vec();
 
     if list_data.porcelain {
-        println!("{}", serde_json::to_string(&available_rocks)?);
+        print_porcelain(&entries, list_data.outdated)
     } else {
-        let formatting = TreeFormatting::dir_tree(FormatCharacters::box_chars());
-        for (name, packages) in available_rocks.into_iter().sorted() {
-            let mut tree = StringTreeNode::new(name.to_string());
-
-            for package in packages {
-                tree.push(format!(
-                    "{}{}",
-                    package.version(),
-                    if package.pinned() == PinnedState::Pinned {
-                        " (pinned)"
-                    } else {
-                        ""
-                    }
-                ));
+        print_table(&entries)
+    }
+}
+
+fn list_tree(list_tree: ListTree, config: &Config) -> Result<Tree> {
+    Ok(match list_tree {
+        ListTree::User => config.user_tree(LuaVersion::from(config)?.clone())?,
+        ListTree::Project => Project::current_or_err()?.tree(config)?,
+        ListTree::Test => Project::current_or_err()?.test_tree(config)?,
+        ListTree::Build => Project::current_or_err()?.build_tree(config)?,
+    })
+}
+
+/// The latest available version of each outdated package, by package ID.
+async fn latest_versions(
+    packages: &[LocalPackage],
+    config: &Config,
+) -> Result<HashMap<LocalPackageId, PackageVersion>> {
+    let progress = MultiProgress::new();
+    let bar = Progress::Progress(progress.new_bar());
+    let package_db = RemotePackageDB::from_config(config, &bar).await?;
+    bar.map(|b| b.finish_and_clear());
+    // Rocks that aren't available from any server, e.g. local builds, are never outdated.
+    Ok(packages
+        .iter()
+        .filter_map(|package| {
+            let latest = package.to_package().has_update(&package_db).ok()??;
+            Some((package.id(), latest))
+        })
+        .collect())
+}
+
+fn print_porcelain(entries: &[ListEntry], outdated: bool) -> Result<()> {
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            let mut value = json!({
+                "name": entry.package.name().to_string(),
+                "version": entry.package.version().to_string(),
+                "pinned": entry.package.pinned().as_bool(),
+                "optional": entry.package.opt() == OptState::Optional,
+                "entrypoint": entry.entrypoint,
+            });
+            if outdated {
+                value["latest"] = json!(entry.latest.as_ref().map(|v| v.to_string()));
             }
+            value
+        })
+        .collect_vec();
+    println!("{}", serde_json::to_string(&entries)?);
+    Ok(())
+}
 
-            println!("{}", tree.to_string_with_format(&formatting)?);
+/// Print the entries in aligned columns,
+/// which are colored if stdout is a terminal.
+fn print_table(entries: &[ListEntry]) -> Result<()> {
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            let markers = [
+                match entry.package.pinned() {
+                    PinnedState::Pinned if entry.entrypoint => Some("pinned"),
+                    PinnedState::Pinned => Some("pinned dependency"),
+                    PinnedState::Unpinned => None,
+                },
+                (entry.package.opt() == OptState::Optional).then_some("optional"),
+            ]
+            .into_iter()
+            .flatten()
+            .join(", ");
+            (
+                entry.package.name().to_string(),
+                entry.package.version().to_string(),
+                markers,
+                entry.latest.as_ref().map(|latest| format!("=> {latest}")),
+            )
+        })
+        .collect_vec();
+    let name_width = rows.iter().map(|row| row.0.len()).max().unwrap_or_default();
+    let version_width = rows.iter().map(|row| row.1.len()).max().unwrap_or_default();
+    let markers_width = rows.iter().map(|row| row.2.len()).max().unwrap_or_default();
+
+    let color_choice = if std::io::stdout().is_terminal() {
+        ColorChoice::Auto
+    } else {
+        ColorChoice::Never
+    };
+    let mut stdout = StandardStream::stdout(color_choice);
+    for (name, version, markers, latest) in rows {
+        let columns = [
+            (
+                Some(name),
+                name_width,
+                ColorSpec::new().set_bold(true).clone(),
+            ),
+            (
+                Some(version),
+                version_width,
+                ColorSpec::new().set_fg(Some(Color::Green)).clone(),
+            ),
+            (
+                Some(markers).filter(|markers| !markers.is_empty() || latest.is_some()),
+                markers_width,
+                ColorSpec::new().set_fg(Some(Color::Cyan)).clone(),
+            ),
+            (
+                latest,
+                0,
+                ColorSpec::new().set_fg(Some(Color::Yellow)).clone(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(column, width, color)| Some((column?, width, color)))
+        .collect_vec();
+        let last = columns.len() - 1;
+        for (i, (column, width, color)) in columns.into_iter().enumerate() {
+            if i > 0 {
+                write!(stdout, "  ")?;
+            }
+            stdout.set_color(&color)?;
+            // Don't pad the last column, so as not to leave trailing whitespace.
+            if i == last {
+                write!(stdout, "{column}")?;
+            } else {
+                write!(stdout, "{column:<width$}")?;
+            }
+            stdout.reset()?;
         }
+        writeln!(stdout)?;
     }
-
     Ok(())
 }
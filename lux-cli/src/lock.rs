@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use eyre::Result;
+use lux_lib::{
+    config::{Config, LuaVersion},
+    lockfile::signature::{sign_lockfile, verify_lockfile_signature},
+    project::Project,
+};
+
+#[derive(Subcommand)]
+pub enum LockCmd {
+    /// Write a detached signature of the lockfile to `lux.lock.sig`.{n}
+    /// Re-sign the lockfile whenever it changes.
+    Sign(Sign),
+    /// Verify that the lockfile is signed by one of the{n}
+    /// `trusted_lockfile_keys` in the config.
+    Verify,
+}
+
+#[derive(Args)]
+pub struct Sign {
+    /// The OpenPGP key to sign the lockfile with.{n}
+    /// Defaults to the default key of gpg.
+    #[arg(long, value_name = "fingerprint")]
+    key: Option<String>,
+}
+
+/// Sign or verify the lockfile of the current project,
+/// or of the user tree if not in a project.
+pub fn lock(cmd: LockCmd, config: Config) -> Result<()> {
+    let lockfile_path = lockfile_path(&config)?;
+    match cmd {
+        LockCmd::Sign(args) => {
+            let signature_path = sign_lockfile(&lockfile_path, args.key.as_deref())?;
+            println!("Wrote signature to {}", signature_path.display());
+        }
+        LockCmd::Verify => {
+            verify_lockfile_signature(&lockfile_path, &config)?;
+            println!("{} is signed by a trusted key.", lockfile_path.display());
+        }
+    }
+    Ok(())
+}
+
+fn lockfile_path(config: &Config) -> Result<PathBuf> {
+    Ok(match Project::current()? {
+        Some(project) => project.lockfile_path(),
+        None => config
+            .user_tree(LuaVersion::from(config)?.clone())?
+            .lockfile_path(),
+    })
+}
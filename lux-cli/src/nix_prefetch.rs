@@ -0,0 +1,32 @@
+use clap::Args;
+use eyre::Result;
+use lux_lib::{
+    config::Config,
+    operations,
+    package::PackageReq,
+    progress::{MultiProgress, Progress},
+};
+
+#[derive(Args)]
+pub struct NixPrefetch {
+    /// The package to prefetch, e.g. `foo@1.0.0-1`.
+    package_req: PackageReq,
+    /// Print the result as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+pub async fn nix_prefetch(args: NixPrefetch, config: Config) -> Result<()> {
+    let progress = MultiProgress::new();
+    let bar = Progress::Progress(progress.new_bar());
+    let data = operations::NixPrefetch::new(&args.package_req, &config, &bar)
+        .prefetch()
+        .await?;
+    bar.map(|b| b.finish_and_clear());
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&data)?);
+    } else {
+        println!("{data}");
+    }
+    Ok(())
+}
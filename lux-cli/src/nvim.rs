@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use eyre::Result;
+use lux_lib::{
+    config::{Config, LuaVersion},
+    operations,
+};
+
+#[derive(clap::Subcommand)]
+pub enum NvimCmd {
+    /// Link the runtime files of installed rocks into a `site/pack/lux`{n}
+    /// package directory that Neovim can load plugins from.{n}
+    /// Add the site directory to Neovim's `packpath` to use them.
+    Link(Link),
+}
+
+#[derive(Args)]
+pub struct Link {
+    /// The Neovim `site` directory to link into.{n}
+    /// Defaults to the `site` directory in the install tree.
+    #[arg(long, value_name = "path")]
+    site_dir: Option<PathBuf>,
+}
+
+pub fn nvim(cmd: NvimCmd, config: Config) -> Result<()> {
+    match cmd {
+        NvimCmd::Link(link) => {
+            let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
+            let linked = operations::NvimLink::new(&tree)
+                .maybe_site_dir(link.site_dir)
+                .link()?;
+            for dir in linked {
+                println!("{}", dir.display());
+            }
+        }
+    }
+    Ok(())
+}
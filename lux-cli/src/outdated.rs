@@ -5,30 +5,49 @@ use eyre::Result;
 use itertools::Itertools;
 use lux_lib::{
     config::{Config, LuaVersion},
+    operations::OutdatedLockfile,
     progress::{MultiProgress, Progress},
     project::Project,
     remote_package_db::RemotePackageDB,
 };
 use text_trees::{FormatCharacters, StringTreeNode, TreeFormatting};
 
-use crate::utils::project::sync_dependencies_if_locked;
+use crate::{
+    diagnostic,
+    utils::{project::sync_dependencies_if_locked, summary::Summary},
+};
 
 #[derive(Args)]
 pub struct Outdated {
     #[arg(long)]
     porcelain: bool,
+
+    /// Only check whether `lx update` would change the lockfile,
+    /// without syncing the tree or downloading any sources.
+    /// Exits with status 9 if it would.
+    #[arg(long)]
+    lockfile: bool,
 }
 
 /// List rocks that are outdated
 /// If in a project, this lists rocks in the project tree
 pub async fn outdated(outdated_data: Outdated, config: Config) -> Result<()> {
+    if outdated_data.lockfile {
+        return outdated_lockfile(outdated_data, config).await;
+    }
     let progress = MultiProgress::new();
     let bar = Progress::Progress(progress.new_bar());
     let project = Project::current()?;
     let tree = match &project {
         Some(project) => {
             // Make sure dependencies are synced if in a project
-            sync_dependencies_if_locked(project, MultiProgress::new_arc(), &config).await?;
+            sync_dependencies_if_locked(
+                project,
+                MultiProgress::new_arc(),
+                &config,
+                &mut Summary::default(),
+            )
+            .await?;
             project.tree(&config)?
         }
         None => {
@@ -90,3 +109,31 @@ pub async fn outdated(outdated_data: Outdated, config: Config) -> Result<()> {
 
     Ok(())
 }
+
+async fn outdated_lockfile(outdated_data: Outdated, config: Config) -> Result<()> {
+    let updates = OutdatedLockfile::new(&config)
+        .progress(MultiProgress::new_arc())
+        .check()
+        .await?;
+
+    if outdated_data.porcelain {
+        println!("{}", serde_json::to_string(&updates)?);
+    } else {
+        for update in &updates {
+            println!(
+                "{} ({}): {} => {} [{}]",
+                update.name(),
+                update.dependencies(),
+                update.locked(),
+                update.update(),
+                update.constraint(),
+            );
+        }
+    }
+
+    if !updates.is_empty() {
+        std::process::exit(diagnostic::UPDATES_AVAILABLE.into());
+    }
+
+    Ok(())
+}
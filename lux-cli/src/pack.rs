@@ -1,14 +1,14 @@
 use std::{path::PathBuf, str::FromStr};
 
 use crate::build;
-use clap::Args;
+use clap::{Args, ValueEnum};
 use eyre::{eyre, Result};
 use lux_lib::{
     build::{Build, BuildBehaviour},
     config::{Config, LuaVersion},
     lua_installation::LuaInstallation,
     lua_rockspec::RemoteLuaRockspec,
-    operations::{self, Install, PackageInstallSpec},
+    operations::{self, ImageReference, Install, PackImage, PackageInstallSpec},
     package::PackageReq,
     progress::MultiProgress,
     project::Project,
@@ -43,6 +43,16 @@ impl FromStr for PackageOrRockspec {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum PackFormat {
+    /// A binary rock, which can be installed with lux or luarocks.
+    #[default]
+    Rock,
+    /// An OCI container image archive, which can be loaded with `docker load`.{n}
+    /// Only supported for projects.
+    Oci,
+}
+
 #[derive(Args)]
 pub struct Pack {
     /// Path to a RockSpec or a package query for a package to pack.{n}
@@ -61,12 +71,55 @@ pub struct Pack {
     /// Lua rockspec.{n}
     #[clap(value_parser)]
     package_or_rockspec: Option<PackageOrRockspec>,
+
+    /// The format to pack to.
+    #[arg(long, value_enum, default_value_t)]
+    format: PackFormat,
+
+    /// The image to use as a base for `--format oci`, e.g. `debian:stable-slim`.{n}
+    /// If not set, the image will only contain the Lua interpreter{n}
+    /// and the project tree, so the interpreter must be statically linked.{n}
+    /// The entrypoint is derived from the `[run]` section of the lux.toml.
+    #[arg(long)]
+    base_image: Option<ImageReference>,
+
+    /// Write an in-toto/SLSA provenance statement for the packed rock{n}
+    /// to `<rock>.intoto.json`, alongside the rock.{n}
+    /// Not supported for `--format oci`.
+    #[arg(long)]
+    attest: bool,
 }
 
 pub async fn pack(args: Pack, config: Config) -> Result<()> {
     let lua_version = LuaVersion::from(&config)?.clone();
     let dest_dir = std::env::current_dir()?;
     let progress = MultiProgress::new_arc();
+    if let PackFormat::Oci = args.format {
+        if args.package_or_rockspec.is_some() {
+            return Err(eyre!("`--format oci` is only supported for projects."));
+        }
+        if args.attest {
+            return Err(eyre!("`--attest` is not supported for `--format oci`."));
+        }
+        let project = Project::current_or_err()?;
+        let package = build::build(build::Build::default(), config.clone())
+            .await?
+            .expect("exptected a `LocalPackage`");
+        let lua = LuaInstallation::new(
+            &project.lua_version(&config)?,
+            &config,
+            &progress.map(|progress| progress.new_bar()),
+        )
+        .await?;
+        let image_path = PackImage::new(dest_dir, &project, package)
+            .config(&config)
+            .lua(&lua)
+            .maybe_base_image(args.base_image)
+            .pack()
+            .await?;
+        print!("image archive created at {}", image_path.display());
+        return Ok(());
+    }
     let result: Result<PathBuf> = match args.package_or_rockspec {
         Some(PackageOrRockspec::Package(package_req)) => {
             let user_tree = config.user_tree(lua_version.clone())?;
@@ -82,28 +135,34 @@ pub async fn pack(args: Pack, config: Config) -> Result<()> {
                                 .build(),
                         )
                         .tree(tree.clone())
+                        .check_conflicts(false)
                         .progress(progress)
                         .install()
                         .await?;
                     let package = packages.first().unwrap();
                     let rock_path = operations::Pack::new(dest_dir, tree, package.clone())
+                        .attest(args.attest)
                         .pack()
                         .await?;
                     Ok(rock_path)
                 }
                 lux_lib::tree::RockMatches::Single(local_package_id) => {
+                    let user_tree = user_tree.tree_for(&local_package_id)?.clone();
                     let lockfile = user_tree.lockfile()?;
                     let package = lockfile.get(&local_package_id).unwrap();
                     let rock_path = operations::Pack::new(dest_dir, user_tree, package.clone())
+                        .attest(args.attest)
                         .pack()
                         .await?;
                     Ok(rock_path)
                 }
                 lux_lib::tree::RockMatches::Many(vec) => {
                     let local_package_id = vec.first().unwrap();
+                    let user_tree = user_tree.tree_for(local_package_id)?.clone();
                     let lockfile = user_tree.lockfile()?;
                     let package = lockfile.get(local_package_id).unwrap();
                     let rock_path = operations::Pack::new(dest_dir, user_tree, package.clone())
+                        .attest(args.attest)
                         .pack()
                         .await?;
                     Ok(rock_path)
@@ -143,6 +202,7 @@ pub async fn pack(args: Pack, config: Config) -> Result<()> {
                 .build()
                 .await?;
             let rock_path = operations::Pack::new(dest_dir, tree, package)
+                .attest(args.attest)
                 .pack()
                 .await?;
             Ok(rock_path)
@@ -160,6 +220,7 @@ pub async fn pack(args: Pack, config: Config) -> Result<()> {
                 .expect("exptected a `LocalPackage`");
             let tree = project.tree(&config)?;
             let rock_path = operations::Pack::new(dest_dir, tree, package)
+                .attest(args.attest)
                 .pack()
                 .await?;
             Ok(rock_path)
@@ -134,7 +134,7 @@ To suppress this warning, set the `--no-loader` option.
     Ok(())
 }
 
-fn mk_package_path(paths: &Paths, prepend: bool) -> PackagePath {
+pub(crate) fn mk_package_path(paths: &Paths, prepend: bool) -> PackagePath {
     if prepend {
         paths.package_path_prepended()
     } else {
@@ -142,7 +142,7 @@ fn mk_package_path(paths: &Paths, prepend: bool) -> PackagePath {
     }
 }
 
-fn mk_package_cpath(paths: &Paths, prepend: bool) -> PackagePath {
+pub(crate) fn mk_package_cpath(paths: &Paths, prepend: bool) -> PackagePath {
     if prepend {
         paths.package_cpath_prepended()
     } else {
@@ -150,7 +150,7 @@ fn mk_package_cpath(paths: &Paths, prepend: bool) -> PackagePath {
     }
 }
 
-fn mk_bin_path(paths: &Paths, prepend: bool) -> Result<BinPath> {
+pub(crate) fn mk_bin_path(paths: &Paths, prepend: bool) -> Result<BinPath> {
     let mut result = if prepend {
         BinPath::from_env()
     } else {
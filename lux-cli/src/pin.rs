@@ -1,16 +1,18 @@
+use std::sync::Arc;
+
 use clap::Args;
 use eyre::eyre;
 use eyre::Context;
 use eyre::Result;
 use itertools::Itertools;
 use lux_lib::config::{Config, LuaVersion};
-use lux_lib::lockfile::PinnedState;
+use lux_lib::lockfile::{LocalPackageLockType, PinnedState};
 use lux_lib::operations;
 use lux_lib::package::PackageName;
 use lux_lib::package::PackageReq;
-use lux_lib::progress::MultiProgress;
+use lux_lib::progress::{MultiProgress, Progress};
 use lux_lib::project::Project;
-use lux_lib::rockspec::lua_dependency;
+use lux_lib::rockspec::lua_dependency::LuaDependencyType;
 use lux_lib::tree::RockMatches;
 
 #[derive(Args)]
@@ -18,6 +20,7 @@ pub struct ChangePin {
     /// Installed package or dependency to pin.
     /// If pinning a dependency in a project, this should
     /// be the package name.
+    /// Transitive dependencies are pinned in the project lockfile.
     package: Vec<PackageReq>,
 
     /// Pin a development dependency.
@@ -45,44 +48,20 @@ pub async fn set_pinned_state(data: ChangePin, config: Config, pin: PinnedState)
                 .map(|pkg| pkg.name())
                 .cloned()
                 .collect_vec();
-            if !packages.is_empty() {
-                project
-                    .set_pinned_state(lua_dependency::LuaDependencyType::Regular(packages), pin)
-                    .await?;
-                operations::Sync::new(&project, &config)
-                    .progress(progress.clone())
-                    .sync_dependencies()
-                    .await
-                    .wrap_err("syncing dependencies with the project lockfile failed.")?;
-            }
-            let build_packages = data.build.unwrap_or_default();
-            if !build_packages.is_empty() {
-                project
-                    .set_pinned_state(
-                        lua_dependency::LuaDependencyType::Build(build_packages),
-                        pin,
-                    )
-                    .await?;
-                operations::Sync::new(&project, &config)
-                    .progress(progress.clone())
-                    .sync_build_dependencies()
-                    .await
-                    .wrap_err("syncing build dependencies with the project lockfile failed.")?;
-            }
-            let test_packages = data.test.unwrap_or_default();
-            if !test_packages.is_empty() {
-                project
-                    .set_pinned_state(lua_dependency::LuaDependencyType::Test(test_packages), pin)
-                    .await?;
-                operations::Sync::new(&project, &config)
-                    .progress(progress.clone())
-                    .sync_test_dependencies()
-                    .await
-                    .wrap_err("syncing test dependencies with the project lockfile failed.")?;
+            for (deps, packages) in [
+                (LocalPackageLockType::Regular, packages),
+                (LocalPackageLockType::Build, data.build.unwrap_or_default()),
+                (LocalPackageLockType::Test, data.test.unwrap_or_default()),
+            ] {
+                if !packages.is_empty() {
+                    set_project_pinned_state(&mut project, &config, deps, packages, pin, &progress)
+                        .await?;
+                }
             }
         }
         None => {
             let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
+            let _lock = tree.lock(&config, &Progress::NoProgress).await?;
 
             for package in &data.package {
                 match tree.match_rocks_and(package, |package| pin != package.pinned())? {
@@ -99,3 +78,56 @@ pub async fn set_pinned_state(data: ChangePin, config: Config, pin: PinnedState)
     }
     Ok(())
 }
+
+/// Direct dependencies are pinned in the lux.toml.
+/// Transitive dependencies are pinned in the project lockfile.
+async fn set_project_pinned_state(
+    project: &mut Project,
+    config: &Config,
+    deps: LocalPackageLockType,
+    packages: Vec<PackageName>,
+    pin: PinnedState,
+    progress: &Arc<Progress<MultiProgress>>,
+) -> Result<()> {
+    let (direct, transitive): (Vec<_>, Vec<_>) = packages
+        .into_iter()
+        .partition(|name| project.toml().declares_dependency(&deps, name));
+
+    if !direct.is_empty() {
+        let dependencies = match deps {
+            LocalPackageLockType::Regular => LuaDependencyType::Regular(direct),
+            LocalPackageLockType::Build => LuaDependencyType::Build(direct),
+            LocalPackageLockType::Test => LuaDependencyType::Test(direct),
+        };
+        project.set_pinned_state(dependencies, pin).await?;
+        let sync = operations::Sync::new(project, config).progress(progress.clone());
+        match deps {
+            LocalPackageLockType::Regular => sync
+                .sync_dependencies()
+                .await
+                .wrap_err("syncing dependencies with the project lockfile failed.")?,
+            LocalPackageLockType::Build => sync
+                .sync_build_dependencies()
+                .await
+                .wrap_err("syncing build dependencies with the project lockfile failed.")?,
+            LocalPackageLockType::Test => sync
+                .sync_test_dependencies()
+                .await
+                .wrap_err("syncing test dependencies with the project lockfile failed.")?,
+        };
+    }
+
+    if !transitive.is_empty() {
+        let tree = match deps {
+            LocalPackageLockType::Regular => project.tree(config)?,
+            LocalPackageLockType::Build => project.build_tree(config)?,
+            LocalPackageLockType::Test => project.test_tree(config)?,
+        };
+        let _lock = tree.lock(config, &Progress::NoProgress).await?;
+        for package in &transitive {
+            operations::set_project_lockfile_pinned_state(project, &tree, package, &deps, pin)?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use clap::Args;
 use eyre::Result;
-use lux_lib::project::Project;
+use lux_lib::project::{project_toml::project_toml_schema, Project};
 
 use crate::utils::file_tree::term_tree_from_paths;
 
@@ -40,3 +40,9 @@ pub fn debug_project(args: DebugProject) -> Result<()> {
 
     Ok(())
 }
+
+/// Print the JSON schema of the `lux.toml` format.
+pub fn debug_schema() -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&project_toml_schema())?);
+    Ok(())
+}
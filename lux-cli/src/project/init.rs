@@ -0,0 +1,314 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use eyre::{eyre, Result};
+use inquire::{Confirm, Select};
+use itertools::Itertools;
+use lux_lib::{
+    lua_rockspec::{BuildBackendSpec, RemoteLuaRockspec},
+    package::PackageName,
+    project::PROJECT_TOML,
+    rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
+};
+use path_absolutize::Absolutize as _;
+
+/// The configuration file of the busted test runner.
+const BUSTED_CONFIG: &str = ".busted";
+
+#[derive(Args)]
+pub struct InitProject {
+    /// The project's name.{n}
+    /// Defaults to the name of the `origin` git remote's repository,{n}
+    /// or to the name of the current directory.
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Convert an existing rockspec without asking for confirmation.
+    #[arg(long, short = 'y')]
+    yes: bool,
+}
+
+/// Turn the current directory into a lux project.
+pub async fn init_project(args: InitProject) -> Result<()> {
+    let dir = std::env::current_dir()?;
+    let toml_path = dir.join(PROJECT_TOML);
+    if toml_path.exists() {
+        return Err(eyre!(
+            "{} already exists - this directory is already a lux project.",
+            toml_path.display()
+        ));
+    }
+
+    let rockspec = match select_rockspec(&dir, args.yes)? {
+        Some(path) => {
+            if args.yes
+                || Confirm::new(&format!(
+                    "Found {}. Convert it to a {PROJECT_TOML}?",
+                    path.display()
+                ))
+                .with_default(true)
+                .prompt()?
+            {
+                Some(RemoteLuaRockspec::new(&std::fs::read_to_string(&path)?)?)
+            } else {
+                None
+            }
+        }
+        None => None,
+    };
+
+    let name = match (args.name, &rockspec) {
+        (Some(name), _) => name,
+        (None, Some(rockspec)) => rockspec.package().to_string(),
+        (None, None) => infer_package_name(&dir)?,
+    };
+    let uses_busted = dir.join(BUSTED_CONFIG).is_file();
+
+    let content = match &rockspec {
+        Some(rockspec) => {
+            let content = rockspec_to_project_toml(rockspec, &name, uses_busted);
+            if !matches!(
+                rockspec.build().current_platform().build_backend,
+                None | Some(BuildBackendSpec::Builtin(_))
+            ) {
+                eprintln!(
+                    "⚠️ The rockspec's `build` table can't be converted automatically. Please port it to the `[build]` table of {PROJECT_TOML}."
+                );
+            }
+            content
+        }
+        None => new_project_toml(&name, uses_busted),
+    };
+    std::fs::write(&toml_path, content)?;
+
+    println!("Wrote {}", toml_path.display());
+    Ok(())
+}
+
+/// Find the rockspecs in the project directory, or in its `rockspecs` directory,
+/// and select one to convert.
+fn select_rockspec(dir: &Path, yes: bool) -> Result<Option<PathBuf>> {
+    let rockspecs = [dir.to_path_buf(), dir.join("rockspecs")]
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "rockspec"))
+        .sorted()
+        .collect_vec();
+    match rockspecs.len() {
+        0 => Ok(None),
+        1 => Ok(rockspecs.into_iter().next()),
+        // Without a prompt, prefer the latest release.
+        _ if yes => Ok(rockspecs
+            .into_iter()
+            .filter_map(|path| {
+                let rockspec =
+                    RemoteLuaRockspec::new(&std::fs::read_to_string(&path).ok()?).ok()?;
+                Some((rockspec.version().clone(), path))
+            })
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, path)| path)),
+        _ => {
+            let options = rockspecs
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect_vec();
+            let selected = Select::new("Which rockspec do you want to convert?", options)
+                .prompt_skippable()?;
+            Ok(selected.map(PathBuf::from))
+        }
+    }
+}
+
+/// Infer the package name from the `origin` git remote, falling back to the directory name.
+fn infer_package_name(dir: &Path) -> Result<String> {
+    let remote_name = git2::Repository::open(dir).ok().and_then(|repo| {
+        let remote = repo.find_remote("origin").ok()?;
+        let url = git_url_parse::GitUrl::parse(remote.url()?).ok()?;
+        Some(url.name)
+    });
+    match remote_name {
+        Some(name) if !name.is_empty() => Ok(name),
+        _ => Ok(dir
+            .absolutize()?
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()),
+    }
+}
+
+fn new_project_toml(name: &str, uses_busted: bool) -> String {
+    let mut content = format!(
+        r#"package = {name}
+version = "0.1.0"
+lua = ">=5.1"
+
+[dependencies]
+# Add your dependencies here
+# `busted = ">=2.0"`
+"#,
+        name = toml_string(&PackageName::new(name.to_string()).to_string()),
+    );
+    push_test_backend(&mut content, uses_busted);
+    content.push_str("\n[build]\ntype = \"builtin\"\n");
+    content
+}
+
+fn rockspec_to_project_toml(rockspec: &RemoteLuaRockspec, name: &str, uses_busted: bool) -> String {
+    let version = rockspec.version().to_string();
+    // lux.toml versions don't have a specrev.
+    let version = version
+        .rsplit_once('-')
+        .map_or(version.as_str(), |(modrev, _)| modrev);
+    let lua = if rockspec.lua().is_any() {
+        ">=5.1".to_string()
+    } else {
+        rockspec.lua().to_string()
+    };
+    let mut content = format!(
+        "package = {}\nversion = {}\nlua = {}\n",
+        toml_string(&PackageName::new(name.to_string()).to_string()),
+        toml_string(version),
+        toml_string(&lua),
+    );
+
+    let description = rockspec.description();
+    let description_fields = [
+        ("summary", description.summary.clone()),
+        ("detailed", description.detailed.clone()),
+        ("license", description.license.clone()),
+        (
+            "homepage",
+            description.homepage.as_ref().map(|url| url.to_string()),
+        ),
+        ("issues_url", description.issues_url.clone()),
+        ("maintainer", description.maintainer.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some(format!("{key} = {}", toml_string(&value?))))
+    .chain((!description.labels.is_empty()).then(|| {
+        format!(
+            "labels = [ {} ]",
+            description
+                .labels
+                .iter()
+                .map(|label| toml_string(label))
+                .join(", ")
+        )
+    }))
+    .collect_vec();
+    if !description_fields.is_empty() {
+        content.push_str(&format!(
+            "\n[description]\n{}\n",
+            description_fields.join("\n")
+        ));
+    }
+
+    for (table, dependencies) in [
+        ("dependencies", rockspec.dependencies()),
+        ("build_dependencies", rockspec.build_dependencies()),
+        ("test_dependencies", rockspec.test_dependencies()),
+    ] {
+        let dependencies = dependencies
+            .current_platform()
+            .iter()
+            .filter(|dep| dep.name().to_string() != "lua")
+            .map(dependency_entry)
+            .collect_vec();
+        if !dependencies.is_empty() {
+            content.push_str(&format!("\n[{table}]\n{}\n", dependencies.join("\n")));
+        }
+    }
+
+    push_test_backend(&mut content, uses_busted);
+    if matches!(
+        rockspec.build().current_platform().build_backend,
+        None | Some(BuildBackendSpec::Builtin(_))
+    ) {
+        content.push_str("\n[build]\ntype = \"builtin\"\n");
+    }
+    content
+}
+
+fn dependency_entry(dep: &LuaDependencySpec) -> String {
+    let version_req = if dep.version_req().is_any() {
+        ">=0".to_string()
+    } else {
+        dep.version_req().to_string()
+    };
+    format!(
+        "{} = {}",
+        toml_key(&dep.name().to_string()),
+        toml_string(&version_req)
+    )
+}
+
+fn push_test_backend(content: &mut String, uses_busted: bool) {
+    if uses_busted {
+        content.push_str("\n[test]\ntype = \"busted\"\n");
+    }
+}
+
+fn toml_string(str: &str) -> String {
+    toml::Value::String(str.to_string()).to_string()
+}
+
+/// Quote a key if it isn't a valid bare key, e.g. because it contains a `.`.
+fn toml_key(key: &str) -> String {
+    if key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        key.to_string()
+    } else {
+        toml_string(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use lux_lib::project::Project;
+
+    #[test]
+    fn convert_rockspec_to_project_toml() {
+        let rockspec = RemoteLuaRockspec::new(
+            r#"
+            rockspec_format = "3.0"
+            package = "foo"
+            version = "1.2.0-1"
+            source = { url = "https://example.com/foo-1.2.0.tar.gz" }
+            description = { summary = "A \"foo\" package", license = "MIT", labels = { "web" } }
+            dependencies = { "lua >= 5.1", "bar >= 2.0", "lua-cjson" }
+            test_dependencies = { "busted" }
+            build = { type = "builtin" }
+            "#,
+        )
+        .unwrap();
+        let content = rockspec_to_project_toml(&rockspec, "foo", true);
+
+        let dir = assert_fs::TempDir::new().unwrap();
+        dir.child(PROJECT_TOML).write_str(&content).unwrap();
+        let project = Project::from_exact(dir.path()).unwrap().unwrap();
+        let local = project.toml().into_local().unwrap();
+        assert_eq!(local.package().to_string(), "foo");
+        assert_eq!(local.version().to_string(), "1.2.0-1");
+        assert_eq!(
+            local.description().summary.as_deref(),
+            Some("A \"foo\" package")
+        );
+        let dependencies = local
+            .dependencies()
+            .current_platform()
+            .iter()
+            .map(|dep| dep.name().to_string())
+            .sorted()
+            .collect_vec();
+        assert_eq!(dependencies, vec!["bar", "lua-cjson"]);
+        assert!(content.contains("[test]\ntype = \"busted\""));
+    }
+}
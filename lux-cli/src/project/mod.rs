@@ -1,5 +1,9 @@
 mod debug;
+mod init;
 mod new;
+mod unknown_fields;
 
 pub use debug::*;
+pub use init::*;
 pub use new::*;
+pub use unknown_fields::*;
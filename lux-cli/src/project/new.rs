@@ -1,4 +1,9 @@
-use std::{error::Error, fmt::Display, path::PathBuf, str::FromStr};
+use std::{
+    error::Error,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use clap::Args;
 use eyre::{eyre, Result};
@@ -13,8 +18,9 @@ use spinners::{Spinner, Spinners};
 
 use crate::utils::github_metadata::{self, RepoMetadata};
 use lux_lib::{
+    config::LuaVersion,
     package::PackageReq,
-    project::{Project, PROJECT_TOML},
+    project::{Project, LUA_VERSION_FILE, PROJECT_TOML},
 };
 
 // TODO:
@@ -38,6 +44,16 @@ impl Display for SourceDirType {
     }
 }
 
+/// The kind of project to create.
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+enum ProjectTemplate {
+    /// A pure Lua project.
+    #[default]
+    Lua,
+    /// A Lua module written in C, with compatibility shims for Lua 5.1 to 5.4.
+    CModule,
+}
+
 #[derive(Args)]
 pub struct NewProject {
     /// The directory of the project.
@@ -70,6 +86,15 @@ pub struct NewProject {
 
     #[arg(long)]
     main: Option<SourceDirType>,
+
+    /// The kind of project to create.
+    #[arg(long, value_enum, default_value_t)]
+    template: ProjectTemplate,
+
+    /// Also write a `.lua-version` file with the latest Lua version the project supports,{n}
+    /// so that other Lua version managers use the same Lua version as lux.
+    #[arg(long)]
+    lua_version_file: bool,
 }
 
 struct NewProjectValidated {
@@ -81,6 +106,8 @@ struct NewProjectValidated {
     lua_versions: PackageReq,
     main: SourceDirType,
     license: Option<LicenseId>,
+    template: ProjectTemplate,
+    lua_version_file: bool,
 }
 
 fn clap_parse_license(s: &str) -> std::result::Result<LicenseId, String> {
@@ -161,6 +188,8 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
             name: Some(name),
             license,
             target,
+            template,
+            lua_version_file,
         } => Ok::<_, eyre::Report>(NewProjectValidated {
             description,
             labels,
@@ -170,6 +199,8 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
             maintainer,
             name,
             target,
+            template,
+            lua_version_file,
         }),
 
         NewProject {
@@ -181,6 +212,8 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
             maintainer,
             name,
             target,
+            template,
+            lua_version_file,
         } => {
             let mut spinner = Spinner::new(
                 Spinners::Dots,
@@ -298,6 +331,8 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
                 lua_versions,
                 maintainer,
                 main: main.unwrap_or(SourceDirType::Src),
+                template,
+                lua_version_file,
             })
         }
     }?;
@@ -306,6 +341,56 @@ pub async fn write_project_rockspec(cli_flags: NewProject) -> Result<()> {
 
     let rocks_path = validated.target.join(PROJECT_TOML);
 
+    let module_name = c_module_name(&validated.name);
+    let template_sections = match validated.template {
+        ProjectTemplate::Lua => format!(
+            r#"
+[dependencies]
+# Add your dependencies here
+# `busted = ">=2.0"`
+
+[run]
+args = [ "{main}/main.lua" ]
+
+[build]
+type = "builtin"
+"#,
+            main = validated.main,
+        ),
+        ProjectTemplate::CModule => format!(
+            r#"
+[dependencies]
+# Add your dependencies here
+# `busted = ">=2.0"`
+
+[test]
+type = "busted"
+
+[build]
+type = "builtin"
+
+[build.modules.{module_name}]
+sources = [ "csrc/{module_name}.c" ]
+
+# Each field of `[build]` can be overridden for a platform in `[build.platforms.<platform>]`.
+# Overrides for `unix` also apply to `linux`, `macosx`, etc.,
+# unless they are overridden for the more specific platform.
+[build.platforms.unix.modules.{module_name}]
+defines = [ "{prefix}_PLATFORM_UNIX" ]
+
+[build.platforms.linux.modules.{module_name}]
+defines = [ "{prefix}_PLATFORM_UNIX", "{prefix}_PLATFORM_LINUX" ]
+
+[build.platforms.macosx.modules.{module_name}]
+defines = [ "{prefix}_PLATFORM_UNIX", "{prefix}_PLATFORM_MACOSX" ]
+
+[build.platforms.windows.modules.{module_name}]
+defines = [ "{prefix}_PLATFORM_WINDOWS" ]
+"#,
+            prefix = module_name.to_uppercase(),
+        ),
+    };
+
     std::fs::write(
         &rocks_path,
         format!(
@@ -319,16 +404,7 @@ summary = "{summary}"
 maintainer = "{maintainer}"
 labels = [ {labels} ]
 {license}
-
-[dependencies]
-# Add your dependencies here
-# `busted = ">=2.0"`
-
-[run]
-args = [ "{main}/main.lua" ]
-
-[build]
-type = "builtin"
+{template_sections}
     "#,
             package_name = validated.name,
             summary = validated.description,
@@ -343,23 +419,201 @@ type = "builtin"
                 .map(|label| "\"".to_string() + &label + "\"")
                 .join(", "),
             lua_version_req = validated.lua_versions.version_req(),
-            main = validated.main,
         )
         .trim(),
     )?;
 
-    let main_dir = validated.target.join(validated.main.to_string());
-    if main_dir.exists() {
+    match validated.template {
+        ProjectTemplate::Lua => {
+            let main_dir = validated.target.join(validated.main.to_string());
+            if main_dir.exists() {
+                eprintln!(
+                    "Directory `{}/` already exists - we won't make any changes to it.",
+                    main_dir.display()
+                );
+            } else {
+                std::fs::create_dir(&main_dir)?;
+                std::fs::write(main_dir.join("main.lua"), r#"print("Hello world!")"#)?;
+            }
+        }
+        ProjectTemplate::CModule => write_c_module_sources(&validated.target, &module_name)?,
+    }
+
+    if validated.lua_version_file {
+        let lua_version_req = validated.lua_versions.version_req();
+        match [
+            LuaVersion::Lua54,
+            LuaVersion::Lua53,
+            LuaVersion::Lua52,
+            LuaVersion::Lua51,
+        ]
+        .into_iter()
+        .find(|lua_version| lua_version_req.matches(&lua_version.as_version()))
+        {
+            Some(lua_version) => std::fs::write(
+                validated.target.join(LUA_VERSION_FILE),
+                format!("{lua_version}\n"),
+            )?,
+            None => eprintln!(
+                "⚠️ WARNING: No Lua version satisfies '{lua_version_req}' - not writing a {LUA_VERSION_FILE} file."
+            ),
+        }
+    }
+
+    println!("All done!");
+
+    Ok(())
+}
+
+/// The name of the Lua module of a C module project,
+/// which must be a valid C identifier, as it is part of the `luaopen_` function's name.
+fn c_module_name(package_name: &str) -> String {
+    let name = package_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+/// Write the C sources and the busted spec of a C module project,
+/// skipping the directories that already exist.
+fn write_c_module_sources(target: &Path, module_name: &str) -> Result<()> {
+    let prefix = module_name.to_uppercase();
+    let csrc_dir = target.join("csrc");
+    if csrc_dir.exists() {
         eprintln!(
             "Directory `{}/` already exists - we won't make any changes to it.",
-            main_dir.display()
+            csrc_dir.display()
         );
     } else {
-        std::fs::create_dir(&main_dir)?;
-        std::fs::write(main_dir.join("main.lua"), r#"print("Hello world!")"#)?;
+        std::fs::create_dir(&csrc_dir)?;
+        std::fs::write(
+            csrc_dir.join("compat.h"),
+            format!(
+                r#"/* Shims for the parts of the Lua 5.2+ C API that Lua 5.1 and LuaJIT lack. */
+#ifndef {prefix}_COMPAT_H
+#define {prefix}_COMPAT_H
+
+#include "lua.h"
+#include "lauxlib.h"
+
+#if LUA_VERSION_NUM == 501
+
+static void {module_name}_setfuncs(lua_State *L, const luaL_Reg *l, int nup) {{
+  luaL_checkstack(L, nup + 1, "too many upvalues");
+  for (; l->name != NULL; l++) {{
+    int i;
+    lua_pushstring(L, l->name);
+    for (i = 0; i < nup; i++) {{
+      lua_pushvalue(L, -(nup + 1));
+    }}
+    lua_pushcclosure(L, l->func, nup);
+    lua_settable(L, -(nup + 3));
+  }}
+  lua_pop(L, nup);
+}}
+
+#define luaL_setfuncs {module_name}_setfuncs
+
+#ifndef luaL_newlib
+#define luaL_newlibtable(L, l) lua_createtable(L, 0, sizeof(l) / sizeof((l)[0]) - 1)
+#define luaL_newlib(L, l) (luaL_newlibtable(L, l), luaL_setfuncs(L, l, 0))
+#endif
+
+#define lua_rawlen lua_objlen
+
+#endif
+
+#if defined(_WIN32)
+#define {prefix}_EXPORT __declspec(dllexport)
+#else
+#define {prefix}_EXPORT
+#endif
+
+#endif
+"#
+            ),
+        )?;
+        std::fs::write(
+            csrc_dir.join(format!("{module_name}.c")),
+            format!(
+                r#"#include "lua.h"
+#include "lauxlib.h"
+
+#include "compat.h"
+
+/* The platform defines are set by the platform overrides in the lux.toml. */
+#if defined({prefix}_PLATFORM_LINUX)
+#define {prefix}_PLATFORM "linux"
+#elif defined({prefix}_PLATFORM_MACOSX)
+#define {prefix}_PLATFORM "macosx"
+#elif defined({prefix}_PLATFORM_UNIX)
+#define {prefix}_PLATFORM "unix"
+#elif defined({prefix}_PLATFORM_WINDOWS)
+#define {prefix}_PLATFORM "windows"
+#else
+#define {prefix}_PLATFORM "unknown"
+#endif
+
+/* add(a, b): Returns the sum of two numbers. */
+static int {module_name}_add(lua_State *L) {{
+  lua_Number a = luaL_checknumber(L, 1);
+  lua_Number b = luaL_checknumber(L, 2);
+  lua_pushnumber(L, a + b);
+  return 1;
+}}
+
+/* platform(): Returns the platform the module was built for. */
+static int {module_name}_platform(lua_State *L) {{
+  lua_pushstring(L, {prefix}_PLATFORM);
+  return 1;
+}}
+
+static const luaL_Reg {module_name}_functions[] = {{
+  {{"add", {module_name}_add}},
+  {{"platform", {module_name}_platform}},
+  {{NULL, NULL}},
+}};
+
+{prefix}_EXPORT int luaopen_{module_name}(lua_State *L) {{
+  luaL_newlib(L, {module_name}_functions);
+  return 1;
+}}
+"#
+            ),
+        )?;
     }
 
-    println!("All done!");
+    let spec_dir = target.join("spec");
+    if spec_dir.exists() {
+        eprintln!(
+            "Directory `{}/` already exists - we won't make any changes to it.",
+            spec_dir.display()
+        );
+    } else {
+        std::fs::create_dir(&spec_dir)?;
+        std::fs::write(
+            spec_dir.join(format!("{module_name}_spec.lua")),
+            format!(
+                r#"local {module_name} = require("{module_name}")
+
+describe("{module_name}", function()
+  it("adds numbers", function()
+    assert.are.equal(3, {module_name}.add(1, 2))
+  end)
+
+  it("knows the platform it was built for", function()
+    assert.is_string({module_name}.platform())
+  end)
+end)
+"#
+            ),
+        )?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,31 @@
+use eyre::{eyre, Result};
+use itertools::Itertools;
+use lux_lib::{config::Config, project::Project};
+
+/// Warn about unknown fields in the current project's lux.toml, e.g. misspelled ones,
+/// or fail if the config denies them.
+pub fn check_unknown_fields(config: &Config) -> Result<()> {
+    if config.no_project() {
+        return Ok(());
+    }
+    // If the project can't be loaded, the command reports the error.
+    let Some(project) = Project::current().ok().flatten() else {
+        return Ok(());
+    };
+    let toml_path = project.toml_path();
+    let unknown_fields = project
+        .unknown_fields()?
+        .into_iter()
+        .map(|field| match field.location() {
+            Some((line, column)) => format!("{}:{line}:{column}: {field}", toml_path.display()),
+            None => format!("{}: {field}", toml_path.display()),
+        })
+        .collect_vec();
+    if config.deny_unknown_fields() && !unknown_fields.is_empty() {
+        return Err(eyre!("{}", unknown_fields.join("\n")));
+    }
+    for field in unknown_fields {
+        eprintln!("⚠️ WARNING: {field}");
+    }
+    Ok(())
+}
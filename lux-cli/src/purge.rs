@@ -1,28 +1,85 @@
+use clap::Args;
 use eyre::Result;
+use indicatif::HumanBytes;
 use inquire::Confirm;
+use itertools::Itertools;
 use lux_lib::{
     config::{Config, LuaVersion},
-    progress::{MultiProgress, ProgressBar},
+    operations::{self, PurgeScope, PurgedTree},
+    package::PackageReq,
+    progress::MultiProgress,
 };
 
+#[derive(Args)]
+pub struct Purge {
+    /// Only purge the install tree itself.{n}
+    /// Can be combined with `--test-tree` and `--build-tree`.{n}
+    /// If none of them are set, all trees are purged.
+    #[arg(long = "tree")]
+    main_tree: bool,
+
+    /// Only purge the tree that test dependencies are installed in.
+    #[arg(long)]
+    test_tree: bool,
+
+    /// Only purge the tree that build dependencies are installed in.
+    #[arg(long)]
+    build_tree: bool,
+
+    /// Keep these packages, along with their dependencies.
+    #[arg(long, value_delimiter = ',')]
+    except: Vec<PackageReq>,
+
+    /// Do not ask for confirmation.
+    #[arg(long, short = 'y')]
+    yes: bool,
+}
+
 /// Purge the user tree
-pub async fn purge(config: Config) -> Result<()> {
+pub async fn purge(args: Purge, config: Config) -> Result<()> {
     let tree = config.user_tree(LuaVersion::from(&config)?.clone())?;
+    let scopes = [
+        (args.main_tree, PurgeScope::Tree),
+        (args.test_tree, PurgeScope::TestTree),
+        (args.build_tree, PurgeScope::BuildTree),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, scope)| enabled.then_some(scope))
+    .collect_vec();
+    let purge = || {
+        operations::Purge::new(&tree, &config)
+            .scopes(scopes.clone())
+            .except(args.except.clone())
+    };
 
-    let len = tree.list()?.len();
+    let plan = purge().dry_run(true).purge().await?;
+    let len: usize = plan.iter().map(|purged| purged.packages.len()).sum();
+    if len == 0 && reclaimed(&plan) == 0 {
+        println!("Nothing to purge.");
+        return Ok(());
+    }
+    for purged in &plan {
+        println!(
+            "{} ({}): {} rocks, {}",
+            purged.scope,
+            purged.root.display(),
+            purged.packages.len(),
+            HumanBytes(purged.reclaimed)
+        );
+    }
 
-    if Confirm::new(&format!("Are you sure you want to purge all {len} rocks?"))
-        .with_default(false)
-        .prompt()?
+    if args.yes
+        || Confirm::new(&format!("Are you sure you want to purge {len} rocks?"))
+            .with_default(false)
+            .prompt()?
     {
-        let root_dir = tree.root();
-
-        let _spinner = MultiProgress::new().add(ProgressBar::from(format!(
-            "🗑️ Purging {}",
-            root_dir.display()
-        )));
-        std::fs::remove_dir_all(tree.root())?;
+        let purged = purge().progress(MultiProgress::new_arc()).purge().await?;
+        println!("🗑️ Reclaimed {}", HumanBytes(reclaimed(&purged)));
     }
 
     Ok(())
 }
+
+fn reclaimed(purged: &[PurgedTree]) -> u64 {
+    purged.iter().map(|purged| purged.reclaimed).sum()
+}
@@ -9,6 +9,7 @@ use crate::utils::project::{
     sync_build_dependencies_if_locked, sync_dependencies_if_locked,
     sync_test_dependencies_if_locked,
 };
+use crate::utils::summary::Summary;
 
 #[derive(Args)]
 pub struct Remove {
@@ -28,12 +29,13 @@ pub struct Remove {
 pub async fn remove(data: Remove, config: Config) -> Result<()> {
     let mut project = Project::current()?.ok_or_eyre("No project found")?;
     let progress = MultiProgress::new_arc();
+    let mut summary = Summary::default();
 
     if !data.package.is_empty() {
         project
             .remove(lua_dependency::DependencyType::Regular(data.package))
             .await?;
-        sync_dependencies_if_locked(&project, progress.clone(), &config).await?;
+        sync_dependencies_if_locked(&project, progress.clone(), &config, &mut summary).await?;
     }
 
     let build_packages = data.build.unwrap_or_default();
@@ -41,7 +43,8 @@ pub async fn remove(data: Remove, config: Config) -> Result<()> {
         project
             .remove(lua_dependency::DependencyType::Build(build_packages))
             .await?;
-        sync_build_dependencies_if_locked(&project, progress.clone(), &config).await?;
+        sync_build_dependencies_if_locked(&project, progress.clone(), &config, &mut summary)
+            .await?;
     }
 
     let test_packages = data.test.unwrap_or_default();
@@ -49,8 +52,10 @@ pub async fn remove(data: Remove, config: Config) -> Result<()> {
         project
             .remove(lua_dependency::DependencyType::Test(test_packages))
             .await?;
-        sync_test_dependencies_if_locked(&project, progress.clone(), &config).await?;
+        sync_test_dependencies_if_locked(&project, progress.clone(), &config, &mut summary).await?;
     }
 
+    summary.print();
+
     Ok(())
 }
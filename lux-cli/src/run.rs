@@ -15,22 +15,36 @@ pub struct Run {
     #[arg(long)]
     no_loader: bool,
 
+    /// Run the project with OpenResty's `resty`, instead of Lua.{n}
+    /// Requires a project that targets LuaJIT.{n}
+    /// OpenResty is looked up from the `OPENRESTY_PREFIX` variable,{n}
+    /// or from the `resty` executable on the PATH.
+    #[arg(long)]
+    resty: bool,
+
     #[clap(flatten)]
     build: Build,
 }
 
 pub async fn run(run_args: Run, config: Config) -> Result<()> {
     let project = Project::current()?.ok_or_eyre("not in a project!")?;
+    let config = run_args.build.config(&project, config)?;
 
     build::build(run_args.build, config.clone()).await?;
 
-    operations::Run::new()
+    let result = operations::Run::new()
         .project(&project)
         .args(&run_args.args)
         .config(&config)
         .disable_loader(run_args.no_loader)
+        .resty(run_args.resty)
         .run()
-        .await?;
+        .await;
+
+    if let Some(exit_code) = result.as_ref().err().and_then(|err| err.exit_code()) {
+        std::process::exit(exit_code);
+    }
+    result?;
 
     Ok(())
 }
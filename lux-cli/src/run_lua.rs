@@ -50,6 +50,10 @@ pub struct RunLua {
 
 pub async fn run_lua(run_lua: RunLua, config: Config) -> Result<()> {
     let project = Project::current()?;
+    let config = match &project {
+        Some(project) => run_lua.build_args.config(project, config)?,
+        None => config,
+    };
     let (lua_version, root, tree, mut welcome_message) = match &project {
         Some(project) => (
             project.toml().lua_version_matches(&config)?,
@@ -14,8 +14,16 @@ use lux_lib::{
 
 #[derive(Args)]
 pub struct Search {
-    lua_package_req: PackageReq,
-    // TODO(vhyrro): Add options.
+    #[arg(required_unless_present = "module")]
+    lua_package_req: Option<PackageReq>,
+    /// Search for packages that provide this Lua module, e.g. `lpeg.re`.{n}
+    /// Only servers whose manifests list the packages' modules are searched.
+    #[arg(long, value_name = "module", conflicts_with_all = ["lua_package_req", "binaries"])]
+    module: Option<String>,
+    /// Only show packages that ship executables.{n}
+    /// Only servers whose manifests list the packages' commands are searched.
+    #[arg(long)]
+    binaries: bool,
     /// Return a machine readable format.
     #[arg(long)]
     porcelain: bool,
@@ -28,11 +36,21 @@ pub async fn search(data: Search, config: Config) -> Result<()> {
 
     let package_db = RemotePackageDB::from_config(&config, &bar).await?;
 
-    bar.map(|b| b.set_message(format!("🔎 Searching for `{}`...", data.lua_package_req)));
-
-    let lua_package_req = data.lua_package_req;
-
-    let result = package_db.search(&lua_package_req);
+    let result = match (data.module, data.lua_package_req) {
+        (Some(module), _) => {
+            bar.map(|b| b.set_message(format!("🔎 Searching for module `{module}`...")));
+            package_db.search_module(&module)
+        }
+        (None, Some(lua_package_req)) => {
+            bar.map(|b| b.set_message(format!("🔎 Searching for `{lua_package_req}`...")));
+            if data.binaries {
+                package_db.search_binaries(&lua_package_req)
+            } else {
+                package_db.search(&lua_package_req)
+            }
+        }
+        (None, None) => unreachable!("clap requires a package or a module"),
+    };
 
     bar.map(|b| b.finish_and_clear());
 
@@ -1,9 +1,13 @@
 use clap::Args;
 use eyre::{eyre, Result, WrapErr};
-use lux_lib::{config::Config, path::Paths};
+use lux_lib::{config::Config, path::Paths, process, project::Project};
+use tempdir::TempDir;
 use which::which;
 
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 use tokio::process::Command;
 
 use super::utils::project::current_project_or_user_tree;
@@ -23,6 +27,119 @@ pub struct Shell {
     /// disabling the Lux loader may result in the wrong modules being loaded.
     #[arg(long)]
     no_loader: bool,
+
+    /// Run a command in the shell's environment and exit,
+    /// instead of starting an interactive shell.
+    #[arg(long)]
+    command: Option<String>,
+}
+
+/// The shells we know how to set up a prompt for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+    Nu,
+    Cmd,
+    Other,
+}
+
+impl ShellKind {
+    fn detect(shell: &Path) -> Self {
+        let name = shell
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match name.as_str() {
+            "bash" => Self::Bash,
+            "zsh" => Self::Zsh,
+            "fish" => Self::Fish,
+            "nu" => Self::Nu,
+            "cmd" => Self::Cmd,
+            _ => Self::Other,
+        }
+    }
+
+    /// Configure `command` to run `cmd` and exit.
+    fn run_command(self, command: &mut Command, cmd: &str) {
+        match self {
+            Self::Cmd => command.arg("/C").arg(cmd),
+            _ => command.arg("-c").arg(cmd),
+        };
+    }
+
+    /// Configure `command` to start an interactive shell with `prefix` prepended to the prompt.
+    /// Shells that need an rc file have it written to `rc_dir`.
+    fn interactive(self, command: &mut Command, prefix: &str, rc_dir: &Path) -> Result<()> {
+        match self {
+            Self::Bash => {
+                let rcfile = rc_dir.join("bashrc");
+                std::fs::write(&rcfile, bash_rc(prefix))?;
+                command.arg("--rcfile").arg(rcfile).arg("-i");
+            }
+            Self::Zsh => {
+                std::fs::write(rc_dir.join(".zshrc"), zsh_rc(prefix))?;
+                let zdotdir = env::var_os("ZDOTDIR")
+                    .or_else(|| env::var_os("HOME"))
+                    .unwrap_or_default();
+                command
+                    .env("LUX_ZDOTDIR", zdotdir)
+                    .env("ZDOTDIR", rc_dir)
+                    .arg("-i");
+            }
+            Self::Fish => {
+                command.arg("--init-command").arg(fish_init(prefix));
+            }
+            Self::Nu => {
+                command.arg("--execute").arg(nu_init(prefix));
+            }
+            Self::Cmd => {
+                let prompt = env::var("PROMPT").unwrap_or("$P$G".into());
+                command.env("PROMPT", format!("{prefix}{prompt}"));
+            }
+            Self::Other => {}
+        }
+        Ok(())
+    }
+}
+
+fn bash_rc(prefix: &str) -> String {
+    format!(
+        r#"[ -f "$HOME/.bashrc" ] && . "$HOME/.bashrc"
+PS1='{prefix}'"$PS1"
+"#,
+    )
+}
+
+fn zsh_rc(prefix: &str) -> String {
+    format!(
+        r#"ZDOTDIR="$LUX_ZDOTDIR"
+unset LUX_ZDOTDIR
+[ -f "$ZDOTDIR/.zshrc" ] && . "$ZDOTDIR/.zshrc"
+PROMPT='{prefix}'"$PROMPT"
+"#,
+    )
+}
+
+fn fish_init(prefix: &str) -> String {
+    format!(
+        r#"functions -q fish_prompt; and functions -c fish_prompt __lux_fish_prompt
+function fish_prompt
+    echo -n '{prefix}'
+    functions -q __lux_fish_prompt; and __lux_fish_prompt
+end"#,
+    )
+}
+
+fn nu_init(prefix: &str) -> String {
+    format!(
+        r#"let lux_prompt = ($env.PROMPT_COMMAND? | default "")
+$env.PROMPT_COMMAND = {{||
+    let prompt = if ($lux_prompt | describe) == "closure" {{ do $lux_prompt }} else {{ $lux_prompt }}
+    '{prefix}' + $prompt
+}}"#,
+    )
 }
 
 pub async fn shell(data: Shell, config: Config) -> Result<()> {
@@ -79,15 +196,58 @@ pub async fn shell(data: Shell, config: Config) -> Result<()> {
         Some(path.init())
     };
 
-    let _ = Command::new(&shell)
+    let project = Project::current()?;
+    let prompt_prefix = match &project {
+        Some(project) => format!("(lx:{}) ", project.toml().package()),
+        None => "(lx) ".to_string(),
+    };
+
+    let shell_kind = ShellKind::detect(&shell);
+    let mut command = Command::new(&shell);
+    command
         .env("PATH", path.path_prepended().joined())
         .env("LUA_PATH", lua_path.joined())
         .env("LUA_CPATH", lua_cpath.joined())
         .env("LUA_INIT", lua_init.unwrap_or_default())
-        .env("LUX_SHELL", "1")
-        .spawn()?
-        .wait()
-        .await?;
+        .env("LUX_SHELL", "1");
+    if let Some(layout) = project
+        .map(|project| project.layout(&config))
+        .transpose()?
+        .flatten()
+    {
+        command.envs(layout.env_vars());
+    }
+
+    // NOTE: The rc files must outlive the shell.
+    let rc_dir = TempDir::new("lux-shell")?;
+    match &data.command {
+        Some(cmd) => shell_kind.run_command(&mut command, cmd),
+        None => shell_kind.interactive(&mut command, &prompt_prefix, rc_dir.path())?,
+    }
+
+    let status = command.spawn()?.wait().await?;
+
+    if data.command.is_some() && !status.success() {
+        drop(rc_dir);
+        std::process::exit(process::exit_code(&status).unwrap_or(1));
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_shell_kind() {
+        assert_eq!(ShellKind::detect(Path::new("/bin/bash")), ShellKind::Bash);
+        assert_eq!(
+            ShellKind::detect(Path::new("/usr/local/bin/fish")),
+            ShellKind::Fish
+        );
+        assert_eq!(ShellKind::detect(Path::new("nu")), ShellKind::Nu);
+        assert_eq!(ShellKind::detect(Path::new("cmd.exe")), ShellKind::Cmd);
+        assert_eq!(ShellKind::detect(Path::new("/bin/dash")), ShellKind::Other);
+    }
+}
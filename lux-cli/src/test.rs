@@ -1,5 +1,8 @@
+use std::time::Duration;
+
 use clap::Args;
-use eyre::{OptionExt, Result};
+use eyre::{eyre, OptionExt, Result};
+use itertools::Itertools;
 use lux_lib::{
     config::Config,
     operations::{self, TestEnv},
@@ -12,28 +15,82 @@ pub struct Test {
     test_args: Option<Vec<String>>,
 
     /// Don't isolate the user environment (keep `HOME` and `XDG` environment variables).
+    /// Overrides `hermetic = true` in the lux.toml's `[test]` section.
     #[arg(long)]
     impure: bool,
 
     /// Ignore the project's lockfile and don't create one.
     #[arg(long)]
     no_lock: bool,
+
+    /// Abort the tests if they take longer than this many seconds.
+    /// Overrides the `timeout` in the lux.toml's `[test]` section.
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// The test suite to run, e.g. `integration` for `[test.integration]` in the lux.toml.
+    /// Defaults to `[test].default_suite`.
+    #[arg(long, conflicts_with = "all_suites")]
+    suite: Option<String>,
+
+    /// Run all test suites, reporting the ones that failed.
+    #[arg(long)]
+    all_suites: bool,
 }
 
 pub async fn test(test: Test, config: Config) -> Result<()> {
     let project = Project::current()?
         .ok_or_eyre("'lux test' must be run in a project root, with a 'project.rockspec'")?;
     let test_args = test.test_args.unwrap_or_default();
-    let test_env = if test.impure {
-        TestEnv::Impure
+    let suites = if test.all_suites {
+        let suites = project
+            .toml()
+            .into_local()?
+            .test_suite_names()
+            .map(|suite| Some(suite.to_string()))
+            .collect_vec();
+        if suites.is_empty() {
+            return Err(eyre!(
+                "no test suites found. Add a `[test.<suite>]` section to the lux.toml."
+            ));
+        }
+        suites
     } else {
-        TestEnv::Pure
+        vec![test.suite]
     };
-    operations::Test::new(project, &config)
-        .args(test_args)
-        .env(test_env)
-        .no_lock(test.no_lock)
-        .run()
-        .await?;
+    let mut failed_suites = Vec::new();
+    for suite in &suites {
+        if let Some(suite) = suite.as_ref().filter(|_| test.all_suites) {
+            println!("🧪 Running test suite {suite}");
+        }
+        let test_env = if test.impure {
+            TestEnv::Impure
+        } else {
+            TestEnv::Pure
+        };
+        let result = operations::Test::new(project.clone(), &config)
+            .args(test_args.clone())
+            .env(test_env)
+            .no_lock(test.no_lock)
+            .maybe_suite(suite.clone())
+            .maybe_timeout(test.timeout.map(Duration::from_secs))
+            .run()
+            .await;
+        match result {
+            Err(err) if test.all_suites => {
+                eprintln!("{err}");
+                failed_suites.extend(suite.clone());
+            }
+            result => result?,
+        }
+    }
+    if !failed_suites.is_empty() {
+        return Err(eyre!(
+            "{} of {} test suites failed: {}",
+            failed_suites.len(),
+            suites.len(),
+            failed_suites.join(", ")
+        ));
+    }
     Ok(())
 }
@@ -0,0 +1,220 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use eyre::{eyre, Result};
+use indicatif::HumanBytes;
+use itertools::Itertools;
+use lux_lib::config::{tree::NamedTree, Config, LuaVersion};
+use path_absolutize::Absolutize;
+use walkdir::WalkDir;
+
+/// The name under which the default user tree is listed.
+const DEFAULT_TREE_NAME: &str = "default";
+
+const LUA_VERSIONS: [LuaVersion; 6] = [
+    LuaVersion::Lua51,
+    LuaVersion::Lua52,
+    LuaVersion::Lua53,
+    LuaVersion::Lua54,
+    LuaVersion::LuaJIT,
+    LuaVersion::LuaJIT52,
+];
+
+#[derive(clap::Subcommand)]
+pub enum TreeCmd {
+    /// List the default tree and the named trees, with their Lua versions,{n}
+    /// package counts and disk usage.
+    List,
+    /// Create a named tree and add it to the `[trees]` section of the config.
+    Create(Create),
+    /// Remove a named tree from the `[trees]` section of the config.
+    Remove(Remove),
+    /// Show the Lua versions, package counts and disk usage of a tree.
+    Info(Info),
+}
+
+#[derive(Args)]
+pub struct Create {
+    /// The name of the tree.
+    name: String,
+
+    /// The root directory of the tree.{n}
+    /// Defaults to `trees/<name>` in the lux data directory.
+    #[arg(long, value_name = "path")]
+    root: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct Remove {
+    /// The name of the tree.
+    name: String,
+
+    /// Also delete the tree's root directory and all packages installed in it.
+    #[arg(long)]
+    purge: bool,
+}
+
+#[derive(Args)]
+pub struct Info {
+    /// The name of the tree.{n}
+    /// Defaults to the selected tree.
+    name: Option<String>,
+}
+
+pub fn tree(cmd: TreeCmd, config: Config) -> Result<()> {
+    match cmd {
+        TreeCmd::List => list(&config),
+        TreeCmd::Create(create) => {
+            let root = match create.root {
+                Some(root) => root.absolutize()?.to_path_buf(),
+                None => Config::get_default_data_path()?
+                    .join("trees")
+                    .join(&create.name),
+            };
+            if create.name == DEFAULT_TREE_NAME {
+                return Err(eyre!("The name '{DEFAULT_TREE_NAME}' is reserved."));
+            }
+            std::fs::create_dir_all(&root)?;
+            NamedTree { root: root.clone() }.add_to_config_file(&create.name)?;
+            println!("Created tree '{}' at {}", create.name, root.display());
+            Ok(())
+        }
+        TreeCmd::Remove(remove) => {
+            let tree = config
+                .trees()
+                .get(&remove.name)
+                .ok_or_else(|| eyre!("No tree named '{}' found.", remove.name))?;
+            if !NamedTree::remove_from_config_file(&remove.name)? {
+                return Err(eyre!(
+                    "The tree '{}' is not defined in the config file.",
+                    remove.name
+                ));
+            }
+            if remove.purge && tree.root.is_dir() {
+                std::fs::remove_dir_all(&tree.root)?;
+                println!(
+                    "Removed tree '{}' and deleted {}",
+                    remove.name,
+                    tree.root.display()
+                );
+            } else {
+                println!("Removed tree '{}'", remove.name);
+            }
+            Ok(())
+        }
+        TreeCmd::Info(info) => {
+            let name = info
+                .name
+                .or(config.tree_name().cloned())
+                .unwrap_or(DEFAULT_TREE_NAME.into());
+            let root = tree_root(&config, &name)?;
+            let stats = TreeStats::new(&config, &root)?;
+            println!("Name: {name}");
+            println!("Root: {}", root.display());
+            if stats.versions.is_empty() {
+                println!("Lua versions: none");
+            } else {
+                println!("Lua versions:");
+                for (version, packages) in &stats.versions {
+                    println!("  {version}: {packages} packages");
+                }
+            }
+            println!("Disk usage: {}", HumanBytes(stats.size));
+            Ok(())
+        }
+    }
+}
+
+fn list(config: &Config) -> Result<()> {
+    let selected = config
+        .tree_name()
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_TREE_NAME);
+    let names = std::iter::once(DEFAULT_TREE_NAME)
+        .chain(config.trees().keys().map(String::as_str).sorted())
+        .collect_vec();
+    let rows = names
+        .into_iter()
+        .map(|name| {
+            let root = tree_root(config, name)?;
+            let stats = TreeStats::new(config, &root)?;
+            let versions = stats
+                .versions
+                .iter()
+                .map(|(version, _)| version.to_string())
+                .join(", ");
+            let packages: usize = stats.versions.iter().map(|(_, packages)| packages).sum();
+            Ok([
+                if name == selected { "*" } else { " " }.to_string(),
+                name.to_string(),
+                root.display().to_string(),
+                if versions.is_empty() {
+                    "-".into()
+                } else {
+                    versions
+                },
+                packages.to_string(),
+                HumanBytes(stats.size).to_string(),
+            ])
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let header = ["", "NAME", "ROOT", "LUA", "PACKAGES", "SIZE"].map(String::from);
+    let widths = (0..header.len())
+        .map(|col| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[col].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect_vec();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+fn tree_root(config: &Config, name: &str) -> Result<PathBuf> {
+    match config.trees().get(name) {
+        Some(tree) => Ok(tree.root.clone()),
+        None if name == DEFAULT_TREE_NAME => Ok(Config::get_default_data_path()?.join("tree")),
+        None => Err(eyre!("No tree named '{name}' found.")),
+    }
+}
+
+struct TreeStats {
+    /// The Lua versions that have packages installed, with the number of packages.
+    versions: Vec<(LuaVersion, usize)>,
+    /// The disk usage of the tree, in bytes.
+    size: u64,
+}
+
+impl TreeStats {
+    fn new(config: &Config, root: &Path) -> Result<Self> {
+        let versions = LUA_VERSIONS
+            .into_iter()
+            .filter(|version| root.join(version.to_string()).is_dir())
+            .map(|version| {
+                let tree = config
+                    .clone()
+                    .with_tree(root.to_path_buf())
+                    .user_tree(version.clone())?;
+                let packages = tree.list()?.values().map(Vec::len).sum();
+                Ok((version, packages))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let size = WalkDir::new(root)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+        Ok(Self { versions, size })
+    }
+}
@@ -63,6 +63,24 @@ Please specify the exact package to uninstall:
     }
 
     let lockfile = tree.lockfile()?;
+    if let Some(system_tree) = tree.system_tree() {
+        let system_packages = packages
+            .iter()
+            .filter(|pkg_id| lockfile.get(pkg_id).is_none())
+            .filter_map(|pkg_id| system_tree.lockfile().ok()?.get(pkg_id).cloned())
+            .map(|pkg| pkg.name().to_string())
+            .collect_vec();
+        if !system_packages.is_empty() {
+            return Err(eyre!(
+                "
+Cannot uninstall packages from the read-only system tree at {}:
+{:#?}
+",
+                system_tree.root().display(),
+                system_packages,
+            ));
+        }
+    }
     let non_entrypoints = packages
         .iter()
         .filter_map(|pkg_id| {
@@ -153,6 +171,7 @@ Reinstall?
             operations::Install::new(&config)
                 .packages(reinstall_specs)
                 .tree(tree)
+                .check_conflicts(false)
                 .progress(progress.clone())
                 .install()
                 .await?;
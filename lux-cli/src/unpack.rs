@@ -1,9 +1,14 @@
-use std::{fs::File, io::Cursor, path::PathBuf};
+use std::{
+    fs::File,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
 
 use clap::Args;
-use eyre::Result;
+use eyre::{eyre, Result};
 use lux_lib::{
     config::Config,
+    luarocks::packed_rock::{PackedRock, PackedRockDiff, RockManifestMismatch},
     operations,
     package::PackageReq,
     progress::{MultiProgress, Progress},
@@ -11,10 +16,18 @@ use lux_lib::{
 
 #[derive(Args)]
 pub struct Unpack {
-    /// A path to a .src.rock file. Usually obtained via `lux download`.
+    /// A path to a .rock or .src.rock file. Usually obtained via `lx download`.
     path: PathBuf,
     /// Where to unpack the rock.
+    #[arg(conflicts_with_all = ["list", "diff"])]
     destination: Option<PathBuf>,
+    /// List the contents of the rock without extracting it.
+    #[arg(long)]
+    list: bool,
+    /// Compare the files of the rock with those of another packed rock,
+    /// without extracting them.
+    #[arg(long, value_name = "OTHER_ROCK")]
+    diff: Option<PathBuf>,
 }
 
 #[derive(Args)]
@@ -25,9 +38,53 @@ pub struct UnpackRemote {
 }
 
 pub async fn unpack(data: Unpack) -> Result<()> {
-    let destination = data.destination.unwrap_or_else(|| {
-        PathBuf::from(data.path.to_string_lossy().trim_end_matches(".src.rock"))
-    });
+    let rock = PackedRock::read(File::open(&data.path)?)?;
+    if let Some(other_path) = data.diff {
+        let other = PackedRock::read(File::open(&other_path)?)?;
+        for diff in rock.diff(&other) {
+            match diff {
+                PackedRockDiff::Added(entry) => println!("+ {}", entry.path()),
+                PackedRockDiff::Removed(entry) => println!("- {}", entry.path()),
+                PackedRockDiff::Changed { new, .. } => println!("~ {}", new.path()),
+            }
+        }
+        return Ok(());
+    }
+    if data.list {
+        for entry in rock.entries() {
+            println!("{}  {:>10}  {}", entry.md5(), entry.size(), entry.path());
+        }
+    }
+    if let Some(mismatches) = rock.verify() {
+        if !mismatches.is_empty() {
+            for mismatch in &mismatches {
+                match mismatch {
+                    RockManifestMismatch::Missing { path } => {
+                        eprintln!("{path}: listed in the rock_manifest, but missing")
+                    }
+                    RockManifestMismatch::Hash {
+                        path,
+                        expected,
+                        actual,
+                    } => eprintln!("{path}: expected md5 {expected}, but got {actual}"),
+                    RockManifestMismatch::Unlisted { path } => {
+                        eprintln!("{path}: not listed in the rock_manifest")
+                    }
+                }
+            }
+            return Err(eyre!(
+                "{} does not match its rock_manifest",
+                data.path.display()
+            ));
+        }
+    }
+    if data.list {
+        return Ok(());
+    }
+
+    let destination = data
+        .destination
+        .unwrap_or_else(|| default_destination(&data.path));
     let src_file = File::open(data.path)?;
     let progress = MultiProgress::new();
     let bar = Progress::Progress(progress.new_bar());
@@ -47,6 +104,22 @@ and type `lux make` to build.",
     Ok(())
 }
 
+/// `foo-1.0-1.src.rock` and `foo-1.0-1.linux-x86_64.rock` are unpacked to `foo-1.0-1`.
+fn default_destination(path: &Path) -> PathBuf {
+    let path = path.to_string_lossy();
+    let path = path.trim_end_matches(".rock");
+    let path = match path.rsplit_once('.') {
+        Some((name, "src" | "all")) => name,
+        Some((name, arch))
+            if arch.contains('-') && !arch.starts_with(|c: char| c.is_ascii_digit()) =>
+        {
+            name
+        }
+        _ => path,
+    };
+    PathBuf::from(path)
+}
+
 pub async fn unpack_remote(data: UnpackRemote, config: Config) -> Result<()> {
     let package_req = data.package_req;
     let progress = MultiProgress::new();
@@ -1,12 +1,19 @@
+use std::time::Instant;
+
 use clap::Args;
 use eyre::{eyre, Context, OptionExt, Result};
 use itertools::Itertools;
-use lux_lib::package::{PackageName, PackageReq};
+use lux_lib::package::{PackageName, PackageReq, PackageVersion};
 use lux_lib::progress::{MultiProgress, Progress, ProgressBar};
 use lux_lib::project::Project;
 use lux_lib::remote_package_db::RemotePackageDB;
 use lux_lib::rockspec::lua_dependency;
-use lux_lib::{config::Config, operations};
+use lux_lib::{
+    config::{Config, ResolutionStrategy},
+    operations,
+};
+
+use crate::utils::summary::{Summary, SummaryRow};
 
 #[derive(Args)]
 pub struct Update {
@@ -32,9 +39,33 @@ pub struct Update {
     /// When used with the --toml flag in a project, these must be package names.
     #[arg(short, long)]
     test: Option<Vec<PackageReq>>,
+
+    /// Resolve each package to the lowest version that satisfies its constraints,
+    /// instead of the highest.{n}
+    /// Useful for checking that lower version bounds are accurate.
+    #[arg(long)]
+    minimal_versions: bool,
+
+    /// Update the selected package to exactly this version,
+    /// which must satisfy the package's constraint.{n}
+    /// Can be used to pin a transitive dependency in the lockfile,
+    /// without modifying the lux.toml.{n}
+    /// Requires exactly one package to be selected.
+    #[arg(long, conflicts_with = "toml")]
+    precise: Option<PackageVersion>,
+
+    /// Also update pinned packages, unpinning them.{n}
+    /// By default, pinned packages are skipped.
+    #[arg(long)]
+    unpin: bool,
 }
 
 pub async fn update(args: Update, config: Config) -> Result<()> {
+    let config = if args.minimal_versions {
+        config.with_resolution_strategy(ResolutionStrategy::Minimal)
+    } else {
+        config
+    };
     let progress = MultiProgress::new_arc();
     progress.map(|p| p.add(ProgressBar::from("🔎 Looking for updates...".to_string())));
 
@@ -70,12 +101,15 @@ pub async fn update(args: Update, config: Config) -> Result<()> {
         }
     }
 
+    let start = Instant::now();
     let updated_packages = operations::Update::new(&config)
         .progress(progress)
         .packages(args.packages)
         .build_dependencies(args.build)
         .test_dependencies(args.test)
         .validate_integrity(!args.no_integrity_check)
+        .maybe_precise(args.precise)
+        .unpin(args.unpin)
         .update()
         .await
         .wrap_err("update failed.")?;
@@ -85,6 +119,15 @@ pub async fn update(args: Update, config: Config) -> Result<()> {
         return Ok(());
     }
 
+    let mut summary = Summary::default();
+    summary.push(SummaryRow {
+        operation: "update",
+        updated: updated_packages.len(),
+        duration: start.elapsed(),
+        ..SummaryRow::default()
+    });
+    summary.print();
+
     Ok(())
 }
 
@@ -0,0 +1,180 @@
+use std::io::{self, IsTerminal, Write};
+
+use termcolor::{Buffer, Color, ColorSpec, WriteColor};
+
+/// Render Markdown for display in a terminal, using ANSI escape codes
+/// if stdout is a terminal and `NO_COLOR` is not set.
+///
+/// This is not a complete CommonMark implementation, but it handles the
+/// elements commonly found in a package's README and docs:
+/// headings, fenced code blocks, lists, block quotes, and inline emphasis, code and links.
+pub(crate) fn render_markdown(content: &str) -> io::Result<String> {
+    let colored = std::io::stdout().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty());
+    let buffer = if colored {
+        Buffer::ansi()
+    } else {
+        Buffer::no_color()
+    };
+    render_markdown_to(buffer, content)
+}
+
+fn render_markdown_to(mut buffer: Buffer, content: &str) -> io::Result<String> {
+    let mut in_code_block = false;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            buffer.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+            write!(buffer, "    {line}")?;
+            buffer.reset()?;
+            writeln!(buffer)?;
+            continue;
+        }
+        if let Some((level, heading)) = parse_heading(trimmed) {
+            let color = if level == 1 {
+                Color::Magenta
+            } else {
+                Color::Blue
+            };
+            buffer.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
+            write!(buffer, "{heading}")?;
+            buffer.reset()?;
+            writeln!(buffer)?;
+            continue;
+        }
+        let indent = &line[..line.len() - trimmed.len()];
+        if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+            .or_else(|| trimmed.strip_prefix("+ "))
+        {
+            write!(buffer, "{indent}  • ")?;
+            render_inline(&mut buffer, item)?;
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            buffer.set_color(ColorSpec::new().set_dimmed(true))?;
+            write!(buffer, "{indent}  │ ")?;
+            buffer.reset()?;
+            render_inline(&mut buffer, quote.trim_start())?;
+        } else {
+            write!(buffer, "{indent}")?;
+            render_inline(&mut buffer, trimmed)?;
+        }
+        writeln!(buffer)?;
+    }
+    Ok(String::from_utf8_lossy(buffer.as_slice()).to_string())
+}
+
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) {
+        line[level..]
+            .strip_prefix(' ')
+            .map(|heading| (level, heading.trim_end_matches('#').trim()))
+    } else {
+        None
+    }
+}
+
+fn render_inline(buffer: &mut Buffer, text: &str) -> io::Result<()> {
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('`') {
+            if let Some(end) = after.find('`') {
+                buffer.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+                write!(buffer, "{}", &after[..end])?;
+                buffer.reset()?;
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix("**").or_else(|| rest.strip_prefix("__")) {
+            if let Some(end) = after.find(&rest[..2]) {
+                buffer.set_color(ColorSpec::new().set_bold(true))?;
+                write!(buffer, "{}", &after[..end])?;
+                buffer.reset()?;
+                rest = &after[end + 2..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('[') {
+            if let Some((label, url, remainder)) =
+                after.split_once("](").and_then(|(label, tail)| {
+                    tail.split_once(')')
+                        .map(|(url, remainder)| (label, url, remainder))
+                })
+            {
+                buffer.set_color(ColorSpec::new().set_underline(true))?;
+                write!(buffer, "{label}")?;
+                buffer.reset()?;
+                buffer.set_color(ColorSpec::new().set_dimmed(true))?;
+                write!(buffer, " <{url}>")?;
+                buffer.reset()?;
+                rest = remainder;
+                continue;
+            }
+        }
+        let mut chars = rest.chars();
+        let c = chars.next().expect("rest is not empty");
+        write!(buffer, "{c}")?;
+        rest = chars.as_str();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip_ansi(text: &str) -> String {
+        let mut result = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                for c in chars.by_ref() {
+                    if c == 'm' {
+                        break;
+                    }
+                }
+            } else {
+                result.push(c);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn render_markdown_elements() {
+        let rendered = render_markdown_to(
+            Buffer::ansi(),
+            "# Title #\n\nSome **bold** and `code`, see [docs](https://example.com).\n\n- item\n\n```lua\nlocal x = 1\n```\n> quote\n",
+        )
+        .unwrap();
+        assert_eq!(
+            strip_ansi(&rendered),
+            "Title\n\nSome bold and code, see docs <https://example.com>.\n\n  • item\n\n    local x = 1\n  │ quote\n"
+        );
+    }
+
+    #[test]
+    fn render_markdown_without_color() {
+        let rendered = render_markdown_to(
+            Buffer::no_color(),
+            "# Title
+
+Some **bold** `code`
+",
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "Title
+
+Some bold code
+"
+        );
+    }
+}
@@ -1,4 +1,6 @@
 pub(crate) mod file_tree;
 pub(crate) mod github_metadata;
 pub(crate) mod install;
+pub(crate) mod markdown;
 pub(crate) mod project;
+pub(crate) mod summary;
@@ -1,16 +1,18 @@
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc, time::Instant};
 
 use eyre::{Context, Result};
 use lux_lib::{
     config::{Config, LuaVersion},
     git::shorthand::GitUrlShorthand,
-    operations::Sync,
+    operations::{Sync, SyncReport},
     package::PackageReq,
     progress::{MultiProgress, Progress},
     project::Project,
     tree::Tree,
 };
 
+use super::summary::{Summary, SummaryRow};
+
 /// Used for parsing alternatives between a git URL shorthand and a package requirement.
 // The `FromStr` instance tries to parse a git URL shorthand first (expecting a git host prefix),
 // and then a package requirement.
@@ -48,14 +50,17 @@ pub async fn sync_dependencies_if_locked(
     project: &Project,
     progress: Arc<Progress<MultiProgress>>,
     config: &Config,
+    summary: &mut Summary,
 ) -> Result<()> {
     // NOTE: We only update the lockfile if one exists.
     // Otherwise, the next `lx build` will remove the packages.
-    Sync::new(project, config)
+    let start = Instant::now();
+    let report = Sync::new(project, config)
         .progress(progress)
         .sync_dependencies()
         .await
         .wrap_err("syncing dependencies with the project lockfile failed.")?;
+    summary.push(sync_summary_row("sync", &report, start));
     Ok(())
 }
 
@@ -63,12 +68,15 @@ pub async fn sync_build_dependencies_if_locked(
     project: &Project,
     progress: Arc<Progress<MultiProgress>>,
     config: &Config,
+    summary: &mut Summary,
 ) -> Result<()> {
-    Sync::new(project, config)
+    let start = Instant::now();
+    let report = Sync::new(project, config)
         .progress(progress.clone())
         .sync_build_dependencies()
         .await
         .wrap_err("syncing build dependencies with the project lockfile failed.")?;
+    summary.push(sync_summary_row("sync (build)", &report, start));
     Ok(())
 }
 
@@ -76,11 +84,24 @@ pub async fn sync_test_dependencies_if_locked(
     project: &Project,
     progress: Arc<Progress<MultiProgress>>,
     config: &Config,
+    summary: &mut Summary,
 ) -> Result<()> {
-    Sync::new(project, config)
+    let start = Instant::now();
+    let report = Sync::new(project, config)
         .progress(progress.clone())
         .sync_test_dependencies()
         .await
         .wrap_err("syncing test dependencies with the project lockfile failed.")?;
+    summary.push(sync_summary_row("sync (test)", &report, start));
     Ok(())
 }
+
+fn sync_summary_row(operation: &'static str, report: &SyncReport, start: Instant) -> SummaryRow {
+    SummaryRow {
+        operation,
+        installed: report.added().len(),
+        removed: report.removed().len(),
+        duration: start.elapsed(),
+        ..SummaryRow::default()
+    }
+}
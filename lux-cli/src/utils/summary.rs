@@ -0,0 +1,123 @@
+use std::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use lux_lib::progress::ProgressMode;
+
+/// The outcome of an install, update or sync operation.
+#[derive(Debug, Default)]
+pub struct SummaryRow {
+    pub operation: &'static str,
+    pub installed: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub failed: usize,
+    pub duration: Duration,
+}
+
+/// A table summarising the operations performed by a command.
+#[derive(Debug, Default)]
+pub struct Summary {
+    rows: Vec<SummaryRow>,
+}
+
+impl Summary {
+    pub fn push(&mut self, row: SummaryRow) {
+        self.rows.push(row)
+    }
+
+    /// Print the summary to stderr, unless in quiet mode.
+    pub fn print(&self) {
+        if !self.rows.is_empty() && ProgressMode::current() != ProgressMode::Quiet {
+            eprint!("{self}");
+        }
+    }
+}
+
+const HEADERS: [&str; 6] = [
+    "operation",
+    "installed",
+    "updated",
+    "removed",
+    "failed",
+    "duration",
+];
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                [
+                    row.operation.to_string(),
+                    row.installed.to_string(),
+                    row.updated.to_string(),
+                    row.removed.to_string(),
+                    row.failed.to_string(),
+                    format!("{:.2}s", row.duration.as_secs_f64()),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let widths: Vec<usize> = (0..HEADERS.len())
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row[column].len())
+                    .chain(std::iter::once(HEADERS[column].len()))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let headers = HEADERS.map(String::from);
+        for row in std::iter::once(&headers).chain(rows.iter()) {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(column, (cell, width))| {
+                    // Left-align the operation names, right-align the numbers
+                    if column == 0 {
+                        format!("{cell:<width$}")
+                    } else {
+                        format!("{cell:>width$}")
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_summary() {
+        let mut summary = Summary::default();
+        summary.push(SummaryRow {
+            operation: "install",
+            installed: 3,
+            duration: Duration::from_millis(1500),
+            ..SummaryRow::default()
+        });
+        summary.push(SummaryRow {
+            operation: "sync (test)",
+            removed: 12,
+            failed: 1,
+            duration: Duration::from_millis(20),
+            ..SummaryRow::default()
+        });
+        assert_eq!(
+            summary.to_string(),
+            "\
+operation    installed  updated  removed  failed  duration
+install              3        0        0       0     1.50s
+sync (test)          0        0       12       1     0.02s
+"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use clap::Args;
+use eyre::Result;
+use lux_lib::{config::Config, operations::ProjectVerify, project::Project};
+
+use crate::diagnostic::CheckFailed;
+
+#[derive(Args)]
+pub struct Verify {
+    /// Don't send a `HEAD` request to check that the source URL can be fetched.
+    #[arg(long)]
+    no_fetch: bool,
+}
+
+pub async fn verify(verify_args: Verify, config: Config) -> Result<()> {
+    let project = Project::current_or_err()?;
+
+    let issues = ProjectVerify::new(&project, &config)
+        .fetch_source(!verify_args.no_fetch)
+        .verify()
+        .await?;
+
+    if issues.is_empty() {
+        return Ok(());
+    }
+
+    for issue in &issues {
+        println!("{issue}");
+    }
+
+    Err(CheckFailed(format!("{} metadata issue(s) found", issues.len())).into())
+}
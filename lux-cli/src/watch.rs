@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use clap::{Args, Subcommand};
+use eyre::Result;
+use itertools::Itertools;
+use lux_lib::{
+    analysis::{require_graph, select_tests, TestSelection},
+    config::Config,
+    operations::{self, TestEnv},
+    project::Project,
+};
+use path_slash::PathExt;
+
+/// How often the project's files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait for more changes after a change,
+/// e.g. when an editor or `git checkout` writes several files.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+#[derive(Subcommand)]
+pub enum WatchCmd {
+    /// Run the project's tests, and rerun them whenever a file in the project changes.
+    Test(WatchTest),
+}
+
+#[derive(Args)]
+pub struct WatchTest {
+    /// Extra arguments to pass to the test runner or test script.
+    test_args: Option<Vec<String>>,
+
+    /// Only rerun the test files that are affected by the changes:{n}
+    /// changed test files, test files named after a changed file,{n}
+    /// e.g. `spec/foo_spec.lua` for `src/foo.lua`,{n}
+    /// and test files that require a changed file, directly or indirectly.{n}
+    /// Changes to files other than Lua files rerun all tests.{n}
+    /// The affected test files are passed to the test runner as arguments,{n}
+    /// like `lx test -- spec/foo_spec.lua`.
+    #[arg(long)]
+    changed_only: bool,
+
+    /// With `--changed-only`, run all tests after this many partial runs,{n}
+    /// in case a test is affected by a change in a way that can't be detected.{n}
+    /// 0 means never.
+    #[arg(long, value_name = "RUNS", default_value_t = 10)]
+    full_run_every: usize,
+
+    /// Don't isolate the user environment (keep `HOME` and `XDG` environment variables).
+    #[arg(long)]
+    impure: bool,
+
+    /// The test suite to run, e.g. `integration` for `[test.integration]` in the lux.toml.
+    #[arg(long)]
+    suite: Option<String>,
+}
+
+pub async fn watch(watch_cmd: WatchCmd, config: Config) -> Result<()> {
+    match watch_cmd {
+        WatchCmd::Test(watch_test_args) => watch_test(watch_test_args, config).await,
+    }
+}
+
+async fn watch_test(args: WatchTest, config: Config) -> Result<()> {
+    let root = Project::current_or_err()?.root().to_path_buf();
+    run_tests(&args, &config, Vec::new()).await?;
+    // Files that the tests write, e.g. the lockfile, don't trigger another run.
+    let mut files = snapshot(&root)?;
+    let mut partial_runs = 0;
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        if changed_files(&files, &snapshot(&root)?).is_empty() {
+            continue;
+        }
+        tokio::time::sleep(DEBOUNCE).await;
+        let new_files = snapshot(&root)?;
+        let changed = changed_files(&files, &new_files);
+        if changed.is_empty() {
+            continue;
+        }
+
+        let full_run_due = args.full_run_every > 0 && partial_runs >= args.full_run_every;
+        let selection = if args.changed_only && !full_run_due {
+            select_affected_tests(&config, &changed)?
+        } else {
+            TestSelection::All
+        };
+        match selection {
+            TestSelection::All => {
+                println!("🧪 Rerunning all tests");
+                partial_runs = 0;
+                run_tests(&args, &config, Vec::new()).await?;
+            }
+            TestSelection::Files(test_files) if test_files.is_empty() => {
+                println!(
+                    "No tests are affected by the changes to {}",
+                    changed.iter().map(|file| file.display()).join(", ")
+                );
+            }
+            TestSelection::Files(test_files) => {
+                println!(
+                    "🧪 Rerunning {}",
+                    test_files.iter().map(|file| file.display()).join(", ")
+                );
+                partial_runs += 1;
+                run_tests(&args, &config, test_files).await?;
+            }
+        }
+        files = snapshot(&root)?;
+    }
+}
+
+fn select_affected_tests(config: &Config, changed: &[PathBuf]) -> Result<TestSelection> {
+    // Reload the project, as its lux.toml may have changed.
+    let project = Project::current_or_err()?;
+    match require_graph(&project, config) {
+        Ok(graph) => Ok(select_tests(&graph, project.root(), changed)),
+        Err(err) => {
+            eprintln!("⚠️ WARNING: Failed to determine the affected tests: {err}");
+            Ok(TestSelection::All)
+        }
+    }
+}
+
+/// Run the tests, or only the `test_files` if there are any.
+/// Failing tests are reported, but don't stop watching.
+async fn run_tests(args: &WatchTest, config: &Config, test_files: Vec<PathBuf>) -> Result<()> {
+    let project = Project::current_or_err()?;
+    let test_env = if args.impure {
+        TestEnv::Impure
+    } else {
+        TestEnv::Pure
+    };
+    let result = operations::Test::new(project, config)
+        .args(args.test_args.clone().unwrap_or_default())
+        .args(
+            test_files
+                .iter()
+                .map(|file| file.to_slash_lossy().to_string()),
+        )
+        .env(test_env)
+        .maybe_suite(args.suite.clone())
+        .run()
+        .await;
+    match result {
+        Ok(()) => println!("✅ Tests passed. Watching for changes..."),
+        Err(err) => eprintln!("❌ {err}\nWatching for changes..."),
+    }
+    Ok(())
+}
+
+/// The modification time of each file in the project, respecting `.gitignore` and similar files.
+fn snapshot(root: &Path) -> Result<HashMap<PathBuf, SystemTime>> {
+    let mut files = HashMap::new();
+    for entry in ignore::WalkBuilder::new(root)
+        .filter_entry(|entry| entry.file_name() != ".lux")
+        .build()
+    {
+        // Files may disappear while we walk the project, e.g. editors' swap files.
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if is_not_found(&err) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let path = entry.path();
+        if path.is_file() {
+            let modified = match entry.metadata() {
+                Ok(metadata) => metadata.modified()?,
+                Err(err) if is_not_found(&err) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let relative_path = path.strip_prefix(root).unwrap_or(path);
+            files.insert(relative_path.to_path_buf(), modified);
+        }
+    }
+    Ok(files)
+}
+
+fn is_not_found(err: &ignore::Error) -> bool {
+    err.io_error()
+        .is_some_and(|err| err.kind() == io::ErrorKind::NotFound)
+}
+
+/// The files that were added, modified or removed.
+fn changed_files(
+    before: &HashMap<PathBuf, SystemTime>,
+    after: &HashMap<PathBuf, SystemTime>,
+) -> Vec<PathBuf> {
+    after
+        .iter()
+        .filter(|(file, modified)| before.get(*file) != Some(modified))
+        .map(|(file, _)| file)
+        .chain(before.keys().filter(|file| !after.contains_key(*file)))
+        .cloned()
+        .sorted()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_changed_files() {
+        let time = SystemTime::UNIX_EPOCH;
+        let later = time + Duration::from_secs(1);
+        let before = HashMap::from([
+            (PathBuf::from("src/foo.lua"), time),
+            (PathBuf::from("src/bar.lua"), time),
+            (PathBuf::from("src/removed.lua"), time),
+        ]);
+        let after = HashMap::from([
+            (PathBuf::from("src/foo.lua"), time),
+            (PathBuf::from("src/bar.lua"), later),
+            (PathBuf::from("src/added.lua"), time),
+        ]);
+        assert_eq!(
+            changed_files(&before, &after),
+            vec![
+                PathBuf::from("src/added.lua"),
+                PathBuf::from("src/bar.lua"),
+                PathBuf::from("src/removed.lua"),
+            ]
+        );
+    }
+}
@@ -1,6 +1,12 @@
 use clap::Args;
-use eyre::Result;
-use lux_lib::{config::Config, lua_rockspec::LuaModule, package::PackageReq, which};
+use eyre::{eyre, Result};
+use lux_lib::{
+    config::Config,
+    lua_rockspec::LuaModule,
+    package::PackageReq,
+    project::Project,
+    which::{self, ModuleMatch, WhichError},
+};
 
 #[derive(Args)]
 pub struct Which {
@@ -8,12 +14,76 @@ pub struct Which {
     module: LuaModule,
     /// Only search in these packages.
     packages: Option<Vec<PackageReq>>,
+    /// Show every match, in priority order,{n}
+    /// instead of only the module that would be loaded.
+    #[arg(long)]
+    all: bool,
+    /// Print the matches as JSON.
+    #[arg(long)]
+    json: bool,
+    /// Search for an installed executable instead of a module.{n}
+    /// Wrapped Lua scripts resolve to the unwrapped scripts they run.
+    #[arg(long)]
+    bin: bool,
+    /// Also print the package that provides the module, and its version.
+    #[arg(long, conflicts_with_all = ["all", "json"])]
+    owner: bool,
+    /// Print the documentation directory of the package that provides the module{n}
+    /// instead of the module's path.
+    #[arg(long, conflicts_with_all = ["all", "json", "owner", "edit"])]
+    doc: bool,
+    /// Open the module that would be loaded in `$VISUAL` or `$EDITOR`.
+    #[arg(long, conflicts_with_all = ["all", "json"])]
+    edit: bool,
 }
 
 pub fn which(args: Which, config: Config) -> Result<()> {
-    let path = which::Which::new(args.module, &config)
+    let project = Project::current()?;
+    let module = args.module.clone();
+    let matches = which::Which::new(args.module, &config)
         .packages(args.packages.unwrap_or_default())
-        .search()?;
-    print!("{}", path.display());
+        .maybe_project(project.as_ref())
+        .executable(args.bin)
+        .search_all()?;
+    let first = matches.first().ok_or_else(|| {
+        if args.bin {
+            WhichError::ExecutableNotFound(module.to_string())
+        } else {
+            WhichError::ModuleNotFound(module.clone())
+        }
+    })?;
+    for shadowed in ModuleMatch::shadowed(&matches) {
+        eprintln!(
+            "⚠️ WARNING: module {} from {} is shadowed by {}",
+            module, shadowed, first
+        );
+    }
+    let matches = if args.all {
+        matches.as_slice()
+    } else {
+        std::slice::from_ref(first)
+    };
+    if args.json {
+        println!("{}", serde_json::to_string(matches)?);
+    } else if args.all {
+        for module_match in matches {
+            println!("{module_match}");
+        }
+    } else if args.doc {
+        let doc = first
+            .doc
+            .as_ref()
+            .ok_or_else(|| eyre!("{} has no documentation directory.", first.owner()))?;
+        print!("{}", doc.display());
+    } else {
+        if args.owner {
+            println!("{}", first.owner());
+        }
+        if args.edit {
+            edit::edit_file(&first.path)?;
+        } else {
+            print!("{}", first.path.display());
+        }
+    }
     Ok(())
 }
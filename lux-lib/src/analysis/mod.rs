@@ -0,0 +1,222 @@
+//! Static analysis of a project's Lua sources.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use mlua::{IntoLua, LuaSerdeExt};
+use path_slash::PathExt;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    config::Config,
+    lua_rockspec::LuaModule,
+    project::Project,
+    which::{ModuleMatch, Which, WhichError},
+};
+
+mod require_scanner;
+mod test_selection;
+
+pub use require_scanner::*;
+pub use test_selection::*;
+
+/// Modules that are part of Lua or LuaJIT, and are never provided by a package.
+const BUILTIN_MODULES: &[&str] = &[
+    "_G",
+    "bit",
+    "bit32",
+    "coroutine",
+    "debug",
+    "ffi",
+    "io",
+    "jit",
+    "math",
+    "os",
+    "package",
+    "string",
+    "table",
+    "utf8",
+];
+
+/// Whether the module (or the module it is a submodule of) is part of Lua or LuaJIT.
+pub fn is_builtin_module(module: &str) -> bool {
+    module
+        .split('.')
+        .next()
+        .is_some_and(|root| BUILTIN_MODULES.contains(&root))
+}
+
+#[derive(Error, Debug)]
+pub enum RequireGraphError {
+    #[error(transparent)]
+    Which(#[from] WhichError),
+    #[error("error collecting Lua files: {0}")]
+    Walk(#[from] ignore::Error),
+    #[error("failed to read {0}: {1}")]
+    Io(String, io::Error),
+}
+
+/// Which modules a project's Lua files require, and where each module is found.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RequireGraph {
+    files: BTreeMap<PathBuf, Vec<Require>>,
+    modules: BTreeMap<String, Vec<ModuleMatch>>,
+}
+
+impl RequireGraph {
+    /// The modules each Lua file requires with a string literal,
+    /// keyed by the file's path, relative to the project root.
+    pub fn files(&self) -> &BTreeMap<PathBuf, Vec<Require>> {
+        &self.files
+    }
+
+    /// Every match for each required module, in priority order,
+    /// as returned by [`Which::search_all`].
+    /// Modules that are not found have no matches, and builtin modules are omitted.
+    pub fn modules(&self) -> &BTreeMap<String, Vec<ModuleMatch>> {
+        &self.modules
+    }
+
+    /// The match that `require` would load, if the module is found.
+    pub fn resolve(&self, module: &str) -> Option<&ModuleMatch> {
+        self.modules.get(module).and_then(|matches| matches.first())
+    }
+}
+
+impl IntoLua for RequireGraph {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        lua.to_value(&self)
+    }
+}
+
+/// Scans the project's Lua files for `require`s and resolves the required modules
+/// against the project's sources and the installed packages.
+pub fn require_graph(
+    project: &Project,
+    config: &Config,
+) -> Result<RequireGraph, RequireGraphError> {
+    let mut graph = RequireGraph::default();
+    for file in lua_files(project.root())? {
+        let content = std::fs::read(project.root().join(&file))
+            .map_err(|err| RequireGraphError::Io(file.clone(), err))?;
+        let requires = scan_requires(&String::from_utf8_lossy(&content));
+        for require in &requires {
+            if graph.modules.contains_key(&require.module) || is_builtin_module(&require.module) {
+                continue;
+            }
+            let Ok(lua_module) = LuaModule::from_str(&require.module) else {
+                continue;
+            };
+            let matches = Which::new(lua_module, config)
+                .project(project)
+                .search_all()?;
+            graph.modules.insert(require.module.clone(), matches);
+        }
+        graph.files.insert(PathBuf::from(file), requires);
+    }
+    Ok(graph)
+}
+
+/// The Lua files in the project, respecting `.gitignore` and similar files,
+/// relative to the project root.
+pub(crate) fn lua_files(project_root: &Path) -> Result<Vec<String>, ignore::Error> {
+    let mut files = Vec::new();
+    for entry in ignore::WalkBuilder::new(project_root)
+        .filter_entry(|entry| entry.file_name() != ".lux")
+        .build()
+    {
+        let path = entry?.into_path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "lua") {
+            let relative_path = path.strip_prefix(project_root).unwrap_or(&path);
+            files.push(relative_path.to_slash_lossy().to_string());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild, PathCopy};
+
+    use crate::{
+        config::{ConfigBuilder, LuaVersion},
+        which::ModuleLocation,
+    };
+
+    use super::*;
+
+    #[test]
+    fn require_graph_resolves_modules() {
+        let tree_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/sample-tree");
+        let temp = assert_fs::TempDir::new().unwrap();
+        let user_tree = temp.child("tree");
+        user_tree.copy_from(&tree_path, &["**"]).unwrap();
+        let project_root = temp.child("project");
+        project_root
+            .child("lux.toml")
+            .write_str(
+                r#"
+package = "my-project"
+version = "0.1.0"
+lua = "5.1"
+
+[build]
+type = "builtin"
+"#,
+            )
+            .unwrap();
+        project_root
+            .child("src/main.lua")
+            .write_str(
+                r#"
+local bar = require("foo.bar")
+local util = require("my.util")
+local missing = require("missing")
+local str = require("string")
+"#,
+            )
+            .unwrap();
+        project_root
+            .child("src/my/util.lua")
+            .write_str("return {}")
+            .unwrap();
+        let project = Project::from(project_root.path()).unwrap().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(user_tree.to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+
+        let graph = require_graph(&project, &config).unwrap();
+        assert_eq!(graph.files().len(), 2);
+        assert_eq!(
+            graph.files()[&PathBuf::from("src/main.lua")]
+                .iter()
+                .map(|require| require.module.as_str())
+                .collect::<Vec<_>>(),
+            vec!["foo.bar", "my.util", "missing", "string"]
+        );
+        assert!(graph.files()[&PathBuf::from("src/my/util.lua")].is_empty());
+        assert_eq!(
+            graph.modules().keys().collect::<Vec<_>>(),
+            vec!["foo.bar", "missing", "my.util"]
+        );
+        assert_eq!(
+            graph.resolve("foo.bar").unwrap().location,
+            ModuleLocation::UserTree
+        );
+        assert_eq!(
+            graph.resolve("my.util").unwrap().location,
+            ModuleLocation::ProjectSources
+        );
+        assert!(graph.resolve("missing").is_none());
+    }
+}
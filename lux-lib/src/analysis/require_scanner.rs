@@ -0,0 +1,190 @@
+use itertools::Itertools;
+use serde::Serialize;
+
+/// A `require` of a module in a Lua file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Require {
+    pub module: String,
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Name(String),
+    String(String),
+    Symbol(char),
+}
+
+/// Scans Lua source code for modules that are loaded with a string literal,
+/// e.g. `require("foo")`, `require "foo"` or `pcall(require, "foo")`,
+/// in the order they are required.
+pub fn scan_requires(content: &str) -> Vec<Require> {
+    let tokens = tokenize(content);
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, (token, _))| matches!(token, Token::Name(name) if name == "require"))
+        // `foo.require "bar"` is not the global `require`
+        .filter(|(index, _)| {
+            !index
+                .checked_sub(1)
+                .is_some_and(|prev| matches!(tokens[prev].0, Token::Symbol('.' | ':')))
+        })
+        .filter_map(|(index, (_, line))| match &tokens[index + 1..] {
+            [(Token::String(module), _), ..]
+            | [(Token::Symbol('(' | ','), _), (Token::String(module), _), (Token::Symbol(')'), _), ..] => {
+                Some(Require {
+                    module: module.clone(),
+                    line: *line,
+                })
+            }
+            _ => None,
+        })
+        .collect_vec()
+}
+
+/// A minimal Lua tokenizer, which is just precise enough to skip comments
+/// and to tell string literals apart from code.
+fn tokenize(content: &str) -> Vec<(Token, usize)> {
+    let chars = content.chars().collect_vec();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\n' => {
+                line += 1;
+                i += 1;
+            }
+            c if c.is_whitespace() => i += 1,
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                let start = i + 2;
+                i = match long_bracket(&chars, start) {
+                    Some((_, end)) => end,
+                    None => chars[start..]
+                        .iter()
+                        .position(|c| *c == '\n')
+                        .map_or(chars.len(), |pos| start + pos),
+                };
+                line += chars[start..i].iter().filter(|c| **c == '\n').count();
+            }
+            '[' => match long_bracket(&chars, i) {
+                Some((value, end)) => {
+                    tokens.push((Token::String(value), line));
+                    line += chars[i..end].iter().filter(|c| **c == '\n').count();
+                    i = end;
+                }
+                None => {
+                    tokens.push((Token::Symbol('['), line));
+                    i += 1;
+                }
+            },
+            quote @ ('"' | '\'') => {
+                let start_line = line;
+                let mut value = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != quote && chars[i] != '\n' {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    if let Some(c) = chars.get(i) {
+                        if *c == '\n' {
+                            line += 1;
+                        }
+                        value.push(*c);
+                    }
+                    i += 1;
+                }
+                tokens.push((Token::String(value), start_line));
+                i += 1;
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push((Token::Name(chars[start..i].iter().collect()), line));
+            }
+            c => {
+                tokens.push((Token::Symbol(c), line));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+/// Parses a long bracket, like `[[...]]` or `[==[...]==]`, starting at `start`.
+/// Returns its content and the index after the closing bracket.
+fn long_bracket(chars: &[char], start: usize) -> Option<(String, usize)> {
+    if chars.get(start) != Some(&'[') {
+        return None;
+    }
+    let level = chars[start + 1..].iter().take_while(|c| **c == '=').count();
+    let content_start = start + level + 2;
+    if chars.get(content_start - 1) != Some(&'[') {
+        return None;
+    }
+    let close = std::iter::once(']')
+        .chain(std::iter::repeat_n('=', level))
+        .chain(std::iter::once(']'))
+        .collect_vec();
+    match chars[content_start..]
+        .windows(close.len())
+        .position(|window| window == close)
+    {
+        Some(pos) => Some((
+            chars[content_start..content_start + pos].iter().collect(),
+            content_start + pos + close.len(),
+        )),
+        None => Some((chars[content_start..].iter().collect(), chars.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_requires_literal_modules() {
+        let content = r#"
+local foo = require("foo")
+local bar = require "bar.baz"
+local ok, qux = pcall(require, 'qux')
+local long = require [[long.module]]
+-- local commented = require("commented")
+--[[
+local block = require("block")
+]]
+local s = "require('in_string')"
+local dynamic = require("prefix." .. name)
+local method = loader.require("method")
+local last = require([==[last]==])
+"#;
+        assert_eq!(
+            scan_requires(content),
+            vec![
+                Require {
+                    module: "foo".into(),
+                    line: 2,
+                },
+                Require {
+                    module: "bar.baz".into(),
+                    line: 3,
+                },
+                Require {
+                    module: "qux".into(),
+                    line: 4,
+                },
+                Require {
+                    module: "long.module".into(),
+                    line: 5,
+                },
+                Require {
+                    module: "last".into(),
+                    line: 13,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use crate::which::ModuleLocation;
+
+use super::RequireGraph;
+
+/// The tests to run after some of a project's files have changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestSelection {
+    /// Run all tests, e.g. because the `lux.toml` or a non-Lua file changed,
+    /// which may affect any test.
+    All,
+    /// Only run these test files, relative to the project root.
+    /// If there are none, no test is affected by the changes.
+    Files(Vec<PathBuf>),
+}
+
+/// Whether a Lua file is a test file according to the common naming conventions:
+/// `foo_spec.lua`, `foo_test.lua` or `test_foo.lua`.
+pub fn is_test_file(path: &Path) -> bool {
+    test_subject(path).is_some()
+}
+
+/// The name of the module a test file tests, e.g. `foo` for `spec/foo_spec.lua`.
+fn test_subject(path: &Path) -> Option<&str> {
+    let stem = lua_file_stem(path)?;
+    stem.strip_suffix("_spec")
+        .or_else(|| stem.strip_suffix("_test"))
+        .or_else(|| stem.strip_prefix("test_"))
+        .filter(|subject| !subject.is_empty())
+}
+
+/// The name of the module a Lua file provides, e.g. `foo` for `src/foo.lua` or `src/foo/init.lua`.
+fn module_stem(path: &Path) -> Option<&str> {
+    match lua_file_stem(path)? {
+        "init" => path.parent()?.file_name()?.to_str(),
+        stem => Some(stem),
+    }
+}
+
+fn lua_file_stem(path: &Path) -> Option<&str> {
+    if path.extension().is_some_and(|ext| ext == "lua") {
+        path.file_stem()?.to_str()
+    } else {
+        None
+    }
+}
+
+/// Select the test files that may be affected by changes to the `changed` files,
+/// whose paths are relative to the project root:
+///
+/// - Changed test files.
+/// - Test files named after a changed file, e.g. `spec/foo_spec.lua` for `src/foo.lua`.
+/// - Test files that require a changed file, directly or through other files of the project.
+///
+/// If a file other than a Lua file changed, all tests are selected.
+pub fn select_tests(
+    graph: &RequireGraph,
+    project_root: &Path,
+    changed: &[PathBuf],
+) -> TestSelection {
+    if changed.iter().any(|file| lua_file_stem(file).is_none()) {
+        return TestSelection::All;
+    }
+
+    // The project files that require each project file.
+    let mut required_by: HashMap<PathBuf, Vec<&PathBuf>> = HashMap::new();
+    for (file, requires) in graph.files() {
+        for require in requires {
+            if let Some(module_match) = graph
+                .resolve(&require.module)
+                .filter(|module_match| module_match.location == ModuleLocation::ProjectSources)
+            {
+                let path = module_match
+                    .path
+                    .strip_prefix(project_root)
+                    .unwrap_or(&module_match.path);
+                required_by
+                    .entry(path.to_path_buf())
+                    .or_default()
+                    .push(file);
+            }
+        }
+    }
+
+    let mut affected = BTreeSet::new();
+    let mut queue = changed.iter().collect::<VecDeque<_>>();
+    while let Some(file) = queue.pop_front() {
+        if affected.insert(file.clone()) {
+            queue.extend(required_by.get(file).into_iter().flatten());
+        }
+    }
+
+    let changed_modules = changed
+        .iter()
+        .filter(|file| !is_test_file(file))
+        .filter_map(|file| module_stem(file))
+        .collect::<BTreeSet<_>>();
+    let named_tests = graph
+        .files()
+        .keys()
+        .filter(|file| test_subject(file).is_some_and(|subject| changed_modules.contains(subject)));
+
+    TestSelection::Files(
+        affected
+            .iter()
+            .chain(named_tests)
+            .filter(|file| is_test_file(file))
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+
+    use crate::{
+        analysis::require_graph,
+        config::{ConfigBuilder, LuaVersion},
+        project::Project,
+    };
+
+    use super::*;
+
+    #[test]
+    fn select_affected_tests() {
+        let project_root = assert_fs::TempDir::new().unwrap();
+        project_root
+            .child("lux.toml")
+            .write_str(
+                r#"
+package = "my-project"
+version = "0.1.0"
+lua = "5.1"
+
+[build]
+type = "builtin"
+"#,
+            )
+            .unwrap();
+        for (file, content) in [
+            ("src/my/util.lua", "return {}"),
+            ("src/my/init.lua", "return require('my.util')"),
+            ("src/other.lua", "return {}"),
+            ("spec/my_spec.lua", "local my = require('my')"),
+            ("spec/util_spec.lua", "return {}"),
+            ("spec/other_spec.lua", "local other = require('other')"),
+        ] {
+            project_root.child(file).write_str(content).unwrap();
+        }
+        let project = Project::from(project_root.path()).unwrap().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(project_root.child("tree").to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let graph = require_graph(&project, &config).unwrap();
+        let select = |changed: &[&str]| {
+            select_tests(
+                &graph,
+                project.root(),
+                &changed.iter().map(PathBuf::from).collect::<Vec<_>>(),
+            )
+        };
+
+        assert_eq!(
+            select(&["src/my/util.lua"]),
+            TestSelection::Files(vec![
+                PathBuf::from("spec/my_spec.lua"),
+                PathBuf::from("spec/util_spec.lua"),
+            ])
+        );
+        assert_eq!(
+            select(&["spec/other_spec.lua"]),
+            TestSelection::Files(vec![PathBuf::from("spec/other_spec.lua")])
+        );
+        assert_eq!(select(&["src/new.lua"]), TestSelection::Files(Vec::new()));
+        assert_eq!(select(&["lux.toml"]), TestSelection::All);
+    }
+}
@@ -7,7 +7,10 @@ use std::{
 use bon::Builder;
 
 use crate::{
-    build::external_dependency::ExternalDependencyInfo,
+    build::{
+        build_log::BuildLog, compile_commands::CompileCommands,
+        external_dependency::ExternalDependencyInfo, manifest::ProjectChanges,
+    },
     config::Config,
     lua_installation::LuaInstallation,
     lua_rockspec::DeploySpec,
@@ -27,6 +30,15 @@ pub(crate) struct RunBuildArgs<'a> {
     pub(crate) tree: &'a Tree,
     pub(crate) build_dir: &'a Path,
     pub(crate) progress: &'a Progress<ProgressBar>,
+    /// Collects the output of the build processes.
+    pub(crate) log: &'a BuildLog<'a>,
+    /// Project files that have changed since the last build.
+    /// If set, backends may skip rebuilding outputs whose sources are unchanged.
+    pub(crate) project_changes: Option<&'a ProjectChanges>,
+    /// If set, Lua sources are symlinked from this directory instead of being copied.
+    pub(crate) dev_link_root: Option<&'a Path>,
+    /// If set, backends record the commands that compile C sources.
+    pub(crate) compile_commands: Option<&'a CompileCommands>,
 }
 
 pub(crate) trait BuildBackend {
@@ -0,0 +1,136 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+    process::Output,
+    sync::Mutex,
+};
+
+use crate::{
+    config::Config,
+    package::PackageSpec,
+    process::LimitedChild,
+    progress::{Progress, ProgressBar},
+};
+
+/// The directory in the cache directory that build logs are stored in.
+pub const BUILD_LOGS_DIR_NAME: &str = "build-logs";
+
+/// The path of the log of the last build of a package.
+pub fn build_log_path(config: &Config, package: &PackageSpec) -> PathBuf {
+    config.cache_dir().join(BUILD_LOGS_DIR_NAME).join(format!(
+        "{}@{}.log",
+        package.name(),
+        package.version()
+    ))
+}
+
+/// Collects the output of a package's build processes.
+/// The full output is always written to the package's build log.
+/// If verbose, each line is also printed above the package's progress bar as soon as it is
+/// written, prefixed with the package name, so that the output of concurrent builds
+/// can be told apart.
+pub(crate) struct BuildLog<'a> {
+    package: String,
+    progress: &'a Progress<ProgressBar>,
+    verbose: bool,
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl<'a> BuildLog<'a> {
+    /// Start a new log for a build of the `package`, replacing the log of its previous build.
+    /// If the log file can't be created, the output is only streamed.
+    pub(crate) fn new(
+        package: &PackageSpec,
+        progress: &'a Progress<ProgressBar>,
+        config: &Config,
+    ) -> Self {
+        let path = build_log_path(config, package);
+        let file = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| File::create(&path))
+            .ok();
+        Self {
+            package: package.name().to_string(),
+            progress,
+            verbose: config.verbose(),
+            path,
+            file: Mutex::new(file),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Wait for a build process to exit, streaming its output to the log.
+    pub(crate) async fn wait_with_output(
+        &self,
+        child: LimitedChild,
+        phase: &str,
+    ) -> io::Result<Output> {
+        self.phase(phase);
+        child
+            .wait_with_output_streamed(phase, |line| self.line(line))
+            .await
+    }
+
+    /// Record the start of a build phase, e.g. a command that is about to run.
+    pub(crate) fn phase(&self, phase: &str) {
+        self.write(format!("==> {phase}\n").as_bytes());
+    }
+
+    /// Record a line of a build process's output, including its line ending.
+    pub(crate) fn line(&self, line: &[u8]) {
+        self.write(line);
+        if self.verbose {
+            let line = String::from_utf8_lossy(line);
+            let message = format!("[{}] {}", self.package, line.trim_end());
+            match self.progress {
+                Progress::Progress(bar) => bar.println(message),
+                Progress::NoProgress => eprintln!("{message}"),
+            }
+        }
+    }
+
+    fn write(&self, bytes: &[u8]) {
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(Err(err)) = file.as_mut().map(|file| file.write_all(bytes)) {
+            eprintln!(
+                "⚠️ WARNING: Failed to write the build log {}: {err}",
+                self.path.display()
+            );
+            *file = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigBuilder;
+
+    #[test]
+    fn write_build_log() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .cache_dir(Some(temp.to_path_buf()))
+            .build()
+            .unwrap();
+        let package = PackageSpec::parse("foo".into(), "1.0.0-1".into()).unwrap();
+        let log = BuildLog::new(&package, &Progress::NoProgress, &config);
+        log.phase("make");
+        log.line(b"cc -c foo.c\n");
+        assert_eq!(
+            log.path(),
+            &temp.join(BUILD_LOGS_DIR_NAME).join("foo@1.0.0-1.log")
+        );
+        assert_eq!(
+            std::fs::read_to_string(log.path()).unwrap(),
+            "==> make\ncc -c foo.c\n"
+        );
+    }
+}
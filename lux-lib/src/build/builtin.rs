@@ -11,6 +11,7 @@ use walkdir::WalkDir;
 use crate::{
     build::{
         backend::{BuildBackend, BuildInfo, RunBuildArgs},
+        manifest::ProjectChanges,
         utils,
     },
     lua_rockspec::{BuiltinBuildSpec, LuaModule, ModuleSpec},
@@ -44,6 +45,9 @@ impl BuildBackend for BuiltinBuildSpec {
         let tree = args.tree;
         let build_dir = args.build_dir;
         let progress = args.progress;
+        let project_changes = args.project_changes;
+        let dev_link_root = args.dev_link_root;
+        let compile_commands = args.compile_commands;
 
         // Detect all Lua modules
         let modules = autodetect_modules(build_dir, source_paths(build_dir, &self.modules))
@@ -53,7 +57,21 @@ impl BuildBackend for BuiltinBuildSpec {
 
         progress.map(|p| p.set_position(modules.len() as u64));
 
+        if let Some(project_changes) = project_changes {
+            remove_deleted_modules(project_changes, &modules, &output_paths.src)?;
+        }
+
         for (destination_path, module_type) in modules.iter() {
+            if is_up_to_date(
+                destination_path,
+                module_type,
+                project_changes,
+                dev_link_root.is_some(),
+                &output_paths.src,
+                &output_paths.lib,
+            ) {
+                continue;
+            }
             match module_type {
                 ModuleSpec::SourcePath(source) => {
                     if source.extension().map(|ext| ext == "c").unwrap_or(false) {
@@ -72,8 +90,22 @@ impl BuildBackend for BuiltinBuildSpec {
                             lua,
                             external_dependencies,
                             config,
+                            compile_commands,
                         )
                         .await?
+                    } else if let Some(dev_link_root) = dev_link_root {
+                        progress.map(|p| {
+                            p.set_message(format!(
+                                "Linking {} to {}...",
+                                &source.to_string_lossy(),
+                                &destination_path
+                            ))
+                        });
+                        utils::link_lua_to_module_path(
+                            &dev_link_root.join(source),
+                            destination_path,
+                            &output_paths.src,
+                        )?
                     } else {
                         progress.map(|p| {
                             p.set_message(format!(
@@ -101,6 +133,7 @@ impl BuildBackend for BuiltinBuildSpec {
                         lua,
                         external_dependencies,
                         config,
+                        compile_commands,
                     )
                     .await?
                 }
@@ -114,6 +147,7 @@ impl BuildBackend for BuiltinBuildSpec {
                         lua,
                         external_dependencies,
                         config,
+                        compile_commands,
                     )
                     .await?
                 }
@@ -144,6 +178,45 @@ impl BuildBackend for BuiltinBuildSpec {
     }
 }
 
+/// Whether a module's output exists and none of its sources have changed since the last build.
+/// With `dev_link`, Lua modules must be symlinks to their sources.
+fn is_up_to_date(
+    destination_path: &LuaModule,
+    module_type: &ModuleSpec,
+    project_changes: Option<&ProjectChanges>,
+    dev_link: bool,
+    src_dir: &Path,
+    lib_dir: &Path,
+) -> bool {
+    let project_changes = match project_changes {
+        Some(project_changes) => project_changes,
+        None => return false,
+    };
+    match module_type {
+        ModuleSpec::SourcePath(source) if source.extension().is_some_and(|ext| ext == "lua") => {
+            // Linked modules are not up to date if we are copying sources and vice versa,
+            // so that we can switch between linked and copied sources.
+            project_changes.is_unchanged(source)
+                && src_dir
+                    .join(destination_path.to_lua_path())
+                    .symlink_metadata()
+                    .is_ok_and(|metadata| {
+                        if dev_link {
+                            metadata.is_symlink()
+                        } else {
+                            metadata.is_file()
+                        }
+                    })
+        }
+        // C modules may depend on headers that aren't listed as sources,
+        // so we only skip them if no non-Lua files have changed.
+        _ => {
+            !project_changes.has_native_changes()
+                && lib_dir.join(destination_path.to_lib_path()).is_file()
+        }
+    }
+}
+
 fn source_paths(build_dir: &Path, modules: &HashMap<LuaModule, ModuleSpec>) -> HashSet<PathBuf> {
     modules
         .iter()
@@ -181,25 +254,64 @@ fn autodetect_modules(
             let diff: PathBuf =
                 pathdiff::diff_paths(build_dir.join(file.clone().into_path()), build_dir)
                     .expect("failed to autodetect modules");
+            (autodetected_module(&diff), ModuleSpec::SourcePath(diff))
+        })
+        .collect()
+}
 
-            // NOTE(vhyrro): You may ask why we convert all paths to Lua module paths
-            // just to convert them back later in the `run()` stage.
-            //
-            // The rockspec requires the format to be like this, and representing our
-            // data in this form allows us to respect any overrides made by the user (which follow
-            // the `module.name` format, not our internal one).
-            let pathbuf = diff.components().skip(1).collect::<PathBuf>();
-            let mut lua_module = LuaModule::from_pathbuf(pathbuf);
-
-            // NOTE(mrcjkb): `LuaModule` does not parse as "<module>.init" from files named "init.lua"
-            // To make sure we don't change the file structure when installing, we append it here.
-            if file.file_name().to_string_lossy().as_bytes() == b"init.lua" {
-                lua_module = lua_module.join(&LuaModule::from_str("init").unwrap())
-            }
+/// The module of a Lua file in the `src`, `lua` or `lib` directory,
+/// with `path` relative to the build directory.
+fn autodetected_module(path: &Path) -> LuaModule {
+    // NOTE(vhyrro): You may ask why we convert all paths to Lua module paths
+    // just to convert them back later in the `run()` stage.
+    //
+    // The rockspec requires the format to be like this, and representing our
+    // data in this form allows us to respect any overrides made by the user (which follow
+    // the `module.name` format, not our internal one).
+    let pathbuf = path.components().skip(1).collect::<PathBuf>();
+    let mut lua_module = LuaModule::from_pathbuf(pathbuf);
 
-            (lua_module, ModuleSpec::SourcePath(diff))
+    // NOTE(mrcjkb): `LuaModule` does not parse as "<module>.init" from files named "init.lua"
+    // To make sure we don't change the file structure when installing, we append it here.
+    if path
+        .file_name()
+        .is_some_and(|file_name| file_name.to_string_lossy().as_bytes() == b"init.lua")
+    {
+        lua_module = lua_module.join(&LuaModule::from_str("init").unwrap())
+    }
+    lua_module
+}
+
+/// Remove the installed modules of Lua files that have been deleted from the project
+/// since the last build, as incremental builds reuse the project's install directory.
+fn remove_deleted_modules(
+    project_changes: &ProjectChanges,
+    modules: &HashMap<LuaModule, ModuleSpec>,
+    src_dir: &Path,
+) -> io::Result<()> {
+    let deleted_modules = project_changes
+        .removed()
+        .filter(|file| {
+            file.extension().is_some_and(|ext| ext == "lua")
+                && ["src", "lua", "lib"]
+                    .iter()
+                    .any(|dir| file.starts_with(dir))
         })
-        .collect()
+        .map(|file| autodetected_module(file))
+        .filter(|module| !modules.contains_key(module));
+    for module in deleted_modules {
+        let installed = src_dir.join(module.to_lua_path());
+        if installed.symlink_metadata().is_err() {
+            continue;
+        }
+        std::fs::remove_file(&installed)?;
+        for dir in installed.ancestors().skip(1) {
+            if dir == src_dir || !dir.starts_with(src_dir) || std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
 }
 
 fn autodetect_src_bin_scripts(build_dir: &Path) -> Vec<PathBuf> {
@@ -214,3 +326,98 @@ fn autodetect_src_bin_scripts(build_dir: &Path) -> Vec<PathBuf> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+
+    use crate::{build::manifest::BuildManifest, config::ConfigBuilder};
+
+    use super::*;
+
+    #[test]
+    fn deleted_modules_are_removed_from_the_tree() {
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let project = assert_fs::TempDir::new().unwrap();
+        project.child("src/foo.lua").write_str("return {}").unwrap();
+        project
+            .child("src/bar/baz.lua")
+            .write_str("return {}")
+            .unwrap();
+        let previous = BuildManifest::from_project_files(project.path(), &config).unwrap();
+
+        let tree = assert_fs::TempDir::new().unwrap();
+        let src_dir = tree.path().join("src");
+        tree.child("src/foo.lua").write_str("return {}").unwrap();
+        tree.child("src/bar/baz.lua")
+            .write_str("return {}")
+            .unwrap();
+
+        std::fs::remove_file(project.path().join("src/bar/baz.lua")).unwrap();
+        let changes = BuildManifest::from_project_files(project.path(), &config)
+            .unwrap()
+            .changes_since(&previous)
+            .unwrap();
+        let modules = autodetect_modules(project.path(), HashSet::new());
+        remove_deleted_modules(&changes, &modules, &src_dir).unwrap();
+
+        assert!(src_dir.join("foo.lua").is_file());
+        assert!(!src_dir.join("bar").join("baz.lua").exists());
+        assert!(!src_dir.join("bar").exists());
+    }
+
+    #[test]
+    fn copied_modules_are_stale_when_dev_linking() {
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let project = assert_fs::TempDir::new().unwrap();
+        project.child("src/foo.lua").write_str("return {}").unwrap();
+        let manifest = BuildManifest::from_project_files(project.path(), &config).unwrap();
+        let changes = manifest.changes_since(&manifest).unwrap();
+
+        let tree = assert_fs::TempDir::new().unwrap();
+        let src_dir = tree.path().join("src");
+        let lib_dir = tree.path().join("lib");
+        let module = LuaModule::from_str("foo").unwrap();
+        let spec = ModuleSpec::SourcePath("src/foo.lua".into());
+        tree.child("src/foo.lua").write_str("return {}").unwrap();
+        assert!(is_up_to_date(
+            &module,
+            &spec,
+            Some(&changes),
+            false,
+            &src_dir,
+            &lib_dir
+        ));
+        assert!(!is_up_to_date(
+            &module,
+            &spec,
+            Some(&changes),
+            true,
+            &src_dir,
+            &lib_dir
+        ));
+
+        #[cfg(unix)]
+        {
+            std::fs::remove_file(src_dir.join("foo.lua")).unwrap();
+            std::os::unix::fs::symlink(project.path().join("src/foo.lua"), src_dir.join("foo.lua"))
+                .unwrap();
+            assert!(is_up_to_date(
+                &module,
+                &spec,
+                Some(&changes),
+                true,
+                &src_dir,
+                &lib_dir
+            ));
+            assert!(!is_up_to_date(
+                &module,
+                &spec,
+                Some(&changes),
+                false,
+                &src_dir,
+                &lib_dir
+            ));
+        }
+    }
+}
@@ -9,11 +9,14 @@ use tokio::process::Command;
 use crate::{
     build::{
         backend::{BuildBackend, BuildInfo, RunBuildArgs},
-        utils,
+        build_log::BuildLog,
+        compile_commands::CompileCommand,
+        compiler_cache, utils,
     },
     config::Config,
     lua_rockspec::CMakeBuildSpec,
     path::{Paths, PathsError},
+    process::{self, ProcessLimits},
     tree::TreeError,
     variables::{self, GetVariableError, HasVariables, VariableSubstitutionError},
 };
@@ -66,6 +69,8 @@ impl BuildBackend for CMakeBuildSpec {
         let external_dependencies = args.external_dependencies;
         let config = args.config;
         let build_dir = args.build_dir;
+        let compile_commands = args.compile_commands;
+        let log = args.log;
 
         let build_tree = args.tree.build_tree(config)?;
         let build_paths = Paths::new(&build_tree)?;
@@ -97,6 +102,19 @@ impl BuildBackend for CMakeBuildSpec {
                 Ok::<_, Self::Err>(format!("{key}={substituted_value}"))
             })
             .fold_ok((), |(), variable| args.push(format!("-D{variable}")))?;
+        for (key, value) in cmake_toolchain_variables(config)
+            .into_iter()
+            .map(|(key, value)| (key, value.clone()))
+            .chain(compiler_cache::cmake_variables(config))
+        {
+            // The rockspec's variables take precedence
+            if !args.iter().any(|arg| arg.starts_with(&format!("-D{key}="))) {
+                args.push(format!("-D{key}={value}"));
+            }
+        }
+        if compile_commands.is_some() {
+            args.push("-DCMAKE_EXPORT_COMPILE_COMMANDS=ON".into());
+        }
 
         spawn_cmake_cmd(
             Command::new(config.cmake_cmd())
@@ -108,9 +126,24 @@ impl BuildBackend for CMakeBuildSpec {
                 .env("LUA_PATH", &lua_path)
                 .env("LUA_CPATH", &lua_cpath),
             config,
+            log,
         )
         .await?;
 
+        if let Some(compile_commands) = compile_commands {
+            // Not all CMake generators support exporting compile commands
+            let database = build_dir
+                .join(CMAKE_BUILD_FILE)
+                .join("compile_commands.json");
+            if let Ok(content) = std::fs::read_to_string(database) {
+                let commands: Vec<CompileCommand> =
+                    serde_json::from_str(&content).unwrap_or_default();
+                for command in commands {
+                    compile_commands.push(command);
+                }
+            }
+        }
+
         if self.build_pass {
             spawn_cmake_cmd(
                 Command::new(config.cmake_cmd())
@@ -123,6 +156,7 @@ impl BuildBackend for CMakeBuildSpec {
                     .env("LUA_PATH", &lua_path)
                     .env("LUA_CPATH", &lua_cpath),
                 config,
+                log,
             )
             .await?
         }
@@ -141,6 +175,7 @@ impl BuildBackend for CMakeBuildSpec {
                     .env("LUA_PATH", &lua_path)
                     .env("LUA_CPATH", &lua_cpath),
                 config,
+                log,
             )
             .await?;
         }
@@ -149,10 +184,17 @@ impl BuildBackend for CMakeBuildSpec {
     }
 }
 
-async fn spawn_cmake_cmd(cmd: &mut Command, config: &Config) -> Result<(), CMakeError> {
-    match cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
-        Ok(child) => match child.wait_with_output().await {
-            Ok(output) if output.status.success() => utils::log_command_output(&output, config),
+async fn spawn_cmake_cmd(
+    cmd: &mut Command,
+    config: &Config,
+    log: &BuildLog<'_>,
+) -> Result<(), CMakeError> {
+    match process::spawn(
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped()),
+        &ProcessLimits::build(config),
+    ) {
+        Ok(child) => match log.wait_with_output(child, &config.cmake_cmd()).await {
+            Ok(output) if output.status.success() => {}
             Ok(output) => {
                 return Err(CMakeError::CommandFailure {
                     name: config.cmake_cmd().clone(),
@@ -167,3 +209,22 @@ async fn spawn_cmake_cmd(cmd: &mut Command, config: &Config) -> Result<(), CMake
     }
     Ok(())
 }
+
+/// The CMake variables that configure the config's toolchain.
+fn cmake_toolchain_variables(config: &Config) -> Vec<(&'static str, &String)> {
+    config
+        .toolchain_variables()
+        .into_iter()
+        .flat_map(|(key, value)| {
+            let cmake_keys: &[&'static str] = match key {
+                "CC" => &["CMAKE_C_COMPILER"],
+                "CXX" => &["CMAKE_CXX_COMPILER"],
+                "AR" => &["CMAKE_AR"],
+                "CFLAGS" => &["CMAKE_C_FLAGS", "CMAKE_CXX_FLAGS"],
+                "LDFLAGS" => &["CMAKE_SHARED_LINKER_FLAGS", "CMAKE_MODULE_LINKER_FLAGS"],
+                _ => &[],
+            };
+            cmake_keys.iter().map(move |cmake_key| (*cmake_key, value))
+        })
+        .collect()
+}
@@ -9,11 +9,15 @@ use tokio::process::Command;
 use which::which;
 
 use crate::{
-    build::backend::{BuildBackend, BuildInfo, RunBuildArgs},
+    build::{
+        backend::{BuildBackend, BuildInfo, RunBuildArgs},
+        build_log::BuildLog,
+    },
     config::Config,
     lua_installation::LuaInstallation,
     lua_rockspec::CommandBuildSpec,
     path::{Paths, PathsError},
+    process::{self, ProcessLimits},
     tree::{RockLayout, TreeError},
     variables::VariableSubstitutionError,
 };
@@ -75,6 +79,7 @@ impl BuildBackend for CommandBuildSpec {
                 config,
                 build_dir,
                 &build_paths,
+                args.log,
             )
             .await?;
         }
@@ -89,6 +94,7 @@ impl BuildBackend for CommandBuildSpec {
                     config,
                     build_dir,
                     &build_paths,
+                    args.log,
                 )
                 .await?;
             }
@@ -97,6 +103,7 @@ impl BuildBackend for CommandBuildSpec {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_command(
     command: &str,
     output_paths: &RockLayout,
@@ -105,6 +112,7 @@ async fn run_command(
     config: &Config,
     build_dir: &Path,
     build_paths: &Paths,
+    log: &BuildLog<'_>,
 ) -> Result<(), CommandError> {
     let lua_path = build_paths.package_path_prepended().joined();
     let lua_cpath = build_paths.package_cpath_prepended().joined();
@@ -121,25 +129,29 @@ async fn run_command(
     #[cfg(not(target_env = "msvc"))]
     let (shell, shell_arg) = (which("sh")?, "-c");
 
-    match Command::new(shell)
-        .arg(shell_arg)
-        .arg(&substituted_cmd)
-        .current_dir(build_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .env("PATH", &bin_path)
-        .env("LUA_PATH", &lua_path)
-        .env("LUA_CPATH", &lua_cpath)
-        .spawn()
-    {
+    match process::spawn(
+        Command::new(shell)
+            .arg(shell_arg)
+            .arg(&substituted_cmd)
+            .current_dir(build_dir)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .env("PATH", &bin_path)
+            .env("LUA_PATH", &lua_path)
+            .env("LUA_CPATH", &lua_cpath),
+        &ProcessLimits::build(config),
+    ) {
         Err(err) => {
             return Err(CommandError::Io {
                 err,
                 command: substituted_cmd,
             })
         }
-        Ok(child) => match child.wait_with_output().await {
-            Ok(output) if output.status.success() => utils::log_command_output(&output, config),
+        Ok(child) => match log
+            .wait_with_output(child, &format!("`{substituted_cmd}`"))
+            .await
+        {
+            Ok(output) if output.status.success() => {}
             Ok(output) => {
                 return Err(CommandError::CommandFailure {
                     command: substituted_cmd,
@@ -0,0 +1,200 @@
+//! A [JSON compilation database](https://clang.llvm.org/docs/JSONCompilationDatabase.html)
+//! (`compile_commands.json`), for clangd-based editing of C modules.
+
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// An entry of a compilation database.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompileCommand {
+    /// The working directory of the compilation.
+    pub directory: PathBuf,
+    /// The source file that is compiled.
+    pub file: PathBuf,
+    /// The compile command, as a list of arguments.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<String>,
+    /// The compile command, as a shell-escaped string.
+    /// Generated by some build systems instead of `arguments`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// The output of the compilation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+}
+
+impl CompileCommand {
+    /// Rewrite the paths in this command that point into `from`, to point into `to`.
+    fn relocate(self, from: &Path, to: &Path) -> Self {
+        let relocate_str = |s: &str| {
+            s.replace(
+                from.to_string_lossy().as_ref(),
+                to.to_string_lossy().as_ref(),
+            )
+        };
+        let relocate_path = |path: &Path| match path.strip_prefix(from) {
+            Ok(relative) => to.join(relative),
+            Err(_) => path.to_path_buf(),
+        };
+        Self {
+            directory: relocate_path(&self.directory),
+            file: relocate_path(&self.file),
+            arguments: self.arguments.iter().map(|arg| relocate_str(arg)).collect(),
+            command: self.command.as_deref().map(relocate_str),
+            output: self.output.as_deref().map(relocate_path),
+        }
+    }
+}
+
+/// A handle for recording the compile commands of builds.
+/// Cloning it is cheap, and all clones record into the same database.
+#[derive(Debug, Clone, Default)]
+pub struct CompileCommands {
+    commands: Arc<Mutex<Vec<CompileCommand>>>,
+    dependency_sources: Option<PathBuf>,
+}
+
+impl CompileCommands {
+    /// Record the compile commands of project builds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also record the compile commands of dependencies that are built from source.
+    /// Their sources are kept in `dir`, so that the compile commands can refer to them.
+    pub fn with_dependency_sources(self, dir: PathBuf) -> Self {
+        Self {
+            dependency_sources: Some(dir),
+            ..self
+        }
+    }
+
+    pub(crate) fn dependency_sources(&self) -> Option<&Path> {
+        self.dependency_sources.as_deref()
+    }
+
+    pub(crate) fn push(&self, command: CompileCommand) {
+        self.commands
+            .lock()
+            .expect("compile commands lock poisoned")
+            .push(command);
+    }
+
+    /// Move the commands recorded in `other` into this database,
+    /// rewriting the paths that point into the build directory `from` to point into `to`.
+    pub(crate) fn extend_relocated(&self, other: CompileCommands, from: &Path, to: &Path) {
+        let commands = std::mem::take(
+            &mut *other
+                .commands
+                .lock()
+                .expect("compile commands lock poisoned"),
+        );
+        self.commands
+            .lock()
+            .expect("compile commands lock poisoned")
+            .extend(
+                commands
+                    .into_iter()
+                    .map(|command| command.relocate(from, to)),
+            );
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.commands
+            .lock()
+            .expect("compile commands lock poisoned")
+            .is_empty()
+    }
+
+    pub fn commands(&self) -> Vec<CompileCommand> {
+        self.commands
+            .lock()
+            .expect("compile commands lock poisoned")
+            .clone()
+    }
+
+    /// Write the recorded compile commands to the compilation database at `path`,
+    /// replacing the existing entries for the recorded files and keeping the others.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let commands = self.commands();
+        let existing: Vec<CompileCommand> = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let files: HashSet<PathBuf> = commands.iter().map(|cmd| cmd.file.clone()).collect();
+        let merged = existing
+            .into_iter()
+            .filter(|existing| !files.contains(&existing.file))
+            .chain(commands)
+            .sorted_by(|a, b| a.file.cmp(&b.file))
+            .collect_vec();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&merged)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::TempDir;
+
+    use super::*;
+
+    fn compile_command(dir: &Path, file: &str, flag: &str) -> CompileCommand {
+        CompileCommand {
+            directory: dir.to_path_buf(),
+            file: dir.join(file),
+            arguments: vec![
+                "cc".into(),
+                format!("-I{}", dir.join("include").display()),
+                flag.into(),
+                "-c".into(),
+                dir.join(file).to_string_lossy().to_string(),
+            ],
+            command: None,
+            output: None,
+        }
+    }
+
+    #[test]
+    fn write_relocated_compile_commands() {
+        let temp_dir = TempDir::new().unwrap();
+        let build_dir = temp_dir.join("build");
+        let project_root = temp_dir.join("project");
+        let database = project_root.join("compile_commands.json");
+
+        let compile_commands = CompileCommands::new();
+        let build = CompileCommands::new();
+        build.push(compile_command(&build_dir, "src/foo.c", "-O2"));
+        build.push(compile_command(&build_dir, "src/bar.c", "-O2"));
+        compile_commands.extend_relocated(build, &build_dir, &project_root);
+        assert_eq!(
+            compile_commands.commands()[0],
+            compile_command(&project_root, "src/foo.c", "-O2")
+        );
+        compile_commands.write(&database).unwrap();
+
+        let compile_commands = CompileCommands::new();
+        compile_commands.push(compile_command(&project_root, "src/foo.c", "-O3"));
+        compile_commands.write(&database).unwrap();
+
+        let written: Vec<CompileCommand> =
+            serde_json::from_str(&std::fs::read_to_string(&database).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            vec![
+                compile_command(&project_root, "src/bar.c", "-O2"),
+                compile_command(&project_root, "src/foo.c", "-O3"),
+            ]
+        );
+    }
+}
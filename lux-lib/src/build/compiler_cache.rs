@@ -0,0 +1,227 @@
+use std::{fmt::Display, str::FromStr};
+
+use mlua::{ExternalResult, FromLua, IntoLua};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::process::Command;
+use which::which;
+
+use crate::config::Config;
+
+/// A compiler cache, which C compiler invocations can be wrapped in
+/// to speed up rebuilds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompilerCache {
+    Sccache,
+    Ccache,
+}
+
+impl FromStr for CompilerCache {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "sccache" => Ok(Self::Sccache),
+            "ccache" => Ok(Self::Ccache),
+            _ => Err("unrecognized compiler cache. Allowed caches: 'sccache', 'ccache'.".into()),
+        }
+    }
+}
+
+impl Display for CompilerCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Sccache => "sccache",
+            Self::Ccache => "ccache",
+        })
+    }
+}
+
+impl FromLua for CompilerCache {
+    fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
+        let cache_str: String = FromLua::from_lua(value, lua)?;
+        Self::from_str(&cache_str).into_lua_err()
+    }
+}
+
+impl IntoLua for CompilerCache {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        self.to_string().into_lua(lua)
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("the compiler cache `{0}` is enabled in the config, but could not be found.\nMake sure it is installed and on the PATH.")]
+pub struct CompilerCacheNotFound(pub(crate) CompilerCache);
+
+/// The number of cache hits and misses of a compiler cache.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompilerCacheStats {
+    pub cache: CompilerCache,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CompilerCacheStats {
+    /// The hits and misses since the `earlier` stats were taken.
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            cache: self.cache,
+            hits: self.hits.saturating_sub(earlier.hits),
+            misses: self.misses.saturating_sub(earlier.misses),
+        }
+    }
+}
+
+impl Display for CompilerCacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.hits + self.misses;
+        write!(
+            f,
+            "Compiler cache ({}): {} hits, {} misses",
+            self.cache, self.hits, self.misses
+        )?;
+        if total > 0 {
+            write!(
+                f,
+                " ({:.1}% hit rate)",
+                self.hits as f64 / total as f64 * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl CompilerCache {
+    /// Check that the compiler cache is installed.
+    pub(crate) fn check(&self) -> Result<(), CompilerCacheNotFound> {
+        which(self.to_string())
+            .map(|_| ())
+            .map_err(|_| CompilerCacheNotFound(*self))
+    }
+
+    /// Query the compiler cache's statistics.
+    /// Returns `None` if they could not be determined, e.g. if the cache isn't installed.
+    pub async fn stats(&self) -> Option<CompilerCacheStats> {
+        let args: &[&str] = match self {
+            Self::Sccache => &["--show-stats", "--stats-format=json"],
+            Self::Ccache => &["--print-stats"],
+        };
+        let output = Command::new(self.to_string())
+            .args(args)
+            .output()
+            .await
+            .ok()
+            .filter(|output| output.status.success())?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (hits, misses) = match self {
+            Self::Sccache => parse_sccache_stats(&stdout)?,
+            Self::Ccache => parse_ccache_stats(&stdout)?,
+        };
+        Some(CompilerCacheStats {
+            cache: *self,
+            hits,
+            misses,
+        })
+    }
+}
+
+/// Parses the hits and misses from the output of `sccache --show-stats --stats-format=json`.
+fn parse_sccache_stats(stdout: &str) -> Option<(u64, u64)> {
+    let json: serde_json::Value = serde_json::from_str(stdout).ok()?;
+    let count = |key: &str| -> Option<u64> {
+        Some(
+            json.get("stats")?
+                .get(key)?
+                .get("counts")?
+                .as_object()?
+                .values()
+                .filter_map(serde_json::Value::as_u64)
+                .sum(),
+        )
+    };
+    Some((count("cache_hits")?, count("cache_misses")?))
+}
+
+/// Parses the hits and misses from the output of `ccache --print-stats`,
+/// which consists of tab-separated key-value pairs.
+fn parse_ccache_stats(stdout: &str) -> Option<(u64, u64)> {
+    let (mut hits, mut misses) = (None, None);
+    for (key, value) in stdout.lines().filter_map(|line| line.split_once('\t')) {
+        let value: u64 = match value.trim().parse() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        match key {
+            "direct_cache_hit" | "preprocessed_cache_hit" => hits = Some(hits.unwrap_or(0) + value),
+            "cache_miss" => misses = Some(value),
+            _ => {}
+        }
+    }
+    Some((hits?, misses?))
+}
+
+/// The `CC` and `CXX` variables, wrapped in the config's compiler cache, if any.
+/// Falls back to the default compilers if the config doesn't set them.
+pub(crate) fn compiler_variables(config: &Config) -> Vec<(&'static str, String)> {
+    match config.compiler_cache() {
+        Some(cache) => [("CC", "cc"), ("CXX", "c++")]
+            .into_iter()
+            .map(|(name, default)| {
+                let compiler = config
+                    .variables()
+                    .get(name)
+                    .map(String::as_str)
+                    .unwrap_or(default);
+                (name, format!("{cache} {compiler}"))
+            })
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// The CMake variables that wrap compiler invocations in the config's compiler cache, if any.
+pub(crate) fn cmake_variables(config: &Config) -> Vec<(&'static str, String)> {
+    match config.compiler_cache() {
+        Some(cache) => ["CMAKE_C_COMPILER_LAUNCHER", "CMAKE_CXX_COMPILER_LAUNCHER"]
+            .into_iter()
+            .map(|name| (name, cache.to_string()))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_compiler_cache_stats() {
+        let sccache = r#"{
+            "stats": {
+                "compile_requests": 5,
+                "cache_hits": { "counts": { "C/C++": 3 }, "adv_counts": {} },
+                "cache_misses": { "counts": { "C/C++": 1, "Rust": 1 }, "adv_counts": {} }
+            }
+        }"#;
+        assert_eq!(parse_sccache_stats(sccache), Some((3, 2)));
+        let ccache = "stats_updated_timestamp\t0\ndirect_cache_hit\t4\npreprocessed_cache_hit\t1\ncache_miss\t2\n";
+        assert_eq!(parse_ccache_stats(ccache), Some((5, 2)));
+        assert_eq!(parse_ccache_stats("cache hit rate 50%"), None);
+        let stats = CompilerCacheStats {
+            cache: CompilerCache::Ccache,
+            hits: 5,
+            misses: 2,
+        }
+        .since(&CompilerCacheStats {
+            cache: CompilerCache::Ccache,
+            hits: 2,
+            misses: 1,
+        });
+        assert_eq!(
+            stats.to_string(),
+            "Compiler cache (ccache): 3 hits, 1 misses (75.0% hit rate)"
+        );
+    }
+}
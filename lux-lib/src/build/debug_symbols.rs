@@ -0,0 +1,258 @@
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use mlua::{ExternalResult, FromLua, IntoLua};
+use path_slash::PathBufExt;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::process::Command;
+
+use crate::config::Config;
+
+/// The name of the directory, relative to a package's `etc` directory,
+/// that split debug symbols are written to.
+pub(crate) const DEBUG_SYMBOLS_DIR_NAME: &str = "debug";
+
+/// What to do with the debug symbols of built C modules.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DebugSymbols {
+    /// Leave the debug symbols in the built libraries.
+    #[default]
+    Keep,
+    /// Strip the debug symbols from the built libraries.
+    Strip,
+    /// Move the debug symbols to separate files in the package's `etc/debug` directory,
+    /// which debuggers can find via the libraries' debug links.
+    Split,
+}
+
+impl FromStr for DebugSymbols {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "strip" => Ok(Self::Strip),
+            "split" => Ok(Self::Split),
+            _ => Err(
+                "unrecognized debug symbols option. Allowed options: 'keep', 'strip', 'split'."
+                    .into(),
+            ),
+        }
+    }
+}
+
+impl Display for DebugSymbols {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Keep => "keep",
+            Self::Strip => "strip",
+            Self::Split => "split",
+        })
+    }
+}
+
+impl FromLua for DebugSymbols {
+    fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
+        let debug_symbols_str: String = FromLua::from_lua(value, lua)?;
+        Self::from_str(&debug_symbols_str).into_lua_err()
+    }
+}
+
+impl IntoLua for DebugSymbols {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        self.to_string().into_lua(lua)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DebugSymbolsError {
+    #[error("IO operation failed while processing debug symbols: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to run `{0}`: {1}")]
+    Spawn(String, io::Error),
+    #[error("`{program}` failed to process the debug symbols of {lib}:\n{stderr}")]
+    CommandFailure {
+        program: String,
+        lib: String,
+        stderr: String,
+    },
+}
+
+/// Strip or split the debug symbols of the shared libraries in `lib_dir`,
+/// as configured by `config.debug_symbols()`.
+/// Split debug symbols are written to `debug_dir`, mirroring the layout of `lib_dir`.
+pub(crate) async fn process_debug_symbols(
+    lib_dir: &Path,
+    debug_dir: &Path,
+    config: &Config,
+) -> Result<(), DebugSymbolsError> {
+    let debug_symbols = config.debug_symbols();
+    if debug_symbols == DebugSymbols::Keep || !lib_dir.is_dir() {
+        return Ok(());
+    }
+    for lib in shared_libraries(lib_dir) {
+        match debug_symbols {
+            DebugSymbols::Keep => {}
+            DebugSymbols::Strip => strip(&lib, config).await?,
+            DebugSymbols::Split => {
+                let relative_path = lib
+                    .strip_prefix(lib_dir)
+                    .expect("library is not in the lib directory");
+                let debug_file = debug_dir.join(debug_file_name(relative_path));
+                split(&lib, &debug_file, config).await?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The shared libraries in `lib_dir`, which may contain debug symbols.
+pub(crate) fn shared_libraries(lib_dir: &Path) -> Vec<PathBuf> {
+    walkdir::WalkDir::new(lib_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION)
+        })
+        .collect()
+}
+
+fn debug_file_name(lib: &Path) -> PathBuf {
+    if cfg!(target_os = "macos") {
+        PathBuf::from(format!("{}.dSYM", lib.display()))
+    } else if cfg!(target_env = "msvc") {
+        lib.with_extension("pdb")
+    } else {
+        PathBuf::from(format!("{}.debug", lib.display()))
+    }
+}
+
+async fn strip(lib: &Path, config: &Config) -> Result<(), DebugSymbolsError> {
+    if cfg!(target_env = "msvc") {
+        // MSVC keeps debug symbols in separate .pdb files,
+        // which we don't install
+        return Ok(());
+    }
+    let strip_cmd = strip_cmd(config);
+    let strip_flag = if cfg!(target_os = "macos") {
+        "-S"
+    } else {
+        "--strip-debug"
+    };
+    run(&strip_cmd, &[strip_flag.into(), lib.into()], lib).await
+}
+
+async fn split(lib: &Path, debug_file: &Path, config: &Config) -> Result<(), DebugSymbolsError> {
+    if let Some(parent) = debug_file.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    if cfg!(target_env = "msvc") {
+        let pdb = lib.with_extension("pdb");
+        if pdb.is_file() {
+            tokio::fs::rename(&pdb, debug_file).await?;
+        }
+        Ok(())
+    } else if cfg!(target_os = "macos") {
+        run(
+            "dsymutil",
+            &[lib.into(), "-o".into(), debug_file.into()],
+            lib,
+        )
+        .await?;
+        strip(lib, config).await
+    } else {
+        let objcopy_cmd = objcopy_cmd(config);
+        run(
+            &objcopy_cmd,
+            &["--only-keep-debug".into(), lib.into(), debug_file.into()],
+            lib,
+        )
+        .await?;
+        let mut debuglink = std::ffi::OsString::from("--add-gnu-debuglink=");
+        debuglink.push(debug_file);
+        run(
+            &objcopy_cmd,
+            &["--strip-debug".into(), debuglink, lib.into()],
+            lib,
+        )
+        .await
+    }
+}
+
+/// The `strip` program, which can be overridden with the `STRIP` variable.
+fn strip_cmd(config: &Config) -> String {
+    config
+        .variables()
+        .get("STRIP")
+        .cloned()
+        .unwrap_or_else(|| "strip".into())
+}
+
+/// The `objcopy` program, which can be overridden with the `OBJCOPY` variable.
+fn objcopy_cmd(config: &Config) -> String {
+    config
+        .variables()
+        .get("OBJCOPY")
+        .cloned()
+        .unwrap_or_else(|| "objcopy".into())
+}
+
+async fn run(
+    program: &str,
+    args: &[std::ffi::OsString],
+    lib: &Path,
+) -> Result<(), DebugSymbolsError> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|err| DebugSymbolsError::Spawn(program.to_string(), err))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(DebugSymbolsError::CommandFailure {
+            program: program.to_string(),
+            lib: lib.to_path_buf().to_slash_lossy().to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_symbols_roundtrip() {
+        for debug_symbols in [DebugSymbols::Keep, DebugSymbols::Strip, DebugSymbols::Split] {
+            assert_eq!(
+                DebugSymbols::from_str(&debug_symbols.to_string()).unwrap(),
+                debug_symbols
+            );
+        }
+        assert!(DebugSymbols::from_str("full").is_err());
+    }
+
+    #[test]
+    fn shared_libraries_in_lib_dir() {
+        let lib_dir = assert_fs::TempDir::new().unwrap();
+        let lib = lib_dir
+            .path()
+            .join("foo")
+            .join(format!("bar.{}", std::env::consts::DLL_EXTENSION));
+        std::fs::create_dir_all(lib.parent().unwrap()).unwrap();
+        std::fs::write(&lib, "").unwrap();
+        std::fs::write(lib_dir.path().join("README.md"), "").unwrap();
+        assert_eq!(shared_libraries(lib_dir.path()), vec![lib]);
+    }
+}
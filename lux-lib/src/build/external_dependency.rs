@@ -3,8 +3,11 @@ use path_slash::{PathBufExt, PathExt};
 use pkg_config::{Config as PkgConfig, Library};
 use std::{
     collections::HashMap,
+    fmt::Display,
     path::{Path, PathBuf},
+    process::{Command, Stdio},
 };
+use target_lexicon::Triple;
 use thiserror::Error;
 
 use crate::{
@@ -29,6 +32,19 @@ pub enum ExternalDependencyError {
     LibraryNotFound(String, String),
 }
 
+impl ExternalDependencyError {
+    /// The name of the external dependency that could not be found, if any.
+    pub fn dependency_name(&self) -> Option<&str> {
+        match self {
+            Self::NotFound(name)
+            | Self::SuccessfulProbeHeaderNotFound(name, _)
+            | Self::HeaderNotFound(name, _)
+            | Self::LibraryNotFound(name, _) => Some(name),
+            Self::Io(_) => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ExternalDependencyInfo {
     pub(crate) include_dir: Option<PathBuf>,
@@ -50,20 +66,182 @@ fn pkg_config_probe(name: &str) -> Option<Library> {
         .ok()
 }
 
+/// The pkg-config packages to probe for an external dependency, in order.
+fn pkg_config_candidates(name: &str, dependency: &ExternalDependencySpec) -> Vec<String> {
+    let mut candidates = vec![name.to_string(), format!("lib{}", name.to_lowercase())];
+    if let Some(lib_name) = &dependency.library {
+        let lib_name = lib_name.to_string_lossy().to_string();
+        let lib_name_without_ext = lib_name.split('.').next().unwrap_or(&lib_name);
+        candidates.push(lib_name_without_ext.to_string());
+        candidates.push(format!("lib{lib_name_without_ext}"));
+    }
+    candidates
+}
+
+/// Details about how an external dependency was probed.
+#[derive(Debug, Default)]
+struct ProbeTrace {
+    pkg_config_candidates: Vec<String>,
+    pkg_config_package: Option<String>,
+    search_prefixes: Vec<PathBuf>,
+}
+
+/// The result of probing an external dependency, along with how it was probed
+/// and the flags it contributes to C builds, for debugging build failures.
+#[derive(Debug)]
+pub struct ExternalDependencyProbe {
+    name: String,
+    trace: ProbeTrace,
+    result: Result<ExternalDependencyInfo, ExternalDependencyError>,
+    compile_flags: Vec<String>,
+    link_flags: Vec<String>,
+}
+
+impl ExternalDependencyProbe {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn result(&self) -> &Result<ExternalDependencyInfo, ExternalDependencyError> {
+        &self.result
+    }
+
+    /// The pkg-config packages that were tried, in order.
+    pub fn pkg_config_candidates(&self) -> &[String] {
+        &self.trace.pkg_config_candidates
+    }
+
+    /// The pkg-config package that the dependency was found with, if any.
+    pub fn pkg_config_package(&self) -> Option<&str> {
+        self.trace.pkg_config_package.as_deref()
+    }
+
+    /// The prefixes that were searched, if the dependency was not found with pkg-config.
+    pub fn search_prefixes(&self) -> &[PathBuf] {
+        &self.trace.search_prefixes
+    }
+
+    /// The flags that are passed to the C compiler.
+    pub fn compile_flags(&self) -> &[String] {
+        &self.compile_flags
+    }
+
+    /// The flags that are passed to the linker.
+    pub fn link_flags(&self) -> &[String] {
+        &self.link_flags
+    }
+}
+
+impl Display for ExternalDependencyProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        writeln!(
+            f,
+            "  pkg-config: tried {}",
+            self.trace.pkg_config_candidates.join(", ")
+        )?;
+        match &self.trace.pkg_config_package {
+            Some(package) => writeln!(f, "  found with pkg-config package {package}")?,
+            None => {
+                writeln!(
+                    f,
+                    "  not found with pkg-config, falling back to search prefixes"
+                )?;
+                for prefix in &self.trace.search_prefixes {
+                    writeln!(f, "    {}", prefix.display())?;
+                }
+            }
+        }
+        match &self.result {
+            Ok(info) => {
+                let display_dir = |dir: &Option<PathBuf>| {
+                    dir.as_ref()
+                        .map(|dir| dir.display().to_string())
+                        .unwrap_or("not found".into())
+                };
+                writeln!(f, "  include dir: {}", display_dir(&info.include_dir))?;
+                writeln!(f, "  lib dir: {}", display_dir(&info.lib_dir))?;
+                writeln!(f, "  bin dir: {}", display_dir(&info.bin_dir))?;
+                if let Some(lib_name) = &info.lib_name {
+                    writeln!(f, "  library: {lib_name}")?;
+                }
+                writeln!(f, "  compile flags: {}", self.compile_flags.join(" "))?;
+                write!(f, "  link flags: {}", self.link_flags.join(" "))
+            }
+            Err(err) => write!(f, "  error: {err}"),
+        }
+    }
+}
+
+/// The compiler for the host, used to format the link flags.
+fn host_compiler() -> Option<cc::Tool> {
+    let host = Triple::host().to_string();
+    cc::Build::new()
+        .cargo_output(false)
+        .cargo_metadata(false)
+        .cargo_warnings(false)
+        .host(&host)
+        .target(&host)
+        .opt_level(3)
+        .try_get_compiler()
+        .ok()
+}
+
 impl ExternalDependencyInfo {
     pub fn probe(
         name: &str,
         dependency: &ExternalDependencySpec,
         config: &ExternalDependencySearchConfig,
     ) -> Result<Self, ExternalDependencyError> {
-        let lib_info = pkg_config_probe(name)
-            .or(pkg_config_probe(&format!("lib{}", name.to_lowercase())))
-            .or(dependency.library.as_ref().and_then(|lib_name| {
-                let lib_name = lib_name.to_string_lossy().to_string();
-                let lib_name_without_ext = lib_name.split('.').next().unwrap_or(&lib_name);
-                pkg_config_probe(lib_name_without_ext)
-                    .or(pkg_config_probe(&format!("lib{lib_name_without_ext}")))
-            }));
+        Self::probe_impl(name, dependency, config, &mut ProbeTrace::default())
+    }
+
+    /// Probe an external dependency, recording how it was probed.
+    /// Unlike [`ExternalDependencyInfo::probe`], this also records failures.
+    pub fn probe_with_details(
+        name: &str,
+        dependency: &ExternalDependencySpec,
+        config: &ExternalDependencySearchConfig,
+    ) -> ExternalDependencyProbe {
+        let mut trace = ProbeTrace::default();
+        let result = Self::probe_impl(name, dependency, config, &mut trace);
+        let (compile_flags, link_flags) = match &result {
+            Ok(info) => (
+                info.include_dir
+                    .iter()
+                    .map(|dir| format!("-I{}", dir.to_slash_lossy()))
+                    .chain(info.define_flags())
+                    .collect_vec(),
+                host_compiler()
+                    .map(|compiler| info.lib_link_args(&compiler))
+                    .unwrap_or_default(),
+            ),
+            Err(_) => (Vec::new(), Vec::new()),
+        };
+        ExternalDependencyProbe {
+            name: name.to_string(),
+            trace,
+            result,
+            compile_flags,
+            link_flags,
+        }
+    }
+
+    fn probe_impl(
+        name: &str,
+        dependency: &ExternalDependencySpec,
+        config: &ExternalDependencySearchConfig,
+        trace: &mut ProbeTrace,
+    ) -> Result<Self, ExternalDependencyError> {
+        trace.pkg_config_candidates = pkg_config_candidates(name, dependency);
+        let lib_info = trace
+            .pkg_config_candidates
+            .iter()
+            .find_map(|candidate| pkg_config_probe(candidate).map(|info| (candidate, info)))
+            .map(|(candidate, info)| {
+                trace.pkg_config_package = Some(candidate.clone());
+                info
+            });
         if let Some(info) = lib_info {
             let include_dir = if let Some(header) = &dependency.header {
                 Some(
@@ -110,13 +288,14 @@ impl ExternalDependencyInfo {
                 lib_info: Some(info),
             });
         }
-        Self::fallback_probe(name, dependency, config)
+        Self::fallback_probe(name, dependency, config, trace)
     }
 
     fn fallback_probe(
         name: &str,
         dependency: &ExternalDependencySpec,
         config: &ExternalDependencySearchConfig,
+        trace: &mut ProbeTrace,
     ) -> Result<Self, ExternalDependencyError> {
         let env_prefix = std::env::var(format!("{}_DIR", name.to_uppercase())).ok();
 
@@ -127,7 +306,9 @@ impl ExternalDependencyInfo {
         if let Some(prefix) = config.prefixes.get(&format!("{}_DIR", name.to_uppercase())) {
             search_prefixes.push(prefix.clone());
         }
+        search_prefixes.extend(package_manager_prefixes(name, config));
         search_prefixes.extend(config.search_prefixes.iter().cloned());
+        trace.search_prefixes = search_prefixes.clone();
 
         let mut include_dir = get_incdir(name, config);
 
@@ -285,6 +466,38 @@ impl HasVariables for HashMap<String, ExternalDependencyInfo> {
     }
 }
 
+/// Installation prefixes reported by the package managers enabled in the config.
+fn package_manager_prefixes(name: &str, config: &ExternalDependencySearchConfig) -> Vec<PathBuf> {
+    let mut prefixes = Vec::new();
+    if config.homebrew {
+        prefixes.extend(homebrew_prefix(name));
+    }
+    if config.vcpkg {
+        prefixes.extend(
+            config
+                .vcpkg_installed_prefix()
+                .filter(|prefix| prefix.is_dir()),
+        );
+    }
+    prefixes
+}
+
+/// Homebrew installs keg-only formulae (like openssl) outside of the default prefixes,
+/// so we have to ask `brew` where to find them.
+fn homebrew_prefix(name: &str) -> Option<PathBuf> {
+    let output = Command::new("brew")
+        .args(["--prefix", &name.to_lowercase()])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let prefix = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    // `brew --prefix` also prints the prefix of formulae that aren't installed
+    Some(prefix).filter(|prefix| prefix.is_dir())
+}
+
 fn library_exists(lib_dir: &Path, lib: &Path, patterns: &[String]) -> bool {
     patterns.iter().any(|pattern| {
         let file_name = pattern.replace('?', &format!("{}", lib.display()));
@@ -476,6 +689,7 @@ mod tests {
                 library: None,
             },
             &config,
+            &mut ProbeTrace::default(),
         )
         .unwrap();
     }
@@ -501,6 +715,7 @@ mod tests {
                 library: None,
             },
             &config,
+            &mut ProbeTrace::default(),
         )
         .unwrap();
     }
@@ -535,6 +750,7 @@ mod tests {
                 header: None,
             },
             &config,
+            &mut ProbeTrace::default(),
         )
         .unwrap();
     }
@@ -573,6 +789,7 @@ mod tests {
                 header: None,
             },
             &config,
+            &mut ProbeTrace::default(),
         )
         .unwrap();
     }
@@ -605,10 +822,41 @@ mod tests {
                 header: None,
             },
             &config,
+            &mut ProbeTrace::default(),
         )
         .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_fallback_detect_vcpkg() {
+        let temp = TempDir::new().unwrap();
+        let prefix_dir = temp.child("installed").child("test-triplet");
+        let include_dir = prefix_dir.child("include");
+        include_dir.create_dir_all().unwrap();
+
+        let header = include_dir.child("foo.h");
+        header.touch().unwrap();
+
+        let config = ExternalDependencySearchConfig {
+            vcpkg: true,
+            vcpkg_root: Some(temp.path().to_path_buf()),
+            vcpkg_triplet: Some("test-triplet".into()),
+            ..ExternalDependencySearchConfig::default()
+        };
+
+        let info = ExternalDependencyInfo::fallback_probe(
+            "foo",
+            &ExternalDependencySpec {
+                header: Some("foo.h".into()),
+                library: None,
+            },
+            &config,
+            &mut ProbeTrace::default(),
+        )
+        .unwrap();
+        assert_eq!(info.include_dir, Some(include_dir.path().to_path_buf()));
+    }
+
     #[tokio::test]
     async fn test_fallback_detect_not_found() {
         let config = ExternalDependencySearchConfig::default();
@@ -620,6 +868,7 @@ mod tests {
                 library: None,
             },
             &config,
+            &mut ProbeTrace::default(),
         );
 
         assert!(matches!(
@@ -628,6 +877,39 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_probe_with_details() {
+        let temp = TempDir::new().unwrap();
+        let prefix_dir = temp.child("usr");
+        prefix_dir.child("include").child("foo.h").touch().unwrap();
+
+        let config = ExternalDependencySearchConfig {
+            search_prefixes: vec![prefix_dir.path().to_path_buf()],
+            ..ExternalDependencySearchConfig::default()
+        };
+        let probe = ExternalDependencyInfo::probe_with_details(
+            "lux-test-foo",
+            &ExternalDependencySpec {
+                header: Some("foo.h".into()),
+                library: Some("foo".into()),
+            },
+            &config,
+        );
+        assert_eq!(
+            probe.pkg_config_candidates(),
+            &["lux-test-foo", "liblux-test-foo", "foo", "libfoo"]
+        );
+        assert_eq!(probe.pkg_config_package(), None);
+        assert!(probe
+            .search_prefixes()
+            .contains(&prefix_dir.path().to_path_buf()));
+        assert!(matches!(
+            probe.result(),
+            Err(ExternalDependencyError::LibraryNotFound { .. })
+        ));
+        assert!(probe.to_string().contains("error:"));
+    }
+
     #[cfg(not(target_env = "msvc"))]
     #[tokio::test]
     async fn test_to_lib_name() {
@@ -0,0 +1,116 @@
+use std::{io, path::Path};
+
+use itertools::Itertools;
+
+const TAGS_FILE: &str = "tags";
+
+/// Generate a Vim help `tags` file for the `*.txt` help files in `doc_dir`,
+/// equivalent to running `:helptags` in Vim.
+/// Does nothing if there are no help files.
+pub(crate) fn generate_helptags(doc_dir: &Path) -> io::Result<()> {
+    if !doc_dir.is_dir() {
+        return Ok(());
+    }
+    let mut tags = Vec::new();
+    for entry in std::fs::read_dir(doc_dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "txt") {
+            continue;
+        }
+        let file_name = path
+            .file_name()
+            .expect("no file name")
+            .to_string_lossy()
+            .to_string();
+        let content = String::from_utf8_lossy(&std::fs::read(&path)?).to_string();
+        tags.extend(
+            content
+                .lines()
+                .flat_map(parse_tags)
+                .map(|tag| (tag.to_string(), file_name.clone())),
+        );
+    }
+    if tags.is_empty() {
+        return Ok(());
+    }
+    let content = tags
+        .into_iter()
+        .sorted()
+        .dedup_by(|(a, _), (b, _)| a == b)
+        .map(|(tag, file_name)| format!("{tag}\t{file_name}\t/*{}*\n", escape_pattern(&tag)))
+        .collect::<String>();
+    std::fs::write(doc_dir.join(TAGS_FILE), content)
+}
+
+/// Find the `*tag*` definitions in a line of a help file.
+/// A tag definition must be preceded by whitespace or the start of the line,
+/// and followed by whitespace or the end of the line.
+fn parse_tags(line: &str) -> Vec<&str> {
+    let mut tags = Vec::new();
+    let bytes = line.as_bytes();
+    let mut start = None;
+    for (i, byte) in bytes.iter().enumerate() {
+        match (byte, start) {
+            (b'*', None) if i == 0 || bytes[i - 1].is_ascii_whitespace() => start = Some(i + 1),
+            (b'*', Some(tag_start)) => {
+                let is_end = bytes.get(i + 1).is_none_or(|b| b.is_ascii_whitespace());
+                if is_end && i > tag_start {
+                    tags.push(&line[tag_start..i]);
+                    start = None;
+                } else {
+                    // This may be the start of another tag
+                    start = if bytes[i - 1].is_ascii_whitespace() {
+                        Some(i + 1)
+                    } else {
+                        None
+                    };
+                }
+            }
+            (b'|', Some(_)) => start = None,
+            (b, Some(_)) if b.is_ascii_whitespace() => start = None,
+            _ => {}
+        }
+    }
+    tags
+}
+
+fn escape_pattern(tag: &str) -> String {
+    tag.replace('\\', "\\\\").replace('/', "\\/")
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn parse_help_tags() {
+        assert_eq!(parse_tags("*foo.txt*  Foo plugin"), vec!["foo.txt"]);
+        assert_eq!(
+            parse_tags("Setup                                   *foo-setup* *foo.setup()*"),
+            vec!["foo-setup", "foo.setup()"]
+        );
+        assert!(parse_tags("a * b * c").is_empty());
+        assert!(parse_tags("see |foo-setup| and 2*3*4").is_empty());
+    }
+
+    #[test]
+    fn generate_tags_file() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("foo.txt")
+            .write_str("*foo.txt*  Foo\n\nUsage    *foo-usage*\nSee |foo-usage|.\n")
+            .unwrap();
+        temp.child("bar.txt")
+            .write_str("*bar.txt*\n*bar/baz*\n")
+            .unwrap();
+        temp.child("README.md").write_str("*not-a-tag*").unwrap();
+        generate_helptags(temp.path()).unwrap();
+        temp.child("tags").assert(
+            "bar.txt\tbar.txt\t/*bar.txt*\n\
+             bar/baz\tbar.txt\t/*bar\\/baz*\n\
+             foo-usage\tfoo.txt\t/*foo-usage*\n\
+             foo.txt\tfoo.txt\t/*foo.txt*\n",
+        );
+    }
+}
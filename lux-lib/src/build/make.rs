@@ -11,10 +11,11 @@ use tokio::process::Command;
 use crate::{
     build::{
         backend::{BuildBackend, BuildInfo, RunBuildArgs},
-        utils,
+        compiler_cache, utils,
     },
     lua_rockspec::MakeBuildSpec,
     path::{Paths, PathsError},
+    process::{self, ProcessLimits},
     tree::TreeError,
     variables::VariableSubstitutionError,
 };
@@ -52,59 +53,73 @@ impl BuildBackend for MakeBuildSpec {
         let external_dependencies = args.external_dependencies;
         let config = args.config;
         let build_dir = args.build_dir;
+        let log = args.log;
 
         let build_tree = args.tree.build_tree(config)?;
         let build_paths = Paths::new(&build_tree)?;
         let lua_path = build_paths.package_path_prepended().joined();
         let lua_cpath = build_paths.package_cpath_prepended().joined();
         let bin_path = build_paths.path_prepended().joined();
+        let limits = ProcessLimits::build(config);
+        // The rockspec's variables take precedence, because make uses the last assignment.
+        let toolchain_args = config
+            .toolchain_variables()
+            .into_iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .chain(
+                compiler_cache::compiler_variables(config)
+                    .into_iter()
+                    .map(|(key, value)| format!("{key}={value}")),
+            )
+            .collect_vec();
 
         // Build step
         if self.build_pass {
-            let build_args = self
-                .variables
+            let build_args = toolchain_args
                 .iter()
-                .chain(&self.build_variables)
-                .filter(|(_, value)| !value.is_empty())
-                .map(|(key, value)| {
-                    let substituted_value = utils::substitute_variables(
-                        value,
-                        output_paths,
-                        lua,
-                        external_dependencies,
-                        config,
-                    )?;
-                    Ok(format!("{key}={substituted_value}").trim().to_string())
-                })
+                .cloned()
+                .map(Ok)
+                .chain(
+                    self.variables
+                        .iter()
+                        .chain(&self.build_variables)
+                        .filter(|(_, value)| !value.is_empty())
+                        .map(|(key, value)| {
+                            let substituted_value = utils::substitute_variables(
+                                value,
+                                output_paths,
+                                lua,
+                                external_dependencies,
+                                config,
+                            )?;
+                            Ok(format!("{key}={substituted_value}").trim().to_string())
+                        }),
+                )
                 .try_collect::<_, Vec<_>, Self::Err>()?;
+            let name = match &self.build_target {
+                Some(build_target) => format!("{} {}", config.make_cmd(), build_target),
+                None => config.make_cmd(),
+            };
             let mut cmd = Command::new(config.make_cmd());
             if let Some(build_target) = &self.build_target {
                 cmd.arg(build_target);
             }
-            match cmd
-                .current_dir(build_dir)
-                .args(["-f", &self.makefile.to_slash_lossy()])
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .args(build_args)
-                .env("PATH", &bin_path)
-                .env("LUA_PATH", &lua_path)
-                .env("LUA_CPATH", &lua_cpath)
-                .spawn()
-            {
-                Ok(child) => match child.wait_with_output().await {
-                    Ok(output) if output.status.success() => {
-                        utils::log_command_output(&output, config)
-                    }
+            match process::spawn(
+                cmd.current_dir(build_dir)
+                    .args(["-f", &self.makefile.to_slash_lossy()])
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .args(build_args)
+                    .env("PATH", &bin_path)
+                    .env("LUA_PATH", &lua_path)
+                    .env("LUA_CPATH", &lua_cpath),
+                &limits,
+            ) {
+                Ok(child) => match log.wait_with_output(child, &name).await {
+                    Ok(output) if output.status.success() => {}
                     Ok(output) => {
                         return Err(MakeError::CommandFailure {
-                            name: match self.build_target {
-                                Some(build_target) => {
-                                    format!("{} {}", config.make_cmd(), build_target)
-                                }
-                                None => config.make_cmd(),
-                            },
-
+                            name,
                             status: output.status,
                             stdout: String::from_utf8_lossy(&output.stdout).into(),
                             stderr: String::from_utf8_lossy(&output.stderr).into(),
@@ -118,37 +133,49 @@ impl BuildBackend for MakeBuildSpec {
 
         // Install step
         if self.install_pass && !no_install {
-            let install_args = self
-                .variables
+            let install_args = toolchain_args
                 .iter()
-                .chain(&self.install_variables)
-                .filter(|(_, value)| !value.is_empty())
-                .map(|(key, value)| {
-                    let substituted_value = utils::substitute_variables(
-                        value,
-                        output_paths,
-                        lua,
-                        external_dependencies,
-                        config,
-                    )?;
-                    Ok(format!("{key}={substituted_value}").trim().to_string())
-                })
+                .cloned()
+                .map(Ok)
+                .chain(
+                    self.variables
+                        .iter()
+                        .chain(&self.install_variables)
+                        .filter(|(_, value)| !value.is_empty())
+                        .map(|(key, value)| {
+                            let substituted_value = utils::substitute_variables(
+                                value,
+                                output_paths,
+                                lua,
+                                external_dependencies,
+                                config,
+                            )?;
+                            Ok(format!("{key}={substituted_value}").trim().to_string())
+                        }),
+                )
                 .try_collect::<_, Vec<_>, Self::Err>()?;
-            match Command::new(config.make_cmd())
-                .current_dir(build_dir)
-                .arg(&self.install_target)
-                .args(["-f", &self.makefile.to_slash_lossy()])
-                .args(install_args)
-                .env("PATH", &bin_path)
-                .env("LUA_PATH", &lua_path)
-                .env("LUA_CPATH", &lua_cpath)
-                .output()
-                .await
-            {
-                Ok(output) if output.status.success() => utils::log_command_output(&output, config),
+            let name = format!("{} {}", config.make_cmd(), self.install_target);
+            let output = match process::spawn(
+                Command::new(config.make_cmd())
+                    .current_dir(build_dir)
+                    .arg(&self.install_target)
+                    .args(["-f", &self.makefile.to_slash_lossy()])
+                    .args(install_args)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .env("PATH", &bin_path)
+                    .env("LUA_PATH", &lua_path)
+                    .env("LUA_CPATH", &lua_cpath),
+                &limits,
+            ) {
+                Ok(child) => log.wait_with_output(child, &name).await,
+                Err(err) => Err(err),
+            };
+            match output {
+                Ok(output) if output.status.success() => {}
                 Ok(output) => {
                     return Err(MakeError::CommandFailure {
-                        name: format!("{} {}", config.make_cmd(), self.install_target),
+                        name,
                         status: output.status,
                         stdout: String::from_utf8_lossy(&output.stdout).into(),
                         stderr: String::from_utf8_lossy(&output.stderr).into(),
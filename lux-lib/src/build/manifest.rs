@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use ssri::Integrity;
+
+use crate::{
+    build::{
+        compiler_cache::CompilerCache, debug_symbols::DebugSymbols, sanitizer::Sanitizer,
+        utils::project_files,
+    },
+    config::{toolchain::Toolchain, Config},
+    hash::HasIntegrity,
+    project::PROJECT_TOML,
+};
+
+/// Content hashes of a project's files, recorded after a successful build.
+/// Used to skip rebuilding modules whose sources have not changed.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct BuildManifest {
+    files: HashMap<PathBuf, Integrity>,
+    #[serde(default)]
+    native_config: NativeBuildConfig,
+}
+
+/// The parts of the config that affect how C modules are compiled.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct NativeBuildConfig {
+    toolchain: Option<Toolchain>,
+    sanitizers: Vec<Sanitizer>,
+    debug_symbols: DebugSymbols,
+    compiler_cache: Option<CompilerCache>,
+}
+
+impl From<&Config> for NativeBuildConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            toolchain: config.toolchain().cloned(),
+            sanitizers: config
+                .sanitizers()
+                .iter()
+                .copied()
+                .sorted()
+                .dedup()
+                .collect(),
+            debug_symbols: config.debug_symbols(),
+            compiler_cache: config.compiler_cache(),
+        }
+    }
+}
+
+impl BuildManifest {
+    /// Hash the project files in `root`, keyed by their path relative to `root`,
+    /// and record the `config` that C modules are compiled with.
+    pub(crate) fn from_project_files(root: &Path, config: &Config) -> io::Result<Self> {
+        let files = project_files(&root.to_path_buf())
+            .into_iter()
+            .filter(|file| file.is_file())
+            .map(|file| {
+                let relative_path = pathdiff::diff_paths(&file, root).unwrap_or(file.clone());
+                Ok((relative_path, file.hash()?))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            files,
+            native_config: config.into(),
+        })
+    }
+
+    /// Returns `None` if the manifest does not exist or cannot be parsed.
+    pub(crate) fn load(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Compute the files that have been added, modified or removed since `previous`.
+    /// Returns `None` if the project's manifest has changed,
+    /// in which case everything must be rebuilt.
+    /// If the config that C modules are compiled with has changed, all C modules must be rebuilt.
+    pub(crate) fn changes_since(&self, previous: &Self) -> Option<ProjectChanges> {
+        let project_toml = PathBuf::from(PROJECT_TOML);
+        if self.files.get(&project_toml) != previous.files.get(&project_toml) {
+            return None;
+        }
+        let changed = self
+            .files
+            .keys()
+            .chain(previous.files.keys())
+            .filter(|file| self.files.get(*file) != previous.files.get(*file))
+            .cloned()
+            .collect();
+        let removed = previous
+            .files
+            .keys()
+            .filter(|file| !self.files.contains_key(*file))
+            .cloned()
+            .collect();
+        Some(ProjectChanges {
+            changed,
+            removed,
+            native_config_changed: self.native_config != previous.native_config,
+        })
+    }
+}
+
+/// Project files that have changed since the last build,
+/// relative to the project root.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProjectChanges {
+    changed: HashSet<PathBuf>,
+    removed: HashSet<PathBuf>,
+    native_config_changed: bool,
+}
+
+impl ProjectChanges {
+    pub(crate) fn is_unchanged(&self, file: &Path) -> bool {
+        !self.changed.contains(file)
+    }
+
+    /// Files that have been deleted from the project since the last build.
+    pub(crate) fn removed(&self) -> impl Iterator<Item = &PathBuf> {
+        self.removed.iter()
+    }
+
+    /// Whether any files that may affect C compilation (i.e. non-Lua files)
+    /// or the config that C modules are compiled with have changed.
+    pub(crate) fn has_native_changes(&self) -> bool {
+        self.native_config_changed
+            || self
+                .changed
+                .iter()
+                .any(|file| file.extension().is_none_or(|ext| ext != "lua"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::*;
+
+    use crate::config::ConfigBuilder;
+
+    use super::*;
+
+    #[test]
+    fn build_manifest_changes() {
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child(PROJECT_TOML)
+            .write_str("package = \"foo\"")
+            .unwrap();
+        temp.child("src/foo.lua").write_str("return {}").unwrap();
+        temp.child("src/bar.lua").write_str("return {}").unwrap();
+        temp.child("csrc/baz.c").write_str("int x;").unwrap();
+        let previous = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+
+        let manifest_path = temp.path().join(".lux/build-manifest.json");
+        previous.write(&manifest_path).unwrap();
+        assert_eq!(BuildManifest::load(&manifest_path).unwrap(), previous);
+
+        temp.child("src/foo.lua").write_str("return { 1 }").unwrap();
+        let current = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+        let changes = current.changes_since(&previous).unwrap();
+        assert!(!changes.is_unchanged(Path::new("src/foo.lua")));
+        assert!(changes.is_unchanged(Path::new("src/bar.lua")));
+        assert!(!changes.has_native_changes());
+        assert_eq!(changes.removed().count(), 0);
+
+        std::fs::remove_file(temp.path().join("src/bar.lua")).unwrap();
+        let current = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+        let changes = current.changes_since(&previous).unwrap();
+        assert_eq!(
+            changes.removed().collect::<Vec<_>>(),
+            vec![&PathBuf::from("src/bar.lua")]
+        );
+
+        temp.child("csrc/baz.h").write_str("int y;").unwrap();
+        let current = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+        assert!(current
+            .changes_since(&previous)
+            .unwrap()
+            .has_native_changes());
+
+        temp.child(PROJECT_TOML)
+            .write_str("package = \"bar\"")
+            .unwrap();
+        let current = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+        assert!(current.changes_since(&previous).is_none());
+    }
+
+    #[test]
+    fn build_manifest_native_config_changes() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("src/foo.lua").write_str("return {}").unwrap();
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let previous = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+        let current = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+        assert!(!current
+            .changes_since(&previous)
+            .unwrap()
+            .has_native_changes());
+
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .debug_symbols(Some(DebugSymbols::Strip))
+            .build()
+            .unwrap();
+        let current = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+        let changes = current.changes_since(&previous).unwrap();
+        assert!(changes.has_native_changes());
+        assert!(changes.is_unchanged(Path::new("src/foo.lua")));
+    }
+
+    #[test]
+    fn build_manifest_sanitizer_changes() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("csrc/foo.c").write_str("int x;").unwrap();
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let previous = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+
+        let config = config.with_sanitizers(vec![Sanitizer::Undefined, Sanitizer::Address]);
+        let instrumented = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+        assert!(instrumented
+            .changes_since(&previous)
+            .unwrap()
+            .has_native_changes());
+
+        let config = config.with_sanitizers(vec![Sanitizer::Address, Sanitizer::Undefined]);
+        let current = BuildManifest::from_project_files(temp.path(), &config).unwrap();
+        assert!(!current
+            .changes_since(&instrumented)
+            .unwrap()
+            .has_native_changes());
+        assert!(previous
+            .changes_since(&current)
+            .unwrap()
+            .has_native_changes());
+    }
+}
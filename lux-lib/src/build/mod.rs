@@ -2,12 +2,18 @@ use crate::build::backend::{BuildBackend, BuildInfo, RunBuildArgs};
 use crate::lockfile::{LockfileError, OptState, RemotePackageSourceUrl};
 use crate::lua_installation::LuaInstallationError;
 use crate::lua_rockspec::LuaVersionError;
-use crate::operations::{RemotePackageSourceMetadata, UnpackError};
+use crate::observer::OperationObserver;
+use crate::operations::{
+    run_cancellable, CancellationToken, Cancelled, RemotePackageSourceMetadata, UnpackError,
+};
 use crate::rockspec::{LuaVersionCompatibility, Rockspec};
+use crate::timings::{RecordTiming, TimingPhase, Timings};
 use crate::tree::{self, EntryType, TreeError};
 use bytes::Bytes;
 use std::collections::HashMap;
 use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Instant;
 use std::{io, path::Path};
 
 use crate::{
@@ -20,13 +26,18 @@ use crate::{
     package::PackageSpec,
     progress::{Progress, ProgressBar},
     remote_package_source::RemotePackageSource,
-    tree::{RockLayout, Tree},
+    tree::{InstalledFiles, RockLayout, Tree},
 };
 use bon::{builder, Builder};
+use build_log::BuildLog;
 use builtin::BuiltinBuildError;
 use cmake::CMakeError;
 use command::CommandError;
+use compile_commands::CompileCommands;
+use compiler_cache::CompilerCacheNotFound;
+use debug_symbols::{DebugSymbols, DebugSymbolsError};
 use external_dependency::{ExternalDependencyError, ExternalDependencyInfo};
+use manifest::ProjectChanges;
 
 use indicatif::style::TemplateError;
 use itertools::Itertools;
@@ -34,9 +45,11 @@ use luarocks::LuarocksBuildError;
 use make::MakeError;
 use mlua::FromLua;
 use patch::{Patch, PatchError};
+use provenance::BuildProvenance;
 use rust_mlua::RustError;
 use source::SourceBuildError;
 use ssri::Integrity;
+use system_package::SystemPackageHint;
 use thiserror::Error;
 use treesitter_parser::TreesitterBuildError;
 use utils::{recursive_copy_dir, CompileCFilesError, InstallBinaryError};
@@ -44,17 +57,26 @@ use utils::{recursive_copy_dir, CompileCFilesError, InstallBinaryError};
 mod builtin;
 mod cmake;
 mod command;
+mod helptags;
 mod luarocks;
 mod make;
+pub(crate) mod manifest;
 mod patch;
 mod rust_mlua;
 mod source;
 mod treesitter_parser;
 
 pub(crate) mod backend;
+pub mod build_log;
 pub(crate) mod utils;
 
+pub mod compile_commands;
+pub mod compiler_cache;
+pub mod debug_symbols;
 pub mod external_dependency;
+pub mod provenance;
+pub mod sanitizer;
+pub mod system_package;
 
 /// A rocks package builder, providing fine-grained control
 /// over how a package should be built.
@@ -76,12 +98,43 @@ pub struct Build<'a, R: Rockspec + HasIntegrity> {
     constraint: LockConstraint,
     #[builder(default)]
     behaviour: BuildBehaviour,
+    /// Variables that take precedence over the config's variables for this build.
+    #[builder(default)]
+    variables: HashMap<String, String>,
+    /// What to do with the debug symbols of this package's C modules,
+    /// taking precedence over the config's `debug_symbols`.
+    debug_symbols: Option<DebugSymbols>,
+    /// Environment variables that this package's build processes may inherit,
+    /// in addition to the config's `build_env_allowlist`.
+    build_env: Option<Vec<String>>,
+    /// Record the time spent in each build phase.
+    timings: Option<Timings>,
+    /// Record the commands that compile C sources.
+    compile_commands: Option<CompileCommands>,
+    /// Notified when the package has been fetched and built, or when the build fails.
+    observer: Option<Arc<dyn OperationObserver>>,
 
     #[builder(setters(vis = "pub(crate)"))]
     source_spec: Option<RemotePackageSourceSpec>,
 
+    /// Project files that have changed since the last build, for incremental builds.
+    #[builder(setters(vis = "pub(crate)"))]
+    project_changes: Option<ProjectChanges>,
+
+    /// Symlink Lua sources from this directory instead of copying them.
+    #[builder(setters(vis = "pub(crate)"))]
+    dev_link_root: Option<&'a Path>,
+
+    /// The directory that the sources were copied from, e.g. the project root.
+    /// Recorded compile commands refer to the sources in this directory.
+    #[builder(setters(vis = "pub(crate)"))]
+    source_root: Option<&'a Path>,
+
     // TODO(vhyrro): Remove this and enforce that this is provided at a type level.
     source: Option<RemotePackageSource>,
+
+    /// Abort the build, killing its child processes, when cancelled.
+    cancellation: Option<CancellationToken>,
 }
 
 pub(crate) enum RemotePackageSourceSpec {
@@ -101,12 +154,25 @@ where
     State: build_builder::State + build_builder::IsComplete,
 {
     pub async fn build(self) -> Result<LocalPackage, BuildError> {
-        do_build(self._build()).await
+        let build = self._build();
+        let observer = build.observer.clone();
+        let package_spec = PackageSpec::new(
+            build.rockspec.package().clone(),
+            build.rockspec.version().clone(),
+        );
+        let result = run_cancellable(build.cancellation.clone(), do_build(build)).await;
+        match &result {
+            Ok(package) => observer.package_built(package),
+            Err(err) => observer.package_failed(&package_spec, err),
+        }
+        result
     }
 }
 
 #[derive(Error, Debug)]
 pub enum BuildError {
+    #[error("build cancelled")]
+    Cancelled(#[from] Cancelled),
     #[error("builtin build failed: {0}")]
     Builtin(#[from] BuiltinBuildError),
     #[error("cmake build failed: {0}")]
@@ -133,6 +199,8 @@ pub enum BuildError {
     SpinnerFailure(#[from] TemplateError),
     #[error(transparent)]
     ExternalDependencyError(#[from] ExternalDependencyError),
+    #[error("{0}\n{1}")]
+    MissingSystemPackage(ExternalDependencyError, SystemPackageHint),
     #[error(transparent)]
     PatchError(#[from] PatchError),
     #[error(transparent)]
@@ -152,6 +220,10 @@ pub enum BuildError {
     InstallBinary(String, InstallBinaryError),
     #[error(transparent)]
     LuaInstallation(#[from] LuaInstallationError),
+    #[error(transparent)]
+    CompilerCacheNotFound(#[from] CompilerCacheNotFound),
+    #[error(transparent)]
+    DebugSymbols(#[from] DebugSymbolsError),
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -219,6 +291,7 @@ async fn install<R: Rockspec + HasIntegrity>(
     entry_type: &EntryType,
     progress: &Progress<ProgressBar>,
     config: &Config,
+    compile_commands: Option<&CompileCommands>,
 ) -> Result<(), BuildError> {
     progress.map(|p| {
         p.set_message(format!(
@@ -255,6 +328,7 @@ async fn install<R: Rockspec + HasIntegrity>(
             lua,
             external_dependencies,
             config,
+            compile_commands,
         )
         .await?;
         progress.map(|p| p.set_position(p.position() + 1));
@@ -311,10 +385,30 @@ where
 
     rockspec.validate_lua_version(&lua.version)?;
 
+    let config = build
+        .config
+        .clone()
+        .with_package_toolchain(rockspec.build().current_platform().toolchain.as_ref())
+        .with_variables(build.variables);
+    let config = match build.debug_symbols {
+        Some(debug_symbols) => config.with_debug_symbols(debug_symbols),
+        None => config,
+    };
+    let config = &match build.build_env {
+        Some(build_env) => config.with_build_env(build_env),
+        None => config,
+    };
+    if let Some(compiler_cache) = config.compiler_cache() {
+        compiler_cache.check()?;
+    }
+
     let tree = build.tree;
 
     let temp_dir = tempdir::TempDir::new(&rockspec.package().to_string())?;
 
+    let package_spec = PackageSpec::new(rockspec.package().clone(), rockspec.version().clone());
+
+    let fetch_start = Instant::now();
     let source_metadata = match build.source_spec {
         Some(RemotePackageSourceSpec::SrcRock(SrcRockSource { bytes, source_url })) => {
             let hash = bytes.hash()?;
@@ -325,25 +419,30 @@ where
             RemotePackageSourceMetadata { hash, source_url }
         }
         Some(RemotePackageSourceSpec::RockSpec(source_url)) => {
-            operations::FetchSrc::new(temp_dir.path(), rockspec, build.config, build.progress)
+            operations::FetchSrc::new(temp_dir.path(), rockspec, config, build.progress)
                 .maybe_source_url(source_url)
                 .fetch_internal()
                 .await?
         }
         None => {
-            operations::FetchSrc::new(temp_dir.path(), rockspec, build.config, build.progress)
+            operations::FetchSrc::new(temp_dir.path(), rockspec, config, build.progress)
                 .fetch_internal()
                 .await?
         }
     };
 
+    build
+        .timings
+        .record(TimingPhase::Fetch, Some(&package_spec), fetch_start);
+    build.observer.package_fetched(&package_spec);
+
     let hashes = LocalPackageHashes {
         rockspec: rockspec.hash()?,
         source: source_metadata.hash.clone(),
     };
 
     let mut package = LocalPackage::from(
-        &PackageSpec::new(rockspec.package().clone(), rockspec.version().clone()),
+        &package_spec,
         build.constraint,
         rockspec.binaries(),
         build
@@ -360,6 +459,7 @@ where
     );
     package.spec.pinned = build.pin;
     package.spec.opt = build.opt;
+    package.provenance = Some(BuildProvenance::new(config));
 
     match tree.lockfile()?.get(&package.id()) {
         Some(package) if build.behaviour == BuildBehaviour::NoForce => Ok(package.clone()),
@@ -415,11 +515,41 @@ where
                 .current_platform()
                 .iter()
                 .map(|(name, dep)| {
-                    ExternalDependencyInfo::probe(name, dep, build.config.external_deps())
+                    ExternalDependencyInfo::probe(name, dep, config.external_deps())
                         .map(|info| (name.clone(), info))
                 })
-                .try_collect::<_, HashMap<_, _>, _>()?;
+                .try_collect::<_, HashMap<_, _>, _>()
+                .map_err(|err| {
+                    match err
+                        .dependency_name()
+                        .and_then(|name| SystemPackageHint::new(name, config.external_deps()))
+                    {
+                        Some(hint) => BuildError::MissingSystemPackage(err, hint),
+                        None => BuildError::ExternalDependencyError(err),
+                    }
+                })?;
+
+            // The build directory is deleted after the build, so we only record
+            // the compile commands of dependencies if we can keep their sources.
+            let compile_commands_root = match (&build.compile_commands, build.source_root) {
+                (Some(_), Some(source_root)) => Some(source_root.to_path_buf()),
+                (Some(compile_commands), None) => {
+                    compile_commands.dependency_sources().map(|dir| {
+                        dir.join(format!(
+                            "{}@{}",
+                            package_spec.name(),
+                            package_spec.version()
+                        ))
+                    })
+                }
+                (None, _) => None,
+            };
+            let build_compile_commands = compile_commands_root
+                .as_ref()
+                .map(|_| CompileCommands::new());
 
+            let log = BuildLog::new(&package_spec, build.progress, config);
+            let compile_start = Instant::now();
             let output = run_build(
                 rockspec,
                 RunBuildArgs::new()
@@ -428,16 +558,30 @@ where
                     .lua(lua)
                     .external_dependencies(&external_dependencies)
                     .deploy(rockspec.deploy().current_platform())
-                    .config(build.config)
+                    .config(config)
                     .tree(tree)
                     .build_dir(&build_dir)
                     .progress(build.progress)
+                    .log(&log)
+                    .maybe_project_changes(build.project_changes.as_ref())
+                    .maybe_dev_link_root(build.dev_link_root)
+                    .maybe_compile_commands(build_compile_commands.as_ref())
                     .build(),
             )
-            .await?;
+            .await
+            .inspect_err(|_| {
+                build
+                    .progress
+                    .map(|p| p.println(format!("📄 Full build log: {}", log.path().display())));
+            })?;
+
+            build
+                .timings
+                .record(TimingPhase::Compile, Some(&package_spec), compile_start);
 
             package.spec.binaries.extend(output.binaries);
 
+            let install_start = Instant::now();
             install(
                 rockspec,
                 tree,
@@ -447,10 +591,40 @@ where
                 &build_dir,
                 &build.entry_type,
                 build.progress,
-                build.config,
+                config,
+                build_compile_commands.as_ref(),
             )
             .await?;
 
+            debug_symbols::process_debug_symbols(
+                &output_paths.lib,
+                &output_paths.etc.join(debug_symbols::DEBUG_SYMBOLS_DIR_NAME),
+                config,
+            )
+            .await?;
+
+            if is_instrumented(rockspec, &output_paths, config) {
+                package.provenance = package
+                    .provenance
+                    .map(|provenance| provenance.with_sanitizers(config.sanitizers().to_vec()));
+            }
+
+            if let (Some(compile_commands), Some(recorded), Some(root)) = (
+                &build.compile_commands,
+                build_compile_commands,
+                compile_commands_root,
+            ) {
+                if !recorded.is_empty() {
+                    if build.source_root.is_none() {
+                        if root.exists() {
+                            tokio::fs::remove_dir_all(&root).await?;
+                        }
+                        recursive_copy_dir(&build_dir, &root).await?;
+                    }
+                    compile_commands.extend_relocated(recorded, &build_dir, &root);
+                }
+            }
+
             for directory in rockspec
                 .build()
                 .current_platform()
@@ -470,15 +644,46 @@ where
 
             recursive_copy_doc_dir(&output_paths, &build_dir).await?;
 
+            if rockspec.deploy().current_platform().generate_helptags {
+                helptags::generate_helptags(&output_paths.doc)?;
+            }
+
             if let Ok(rockspec_str) = rockspec.to_lua_remote_rockspec_string() {
                 std::fs::write(output_paths.rockspec_path(), rockspec_str)?;
             }
 
+            if config.relocatable() {
+                utils::make_pkg_config_files_relocatable(&output_paths)?;
+            }
+
+            let binaries = if build.entry_type.is_entrypoint() {
+                package.spec.binaries.iter().collect_vec()
+            } else {
+                Vec::new()
+            };
+            InstalledFiles::collect(tree, &output_paths, binaries)?.write(&output_paths)?;
+
+            build
+                .timings
+                .record(TimingPhase::Install, Some(&package_spec), install_start);
+
             Ok(package)
         }
     }
 }
 
+/// Whether the C modules that were built for `rockspec` are instrumented with sanitizers.
+/// Only the builtin backend and the rockspec's `install.lib` instrument C modules.
+/// Incremental builds only reuse C modules that were compiled with the same sanitizers,
+/// as changing them is a native change in the build manifest.
+fn is_instrumented<R: Rockspec>(rockspec: &R, output_paths: &RockLayout, config: &Config) -> bool {
+    let build_spec = rockspec.build().current_platform();
+    !config.sanitizers().is_empty()
+        && (matches!(build_spec.build_backend, Some(BuildBackendSpec::Builtin(_)))
+            || !build_spec.install.lib.is_empty())
+        && !debug_symbols::shared_libraries(&output_paths.lib).is_empty()
+}
+
 async fn recursive_copy_doc_dir(
     output_paths: &RockLayout,
     build_dir: &Path,
@@ -546,6 +751,9 @@ mod tests {
         let project = Project::from(&project_root).unwrap().unwrap();
         let rockspec = project.toml().into_remote().unwrap();
         let progress = Progress::Progress(MultiProgress::new());
+        let bar = progress.map(|p| p.new_bar());
+        let package = PackageSpec::new(rockspec.package().clone(), rockspec.version().clone());
+        let log = BuildLog::new(&package, &bar, &config);
         run_build(
             &rockspec,
             RunBuildArgs::new()
@@ -557,7 +765,8 @@ mod tests {
                 .config(&config)
                 .tree(&tree)
                 .build_dir(&build_dir)
-                .progress(&progress.map(|p| p.new_bar()))
+                .progress(&bar)
+                .log(&log)
                 .build(),
         )
         .await
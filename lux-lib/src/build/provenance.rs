@@ -0,0 +1,192 @@
+use std::{collections::BTreeMap, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use ssri::Integrity;
+use target_lexicon::Triple;
+
+use crate::{
+    build::{sanitizer::Sanitizer, utils::c_compiler_identity},
+    config::Config,
+    lockfile::{LocalPackage, RemotePackageSourceUrl},
+    variables::HasVariables,
+};
+
+/// The name of the file that holds the build provenance in a packed rock.
+pub(crate) const PROVENANCE_FILE_NAME: &str = "provenance.json";
+
+/// The build variables that are recorded in the build provenance.
+const RECORDED_BUILD_FLAGS: [&str; 2] = ["CFLAGS", "LIBFLAG"];
+
+/// Records how a package was built, for supply-chain auditing.
+/// The source URL and integrity are recorded alongside it in the lockfile.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct BuildProvenance {
+    /// The version of lux that built the package.
+    lux_version: String,
+    /// The target triple of the host that built the package.
+    platform: String,
+    /// The family and version of the C compiler that was available to the build, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compiler: Option<String>,
+    #[serde(default)]
+    build_flags: BTreeMap<String, String>,
+    /// The sanitizers that the package's C modules are instrumented with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sanitizers: Vec<Sanitizer>,
+}
+
+impl BuildProvenance {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            lux_version: env!("CARGO_PKG_VERSION").into(),
+            platform: Triple::host().to_string(),
+            compiler: c_compiler_identity(config),
+            build_flags: RECORDED_BUILD_FLAGS
+                .into_iter()
+                .filter_map(|name| {
+                    config
+                        .get_variable(name)
+                        .ok()
+                        .flatten()
+                        .map(|value| (name.to_string(), value))
+                })
+                .collect(),
+            sanitizers: Vec::new(),
+        }
+    }
+
+    /// Record that the package's C modules are instrumented with `sanitizers`.
+    pub(crate) fn with_sanitizers(self, sanitizers: Vec<Sanitizer>) -> Self {
+        Self { sanitizers, ..self }
+    }
+
+    pub fn lux_version(&self) -> &str {
+        &self.lux_version
+    }
+
+    pub fn platform(&self) -> &str {
+        &self.platform
+    }
+
+    pub fn compiler(&self) -> Option<&str> {
+        self.compiler.as_deref()
+    }
+
+    pub fn build_flags(&self) -> &BTreeMap<String, String> {
+        &self.build_flags
+    }
+
+    pub fn sanitizers(&self) -> &[Sanitizer] {
+        &self.sanitizers
+    }
+}
+
+/// Creates an [in-toto](https://in-toto.io) statement with a
+/// [SLSA provenance](https://slsa.dev/provenance/v1) predicate for a packed rock.
+pub(crate) fn attestation(package: &LocalPackage, artifact: &Path) -> io::Result<String> {
+    let artifact_digest = hex::encode(Sha256::digest(std::fs::read(artifact)?));
+    let artifact_name = artifact
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let resolved_dependencies = package
+        .source_url
+        .as_ref()
+        .map(|source_url| {
+            json!({
+                "uri": source_uri(source_url),
+                "digest": digest(&package.hashes().source),
+            })
+        })
+        .into_iter()
+        .chain(Some(json!({
+            "uri": format!("{}-{}.rockspec", package.name(), package.version()),
+            "digest": digest(&package.hashes().rockspec),
+        })))
+        .collect::<Vec<_>>();
+    let provenance = package.provenance();
+    let statement = json!({
+        "_type": "https://in-toto.io/Statement/v1",
+        "subject": [{
+            "name": artifact_name,
+            "digest": { "sha256": artifact_digest },
+        }],
+        "predicateType": "https://slsa.dev/provenance/v1",
+        "predicate": {
+            "buildDefinition": {
+                "buildType": format!("{}/rock@v1", env!("CARGO_PKG_REPOSITORY")),
+                "externalParameters": {
+                    "package": package.name().to_string(),
+                    "version": package.version().to_string(),
+                },
+                "internalParameters": provenance.map(|provenance| json!({
+                    "platform": provenance.platform(),
+                    "compiler": provenance.compiler(),
+                    "buildFlags": provenance.build_flags(),
+                })),
+                "resolvedDependencies": resolved_dependencies,
+            },
+            "runDetails": {
+                "builder": {
+                    "id": env!("CARGO_PKG_REPOSITORY"),
+                    "version": {
+                        "lux": provenance
+                            .map(|provenance| provenance.lux_version())
+                            .unwrap_or(env!("CARGO_PKG_VERSION")),
+                    },
+                },
+            },
+        },
+    });
+    Ok(serde_json::to_string_pretty(&statement)?)
+}
+
+fn source_uri(source_url: &RemotePackageSourceUrl) -> String {
+    match source_url {
+        RemotePackageSourceUrl::Git { url, checkout_ref } => format!("git+{url}@{checkout_ref}"),
+        RemotePackageSourceUrl::Url { url } => url.to_string(),
+        RemotePackageSourceUrl::File { path } => format!("file://{}", path.display()),
+    }
+}
+
+fn digest(integrity: &Integrity) -> serde_json::Value {
+    let (algorithm, hex) = integrity.to_hex();
+    serde_json::Value::Object([(algorithm.to_string(), hex.into())].into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provenance_roundtrip() {
+        let config = crate::config::ConfigBuilder::default().build().unwrap();
+        let provenance = BuildProvenance::new(&config);
+        assert_eq!(provenance.lux_version(), env!("CARGO_PKG_VERSION"));
+        assert!(provenance.build_flags().contains_key("CFLAGS"));
+        let json = serde_json::to_string(&provenance).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BuildProvenance>(&json).unwrap(),
+            provenance
+        );
+    }
+
+    #[test]
+    fn source_uris() {
+        assert_eq!(
+            source_uri(&RemotePackageSourceUrl::Git {
+                url: "https://github.com/lunarmodules/luassert.git".into(),
+                checkout_ref: "v1.9.0".into(),
+            }),
+            "git+https://github.com/lunarmodules/luassert.git@v1.9.0"
+        );
+        assert_eq!(
+            source_uri(&RemotePackageSourceUrl::Url {
+                url: "https://luarocks.org/foo-1.0.0-1.src.rock".parse().unwrap(),
+            }),
+            "https://luarocks.org/foo-1.0.0-1.src.rock"
+        );
+    }
+}
@@ -1,12 +1,13 @@
 use super::utils::c_dylib_extension;
 use crate::build::backend::{BuildBackend, BuildInfo, RunBuildArgs};
 use crate::config::LuaVersionUnset;
+use crate::process::{self, ProcessLimits};
 use crate::progress::{Progress, ProgressBar};
 use crate::{config::LuaVersion, lua_rockspec::RustMluaBuildSpec, tree::RockLayout};
 use itertools::Itertools;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::process::ExitStatus;
+use std::process::{ExitStatus, Stdio};
 use std::{fs, io};
 use thiserror::Error;
 use tokio::process::Command;
@@ -54,12 +55,18 @@ impl BuildBackend for RustMluaBuildSpec {
         }
         build_args.push("--features");
         build_args.push(&features);
-        match Command::new("cargo")
-            .current_dir(build_dir)
-            .args(build_args)
-            .output()
-            .await
-        {
+        let output = match process::spawn(
+            Command::new("cargo")
+                .current_dir(build_dir)
+                .args(build_args)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+            &ProcessLimits::build(config),
+        ) {
+            Ok(child) => args.log.wait_with_output(child, "cargo build").await,
+            Err(err) => Err(err),
+        };
+        match output {
             Ok(output) if output.status.success() => {}
             Ok(output) => {
                 return Err(RustError::CargoBuild {
@@ -0,0 +1,141 @@
+use std::{fmt::Display, path::PathBuf, str::FromStr};
+
+use itertools::Itertools;
+use mlua::{ExternalResult, FromLua, IntoLua};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::{build::utils, config::Config};
+
+/// A sanitizer, which C modules can be instrumented with to debug crashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sanitizer {
+    /// AddressSanitizer, which detects memory errors, e.g. out-of-bounds accesses.
+    Address,
+    /// UndefinedBehaviorSanitizer, which detects undefined behaviour, e.g. integer overflows.
+    Undefined,
+}
+
+impl FromStr for Sanitizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "address" => Ok(Self::Address),
+            "undefined" => Ok(Self::Undefined),
+            _ => Err("unrecognized sanitizer. Allowed sanitizers: 'address', 'undefined'.".into()),
+        }
+    }
+}
+
+impl Display for Sanitizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Address => "address",
+            Self::Undefined => "undefined",
+        })
+    }
+}
+
+impl FromLua for Sanitizer {
+    fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
+        let sanitizer_str: String = FromLua::from_lua(value, lua)?;
+        Self::from_str(&sanitizer_str).into_lua_err()
+    }
+}
+
+impl IntoLua for Sanitizer {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        self.to_string().into_lua(lua)
+    }
+}
+
+/// The flags to instrument C sources with `sanitizers`.
+/// The compiler also passes them to the linker, which links the sanitizers' runtimes.
+pub(crate) fn compile_flags(sanitizers: &[Sanitizer], compiler: &cc::Tool) -> Vec<String> {
+    if sanitizers.is_empty() {
+        Vec::new()
+    } else if compiler.is_like_msvc() {
+        // MSVC only supports AddressSanitizer
+        if sanitizers.contains(&Sanitizer::Address) {
+            vec!["/fsanitize=address".into(), "/Zi".into()]
+        } else {
+            Vec::new()
+        }
+    } else {
+        vec![
+            fsanitize_flag(sanitizers),
+            "-fno-omit-frame-pointer".into(),
+            "-g".into(),
+        ]
+    }
+}
+
+fn fsanitize_flag(sanitizers: &[Sanitizer]) -> String {
+    format!(
+        "-fsanitize={}",
+        sanitizers.iter().sorted().dedup().join(",")
+    )
+}
+
+/// The environment to run a Lua interpreter that loads C modules instrumented with `sanitizers`.
+/// Variables that are already set in the environment are not overridden.
+pub(crate) async fn runtime_env(
+    sanitizers: &[Sanitizer],
+    config: &Config,
+) -> Vec<(&'static str, String)> {
+    let mut env = Vec::new();
+    if sanitizers.contains(&Sanitizer::Address) {
+        // The Lua interpreter doesn't free everything before exiting
+        env.push(("ASAN_OPTIONS", "detect_leaks=0".into()));
+        // The interpreter isn't instrumented, so the runtime has to be loaded first
+        if cfg!(target_os = "linux") {
+            if let Some(runtime) = asan_runtime(config).await {
+                env.push(("LD_PRELOAD", runtime.to_string_lossy().to_string()));
+            }
+        }
+    }
+    if sanitizers.contains(&Sanitizer::Undefined) {
+        env.push(("UBSAN_OPTIONS", "print_stacktrace=1".into()));
+    }
+    env.into_iter()
+        .filter(|(name, _)| std::env::var_os(name).is_none())
+        .collect()
+}
+
+/// The shared AddressSanitizer runtime of the C compiler, if it can be found.
+async fn asan_runtime(config: &Config) -> Option<PathBuf> {
+    let compiler = utils::find_c_compiler(config)?;
+    let output = Command::new(compiler)
+        .arg("-print-file-name=libasan.so")
+        .output()
+        .await
+        .ok()?;
+    let runtime = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    // If the compiler can't find the runtime, it prints the file name
+    if output.status.success() && runtime.is_absolute() && runtime.is_file() {
+        Some(runtime)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fsanitize_flags() {
+        assert_eq!(
+            fsanitize_flag(&[
+                Sanitizer::Undefined,
+                Sanitizer::Address,
+                Sanitizer::Undefined
+            ]),
+            "-fsanitize=address,undefined"
+        );
+        assert_eq!(Sanitizer::from_str("address").unwrap(), Sanitizer::Address);
+        assert!(Sanitizer::from_str("thread").is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io,
+    process::{Command, ExitStatus},
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use which::which;
+
+use crate::config::external_deps::ExternalDependencySearchConfig;
+
+/// A system package manager that may provide external dependencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
+#[serde(rename_all = "lowercase")]
+pub enum SystemPackageManager {
+    Apt,
+    Dnf,
+    Pacman,
+    Brew,
+    Vcpkg,
+}
+
+impl SystemPackageManager {
+    fn executable(&self) -> &'static str {
+        match self {
+            Self::Apt => "apt-get",
+            Self::Dnf => "dnf",
+            Self::Pacman => "pacman",
+            Self::Brew => "brew",
+            Self::Vcpkg => "vcpkg",
+        }
+    }
+
+    fn needs_root(&self) -> bool {
+        matches!(self, Self::Apt | Self::Dnf | Self::Pacman)
+    }
+
+    /// Find the first supported package manager that is available on this system.
+    pub fn detect() -> Option<Self> {
+        Self::iter().find(|manager| which(manager.executable()).is_ok())
+    }
+
+    /// The command used to install the given packages.
+    pub fn install_command(&self, packages: &[String]) -> Vec<String> {
+        let mut command = Vec::new();
+        if self.needs_root() && !is_root() {
+            command.push("sudo".into());
+        }
+        command.push(self.executable().into());
+        match self {
+            Self::Pacman => command.push("-S".into()),
+            Self::Apt | Self::Dnf | Self::Brew | Self::Vcpkg => command.push("install".into()),
+        }
+        command.extend(packages.iter().cloned());
+        command
+    }
+
+    /// Install the given packages, inheriting stdin, stdout and stderr,
+    /// so that the package manager can prompt the user.
+    pub fn install(&self, packages: &[String]) -> io::Result<ExitStatus> {
+        let command = self.install_command(packages);
+        let (program, args) = command.split_first().expect("empty install command");
+        Command::new(program).args(args).status()
+    }
+}
+
+impl Display for SystemPackageManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Apt => "apt",
+            Self::Dnf => "dnf",
+            Self::Pacman => "pacman",
+            Self::Brew => "brew",
+            Self::Vcpkg => "vcpkg",
+        }
+        .fmt(f)
+    }
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    // The effective user decides whether the package manager can be run without `sudo`.
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+/// Names of the system packages that provide an external dependency.
+#[derive(Debug, Clone)]
+pub struct SystemPackageHint {
+    pub(crate) dependency: String,
+    pub(crate) packages: HashMap<SystemPackageManager, String>,
+}
+
+impl SystemPackageHint {
+    /// Look up the system packages for an external dependency,
+    /// with entries in the config taking precedence over the built-in defaults.
+    pub fn new(dependency: &str, config: &ExternalDependencySearchConfig) -> Option<Self> {
+        let key = dependency.to_lowercase();
+        let mut packages = default_system_packages(&key);
+        if let Some(overrides) = config.system_packages.get(&key) {
+            packages.extend(overrides.clone());
+        }
+        if packages.is_empty() {
+            None
+        } else {
+            Some(Self {
+                dependency: dependency.to_string(),
+                packages,
+            })
+        }
+    }
+
+    pub fn dependency(&self) -> &str {
+        &self.dependency
+    }
+
+    pub fn package(&self, manager: &SystemPackageManager) -> Option<&String> {
+        self.packages.get(manager)
+    }
+}
+
+impl Display for SystemPackageHint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "hint: {} may be provided by one of the following system packages:",
+            self.dependency
+        )?;
+        let lines = SystemPackageManager::iter()
+            .filter_map(|manager| {
+                self.packages
+                    .get(&manager)
+                    .map(|package| format!("  {manager}: {package}"))
+            })
+            .join("\n");
+        write!(f, "{lines}")
+    }
+}
+
+fn default_system_packages(dependency: &str) -> HashMap<SystemPackageManager, String> {
+    use SystemPackageManager::*;
+    let entries: &[(SystemPackageManager, &str)] = match dependency {
+        "openssl" | "crypto" | "ssl" => &[
+            (Apt, "libssl-dev"),
+            (Dnf, "openssl-devel"),
+            (Pacman, "openssl"),
+            (Brew, "openssl@3"),
+            (Vcpkg, "openssl"),
+        ],
+        "zlib" | "z" => &[
+            (Apt, "zlib1g-dev"),
+            (Dnf, "zlib-devel"),
+            (Pacman, "zlib"),
+            (Brew, "zlib"),
+            (Vcpkg, "zlib"),
+        ],
+        "pcre" => &[
+            (Apt, "libpcre3-dev"),
+            (Dnf, "pcre-devel"),
+            (Pacman, "pcre"),
+            (Brew, "pcre"),
+            (Vcpkg, "pcre"),
+        ],
+        "pcre2" => &[
+            (Apt, "libpcre2-dev"),
+            (Dnf, "pcre2-devel"),
+            (Pacman, "pcre2"),
+            (Brew, "pcre2"),
+            (Vcpkg, "pcre2"),
+        ],
+        "sqlite" | "sqlite3" => &[
+            (Apt, "libsqlite3-dev"),
+            (Dnf, "sqlite-devel"),
+            (Pacman, "sqlite"),
+            (Brew, "sqlite"),
+            (Vcpkg, "sqlite3"),
+        ],
+        "yaml" | "libyaml" => &[
+            (Apt, "libyaml-dev"),
+            (Dnf, "libyaml-devel"),
+            (Pacman, "libyaml"),
+            (Brew, "libyaml"),
+            (Vcpkg, "libyaml"),
+        ],
+        "expat" => &[
+            (Apt, "libexpat1-dev"),
+            (Dnf, "expat-devel"),
+            (Pacman, "expat"),
+            (Brew, "expat"),
+            (Vcpkg, "expat"),
+        ],
+        "curl" | "libcurl" => &[
+            (Apt, "libcurl4-openssl-dev"),
+            (Dnf, "libcurl-devel"),
+            (Pacman, "curl"),
+            (Brew, "curl"),
+            (Vcpkg, "curl"),
+        ],
+        "readline" => &[
+            (Apt, "libreadline-dev"),
+            (Dnf, "readline-devel"),
+            (Pacman, "readline"),
+            (Brew, "readline"),
+        ],
+        "uuid" | "libuuid" => &[
+            (Apt, "uuid-dev"),
+            (Dnf, "libuuid-devel"),
+            (Pacman, "util-linux-libs"),
+            (Brew, "ossp-uuid"),
+        ],
+        "libuv" | "uv" => &[
+            (Apt, "libuv1-dev"),
+            (Dnf, "libuv-devel"),
+            (Pacman, "libuv"),
+            (Brew, "libuv"),
+            (Vcpkg, "libuv"),
+        ],
+        _ => &[],
+    };
+    entries
+        .iter()
+        .map(|(manager, package)| (*manager, package.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_package_hint_defaults() {
+        let config = ExternalDependencySearchConfig::default();
+        let hint = SystemPackageHint::new("OPENSSL", &config).unwrap();
+        assert_eq!(
+            hint.package(&SystemPackageManager::Apt),
+            Some(&"libssl-dev".to_string())
+        );
+        assert!(SystemPackageHint::new("unknown-dependency", &config).is_none());
+    }
+
+    #[test]
+    fn system_package_hint_config_override() {
+        let mut config = ExternalDependencySearchConfig::default();
+        config.system_packages.insert(
+            "foo".into(),
+            HashMap::from_iter([(SystemPackageManager::Dnf, "foo-devel".into())]),
+        );
+        config.system_packages.insert(
+            "zlib".into(),
+            HashMap::from_iter([(SystemPackageManager::Apt, "zlib-custom-dev".into())]),
+        );
+        let hint = SystemPackageHint::new("foo", &config).unwrap();
+        assert_eq!(
+            hint.package(&SystemPackageManager::Dnf),
+            Some(&"foo-devel".to_string())
+        );
+        let hint = SystemPackageHint::new("zlib", &config).unwrap();
+        assert_eq!(
+            hint.package(&SystemPackageManager::Apt),
+            Some(&"zlib-custom-dev".to_string())
+        );
+        assert_eq!(
+            hint.package(&SystemPackageManager::Dnf),
+            Some(&"zlib-devel".to_string())
+        );
+    }
+
+    #[test]
+    fn system_package_hint_config_keys_are_case_insensitive() {
+        let config: ExternalDependencySearchConfig = toml::from_str(
+            r#"
+prefixes = {}
+
+[system_packages.FOO]
+dnf = "foo-devel"
+"#,
+        )
+        .unwrap();
+        let hint = SystemPackageHint::new("Foo", &config).unwrap();
+        assert_eq!(
+            hint.package(&SystemPackageManager::Dnf),
+            Some(&"foo-devel".to_string())
+        );
+    }
+
+    #[test]
+    fn install_command() {
+        let command =
+            SystemPackageManager::Pacman.install_command(&["openssl".into(), "zlib".into()]);
+        assert!(command.ends_with(&[
+            "pacman".to_string(),
+            "-S".into(),
+            "openssl".into(),
+            "zlib".into()
+        ]));
+    }
+}
@@ -1,14 +1,22 @@
 use crate::build::backend::{BuildBackend, BuildInfo, RunBuildArgs};
+use crate::build::utils::recursive_copy_dir;
 use crate::config::LuaVersionUnset;
 use crate::lua_rockspec::TreesitterParserBuildSpec;
+use crate::path::{Paths, PathsError};
+use crate::process::{self, ProcessLimits};
+use crate::tree::TreeError;
 use std::io;
 use std::num::ParseIntError;
 use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
 use thiserror::Error;
+use tokio::process::Command;
 use tree_sitter_generate::GenerateError;
 
 const DEFAULT_GENERATE_ABI_VERSION: usize = tree_sitter::LANGUAGE_VERSION;
 
+const TREE_SITTER_CLI: &str = "tree-sitter";
+
 #[derive(Error, Debug)]
 pub enum TreesitterBuildError {
     #[error(transparent)]
@@ -25,6 +33,22 @@ pub enum TreesitterBuildError {
     CreateDir { dir: PathBuf, err: io::Error },
     #[error("error writing query file: {0}")]
     WriteQuery(io::Error),
+    #[error("error copying queries: {0}")]
+    CopyQueries(io::Error),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    Paths(#[from] PathsError),
+    #[error("failed to run `{TREE_SITTER_CLI} generate`: `{TREE_SITTER_CLI}` command not found!")]
+    CommandNotFound,
+    #[error("failed to run `{TREE_SITTER_CLI} generate`: {0}")]
+    Io(io::Error),
+    #[error("`{TREE_SITTER_CLI} generate` failed.\n\n{status}\n\nstdout:\n{stdout}\n\nstderr:\n{stderr}")]
+    CommandFailure {
+        status: ExitStatus,
+        stdout: String,
+        stderr: String,
+    },
 }
 
 impl BuildBackend for TreesitterParserBuildSpec {
@@ -32,13 +56,49 @@ impl BuildBackend for TreesitterParserBuildSpec {
 
     async fn run(self, args: RunBuildArgs<'_>) -> Result<BuildInfo, Self::Err> {
         let output_paths = args.output_paths;
-        let build_dir = args.build_dir;
+        let source_dir = args.build_dir;
         let progress = args.progress;
+        let config = args.config;
         let build_dir = self
             .location
-            .map(|dir| build_dir.join(dir))
-            .unwrap_or(build_dir.to_path_buf());
-        if self.generate {
+            .map(|dir| source_dir.join(dir))
+            .unwrap_or(source_dir.to_path_buf());
+        if self.generate_from_grammar {
+            progress
+                .map(|b| b.set_message("📖 ✍Generating tree-sitter grammar from grammar.js..."));
+            let build_tree = args.tree.build_tree(config)?;
+            let build_paths = Paths::new(&build_tree)?;
+            let bin_path = build_paths.path_prepended().joined();
+            match process::spawn(
+                Command::new(TREE_SITTER_CLI)
+                    .current_dir(&build_dir)
+                    .arg("generate")
+                    .env("PATH", &bin_path)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped()),
+                &ProcessLimits::build(config),
+            ) {
+                Ok(child) => match args
+                    .log
+                    .wait_with_output(child, "tree-sitter generate")
+                    .await
+                {
+                    Ok(output) if output.status.success() => {}
+                    Ok(output) => {
+                        return Err(TreesitterBuildError::CommandFailure {
+                            status: output.status,
+                            stdout: String::from_utf8_lossy(&output.stdout).into(),
+                            stderr: String::from_utf8_lossy(&output.stderr).into(),
+                        });
+                    }
+                    Err(err) => return Err(TreesitterBuildError::Io(err)),
+                },
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    return Err(TreesitterBuildError::CommandNotFound)
+                }
+                Err(err) => return Err(TreesitterBuildError::Io(err)),
+            }
+        } else if self.generate {
             progress.map(|b| b.set_message("📖 ✍Generating tree-sitter grammar..."));
             let abi_version = match std::env::var("TREE_SITTER_LANGUAGE_VERSION") {
                 Ok(v) => v.parse()?,
@@ -77,6 +137,27 @@ impl BuildBackend for TreesitterParserBuildSpec {
         }
 
         let queries_dir = output_paths.etc.join("queries");
+
+        // Install query files shipped with the grammar, either in `queries/`
+        // or in `queries/<lang>/` (the nvim-treesitter convention).
+        let source_queries_dir = [build_dir.join("queries"), source_dir.join("queries")]
+            .into_iter()
+            .find(|dir| dir.is_dir())
+            .map(|dir| {
+                let lang_dir = dir.join(&self.lang);
+                if lang_dir.is_dir() {
+                    lang_dir
+                } else {
+                    dir
+                }
+            });
+        if let Some(source_queries_dir) = source_queries_dir {
+            progress.map(|b| b.set_message("🌳 Installing tree-sitter queries..."));
+            recursive_copy_dir(&source_queries_dir, &queries_dir)
+                .await
+                .map_err(TreesitterBuildError::CopyQueries)?;
+        }
+
         if !self.queries.is_empty() {
             tokio::fs::create_dir_all(&queries_dir)
                 .await
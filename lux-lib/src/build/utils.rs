@@ -1,8 +1,14 @@
 use crate::{
+    build::{
+        compile_commands::{CompileCommand, CompileCommands},
+        compiler_cache::CompilerCache,
+        sanitizer,
+    },
     config::Config,
     lua_installation::LuaInstallation,
     lua_rockspec::{DeploySpec, LuaModule, ModulePaths},
     path::{Paths, PathsError},
+    process::{self, ProcessLimits},
     tree::{RockLayout, Tree},
     variables::{self, Environment, VariableSubstitutionError},
 };
@@ -38,11 +44,45 @@ pub(crate) fn copy_lua_to_module_path(
 
     std::fs::create_dir_all(target.parent().unwrap())?;
 
+    // Don't copy through a symlink created by `link_lua_to_module_path`,
+    // as that would overwrite the linked source.
+    if target.is_symlink() {
+        std::fs::remove_file(&target)?;
+    }
+
     std::fs::copy(source, target)?;
 
     Ok(())
 }
 
+/// Symlinks a lua source file to a specific destination, so that edits to the source
+/// are picked up without rebuilding. The destination is described by a `module.path` syntax.
+pub(crate) fn link_lua_to_module_path(
+    source: &Path,
+    target_module: &LuaModule,
+    target_dir: &Path,
+) -> io::Result<()> {
+    let target = target_dir.join(target_module.to_lua_path());
+
+    std::fs::create_dir_all(target.parent().unwrap())?;
+
+    if target.symlink_metadata().is_ok() {
+        std::fs::remove_file(&target)?;
+    }
+
+    symlink_file(source, &target)
+}
+
+#[cfg(unix)]
+fn symlink_file(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(source, target)
+}
+
+#[cfg(windows)]
+fn symlink_file(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(source, target)
+}
+
 /// Get the files that Lux treats as project files
 /// This respects ignore files and excludes hidden files and directories.
 pub(crate) fn project_files(src: &PathBuf) -> Vec<PathBuf> {
@@ -97,17 +137,18 @@ fn validate_output(output: &Output) -> Result<(), OutputValidationError> {
 pub enum CompileCFilesError {
     #[error("IO operation while compiling C files: {0}")]
     Io(#[from] io::Error),
-    #[error("failed to compile intermediates from C files: {0}")]
-    CompileIntermediates(cc::Error),
     #[error("error compiling C files (compilation failed): {0}")]
     Compilation(#[from] cc::Error),
     #[error("error compiling C files (output validation failed): {0}")]
     OutputValidation(#[from] OutputValidationError),
     #[error("compiling C files succeeded, but the expected library {0} was not created")]
     LibOutputNotCreated(String),
+    #[error("failed to compile intermediates from C files: {0}")]
+    CompileIntermediates(#[from] CompileIntermediatesError),
 }
 
 /// Compiles a set of C files into a single dynamic library and places them under `{target_dir}/{target_file}`.
+/// If `compile_commands` is set, the compile commands are recorded in it.
 /// # Panics
 /// Panics if no parent or no filename can be determined for the target path.
 pub(crate) async fn compile_c_files(
@@ -117,6 +158,7 @@ pub(crate) async fn compile_c_files(
     lua: &LuaInstallation,
     external_dependencies: &HashMap<String, ExternalDependencyInfo>,
     config: &Config,
+    compile_commands: Option<&CompileCommands>,
 ) -> Result<(), CompileCFilesError> {
     let target = target_dir.join(target_module.to_lib_path());
 
@@ -149,8 +191,9 @@ pub(crate) async fn compile_c_files(
                 .filter_map(|(_, dep)| dep.include_dir.as_ref()),
         )
         .opt_level(3)
-        .out_dir(intermediate_dir)
+        .out_dir(intermediate_dir.path())
         .target(&host.to_string());
+    apply_toolchain(build, config);
 
     let compiler = build.try_get_compiler()?;
     // Suppress all warnings
@@ -162,10 +205,22 @@ pub(crate) async fn compile_c_files(
     for arg in lua.define_flags() {
         build.flag(&arg);
     }
+    for flag in sanitizer::compile_flags(config.sanitizers(), &compiler) {
+        build.flag(&flag);
+    }
 
-    let objects = build
-        .try_compile_intermediates()
-        .map_err(CompileCFilesError::CompileIntermediates)?;
+    if let Some(compile_commands) = compile_commands {
+        record_compile_commands(compile_commands, &build.try_get_compiler()?, files);
+    }
+
+    let objects = compile_intermediates(
+        config.compiler_cache(),
+        &build.try_get_compiler()?,
+        files,
+        intermediate_dir.path(),
+        config,
+    )
+    .await?;
 
     let output_path = parent.join(&file);
 
@@ -186,8 +241,8 @@ pub(crate) async fn compile_c_files(
                     .iter()
                     .flat_map(|(_, dep)| dep.lib_link_args(&compiler)),
             )
-            .output()
-            .await?
+            .args(toolchain_ldflags(config));
+        run_compiler(&mut cmd, config).await?
     } else {
         let cmd = build.shared_flag(true).try_get_compiler()?.to_command();
         let mut cmd: tokio::process::Command = cmd.into();
@@ -199,8 +254,8 @@ pub(crate) async fn compile_c_files(
                     .flat_map(|(_, dep)| dep.lib_link_args(&compiler)),
             )
             .args(&objects)
-            .output()
-            .await?
+            .args(toolchain_ldflags(config));
+        run_compiler(&mut cmd, config).await?
     };
 
     if config.verbose() {
@@ -224,6 +279,169 @@ pub(crate) async fn compile_c_files(
     }
 }
 
+/// Run a compiler or linker command to completion, subject to the config's build limits.
+async fn run_compiler(cmd: &mut Command, config: &Config) -> io::Result<Output> {
+    let phase = cmd.as_std().get_program().to_string_lossy().to_string();
+    process::spawn(
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped()),
+        &ProcessLimits::build(config),
+    )?
+    .wait_with_output_streamed(&phase, |_| {})
+    .await
+}
+
+/// Use the config's toolchain, if any, instead of the detected compiler toolchain.
+fn apply_toolchain(build: &mut cc::Build, config: &Config) {
+    if let Some(toolchain) = config.toolchain() {
+        if let Some(cc) = toolchain.cc() {
+            build.compiler(cc);
+        }
+        if let Some(ar) = toolchain.ar() {
+            build.archiver(ar);
+        }
+        for flag in toolchain.cflags() {
+            build.flag(flag);
+        }
+    }
+}
+
+fn get_c_compiler(config: &Config) -> Option<cc::Tool> {
+    let mut build = cc::Build::new();
+    let build = build
+        .cargo_output(false)
+        .cargo_metadata(false)
+        .cargo_warnings(false)
+        .host(std::env::consts::OS)
+        .opt_level(3)
+        .target(&Triple::host().to_string());
+    apply_toolchain(build, config);
+    build.try_get_compiler().ok()
+}
+
+/// The C compiler that C modules would be built with, if it is installed.
+pub(crate) fn find_c_compiler(config: &Config) -> Option<PathBuf> {
+    which(get_c_compiler(config)?.path()).ok()
+}
+
+/// The family and version of the C compiler that C modules would be built with,
+/// e.g. `gnu 13.2.0`, if it is installed.
+/// Unlike its path, this does not depend on the host it is installed on.
+pub(crate) fn c_compiler_identity(config: &Config) -> Option<String> {
+    let compiler = get_c_compiler(config)?;
+    which(compiler.path()).ok()?;
+    if compiler.is_like_msvc() {
+        // MSVC has no `--version` flag.
+        return Some("msvc".into());
+    }
+    let family = if compiler.is_like_clang() {
+        "clang"
+    } else if compiler.is_like_gnu() {
+        "gnu"
+    } else {
+        "unknown"
+    };
+    let version = std::process::Command::new(compiler.path())
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            // e.g. `gcc (GCC) 13.2.0` or `clang version 17.0.6`
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .next()?
+                .split_whitespace()
+                .rev()
+                .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+                .map(str::to_string)
+        });
+    Some(match version {
+        Some(version) => format!("{family} {version}"),
+        None => family.into(),
+    })
+}
+
+fn toolchain_ldflags(config: &Config) -> &[String] {
+    config
+        .toolchain()
+        .map(|toolchain| toolchain.ldflags())
+        .unwrap_or_default()
+}
+
+#[derive(Error, Debug)]
+pub enum CompileIntermediatesError {
+    #[error("IO operation while compiling intermediates: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    OutputValidation(#[from] OutputValidationError),
+}
+
+/// Compiles each of the `files` to an object file in `out_dir`,
+/// wrapping the compiler invocations in the `compiler_cache`, if any.
+/// `cc::Build` can only pick up a compiler cache from the environment
+/// and spawns the compiler without the config's build limits,
+/// so we invoke the compiler ourselves.
+async fn compile_intermediates(
+    compiler_cache: Option<CompilerCache>,
+    compiler: &cc::Tool,
+    files: &[PathBuf],
+    out_dir: &Path,
+    config: &Config,
+) -> Result<Vec<PathBuf>, CompileIntermediatesError> {
+    futures::future::try_join_all(files.iter().enumerate().map(|(i, file)| async move {
+        let stem = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let object = out_dir.join(format!("{i}-{stem}.{}", c_obj_extension()));
+        let compiler_cmd = compiler.to_command();
+        let mut cmd = match compiler_cache {
+            Some(compiler_cache) => {
+                let mut cmd = Command::new(compiler_cache.to_string());
+                cmd.arg(compiler_cmd.get_program());
+                cmd
+            }
+            None => Command::new(compiler_cmd.get_program()),
+        };
+        cmd.args(compiler_cmd.get_args()).envs(
+            compiler_cmd
+                .get_envs()
+                .filter_map(|(key, value)| Some((key, value?))),
+        );
+        if compiler.is_like_msvc() {
+            cmd.arg(format!("/Fo{}", object.display())).arg("/c");
+        } else {
+            cmd.arg("-o").arg(&object).arg("-c");
+        }
+        let output = run_compiler(cmd.arg(file), config).await?;
+        validate_output(&output)?;
+        Ok::<_, CompileIntermediatesError>(object)
+    }))
+    .await
+}
+
+/// Record the commands that compile each of the `files` with the `compiler`.
+fn record_compile_commands(
+    compile_commands: &CompileCommands,
+    compiler: &cc::Tool,
+    files: &[PathBuf],
+) {
+    for file in files {
+        let arguments = std::iter::once(compiler.path().as_os_str())
+            .chain(compiler.args().iter().map(|arg| arg.as_os_str()))
+            .map(|arg| arg.to_string_lossy().to_string())
+            .chain(["-c".into(), file.to_string_lossy().to_string()])
+            .collect();
+        compile_commands.push(CompileCommand {
+            directory: file.parent().map(Path::to_path_buf).unwrap_or_default(),
+            file: file.clone(),
+            arguments,
+            command: None,
+            output: None,
+        });
+    }
+}
+
 /// On MSVC, we need to create Lua definitions manually
 fn mk_def_file(
     dir: PathBuf,
@@ -297,19 +515,21 @@ pub(crate) fn default_libflag() -> &'static str {
 pub enum CompileCModulesError {
     #[error("IO operation while compiling C modules: {0}")]
     Io(#[from] io::Error),
-    #[error("failed to compile intermediates from C modules: {0}")]
-    CompileIntermediates(cc::Error),
     #[error("error compiling C modules (compilation failed): {0}")]
     Compilation(#[from] cc::Error),
     #[error("error compiling C modules (output validation failed): {0}")]
     OutputValidation(#[from] OutputValidationError),
     #[error("compiling C modules succeeded, but the expected library {0} was not created")]
     LibOutputNotCreated(String),
+    #[error("failed to compile intermediates from C modules: {0}")]
+    CompileIntermediates(#[from] CompileIntermediatesError),
 }
 
 /// Compiles a set of C files (with extra metadata) to a given destination.
+/// If `compile_commands` is set, the compile commands are recorded in it.
 /// # Panics
 /// Panics if no filename for the target path can be determined.
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn compile_c_modules(
     data: &ModulePaths,
     source_dir: &Path,
@@ -318,6 +538,7 @@ pub(crate) async fn compile_c_modules(
     lua: &LuaInstallation,
     external_dependencies: &HashMap<String, ExternalDependencyInfo>,
     config: &Config,
+    compile_commands: Option<&CompileCommands>,
 ) -> Result<(), CompileCModulesError> {
     let target = target_dir.join(target_module.to_lib_path());
 
@@ -359,8 +580,9 @@ pub(crate) async fn compile_c_modules(
                 .filter_map(|(_, dep)| dep.include_dir.as_ref()),
         )
         .opt_level(3)
-        .out_dir(intermediate_dir)
+        .out_dir(intermediate_dir.path())
         .target(&host.to_string());
+    apply_toolchain(build, config);
 
     let compiler = build.try_get_compiler()?;
     let is_msvc = compiler.is_like_msvc();
@@ -373,6 +595,9 @@ pub(crate) async fn compile_c_modules(
     for arg in lua.define_flags() {
         build.flag(&arg);
     }
+    for flag in sanitizer::compile_flags(config.sanitizers(), &compiler) {
+        build.flag(&flag);
+    }
 
     // `cc::Build` has no `defines()` function, so we manually feed in the
     // definitions in a verbose loop
@@ -385,10 +610,23 @@ pub(crate) async fn compile_c_modules(
         .expect("Couldn't determine filename")
         .to_string_lossy()
         .to_string();
+    if let Some(compile_commands) = compile_commands {
+        record_compile_commands(
+            compile_commands,
+            &build.try_get_compiler()?,
+            &build.get_files().map(Path::to_path_buf).collect_vec(),
+        );
+    }
+
     // See https://github.com/rust-lang/cc-rs/issues/594#issuecomment-2110551057
-    let objects = build
-        .try_compile_intermediates()
-        .map_err(CompileCModulesError::CompileIntermediates)?;
+    let objects = compile_intermediates(
+        config.compiler_cache(),
+        &build.try_get_compiler()?,
+        &build.get_files().map(Path::to_path_buf).collect_vec(),
+        intermediate_dir.path(),
+        config,
+    )
+    .await?;
 
     let libdir_args = data.libdirs.iter().map(|libdir| {
         if is_msvc {
@@ -426,8 +664,8 @@ pub(crate) async fn compile_c_modules(
             )
             .args(libdir_args)
             .args(library_args)
-            .output()
-            .await?
+            .args(toolchain_ldflags(config));
+        run_compiler(&mut cmd, config).await?
     } else {
         let cmd = build.shared_flag(true).try_get_compiler()?.to_command();
         let mut cmd: tokio::process::Command = cmd.into();
@@ -441,8 +679,8 @@ pub(crate) async fn compile_c_modules(
             .args(&objects)
             .args(libdir_args)
             .args(library_args)
-            .output()
-            .await?
+            .args(toolchain_ldflags(config));
+        run_compiler(&mut cmd, config).await?
     };
 
     if config.verbose() {
@@ -497,14 +735,15 @@ pub(crate) async fn install_binary(
 ) -> Result<PathBuf, InstallBinaryError> {
     tokio::fs::create_dir_all(&tree.bin()).await?;
     let paths = Paths::new(tree)?;
-    let script =
-        if deploy.wrap_bin_scripts && is_compatible_lua_script(source, lua, &paths, config).await {
-            install_wrapped_binary(source, target, tree, lua, config).await?
-        } else {
-            let target = tree.bin().join(target);
-            tokio::fs::copy(source, &target).await?;
-            target
-        };
+    let wrap_bin_scripts = config.wrap_bin_scripts().unwrap_or(deploy.wrap_bin_scripts);
+    let script = if wrap_bin_scripts && is_compatible_lua_script(source, lua, &paths, config).await
+    {
+        install_wrapped_binary(source, target, tree, lua, config).await?
+    } else {
+        let target = tree.bin().join(target);
+        tokio::fs::copy(source, &target).await?;
+        target
+    };
 
     #[cfg(unix)]
     set_executable_permissions(&script).await?;
@@ -544,6 +783,17 @@ async fn install_wrapped_binary(
     let lua_bin = lua
         .lua_binary_or_config_override(config)
         .ok_or(WrapBinaryError::NoLuaBinary)?;
+    let lua_bin_path = PathBuf::from(&lua_bin);
+    let lua_bin = if config.relocatable() && lua_bin_path.starts_with(tree.root()) {
+        format!("\"{}\"", wrapper_relative_path(&lua_bin_path, tree))
+    } else {
+        lua_bin
+    };
+    let unwrapped_bin = if config.relocatable() {
+        wrapper_relative_path(&unwrapped_bin, tree)
+    } else {
+        unwrapped_bin.display().to_string()
+    };
 
     #[cfg(target_family = "unix")]
     let content = format!(
@@ -551,8 +801,7 @@ async fn install_wrapped_binary(
 
 exec {0} "{1}" "$@"
 "#,
-        lua_bin,
-        unwrapped_bin.display(),
+        lua_bin, unwrapped_bin,
     );
     #[cfg(target_family = "windows")]
     let content = format!(
@@ -563,14 +812,54 @@ setlocal
 
 exit /b %ERRORLEVEL%
 "#,
-        lua_bin,
-        unwrapped_bin.display(),
+        lua_bin, unwrapped_bin,
     );
 
     tokio::fs::write(&target, content).await?;
     Ok(target)
 }
 
+/// Rewrite absolute paths to the rock's install directory in installed pkg-config files,
+/// so that they are resolved relative to the `.pc` file, using pkg-config's `${pcfiledir}`.
+pub(crate) fn make_pkg_config_files_relocatable(output_paths: &RockLayout) -> io::Result<()> {
+    let rock_path = output_paths.rock_path.to_slash_lossy().to_string();
+    for entry in walkdir::WalkDir::new(&output_paths.rock_path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "pc"))
+    {
+        let pc_file = entry.path();
+        let content = std::fs::read_to_string(pc_file)?;
+        if !content.contains(&rock_path) {
+            continue;
+        }
+        let pc_dir = pc_file.parent().unwrap_or(&output_paths.rock_path);
+        let relative_rock_path = pathdiff::diff_paths(&output_paths.rock_path, pc_dir)
+            .unwrap_or_default()
+            .to_slash_lossy()
+            .to_string();
+        let relocatable_rock_path = if relative_rock_path.is_empty() {
+            "${pcfiledir}".to_string()
+        } else {
+            format!("${{pcfiledir}}/{relative_rock_path}")
+        };
+        std::fs::write(pc_file, content.replace(&rock_path, &relocatable_rock_path))?;
+    }
+    Ok(())
+}
+
+/// A path in a bin wrapper script, relative to the directory containing the wrapper.
+fn wrapper_relative_path(path: &Path, tree: &Tree) -> String {
+    let relative_path = pathdiff::diff_paths(path, tree.bin()).unwrap_or(path.to_path_buf());
+    #[cfg(target_family = "unix")]
+    let wrapper_dir = "$(dirname \"$0\")/";
+    // `%~dp0` includes a trailing path separator
+    #[cfg(target_family = "windows")]
+    let wrapper_dir = "%~dp0";
+    format!("{}{}", wrapper_dir, relative_path.display())
+}
+
 #[cfg(unix)]
 async fn set_executable_permissions(script: &Path) -> std::io::Result<()> {
     let mut perms = tokio::fs::metadata(&script).await?.permissions();
@@ -685,7 +974,7 @@ mod tests {
     use tokio::process::Command;
 
     use crate::{
-        config::{ConfigBuilder, LuaVersion},
+        config::{toolchain::Toolchain, ConfigBuilder, LuaVersion},
         lua_installation::detect_installed_lua_version,
         progress::{MultiProgress, Progress},
     };
@@ -749,4 +1038,156 @@ mod tests {
             .await
             .is_ok_and(|status| status.success()));
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wrapper_relative_path() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .relocatable(Some(true))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        assert_eq!(
+            wrapper_relative_path(&tree.unwrapped_bin().join("foo"), &tree),
+            "$(dirname \"$0\")/unwrapped/foo"
+        );
+        assert_eq!(
+            wrapper_relative_path(&tree.root().join("lua").join("bin").join("lua"), &tree),
+            "$(dirname \"$0\")/../lua/bin/lua"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_make_pkg_config_files_relocatable() {
+        use assert_fs::prelude::*;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let rock_path = temp.child("foo@1.0.0-1");
+        let layout = RockLayout {
+            rock_path: rock_path.to_path_buf(),
+            etc: rock_path.join("etc"),
+            lib: rock_path.join("lib"),
+            src: rock_path.join("src"),
+            bin: temp.join("bin"),
+            conf: rock_path.join("etc").join("conf"),
+            doc: rock_path.join("etc").join("doc"),
+        };
+        let pc_file = rock_path.child("lib/pkgconfig/foo.pc");
+        pc_file
+            .write_str(&format!(
+                "prefix={}\nlibdir=${{prefix}}/lib\n",
+                rock_path.display()
+            ))
+            .unwrap();
+        make_pkg_config_files_relocatable(&layout).unwrap();
+        pc_file.assert("prefix=${pcfiledir}/../..\nlibdir=${prefix}/lib\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_then_copy_lua_module() {
+        use assert_fs::prelude::*;
+        use std::str::FromStr;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let source = temp.child("project/src/foo.lua");
+        source.write_str("return 1").unwrap();
+        let src_dir = temp.child("tree/src");
+        let module = LuaModule::from_str("foo.bar").unwrap();
+        let target = src_dir.join(module.to_lua_path());
+
+        link_lua_to_module_path(source.path(), &module, &src_dir).unwrap();
+        assert!(target.is_symlink());
+        source.write_str("return 2").unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "return 2");
+
+        let copied = temp.child("build/foo.lua");
+        copied.write_str("return 3").unwrap();
+        copy_lua_to_module_path(&copied.to_path_buf(), &module, &src_dir).unwrap();
+        assert!(!target.is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "return 3");
+        source.assert("return 2");
+    }
+
+    /// A compiler that writes its environment to its output file.
+    /// It fails without an output file, so that `cc` treats it as a GNU compiler.
+    #[cfg(unix)]
+    fn env_dumping_compiler(dir: &Path) -> cc::Tool {
+        let compiler = dir.join("dump-env-cc");
+        std::fs::write(
+            &compiler,
+            "#!/bin/sh\nwhile [ $# -gt 0 ]; do\n  [ \"$1\" = -o ] && out=\"$2\"\n  shift\ndone\n[ -n \"$out\" ] || exit 1\nenv > \"$out\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&compiler, std::fs::Permissions::from_mode(0o755)).unwrap();
+        cc::Build::new()
+            .cargo_metadata(false)
+            .cargo_warnings(false)
+            .opt_level(0)
+            .host(&Triple::host().to_string())
+            .target(&Triple::host().to_string())
+            .compiler(&compiler)
+            .try_get_compiler()
+            .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn c_compiler_identity_uses_the_configured_toolchain() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let compiler = temp.path().join("fake-cc");
+        std::fs::write(
+            &compiler,
+            "#!/bin/sh\n[ \"$1\" = --version ] || exit 1\necho 'fake-cc (GCC) 1.2.3'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&compiler, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let toolchain = Toolchain {
+            cc: Some(compiler.to_string_lossy().to_string()),
+            ..Toolchain::default()
+        };
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .toolchains(Some(HashMap::from_iter([("fake".into(), toolchain)])))
+            .toolchain(Some("fake".into()))
+            .build()
+            .unwrap();
+        assert_eq!(c_compiler_identity(&config), Some("gnu 1.2.3".into()));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn compile_intermediates_scrubs_the_environment() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let compiler = env_dumping_compiler(temp.path());
+        let source = temp.path().join("foo.c");
+        std::fs::write(&source, "int foo;").unwrap();
+        let compile = |config: Config| {
+            let compiler = compiler.clone();
+            let source = source.clone();
+            let out_dir = temp.path().to_path_buf();
+            async move {
+                let objects = compile_intermediates(None, &compiler, &[source], &out_dir, &config)
+                    .await
+                    .unwrap();
+                std::fs::read_to_string(&objects[0]).unwrap()
+            }
+        };
+
+        // Cargo sets `CARGO_MANIFEST_DIR` when running tests.
+        let env = compile(ConfigBuilder::new().unwrap().build().unwrap()).await;
+        assert!(env.contains("CARGO_MANIFEST_DIR="));
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .build_env_allowlist(Some(Vec::new()))
+            .build()
+            .unwrap();
+        let env = compile(config).await;
+        assert!(!env.contains("CARGO_MANIFEST_DIR="));
+        assert!(env.contains("PATH="));
+    }
 }
@@ -1,6 +1,8 @@
 use std::{collections::HashMap, path::PathBuf};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::build::system_package::SystemPackageManager;
 
 /// Used as a fallback when searching for external dependencies if they
 /// cannot be found using pkg-config.
@@ -30,6 +32,44 @@ pub struct ExternalDependencySearchConfig {
     /// Known installation prefixes for specific dependencies.
     /// These can also be set via environment variables.
     pub(crate) prefixes: HashMap<String, PathBuf>,
+    /// Whether to query Homebrew for the installation prefix of a dependency.
+    /// Enabled by default on macOS.
+    #[serde(default = "default_homebrew")]
+    pub(crate) homebrew: bool,
+    /// Whether to search the vcpkg installation tree for a dependency.
+    /// Enabled by default on Windows.
+    #[serde(default = "default_vcpkg")]
+    pub(crate) vcpkg: bool,
+    /// The vcpkg root directory.
+    /// Defaults to the `VCPKG_ROOT` environment variable.
+    #[serde(default)]
+    pub(crate) vcpkg_root: Option<PathBuf>,
+    /// The vcpkg triplet to search, e.g. `x64-windows`.
+    /// Defaults to the `VCPKG_DEFAULT_TRIPLET` environment variable,
+    /// or the triplet for the host platform.
+    #[serde(default)]
+    pub(crate) vcpkg_triplet: Option<String>,
+    /// System packages that provide external dependencies, per package manager.
+    /// Entries take precedence over the built-in defaults. For example:
+    /// `system_packages.foo = { apt = "libfoo-dev", dnf = "foo-devel" }`
+    /// Dependency names are case-insensitive.
+    #[serde(default, deserialize_with = "deserialize_system_packages")]
+    pub(crate) system_packages: HashMap<String, HashMap<SystemPackageManager, String>>,
+}
+
+/// Lowercases the dependency names, which system packages are looked up by.
+fn deserialize_system_packages<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, HashMap<SystemPackageManager, String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(
+        HashMap::<String, HashMap<SystemPackageManager, String>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(dependency, packages)| (dependency.to_lowercase(), packages))
+            .collect(),
+    )
 }
 
 impl Default for ExternalDependencySearchConfig {
@@ -43,10 +83,53 @@ impl Default for ExternalDependencySearchConfig {
             lib_subdirs: default_lib_subdirs(),
             search_prefixes: default_prefixes(),
             prefixes: HashMap::default(),
+            homebrew: default_homebrew(),
+            vcpkg: default_vcpkg(),
+            vcpkg_root: None,
+            vcpkg_triplet: None,
+            system_packages: HashMap::default(),
         }
     }
 }
 
+impl ExternalDependencySearchConfig {
+    /// The vcpkg installation prefix for the configured (or default) triplet.
+    pub(crate) fn vcpkg_installed_prefix(&self) -> Option<PathBuf> {
+        let root = self
+            .vcpkg_root
+            .clone()
+            .or_else(|| std::env::var("VCPKG_ROOT").ok().map(PathBuf::from))?;
+        let triplet = self
+            .vcpkg_triplet
+            .clone()
+            .or_else(|| std::env::var("VCPKG_DEFAULT_TRIPLET").ok())
+            .unwrap_or_else(default_vcpkg_triplet);
+        Some(root.join("installed").join(triplet))
+    }
+}
+
+fn default_homebrew() -> bool {
+    cfg!(target_os = "macos")
+}
+
+fn default_vcpkg() -> bool {
+    cfg!(target_family = "windows")
+}
+
+fn default_vcpkg_triplet() -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "x86" => "x86",
+        "aarch64" => "arm64",
+        arch => arch,
+    };
+    let os = match std::env::consts::OS {
+        "macos" => "osx",
+        os => os,
+    };
+    format!("{arch}-{os}")
+}
+
 fn default_bin_patterns() -> Vec<String> {
     vec!["?".into()]
 }
@@ -0,0 +1,286 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use mlua::{Lua, Table, Value};
+use strum::IntoEnumIterator;
+use thiserror::Error;
+use url::Url;
+
+use crate::lua_rockspec::PlatformIdentifier;
+
+use super::{tree::NamedTree, ConfigBuilder, LuaVersion};
+
+#[derive(Error, Debug)]
+pub enum LuarocksConfigError {
+    #[error("failed to read luarocks config: {0}")]
+    Io(#[from] io::Error),
+    #[error("error evaluating luarocks config: {0}")]
+    Lua(#[from] mlua::Error),
+    #[error("invalid URL in luarocks `rocks_servers`: {0}")]
+    Url(#[from] url::ParseError),
+    #[error(
+        "expected the `{field}` entry of the luarocks config to be {expected}, but got a {actual}."
+    )]
+    InvalidType {
+        field: String,
+        expected: &'static str,
+        actual: &'static str,
+    },
+}
+
+/// The settings of a luarocks config file (`config-5.x.lua` or `config.lua`)
+/// that have an equivalent in the lux config.
+#[derive(Debug, Default, PartialEq)]
+pub struct LuarocksConfig {
+    /// The `rocks_servers`, in order of preference.
+    /// For servers with mirrors, only the first mirror is kept.
+    pub rocks_servers: Vec<Url>,
+    /// The `rocks_trees`, with their names.
+    /// Unnamed trees are named `luarocks-<n>`, where `<n>` is their position in the list.
+    pub rocks_trees: Vec<(String, PathBuf)>,
+    pub variables: HashMap<String, String>,
+    pub lua_version: Option<LuaVersion>,
+}
+
+impl LuarocksConfig {
+    /// Evaluate a luarocks config file.
+    /// The Lua version is inferred from the file name if the config doesn't set it,
+    /// e.g. `config-5.1.lua`.
+    pub fn from_file(path: &Path) -> Result<Self, LuarocksConfigError> {
+        let content = std::fs::read_to_string(path)?;
+        let file_lua_version = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix("config-"))
+            .and_then(|version| version.parse().ok());
+        Self::evaluate(&content, file_lua_version)
+    }
+
+    pub fn new(content: &str) -> Result<Self, LuarocksConfigError> {
+        Self::evaluate(content, None)
+    }
+
+    /// The user config files luarocks reads by default, most specific first.
+    pub fn default_config_files() -> Vec<PathBuf> {
+        let Some(home) = home_dir() else {
+            return Vec::new();
+        };
+        let luarocks_dir = home.join(".luarocks");
+        ["5.4", "5.3", "5.2", "5.1"]
+            .into_iter()
+            .map(|version| luarocks_dir.join(format!("config-{version}.lua")))
+            .chain(std::iter::once(luarocks_dir.join("config.lua")))
+            .collect()
+    }
+
+    fn evaluate(
+        content: &str,
+        file_lua_version: Option<LuaVersion>,
+    ) -> Result<Self, LuarocksConfigError> {
+        let lua = Lua::new();
+        let globals = lua.globals();
+        // luarocks evaluates its config files with these globals set
+        if let Some(home) = home_dir() {
+            globals.set("home", home.to_string_lossy().to_string())?;
+        }
+        if let Some(lua_version) = &file_lua_version {
+            globals.set("lua_version", lua_version.to_string())?;
+        }
+        globals.set(
+            "os_getenv",
+            lua.create_function(|_, name: String| Ok(std::env::var(name).ok()))?,
+        )?;
+        let platforms = lua.create_table()?;
+        let current_platform = PlatformIdentifier::default();
+        for platform in PlatformIdentifier::iter().filter(|platform| platform <= &current_platform)
+        {
+            platforms.set(platform.to_string(), true)?;
+        }
+        globals.set("platforms", platforms)?;
+        globals.set("processor", std::env::consts::ARCH)?;
+        globals.set("target_cpu", std::env::consts::ARCH)?;
+        globals.set("variables", lua.create_table()?)?;
+
+        lua.load(content).exec()?;
+
+        let rocks_servers = match globals.get("rocks_servers")? {
+            Value::Nil => Vec::new(),
+            Value::Table(servers) => servers
+                .sequence_values::<Value>()
+                .filter_map(|server| match server {
+                    Ok(Value::Table(mirrors)) => mirrors.get::<Option<String>>(1).transpose(),
+                    server => server
+                        .and_then(|server| lua.unpack(server))
+                        .map(Some)
+                        .transpose(),
+                })
+                .map(|server| Ok(Url::parse(&server?)?))
+                .collect::<Result<_, LuarocksConfigError>>()?,
+            value => return Err(invalid_type("rocks_servers", "a list", &value)),
+        };
+
+        let rocks_trees = match globals.get("rocks_trees")? {
+            Value::Nil => Vec::new(),
+            Value::Table(trees) => trees
+                .sequence_values::<Value>()
+                .enumerate()
+                .map(|(index, tree)| {
+                    let default_name = format!("luarocks-{}", index + 1);
+                    match tree? {
+                        Value::String(root) => Ok((default_name, root.to_str()?.as_ref().into())),
+                        Value::Table(tree) => {
+                            let name: Option<String> = tree.get("name")?;
+                            let root: String = tree.get("root")?;
+                            Ok((name.unwrap_or(default_name), root.into()))
+                        }
+                        value => Err(invalid_type("rocks_trees", "a list of trees", &value)),
+                    }
+                })
+                .collect::<Result<_, LuarocksConfigError>>()?,
+            value => return Err(invalid_type("rocks_trees", "a list", &value)),
+        };
+
+        let variables: Table = match globals.get("variables")? {
+            Value::Table(variables) => variables,
+            value => return Err(invalid_type("variables", "a table", &value)),
+        };
+        let variables = variables
+            .pairs::<String, Value>()
+            .filter_map(|pair| match pair {
+                Ok((key, Value::String(value))) => Some(
+                    value
+                        .to_str()
+                        .map(|value| (key, value.to_string()))
+                        .map_err(LuarocksConfigError::from),
+                ),
+                Ok((key, Value::Integer(value))) => Some(Ok((key, value.to_string()))),
+                Ok((key, Value::Number(value))) => Some(Ok((key, value.to_string()))),
+                Ok(_) => None,
+                Err(err) => Some(Err(err.into())),
+            })
+            .collect::<Result<_, _>>()?;
+
+        let lua_version = match globals.get("lua_version")? {
+            Value::String(version) => version.to_str()?.parse().ok(),
+            _ => None,
+        }
+        .or(file_lua_version);
+
+        Ok(Self {
+            rocks_servers,
+            rocks_trees,
+            variables,
+            lua_version,
+        })
+    }
+}
+
+impl ConfigBuilder {
+    /// Merge the settings of a luarocks config into this config.
+    /// The luarocks settings take precedence over existing ones.
+    pub fn with_luarocks_config(self, luarocks: LuarocksConfig) -> Self {
+        let mut servers = luarocks.rocks_servers.into_iter();
+        let server = servers.next();
+        let extra_servers = servers.collect::<Vec<_>>();
+        let trees = self
+            .trees
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .chain(
+                luarocks
+                    .rocks_trees
+                    .into_iter()
+                    .map(|(name, root)| (name, NamedTree { root })),
+            )
+            .collect::<HashMap<_, _>>();
+        let variables = self
+            .variables
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .chain(luarocks.variables)
+            .collect::<HashMap<_, _>>();
+        self.server(server)
+            .extra_servers((!extra_servers.is_empty()).then_some(extra_servers))
+            .trees((!trees.is_empty()).then_some(trees))
+            .variables((!variables.is_empty()).then_some(variables))
+            .lua_version(luarocks.lua_version)
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf())
+}
+
+fn invalid_type(field: &str, expected: &'static str, value: &Value) -> LuarocksConfigError {
+    LuarocksConfigError::InvalidType {
+        field: field.to_string(),
+        expected,
+        actual: value.type_name(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_luarocks_config() {
+        let content = r#"
+            rocks_trees = {
+                { name = "user", root = "/home/user/.luarocks" },
+                "/opt/rocks",
+            }
+            rocks_servers = {
+                "https://example.com/rocks",
+                { "https://mirror.example.com", "https://fallback.example.com" },
+            }
+            variables.CC = "clang"
+            variables.LUA_INCDIR = "/usr/include/lua5.1"
+            if platforms.windows then
+                variables.MAKE = "mingw32-make"
+            end
+            lua_version = "5.1"
+        "#;
+        let luarocks = LuarocksConfig::new(content).unwrap();
+        assert_eq!(
+            luarocks,
+            LuarocksConfig {
+                rocks_servers: vec![
+                    Url::parse("https://example.com/rocks").unwrap(),
+                    Url::parse("https://mirror.example.com").unwrap(),
+                ],
+                rocks_trees: vec![
+                    ("user".into(), "/home/user/.luarocks".into()),
+                    ("luarocks-2".into(), "/opt/rocks".into()),
+                ],
+                variables: HashMap::from([
+                    ("CC".into(), "clang".into()),
+                    ("LUA_INCDIR".into(), "/usr/include/lua5.1".into()),
+                ]),
+                lua_version: Some(LuaVersion::Lua51),
+            }
+        );
+
+        let config = ConfigBuilder::default()
+            .with_luarocks_config(luarocks)
+            .build()
+            .unwrap();
+        assert_eq!(config.server().as_str(), "https://example.com/rocks");
+        assert_eq!(
+            config.extra_servers(),
+            &vec![Url::parse("https://mirror.example.com").unwrap()]
+        );
+        assert_eq!(config.variables().get("CC").unwrap(), "clang");
+        assert_eq!(
+            config.trees().get("user").unwrap().root,
+            PathBuf::from("/home/user/.luarocks")
+        );
+
+        let _ = LuarocksConfig::new("rocks_servers = 'https://example.com'").unwrap_err();
+    }
+}
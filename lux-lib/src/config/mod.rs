@@ -2,25 +2,44 @@ use directories::ProjectDirs;
 use external_deps::ExternalDependencySearchConfig;
 use itertools::Itertools;
 use mlua::{ExternalError, ExternalResult, FromLua, IntoLua, UserData};
+use profile::BuildProfile;
 use serde::{Deserialize, Serialize, Serializer};
 use std::{
-    collections::HashMap, env, fmt::Display, io, path::PathBuf, str::FromStr, time::Duration,
+    collections::HashMap,
+    env,
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
 use thiserror::Error;
-use tree::RockLayoutConfig;
+use toolchain::Toolchain;
+use tree::{NamedTree, RockLayoutConfig};
 use url::Url;
 
-use crate::tree::{Tree, TreeError};
+use crate::http::HttpClient;
+use crate::tree::{Tree, TreeError, TreeRecovery};
 use crate::variables::GetVariableError;
 use crate::{
-    build::utils,
+    build::{
+        compiler_cache::CompilerCache, debug_symbols::DebugSymbols, sanitizer::Sanitizer, utils,
+    },
+    lockfile::signature::parse_trusted_key,
     package::{PackageVersion, PackageVersionReq},
     variables::HasVariables,
 };
 
 pub mod external_deps;
+pub mod luarocks;
+mod overrides;
+pub mod profile;
+pub mod toolchain;
 pub mod tree;
 
+pub use overrides::*;
+
 const DEV_PATH: &str = "dev/";
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -85,6 +104,16 @@ impl LuaVersion {
             .unwrap()
     }
 
+    /// Whether this Lua version satisfies a `lua` version requirement.
+    /// An exact requirement, like `==5.1.5`, pins a patch release of a Lua version,
+    /// so it is satisfied by that Lua version.
+    pub(crate) fn satisfies(&self, lua_req: &PackageVersionReq) -> bool {
+        match lua_req.exact_version() {
+            Some(version) => LuaVersion::from_version(version).is_ok_and(|v| &v == self),
+            None => lua_req.matches(&self.as_version()),
+        }
+    }
+
     /// Get the LuaVersion from a version that has been parsed from the `lua -v` output
     pub fn from_version(version: PackageVersion) -> Result<LuaVersion, LuaVersionError> {
         // NOTE: Special case. luajit -v outputs 2.x.y as a version
@@ -136,6 +165,15 @@ impl LuaVersion {
     }
 }
 
+impl HasVariables for LuaVersion {
+    fn get_variable(&self, input: &str) -> Result<Option<String>, GetVariableError> {
+        Ok(match input {
+            "LUA_VERSION" => Some(self.version_compatibility_str()),
+            _ => None,
+        })
+    }
+}
+
 impl FromStr for LuaVersion {
     type Err = String;
 
@@ -168,6 +206,61 @@ impl Display for LuaVersion {
     }
 }
 
+/// How the resolver selects a version among the ones that satisfy a requirement.
+///
+/// Candidates are taken from the first server that has a match,
+/// checking the development servers, the extra servers and the main server, in that order.
+/// Among that server's candidates, ties between equal versions are broken
+/// by rock type (binary, then rockspec, then source rock) and then by the version string,
+/// so that resolution does not depend on the manifest's ordering.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResolutionStrategy {
+    /// Select the highest version that satisfies the requirement.
+    #[default]
+    Highest,
+    /// Select the lowest version that satisfies the requirement.
+    /// Useful for checking that a package's lower version bounds are accurate.
+    Minimal,
+}
+
+impl FromStr for ResolutionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "highest" => Ok(Self::Highest),
+            "minimal" => Ok(Self::Minimal),
+            _ => Err(
+                "unrecognized resolution strategy. Allowed strategies: 'highest', 'minimal'."
+                    .into(),
+            ),
+        }
+    }
+}
+
+impl Display for ResolutionStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Highest => "highest",
+            Self::Minimal => "minimal",
+        })
+    }
+}
+
+impl FromLua for ResolutionStrategy {
+    fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
+        let strategy_str: String = FromLua::from_lua(value, lua)?;
+        Self::from_str(&strategy_str).into_lua_err()
+    }
+}
+
+impl IntoLua for ResolutionStrategy {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        self.to_string().into_lua(lua)
+    }
+}
+
 #[derive(Error, Debug)]
 #[error("could not find a valid home directory")]
 pub struct NoValidHomeDirectory;
@@ -182,6 +275,13 @@ pub struct Config {
     lua_dir: Option<PathBuf>,
     lua_version: Option<LuaVersion>,
     user_tree: PathBuf,
+    /// A read-only tree, e.g. provisioned by a distribution package or a container image,
+    /// whose packages are available in addition to those of the writable tree.
+    system_tree: Option<PathBuf>,
+    /// Named install trees, which can be selected instead of the default user tree.
+    trees: HashMap<String, NamedTree>,
+    /// The name of the selected named tree, if any.
+    tree_name: Option<String>,
     no_project: bool,
     verbose: bool,
     timeout: Duration,
@@ -194,6 +294,63 @@ pub struct Config {
     cache_dir: PathBuf,
     data_dir: PathBuf,
     generate_luarc: bool,
+    /// Overrides the rockspecs' `deploy.wrap_bin_scripts`.
+    wrap_bin_scripts: Option<bool>,
+    /// Express paths written during install, e.g. in bin wrappers, pkg-config files
+    /// and generated loaders, relative to the tree, so that a built tree
+    /// can be moved to a different location without rebuilding.
+    relocatable: bool,
+    /// The maximum time a build step (e.g. `make` or `cmake`) may take.
+    build_timeout: Option<Duration>,
+    /// How long to wait for another process to release its lock on an install tree.
+    /// Zero means waiting indefinitely.
+    lock_timeout: Duration,
+    /// The maximum memory, in MiB, each spawned build or test process may use.
+    process_memory_limit: Option<u64>,
+    /// The maximum CPU time each spawned build or test process may use.
+    process_cpu_time_limit: Option<Duration>,
+    /// How the resolver selects among the versions that satisfy a requirement.
+    resolution_strategy: ResolutionStrategy,
+    /// Named compiler toolchain presets, in addition to the built-in `gcc` and `clang`.
+    toolchains: HashMap<String, Toolchain>,
+    /// The name of the selected toolchain preset.
+    toolchain_preset: Option<String>,
+    /// The toolchain to build C modules with.
+    toolchain: Option<Toolchain>,
+    /// The name of the selected build profile, if any.
+    profile: Option<String>,
+    /// The root of the project trees, if projects are built outside of their `.lux` directory.
+    project_tree_root: Option<PathBuf>,
+    /// The compiler cache to wrap C compiler invocations in.
+    compiler_cache: Option<CompilerCache>,
+    /// What to do with the debug symbols of built C modules.
+    debug_symbols: DebugSymbols,
+    /// The sanitizers to instrument C modules with.
+    sanitizers: Vec<Sanitizer>,
+    /// The environment variables that build processes inherit, in addition to
+    /// the ones that build tools need to run.
+    /// If unset, build processes inherit the whole environment.
+    build_env_allowlist: Option<Vec<String>>,
+    /// What to do with packages in a tree's lockfile whose install directories are missing.
+    tree_recovery: TreeRecovery,
+    /// Refuse to sync a project whose lockfile isn't signed by a trusted key.
+    require_signed_lockfile: bool,
+    /// The fingerprints or long key IDs of the keys that are trusted to sign lockfiles.
+    trusted_lockfile_keys: Vec<String>,
+    /// How often to retry idempotent HTTP requests that fail with a transient error.
+    http_retries: usize,
+    /// The maximum number of HTTP requests per second for each host.
+    http_rate_limits: HashMap<String, u32>,
+    /// Allow package servers to be accessed over plain HTTP.
+    allow_insecure_servers: bool,
+    /// The hosts of package servers that may be accessed over plain HTTP,
+    /// e.g. intranet registries.
+    insecure_servers: Vec<String>,
+    /// Refuse to load a project whose lux.toml contains unknown fields.
+    deny_unknown_fields: bool,
+    /// The HTTP client, which is created when it is first used
+    /// and shared by all clones of this config.
+    http_client: Arc<OnceLock<HttpClient>>,
 }
 
 impl Config {
@@ -221,6 +378,32 @@ impl Config {
     pub fn with_tree(self, tree: PathBuf) -> Self {
         Self {
             user_tree: tree,
+            tree_name: None,
+            ..self
+        }
+    }
+
+    /// Override whether to wrap Lua bin scripts,
+    /// taking precedence over the rockspecs' `deploy.wrap_bin_scripts`.
+    pub fn with_wrap_bin_scripts(self, wrap_bin_scripts: bool) -> Self {
+        Self {
+            wrap_bin_scripts: Some(wrap_bin_scripts),
+            ..self
+        }
+    }
+
+    pub fn with_resolution_strategy(self, resolution_strategy: ResolutionStrategy) -> Self {
+        Self {
+            resolution_strategy,
+            ..self
+        }
+    }
+
+    /// Merge `variables` over the existing variables,
+    /// taking precedence over any existing entries.
+    pub fn with_variables(self, variables: HashMap<String, String>) -> Self {
+        Self {
+            variables: self.variables.into_iter().chain(variables).collect(),
             ..self
         }
     }
@@ -267,6 +450,35 @@ impl Config {
         Tree::new(self.user_tree.clone(), version, self)
     }
 
+    /// The root of a read-only tree, whose packages are used to satisfy dependencies
+    /// in addition to those installed in the user or project tree.
+    /// Packages are never installed into the system tree.
+    pub fn system_tree(&self) -> Option<&PathBuf> {
+        self.system_tree.as_ref()
+    }
+
+    /// The named install trees defined in the config.
+    pub fn trees(&self) -> &HashMap<String, NamedTree> {
+        &self.trees
+    }
+
+    /// The name of the selected named tree, if the user tree is a named tree.
+    pub fn tree_name(&self) -> Option<&String> {
+        self.tree_name.as_ref()
+    }
+
+    /// Select a named tree as the user tree.
+    pub fn with_tree_name(self, tree_name: String) -> Result<Self, ConfigError> {
+        match self.trees.get(&tree_name) {
+            Some(tree) => Ok(Self {
+                user_tree: tree.root.clone(),
+                tree_name: Some(tree_name),
+                ..self
+            }),
+            None => Err(ConfigError::UnknownTree(tree_name)),
+        }
+    }
+
     pub fn no_project(&self) -> bool {
         self.no_project
     }
@@ -297,6 +509,17 @@ impl Config {
         &self.variables
     }
 
+    /// The variables that differ from lux's built-in defaults,
+    /// e.g. those set in the config file or by the toolchain, sorted by name.
+    pub fn custom_variables(&self) -> Vec<(&String, &String)> {
+        let defaults: HashMap<String, String> = default_variables().collect();
+        self.variables
+            .iter()
+            .filter(|(name, value)| defaults.get(*name) != Some(*value))
+            .sorted()
+            .collect()
+    }
+
     pub fn external_deps(&self) -> &ExternalDependencySearchConfig {
         &self.external_deps
     }
@@ -316,6 +539,229 @@ impl Config {
     pub fn generate_luarc(&self) -> bool {
         self.generate_luarc
     }
+
+    /// If set, overrides the rockspecs' `deploy.wrap_bin_scripts`.
+    pub fn wrap_bin_scripts(&self) -> Option<bool> {
+        self.wrap_bin_scripts
+    }
+
+    pub fn relocatable(&self) -> bool {
+        self.relocatable
+    }
+
+    pub fn build_timeout(&self) -> Option<&Duration> {
+        self.build_timeout.as_ref()
+    }
+
+    /// How long to wait for another process to release its lock on an install tree.
+    /// Zero means waiting indefinitely.
+    pub fn lock_timeout(&self) -> Duration {
+        self.lock_timeout
+    }
+
+    /// The maximum memory, in MiB, each spawned build or test process may use.
+    pub fn process_memory_limit(&self) -> Option<u64> {
+        self.process_memory_limit
+    }
+
+    pub fn process_cpu_time_limit(&self) -> Option<&Duration> {
+        self.process_cpu_time_limit.as_ref()
+    }
+
+    pub fn resolution_strategy(&self) -> ResolutionStrategy {
+        self.resolution_strategy
+    }
+
+    /// Select the toolchain preset to build C modules with,
+    /// taking precedence over the projects' `[build.toolchain]`.
+    pub fn with_toolchain(self, toolchain: String) -> Result<Self, ConfigError> {
+        match self.toolchain_preset(&toolchain) {
+            Some(preset) => Ok(Self {
+                toolchain_preset: Some(toolchain),
+                toolchain: Some(preset),
+                ..self
+            }),
+            None => Err(ConfigError::UnknownToolchain(toolchain)),
+        }
+    }
+
+    /// Select a build profile, whose flags are appended to the toolchain's flags.
+    /// The profile's `debug_symbols`, if set, take precedence.
+    /// Projects are built to a separate tree for each profile.
+    pub fn with_profile(self, name: String, profile: &BuildProfile) -> Self {
+        let toolchain = match &self.toolchain {
+            Some(toolchain) => toolchain.apply_overrides(&profile.toolchain()),
+            None => profile.toolchain(),
+        };
+        Self {
+            toolchain: Some(toolchain),
+            profile: Some(name),
+            debug_symbols: profile.debug_symbols.unwrap_or(self.debug_symbols),
+            ..self
+        }
+    }
+
+    /// The name of the selected build profile, if any.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Build and install projects and their dependencies into a tree at `root`,
+    /// e.g. a staging directory for packaging, instead of the project's `.lux` directory.
+    /// Takes precedence over the separate trees of build profiles.
+    pub fn with_project_tree_root(self, root: PathBuf) -> Self {
+        Self {
+            project_tree_root: Some(root),
+            ..self
+        }
+    }
+
+    /// The root of the project trees, if set with [`Config::with_project_tree_root`].
+    pub fn project_tree_root(&self) -> Option<&Path> {
+        self.project_tree_root.as_deref()
+    }
+
+    /// Apply the selected toolchain preset, if any, on top of a package's `toolchain`,
+    /// and set the toolchain's variables.
+    pub(crate) fn with_package_toolchain(self, toolchain: Option<&Toolchain>) -> Self {
+        let toolchain = match (toolchain, &self.toolchain) {
+            (Some(toolchain), Some(preset)) => Some(toolchain.apply_overrides(preset)),
+            (toolchain, preset) => toolchain.or(preset.as_ref()).cloned(),
+        };
+        let variables = toolchain
+            .as_ref()
+            .map(|toolchain| toolchain.variables(&self.variables))
+            .unwrap_or_default();
+        Self { toolchain, ..self }.with_variables(variables)
+    }
+
+    /// The toolchain to build C modules with, if not the default toolchain.
+    pub fn toolchain(&self) -> Option<&Toolchain> {
+        self.toolchain.as_ref()
+    }
+
+    /// The variables that the toolchain sets, e.g. `CC`, to pass to the build backends.
+    pub(crate) fn toolchain_variables(&self) -> Vec<(&'static str, &String)> {
+        self.toolchain
+            .iter()
+            .flat_map(|toolchain| toolchain.variable_names())
+            .filter_map(|name| self.variables.get(name).map(|value| (name, value)))
+            .collect()
+    }
+
+    pub fn compiler_cache(&self) -> Option<CompilerCache> {
+        self.compiler_cache
+    }
+
+    pub fn debug_symbols(&self) -> DebugSymbols {
+        self.debug_symbols
+    }
+
+    /// What to do with packages in a tree's lockfile whose install directories are missing,
+    /// e.g. because they were deleted manually.
+    pub fn tree_recovery(&self) -> TreeRecovery {
+        self.tree_recovery
+    }
+
+    /// Whether lockfiles must have a valid signature by one of the
+    /// `trusted_lockfile_keys` before packages are installed from them.
+    pub fn require_signed_lockfile(&self) -> bool {
+        self.require_signed_lockfile
+    }
+
+    /// The fingerprints or long key IDs of the keys that are trusted to sign lockfiles.
+    pub fn trusted_lockfile_keys(&self) -> &[String] {
+        &self.trusted_lockfile_keys
+    }
+
+    /// How often to retry idempotent HTTP requests that fail with a connection error,
+    /// a timeout, a `429` or a server error.
+    pub fn http_retries(&self) -> usize {
+        self.http_retries
+    }
+
+    /// The maximum number of HTTP requests per second for each host.
+    pub fn http_rate_limits(&self) -> &HashMap<String, u32> {
+        &self.http_rate_limits
+    }
+
+    /// Whether package servers may be accessed over plain HTTP.
+    /// By default, manifests and rocks are only downloaded, and rocks only uploaded,
+    /// over HTTPS, and redirects to plain HTTP are refused.
+    pub fn allow_insecure_servers(&self) -> bool {
+        self.allow_insecure_servers
+    }
+
+    /// The hosts of package servers that may be accessed over plain HTTP,
+    /// even if `allow_insecure_servers` is not set, e.g. intranet registries.
+    pub fn insecure_servers(&self) -> &[String] {
+        &self.insecure_servers
+    }
+
+    /// Whether unknown fields in a project's lux.toml are errors.
+    /// By default, they are reported as warnings.
+    pub fn deny_unknown_fields(&self) -> bool {
+        self.deny_unknown_fields
+    }
+
+    /// The HTTP client that all requests are sent with.
+    pub fn http_client(&self) -> Result<&HttpClient, reqwest::Error> {
+        if let Some(client) = self.http_client.get() {
+            return Ok(client);
+        }
+        let client = HttpClient::new(self)?;
+        Ok(self.http_client.get_or_init(|| client))
+    }
+
+    pub fn sanitizers(&self) -> &[Sanitizer] {
+        &self.sanitizers
+    }
+
+    /// The environment variables that build processes inherit, in addition to
+    /// the ones that build tools need to run.
+    /// Entries ending in `*` match any variable with that prefix, e.g. `LC_*`.
+    /// If `None`, build processes inherit the whole environment.
+    pub fn build_env_allowlist(&self) -> Option<&Vec<String>> {
+        self.build_env_allowlist.as_ref()
+    }
+
+    /// Allow build processes to inherit the `variables`, in addition to the configured allowlist.
+    /// This scrubs the environment of build processes, even if no allowlist is configured.
+    pub fn with_build_env(self, variables: Vec<String>) -> Self {
+        let build_env_allowlist = self
+            .build_env_allowlist
+            .into_iter()
+            .flatten()
+            .chain(variables)
+            .unique()
+            .collect();
+        Self {
+            build_env_allowlist: Some(build_env_allowlist),
+            ..self
+        }
+    }
+
+    /// Instrument C modules built by the builtin backend with `sanitizers`.
+    pub fn with_sanitizers(self, sanitizers: Vec<Sanitizer>) -> Self {
+        Self { sanitizers, ..self }
+    }
+
+    /// Keep, strip or split the debug symbols of built C modules.
+    pub fn with_debug_symbols(self, debug_symbols: DebugSymbols) -> Self {
+        Self {
+            debug_symbols,
+            ..self
+        }
+    }
+
+    /// Look up a toolchain preset by name.
+    /// Presets in the config take precedence over the built-in presets.
+    pub fn toolchain_preset(&self, name: &str) -> Option<Toolchain> {
+        self.toolchains
+            .get(name)
+            .cloned()
+            .or_else(|| Toolchain::builtin_preset(name))
+    }
 }
 
 impl HasVariables for Config {
@@ -336,6 +782,22 @@ pub enum ConfigError {
     UrlParseError(#[from] url::ParseError),
     #[error("error initializing compiler toolchain: {0}")]
     CompilerToolchain(#[from] cc::Error),
+    #[error("unknown toolchain preset '{0}'. Built-in presets: 'gcc', 'clang'.")]
+    UnknownToolchain(String),
+    #[error(
+        "unknown tree '{0}'. Named trees can be defined in the `[trees]` section of the config."
+    )]
+    UnknownTree(String),
+    #[error("a tree named '{0}' already exists.")]
+    TreeExists(String),
+    #[error("expected the `trees` entry of the lux config to be a table.")]
+    InvalidTrees,
+    #[error("failed to read config file {0}: {1}")]
+    ReadConfigFile(PathBuf, io::Error),
+    #[error("error editing lux config: {0}")]
+    TomlEdit(#[from] toml_edit::TomlError),
+    #[error("invalid trusted lockfile key '{0}'.\nHINT: Use the full 40-digit fingerprint or the 16-digit long key ID.")]
+    InvalidTrustedKey(String),
 }
 
 #[derive(Clone, Default, Deserialize, Serialize)]
@@ -356,6 +818,11 @@ pub struct ConfigBuilder {
     namespace: Option<String>,
     lua_version: Option<LuaVersion>,
     user_tree: Option<PathBuf>,
+    system_tree: Option<PathBuf>,
+    /// Named install trees, e.g.
+    /// `[trees.work] root = "/path/to/tree"`
+    trees: Option<HashMap<String, NamedTree>>,
+    tree_name: Option<String>,
     lua_dir: Option<PathBuf>,
     cache_dir: Option<PathBuf>,
     data_dir: Option<PathBuf>,
@@ -371,6 +838,28 @@ pub struct ConfigBuilder {
     #[serde(default)]
     entrypoint_layout: RockLayoutConfig,
     generate_luarc: Option<bool>,
+    wrap_bin_scripts: Option<bool>,
+    relocatable: Option<bool>,
+    build_timeout: Option<Duration>,
+    lock_timeout: Option<Duration>,
+    process_memory_limit: Option<u64>,
+    process_cpu_time_limit: Option<Duration>,
+    resolution_strategy: Option<ResolutionStrategy>,
+    /// Named compiler toolchain presets, e.g.
+    /// `[toolchains.zig] cc = "zig cc"`
+    toolchains: Option<HashMap<String, Toolchain>>,
+    toolchain: Option<String>,
+    compiler_cache: Option<CompilerCache>,
+    debug_symbols: Option<DebugSymbols>,
+    build_env_allowlist: Option<Vec<String>>,
+    tree_recovery: Option<TreeRecovery>,
+    require_signed_lockfile: Option<bool>,
+    trusted_lockfile_keys: Option<Vec<String>>,
+    http_retries: Option<usize>,
+    http_rate_limits: Option<HashMap<String, u32>>,
+    allow_insecure_servers: Option<bool>,
+    insecure_servers: Option<Vec<String>>,
+    deny_unknown_fields: Option<bool>,
 }
 
 /// A builder for the lux `Config`.
@@ -386,6 +875,30 @@ impl ConfigBuilder {
         }
     }
 
+    /// Create a new `ConfigBuilder` from the config file at `path`, layered on top of
+    /// the default config file, if present.
+    /// Settings in `path` take precedence, and tables such as `[variables]` are merged.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let default_config_file = Self::config_file()?;
+        let base = if default_config_file.is_file() {
+            Some(std::fs::read_to_string(&default_config_file)?)
+        } else {
+            None
+        };
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| ConfigError::ReadConfigFile(path.to_path_buf(), err))?;
+        Self::from_layered_toml(base.as_deref(), &content)
+    }
+
+    fn from_layered_toml(base: Option<&str>, content: &str) -> Result<Self, ConfigError> {
+        let mut table: toml::Table = match base {
+            Some(base) => toml::from_str(base)?,
+            None => toml::Table::new(),
+        };
+        merge_toml_tables(&mut table, toml::from_str(content)?);
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
     /// Get the path to the lux config file.
     pub fn config_file() -> Result<PathBuf, NoValidHomeDirectory> {
         let project_dirs =
@@ -449,6 +962,28 @@ impl ConfigBuilder {
         }
     }
 
+    pub fn system_tree(self, tree: Option<PathBuf>) -> Self {
+        Self {
+            system_tree: tree.or(self.system_tree),
+            ..self
+        }
+    }
+
+    pub fn trees(self, trees: Option<HashMap<String, NamedTree>>) -> Self {
+        Self {
+            trees: trees.or(self.trees),
+            ..self
+        }
+    }
+
+    /// Select a named tree from the `[trees]` section as the user tree.
+    pub fn tree_name(self, tree_name: Option<String>) -> Self {
+        Self {
+            tree_name: tree_name.or(self.tree_name),
+            ..self
+        }
+    }
+
     pub fn no_project(self, no_project: Option<bool>) -> Self {
         Self {
             no_project: no_project.or(self.no_project),
@@ -505,6 +1040,161 @@ impl ConfigBuilder {
         }
     }
 
+    pub fn wrap_bin_scripts(self, wrap_bin_scripts: Option<bool>) -> Self {
+        Self {
+            wrap_bin_scripts: wrap_bin_scripts.or(self.wrap_bin_scripts),
+            ..self
+        }
+    }
+
+    pub fn relocatable(self, relocatable: Option<bool>) -> Self {
+        Self {
+            relocatable: relocatable.or(self.relocatable),
+            ..self
+        }
+    }
+
+    pub fn build_timeout(self, build_timeout: Option<Duration>) -> Self {
+        Self {
+            build_timeout: build_timeout.or(self.build_timeout),
+            ..self
+        }
+    }
+
+    /// Set how long to wait for another process to release its lock on an install tree.
+    pub fn lock_timeout(self, lock_timeout: Option<Duration>) -> Self {
+        Self {
+            lock_timeout: lock_timeout.or(self.lock_timeout),
+            ..self
+        }
+    }
+
+    /// Set the maximum memory, in MiB, each spawned build or test process may use.
+    pub fn process_memory_limit(self, process_memory_limit: Option<u64>) -> Self {
+        Self {
+            process_memory_limit: process_memory_limit.or(self.process_memory_limit),
+            ..self
+        }
+    }
+
+    pub fn process_cpu_time_limit(self, process_cpu_time_limit: Option<Duration>) -> Self {
+        Self {
+            process_cpu_time_limit: process_cpu_time_limit.or(self.process_cpu_time_limit),
+            ..self
+        }
+    }
+
+    pub fn resolution_strategy(self, resolution_strategy: Option<ResolutionStrategy>) -> Self {
+        Self {
+            resolution_strategy: resolution_strategy.or(self.resolution_strategy),
+            ..self
+        }
+    }
+
+    pub fn toolchains(self, toolchains: Option<HashMap<String, Toolchain>>) -> Self {
+        Self {
+            toolchains: toolchains.or(self.toolchains),
+            ..self
+        }
+    }
+
+    /// Set the name of the toolchain preset to build C modules with.
+    pub fn toolchain(self, toolchain: Option<String>) -> Self {
+        Self {
+            toolchain: toolchain.or(self.toolchain),
+            ..self
+        }
+    }
+
+    /// Set the compiler cache to wrap C compiler invocations in.
+    pub fn compiler_cache(self, compiler_cache: Option<CompilerCache>) -> Self {
+        Self {
+            compiler_cache: compiler_cache.or(self.compiler_cache),
+            ..self
+        }
+    }
+
+    /// Set what to do with the debug symbols of built C modules.
+    pub fn debug_symbols(self, debug_symbols: Option<DebugSymbols>) -> Self {
+        Self {
+            debug_symbols: debug_symbols.or(self.debug_symbols),
+            ..self
+        }
+    }
+
+    /// Set the environment variables that build processes inherit.
+    /// Entries ending in `*` match any variable with that prefix.
+    pub fn build_env_allowlist(self, build_env_allowlist: Option<Vec<String>>) -> Self {
+        Self {
+            build_env_allowlist: build_env_allowlist.or(self.build_env_allowlist),
+            ..self
+        }
+    }
+
+    /// Set what to do with packages in a tree's lockfile whose install directories are missing.
+    pub fn tree_recovery(self, tree_recovery: Option<TreeRecovery>) -> Self {
+        Self {
+            tree_recovery: tree_recovery.or(self.tree_recovery),
+            ..self
+        }
+    }
+
+    /// Set whether lockfiles must be signed by a trusted key.
+    pub fn require_signed_lockfile(self, require_signed_lockfile: Option<bool>) -> Self {
+        Self {
+            require_signed_lockfile: require_signed_lockfile.or(self.require_signed_lockfile),
+            ..self
+        }
+    }
+
+    /// Set the fingerprints or long key IDs of the keys that are trusted to sign lockfiles.
+    pub fn trusted_lockfile_keys(self, trusted_lockfile_keys: Option<Vec<String>>) -> Self {
+        Self {
+            trusted_lockfile_keys: trusted_lockfile_keys.or(self.trusted_lockfile_keys),
+            ..self
+        }
+    }
+
+    /// Set how often to retry idempotent HTTP requests that fail with a transient error.
+    pub fn http_retries(self, http_retries: Option<usize>) -> Self {
+        Self {
+            http_retries: http_retries.or(self.http_retries),
+            ..self
+        }
+    }
+
+    /// Set the maximum number of HTTP requests per second for each host.
+    pub fn http_rate_limits(self, http_rate_limits: Option<HashMap<String, u32>>) -> Self {
+        Self {
+            http_rate_limits: http_rate_limits.or(self.http_rate_limits),
+            ..self
+        }
+    }
+
+    /// Set whether package servers may be accessed over plain HTTP.
+    pub fn allow_insecure_servers(self, allow_insecure_servers: Option<bool>) -> Self {
+        Self {
+            allow_insecure_servers: allow_insecure_servers.or(self.allow_insecure_servers),
+            ..self
+        }
+    }
+
+    /// Set the hosts of package servers that may be accessed over plain HTTP.
+    pub fn insecure_servers(self, insecure_servers: Option<Vec<String>>) -> Self {
+        Self {
+            insecure_servers: insecure_servers.or(self.insecure_servers),
+            ..self
+        }
+    }
+
+    /// Set whether unknown fields in a project's lux.toml are errors.
+    pub fn deny_unknown_fields(self, deny_unknown_fields: Option<bool>) -> Self {
+        Self {
+            deny_unknown_fields: deny_unknown_fields.or(self.deny_unknown_fields),
+            ..self
+        }
+    }
+
     pub fn build(self) -> Result<Config, ConfigError> {
         let data_dir = self.data_dir.unwrap_or(Config::get_default_data_path()?);
         let cache_dir = self.cache_dir.unwrap_or(Config::get_default_cache_path()?);
@@ -514,7 +1204,7 @@ impl ConfigBuilder {
             .lua_version
             .or(crate::lua_installation::detect_installed_lua_version());
 
-        Ok(Config {
+        let config = Config {
             enable_development_packages: self.enable_development_packages.unwrap_or(false),
             server: self
                 .server
@@ -525,6 +1215,9 @@ impl ConfigBuilder {
             lua_dir: self.lua_dir,
             lua_version,
             user_tree,
+            system_tree: self.system_tree,
+            trees: self.trees.unwrap_or_default(),
+            tree_name: None,
             no_project: self.no_project.unwrap_or(false),
             verbose: self.verbose.unwrap_or(false),
             timeout: self.timeout.unwrap_or_else(|| Duration::from_secs(30)),
@@ -536,7 +1229,47 @@ impl ConfigBuilder {
             cache_dir,
             data_dir,
             generate_luarc: self.generate_luarc.unwrap_or(true),
-        })
+            wrap_bin_scripts: self.wrap_bin_scripts,
+            relocatable: self.relocatable.unwrap_or(false),
+            build_timeout: self.build_timeout,
+            lock_timeout: self
+                .lock_timeout
+                .unwrap_or_else(|| Duration::from_secs(300)),
+            process_memory_limit: self.process_memory_limit,
+            process_cpu_time_limit: self.process_cpu_time_limit,
+            resolution_strategy: self.resolution_strategy.unwrap_or_default(),
+            toolchains: self.toolchains.unwrap_or_default(),
+            toolchain_preset: None,
+            toolchain: None,
+            profile: None,
+            project_tree_root: None,
+            compiler_cache: self.compiler_cache,
+            debug_symbols: self.debug_symbols.unwrap_or_default(),
+            sanitizers: Vec::new(),
+            build_env_allowlist: self.build_env_allowlist,
+            tree_recovery: self.tree_recovery.unwrap_or_default(),
+            require_signed_lockfile: self.require_signed_lockfile.unwrap_or(false),
+            trusted_lockfile_keys: self
+                .trusted_lockfile_keys
+                .unwrap_or_default()
+                .into_iter()
+                .map(|key| parse_trusted_key(&key).ok_or(ConfigError::InvalidTrustedKey(key)))
+                .try_collect()?,
+            http_retries: self.http_retries.unwrap_or(3),
+            http_rate_limits: self.http_rate_limits.unwrap_or_default(),
+            allow_insecure_servers: self.allow_insecure_servers.unwrap_or(false),
+            insecure_servers: self.insecure_servers.unwrap_or_default(),
+            deny_unknown_fields: self.deny_unknown_fields.unwrap_or(false),
+            http_client: Arc::default(),
+        };
+        let config = match self.tree_name {
+            Some(tree_name) => config.with_tree_name(tree_name)?,
+            None => config,
+        };
+        match self.toolchain {
+            Some(toolchain) => config.with_toolchain(toolchain),
+            None => Ok(config),
+        }
     }
 }
 
@@ -552,6 +1285,9 @@ impl From<Config> for ConfigBuilder {
             lua_dir: value.lua_dir,
             lua_version: value.lua_version,
             user_tree: Some(value.user_tree),
+            system_tree: value.system_tree,
+            trees: Some(value.trees),
+            tree_name: value.tree_name,
             no_project: Some(value.no_project),
             verbose: Some(value.verbose),
             timeout: Some(value.timeout),
@@ -561,6 +1297,40 @@ impl From<Config> for ConfigBuilder {
             external_deps: value.external_deps,
             entrypoint_layout: value.entrypoint_layout,
             generate_luarc: Some(value.generate_luarc),
+            wrap_bin_scripts: value.wrap_bin_scripts,
+            relocatable: Some(value.relocatable),
+            build_timeout: value.build_timeout,
+            lock_timeout: Some(value.lock_timeout),
+            process_memory_limit: value.process_memory_limit,
+            process_cpu_time_limit: value.process_cpu_time_limit,
+            resolution_strategy: Some(value.resolution_strategy),
+            toolchains: Some(value.toolchains),
+            toolchain: value.toolchain_preset,
+            compiler_cache: value.compiler_cache,
+            debug_symbols: Some(value.debug_symbols),
+            build_env_allowlist: value.build_env_allowlist,
+            tree_recovery: Some(value.tree_recovery),
+            require_signed_lockfile: Some(value.require_signed_lockfile),
+            trusted_lockfile_keys: Some(value.trusted_lockfile_keys),
+            http_retries: Some(value.http_retries),
+            http_rate_limits: Some(value.http_rate_limits),
+            allow_insecure_servers: Some(value.allow_insecure_servers),
+            insecure_servers: Some(value.insecure_servers),
+            deny_unknown_fields: Some(value.deny_unknown_fields),
+        }
+    }
+}
+
+/// Merge `overlay` into `base`, recursing into tables that are present in both.
+fn merge_toml_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_toml_tables(base_table, overlay_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
         }
     }
 }
@@ -659,6 +1429,7 @@ impl UserData for Config {
         methods.add_method("user_tree", |_, this, lua_version: LuaVersion| {
             this.user_tree(lua_version).into_lua_err()
         });
+        methods.add_method("system_tree", |_, this, ()| Ok(this.system_tree().cloned()));
         methods.add_method("no_project", |_, this, ()| Ok(this.no_project()));
         methods.add_method("verbose", |_, this, ()| Ok(this.verbose()));
         methods.add_method("timeout", |_, this, ()| Ok(this.timeout().as_secs()));
@@ -668,6 +1439,51 @@ impl UserData for Config {
             Ok(this.entrypoint_layout().clone())
         });
         methods.add_method("variables", |_, this, ()| Ok(this.variables().clone()));
+        methods.add_method(
+            "wrap_bin_scripts",
+            |_, this, ()| Ok(this.wrap_bin_scripts()),
+        );
+        methods.add_method("relocatable", |_, this, ()| Ok(this.relocatable()));
+        methods.add_method("build_timeout", |_, this, ()| {
+            Ok(this.build_timeout().map(Duration::as_secs))
+        });
+        methods.add_method("lock_timeout", |_, this, ()| {
+            Ok(this.lock_timeout().as_secs())
+        });
+        methods.add_method("process_memory_limit", |_, this, ()| {
+            Ok(this.process_memory_limit())
+        });
+        methods.add_method("process_cpu_time_limit", |_, this, ()| {
+            Ok(this.process_cpu_time_limit().map(Duration::as_secs))
+        });
+        methods.add_method("resolution_strategy", |_, this, ()| {
+            Ok(this.resolution_strategy())
+        });
+        methods.add_method("compiler_cache", |_, this, ()| Ok(this.compiler_cache()));
+        methods.add_method("debug_symbols", |_, this, ()| Ok(this.debug_symbols()));
+        methods.add_method("build_env_allowlist", |_, this, ()| {
+            Ok(this.build_env_allowlist().cloned())
+        });
+        methods.add_method("tree_recovery", |_, this, ()| Ok(this.tree_recovery()));
+        methods.add_method("require_signed_lockfile", |_, this, ()| {
+            Ok(this.require_signed_lockfile())
+        });
+        methods.add_method("trusted_lockfile_keys", |_, this, ()| {
+            Ok(this.trusted_lockfile_keys().to_vec())
+        });
+        methods.add_method("http_retries", |_, this, ()| Ok(this.http_retries()));
+        methods.add_method("http_rate_limits", |_, this, ()| {
+            Ok(this.http_rate_limits().clone())
+        });
+        methods.add_method("allow_insecure_servers", |_, this, ()| {
+            Ok(this.allow_insecure_servers())
+        });
+        methods.add_method("insecure_servers", |_, this, ()| {
+            Ok(this.insecure_servers().to_vec())
+        });
+        methods.add_method("deny_unknown_fields", |_, this, ()| {
+            Ok(this.deny_unknown_fields())
+        });
         // FIXME: This is a temporary workaround to get the external_deps hooked up to Lua
         // methods.add_method("external_deps", |_, this, ()| {
         //     Ok(this.external_deps().clone())
@@ -713,6 +1529,9 @@ impl UserData for ConfigBuilder {
         methods.add_method("user_tree", |_, this, tree: Option<PathBuf>| {
             Ok(this.clone().user_tree(tree))
         });
+        methods.add_method("system_tree", |_, this, tree: Option<PathBuf>| {
+            Ok(this.clone().system_tree(tree))
+        });
         methods.add_method("no_project", |_, this, no_project: Option<bool>| {
             Ok(this.clone().no_project(no_project))
         });
@@ -739,6 +1558,94 @@ impl UserData for ConfigBuilder {
         methods.add_method("generate_luarc", |_, this, generate: Option<bool>| {
             Ok(this.clone().generate_luarc(generate))
         });
+        methods.add_method("wrap_bin_scripts", |_, this, wrap: Option<bool>| {
+            Ok(this.clone().wrap_bin_scripts(wrap))
+        });
+        methods.add_method("relocatable", |_, this, relocatable: Option<bool>| {
+            Ok(this.clone().relocatable(relocatable))
+        });
+        methods.add_method("build_timeout", |_, this, timeout: Option<u64>| {
+            Ok(this.clone().build_timeout(timeout.map(Duration::from_secs)))
+        });
+        methods.add_method("lock_timeout", |_, this, timeout: Option<u64>| {
+            Ok(this.clone().lock_timeout(timeout.map(Duration::from_secs)))
+        });
+        methods.add_method("process_memory_limit", |_, this, limit: Option<u64>| {
+            Ok(this.clone().process_memory_limit(limit))
+        });
+        methods.add_method("process_cpu_time_limit", |_, this, limit: Option<u64>| {
+            Ok(this
+                .clone()
+                .process_cpu_time_limit(limit.map(Duration::from_secs)))
+        });
+        methods.add_method(
+            "resolution_strategy",
+            |_, this, strategy: Option<ResolutionStrategy>| {
+                Ok(this.clone().resolution_strategy(strategy))
+            },
+        );
+        methods.add_method("compiler_cache", |_, this, cache: Option<CompilerCache>| {
+            Ok(this.clone().compiler_cache(cache))
+        });
+        methods.add_method(
+            "debug_symbols",
+            |_, this, debug_symbols: Option<DebugSymbols>| {
+                Ok(this.clone().debug_symbols(debug_symbols))
+            },
+        );
+        methods.add_method(
+            "build_env_allowlist",
+            |_, this, build_env_allowlist: Option<Vec<String>>| {
+                Ok(this.clone().build_env_allowlist(build_env_allowlist))
+            },
+        );
+        methods.add_method(
+            "tree_recovery",
+            |_, this, tree_recovery: Option<TreeRecovery>| {
+                Ok(this.clone().tree_recovery(tree_recovery))
+            },
+        );
+        methods.add_method(
+            "require_signed_lockfile",
+            |_, this, require_signed_lockfile: Option<bool>| {
+                Ok(this
+                    .clone()
+                    .require_signed_lockfile(require_signed_lockfile))
+            },
+        );
+        methods.add_method(
+            "trusted_lockfile_keys",
+            |_, this, trusted_lockfile_keys: Option<Vec<String>>| {
+                Ok(this.clone().trusted_lockfile_keys(trusted_lockfile_keys))
+            },
+        );
+        methods.add_method("http_retries", |_, this, http_retries: Option<usize>| {
+            Ok(this.clone().http_retries(http_retries))
+        });
+        methods.add_method(
+            "http_rate_limits",
+            |_, this, http_rate_limits: Option<HashMap<String, u32>>| {
+                Ok(this.clone().http_rate_limits(http_rate_limits))
+            },
+        );
+        methods.add_method(
+            "allow_insecure_servers",
+            |_, this, allow_insecure_servers: Option<bool>| {
+                Ok(this.clone().allow_insecure_servers(allow_insecure_servers))
+            },
+        );
+        methods.add_method(
+            "insecure_servers",
+            |_, this, insecure_servers: Option<Vec<String>>| {
+                Ok(this.clone().insecure_servers(insecure_servers))
+            },
+        );
+        methods.add_method(
+            "deny_unknown_fields",
+            |_, this, deny_unknown_fields: Option<bool>| {
+                Ok(this.clone().deny_unknown_fields(deny_unknown_fields))
+            },
+        );
         methods.add_method("build", |_, this, ()| this.clone().build().into_lua_err());
     }
 }
@@ -0,0 +1,360 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bon::Builder;
+use url::Url;
+
+use super::{tree::RockLayoutConfig, Config, ConfigBuilder, ConfigError, LuaVersion};
+
+/// Overrides for the settings in the lux config file, e.g. from command line flags.
+///
+/// Overrides take precedence over the config file, which takes precedence over the defaults.
+/// Unset options and flags that are `false` leave the config file's settings untouched,
+/// so that e.g. `dev = true` in the config file cannot be disabled by omitting `--dev`.
+#[derive(Builder, Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    /// Enable the development sub-repositories of the luarocks servers.
+    #[builder(default)]
+    dev: bool,
+    server: Option<Url>,
+    /// Replaces the config file's extra servers.
+    extra_servers: Option<Vec<Url>>,
+    only_sources: Option<String>,
+    namespace: Option<String>,
+    lua_dir: Option<PathBuf>,
+    lua_version: Option<LuaVersion>,
+    /// The Lua version to use if neither the overrides nor the config file set one,
+    /// e.g. the current project's [`crate::project::Project::default_lua_version`].
+    /// Takes precedence over the detected Lua installation.
+    default_lua_version: Option<LuaVersion>,
+    user_tree: Option<PathBuf>,
+    /// A read-only tree whose packages are available in addition to the user or project tree's.
+    system_tree: Option<PathBuf>,
+    /// Select a named tree from the config's `[trees]` section.
+    tree_name: Option<String>,
+    cache_dir: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    #[builder(default)]
+    no_project: bool,
+    /// Merged into the config file's variables, replacing variables with the same name.
+    #[builder(default)]
+    variables: HashMap<String, String>,
+    #[builder(default)]
+    verbose: bool,
+    /// Use the Neovim rock layout for new install trees.
+    #[builder(default)]
+    nvim: bool,
+    timeout: Option<Duration>,
+    /// How long to wait for another process to release its lock on an install tree.
+    lock_timeout: Option<Duration>,
+    /// Disable generating a `.luarc.json` when building a project.
+    #[builder(default)]
+    no_luarc: bool,
+    /// Refuse to sync a project whose lockfile isn't signed by a trusted key.
+    #[builder(default)]
+    require_signed_lockfile: bool,
+    /// Allow package servers to be accessed over plain HTTP.
+    #[builder(default)]
+    allow_insecure_servers: bool,
+    /// Treat unknown fields in a project's lux.toml as errors.
+    #[builder(default)]
+    deny_unknown_fields: bool,
+}
+
+impl ConfigBuilder {
+    /// Apply overrides, e.g. from command line flags, on top of this builder.
+    pub fn with_overrides(self, overrides: ConfigOverrides) -> Self {
+        let variables = if overrides.variables.is_empty() {
+            self.variables.clone()
+        } else {
+            Some(
+                self.variables
+                    .clone()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .chain(overrides.variables)
+                    .collect(),
+            )
+        };
+        let builder = if overrides.nvim {
+            self.entrypoint_layout(RockLayoutConfig::new_nvim_layout())
+        } else {
+            self
+        };
+        // An explicit tree takes precedence over a named tree selected in the config file.
+        let builder = if overrides.user_tree.is_some() {
+            Self {
+                tree_name: None,
+                ..builder
+            }
+        } else {
+            builder
+        };
+        let lua_version = overrides
+            .lua_version
+            .or(builder.lua_version.clone())
+            .or(overrides.default_lua_version);
+        builder
+            .dev(overrides.dev.then_some(true))
+            .server(overrides.server)
+            .extra_servers(overrides.extra_servers)
+            .only_sources(overrides.only_sources)
+            .namespace(overrides.namespace)
+            .lua_dir(overrides.lua_dir)
+            .lua_version(lua_version)
+            .user_tree(overrides.user_tree)
+            .system_tree(overrides.system_tree)
+            .tree_name(overrides.tree_name)
+            .cache_dir(overrides.cache_dir)
+            .data_dir(overrides.data_dir)
+            .no_project(overrides.no_project.then_some(true))
+            .variables(variables)
+            .verbose(overrides.verbose.then_some(true))
+            .timeout(overrides.timeout)
+            .lock_timeout(overrides.lock_timeout)
+            .generate_luarc(overrides.no_luarc.then_some(false))
+            .require_signed_lockfile(overrides.require_signed_lockfile.then_some(true))
+            .allow_insecure_servers(overrides.allow_insecure_servers.then_some(true))
+            .deny_unknown_fields(overrides.deny_unknown_fields.then_some(true))
+    }
+}
+
+impl Config {
+    /// Load the lux config file, if present, and apply `overrides` on top of it.
+    /// This resolves the config the same way the `lx` CLI does.
+    pub fn from_overrides(overrides: ConfigOverrides) -> Result<Self, ConfigError> {
+        ConfigBuilder::new()?.with_overrides(overrides).build()
+    }
+
+    /// Like [`Config::from_overrides`], but with the config file at `path` layered
+    /// on top of the default config file.
+    /// Overrides take precedence over `path`, which takes precedence over the default config file.
+    pub fn from_file(path: &Path, overrides: ConfigOverrides) -> Result<Self, ConfigError> {
+        ConfigBuilder::from_file(path)?
+            .with_overrides(overrides)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_file() -> ConfigBuilder {
+        toml::from_str(
+            r#"
+            server = "https://example.com/"
+            namespace = "file"
+            enable_development_packages = true
+            generate_luarc = false
+            verbose = true
+
+            [variables]
+            FOO = "file"
+            BAR = "file"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn unset_overrides_keep_config_file() {
+        let builder = config_file().with_overrides(ConfigOverrides::default());
+        assert_eq!(
+            builder.server,
+            Some("https://example.com/".parse().unwrap())
+        );
+        assert_eq!(builder.namespace, Some("file".into()));
+        assert_eq!(builder.variables.unwrap()["FOO"], "file");
+    }
+
+    #[test]
+    fn false_flags_keep_config_file() {
+        let builder = config_file().with_overrides(
+            ConfigOverrides::builder()
+                .dev(false)
+                .verbose(false)
+                .no_luarc(false)
+                .build(),
+        );
+        assert_eq!(builder.enable_development_packages, Some(true));
+        assert_eq!(builder.verbose, Some(true));
+        assert_eq!(builder.generate_luarc, Some(false));
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_config_file() {
+        let builder = config_file().with_overrides(
+            ConfigOverrides::builder()
+                .server("https://override.com/".parse().unwrap())
+                .namespace("override".into())
+                .no_project(true)
+                .build(),
+        );
+        assert_eq!(
+            builder.server,
+            Some("https://override.com/".parse().unwrap())
+        );
+        assert_eq!(builder.namespace, Some("override".into()));
+        assert_eq!(builder.no_project, Some(true));
+    }
+
+    #[test]
+    fn default_lua_version_precedence() {
+        let lua_version = |config_file: Option<LuaVersion>, overrides: ConfigOverrides| {
+            ConfigBuilder::default()
+                .lua_version(config_file)
+                .with_overrides(overrides)
+                .lua_version
+        };
+        let default = || ConfigOverrides::builder().default_lua_version(LuaVersion::Lua51);
+        assert_eq!(
+            lua_version(None, default().build()),
+            Some(LuaVersion::Lua51)
+        );
+        assert_eq!(
+            lua_version(Some(LuaVersion::Lua53), default().build()),
+            Some(LuaVersion::Lua53)
+        );
+        assert_eq!(
+            lua_version(
+                Some(LuaVersion::Lua53),
+                default().lua_version(LuaVersion::LuaJIT).build()
+            ),
+            Some(LuaVersion::LuaJIT)
+        );
+    }
+
+    #[test]
+    fn variables_are_merged() {
+        let builder = config_file().with_overrides(
+            ConfigOverrides::builder()
+                .variables(HashMap::from([("FOO".into(), "override".into())]))
+                .build(),
+        );
+        let variables = builder.variables.unwrap();
+        assert_eq!(variables["FOO"], "override");
+        assert_eq!(variables["BAR"], "file");
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_defaults() {
+        let config = ConfigBuilder::default()
+            .with_overrides(
+                ConfigOverrides::builder()
+                    .timeout(Duration::from_secs(5))
+                    .no_luarc(true)
+                    .build(),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(config.timeout(), &Duration::from_secs(5));
+        assert!(!config.generate_luarc());
+        assert!(!config.verbose());
+    }
+
+    #[test]
+    fn explicit_config_file_precedence() {
+        let explicit = r#"
+            namespace = "explicit"
+            verbose = false
+
+            [variables]
+            FOO = "explicit"
+            "#;
+        let builder = ConfigBuilder::from_layered_toml(
+            Some(&toml::to_string(&config_file()).unwrap()),
+            explicit,
+        )
+        .unwrap();
+        assert_eq!(
+            builder.server,
+            Some("https://example.com/".parse().unwrap())
+        );
+        assert_eq!(builder.namespace, Some("explicit".into()));
+        assert_eq!(builder.verbose, Some(false));
+        let variables = builder.variables.clone().unwrap();
+        assert_eq!(variables["FOO"], "explicit");
+        assert_eq!(variables["BAR"], "file");
+
+        let builder = builder.with_overrides(
+            ConfigOverrides::builder()
+                .namespace("override".into())
+                .variables(HashMap::from([("BAR".into(), "override".into())]))
+                .build(),
+        );
+        assert_eq!(builder.namespace, Some("override".into()));
+        let variables = builder.variables.unwrap();
+        assert_eq!(variables["FOO"], "explicit");
+        assert_eq!(variables["BAR"], "override");
+
+        let builder = ConfigBuilder::from_layered_toml(None, explicit).unwrap();
+        assert_eq!(builder.server, None);
+        assert_eq!(builder.namespace, Some("explicit".into()));
+
+        assert!(matches!(
+            Config::from_file(
+                Path::new("/nonexistent/lux/config.toml"),
+                ConfigOverrides::default()
+            ),
+            Err(ConfigError::ReadConfigFile(..))
+        ));
+    }
+
+    #[test]
+    fn select_named_tree() {
+        let builder: ConfigBuilder = toml::from_str(
+            r#"
+            tree_name = "work"
+
+            [trees.work]
+            root = "/trees/work"
+
+            [trees.experiments]
+            root = "/trees/experiments"
+            "#,
+        )
+        .unwrap();
+        let config = builder.clone().build().unwrap();
+        assert_eq!(config.tree_name(), Some(&"work".into()));
+        assert_eq!(config.user_tree, PathBuf::from("/trees/work"));
+
+        let config = builder
+            .clone()
+            .with_overrides(
+                ConfigOverrides::builder()
+                    .tree_name("experiments".into())
+                    .build(),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(config.user_tree, PathBuf::from("/trees/experiments"));
+
+        // An explicit tree takes precedence over the config file's named tree.
+        let config = builder
+            .clone()
+            .with_overrides(
+                ConfigOverrides::builder()
+                    .user_tree("/trees/other".into())
+                    .build(),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(config.tree_name(), None);
+        assert_eq!(config.user_tree, PathBuf::from("/trees/other"));
+
+        assert!(matches!(
+            builder
+                .with_overrides(
+                    ConfigOverrides::builder()
+                        .tree_name("unknown".into())
+                        .build()
+                )
+                .build(),
+            Err(ConfigError::UnknownTree(_))
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::build::debug_symbols::DebugSymbols;
+
+use super::toolchain::Toolchain;
+
+/// The name of the profile for debugging C modules.
+pub const DEV_PROFILE: &str = "dev";
+/// The name of the profile for optimized C modules.
+pub const RELEASE_PROFILE: &str = "release";
+
+/// A build profile, selected with `--profile <name>`.
+/// Projects can define or override profiles in the `[profile.<name>]` sections of their lux.toml,
+/// e.g. `[profile.dev] cflags = ["-O0", "-g", "-fsanitize=address"]`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, JsonSchema)]
+pub struct BuildProfile {
+    /// Flags to pass to the C/C++ compiler,
+    /// in addition to the toolchain's `cflags`, taking precedence over them.
+    #[serde(default)]
+    pub(crate) cflags: Vec<String>,
+    /// Flags to pass to the linker, in addition to the toolchain's `ldflags`.
+    #[serde(default)]
+    pub(crate) ldflags: Vec<String>,
+    /// What to do with the debug symbols of built C modules,
+    /// e.g. `debug_symbols = "strip"`.
+    #[serde(default)]
+    pub(crate) debug_symbols: Option<DebugSymbols>,
+}
+
+impl BuildProfile {
+    /// The profiles that are available without any configuration.
+    pub(crate) fn builtin(name: &str) -> Option<Self> {
+        let cflags: &[&str] = match name {
+            DEV_PROFILE => &["-O0", "-g"],
+            RELEASE_PROFILE => &["-O2"],
+            _ => return None,
+        };
+        Some(Self {
+            cflags: cflags.iter().map(|flag| flag.to_string()).collect(),
+            ldflags: Vec::new(),
+            debug_symbols: None,
+        })
+    }
+
+    pub fn cflags(&self) -> &[String] {
+        &self.cflags
+    }
+
+    pub fn ldflags(&self) -> &[String] {
+        &self.ldflags
+    }
+
+    pub fn debug_symbols(&self) -> Option<DebugSymbols> {
+        self.debug_symbols
+    }
+
+    pub(crate) fn toolchain(&self) -> Toolchain {
+        Toolchain {
+            cflags: self.cflags.clone(),
+            ldflags: self.ldflags.clone(),
+            ..Toolchain::default()
+        }
+    }
+}
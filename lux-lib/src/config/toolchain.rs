@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The C/C++ compiler toolchain to build C modules with.
+/// Can be specified per project, in the `[build.toolchain]` section of the lux.toml,
+/// or as a named preset in the `[toolchains]` section of the config.
+/// Unset fields fall back to the compiler toolchain that is detected for the host.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Toolchain {
+    /// The C compiler.
+    #[serde(default)]
+    pub(crate) cc: Option<String>,
+    /// The C++ compiler.
+    #[serde(default)]
+    pub(crate) cxx: Option<String>,
+    /// The archiver.
+    #[serde(default)]
+    pub(crate) ar: Option<String>,
+    /// Flags to pass to the C/C++ compiler, in addition to the `CFLAGS` variable.
+    #[serde(default)]
+    pub(crate) cflags: Vec<String>,
+    /// Flags to pass to the linker, in addition to the `LDFLAGS` variable.
+    #[serde(default)]
+    pub(crate) ldflags: Vec<String>,
+}
+
+impl Toolchain {
+    /// The toolchain presets that are available without any configuration.
+    pub(crate) fn builtin_preset(name: &str) -> Option<Self> {
+        let (cc, cxx, ar) = match name {
+            "gcc" => ("gcc", "g++", "ar"),
+            "clang" => ("clang", "clang++", "llvm-ar"),
+            _ => return None,
+        };
+        Some(Self {
+            cc: Some(cc.into()),
+            cxx: Some(cxx.into()),
+            ar: Some(ar.into()),
+            ..Self::default()
+        })
+    }
+
+    pub fn cc(&self) -> Option<&str> {
+        self.cc.as_deref()
+    }
+
+    pub fn cxx(&self) -> Option<&str> {
+        self.cxx.as_deref()
+    }
+
+    pub fn ar(&self) -> Option<&str> {
+        self.ar.as_deref()
+    }
+
+    pub fn cflags(&self) -> &[String] {
+        &self.cflags
+    }
+
+    pub fn ldflags(&self) -> &[String] {
+        &self.ldflags
+    }
+
+    /// Apply `override_spec` on top of this toolchain.
+    /// The programs of `override_spec` take precedence, and flags are appended.
+    pub(crate) fn apply_overrides(&self, override_spec: &Self) -> Self {
+        Self {
+            cc: override_spec.cc.clone().or(self.cc.clone()),
+            cxx: override_spec.cxx.clone().or(self.cxx.clone()),
+            ar: override_spec.ar.clone().or(self.ar.clone()),
+            cflags: self
+                .cflags
+                .iter()
+                .chain(&override_spec.cflags)
+                .unique()
+                .cloned()
+                .collect(),
+            ldflags: self
+                .ldflags
+                .iter()
+                .chain(&override_spec.ldflags)
+                .unique()
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The names of the variables that this toolchain sets.
+    pub(crate) fn variable_names(&self) -> Vec<&'static str> {
+        [
+            ("CC", self.cc.is_some()),
+            ("CXX", self.cxx.is_some()),
+            ("AR", self.ar.is_some()),
+            ("CFLAGS", !self.cflags.is_empty()),
+            ("LDFLAGS", !self.ldflags.is_empty()),
+        ]
+        .into_iter()
+        .filter(|(_, is_set)| *is_set)
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    /// The `CC`, `CXX`, `AR`, `CFLAGS` and `LDFLAGS` variables for this toolchain,
+    /// with the flags appended to the ones in `variables`.
+    /// These are passed to the build backends, e.g. as `make` variables.
+    pub(crate) fn variables(&self, variables: &HashMap<String, String>) -> HashMap<String, String> {
+        let append_flags = |name: &str, flags: &[String]| {
+            variables
+                .get(name)
+                .filter(|existing| !existing.is_empty())
+                .into_iter()
+                .cloned()
+                .chain(flags.iter().cloned())
+                .join(" ")
+        };
+        [
+            ("CC", self.cc.clone()),
+            ("CXX", self.cxx.clone()),
+            ("AR", self.ar.clone()),
+            (
+                "CFLAGS",
+                Some(append_flags("CFLAGS", &self.cflags)).filter(|_| !self.cflags.is_empty()),
+            ),
+            (
+                "LDFLAGS",
+                Some(append_flags("LDFLAGS", &self.ldflags)).filter(|_| !self.ldflags.is_empty()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name.to_string(), value)))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toolchain_variables() {
+        let toolchain = Toolchain::builtin_preset("clang")
+            .unwrap()
+            .apply_overrides(&Toolchain {
+                cc: Some("clang-19".into()),
+                cflags: vec!["-fsanitize=address".into()],
+                ..Toolchain::default()
+            });
+        let variables = toolchain.variables(&HashMap::from([("CFLAGS".into(), "-O2".into())]));
+        assert_eq!(
+            variables,
+            HashMap::from([
+                ("CC".into(), "clang-19".into()),
+                ("CXX".into(), "clang++".into()),
+                ("AR".into(), "llvm-ar".into()),
+                ("CFLAGS".into(), "-O2 -fsanitize=address".into()),
+            ])
+        );
+        assert_eq!(
+            toolchain.variable_names(),
+            vec!["CC", "CXX", "AR", "CFLAGS"]
+        );
+        assert!(Toolchain::builtin_preset("msvc").is_none());
+    }
+}
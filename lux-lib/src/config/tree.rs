@@ -1,6 +1,84 @@
 use mlua::{FromLua, UserData};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{path::PathBuf, str::FromStr};
+use toml_edit::DocumentMut;
+
+use super::{ConfigBuilder, ConfigError};
+
+/// The root of the Neovim package directory, relative to a tree's root,
+/// in which plugins are installed with the `--nvim` preset.
+pub(crate) const NVIM_PACK_ROOT: &str = "site/pack/lux";
+
+/// A named install tree, defined in the `[trees]` section of the config,
+/// e.g. `[trees.work] root = "/path/to/tree"`.
+/// Can be selected with `--tree-name <name>`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct NamedTree {
+    /// The root directory of the tree.
+    pub root: PathBuf,
+}
+
+impl NamedTree {
+    /// Add this tree to the `[trees]` section of the config file, creating it if necessary.
+    pub fn add_to_config_file(&self, name: &str) -> Result<(), ConfigError> {
+        let config_file = ConfigBuilder::config_file()?;
+        let content = if config_file.is_file() {
+            std::fs::read_to_string(&config_file)?
+        } else {
+            std::fs::create_dir_all(config_file.parent().unwrap())?;
+            String::default()
+        };
+        std::fs::write(&config_file, self.add_to_config(&content, name)?)?;
+        Ok(())
+    }
+
+    /// Remove a tree from the `[trees]` section of the config file.
+    /// Returns `false` if there is no tree with that name.
+    pub fn remove_from_config_file(name: &str) -> Result<bool, ConfigError> {
+        let config_file = ConfigBuilder::config_file()?;
+        if !config_file.is_file() {
+            return Ok(false);
+        }
+        let content = std::fs::read_to_string(&config_file)?;
+        match Self::remove_from_config(&content, name)? {
+            Some(content) => {
+                std::fs::write(&config_file, content)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn add_to_config(&self, content: &str, name: &str) -> Result<String, ConfigError> {
+        let mut doc = DocumentMut::from_str(content)?;
+        let trees = doc
+            .entry("trees")
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                toml_edit::Item::Table(table)
+            })
+            .as_table_mut()
+            .ok_or(ConfigError::InvalidTrees)?;
+        if trees.contains_key(name) {
+            return Err(ConfigError::TreeExists(name.to_string()));
+        }
+        let mut tree = toml_edit::Table::new();
+        tree["root"] = toml_edit::value(self.root.to_string_lossy().to_string());
+        trees.insert(name, toml_edit::Item::Table(tree));
+        Ok(doc.to_string())
+    }
+
+    fn remove_from_config(content: &str, name: &str) -> Result<Option<String>, ConfigError> {
+        let mut doc = DocumentMut::from_str(content)?;
+        let removed = doc
+            .get_mut("trees")
+            .and_then(|trees| trees.as_table_like_mut())
+            .and_then(|trees| trees.remove(name))
+            .is_some();
+        Ok(removed.then(|| doc.to_string()))
+    }
+}
 
 /// Template configuration for a rock's tree layout
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, FromLua)]
@@ -34,7 +112,7 @@ impl RockLayoutConfig {
     /// - `opt_etc`: `opt`
     pub fn new_nvim_layout() -> Self {
         Self {
-            etc_root: Some("site/pack/lux".into()),
+            etc_root: Some(NVIM_PACK_ROOT.into()),
             etc: "start".into(),
             opt_etc: "opt".into(),
             conf: "conf".into(),
@@ -67,3 +145,46 @@ impl UserData for RockLayoutConfig {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_named_trees_in_config() {
+        let content = r#"# My lux config
+namespace = "foo"
+"#;
+        let tree = NamedTree {
+            root: "/trees/work".into(),
+        };
+        let content = tree.add_to_config(content, "work").unwrap();
+        assert_eq!(
+            content,
+            r#"# My lux config
+namespace = "foo"
+
+[trees.work]
+root = "/trees/work"
+"#
+        );
+        let builder: ConfigBuilder = toml::from_str(&content).unwrap();
+        assert_eq!(builder.trees.unwrap()["work"], tree);
+        assert!(matches!(
+            tree.add_to_config(&content, "work"),
+            Err(ConfigError::TreeExists(_))
+        ));
+
+        assert_eq!(
+            NamedTree::remove_from_config(&content, "work")
+                .unwrap()
+                .unwrap(),
+            r#"# My lux config
+namespace = "foo"
+"#
+        );
+        assert!(NamedTree::remove_from_config(&content, "other")
+            .unwrap()
+            .is_none());
+    }
+}
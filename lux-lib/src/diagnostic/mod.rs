@@ -0,0 +1,667 @@
+//! Stable error codes for errors that users are likely to run into,
+//! each with a short explanation, a hint on how to resolve it,
+//! and extended documentation, which can be printed with `lx explain <code>`.
+//!
+//! Not every error variant has a code. Codes are reserved for errors that users
+//! can resolve themselves, like a missing external dependency or an unset Lua version.
+//! Errors that wrap other errors report the code of their source, if it has one.
+//! All other errors, e.g. I/O errors or bugs, have no code,
+//! but are still classified by [`error_class`].
+//!
+//! Codes are never reused or renumbered. When an error is removed, its code is retired.
+
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use crate::{
+    build::{
+        compiler_cache::CompilerCacheNotFound, external_dependency::ExternalDependencyError,
+        BuildError,
+    },
+    config::{ConfigError, LuaVersionUnset},
+    lockfile::LockfileIntegrityError,
+    lua_installation::{nlua::NluaError, LuaInstallationError},
+    lua_rockspec::LuaVersionError,
+    operations::{
+        BuildProjectError, BundleError, InstallError, RunCommandError, RunError, RunTestsError,
+        SearchAndDownloadError, SyncError,
+    },
+    project::{
+        project_toml::{
+            LocalProjectTomlValidationError, ProjectTomlError, ProjectTomlIntegrityError,
+        },
+        ProjectError,
+    },
+    remote_package_db::{RemotePackageDBError, RemotePackageDbIntegrityError, SearchError},
+};
+
+/// A stable error code, e.g. `LUX0003`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorCode(u16);
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LUX{:04}", self.0)
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = String;
+
+    /// Parses `LUX0003`, case-insensitively, or just the number, e.g. `3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = if s.len() > 3 && s[..3].eq_ignore_ascii_case("lux") {
+            &s[3..]
+        } else {
+            s
+        };
+        let code = number
+            .parse()
+            .map(ErrorCode)
+            .map_err(|_| format!("invalid error code '{s}'. Expected a code like 'LUX0001'."))?;
+        if code.info().is_some() {
+            Ok(code)
+        } else {
+            Err(format!("unknown error code {code}."))
+        }
+    }
+}
+
+impl ErrorCode {
+    fn info(&self) -> Option<&'static ErrorCodeInfo> {
+        ERROR_CODES.iter().find(|info| info.code == *self)
+    }
+
+    /// A one-line explanation of the error.
+    pub fn summary(&self) -> &'static str {
+        self.info().map(|info| info.summary).unwrap_or_default()
+    }
+
+    /// A hint on how to resolve the error.
+    pub fn hint(&self) -> &'static str {
+        self.info().map(|info| info.hint).unwrap_or_default()
+    }
+
+    /// Extended documentation, in markdown.
+    pub fn explanation(&self) -> &'static str {
+        self.info().map(|info| info.explanation).unwrap_or_default()
+    }
+
+    /// The class of failure that the error belongs to.
+    pub fn class(&self) -> ErrorClass {
+        self.info().map(|info| info.class).unwrap_or_default()
+    }
+
+    /// All known error codes, in ascending order.
+    pub fn all() -> impl Iterator<Item = ErrorCode> {
+        ERROR_CODES.iter().map(|info| info.code)
+    }
+}
+
+/// The class of a failure, e.g. to report it with a stable exit code,
+/// so that scripts can tell failures apart without parsing error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ErrorClass {
+    /// No package satisfies a requirement.
+    Resolution,
+    /// A package or project could not be built or installed.
+    Build,
+    /// A server could not be reached, or responded with an error.
+    Network,
+    /// A hash, lockfile or metadata check failed.
+    Verification,
+    /// The project's tests failed.
+    Test,
+    /// The configuration or the `lux.toml` is invalid, or a required tool is missing.
+    Config,
+    /// Any other failure.
+    #[default]
+    Other,
+}
+
+impl Display for ErrorClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Resolution => "resolution".fmt(f),
+            Self::Build => "build".fmt(f),
+            Self::Network => "network".fmt(f),
+            Self::Verification => "verification".fmt(f),
+            Self::Test => "test".fmt(f),
+            Self::Config => "config".fmt(f),
+            Self::Other => "other".fmt(f),
+        }
+    }
+}
+
+struct ErrorCodeInfo {
+    code: ErrorCode,
+    class: ErrorClass,
+    summary: &'static str,
+    hint: &'static str,
+    explanation: &'static str,
+}
+
+const LUA_VERSION_UNSET: ErrorCode = ErrorCode(1);
+const LUA_VERSION_UNSUPPORTED: ErrorCode = ErrorCode(2);
+const EXTERNAL_DEPENDENCY_NOT_FOUND: ErrorCode = ErrorCode(3);
+const COMPILER_CACHE_NOT_FOUND: ErrorCode = ErrorCode(4);
+const UNKNOWN_TOOLCHAIN: ErrorCode = ErrorCode(5);
+const ROCK_NOT_FOUND: ErrorCode = ErrorCode(6);
+const ROCK_NOT_FOUND_IN_LOCKFILE: ErrorCode = ErrorCode(7);
+const SOURCE_INTEGRITY_MISMATCH: ErrorCode = ErrorCode(8);
+const NO_RUN_FIELD: ErrorCode = ErrorCode(9);
+const NON_PORTABLE_RUN_COMMAND: ErrorCode = ErrorCode(10);
+const OPENRESTY_NOT_FOUND: ErrorCode = ErrorCode(11);
+const RESTY_LUA_VERSION: ErrorCode = ErrorCode(12);
+const BUNDLE_C_MODULE: ErrorCode = ErrorCode(13);
+const NEOVIM_NOT_FOUND: ErrorCode = ErrorCode(14);
+const PINNED_LUA_VERSION_MISMATCH: ErrorCode = ErrorCode(15);
+
+const ERROR_CODES: &[ErrorCodeInfo] = &[
+    ErrorCodeInfo {
+        code: LUA_VERSION_UNSET,
+        class: ErrorClass::Config,
+        summary: "no Lua version is configured",
+        hint: "set the Lua version with `--lua-version`, or add a `lua` dependency to your lux.toml",
+        explanation: include_str!("codes/LUX0001.md"),
+    },
+    ErrorCodeInfo {
+        code: LUA_VERSION_UNSUPPORTED,
+        class: ErrorClass::Resolution,
+        summary: "a package does not support the configured Lua version",
+        hint: "select a Lua version that the package supports, or use a different version of the package",
+        explanation: include_str!("codes/LUX0002.md"),
+    },
+    ErrorCodeInfo {
+        code: EXTERNAL_DEPENDENCY_NOT_FOUND,
+        class: ErrorClass::Build,
+        summary: "an external (system) dependency could not be found",
+        hint: "install the dependency, or set `<NAME>_DIR` to its prefix. Run `lx debug external-deps` to see where Lux looked",
+        explanation: include_str!("codes/LUX0003.md"),
+    },
+    ErrorCodeInfo {
+        code: COMPILER_CACHE_NOT_FOUND,
+        class: ErrorClass::Config,
+        summary: "the configured compiler cache is not installed",
+        hint: "install the compiler cache, or unset `compiler_cache` in your config",
+        explanation: include_str!("codes/LUX0004.md"),
+    },
+    ErrorCodeInfo {
+        code: UNKNOWN_TOOLCHAIN,
+        class: ErrorClass::Config,
+        summary: "the selected compiler toolchain preset does not exist",
+        hint: "use one of the built-in presets `gcc` or `clang`, or define the preset in the `[toolchains]` section of your config",
+        explanation: include_str!("codes/LUX0005.md"),
+    },
+    ErrorCodeInfo {
+        code: ROCK_NOT_FOUND,
+        class: ErrorClass::Resolution,
+        summary: "no package matches the requirement",
+        hint: "check the package name and version constraint with `lx search`",
+        explanation: include_str!("codes/LUX0006.md"),
+    },
+    ErrorCodeInfo {
+        code: ROCK_NOT_FOUND_IN_LOCKFILE,
+        class: ErrorClass::Resolution,
+        summary: "no package in the lockfile matches the requirement",
+        hint: "run `lx update` to update the lockfile",
+        explanation: include_str!("codes/LUX0007.md"),
+    },
+    ErrorCodeInfo {
+        code: SOURCE_INTEGRITY_MISMATCH,
+        class: ErrorClass::Verification,
+        summary: "a package's source does not match its expected hash",
+        hint: "if the upstream source changed intentionally, update the hash in the lux.toml or lockfile",
+        explanation: include_str!("codes/LUX0008.md"),
+    },
+    ErrorCodeInfo {
+        code: NO_RUN_FIELD,
+        class: ErrorClass::Config,
+        summary: "the project has no `[run]` section",
+        hint: "add a `[run]` section with the `args` to run to your lux.toml",
+        explanation: include_str!("codes/LUX0009.md"),
+    },
+    ErrorCodeInfo {
+        code: NON_PORTABLE_RUN_COMMAND,
+        class: ErrorClass::Config,
+        summary: "the `[run]` command is a Lua interpreter that is not portable",
+        hint: "remove the `command` field and let Lux choose the Lua interpreter",
+        explanation: include_str!("codes/LUX0010.md"),
+    },
+    ErrorCodeInfo {
+        code: OPENRESTY_NOT_FOUND,
+        class: ErrorClass::Config,
+        summary: "no OpenResty installation could be found",
+        hint: "set the `OPENRESTY_PREFIX` variable in your config, or make sure `resty` is on the PATH",
+        explanation: include_str!("codes/LUX0011.md"),
+    },
+    ErrorCodeInfo {
+        code: RESTY_LUA_VERSION,
+        class: ErrorClass::Config,
+        summary: "`resty` can only run projects that target LuaJIT",
+        hint: "build the project with `--lua-version jit`",
+        explanation: include_str!("codes/LUX0012.md"),
+    },
+    ErrorCodeInfo {
+        code: BUNDLE_C_MODULE,
+        class: ErrorClass::Build,
+        summary: "a package with C modules cannot be bundled",
+        hint: "replace the dependency with a pure Lua alternative, or distribute the project as a rock",
+        explanation: include_str!("codes/LUX0013.md"),
+    },
+    ErrorCodeInfo {
+        code: NEOVIM_NOT_FOUND,
+        class: ErrorClass::Config,
+        summary: "Neovim, which the busted-nlua test backend runs tests with, could not be found",
+        hint: "install Neovim, or set the `NVIM` variable in your config",
+        explanation: include_str!("codes/LUX0014.md"),
+    },
+    ErrorCodeInfo {
+        code: PINNED_LUA_VERSION_MISMATCH,
+        class: ErrorClass::Config,
+        summary: "the configured Lua version does not match the project's pinned Lua version",
+        hint: "select the Lua version of the pinned release with `--lua-version`",
+        explanation: include_str!("codes/LUX0015.md"),
+    },
+];
+
+/// An error that may have a stable [`ErrorCode`].
+pub trait Diagnostic {
+    fn code(&self) -> Option<ErrorCode>;
+}
+
+/// Find the error code of `err` or one of its sources.
+pub fn error_code(err: &(dyn Error + 'static)) -> Option<ErrorCode> {
+    std::iter::successors(Some(err), |&err| err.source()).find_map(diagnostic_code)
+}
+
+/// Find the class of `err`, preferring the most specific of its sources,
+/// e.g. a network error over the build error it caused.
+pub fn error_class(err: &(dyn Error + 'static)) -> ErrorClass {
+    std::iter::successors(Some(err), |&err| err.source())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .find_map(|err| {
+            diagnostic_code(err)
+                .map(|code| code.class())
+                .or_else(|| diagnostic_class(err))
+        })
+        .unwrap_or_default()
+}
+
+/// The class of errors that don't have an error code.
+fn diagnostic_class(err: &(dyn Error + 'static)) -> Option<ErrorClass> {
+    if err.is::<reqwest::Error>() {
+        return Some(ErrorClass::Network);
+    }
+    if let Some(err) = err.downcast_ref::<git2::Error>() {
+        return matches!(
+            err.class(),
+            git2::ErrorClass::Net | git2::ErrorClass::Http | git2::ErrorClass::Ssl
+        )
+        .then_some(ErrorClass::Network);
+    }
+    if let Some(err) = err.downcast_ref::<SearchError>() {
+        return matches!(
+            err,
+            SearchError::RockNotFound(_) | SearchError::RockNotFoundInLockfile(_)
+        )
+        .then_some(ErrorClass::Resolution);
+    }
+    if let Some(err) = err.downcast_ref::<RunTestsError>() {
+        return matches!(err, RunTestsError::TestFailure).then_some(ErrorClass::Test);
+    }
+    macro_rules! downcast_class {
+        ($($class:ident => [$($ty:ty),* $(,)?]),* $(,)?) => {
+            $($(
+                if err.is::<$ty>() {
+                    return Some(ErrorClass::$class);
+                }
+            )*)*
+        };
+    }
+    downcast_class!(
+        Verification => [
+            LockfileIntegrityError,
+            ProjectTomlIntegrityError,
+            RemotePackageDbIntegrityError,
+        ],
+        Build => [BuildProjectError, BuildError, BundleError, InstallError, SyncError],
+        Config => [
+            ConfigError,
+            ProjectError,
+            ProjectTomlError,
+            LocalProjectTomlValidationError,
+        ],
+    );
+    None
+}
+
+fn diagnostic_code(err: &(dyn Error + 'static)) -> Option<ErrorCode> {
+    macro_rules! downcast_code {
+        ($($ty:ty),* $(,)?) => {
+            $(
+                if let Some(err) = err.downcast_ref::<$ty>() {
+                    return err.code();
+                }
+            )*
+        };
+    }
+    downcast_code!(
+        BuildProjectError,
+        BuildError,
+        BundleError,
+        CompilerCacheNotFound,
+        ConfigError,
+        ExternalDependencyError,
+        InstallError,
+        LuaInstallationError,
+        LuaVersionError,
+        LuaVersionUnset,
+        NluaError,
+        RemotePackageDBError,
+        RunCommandError,
+        RunError,
+        RunTestsError,
+        SearchAndDownloadError,
+        SearchError,
+        SyncError,
+    );
+    None
+}
+
+impl Diagnostic for LuaVersionUnset {
+    fn code(&self) -> Option<ErrorCode> {
+        Some(LUA_VERSION_UNSET)
+    }
+}
+
+impl Diagnostic for LuaVersionError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::LuaVersionUnsupported(..) => Some(LUA_VERSION_UNSUPPORTED),
+            Self::LuaVersionUnset(err) => err.code(),
+        }
+    }
+}
+
+impl Diagnostic for ExternalDependencyError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::NotFound(_)
+            | Self::SuccessfulProbeHeaderNotFound(..)
+            | Self::HeaderNotFound(..)
+            | Self::LibraryNotFound(..) => Some(EXTERNAL_DEPENDENCY_NOT_FOUND),
+            Self::Io(_) => None,
+        }
+    }
+}
+
+impl Diagnostic for CompilerCacheNotFound {
+    fn code(&self) -> Option<ErrorCode> {
+        Some(COMPILER_CACHE_NOT_FOUND)
+    }
+}
+
+impl Diagnostic for ConfigError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::UnknownToolchain(_) => Some(UNKNOWN_TOOLCHAIN),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for RemotePackageDBError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::ConfigError(err) => err.code(),
+            Self::ManifestError(_) => None,
+        }
+    }
+}
+
+impl Diagnostic for SearchError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::RockNotFound(_) => Some(ROCK_NOT_FOUND),
+            Self::RockNotFoundInLockfile(_) => Some(ROCK_NOT_FOUND_IN_LOCKFILE),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for SearchAndDownloadError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Search(err) => err.code(),
+            Self::RemotePackageDB(err) => err.code(),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for LuaInstallationError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::LuaVersionUnset(err) => err.code(),
+            Self::PinnedVersionMismatch { .. } => Some(PINNED_LUA_VERSION_MISMATCH),
+            Self::PinnedSourceMismatch { .. } => Some(SOURCE_INTEGRITY_MISMATCH),
+            Self::Build(_)
+            | Self::UnsupportedPinnedVersion(_)
+            | Self::PinnedInstallationNotFound(_) => None,
+        }
+    }
+}
+
+impl Diagnostic for BuildError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::ExternalDependencyError(err) | Self::MissingSystemPackage(err, _) => err.code(),
+            Self::LuaVersion(err) => err.code(),
+            Self::LuaInstallation(err) => err.code(),
+            Self::CompilerCacheNotFound(err) => err.code(),
+            Self::SourceIntegrityMismatch { .. } => Some(SOURCE_INTEGRITY_MISMATCH),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for InstallError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::SearchAndDownloadError(err) => err.code(),
+            Self::LuaVersionUnset(err) => err.code(),
+            Self::LuaInstallation(err) => err.code(),
+            Self::BuildError(_, err) | Self::BuildDependencyError(_, err) => err.code(),
+            Self::RemotePackageDB(err) => err.code(),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for SyncError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Install(err) => err.code(),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for BuildProjectError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::LuaInstallation(err) => err.code(),
+            Self::InstallDependencies(err) | Self::InstallBuildDependencies(err) => err.code(),
+            Self::SyncDependencies(err) | Self::SyncBuildDependencies(err) => err.code(),
+            Self::Build(err) => err.code(),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for RunCommandError {
+    fn code(&self) -> Option<ErrorCode> {
+        Some(NON_PORTABLE_RUN_COMMAND)
+    }
+}
+
+impl Diagnostic for RunError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::RunCommand(err) => err.code(),
+            Self::LuaVersion(err) => err.code(),
+            Self::NoRunField => Some(NO_RUN_FIELD),
+            Self::OpenRestyNotFound => Some(OPENRESTY_NOT_FOUND),
+            Self::RestyLuaVersion(_) => Some(RESTY_LUA_VERSION),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for BundleError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::CModule { .. } => Some(BUNDLE_C_MODULE),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for NluaError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::NeovimNotFound(_) => Some(NEOVIM_NOT_FOUND),
+            Self::Io(_) => None,
+        }
+    }
+}
+
+impl Diagnostic for RunTestsError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Sync(err) => err.code(),
+            Self::LuaVersion(err) => err.code(),
+            Self::Nlua(err) => err.code(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        build::compiler_cache::CompilerCache, config::LuaVersion, operations::RunCommand,
+        package::PackageReq,
+    };
+
+    use super::*;
+
+    #[test]
+    fn error_codes_are_unique_and_documented() {
+        let codes: HashSet<ErrorCode> = ErrorCode::all().collect();
+        assert_eq!(codes.len(), ERROR_CODES.len());
+        for code in ErrorCode::all() {
+            assert!(!code.summary().is_empty());
+            assert!(!code.hint().is_empty());
+            assert!(code.explanation().starts_with(&format!("# {code}")));
+        }
+    }
+
+    #[test]
+    fn every_error_code_is_reported() {
+        let package = || "foo".into();
+        let version = || "1.0.0-1".parse().unwrap();
+        let integrity = || {
+            "sha256-uBr6qNv1DqQvTsS5xd8ulK3Ob3gr0lZPA9ISbR8enlQ="
+                .parse::<ssri::Integrity>()
+                .unwrap()
+        };
+        let errors: Vec<Box<dyn Error>> = vec![
+            Box::new(LuaVersionUnset),
+            Box::new(LuaVersionError::LuaVersionUnsupported(
+                LuaVersion::Lua51,
+                package(),
+                version(),
+            )),
+            Box::new(ExternalDependencyError::NotFound("foo".into())),
+            Box::new(CompilerCacheNotFound(CompilerCache::Sccache)),
+            Box::new(ConfigError::UnknownToolchain("foo".into())),
+            Box::new(SearchError::RockNotFound(
+                PackageReq::new("foo".into(), None).unwrap(),
+            )),
+            Box::new(SearchError::RockNotFoundInLockfile(
+                PackageReq::new("foo".into(), None).unwrap(),
+            )),
+            Box::new(BuildError::SourceIntegrityMismatch {
+                expected: integrity(),
+                actual: integrity(),
+            }),
+            Box::new(RunError::NoRunField),
+            Box::new(RunCommand::from("lua".into()).unwrap_err()),
+            Box::new(RunError::OpenRestyNotFound),
+            Box::new(RunError::RestyLuaVersion(LuaVersion::Lua51)),
+            Box::new(BundleError::CModule {
+                package: "foo".into(),
+                module: "foo.core".into(),
+            }),
+            Box::new(NluaError::NeovimNotFound("nvim".into())),
+            Box::new(LuaInstallationError::PinnedVersionMismatch {
+                pinned_version: version(),
+                lua_version: LuaVersion::Lua51,
+            }),
+        ];
+        let reported: HashSet<ErrorCode> = errors
+            .iter()
+            .map(|err| error_code(err.as_ref()).unwrap())
+            .collect();
+        assert_eq!(reported, ErrorCode::all().collect());
+    }
+
+    #[test]
+    fn parse_error_code() {
+        assert_eq!("LUX0003".parse(), Ok(EXTERNAL_DEPENDENCY_NOT_FOUND));
+        assert_eq!("lux0003".parse(), Ok(EXTERNAL_DEPENDENCY_NOT_FOUND));
+        assert_eq!("3".parse(), Ok(EXTERNAL_DEPENDENCY_NOT_FOUND));
+        assert!("LUX9999".parse::<ErrorCode>().is_err());
+        assert!("E0308".parse::<ErrorCode>().is_err());
+    }
+
+    #[test]
+    fn nested_error_code() {
+        let err = InstallError::SearchAndDownloadError(SearchAndDownloadError::Search(
+            SearchError::RockNotFound(PackageReq::new("foo".into(), None).unwrap()),
+        ));
+        assert_eq!(error_code(&err), Some(ROCK_NOT_FOUND));
+        let err = BuildProjectError::Build(BuildError::ExternalDependencyError(
+            ExternalDependencyError::NotFound("foo".into()),
+        ));
+        assert_eq!(error_code(&err), Some(EXTERNAL_DEPENDENCY_NOT_FOUND));
+        let err = std::io::Error::other("not a lux error");
+        assert_eq!(error_code(&err), None);
+    }
+
+    #[test]
+    fn classify_errors() {
+        let err = InstallError::SearchAndDownloadError(SearchAndDownloadError::Search(
+            SearchError::RockNotFound(PackageReq::new("foo".into(), None).unwrap()),
+        ));
+        assert_eq!(error_class(&err), ErrorClass::Resolution);
+        let err = BuildProjectError::Build(BuildError::ExternalDependencyError(
+            ExternalDependencyError::NotFound("foo".into()),
+        ));
+        assert_eq!(error_class(&err), ErrorClass::Build);
+        assert_eq!(error_class(&RunTestsError::TestFailure), ErrorClass::Test);
+        let err = git2::Error::new(
+            git2::ErrorCode::GenericError,
+            git2::ErrorClass::Net,
+            "failed to resolve address",
+        );
+        assert_eq!(error_class(&err), ErrorClass::Network);
+        let err = std::io::Error::other("not a lux error");
+        assert_eq!(error_class(&err), ErrorClass::Other);
+    }
+}
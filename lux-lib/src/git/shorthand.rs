@@ -1,7 +1,8 @@
-use std::{fmt::Display, str::FromStr};
+use std::{borrow::Cow, fmt::Display, str::FromStr};
 
 use chumsky::{prelude::*, Parser};
 use git_url_parse::{GitUrl, GitUrlParseError};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{de, Deserialize, Deserializer};
 use thiserror::Error;
 
@@ -58,6 +59,19 @@ impl<'de> Deserialize<'de> for GitUrlShorthand {
     }
 }
 
+impl JsonSchema for GitUrlShorthand {
+    fn schema_name() -> Cow<'static, str> {
+        "GitUrlShorthand".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "A git URL, or a shorthand, e.g. `github:owner/repo`",
+        })
+    }
+}
+
 impl From<GitUrl> for GitUrlShorthand {
     fn from(value: GitUrl) -> Self {
         Self(value)
@@ -6,6 +6,9 @@ use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 use tempdir::TempDir;
 
+/// The alphabet used by Nix's base-32 encoding, which omits `e`, `o`, `t` and `u`.
+const NIX_BASE32_ALPHABET: &[u8] = b"0123456789abcdfghijklmnpqrsvwxyz";
+
 pub trait HasIntegrity {
     fn hash(&self) -> io::Result<Integrity>;
 }
@@ -48,6 +51,30 @@ impl HasIntegrity for Bytes {
     }
 }
 
+/// The digest of the `integrity` in Nix's base-32 encoding,
+/// as used in store paths and by `nix-prefetch-url`.
+pub fn to_nix_base32(integrity: &Integrity) -> String {
+    let (_, hex_digest) = integrity.to_hex();
+    let digest = hex::decode(hex_digest).expect("ssri produced invalid hex");
+    nix_base32(&digest)
+}
+
+/// Encode bytes using Nix's base-32 encoding.
+fn nix_base32(bytes: &[u8]) -> String {
+    let len = (bytes.len() * 8).div_ceil(5);
+    (0..len)
+        .rev()
+        .map(|n| {
+            let bit = n * 5;
+            let i = bit / 8;
+            let j = bit % 8;
+            let low = bytes[i] as u16 >> j;
+            let high = bytes.get(i + 1).map_or(0, |byte| (*byte as u16) << (8 - j));
+            NIX_BASE32_ALPHABET[((low | high) & 0x1f) as usize] as char
+        })
+        .collect()
+}
+
 fn hash_file(path: &Path, integrity_opts: &mut IntegrityOpts) -> io::Result<()> {
     let mut file = File::open(path)?;
     let mut buffer = Vec::new();
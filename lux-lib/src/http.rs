@@ -0,0 +1,418 @@
+//! The HTTP client that lux uses for all of its requests,
+//! e.g. to fetch manifests, download rocks and sources, or upload rocks.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    multipart::Form,
+    redirect, IntoUrl, Method, RequestBuilder, Response, StatusCode, Url,
+};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::config::Config;
+
+/// The `User-Agent` that lux sends with its requests.
+pub const USER_AGENT: &str = concat!("lux/", env!("CARGO_PKG_VERSION"));
+
+/// How long to wait before the first retry of a failed request.
+/// The delay doubles with each retry.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// The maximum number of redirects to follow, which is the same as `reqwest`'s default.
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Error)]
+#[error("refusing to access the package server {0} over plain HTTP.\nHINT: Use HTTPS, or, if you trust the server, add its host to `insecure_servers` in the config or pass `--allow-insecure-servers`.")]
+pub struct InsecureServerError(String);
+
+/// A pooled HTTP client, shared by all operations that use the same [`Config`].
+/// Requests are rate limited per host, according to the config's `http_rate_limits`,
+/// and idempotent requests that fail with a connection error, a timeout, a `429`
+/// or a server error are retried up to `http_retries` times.
+/// If the config is verbose, each request is logged with its status and duration.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    /// The client for requests to package servers,
+    /// which refuses to follow redirects to insecure servers.
+    server_client: reqwest::Client,
+    insecure_servers: InsecureServers,
+    retries: usize,
+    verbose: bool,
+    rate_limits: HashMap<String, Duration>,
+    /// The earliest time at which the next request may be sent to each rate limited host.
+    next_request: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl HttpClient {
+    pub(crate) fn new(config: &Config) -> Result<Self, reqwest::Error> {
+        let builder = || {
+            let builder = reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .pool_idle_timeout(Duration::from_secs(90));
+            // A zero timeout means waiting indefinitely.
+            if config.timeout().is_zero() {
+                builder
+            } else {
+                builder
+                    .connect_timeout(*config.timeout())
+                    .read_timeout(*config.timeout())
+            }
+        };
+        let insecure_servers = InsecureServers {
+            allow_all: config.allow_insecure_servers(),
+            hosts: config.insecure_servers().to_vec(),
+        };
+        let redirect_policy = {
+            let insecure_servers = insecure_servers.clone();
+            redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if let Err(err) = insecure_servers.check(attempt.url()) {
+                    attempt.error(err)
+                } else {
+                    attempt.follow()
+                }
+            })
+        };
+        Ok(Self {
+            client: builder().build()?,
+            server_client: builder().redirect(redirect_policy).build()?,
+            insecure_servers,
+            retries: config.http_retries(),
+            verbose: config.verbose(),
+            rate_limits: config
+                .http_rate_limits()
+                .iter()
+                .filter(|(_, requests_per_second)| **requests_per_second > 0)
+                .map(|(host, requests_per_second)| {
+                    (host.clone(), Duration::from_secs(1) / *requests_per_second)
+                })
+                .collect(),
+            next_request: Arc::default(),
+        })
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> HttpRequest<'_> {
+        self.request(Method::GET, url)
+    }
+
+    pub fn head(&self, url: impl IntoUrl) -> HttpRequest<'_> {
+        self.request(Method::HEAD, url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> HttpRequest<'_> {
+        self.request(Method::POST, url)
+    }
+
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> HttpRequest<'_> {
+        HttpRequest {
+            client: self,
+            builder: self.client.request(method, url),
+            redact: false,
+        }
+    }
+
+    /// Start a request to a package server, e.g. to fetch a manifest, or to download
+    /// or upload a rock. Unless the server is allowed to be insecure by the config,
+    /// it must be accessed over HTTPS, and redirects to plain HTTP are refused.
+    pub fn server_request(
+        &self,
+        method: Method,
+        url: Url,
+    ) -> Result<HttpRequest<'_>, InsecureServerError> {
+        self.insecure_servers.check(&url)?;
+        Ok(HttpRequest {
+            client: self,
+            builder: self.server_client.request(method, url),
+            redact: false,
+        })
+    }
+
+    /// Wait until the rate limit of the `url`'s host allows another request.
+    async fn rate_limit(&self, url: &Url) {
+        let Some((host, interval)) = url
+            .host_str()
+            .and_then(|host| self.rate_limits.get_key_value(host))
+        else {
+            return;
+        };
+        let delay = {
+            let mut next_request = self
+                .next_request
+                .lock()
+                .unwrap_or_else(|err| err.into_inner());
+            let now = Instant::now();
+            let scheduled = next_request
+                .get(host)
+                .copied()
+                .filter(|next| *next > now)
+                .unwrap_or(now);
+            next_request.insert(host.clone(), scheduled + *interval);
+            scheduled - now
+        };
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    fn log(
+        &self,
+        method: &Method,
+        url: &Url,
+        redact: bool,
+        result: &reqwest::Result<Response>,
+        start: Instant,
+    ) {
+        if !self.verbose {
+            return;
+        }
+        let url = if redact {
+            format!(
+                "{}://{}/<redacted>",
+                url.scheme(),
+                url.host_str().unwrap_or_default()
+            )
+        } else {
+            url.to_string()
+        };
+        let outcome = match result {
+            Ok(response) => response.status().to_string(),
+            Err(err) if err.is_timeout() => "timed out".into(),
+            Err(err) if err.is_connect() => "connection failed".into(),
+            Err(err) if err.is_redirect() => "redirect refused".into(),
+            Err(_) => "failed".into(),
+        };
+        eprintln!(
+            "🌐 {method} {url} {outcome} ({} ms)",
+            start.elapsed().as_millis()
+        );
+    }
+}
+
+/// A request that is sent with the [`HttpClient`]'s rate limiting, retries and logging.
+pub struct HttpRequest<'a> {
+    client: &'a HttpClient,
+    builder: RequestBuilder,
+    redact: bool,
+}
+
+impl HttpRequest<'_> {
+    pub fn header(self, key: HeaderName, value: HeaderValue) -> Self {
+        self.map(|builder| builder.header(key, value))
+    }
+
+    pub fn bearer_auth(self, token: impl std::fmt::Display) -> Self {
+        self.map(|builder| builder.bearer_auth(token))
+    }
+
+    pub fn query<T: Serialize + ?Sized>(self, query: &T) -> Self {
+        self.map(|builder| builder.query(query))
+    }
+
+    pub fn json<T: Serialize + ?Sized>(self, json: &T) -> Self {
+        self.map(|builder| builder.json(json))
+    }
+
+    /// Multipart requests are never retried, as their bodies can't be replayed.
+    pub fn multipart(self, form: Form) -> Self {
+        self.map(|builder| builder.multipart(form))
+    }
+
+    /// Do not log the request's URL, e.g. because it contains an API key.
+    pub fn redact_url(self) -> Self {
+        Self {
+            redact: true,
+            ..self
+        }
+    }
+
+    pub async fn send(self) -> reqwest::Result<Response> {
+        let client = self.client;
+        // The request is sent with the `reqwest` client it was built with,
+        // which differs for requests to package servers.
+        let (reqwest_client, request) = self.builder.build_split();
+        let mut request = request?;
+        let retries = if matches!(*request.method(), Method::GET | Method::HEAD) {
+            client.retries
+        } else {
+            0
+        };
+        let mut attempt = 0;
+        loop {
+            let retry = (attempt < retries).then(|| request.try_clone()).flatten();
+            client.rate_limit(request.url()).await;
+            let start = Instant::now();
+            let (method, url) = (request.method().clone(), request.url().clone());
+            let result = reqwest_client.execute(request).await;
+            client.log(&method, &url, self.redact, &result, start);
+            request = match retry {
+                Some(retry) if is_retryable(&result) => retry,
+                _ => return result,
+            };
+            tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt as u32)).await;
+            attempt += 1;
+        }
+    }
+
+    fn map(self, f: impl FnOnce(RequestBuilder) -> RequestBuilder) -> Self {
+        Self {
+            builder: f(self.builder),
+            ..self
+        }
+    }
+}
+
+/// The package servers that may be accessed over plain HTTP.
+#[derive(Debug, Clone)]
+struct InsecureServers {
+    allow_all: bool,
+    hosts: Vec<String>,
+}
+
+impl InsecureServers {
+    fn check(&self, url: &Url) -> Result<(), InsecureServerError> {
+        let is_allowed = url.scheme() != "http"
+            || self.allow_all
+            || url.host_str().is_some_and(|host| {
+                // IPv6 hosts are enclosed in brackets in URLs.
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                self.hosts
+                    .iter()
+                    .any(|allowed| allowed.trim_start_matches('[').trim_end_matches(']') == host)
+            });
+        if is_allowed {
+            Ok(())
+        } else {
+            // Only report the origin, as the URL may contain an API key.
+            Err(InsecureServerError(url.origin().ascii_serialization()))
+        }
+    }
+}
+
+fn is_retryable(result: &reqwest::Result<Response>) -> bool {
+    match result {
+        Ok(response) => {
+            response.status() == StatusCode::TOO_MANY_REQUESTS
+                || response.status().is_server_error()
+        }
+        Err(err) => err.is_connect() || err.is_timeout(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigBuilder;
+    use httptest::{matchers::request, responders::status_code, Expectation, Server};
+
+    #[tokio::test]
+    async fn retry_server_errors() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/manifest"))
+                .times(2)
+                .respond_with(httptest::cycle![
+                    status_code(503),
+                    status_code(200).body("ok"),
+                ]),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/upload"))
+                .times(1)
+                .respond_with(status_code(503)),
+        );
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let client = config.http_client().unwrap();
+        let response = client
+            .get(server.url_str("/manifest"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        let response = client.post(server.url_str("/upload")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_per_host() {
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .http_rate_limits(Some(HashMap::from([("example.com".into(), 10)])))
+            .build()
+            .unwrap();
+        let client = HttpClient::new(&config).unwrap();
+        let url: Url = "https://example.com/manifest".parse().unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            client.rate_limit(&url).await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        let start = Instant::now();
+        client
+            .rate_limit(&"https://luarocks.org/manifest".parse().unwrap())
+            .await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn refuse_insecure_servers() {
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .insecure_servers(Some(vec!["intranet.local".into()]))
+            .build()
+            .unwrap();
+        let client = HttpClient::new(&config).unwrap();
+        let url = |url: &str| url.parse::<Url>().unwrap();
+        assert!(client
+            .server_request(Method::GET, url("http://luarocks.org/manifest"))
+            .is_err());
+        assert!(client
+            .server_request(Method::GET, url("https://luarocks.org/manifest"))
+            .is_ok());
+        assert!(client
+            .server_request(Method::GET, url("http://intranet.local/manifest"))
+            .is_ok());
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .allow_insecure_servers(Some(true))
+            .build()
+            .unwrap();
+        let client = HttpClient::new(&config).unwrap();
+        assert!(client
+            .server_request(Method::GET, url("http://luarocks.org/manifest"))
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn refuse_redirects_to_insecure_servers() {
+        let server = Server::run();
+        let insecure_url = format!("http://localhost:{}/manifest", server.addr().port());
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/redirect"))
+                .respond_with(status_code(302).insert_header("Location", insecure_url)),
+        );
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .insecure_servers(Some(vec![server.addr().ip().to_string()]))
+            .build()
+            .unwrap();
+        let client = HttpClient::new(&config).unwrap();
+        let err = client
+            .server_request(
+                Method::GET,
+                server.url("/redirect").to_string().parse().unwrap(),
+            )
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_redirect());
+    }
+}
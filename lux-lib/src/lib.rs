@@ -1,20 +1,26 @@
+pub mod analysis;
 pub mod build;
 pub mod config;
+pub mod diagnostic;
 pub mod git;
 pub mod hash;
+pub mod http;
 pub mod lockfile;
 pub mod lua;
 pub mod lua_installation;
 pub mod lua_rockspec;
 pub mod luarocks;
 pub mod manifest;
+pub mod observer;
 pub mod operations;
 pub mod package;
 pub mod path;
+pub mod process;
 pub mod progress;
 pub mod project;
 pub mod remote_package_db;
 pub mod rockspec;
+pub mod timings;
 pub mod tree;
 pub mod upload;
 pub mod which;
@@ -14,7 +14,9 @@ use ssri::Integrity;
 use thiserror::Error;
 use url::Url;
 
+use crate::build::provenance::BuildProvenance;
 use crate::config::tree::RockLayoutConfig;
+use crate::lua_rockspec::PlatformIdentifier;
 use crate::package::{
     PackageName, PackageReq, PackageSpec, PackageVersion, PackageVersionReq,
     PackageVersionReqError, RemotePackageTypeFilterSpec,
@@ -23,6 +25,8 @@ use crate::remote_package_source::RemotePackageSource;
 use crate::rockspec::lua_dependency::LuaDependencySpec;
 use crate::rockspec::RockBinaries;
 
+pub mod signature;
+
 const LOCKFILE_VERSION_STR: &str = "1.0.0";
 
 #[derive(Copy, Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
@@ -364,6 +368,8 @@ pub struct LocalPackage {
     pub(crate) source: RemotePackageSource,
     pub(crate) source_url: Option<RemotePackageSourceUrl>,
     hashes: LocalPackageHashes,
+    /// How the package was built, if it was built by lux.
+    pub(crate) provenance: Option<BuildProvenance>,
 }
 
 impl UserData for LocalPackage {
@@ -408,6 +414,8 @@ struct LocalPackageIntermediate {
     source: RemotePackageSource,
     source_url: Option<RemotePackageSourceUrl>,
     hashes: LocalPackageHashes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    provenance: Option<BuildProvenance>,
 }
 
 impl TryFrom<LocalPackageIntermediate> for LocalPackage {
@@ -428,6 +436,7 @@ impl TryFrom<LocalPackageIntermediate> for LocalPackage {
             source: value.source,
             source_url: value.source_url,
             hashes: value.hashes,
+            provenance: value.provenance,
         })
     }
 }
@@ -445,6 +454,7 @@ impl From<&LocalPackage> for LocalPackageIntermediate {
             source: value.source.clone(),
             source_url: value.source_url.clone(),
             hashes: value.hashes.clone(),
+            provenance: value.provenance.clone(),
         }
     }
 }
@@ -490,6 +500,7 @@ impl LocalPackage {
             source,
             source_url,
             hashes,
+            provenance: None,
         }
     }
 
@@ -529,6 +540,11 @@ impl LocalPackage {
         &self.hashes
     }
 
+    /// How the package was built, if it was built by lux.
+    pub fn provenance(&self) -> Option<&BuildProvenance> {
+        self.provenance.as_ref()
+    }
+
     pub fn to_package(&self) -> PackageSpec {
         self.spec.to_package()
     }
@@ -558,6 +574,15 @@ impl PartialOrd for LocalPackageHashes {
     }
 }
 
+/// The Lua interpreter of a project that pins an exact Lua version,
+/// e.g. `lua = "=5.1.5"` in the lux.toml.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct LockedLuaInterpreter {
+    pub version: PackageVersion,
+    /// The hash of the Lua sources the interpreter was built from.
+    pub source: Integrity,
+}
+
 impl mlua::UserData for LocalPackageHashes {
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
         methods.add_method("rockspec", |_, this, ()| Ok(this.rockspec.to_hex().1));
@@ -660,10 +685,16 @@ pub(crate) struct LocalPackageLock {
 
     #[serde(serialize_with = "serialize_sorted_package_ids")]
     entrypoints: Vec<LocalPackageId>,
+
+    /// Entrypoints that are only required on some platforms,
+    /// because of per-platform dependency overrides, keyed by the platform they were resolved on.
+    /// The `entrypoints` are required on every platform.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    platform_entrypoints: BTreeMap<String, Vec<LocalPackageId>>,
 }
 
 impl LocalPackageLock {
-    fn get(&self, id: &LocalPackageId) -> Option<&LocalPackage> {
+    pub(crate) fn get(&self, id: &LocalPackageId) -> Option<&LocalPackage> {
         self.rocks.get(id)
     }
 
@@ -675,10 +706,122 @@ impl LocalPackageLock {
         &self.rocks
     }
 
-    fn is_entrypoint(&self, package: &LocalPackageId) -> bool {
+    pub(crate) fn is_entrypoint(&self, package: &LocalPackageId) -> bool {
         self.entrypoints.contains(package)
     }
 
+    /// The packages that are required on `platform`, i.e. the platform-independent entrypoints,
+    /// the entrypoints that are specific to `platform`, and their dependencies.
+    pub(crate) fn for_platform(&self, platform: &PlatformIdentifier) -> LocalPackageLock {
+        let entrypoints = self
+            .entrypoints
+            .iter()
+            .chain(
+                self.platform_entrypoints
+                    .get(&platform.to_string())
+                    .into_iter()
+                    .flatten(),
+            )
+            .unique()
+            .cloned()
+            .collect_vec();
+        let rocks = entrypoints
+            .iter()
+            .flat_map(|id| self.get_all_dependencies(id))
+            .map(|package| (package.id(), package.clone()))
+            .collect();
+        LocalPackageLock {
+            rocks,
+            entrypoints,
+            platform_entrypoints: BTreeMap::new(),
+        }
+    }
+
+    /// Replace the packages required on `platform` with the ones in `lock`,
+    /// keeping the entrypoints that are specific to other platforms.
+    /// The entrypoints of `lock` for which `is_platform_specific` is `true`
+    /// are only required on `platform`.
+    fn sync_platform<F>(
+        &mut self,
+        lock: &LocalPackageLock,
+        platform: &PlatformIdentifier,
+        is_platform_specific: F,
+    ) where
+        F: Fn(&LocalPackage) -> bool,
+    {
+        let (specific, common): (Vec<_>, Vec<_>) = lock
+            .entrypoints
+            .iter()
+            .cloned()
+            .partition(|id| lock.get(id).is_some_and(&is_platform_specific));
+        let mut platform_entrypoints = self.platform_entrypoints.clone();
+        platform_entrypoints.remove(&platform.to_string());
+        if !specific.is_empty() {
+            platform_entrypoints.insert(
+                platform.to_string(),
+                specific.into_iter().sorted().collect(),
+            );
+        }
+        let mut rocks = lock.rocks.clone();
+        rocks.extend(
+            platform_entrypoints
+                .values()
+                .flatten()
+                .flat_map(|id| self.get_all_dependencies(id))
+                .map(|package| (package.id(), package.clone())),
+        );
+        *self = LocalPackageLock {
+            rocks,
+            entrypoints: common,
+            platform_entrypoints,
+        };
+    }
+
+    /// Whether a package with the same name is an entrypoint that is specific to `platform`.
+    fn is_platform_entrypoint(
+        &self,
+        package: &LocalPackage,
+        platform: &PlatformIdentifier,
+    ) -> bool {
+        self.platform_entrypoints
+            .get(&platform.to_string())
+            .is_some_and(|entrypoints| {
+                entrypoints
+                    .iter()
+                    .filter_map(|id| self.get(id))
+                    .any(|entrypoint| entrypoint.name() == package.name())
+            })
+    }
+
+    fn dependency_graph(&self) -> DependencyGraph {
+        DependencyGraph {
+            nodes: self
+                .rocks
+                .iter()
+                .map(|(id, package)| DependencyGraphNode {
+                    id: id.clone(),
+                    name: package.name().clone(),
+                    version: package.version().clone(),
+                    pinned: package.pinned(),
+                    entrypoint: self.is_entrypoint(id),
+                })
+                .collect(),
+            edges: self
+                .rocks
+                .iter()
+                .flat_map(|(id, package)| {
+                    package
+                        .dependencies()
+                        .into_iter()
+                        .map(|dependency| DependencyGraphEdge {
+                            from: id.clone(),
+                            to: dependency.clone(),
+                        })
+                })
+                .collect(),
+        }
+    }
+
     fn is_dependency(&self, package: &LocalPackageId) -> bool {
         self.rocks
             .values()
@@ -694,13 +837,36 @@ impl LocalPackageLock {
             .into_group_map()
     }
 
-    fn remove(&mut self, target: &LocalPackage) {
+    pub(crate) fn remove(&mut self, target: &LocalPackage) {
         self.remove_by_id(&target.id())
     }
 
     fn remove_by_id(&mut self, target: &LocalPackageId) {
         self.rocks.remove(target);
         self.entrypoints.retain(|x| x != target);
+        for entrypoints in self.platform_entrypoints.values_mut() {
+            entrypoints.retain(|x| x != target);
+        }
+        self.platform_entrypoints
+            .retain(|_, entrypoints| !entrypoints.is_empty());
+    }
+
+    /// Replace a package with a new version of itself, e.g. after its pinned state has changed,
+    /// updating the entrypoints and the dependencies of other packages that refer to it.
+    fn replace(&mut self, old: &LocalPackageId, new: &LocalPackage) {
+        let new_id = new.id();
+        self.rocks.remove(old);
+        self.rocks.insert(new_id.clone(), new.clone());
+        self.entrypoints
+            .iter_mut()
+            .chain(self.platform_entrypoints.values_mut().flatten())
+            .filter(|id| *id == old)
+            .for_each(|id| *id = new_id.clone());
+        self.rocks
+            .values_mut()
+            .flat_map(|rock| rock.spec.dependencies.iter_mut())
+            .filter(|id| *id == old)
+            .for_each(|id| *id = new_id.clone());
     }
 
     pub(crate) fn has_rock(
@@ -839,6 +1005,8 @@ pub struct ProjectLockfile<P: LockfilePermissions> {
     test_dependencies: LocalPackageLock,
     #[serde(default, skip_serializing_if = "LocalPackageLock::is_empty")]
     build_dependencies: LocalPackageLock,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lua: Option<LockedLuaInterpreter>,
 }
 
 #[derive(Error, Debug)]
@@ -872,10 +1040,64 @@ pub(crate) struct PackageSyncSpec {
     pub to_remove: Vec<LocalPackage>,
 }
 
+/// A package in a lockfile's [`DependencyGraph`].
+#[derive(Debug, Clone)]
+pub struct DependencyGraphNode {
+    pub id: LocalPackageId,
+    pub name: PackageName,
+    pub version: PackageVersion,
+    pub pinned: PinnedState,
+    /// Whether the package was installed explicitly, rather than as a dependency.
+    pub entrypoint: bool,
+}
+
+/// A dependency of the package `from` on the package `to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyGraphEdge {
+    pub from: LocalPackageId,
+    pub to: LocalPackageId,
+}
+
+/// The packages in a lockfile and the dependencies between them.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    pub nodes: Vec<DependencyGraphNode>,
+    pub edges: Vec<DependencyGraphEdge>,
+}
+
+impl IntoLua for DependencyGraph {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        let table = lua.create_table()?;
+        let nodes = lua.create_table()?;
+        for node in self.nodes {
+            let node_table = lua.create_table()?;
+            node_table.set("id", node.id)?;
+            node_table.set("name", node.name)?;
+            node_table.set("version", node.version)?;
+            node_table.set("pinned", node.pinned)?;
+            node_table.set("entrypoint", node.entrypoint)?;
+            nodes.push(node_table)?;
+        }
+        let edges = lua.create_table()?;
+        for edge in self.edges {
+            let edge_table = lua.create_table()?;
+            edge_table.set("from", edge.from)?;
+            edge_table.set("to", edge.to)?;
+            edges.push(edge_table)?;
+        }
+        table.set("nodes", nodes)?;
+        table.set("edges", edges)?;
+        table.into_lua(lua)
+    }
+}
+
 impl UserData for Lockfile<ReadOnly> {
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
         methods.add_method("version", |_, this, _: ()| Ok(this.version().clone()));
         methods.add_method("rocks", |_, this, _: ()| Ok(this.rocks().clone()));
+        methods.add_method("dependency_graph", |_, this, _: ()| {
+            Ok(this.dependency_graph())
+        });
         methods.add_method("get", |_, this, id: LocalPackageId| {
             Ok(this.get(&id).cloned())
         });
@@ -908,6 +1130,11 @@ impl<P: LockfilePermissions> Lockfile<P> {
         self.lock.is_entrypoint(package)
     }
 
+    /// The packages in this lockfile and the dependencies between them.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        self.lock.dependency_graph()
+    }
+
     pub(crate) fn local_pkg_lock(&self) -> &LocalPackageLock {
         &self.lock
     }
@@ -1016,18 +1243,6 @@ impl<P: LockfilePermissions> ProjectLockfile<P> {
         }
     }
 
-    pub(crate) fn get(
-        &self,
-        id: &LocalPackageId,
-        deps: &LocalPackageLockType,
-    ) -> Option<&LocalPackage> {
-        match deps {
-            LocalPackageLockType::Regular => self.dependencies.get(id),
-            LocalPackageLockType::Test => self.test_dependencies.get(id),
-            LocalPackageLockType::Build => self.build_dependencies.get(id),
-        }
-    }
-
     pub(crate) fn is_entrypoint(
         &self,
         package: &LocalPackageId,
@@ -1040,16 +1255,14 @@ impl<P: LockfilePermissions> ProjectLockfile<P> {
         }
     }
 
-    pub(crate) fn package_sync_spec(
-        &self,
-        packages: &[LuaDependencySpec],
-        deps: &LocalPackageLockType,
-    ) -> PackageSyncSpec {
-        match deps {
-            LocalPackageLockType::Regular => self.dependencies.package_sync_spec(packages),
-            LocalPackageLockType::Test => self.test_dependencies.package_sync_spec(packages),
-            LocalPackageLockType::Build => self.build_dependencies.package_sync_spec(packages),
-        }
+    /// The packages of the given dependency type and the dependencies between them.
+    pub fn dependency_graph(&self, deps: &LocalPackageLockType) -> DependencyGraph {
+        self.local_pkg_lock(deps).dependency_graph()
+    }
+
+    /// The Lua interpreter the project was built with, if it pins an exact Lua version.
+    pub fn lua(&self) -> Option<&LockedLuaInterpreter> {
+        self.lua.as_ref()
     }
 
     pub(crate) fn local_pkg_lock(&self, deps: &LocalPackageLockType) -> &LocalPackageLock {
@@ -1060,6 +1273,17 @@ impl<P: LockfilePermissions> ProjectLockfile<P> {
         }
     }
 
+    /// Whether a package with the same name is an entrypoint that is only required on `platform`.
+    pub(crate) fn is_platform_entrypoint(
+        &self,
+        package: &LocalPackage,
+        deps: &LocalPackageLockType,
+        platform: &PlatformIdentifier,
+    ) -> bool {
+        self.local_pkg_lock(deps)
+            .is_platform_entrypoint(package, platform)
+    }
+
     fn flush(&self) -> io::Result<()> {
         let content = serde_json::to_string_pretty(&self)?;
 
@@ -1180,6 +1404,7 @@ impl ProjectLockfile<ReadOnly> {
                     dependencies: LocalPackageLock::default(),
                     test_dependencies: LocalPackageLock::default(),
                     build_dependencies: LocalPackageLock::default(),
+                    lua: None,
                 };
                 let json_str =
                     serde_json::to_string(&empty_lockfile).map_err(LockfileError::WriteJson)?;
@@ -1212,6 +1437,7 @@ impl ProjectLockfile<ReadOnly> {
             dependencies: self.dependencies,
             test_dependencies: self.test_dependencies,
             build_dependencies: self.build_dependencies,
+            lua: self.lua,
         }
     }
 
@@ -1262,14 +1488,14 @@ impl Lockfile<ReadWrite> {
         self.add(dependency);
     }
 
-    pub(crate) fn remove(&mut self, target: &LocalPackage) {
-        self.lock.remove(target)
-    }
-
     pub(crate) fn remove_by_id(&mut self, target: &LocalPackageId) {
         self.lock.remove_by_id(target)
     }
 
+    pub(crate) fn replace(&mut self, old: &LocalPackageId, new: &LocalPackage) {
+        self.lock.replace(old, new)
+    }
+
     pub(crate) fn sync(&mut self, lock: &LocalPackageLock) {
         self.lock = lock.clone();
     }
@@ -1278,24 +1504,47 @@ impl Lockfile<ReadWrite> {
 }
 
 impl ProjectLockfile<ReadWrite> {
-    pub(crate) fn remove(&mut self, target: &LocalPackage, deps: &LocalPackageLockType) {
+    pub(crate) fn set_lua(&mut self, lua: Option<LockedLuaInterpreter>) {
+        self.lua = lua;
+    }
+
+    pub(crate) fn replace(
+        &mut self,
+        old: &LocalPackageId,
+        new: &LocalPackage,
+        deps: &LocalPackageLockType,
+    ) {
         match deps {
-            LocalPackageLockType::Regular => self.dependencies.remove(target),
-            LocalPackageLockType::Test => self.test_dependencies.remove(target),
-            LocalPackageLockType::Build => self.build_dependencies.remove(target),
+            LocalPackageLockType::Regular => self.dependencies.replace(old, new),
+            LocalPackageLockType::Test => self.test_dependencies.replace(old, new),
+            LocalPackageLockType::Build => self.build_dependencies.replace(old, new),
         }
     }
 
-    pub(crate) fn sync(&mut self, lock: &LocalPackageLock, deps: &LocalPackageLockType) {
+    /// Replace the packages required on `platform` with the ones in `lock`,
+    /// keeping the packages that are only required on other platforms,
+    /// so that the lockfile can be shared between platforms.
+    pub(crate) fn sync_platform<F>(
+        &mut self,
+        lock: &LocalPackageLock,
+        deps: &LocalPackageLockType,
+        platform: &PlatformIdentifier,
+        is_platform_specific: F,
+    ) where
+        F: Fn(&LocalPackage) -> bool,
+    {
         match deps {
             LocalPackageLockType::Regular => {
-                self.dependencies = lock.clone();
+                self.dependencies
+                    .sync_platform(lock, platform, is_platform_specific)
             }
             LocalPackageLockType::Test => {
-                self.test_dependencies = lock.clone();
+                self.test_dependencies
+                    .sync_platform(lock, platform, is_platform_specific)
             }
             LocalPackageLockType::Build => {
-                self.build_dependencies = lock.clone();
+                self.build_dependencies
+                    .sync_platform(lock, platform, is_platform_specific)
             }
         }
     }
@@ -1488,6 +1737,28 @@ mod tests {
         assert_json_snapshot!(lockfile, { ".**" => sorted_redaction() });
     }
 
+    #[test]
+    fn project_lockfile_lua_interpreter() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let lockfile_path = temp.join("lux.lock");
+        let lockfile = ProjectLockfile::new(lockfile_path.clone()).unwrap();
+        assert!(lockfile.lua().is_none());
+
+        let locked = LockedLuaInterpreter {
+            version: "5.1.5".parse().unwrap(),
+            source: "sha256-JkD8VqeV8p0o7xXhPDSkfiI5YLAkDoywqC2bBzhpUzM="
+                .parse()
+                .unwrap(),
+        };
+        lockfile.write_guard().set_lua(Some(locked.clone()));
+        let lockfile = ProjectLockfile::load(lockfile_path.clone()).unwrap();
+        assert_eq!(lockfile.lua(), Some(&locked));
+
+        lockfile.write_guard().set_lua(None);
+        let lockfile = ProjectLockfile::load(lockfile_path).unwrap();
+        assert!(lockfile.lua().is_none());
+    }
+
     #[test]
     fn add_rocks() {
         let temp = assert_fs::TempDir::new().unwrap();
@@ -1561,12 +1832,153 @@ mod tests {
         let _ = tree.lockfile().unwrap().write_guard(); // Try to create the lockfile but don't actually do anything with it.
     }
 
+    #[test]
+    fn sync_platform_keeps_other_platforms() {
+        let mock_hashes = LocalPackageHashes {
+            rockspec: "sha256-uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+                .parse()
+                .unwrap(),
+            source: "sha256-uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+                .parse()
+                .unwrap(),
+        };
+        let package = |name: &str| {
+            LocalPackage::from(
+                &PackageSpec::parse(name.to_string(), "1.0.0".to_string()).unwrap(),
+                LockConstraint::Unconstrained,
+                RockBinaries::default(),
+                RemotePackageSource::Local,
+                None,
+                mock_hashes.clone(),
+            )
+        };
+        let lock_with = |packages: &[&LocalPackage]| LocalPackageLock {
+            rocks: packages
+                .iter()
+                .map(|package| (package.id(), (*package).clone()))
+                .collect(),
+            entrypoints: packages.iter().map(|package| package.id()).collect(),
+            platform_entrypoints: BTreeMap::new(),
+        };
+        let common = package("common");
+        let linux_only = package("luv");
+        let windows_only = package("winapi");
+
+        let mut lock = LocalPackageLock::default();
+        lock.sync_platform(
+            &lock_with(&[&common, &linux_only]),
+            &PlatformIdentifier::Linux,
+            |package| package.name() == linux_only.name(),
+        );
+        lock.sync_platform(
+            &lock_with(&[&common, &windows_only]),
+            &PlatformIdentifier::Windows,
+            |package| package.name() == windows_only.name(),
+        );
+
+        assert_eq!(lock.entrypoints, vec![common.id()]);
+        assert_eq!(lock.rocks.len(), 3);
+        assert!(lock.is_platform_entrypoint(&linux_only, &PlatformIdentifier::Linux));
+        assert!(!lock.is_platform_entrypoint(&linux_only, &PlatformIdentifier::Windows));
+
+        let linux = lock.for_platform(&PlatformIdentifier::Linux);
+        assert_eq!(
+            linux.entrypoints.iter().sorted().collect_vec(),
+            vec![&common.id(), &linux_only.id()]
+                .into_iter()
+                .sorted()
+                .collect_vec()
+        );
+        assert!(!linux.rocks.contains_key(&windows_only.id()));
+        let freebsd = lock.for_platform(&PlatformIdentifier::FreeBSD);
+        assert_eq!(freebsd.entrypoints, vec![common.id()]);
+
+        let serialized = serde_json::to_value(&lock).unwrap();
+        assert_eq!(
+            serialized["platform_entrypoints"]["windows"],
+            serde_json::json!([windows_only.id()])
+        );
+        let deserialized: LocalPackageLock = serde_json::from_value(serialized).unwrap();
+        assert_eq!(deserialized.platform_entrypoints, lock.platform_entrypoints);
+    }
+
     fn get_test_lockfile() -> Lockfile<ReadOnly> {
         let sample_tree = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .join("resources/test/sample-tree/5.1/lux.lock");
         Lockfile::new(sample_tree, RockLayoutConfig::default()).unwrap()
     }
 
+    #[test]
+    fn test_dependency_graph() {
+        let lockfile = get_test_lockfile();
+        let graph = lockfile.dependency_graph();
+        assert_eq!(graph.nodes.len(), lockfile.rocks().len());
+
+        let neorg = graph
+            .nodes
+            .iter()
+            .find(|node| node.name.to_string() == "neorg")
+            .unwrap();
+        assert!(neorg.entrypoint);
+        assert_eq!(neorg.version.to_string(), "8.8.1-1");
+        assert_eq!(neorg.pinned, PinnedState::Unpinned);
+        let neorg_dependencies = graph
+            .edges
+            .iter()
+            .filter(|edge| edge.from == neorg.id)
+            .map(|edge| lockfile.get(&edge.to).unwrap().name().to_string())
+            .sorted()
+            .collect_vec();
+        assert_eq!(
+            neorg_dependencies,
+            vec![
+                "lua-utils.nvim",
+                "nui.nvim",
+                "nvim-nio",
+                "pathlib.nvim",
+                "plenary.nvim"
+            ]
+        );
+
+        let nvim_nio = graph
+            .nodes
+            .iter()
+            .filter(|node| node.name.to_string() == "nvim-nio")
+            .collect_vec();
+        assert_eq!(nvim_nio.len(), 2);
+        assert!(nvim_nio.iter().all(|node| !node.entrypoint));
+    }
+
+    #[test]
+    fn replace_pinned_dependency() {
+        let mut lockfile = get_test_lockfile().into_temporary();
+        let neorg_id = lockfile
+            .rocks()
+            .values()
+            .find(|rock| rock.name().to_string() == "neorg")
+            .unwrap()
+            .id();
+        let pathlib = lockfile
+            .rocks()
+            .values()
+            .find(|rock| rock.name().to_string() == "pathlib.nvim")
+            .unwrap()
+            .clone();
+        let mut pinned = pathlib.clone();
+        pinned.spec.pinned = PinnedState::Pinned;
+        lockfile.replace(&pathlib.id(), &pinned);
+
+        assert!(lockfile.get(&pathlib.id()).is_none());
+        assert_eq!(
+            lockfile.get(&pinned.id()).unwrap().pinned(),
+            PinnedState::Pinned
+        );
+        assert!(!lockfile.is_entrypoint(&pinned.id()));
+        let neorg_dependencies = lockfile.get(&neorg_id).unwrap().dependencies();
+        assert!(neorg_dependencies.contains(&&pinned.id()));
+        assert!(!neorg_dependencies.contains(&&pathlib.id()));
+    }
+
     #[test]
     fn test_sync_spec() {
         let lockfile = get_test_lockfile();
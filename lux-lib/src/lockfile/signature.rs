@@ -0,0 +1,203 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::config::Config;
+
+#[cfg(not(target_env = "msvc"))]
+use gpgme::{Context, Data};
+#[cfg(not(target_env = "msvc"))]
+use std::io::Read;
+
+/// The extension that is appended to a lockfile's name for its detached signature.
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+#[derive(Error, Debug)]
+pub enum LockfileSignatureError {
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, io::Error),
+    #[error("failed to write {0}: {1}")]
+    Write(PathBuf, io::Error),
+    #[error("{0} is not signed.\nHINT: Sign it with `lx lock sign`.")]
+    NotSigned(PathBuf),
+    #[error("cannot verify the signature of {0}: no trusted lockfile keys are configured.\nHINT: Add the fingerprints of the keys you trust to `trusted_lockfile_keys` in the config.")]
+    NoTrustedKeys(PathBuf),
+    #[error("the signature of {0} is invalid or was not made by a trusted key")]
+    Untrusted(PathBuf),
+    #[cfg(not(target_env = "msvc"))]
+    #[error(transparent)]
+    Gpg(#[from] gpgme::Error),
+    #[cfg(target_env = "msvc")]
+    #[error("signing lockfiles is not supported on this platform")]
+    Unsupported,
+}
+
+/// The path of the detached signature of the lockfile at `lockfile_path`.
+pub fn signature_path(lockfile_path: &Path) -> PathBuf {
+    let mut path = lockfile_path.as_os_str().to_os_string();
+    path.push(".");
+    path.push(SIGNATURE_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Write an ASCII-armored detached OpenPGP signature of the lockfile at `lockfile_path`
+/// next to it, returning the signature's path.
+/// If no `key` is given, the default OpenPGP key is used.
+#[cfg(not(target_env = "msvc"))]
+pub fn sign_lockfile(
+    lockfile_path: &Path,
+    key: Option<&str>,
+) -> Result<PathBuf, LockfileSignatureError> {
+    let content = std::fs::read(lockfile_path)
+        .map_err(|err| LockfileSignatureError::Read(lockfile_path.to_path_buf(), err))?;
+
+    let mut ctx = Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    ctx.set_armor(true);
+    if let Some(key) = key {
+        let key = ctx.get_secret_key(key)?;
+        ctx.add_signer(&key)?;
+    }
+    let mut signature = Data::new()?;
+    ctx.sign_detached(content, &mut signature)?;
+    let mut signature_str = String::new();
+    signature
+        .read_to_string(&mut signature_str)
+        .map_err(|err| LockfileSignatureError::Read(lockfile_path.to_path_buf(), err))?;
+
+    let path = signature_path(lockfile_path);
+    std::fs::write(&path, signature_str)
+        .map_err(|err| LockfileSignatureError::Write(path.clone(), err))?;
+    Ok(path)
+}
+
+#[cfg(target_env = "msvc")]
+pub fn sign_lockfile(
+    _lockfile_path: &Path,
+    _key: Option<&str>,
+) -> Result<PathBuf, LockfileSignatureError> {
+    Err(LockfileSignatureError::Unsupported)
+}
+
+/// Verify that the lockfile at `lockfile_path` has a valid detached signature
+/// made by one of the config's `trusted_lockfile_keys`.
+#[cfg(not(target_env = "msvc"))]
+pub fn verify_lockfile_signature(
+    lockfile_path: &Path,
+    config: &Config,
+) -> Result<(), LockfileSignatureError> {
+    let trusted_keys = config.trusted_lockfile_keys();
+    if trusted_keys.is_empty() {
+        return Err(LockfileSignatureError::NoTrustedKeys(
+            lockfile_path.to_path_buf(),
+        ));
+    }
+    let path = signature_path(lockfile_path);
+    if !path.is_file() {
+        return Err(LockfileSignatureError::NotSigned(
+            lockfile_path.to_path_buf(),
+        ));
+    }
+    let content = std::fs::read(lockfile_path)
+        .map_err(|err| LockfileSignatureError::Read(lockfile_path.to_path_buf(), err))?;
+    let signature =
+        std::fs::read(&path).map_err(|err| LockfileSignatureError::Read(path.clone(), err))?;
+
+    let mut ctx = Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    let result = ctx.verify_detached(signature, content)?;
+    let is_trusted = result.signatures().any(|signature| {
+        signature.status().is_ok()
+            && signature
+                .fingerprint()
+                .is_ok_and(|fingerprint| is_trusted_key(fingerprint, trusted_keys))
+    });
+    if is_trusted {
+        Ok(())
+    } else {
+        Err(LockfileSignatureError::Untrusted(
+            lockfile_path.to_path_buf(),
+        ))
+    }
+}
+
+#[cfg(target_env = "msvc")]
+pub fn verify_lockfile_signature(
+    _lockfile_path: &Path,
+    _config: &Config,
+) -> Result<(), LockfileSignatureError> {
+    Err(LockfileSignatureError::Unsupported)
+}
+
+/// Normalize a trusted key, which must be a full 40-digit fingerprint
+/// or a 16-digit long key ID, optionally prefixed with `0x` and grouped with spaces.
+/// Returns `None` for anything else, e.g. short key IDs, which can be forged by collision.
+pub(crate) fn parse_trusted_key(key: &str) -> Option<String> {
+    let key = key.replace(' ', "").to_uppercase();
+    let key = key.strip_prefix("0X").unwrap_or(&key);
+    (matches!(key.len(), 16 | 40) && key.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| key.to_string())
+}
+
+/// Whether the key with the `fingerprint` is one of the `trusted_keys`,
+/// which may be full fingerprints or long key IDs, i.e. their last 16 digits.
+#[cfg_attr(target_env = "msvc", allow(dead_code))]
+fn is_trusted_key(fingerprint: &str, trusted_keys: &[String]) -> bool {
+    let fingerprint = fingerprint.to_uppercase();
+    fingerprint.len() == 40
+        && trusted_keys
+            .iter()
+            .filter_map(|key| parse_trusted_key(key))
+            .any(|key| fingerprint.ends_with(&key))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ConfigBuilder, ConfigError};
+
+    use super::*;
+
+    #[test]
+    fn lockfile_signature_path() {
+        assert_eq!(
+            signature_path(Path::new("/project/lux.lock")),
+            PathBuf::from("/project/lux.lock.sig")
+        );
+    }
+
+    #[test]
+    fn trusted_lockfile_keys() {
+        let fingerprint = "D8A4C4F1B2E9A1F3C0B5E6A7D4C3B2A1F0E9D8C7";
+        let trusted = |key: &str| is_trusted_key(fingerprint, &[key.to_string()]);
+        assert!(trusted(fingerprint));
+        assert!(trusted("d8a4 c4f1 b2e9 a1f3 c0b5 e6a7 d4c3 b2a1 f0e9 d8c7"));
+        assert!(trusted("0xD4C3B2A1F0E9D8C7"));
+        assert!(!trusted("D4C3B2A1F0E9D8C8"));
+        assert!(!trusted(""));
+        assert!(!trusted("7"));
+        assert!(!trusted("F0E9D8C7"));
+        assert!(!trusted("C3B2A1F0E9D8C7"));
+        assert!(!trusted("zzC3B2A1F0E9D8C7"));
+    }
+
+    #[test]
+    fn config_rejects_short_trusted_keys() {
+        let config = |key: &str| {
+            ConfigBuilder::new()
+                .unwrap()
+                .trusted_lockfile_keys(Some(vec![key.to_string()]))
+                .build()
+        };
+        assert_eq!(
+            config("0xd4c3 b2a1 f0e9 d8c7")
+                .unwrap()
+                .trusted_lockfile_keys(),
+            ["D4C3B2A1F0E9D8C7"]
+        );
+        assert!(matches!(
+            config("F0E9D8C7"),
+            Err(ConfigError::InvalidTrustedKey(_))
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 use is_executable::IsExecutable;
 use itertools::Itertools;
 use path_slash::PathBufExt;
+use ssri::Integrity;
 use std::fmt;
 use std::fmt::Display;
 use std::io;
@@ -14,6 +15,7 @@ use crate::build::external_dependency::ExternalDependencyInfo;
 use crate::build::utils::{c_lib_extension, format_path};
 use crate::config::external_deps::ExternalDependencySearchConfig;
 use crate::config::LuaVersionUnset;
+use crate::lockfile::LockedLuaInterpreter;
 use crate::lua_rockspec::ExternalDependencySpec;
 use crate::operations;
 use crate::operations::BuildLuaError;
@@ -22,12 +24,31 @@ use crate::progress::ProgressBar;
 use crate::variables::GetVariableError;
 use crate::{
     config::{Config, LuaVersion},
-    package::PackageVersion,
+    package::{HasModRev, PackageVersion},
     variables::HasVariables,
 };
 use lazy_static::lazy_static;
 use tokio::sync::Mutex;
 
+pub(crate) mod nlua;
+pub(crate) mod openresty;
+
+/// Marks a Lua installation that only consists of the C headers.
+const HEADERS_ONLY_MARKER: &str = ".headers-only";
+
+/// Records the exact version and source hash of a pinned Lua installation.
+const PINNED_LUA_MARKER: &str = ".lux-lua.json";
+
+/// The C headers that Lux installs with Lua.
+pub(crate) const LUA_HEADERS: &[&str] = &[
+    "lauxlib.h",
+    "lua.h",
+    "luaconf.h",
+    "luajit.h",
+    "lualib.h",
+    "lua.hpp",
+];
+
 // Because installing lua is not thread-safe, we have to synchronize with a global Mutex
 lazy_static! {
     static ref NEW_MUTEX: Mutex<i32> = Mutex::new(0i32);
@@ -40,6 +61,8 @@ pub struct LuaInstallation {
     dependency_info: ExternalDependencyInfo,
     /// Binary to the Lua executable, if present
     pub(crate) bin: Option<PathBuf>,
+    /// Whether this installation only consists of the C headers
+    headers_only: bool,
 }
 
 #[derive(Debug, Error)]
@@ -81,6 +104,30 @@ pub enum LuaInstallationError {
     Build(#[from] BuildLuaError),
     #[error(transparent)]
     LuaVersionUnset(#[from] LuaVersionUnset),
+    #[error(
+        "cannot pin Lua {}: only exact PUC Lua releases (5.1.x - 5.4.x) can be pinned",
+        .0.to_modrev_string()
+    )]
+    UnsupportedPinnedVersion(PackageVersion),
+    #[error(
+        "the project pins Lua {}, but Lux is configured to use Lua {lua_version}",
+        pinned_version.to_modrev_string()
+    )]
+    PinnedVersionMismatch {
+        pinned_version: PackageVersion,
+        lua_version: LuaVersion,
+    },
+    #[error(
+        "the installed Lua {} was built from sources that don't match the lockfile.\nExpected: {expected},\nbut got: {actual}",
+        version.to_modrev_string()
+    )]
+    PinnedSourceMismatch {
+        version: PackageVersion,
+        expected: Integrity,
+        actual: Integrity,
+    },
+    #[error("built Lua, but could not find the installation in {0}")]
+    PinnedInstallationNotFound(PathBuf),
 }
 
 impl LuaInstallation {
@@ -97,34 +144,136 @@ impl LuaInstallation {
         progress: &Progress<ProgressBar>,
     ) -> Result<Self, LuaInstallationError> {
         let _lock = NEW_MUTEX.lock().await;
+        match Self::find(version, config) {
+            Some(lua_installation) => Ok(lua_installation),
+            None => Self::install(version, config, progress).await,
+        }
+    }
+
+    /// Find an existing Lua installation, either on the system,
+    /// in an OpenResty installation (for LuaJIT) or installed by Lux, without installing Lua.
+    pub fn find(version: &LuaVersion, config: &Config) -> Option<Self> {
         if let Some(lua_intallation) = Self::probe(version, config.external_deps()) {
-            return Ok(lua_intallation);
+            return Some(lua_intallation);
+        }
+        if let Some(lua_installation) = openresty::OpenResty::detect(config)
+            .and_then(|openresty| openresty.lua_installation(version))
+        {
+            return Some(lua_installation);
         }
-        let output = Self::root_dir(version, config);
+        Self::from_root_dir(version, &Self::root_dir(version, config))
+    }
+
+    /// Find or install the exact Lua release `pinned_version`, e.g. `5.1.5`,
+    /// which a project pins with `lua = "=5.1.5"` in its lux.toml.
+    /// Lux always builds pinned Lua versions from source, so that their sources can be verified
+    /// against the `locked` interpreter from the project lockfile.
+    /// Returns the installation, along with the interpreter to record in the lockfile.
+    pub async fn new_pinned(
+        pinned_version: &PackageVersion,
+        locked: Option<&LockedLuaInterpreter>,
+        config: &Config,
+        progress: &Progress<ProgressBar>,
+    ) -> Result<(Self, LockedLuaInterpreter), LuaInstallationError> {
+        let version = LuaVersion::from_version(pinned_version.clone())
+            .ok()
+            .filter(|version| !version.is_luajit())
+            .ok_or_else(|| {
+                LuaInstallationError::UnsupportedPinnedVersion(pinned_version.clone())
+            })?;
+        if let Ok(configured_version) = LuaVersion::from(config) {
+            if configured_version != &version {
+                return Err(LuaInstallationError::PinnedVersionMismatch {
+                    pinned_version: pinned_version.clone(),
+                    lua_version: configured_version.clone(),
+                });
+            }
+        }
+        let locked = locked.filter(|locked| &locked.version == pinned_version);
+
+        let _lock = NEW_MUTEX.lock().await;
+        let root_dir = Self::pinned_root_dir(pinned_version, config);
+        let marker = root_dir.join(PINNED_LUA_MARKER);
+        let installed: Option<LockedLuaInterpreter> = std::fs::read_to_string(&marker)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .filter(|installed: &LockedLuaInterpreter| &installed.version == pinned_version);
+        if let Some(installed) = installed {
+            if let Some(locked) = locked {
+                if installed.source.matches(&locked.source).is_none() {
+                    return Err(LuaInstallationError::PinnedSourceMismatch {
+                        version: pinned_version.clone(),
+                        expected: locked.source.clone(),
+                        actual: installed.source,
+                    });
+                }
+            }
+            if let Some(lua_installation) = Self::from_root_dir(&version, &root_dir) {
+                return Ok((lua_installation, installed));
+            }
+        }
+
+        let _lock = INSTALL_MUTEX.lock().await;
+        let source = operations::BuildLua::new()
+            .lua_version(&version)
+            .version(pinned_version)
+            .maybe_integrity(locked.map(|locked| &locked.source))
+            .install_dir(&root_dir)
+            .config(config)
+            .progress(progress)
+            .build()
+            .await?
+            .expect("PUC Lua is built from a source archive");
+        let installed = LockedLuaInterpreter {
+            version: pinned_version.clone(),
+            source,
+        };
+        let marker_content = serde_json::to_string(&installed).map_err(io::Error::from);
+        marker_content
+            .and_then(|content| std::fs::write(&marker, content))
+            .map_err(BuildLuaError::Io)?;
+        let lua_installation = Self::from_root_dir(&version, &root_dir)
+            .ok_or(LuaInstallationError::PinnedInstallationNotFound(root_dir))?;
+        Ok((lua_installation, installed))
+    }
+
+    /// Find an exact Lua release, installed with [`LuaInstallation::new_pinned`], without installing it.
+    pub fn find_pinned(pinned_version: &PackageVersion, config: &Config) -> Option<Self> {
+        let version = LuaVersion::from_version(pinned_version.clone()).ok()?;
+        let root_dir = Self::pinned_root_dir(pinned_version, config);
+        if root_dir.join(PINNED_LUA_MARKER).is_file() {
+            Self::from_root_dir(&version, &root_dir)
+        } else {
+            None
+        }
+    }
+
+    fn from_root_dir(version: &LuaVersion, root_dir: &Path) -> Option<Self> {
+        let output = root_dir;
         let include_dir = output.join("include");
         let lib_dir = output.join("lib");
         let lua_lib_name = get_lua_lib_name(&lib_dir, version);
-        if include_dir.is_dir() && lua_lib_name.is_some() {
+        let headers_only = output.join(HEADERS_ONLY_MARKER).is_file();
+        if include_dir.is_dir() && (lua_lib_name.is_some() || headers_only) {
             let bin_dir = Some(output.join("bin")).filter(|bin_path| bin_path.is_dir());
             let bin = bin_dir
                 .as_ref()
                 .and_then(|bin_path| find_lua_executable(bin_path));
-            let lib_dir = output.join("lib");
-            let lua_lib_name = get_lua_lib_name(&lib_dir, version);
-            let include_dir = Some(output.join("include"));
-            Ok(LuaInstallation {
+            let lib_dir = Some(lib_dir).filter(|lib_dir| lib_dir.is_dir());
+            Some(LuaInstallation {
                 version: version.clone(),
                 dependency_info: ExternalDependencyInfo {
-                    include_dir,
-                    lib_dir: Some(lib_dir),
+                    include_dir: Some(include_dir),
+                    lib_dir,
                     bin_dir,
                     lib_info: None,
                     lib_name: lua_lib_name,
                 },
                 bin,
+                headers_only,
             })
         } else {
-            Self::install(version, config, progress).await
+            None
         }
     }
 
@@ -163,6 +312,7 @@ impl LuaInstallation {
                 version: version.clone(),
                 dependency_info: dependency_info.unwrap(),
                 bin,
+                headers_only: false,
             })
         } else {
             None
@@ -185,6 +335,10 @@ impl LuaInstallation {
             .progress(progress)
             .build()
             .await?;
+        let headers_only_marker = target.join(HEADERS_ONLY_MARKER);
+        if headers_only_marker.is_file() {
+            std::fs::remove_file(headers_only_marker).map_err(BuildLuaError::Io)?;
+        }
 
         let include_dir = target.join("include");
         let lib_dir = target.join("lib");
@@ -203,6 +357,52 @@ impl LuaInstallation {
                 lib_name: lua_lib_name,
             },
             bin,
+            headers_only: false,
+        })
+    }
+
+    /// Install only the C headers for the given Lua version,
+    /// which is enough to build C rocks against a Lua interpreter that already exists.
+    /// This is much faster than building Lua.
+    pub async fn install_headers(
+        version: &LuaVersion,
+        config: &Config,
+        progress: &Progress<ProgressBar>,
+    ) -> Result<Self, LuaInstallationError> {
+        let _lock = INSTALL_MUTEX.lock().await;
+
+        let target = Self::root_dir(version, config);
+
+        operations::BuildLua::new()
+            .lua_version(version)
+            .install_dir(&target)
+            .config(config)
+            .progress(progress)
+            .headers_only(true)
+            .build()
+            .await?;
+        std::fs::write(target.join(HEADERS_ONLY_MARKER), version.to_string())
+            .map_err(BuildLuaError::Io)?;
+
+        let lib_dir = Some(target.join("lib")).filter(|lib_dir| lib_dir.is_dir());
+        let lua_lib_name = lib_dir
+            .as_ref()
+            .and_then(|lib_dir| get_lua_lib_name(lib_dir, version));
+        let bin_dir = Some(target.join("bin")).filter(|bin_path| bin_path.is_dir());
+        let bin = bin_dir
+            .as_ref()
+            .and_then(|bin_path| find_lua_executable(bin_path));
+        Ok(LuaInstallation {
+            version: version.clone(),
+            dependency_info: ExternalDependencyInfo {
+                include_dir: Some(target.join("include")),
+                lib_dir,
+                bin_dir,
+                lib_info: None,
+                lib_name: lua_lib_name,
+            },
+            bin,
+            headers_only: true,
         })
     }
 
@@ -210,6 +410,35 @@ impl LuaInstallation {
         self.dependency_info.include_dir.iter().collect_vec()
     }
 
+    /// The Lua C headers in the include directories.
+    pub fn headers(&self) -> Vec<PathBuf> {
+        self.includes()
+            .into_iter()
+            .flat_map(|include_dir| LUA_HEADERS.iter().map(|header| include_dir.join(header)))
+            .filter(|header| header.is_file())
+            .collect_vec()
+    }
+
+    /// Whether this installation only consists of the C headers,
+    /// installed with [`LuaInstallation::install_headers`].
+    pub fn is_headers_only(&self) -> bool {
+        self.headers_only
+    }
+
+    /// The Lua executable, if present.
+    pub fn bin(&self) -> Option<&Path> {
+        self.bin.as_deref()
+    }
+
+    fn pinned_root_dir(pinned_version: &PackageVersion, config: &Config) -> PathBuf {
+        config.lua_dir().cloned().unwrap_or_else(|| {
+            config
+                .data_dir()
+                .join(".lua")
+                .join(pinned_version.to_modrev_string())
+        })
+    }
+
     fn root_dir(version: &LuaVersion, config: &Config) -> PathBuf {
         if let Some(lua_dir) = config.lua_dir() {
             return lua_dir.clone();
@@ -279,6 +508,7 @@ impl HasVariables for LuaInstallation {
                 .ok())
                 .map(|lua| format_path(&lua)),
             "LUALIB" => self.lua_lib().or(Some("".into())),
+            "LUA_VERSION" => Some(self.version.version_compatibility_str()),
             _ => None,
         })
     }
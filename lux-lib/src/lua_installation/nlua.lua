@@ -0,0 +1,37 @@
+-- A Lua interpreter shim, which runs Lua with Neovim's LuaJIT.
+-- Installed by Lux and invoked with `nvim -l`. Do not edit.
+--
+-- Supports a subset of the `lua` CLI: `nlua [-e chunk]... [-v] [script [args]]`
+
+local argv = _G.arg or {}
+local i = 1
+while i <= #argv do
+  local flag = argv[i]
+  if flag == "-e" then
+    i = i + 1
+    local chunk = assert(loadstring(assert(argv[i], "'-e' needs argument"), "=(command line)"))
+    chunk()
+  elseif flag == "-v" then
+    print(_VERSION .. " (" .. (jit and jit.version or "Neovim") .. ")")
+  elseif flag == "--" then
+    i = i + 1
+    break
+  else
+    break
+  end
+  i = i + 1
+end
+
+local script = argv[i]
+if script == nil then
+  os.exit(0)
+end
+
+local script_args = { [0] = script }
+for j = i + 1, #argv do
+  script_args[#script_args + 1] = argv[j]
+end
+_G.arg = script_args
+
+local chunk = assert(loadfile(script))
+chunk(unpack(script_args))
@@ -0,0 +1,148 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+use which::which;
+
+use crate::{config::Config, tree::Tree};
+
+/// The Lua script that implements the `lua` CLI for Neovim.
+const NLUA_DRIVER: &str = include_str!("nlua.lua");
+
+#[cfg(target_family = "unix")]
+const NLUA_EXE: &str = "nlua";
+#[cfg(target_family = "windows")]
+const NLUA_EXE: &str = "nlua.bat";
+
+/// The `NVIM_APPNAME` of the Neovim instances that run the tests,
+/// so that they don't load the user's Neovim config or share its data and state.
+pub(crate) const NVIM_APPNAME: &str = "lux-nlua";
+
+#[derive(Error, Debug)]
+pub enum NluaError {
+    #[error(
+        "the busted-nlua test backend requires Neovim, but `{0}` could not be found.\n\
+        Make sure Neovim is installed and on the PATH, or set the `NVIM` variable in your config."
+    )]
+    NeovimNotFound(String),
+    #[error("error installing nlua: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Locate the Neovim binary, prioritising the `NVIM` variable in the config.
+pub(crate) fn neovim_binary(config: &Config) -> Result<PathBuf, NluaError> {
+    let nvim = config
+        .variables()
+        .get("NVIM")
+        .cloned()
+        .unwrap_or("nvim".into());
+    let nvim_path = PathBuf::from(&nvim);
+    if nvim_path.is_file() {
+        Ok(nvim_path)
+    } else {
+        which(&nvim).map_err(|_| NluaError::NeovimNotFound(nvim))
+    }
+}
+
+/// Install an `nlua` executable, which runs Lua scripts with Neovim, into the tree.
+/// Unlike the `nlua` rock, this works on all platforms, because it doesn't rely on
+/// a shebang, and it knows where to find Neovim.
+/// Returns the path to the executable.
+pub(crate) fn install_nlua(tree: &Tree, config: &Config) -> Result<PathBuf, NluaError> {
+    let nvim = neovim_binary(config)?;
+    // We don't install into the tree's bin directory, where the `nlua` rock's wrapper would overwrite it.
+    let nlua_dir = tree.root().join("nlua");
+    std::fs::create_dir_all(&nlua_dir)?;
+    let driver = nlua_dir.join("nlua.lua");
+    std::fs::write(&driver, NLUA_DRIVER)?;
+    let nlua = nlua_dir.join(NLUA_EXE);
+    std::fs::write(&nlua, nlua_wrapper(&nvim, &driver))?;
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&nlua, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(nlua)
+}
+
+fn nlua_wrapper(nvim: &Path, driver: &Path) -> String {
+    #[cfg(target_family = "unix")]
+    let content = format!(
+        r#"#!/bin/sh
+
+exec "{0}" -l "{1}" "$@"
+"#,
+        nvim.display(),
+        driver.display(),
+    );
+    #[cfg(target_family = "windows")]
+    let content = format!(
+        r#"@echo off
+setlocal
+
+"{0}" -l "{1}" %*
+
+exit /b %ERRORLEVEL%
+"#,
+        nvim.display(),
+        driver.display(),
+    );
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::TempDir;
+    use tokio::process::Command;
+
+    use crate::config::{ConfigBuilder, LuaVersion};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn nlua_runs_lua_with_neovim() {
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        if neovim_binary(&config).is_err() {
+            println!("Skipping test because Neovim is not installed");
+            return;
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let tree = Tree::new(temp_dir.to_path_buf(), LuaVersion::Lua51, &config).unwrap();
+        let nlua = install_nlua(&tree, &config).unwrap();
+        let script = temp_dir.join("script.lua");
+        std::fs::write(
+            &script,
+            "assert(vim ~= nil)\nassert(arg[0]:match('script.lua$'))\nassert(arg[1] == 'foo')\nos.exit(tonumber(arg[2]))",
+        )
+        .unwrap();
+        let status = Command::new(&nlua)
+            .arg(&script)
+            .args(["foo", "3"])
+            .env("NVIM_APPNAME", NVIM_APPNAME)
+            .status()
+            .await
+            .unwrap();
+        assert_eq!(status.code(), Some(3));
+        let status = Command::new(&nlua)
+            .args(["-e", "assert(vim ~= nil)"])
+            .status()
+            .await
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn neovim_binary_not_found() {
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .variables(Some([("NVIM".into(), "lux-no-such-nvim".into())].into()))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            neovim_binary(&config),
+            Err(NluaError::NeovimNotFound(nvim)) if nvim == "lux-no-such-nvim"
+        ));
+    }
+}
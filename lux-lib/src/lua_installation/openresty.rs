@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+use which::which;
+
+use crate::{
+    build::external_dependency::ExternalDependencyInfo,
+    config::{Config, LuaVersion},
+};
+
+use super::{find_lua_executable, get_lua_lib_name, LuaInstallation};
+
+/// The prefix that OpenResty is installed to by default.
+const DEFAULT_PREFIX: &str = "/usr/local/openresty";
+
+/// An [OpenResty](https://openresty.org) installation,
+/// which bundles LuaJIT with nginx and its own Lua libraries.
+#[derive(Debug, Clone)]
+pub(crate) struct OpenResty {
+    prefix: PathBuf,
+}
+
+impl OpenResty {
+    /// Detect an OpenResty installation, prioritising the `OPENRESTY_PREFIX` variable in the config,
+    /// followed by the prefix of the `resty` or `openresty` executable on the PATH
+    /// and the default prefix.
+    pub(crate) fn detect(config: &Config) -> Option<Self> {
+        config
+            .variables()
+            .get("OPENRESTY_PREFIX")
+            .map(PathBuf::from)
+            .or_else(|| {
+                ["resty", "openresty"]
+                    .into_iter()
+                    .filter_map(|exe| which(exe).ok())
+                    // Distribution packages symlink the executables into /usr/bin
+                    .filter_map(|exe| exe.canonicalize().ok())
+                    .find_map(|exe| Some(exe.parent()?.parent()?.to_path_buf()))
+            })
+            .or_else(|| Some(PathBuf::from(DEFAULT_PREFIX)))
+            .filter(|prefix| prefix.join("luajit").is_dir())
+            .map(|prefix| Self { prefix })
+    }
+
+    /// The `resty` script, which runs Lua scripts in a headless nginx.
+    pub(crate) fn resty(&self) -> PathBuf {
+        self.prefix.join("bin").join("resty")
+    }
+
+    /// The directories containing OpenResty's Lua libraries, like `resty.core`.
+    /// Lua modules and C modules are installed side by side.
+    pub(crate) fn lualib_dirs(&self) -> Vec<PathBuf> {
+        vec![
+            self.prefix.join("site").join("lualib"),
+            self.prefix.join("lualib"),
+        ]
+    }
+
+    /// OpenResty's LuaJIT, which C modules can be built against.
+    pub(crate) fn lua_installation(&self, version: &LuaVersion) -> Option<LuaInstallation> {
+        if !matches!(version, LuaVersion::LuaJIT) {
+            return None;
+        }
+        let luajit_dir = self.prefix.join("luajit");
+        // The headers are in a versioned directory, e.g. include/luajit-2.1
+        let include_dir = find_luajit_include_dir(&luajit_dir.join("include"))?;
+        let lib_dir = luajit_dir.join("lib");
+        let lib_name = get_lua_lib_name(&lib_dir, version)?;
+        let bin_dir = luajit_dir.join("bin");
+        let bin = find_lua_executable(&bin_dir);
+        Some(LuaInstallation {
+            version: version.clone(),
+            dependency_info: ExternalDependencyInfo {
+                include_dir: Some(include_dir),
+                lib_dir: Some(lib_dir),
+                bin_dir: Some(bin_dir).filter(|dir| dir.is_dir()),
+                lib_info: None,
+                lib_name: Some(lib_name),
+            },
+            bin,
+            headers_only: false,
+        })
+    }
+}
+
+fn find_luajit_include_dir(include_root: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(include_root)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|dir| {
+            dir.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("luajit"))
+                && dir.join("lua.h").is_file()
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use assert_fs::{prelude::*, TempDir};
+
+    use crate::{build::utils::c_lib_extension, config::ConfigBuilder};
+
+    use super::*;
+
+    #[test]
+    fn detect_openresty_prefix() {
+        let prefix = TempDir::new().unwrap();
+        prefix
+            .child("luajit/include/luajit-2.1/lua.h")
+            .write_str("")
+            .unwrap();
+        prefix
+            .child(format!("luajit/lib/libluajit-5.1.{}", c_lib_extension()))
+            .write_str("")
+            .unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .variables(Some(HashMap::from([(
+                "OPENRESTY_PREFIX".into(),
+                prefix.path().to_string_lossy().to_string(),
+            )])))
+            .build()
+            .unwrap();
+        let openresty = OpenResty::detect(&config).unwrap();
+        assert_eq!(openresty.resty(), prefix.join("bin").join("resty"));
+        assert_eq!(
+            openresty.lualib_dirs(),
+            vec![prefix.join("site").join("lualib"), prefix.join("lualib")]
+        );
+        assert!(openresty.lua_installation(&LuaVersion::Lua51).is_none());
+        let lua = openresty.lua_installation(&LuaVersion::LuaJIT).unwrap();
+        assert_eq!(
+            lua.includes(),
+            vec![&prefix.join("luajit").join("include").join("luajit-2.1")]
+        );
+    }
+}
@@ -1,14 +1,18 @@
 use itertools::Itertools;
 use mlua::{IntoLua, UserData};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{de, Deserialize, Deserializer};
-use std::{collections::HashMap, convert::Infallible, fmt::Display, path::PathBuf, str::FromStr};
+use std::{
+    borrow::Cow, collections::HashMap, convert::Infallible, fmt::Display, path::PathBuf,
+    str::FromStr,
+};
 use thiserror::Error;
 
 use crate::{
     build::utils::c_dylib_extension,
     lua_rockspec::{
-        deserialize_vec_from_lua_array_or_string, DisplayAsLuaValue, FromPlatformOverridable,
-        PartialOverride, PerPlatform, PlatformOverridable,
+        deserialize_vec_from_lua_array_or_string, lua_array_or_string_schema, DisplayAsLuaValue,
+        FromPlatformOverridable, PartialOverride, PerPlatform, PlatformOverridable,
     },
 };
 
@@ -179,6 +183,23 @@ impl<'de> Deserialize<'de> for ModuleSpecInternal {
     }
 }
 
+impl JsonSchema for ModuleSpecInternal {
+    fn schema_name() -> Cow<'static, str> {
+        "ModuleSpec".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "The source file of a module, its C source files, or how to build it from C sources",
+            "anyOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } },
+                generator.subschema_for::<ModulePathsInternal>(),
+            ],
+        })
+    }
+}
+
 impl DisplayAsLuaValue for ModuleSpecInternal {
     fn display_lua_value(&self) -> DisplayLuaValue {
         match self {
@@ -230,9 +251,12 @@ impl PartialOverride for ModuleSpecInternal {
             (ModuleSpecInternal::SourcePaths(_), b @ ModuleSpecInternal::SourcePaths(_)) => {
                 Ok(b.to_owned())
             }
-            (ModuleSpecInternal::ModulePaths(a), ModuleSpecInternal::ModulePaths(b)) => Ok(
-                ModuleSpecInternal::ModulePaths(a.apply_overrides(b).unwrap()),
-            ),
+            (
+                ModuleSpecInternal::ModulePaths(override_paths),
+                ModuleSpecInternal::ModulePaths(base_paths),
+            ) => Ok(ModuleSpecInternal::ModulePaths(
+                base_paths.apply_overrides(override_paths).unwrap(),
+            )),
             _ => Err(ModuleSpecAmbiguousPlatformOverride),
         }
     }
@@ -315,17 +339,23 @@ impl<'de> Deserialize<'de> for ModulePaths {
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize, Clone, Default)]
+#[derive(Debug, PartialEq, Deserialize, JsonSchema, Clone, Default)]
+#[schemars(rename = "ModulePaths")]
 pub struct ModulePathsInternal {
     #[serde(default, deserialize_with = "deserialize_vec_from_lua_array_or_string")]
+    #[schemars(schema_with = "lua_array_or_string_schema")]
     pub sources: Vec<PathBuf>,
     #[serde(default, deserialize_with = "deserialize_vec_from_lua_array_or_string")]
+    #[schemars(schema_with = "lua_array_or_string_schema")]
     pub libraries: Vec<PathBuf>,
     #[serde(default, deserialize_with = "deserialize_definitions")]
+    #[schemars(schema_with = "lua_array_or_string_schema")]
     pub defines: Vec<(String, Option<String>)>,
     #[serde(default, deserialize_with = "deserialize_vec_from_lua_array_or_string")]
+    #[schemars(schema_with = "lua_array_or_string_schema")]
     pub incdirs: Vec<PathBuf>,
     #[serde(default, deserialize_with = "deserialize_vec_from_lua_array_or_string")]
+    #[schemars(schema_with = "lua_array_or_string_schema")]
     pub libdirs: Vec<PathBuf>,
 }
 
@@ -0,0 +1,63 @@
+//! Quirks of the luarocks build that legacy rockspecs rely on,
+//! even though they are not part of the rockspec format.
+//!
+//! Each quirk maps an off-spec build table onto the equivalent spec-compliant one,
+//! so that the build backends don't need to know about them.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::{de, Deserialize, Deserializer};
+
+use super::{BuildSpecInternal, BuildType, LuaTableKey};
+
+/// Normalise a `build` table before it is converted to a [`super::BuildSpec`].
+pub(super) fn apply_quirks(internal: BuildSpecInternal) -> BuildSpecInternal {
+    skip_make_without_passes(internal)
+}
+
+/// Some legacy rockspecs use `type = "make"` with both `build_pass` and `install_pass`
+/// disabled, as a way to only copy the files listed in the `install` table.
+/// luarocks never invokes `make` in that case, so we treat it like `type = "none"`,
+/// which doesn't require `make` to be installed or the `Makefile` to exist.
+fn skip_make_without_passes(internal: BuildSpecInternal) -> BuildSpecInternal {
+    if internal.build_type == Some(BuildType::Make)
+        && internal.build_pass == Some(false)
+        && internal.install_pass == Some(false)
+    {
+        BuildSpecInternal {
+            build_type: Some(BuildType::None),
+            ..internal
+        }
+    } else {
+        internal
+    }
+}
+
+/// luarocks allows `install.conf` to be a list,
+/// in which case each file is installed under its file name.
+pub(super) fn deserialize_conf_path_map<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, PathBuf>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::<LuaTableKey, PathBuf>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, value)| {
+            let key = match key {
+                LuaTableKey::IntKey(_) => value
+                    .file_name()
+                    .ok_or_else(|| {
+                        de::Error::custom(format!(
+                            "unable to determine the file name of {}",
+                            value.display()
+                        ))
+                    })?
+                    .to_string_lossy()
+                    .to_string(),
+                LuaTableKey::StringKey(key) => key,
+            };
+            Ok((key, value))
+        })
+        .collect()
+}
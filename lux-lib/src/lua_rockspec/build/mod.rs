@@ -1,5 +1,6 @@
 mod builtin;
 mod cmake;
+mod luarocks_compat;
 mod make;
 mod rust_mlua;
 mod tree_sitter;
@@ -20,12 +21,16 @@ use itertools::Itertools;
 
 use mlua::{FromLua, IntoLua, Lua, LuaSerdeExt, UserData, Value};
 use std::{
-    collections::HashMap, env::consts::DLL_EXTENSION, fmt::Display, path::PathBuf, str::FromStr,
+    borrow::Cow, collections::HashMap, env::consts::DLL_EXTENSION, fmt::Display, path::PathBuf,
+    str::FromStr,
 };
 use thiserror::Error;
 
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{de, de::IntoDeserializer, Deserialize, Deserializer};
 
+use crate::config::toolchain::Toolchain;
+
 use super::{
     mlua_json_value_to_vec, DisplayAsLuaKV, DisplayAsLuaValue, DisplayLuaKV, DisplayLuaValue,
     LuaTableKey, PartialOverride, PerPlatform, PlatformIdentifier,
@@ -49,6 +54,9 @@ pub struct BuildSpec {
     // NOTE: This cannot be a diffy::Patch<'a, str>
     // because Lua::from_value requires a DeserializeOwned
     pub patches: HashMap<PathBuf, String>,
+    /// The compiler toolchain to build C modules with, from `[build.toolchain]` in the lux.toml.
+    /// Generated rockspecs leave it out, so luarocks builds with its own compiler settings.
+    pub toolchain: Option<Toolchain>,
 }
 
 impl Default for BuildSpec {
@@ -58,6 +66,7 @@ impl Default for BuildSpec {
             install: InstallSpec::default(),
             copy_directories: Vec::default(),
             patches: HashMap::default(),
+            toolchain: None,
         }
     }
 }
@@ -89,12 +98,15 @@ pub enum BuildSpecInternalError {
     ModulePathsMissingSources(#[from] ModulePathsMissingSources),
     #[error(transparent)]
     ParseLuaModuleError(#[from] ParseLuaModuleError),
+    #[error(transparent)]
+    AmbiguousPlatformOverride(#[from] ModuleSpecAmbiguousPlatformOverride),
 }
 
 impl BuildSpec {
     pub(crate) fn from_internal_spec(
         internal: BuildSpecInternal,
     ) -> Result<Self, BuildSpecInternalError> {
+        let internal = luarocks_compat::apply_quirks(internal);
         let build_backend = match internal.build_type.unwrap_or_default() {
             BuildType::Builtin => Some(BuildBackendSpec::Builtin(BuiltinBuildSpec {
                 modules: internal
@@ -187,6 +199,7 @@ impl BuildSpec {
                         .ok_or(BuildSpecInternalError::NoTreesitterParserLanguageSpecified)?,
                     parser: internal.parser.unwrap_or(false),
                     generate: internal.generate.unwrap_or(false),
+                    generate_from_grammar: internal.generate_from_grammar.unwrap_or(false),
                     location: internal.location,
                     queries: internal.queries.unwrap_or_default(),
                 },
@@ -198,6 +211,7 @@ impl BuildSpec {
             install: internal.install.unwrap_or_default(),
             copy_directories: internal.copy_directories.unwrap_or_default(),
             patches: internal.patches.unwrap_or_default(),
+            toolchain: internal.toolchain,
         })
     }
 }
@@ -338,16 +352,21 @@ impl LuaPathBufTable {
 /// to indicate which subdirectory the file should be copied to.
 /// For example, build.install.lua = {["foo.bar"] = {"src/bar.lua"}} will copy src/bar.lua
 /// to the foo directory under the rock's Lua files directory.
-#[derive(Debug, PartialEq, Default, Deserialize, Clone)]
+#[derive(Debug, PartialEq, Default, Deserialize, JsonSchema, Clone)]
 pub struct InstallSpec {
     /// Lua modules written in Lua.
     #[serde(default, deserialize_with = "deserialize_module_path_map")]
+    #[schemars(with = "HashMap<String, PathBuf>")]
     pub lua: HashMap<LuaModule, PathBuf>,
     /// Dynamic libraries implemented compiled Lua modules.
     #[serde(default, deserialize_with = "deserialize_module_path_map")]
+    #[schemars(with = "HashMap<String, PathBuf>")]
     pub lib: HashMap<LuaModule, PathBuf>,
     /// Configuration files.
-    #[serde(default)]
+    #[serde(
+        default,
+        deserialize_with = "luarocks_compat::deserialize_conf_path_map"
+    )]
     pub conf: HashMap<String, PathBuf>,
     /// Lua command-line scripts.
     // TODO(vhyrro): The String component should be checked to ensure that it consists of a single
@@ -476,11 +495,13 @@ impl DisplayAsLuaKV for InstallSpec {
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize, Default, Clone)]
+#[derive(Debug, PartialEq, Deserialize, JsonSchema, Default, Clone)]
+#[schemars(rename = "BuildSpec")]
 pub(crate) struct BuildSpecInternal {
     #[serde(rename = "type", default)]
     pub(crate) build_type: Option<BuildType>,
     #[serde(rename = "modules", default)]
+    #[schemars(with = "Option<HashMap<String, ModuleSpecInternal>>")]
     pub(crate) builtin_spec: Option<HashMap<LuaTableKey, ModuleSpecInternal>>,
     #[serde(default)]
     pub(crate) makefile: Option<PathBuf>,
@@ -516,6 +537,7 @@ pub(crate) struct BuildSpecInternal {
     #[serde(default)]
     pub(crate) default_features: Option<bool>,
     #[serde(default)]
+    #[schemars(with = "Option<HashMap<String, PathBuf>>")]
     pub(crate) include: Option<HashMap<LuaTableKey, PathBuf>>,
     #[serde(default)]
     pub(crate) features: Option<Vec<String>>,
@@ -527,9 +549,34 @@ pub(crate) struct BuildSpecInternal {
     #[serde(default)]
     pub(crate) generate: Option<bool>,
     #[serde(default)]
+    pub(crate) generate_from_grammar: Option<bool>,
+    #[serde(default)]
     pub(crate) location: Option<PathBuf>,
     #[serde(default)]
-    pub(crate) queries: Option<HashMap<PathBuf, String>>,
+    pub(crate) queries: Option<HashMap<PathBuf, String>>, // Lux extensions
+    #[serde(default)]
+    pub(crate) toolchain: Option<Toolchain>,
+    /// Per-platform overrides, e.g. `[build.platforms.windows]`.
+    /// Only used in a `lux.toml`, as rockspec platform overrides are extracted when decoding them.
+    #[serde(default)]
+    #[schemars(with = "Option<HashMap<String, BuildSpecInternal>>")]
+    pub(crate) platforms: Option<HashMap<PlatformIdentifier, BuildSpecInternal>>,
+}
+
+impl BuildSpecInternal {
+    /// Apply the `platforms` overrides to the base spec for each platform.
+    pub(crate) fn into_per_platform(
+        mut self,
+    ) -> Result<PerPlatform<BuildSpec>, BuildSpecInternalError> {
+        let mut per_platform = self.platforms.take().unwrap_or_default();
+        override_platform_specs(&mut per_platform, &self)?;
+        PerPlatform {
+            default: self,
+            per_platform,
+        }
+        .map(|internal| BuildSpec::from_internal_spec(internal.clone()))
+        .transpose()
+    }
 }
 
 impl FromLua for PerPlatform<BuildSpecInternal> {
@@ -573,16 +620,18 @@ fn override_platform_specs(
         // Add base dependencies for each platform
         per_platform.insert(platform, override_build_spec_internal(base, &build_spec)?);
     }
-    for (platform, build_spec) in per_platform_raw {
+    for (platform, build_spec) in &per_platform_raw {
         for extended_platform in &platform.get_extended_platforms() {
             let extended_spec = per_platform
                 .get(extended_platform)
                 .unwrap_or(&base.to_owned())
                 .to_owned();
-            per_platform.insert(
-                extended_platform.to_owned(),
-                override_build_spec_internal(&extended_spec, &build_spec)?,
-            );
+            let mut overridden = override_build_spec_internal(&extended_spec, build_spec)?;
+            // The extended platform's own overrides are more specific.
+            if let Some(extended_override) = per_platform_raw.get(extended_platform) {
+                overridden = override_build_spec_internal(&overridden, extended_override)?;
+            }
+            per_platform.insert(extended_platform.to_owned(), overridden);
         }
     }
     Ok(())
@@ -636,7 +685,7 @@ fn override_build_spec_internal(
         ),
         make_install_variables: merge_map_opts(
             &override_spec.make_install_variables,
-            &base.make_build_variables,
+            &base.make_install_variables,
         ),
         variables: merge_map_opts(&override_spec.variables, &base.variables),
         cmake_lists_content: override_opt(
@@ -667,8 +716,19 @@ fn override_build_spec_internal(
         lang: override_opt(&override_spec.lang, &base.lang),
         parser: override_opt(&override_spec.parser, &base.parser),
         generate: override_opt(&override_spec.generate, &base.generate),
+        generate_from_grammar: override_opt(
+            &override_spec.generate_from_grammar,
+            &base.generate_from_grammar,
+        ),
         location: override_opt(&override_spec.location, &base.location),
         queries: merge_map_opts(&override_spec.queries, &base.queries),
+        toolchain: match (&base.toolchain, &override_spec.toolchain) {
+            (Some(base), Some(override_toolchain)) => {
+                Some(base.apply_overrides(override_toolchain))
+            }
+            (base, override_toolchain) => override_toolchain.clone().or(base.clone()),
+        },
+        platforms: None,
     })
 }
 
@@ -904,6 +964,12 @@ impl DisplayAsLuaKV for BuildSpecInternal {
                 value: DisplayLuaValue::Boolean(*generate),
             });
         }
+        if let Some(generate_from_grammar) = &self.generate_from_grammar {
+            result.push(DisplayLuaKV {
+                key: "generate_from_grammar".to_string(),
+                value: DisplayLuaValue::Boolean(*generate_from_grammar),
+            });
+        }
         if let Some(location) = &self.location {
             result.push(DisplayLuaKV {
                 key: "location".to_string(),
@@ -924,6 +990,21 @@ impl DisplayAsLuaKV for BuildSpecInternal {
                 ),
             });
         }
+        if let Some(platforms) = &self.platforms {
+            result.push(DisplayLuaKV {
+                key: "platforms".to_string(),
+                value: DisplayLuaValue::Table(
+                    platforms
+                        .iter()
+                        .sorted_by_key(|(platform, _)| platform.to_string())
+                        .map(|(platform, spec)| DisplayLuaKV {
+                            key: platform.to_string(),
+                            value: spec.display_lua().value,
+                        })
+                        .collect(),
+                ),
+            });
+        }
 
         DisplayLuaKV {
             key: "build".to_string(),
@@ -974,6 +1055,33 @@ impl<'de> Deserialize<'de> for BuildType {
     }
 }
 
+impl JsonSchema for BuildType {
+    fn schema_name() -> Cow<'static, str> {
+        "BuildType".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "The build backend, or the name of a rock that provides one",
+            "anyOf": [
+                {
+                    "enum": [
+                        "builtin",
+                        "make",
+                        "cmake",
+                        "command",
+                        "none",
+                        "rust-mlua",
+                        "treesitter-parser",
+                        "source",
+                    ],
+                },
+                { "type": "string" },
+            ],
+        })
+    }
+}
+
 impl Display for BuildType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
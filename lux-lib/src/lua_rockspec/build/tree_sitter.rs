@@ -14,6 +14,10 @@ pub struct TreesitterParserBuildSpec {
     /// Must the sources be generated?
     pub generate: bool,
 
+    /// Generate the sources from `grammar.js` using the `tree-sitter` CLI,
+    /// which must be provided by a build dependency or be on the `PATH`.
+    pub generate_from_grammar: bool,
+
     /// tree-sitter grammar's location (relative to the source root)
     pub location: Option<PathBuf>,
 
@@ -26,6 +30,9 @@ impl UserData for TreesitterParserBuildSpec {
         methods.add_method("lang", |_, this, _: ()| Ok(this.lang.clone()));
         methods.add_method("parser", |_, this, _: ()| Ok(this.parser));
         methods.add_method("generate", |_, this, _: ()| Ok(this.generate));
+        methods.add_method("generate_from_grammar", |_, this, _: ()| {
+            Ok(this.generate_from_grammar)
+        });
         methods.add_method("location", |_, this, _: ()| Ok(this.location.clone()));
         methods.add_method("queries", |_, this, _: ()| Ok(this.queries.clone()));
     }
@@ -2,6 +2,7 @@ use std::{collections::HashMap, convert::Infallible, path::PathBuf};
 
 use mlua::{FromLua, IntoLua};
 use path_slash::PathBufExt;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use super::{
@@ -10,7 +11,7 @@ use super::{
 };
 
 /// Can be defined in a [platform-agnostic](https://github.com/luarocks/luarocks/wiki/platform-agnostic-external-dependencies) manner
-#[derive(Debug, PartialEq, Clone, Deserialize, Default)]
+#[derive(Debug, PartialEq, Clone, Deserialize, JsonSchema, Default)]
 pub struct ExternalDependencySpec {
     /// A header file, e.g. "foo.h"
     pub(crate) header: Option<PathBuf>,
@@ -1,5 +1,6 @@
 use std::convert::Infallible;
 
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::lua_rockspec::{DisplayAsLuaKV, DisplayLuaKV, DisplayLuaValue};
@@ -9,19 +10,25 @@ use super::{PartialOverride, PerPlatform, PlatformOverridable};
 /// An undocumented part of the rockspec format.
 ///
 /// Specifies additional install options
-#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, JsonSchema)]
 pub struct DeploySpec {
     /// Whether to wrap installed Lua bin scripts to be executed with
     /// the detected or configured Lua installation.
     /// Defaults to `true`.
     #[serde(default = "default_wrap_bin_scripts")]
     pub wrap_bin_scripts: bool,
+    /// Whether to generate a Vim help `tags` file for the `*.txt`
+    /// help files installed in the rock's `doc` directory.
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub generate_helptags: bool,
 }
 
 impl Default for DeploySpec {
     fn default() -> Self {
         Self {
             wrap_bin_scripts: true,
+            generate_helptags: false,
         }
     }
 }
@@ -32,6 +39,7 @@ impl PartialOverride for DeploySpec {
     fn apply_overrides(&self, override_spec: &Self) -> Result<Self, Self::Err> {
         Ok(Self {
             wrap_bin_scripts: override_spec.wrap_bin_scripts,
+            generate_helptags: override_spec.generate_helptags,
         })
     }
 }
@@ -50,12 +58,19 @@ impl PlatformOverridable for DeploySpec {
 
 impl DisplayAsLuaKV for DeploySpec {
     fn display_lua(&self) -> DisplayLuaKV {
+        let mut result = vec![DisplayLuaKV {
+            key: "wrap_bin_scripts".to_string(),
+            value: DisplayLuaValue::Boolean(self.wrap_bin_scripts),
+        }];
+        if self.generate_helptags {
+            result.push(DisplayLuaKV {
+                key: "generate_helptags".to_string(),
+                value: DisplayLuaValue::Boolean(self.generate_helptags),
+            });
+        }
         DisplayLuaKV {
             key: "deploy".to_string(),
-            value: DisplayLuaValue::Table(vec![DisplayLuaKV {
-                key: "wrap_bin_scripts".to_string(),
-                value: DisplayLuaValue::Boolean(self.wrap_bin_scripts),
-            }]),
+            value: DisplayLuaValue::Table(result),
         }
     }
 }
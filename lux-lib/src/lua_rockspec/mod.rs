@@ -8,10 +8,12 @@ mod serde_util;
 mod test_spec;
 
 use std::{
-    collections::HashMap, convert::Infallible, fmt::Display, io, path::PathBuf, str::FromStr,
+    borrow::Cow, collections::HashMap, convert::Infallible, fmt::Display, io, path::PathBuf,
+    str::FromStr,
 };
 
 use mlua::{FromLua, IntoLua, Lua, LuaSerdeExt, UserData, Value};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 pub use build::*;
@@ -31,6 +33,7 @@ use crate::{
     config::{LuaVersion, LuaVersionUnset},
     hash::HasIntegrity,
     package::{PackageName, PackageSpec, PackageVersion, PackageVersionReq},
+    progress::ProgressMode,
     project::project_toml::ProjectTomlError,
     project::ProjectRoot,
     rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
@@ -192,6 +195,21 @@ impl LocalLuaRockspec {
                 )));
             }
         }
+
+        if let Some(format) = rockspec
+            .format()
+            .as_ref()
+            .filter(|format| format.is_unknown())
+        {
+            if ProgressMode::current() != ProgressMode::Quiet {
+                eprintln!(
+                    "⚠️ WARNING: {}@{} uses rockspec format {format}, which is newer than the supported format {}. Fields that lux doesn't know about are ignored.",
+                    rockspec.package(),
+                    rockspec.version(),
+                    RockspecFormat::LATEST,
+                );
+            }
+        }
         Ok(rockspec)
     }
 }
@@ -342,6 +360,35 @@ impl RemoteLuaRockspec {
         Ok(rockspec)
     }
 
+    /// The rockspec's fields as JSON, as they are declared in the rockspec.
+    pub fn to_json(&self) -> Result<serde_json::Value, LuaRockspecError> {
+        let lua = Lua::new();
+        lua.load(&self.local.raw_content).exec()?;
+        let globals = lua.globals();
+        let mut fields = serde_json::Map::new();
+        for field in [
+            "rockspec_format",
+            "package",
+            "version",
+            "description",
+            "supported_platforms",
+            "dependencies",
+            "build_dependencies",
+            "external_dependencies",
+            "test_dependencies",
+            "source",
+            "build",
+            "test",
+            "deploy",
+        ] {
+            let value: Value = globals.get(field)?;
+            if !value.is_nil() {
+                fields.insert(field.into(), lua.from_value(value)?);
+            }
+        }
+        Ok(serde_json::Value::Object(fields))
+    }
+
     pub fn from_package_and_source_spec(
         package_spec: PackageSpec,
         source_spec: RockSourceSpec,
@@ -381,6 +428,7 @@ build = {{
                 install: InstallSpec::default(),
                 copy_directories: Vec::new(),
                 patches: HashMap::new(),
+                toolchain: None,
             }),
             source: PerPlatform::new(source.clone()),
             test: PerPlatform::default(),
@@ -425,13 +473,16 @@ impl Rockspec for RemoteLuaRockspec {
         match self.format() {
             // Rockspec formats < 3.0 don't support `build_dependencies`,
             // so we have to return regular dependencies if the build backend might need to use them.
-            Some(RockspecFormat::_1_0 | RockspecFormat::_2_0)
-                if self
-                    .build()
-                    .current_platform()
-                    .build_backend
-                    .as_ref()
-                    .is_some_and(|build_backend| build_backend.can_use_build_dependencies()) =>
+            Some(format)
+                if *format < RockspecFormat::_3_0
+                    && self
+                        .build()
+                        .current_platform()
+                        .build_backend
+                        .as_ref()
+                        .is_some_and(|build_backend| {
+                            build_backend.can_use_build_dependencies()
+                        }) =>
             {
                 self.local.dependencies()
             }
@@ -502,7 +553,7 @@ impl HasIntegrity for RemoteLuaRockspec {
     }
 }
 
-#[derive(Clone, Deserialize, Debug, PartialEq, Default)]
+#[derive(Clone, Deserialize, JsonSchema, Debug, PartialEq, Default)]
 pub struct RockDescription {
     /// A one-line description of the package.
     pub summary: Option<String>,
@@ -609,14 +660,46 @@ impl UserData for RockDescription {
 #[error("invalid rockspec format: {0}")]
 pub struct InvalidRockspecFormat(String);
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum RockspecFormat {
-    #[serde(rename = "1.0")]
     _1_0,
-    #[serde(rename = "2.0")]
     _2_0,
-    #[serde(rename = "3.0")]
     _3_0,
+    /// A newer minor version of a known format, e.g. `3.1`.
+    /// Lux parses the fields it knows about and ignores any others,
+    /// which luarocks guarantees to be backward compatible within a major version.
+    Unknown(String),
+}
+
+impl RockspecFormat {
+    /// The newest rockspec format that lux fully supports.
+    pub const LATEST: Self = Self::_3_0;
+
+    /// Whether this is a newer format than lux supports.
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Unknown(_))
+    }
+
+    /// The `(major, minor)` version of the format.
+    fn version(&self) -> (u64, u64) {
+        match self {
+            Self::_1_0 => (1, 0),
+            Self::_2_0 => (2, 0),
+            Self::_3_0 => (3, 0),
+            Self::Unknown(format) => parse_format_version(format).unwrap_or_default(),
+        }
+    }
+}
+
+fn parse_format_version(format: &str) -> Option<(u64, u64)> {
+    let (major, minor) = format.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+impl PartialOrd for RockspecFormat {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.version().cmp(&other.version()))
+    }
 }
 
 impl FromStr for RockspecFormat {
@@ -627,11 +710,54 @@ impl FromStr for RockspecFormat {
             "1.0" => Ok(Self::_1_0),
             "2.0" => Ok(Self::_2_0),
             "3.0" => Ok(Self::_3_0),
-            txt => Err(InvalidRockspecFormat(txt.to_string())),
+            // Minor versions are backward compatible, so we accept unknown minor versions
+            // of known major versions, ignoring any fields we don't know about.
+            txt => match parse_format_version(txt) {
+                Some((major, _)) if (1..=Self::LATEST.version().0).contains(&major) => {
+                    Ok(Self::Unknown(txt.to_string()))
+                }
+                _ => Err(InvalidRockspecFormat(txt.to_string())),
+            },
         }
     }
 }
 
+impl Serialize for RockspecFormat {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for RockspecFormat {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for RockspecFormat {
+    fn schema_name() -> Cow<'static, str> {
+        "RockspecFormat".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "The rockspec format version",
+            "anyOf": [
+                { "enum": ["1.0", "2.0", "3.0"] },
+                { "type": "string" },
+            ],
+        })
+    }
+}
+
 impl From<&str> for RockspecFormat {
     fn from(s: &str) -> Self {
         Self::from_str(s).unwrap()
@@ -660,6 +786,7 @@ impl Display for RockspecFormat {
             Self::_1_0 => write!(f, "1.0"),
             Self::_2_0 => write!(f, "2.0"),
             Self::_3_0 => write!(f, "3.0"),
+            Self::Unknown(format) => f.write_str(format),
         }
     }
 }
@@ -702,6 +829,24 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn rockspec_to_json() {
+        let rockspec_content = r#"
+        package = 'foo'
+        version = '1.0.0-1'
+        source = {
+            url = 'https://github.com/nvim-neorocks/rocks.nvim/archive/1.0.0/rocks.nvim.zip',
+        }
+        dependencies = { 'lua >= 5.1' }
+        "#;
+        let rockspec = RemoteLuaRockspec::new(rockspec_content).unwrap();
+        let json = rockspec.to_json().unwrap();
+        assert_eq!(json["package"], "foo");
+        assert_eq!(json["version"], "1.0.0-1");
+        assert_eq!(json["dependencies"][0], "lua >= 5.1");
+        assert!(json.get("build").is_none());
+    }
+
     #[tokio::test]
     pub async fn parse_rockspec() {
         let rockspec_content = "
@@ -1454,6 +1599,20 @@ mod tests {
         let rockspec = RemoteLuaRockspec::new(rockspec_content).unwrap();
         let deploy_spec = &rockspec.deploy().current_platform();
         assert!(!deploy_spec.wrap_bin_scripts);
+        assert!(!deploy_spec.generate_helptags);
+        let rockspec_content = "
+        rockspec_format = '1.0'\n
+        package = 'foo'\n
+        version = '1.0.0-1'\n
+        deploy = {\n
+            generate_helptags = true,\n
+        }\n
+        source = { url = 'git+https://hub.com/example-project/foo.zip' }\n
+        ";
+        let rockspec = RemoteLuaRockspec::new(rockspec_content).unwrap();
+        let deploy_spec = &rockspec.deploy().current_platform();
+        assert!(deploy_spec.wrap_bin_scripts);
+        assert!(deploy_spec.generate_helptags);
     }
 
     #[tokio::test]
@@ -1619,6 +1778,46 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    pub async fn treesitter_parser_rockspec() {
+        let rockspec_content = "
+    rockspec_format = '3.0'\n
+    package = 'tree-sitter-foo'\n
+    version = 'scm-1'\n
+    source = {\n
+        url = 'https://github.com/example/tree-sitter-foo/archive/1.0.0.zip',\n
+    }\n
+    build = {
+        type = 'treesitter-parser',
+        lang = 'foo',
+        parser = true,
+        generate_from_grammar = true,
+        queries = {
+            ['highlights.scm'] = '(identifier) @variable',
+        },
+    }
+            ";
+        let rockspec = RemoteLuaRockspec::new(rockspec_content).unwrap();
+        let build_spec = rockspec.local.build.current_platform();
+        if let Some(BuildBackendSpec::TreesitterParser(build_spec)) =
+            build_spec.build_backend.to_owned()
+        {
+            assert_eq!(build_spec.lang, "foo");
+            assert!(build_spec.parser);
+            assert!(!build_spec.generate);
+            assert!(build_spec.generate_from_grammar);
+            assert_eq!(
+                build_spec
+                    .queries
+                    .get(&PathBuf::from("highlights.scm"))
+                    .unwrap(),
+                "(identifier) @variable"
+            );
+        } else {
+            panic!("Expected TreesitterParser build backend");
+        }
+    }
+
     #[tokio::test]
     pub async fn regression_ltui() {
         let content =
@@ -1658,6 +1857,73 @@ mod tests {
         );
     }
 
+    fn read_luarocks_compat_rockspec(name: &str) -> RemoteLuaRockspec {
+        let content =
+            std::fs::read_to_string(PathBuf::from("resources/test/luarocks-compat").join(name))
+                .unwrap();
+        RemoteLuaRockspec::new(&content).unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn regression_luarocks_compat_install_only() {
+        let rockspec = read_luarocks_compat_rockspec("install-only-1.0-1.rockspec");
+        let build_spec = rockspec.build().current_platform();
+        assert_eq!(build_spec.build_backend, None);
+        assert_eq!(
+            build_spec.install.lua,
+            HashMap::from([
+                (
+                    "install_only".parse().unwrap(),
+                    PathBuf::from("src/install_only.lua")
+                ),
+                (
+                    "install_only.util".parse().unwrap(),
+                    PathBuf::from("src/util.lua")
+                ),
+            ])
+        );
+        assert_eq!(
+            build_spec.install.conf,
+            HashMap::from([(
+                "install-only.conf".into(),
+                PathBuf::from("config/install-only.conf")
+            )])
+        );
+        assert_eq!(
+            build_spec.install.bin,
+            HashMap::from([("install-only".into(), PathBuf::from("bin/install-only"))])
+        );
+    }
+
+    #[tokio::test]
+    pub async fn regression_luarocks_compat_make_without_passes() {
+        let rockspec = read_luarocks_compat_rockspec("make-no-passes-1.0-1.rockspec");
+        let build_spec = rockspec.build().current_platform();
+        assert_eq!(build_spec.build_backend, None);
+        assert_eq!(build_spec.install.lua.len(), 1);
+    }
+
+    #[tokio::test]
+    pub async fn regression_luarocks_compat_make_platform_variables() {
+        let rockspec = read_luarocks_compat_rockspec("make-platform-variables-1.0-1.rockspec");
+        match &rockspec.build().current_platform().build_backend {
+            Some(BuildBackendSpec::Make(make_spec)) => {
+                assert_eq!(
+                    make_spec.build_variables,
+                    HashMap::from([
+                        ("CFLAGS".into(), "$(CFLAGS)".into()),
+                        ("LIBFLAG".into(), "-shared".into()),
+                    ])
+                );
+                assert_eq!(
+                    make_spec.install_variables,
+                    HashMap::from([("INST_LUADIR".into(), "$(LUADIR)".into())])
+                );
+            }
+            _ => panic!("Expected Make build backend"),
+        }
+    }
+
     #[tokio::test]
     pub async fn regression_external_dependencies() {
         let content =
@@ -1707,4 +1973,39 @@ mod tests {
         assert_eq!(rockspec2.local.version, "1.0.5".parse().unwrap());
         assert_eq!(rockspec2.local.source, PerPlatform::new(source_spec.into()));
     }
+
+    #[tokio::test]
+    pub async fn parse_unknown_rockspec_format() {
+        let rockspec_content = "
+        rockspec_format = '3.1'\n
+        package = 'foo'\n
+        version = '1.0.0-1'\n
+        source = {\n
+            url = 'https://github.com/nvim-neorocks/rocks.nvim/archive/1.0.0/rocks.nvim.zip',\n
+        }\n
+        some_future_field = { foo = 'bar' }\n
+        "
+        .to_string();
+        let rockspec = RemoteLuaRockspec::new(&rockspec_content).unwrap();
+        assert_eq!(
+            rockspec.local.rockspec_format,
+            Some(RockspecFormat::Unknown("3.1".into()))
+        );
+        assert_eq!(rockspec.local.package, "foo".into());
+
+        let rockspec_content = rockspec_content.replace("'3.1'", "'4.0'");
+        let _rockspec = RemoteLuaRockspec::new(&rockspec_content).unwrap_err();
+
+        let format: RockspecFormat = "3.1".parse().unwrap();
+        assert!(format.is_unknown());
+        assert!(format > RockspecFormat::LATEST);
+        assert!(RockspecFormat::_2_0 < RockspecFormat::_3_0);
+        assert_eq!(format.to_string(), "3.1");
+        assert_eq!(
+            serde_json::from_value::<RockspecFormat>(serde_json::to_value(&format).unwrap())
+                .unwrap(),
+            format
+        );
+        assert!("foo".parse::<RockspecFormat>().is_err());
+    }
 }
@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 use itertools::Itertools;
+use schemars::{json_schema, Schema, SchemaGenerator};
 use serde::{de, Deserialize, Deserializer};
 use thiserror::Error;
 
@@ -28,6 +29,17 @@ impl<'de> Deserialize<'de> for LuaTableKey {
     }
 }
 
+/// The JSON schema of a value that is deserialized with
+/// [`deserialize_vec_from_lua_array_or_string`].
+pub(crate) fn lua_array_or_string_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "anyOf": [
+            { "type": "string" },
+            { "type": "array", "items": { "type": "string" } },
+        ],
+    })
+}
+
 /// Deserialize a json value into a Vec<T>, treating empty json objects as empty lists
 /// If the json value is a string, this returns a singleton vector containing that value.
 /// This is needed to be able to deserialise RockSpec tables that luarocks
@@ -141,6 +153,7 @@ impl Display for DisplayLuaKV {
             .key
             .chars()
             .all(|c| c == '_' || c.is_ascii_alphanumeric())
+            || self.key.starts_with(|c: char| c.is_ascii_digit())
         {
             write!(f, "['{}'] = {}", self.key, self.value)
         } else {
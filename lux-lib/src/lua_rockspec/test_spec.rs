@@ -2,9 +2,14 @@ use itertools::Itertools;
 use mlua::{FromLua, IntoLua, UserData};
 use path_slash::PathExt;
 use serde_enum_str::Serialize_enum_str;
-use std::{convert::Infallible, path::PathBuf};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer};
 
 use crate::{
@@ -19,11 +24,6 @@ use super::{
     PerPlatform, PerPlatformWrapper, PlatformOverridable,
 };
 
-#[cfg(target_family = "unix")]
-const NLUA_EXE: &str = "nlua";
-#[cfg(target_family = "windows")]
-const NLUA_EXE: &str = "nlua.bat";
-
 #[derive(Error, Debug)]
 pub enum TestSpecDecodeError {
     #[error("'command' test type must specify 'command' or 'script' field")]
@@ -109,17 +109,27 @@ impl ValidatedTestSpec {
         }
     }
 
-    pub(crate) fn test_config(&self, config: &Config) -> Result<Config, ConfigError> {
-        match self {
-            Self::BustedNlua(_) => {
+    /// The config to run the tests with.
+    /// For busted-nlua, `nlua` is the path to the `nlua` executable that runs Lua with Neovim.
+    pub(crate) fn test_config(
+        &self,
+        config: &Config,
+        nlua: Option<&Path>,
+    ) -> Result<Config, ConfigError> {
+        match (self, nlua) {
+            (Self::BustedNlua(_), Some(nlua)) => {
+                let mut variables = config.variables().clone();
+                variables.insert("LUA".to_string(), nlua.to_slash_lossy().to_string());
+                let config_builder: ConfigBuilder = config.clone().into();
+                Ok(config_builder
+                    .lua_version(Some(LuaVersion::Lua51))
+                    .variables(Some(variables))
+                    .build()?)
+            }
+            (Self::BustedNlua(_), None) => {
                 let config_builder: ConfigBuilder = config.clone().into();
                 Ok(config_builder
                     .lua_version(Some(LuaVersion::Lua51))
-                    .variables(Some(
-                        vec![("LUA".to_string(), NLUA_EXE.to_string())]
-                            .into_iter()
-                            .collect(),
-                    ))
                     .build()?)
             }
             _ => Ok(config.clone()),
@@ -129,10 +139,8 @@ impl ValidatedTestSpec {
     fn test_dependencies(&self) -> Vec<PackageReq> {
         match self {
             Self::Busted(_) => vec![PackageReq::new("busted".into(), None).unwrap()],
-            Self::BustedNlua(_) => vec![
-                PackageReq::new("busted".into(), None).unwrap(),
-                PackageReq::new("nlua".into(), None).unwrap(),
-            ],
+            // Lux installs its own nlua, see `lua_installation::nlua`
+            Self::BustedNlua(_) => vec![PackageReq::new("busted".into(), None).unwrap()],
             Self::Command(_) => Vec::new(),
             Self::LuaScript(_) => Vec::new(),
         }
@@ -167,6 +175,9 @@ impl FromPlatformOverridable<TestSpecInternal, Self> for TestSpec {
             Some(TestType::Busted) => Ok(Self::Busted(BustedTestSpec {
                 flags: internal.flags.unwrap_or_default(),
             })),
+            Some(TestType::BustedNlua) => Ok(Self::BustedNlua(BustedTestSpec {
+                flags: internal.flags.unwrap_or_default(),
+            })),
             Some(TestType::Command) => match (internal.command, internal.lua_script) {
                 (None, None) => Err(TestSpecDecodeError::NoCommandOrScript),
                 (None, Some(script)) => Ok(Self::Script(LuaScriptTestSpec {
@@ -244,14 +255,16 @@ impl UserData for LuaScriptTestSpec {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize_enum_str, PartialEq, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Serialize_enum_str, PartialEq, Clone)]
 #[serde(rename_all = "lowercase")]
 pub(crate) enum TestType {
     Busted,
+    #[serde(rename = "busted-nlua")]
+    BustedNlua,
     Command,
 }
 
-#[derive(Debug, PartialEq, Deserialize, Default, Clone)]
+#[derive(Debug, PartialEq, Deserialize, JsonSchema, Default, Clone)]
 pub(crate) struct TestSpecInternal {
     #[serde(default, rename = "type")]
     pub(crate) test_type: Option<TestType>,
@@ -261,6 +274,22 @@ pub(crate) struct TestSpecInternal {
     pub(crate) command: Option<String>,
     #[serde(default, rename = "script", alias = "lua_script")]
     pub(crate) lua_script: Option<PathBuf>,
+    /// The maximum time, in seconds, the test suite may take,
+    /// unless overridden with `lx test --timeout`.
+    #[serde(default)]
+    pub(crate) timeout: Option<u64>,
+    /// Run the tests in a scrubbed environment, with only the variables set by Lux.
+    /// Has no effect with `lx test --impure`.
+    #[serde(default)]
+    pub(crate) hermetic: Option<bool>,
+    /// The working directory of the tests, relative to the project root.
+    /// May contain layout variables, e.g. `$(LUADIR)`.
+    #[serde(default)]
+    pub(crate) cwd: Option<PathBuf>,
+    /// Environment variables to set when running the tests.
+    /// Their values may contain layout variables, e.g. `$(PREFIX)`.
+    #[serde(default)]
+    pub(crate) env: Option<HashMap<String, String>>,
 }
 
 impl PartialOverride for TestSpecInternal {
@@ -287,6 +316,16 @@ impl PartialOverride for TestSpecInternal {
                 Some(_) => None,
                 None => override_opt(&override_spec.lua_script, &self.lua_script),
             },
+            timeout: override_opt(&override_spec.timeout, &self.timeout),
+            hermetic: override_opt(&override_spec.hermetic, &self.hermetic),
+            cwd: override_opt(&override_spec.cwd, &self.cwd),
+            env: match (override_spec.env.clone(), self.env.clone()) {
+                (Some(override_env), Some(base_env)) => {
+                    Some(base_env.into_iter().chain(override_env).collect())
+                }
+                (None, base_env) => base_env,
+                (override_env, None) => override_env,
+            },
         })
     }
 }
@@ -400,6 +439,19 @@ mod tests {
             })
         );
         let lua_content = "
+        test = {\n
+            type = 'busted-nlua',\n
+        }\n
+        ";
+        let lua = Lua::new();
+        lua.load(lua_content).exec().unwrap();
+        let test_spec: PerPlatform<TestSpec> =
+            PerPlatform::from_lua(lua.globals().get("test").unwrap(), &lua).unwrap();
+        assert_eq!(
+            test_spec.default,
+            TestSpec::BustedNlua(BustedTestSpec::default())
+        );
+        let lua_content = "
         test = {\n
             type = 'command',\n
         }\n
@@ -11,7 +11,8 @@ use thiserror::Error;
 use crate::{
     build::{
         external_dependency::{ExternalDependencyError, ExternalDependencyInfo},
-        utils::recursive_copy_dir,
+        provenance::PROVENANCE_FILE_NAME,
+        utils::{make_pkg_config_files_relocatable, recursive_copy_dir},
         BuildBehaviour,
     },
     config::Config,
@@ -25,7 +26,7 @@ use crate::{
     progress::{Progress, ProgressBar},
     remote_package_source::RemotePackageSource,
     rockspec::Rockspec,
-    tree::{self, Tree, TreeError},
+    tree::{self, InstalledFiles, Tree, TreeError},
 };
 use crate::{lockfile::RemotePackageSourceUrl, rockspec::LuaVersionCompatibility};
 
@@ -207,6 +208,22 @@ impl<'a> BinaryRockInstall<'a> {
                     tokio::fs::copy(&rockspec_path, output_paths.rockspec_path()).await?;
                     tokio::fs::remove_file(&rockspec_path).await?;
                 }
+                // Carry over the provenance of rocks that were packed by lux
+                let provenance_path = output_paths.etc.join(PROVENANCE_FILE_NAME);
+                if provenance_path.is_file() {
+                    let provenance_json = tokio::fs::read_to_string(&provenance_path).await?;
+                    package.provenance = serde_json::from_str(&provenance_json).ok();
+                    tokio::fs::remove_file(&provenance_path).await?;
+                }
+                if self.config.relocatable() {
+                    make_pkg_config_files_relocatable(&output_paths)?;
+                }
+                InstalledFiles::collect(
+                    self.tree,
+                    &output_paths,
+                    rock_manifest.bin.entries.keys(),
+                )?
+                .write(&output_paths)?;
                 Ok(package)
             }
         }
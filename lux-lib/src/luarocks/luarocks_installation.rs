@@ -118,6 +118,7 @@ impl LuaRocksInstallation {
     ) -> Result<(), LuaRocksInstallError> {
         use crate::{lua_rockspec::RemoteLuaRockspec, package::PackageReq};
 
+        let _lock = self.tree.lock(&self.config, progress).await?;
         let mut lockfile = self.tree.lockfile()?.write_guard();
 
         let luarocks_req =
@@ -149,7 +150,11 @@ impl LuaRocksInstallation {
         use crate::{hash::HasIntegrity, operations};
         use std::io::Cursor;
         let url = "https://luarocks.github.io/luarocks/releases/luarocks-3.11.1-windows-64.zip";
-        let response = reqwest::get(url.to_owned())
+        let response = self
+            .config
+            .http_client()?
+            .get(url.to_owned())
+            .send()
             .await?
             .error_for_status()?
             .bytes()
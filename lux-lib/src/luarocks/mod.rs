@@ -1,5 +1,6 @@
 pub mod install_binary_rock;
 pub mod luarocks_installation;
+pub mod packed_rock;
 pub mod rock_manifest;
 
 /// Retrieves the target compilation platform and returns it as a luarocks identifier.
@@ -0,0 +1,281 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use path_slash::PathBufExt;
+use thiserror::Error;
+
+use super::rock_manifest::{DirOrFileEntry, RockManifest, RockManifestError};
+
+const ROCK_MANIFEST: &str = "rock_manifest";
+
+#[derive(Error, Debug)]
+pub enum PackedRockError {
+    #[error("failed to read packed rock: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("failed to read packed rock: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    RockManifest(#[from] RockManifestError),
+}
+
+/// A file in a packed rock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedRockEntry {
+    path: String,
+    size: u64,
+    md5: String,
+}
+
+impl PackedRockEntry {
+    /// The path of the file, relative to the root of the rock.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The uncompressed size of the file, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The md5 hash of the file's content, as used in a `rock_manifest`.
+    pub fn md5(&self) -> &str {
+        &self.md5
+    }
+}
+
+/// A file whose content doesn't match the `rock_manifest` of the packed rock it is in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RockManifestMismatch {
+    /// The file is listed in the `rock_manifest`, but is missing from the rock.
+    Missing { path: String },
+    /// The md5 hash of the file doesn't match the one in the `rock_manifest`.
+    Hash {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+    /// The file is not listed in the `rock_manifest`.
+    Unlisted { path: String },
+}
+
+/// A difference between the files of two packed rocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackedRockDiff {
+    Added(PackedRockEntry),
+    Removed(PackedRockEntry),
+    Changed {
+        old: PackedRockEntry,
+        new: PackedRockEntry,
+    },
+}
+
+/// The contents of a packed rock (`.rock` or `.src.rock`), read without extracting it.
+#[derive(Debug)]
+pub struct PackedRock {
+    entries: BTreeMap<String, PackedRockEntry>,
+    rock_manifest: Option<RockManifest>,
+}
+
+impl PackedRock {
+    pub fn read<R: Read + Seek>(reader: R) -> Result<Self, PackedRockError> {
+        let mut zip = zip::ZipArchive::new(reader)?;
+        let mut entries = BTreeMap::new();
+        let mut rock_manifest = None;
+        for index in 0..zip.len() {
+            let mut file = zip.by_index(index)?;
+            if file.is_dir() {
+                continue;
+            }
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)?;
+            let path = file.name().to_string();
+            if path == ROCK_MANIFEST {
+                rock_manifest = Some(RockManifest::new(&String::from_utf8_lossy(&content))?);
+            }
+            entries.insert(
+                path.clone(),
+                PackedRockEntry {
+                    path,
+                    size: file.size(),
+                    md5: format!("{:x}", md5::compute(&content)),
+                },
+            );
+        }
+        Ok(Self {
+            entries,
+            rock_manifest,
+        })
+    }
+
+    /// The files in the packed rock, sorted by path.
+    pub fn entries(&self) -> impl Iterator<Item = &PackedRockEntry> {
+        self.entries.values()
+    }
+
+    /// Check the files in the packed rock against the md5 hashes in its `rock_manifest`.
+    /// Returns `None` if the rock has no `rock_manifest`, as is the case for `.src.rock`s.
+    pub fn verify(&self) -> Option<Vec<RockManifestMismatch>> {
+        let expected = flatten_rock_manifest(self.rock_manifest.as_ref()?);
+        let mismatches = expected
+            .iter()
+            .filter_map(|(path, expected)| match self.entries.get(path) {
+                None => Some(RockManifestMismatch::Missing { path: path.clone() }),
+                Some(entry) if &entry.md5 != expected => Some(RockManifestMismatch::Hash {
+                    path: path.clone(),
+                    expected: expected.clone(),
+                    actual: entry.md5.clone(),
+                }),
+                Some(_) => None,
+            })
+            .chain(
+                self.entries
+                    .keys()
+                    .filter(|path| *path != ROCK_MANIFEST && !expected.contains_key(*path))
+                    .map(|path| RockManifestMismatch::Unlisted { path: path.clone() }),
+            )
+            .collect();
+        Some(mismatches)
+    }
+
+    /// The files that were added, removed or changed in `other`, compared to this rock.
+    pub fn diff(&self, other: &PackedRock) -> Vec<PackedRockDiff> {
+        let mut paths = self
+            .entries
+            .keys()
+            .chain(other.entries.keys())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        paths
+            .into_iter()
+            .filter_map(
+                |path| match (self.entries.get(path), other.entries.get(path)) {
+                    (Some(old), None) => Some(PackedRockDiff::Removed(old.clone())),
+                    (None, Some(new)) => Some(PackedRockDiff::Added(new.clone())),
+                    (Some(old), Some(new)) if old.md5 != new.md5 => Some(PackedRockDiff::Changed {
+                        old: old.clone(),
+                        new: new.clone(),
+                    }),
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+}
+
+/// Flatten a `rock_manifest` into a map of file paths, relative to the root of the rock,
+/// to their md5 hashes.
+fn flatten_rock_manifest(rock_manifest: &RockManifest) -> BTreeMap<String, String> {
+    let mut result = BTreeMap::new();
+    for (dir, entries) in [
+        ("lua", &rock_manifest.lua.entries),
+        ("lib", &rock_manifest.lib.entries),
+        ("doc", &rock_manifest.doc.entries),
+        ("conf", &rock_manifest.conf.entries),
+    ] {
+        flatten_dir_entries(&mut result, Path::new(dir), entries);
+    }
+    for (path, md5) in &rock_manifest.bin.entries {
+        result.insert(
+            PathBuf::from("bin").join(path).to_slash_lossy().to_string(),
+            md5.clone(),
+        );
+    }
+    flatten_dir_entries(&mut result, Path::new(""), &rock_manifest.root.entries);
+    result
+}
+
+fn flatten_dir_entries(
+    result: &mut BTreeMap<String, String>,
+    dir: &Path,
+    entries: &HashMap<PathBuf, DirOrFileEntry>,
+) {
+    for (path, entry) in entries {
+        let path = dir.join(path);
+        match entry {
+            DirOrFileEntry::FileEntry(md5) => {
+                result.insert(path.to_slash_lossy().to_string(), md5.clone());
+            }
+            DirOrFileEntry::DirEntry(entries) => flatten_dir_entries(result, &path, entries),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Cursor, io::Write};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    fn read_test_rock(name: &str) -> PackedRock {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("resources/test")
+            .join(name);
+        PackedRock::read(File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn verify_rock_manifest() {
+        let rock = read_test_rock("toml-edit-0.6.0-1.linux-x86_64.rock");
+        assert_eq!(rock.entries().count(), 6);
+        assert_eq!(rock.verify(), Some(Vec::new()));
+
+        let src_rock = read_test_rock("luatest-0.2-1.src.rock");
+        assert_eq!(src_rock.verify(), None);
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        zip.start_file("rock_manifest", options).unwrap();
+        zip.write_all(br#"rock_manifest = { lua = { ["foo.lua"] = "0", ["bar.lua"] = "0" } }"#)
+            .unwrap();
+        zip.start_file("lua/foo.lua", options).unwrap();
+        zip.write_all(b"return {}").unwrap();
+        zip.start_file("extra.txt", options).unwrap();
+        let rock = PackedRock::read(zip.finish().unwrap()).unwrap();
+        assert_eq!(
+            rock.verify().unwrap(),
+            vec![
+                RockManifestMismatch::Missing {
+                    path: "lua/bar.lua".into()
+                },
+                RockManifestMismatch::Hash {
+                    path: "lua/foo.lua".into(),
+                    expected: "0".into(),
+                    actual: format!("{:x}", md5::compute(b"return {}")),
+                },
+                RockManifestMismatch::Unlisted {
+                    path: "extra.txt".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_packed_rocks() {
+        let rock = read_test_rock("toml-edit-0.6.0-1.linux-x86_64.rock");
+        assert!(rock.diff(&rock).is_empty());
+
+        let other = read_test_rock("sample-project-0.1.0-1.all.rock");
+        let diff = rock.diff(&other);
+        assert!(diff.contains(&PackedRockDiff::Added(
+            other
+                .entries()
+                .find(|entry| entry.path() == "lua/main.lua")
+                .unwrap()
+                .clone()
+        )));
+        assert!(diff.iter().any(|diff| matches!(
+            diff,
+            PackedRockDiff::Removed(entry) if entry.path() == "lib/toml_edit.so"
+        )));
+        assert!(diff.iter().any(|diff| matches!(
+            diff,
+            PackedRockDiff::Changed { new, .. } if new.path() == "rock_manifest"
+        )));
+    }
+}
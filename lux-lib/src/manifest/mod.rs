@@ -1,10 +1,14 @@
 use itertools::Itertools;
 use mlua::{Lua, LuaSerdeExt};
-use reqwest::{header::ToStrError, Client};
+use reqwest::{
+    header::{ToStrError, ETAG},
+    Method, Response,
+};
+use serde::de::IgnoredAny;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::string::FromUtf8Error;
 use std::time::SystemTime;
-use std::{cmp::Ordering, collections::HashMap};
 use thiserror::Error;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
@@ -13,10 +17,11 @@ use url::Url;
 use zip::ZipArchive;
 
 use crate::config::LuaVersionUnset;
+use crate::http::{HttpClient, InsecureServerError};
 use crate::package::{RemotePackageType, RemotePackageTypeFilterSpec};
 use crate::progress::{Progress, ProgressBar};
 use crate::{
-    config::{Config, LuaVersion},
+    config::{Config, LuaVersion, ResolutionStrategy},
     package::{PackageName, PackageReq, PackageSpec, PackageVersion, RemotePackage},
     remote_package_source::RemotePackageSource,
 };
@@ -27,6 +32,8 @@ pub enum ManifestFromServerError {
     Io(#[from] io::Error),
     #[error("failed to pull manifest: {0}")]
     Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    InsecureServer(#[from] InsecureServerError),
     #[error("failed to parse manifest: {0}")]
     FromUtf8(#[from] FromUtf8Error),
     #[error("invalidate date received from server: {0}")]
@@ -47,23 +54,28 @@ async fn get_manifest(
     url: Url,
     manifest_version: String,
     target: &Path,
-    client: &Client,
+    client: &HttpClient,
 ) -> Result<String, ManifestFromServerError> {
-    let response = client.get(url.clone()).send().await?;
+    let response = client
+        .server_request(Method::GET, url.clone())?
+        .send()
+        .await?;
     if response.status().is_client_error() {
         let url = fallback_unzipped_url(&url)?;
-        let manifest_bytes = client
-            .get(url)
+        let response = client
+            .server_request(Method::GET, url)?
             .send()
             .await?
-            .error_for_status()?
-            .bytes()
-            .await?;
+            .error_for_status()?;
+        write_etag(target, &response).await?;
+        let manifest_bytes = response.bytes().await?;
         let manifest = String::from_utf8(manifest_bytes.to_vec())?;
         tokio::fs::write(&target, &manifest).await?;
         Ok(manifest)
     } else {
-        let manifest_bytes = response.error_for_status()?.bytes().await?;
+        let response = response.error_for_status()?;
+        write_etag(target, &response).await?;
+        let manifest_bytes = response.bytes().await?;
         let mut archive = ZipArchive::new(std::io::Cursor::new(manifest_bytes))
             .map_err(|err| ManifestFromServerError::ZipRead(url.clone(), err))?;
 
@@ -94,6 +106,25 @@ async fn get_manifest(
     }
 }
 
+/// The file in which the `ETag` of a cached manifest is stored.
+fn etag_file(cache: &Path) -> PathBuf {
+    let mut etag_file = cache.as_os_str().to_owned();
+    etag_file.push(".etag");
+    PathBuf::from(etag_file)
+}
+
+/// Stores the `ETag` of the manifest response, if the server sent one,
+/// so that the next lookup can tell whether the cached manifest is outdated.
+async fn write_etag(target: &Path, response: &Response) -> Result<(), ManifestFromServerError> {
+    let etag_file = etag_file(target);
+    match response.headers().get(ETAG) {
+        Some(etag) => fs::write(&etag_file, etag.to_str()?).await?,
+        None if etag_file.is_file() => fs::remove_file(&etag_file).await?,
+        None => {}
+    }
+    Ok(())
+}
+
 /// Look up the manifest from a cache, or get the manifest from the server
 /// if the cache doesn't exist or is outdated.
 async fn manifest_from_cache_or_server(
@@ -108,21 +139,43 @@ async fn manifest_from_cache_or_server(
     // needing to pull it from the luarocks servers each time).
     let cache = mk_manifest_cache(&url, config).await?;
 
-    let client = Client::new();
+    let client = config.http_client()?;
 
     // Read the metadata of the local cache and attempt to get the last modified date.
     if let Ok(metadata) = fs::metadata(&cache).await {
         let last_modified_local: SystemTime = metadata.modified()?;
 
         // Ask the server for the last modified date of its manifest.
-        let response = match client.head(url.clone()).send().await? {
+        let response = match client
+            .server_request(Method::HEAD, url.clone())?
+            .send()
+            .await?
+        {
             response if response.status().is_client_error() => {
                 let url = fallback_unzipped_url(&url)?;
-                client.head(url).send().await?.error_for_status()?
+                client
+                    .server_request(Method::HEAD, url)?
+                    .send()
+                    .await?
+                    .error_for_status()?
             }
             response => response.error_for_status()?,
         };
 
+        // Prefer the ETag over the last modified date, as it changes with the content.
+        if let (Ok(cached_etag), Some(server_etag)) = (
+            fs::read_to_string(etag_file(&cache)).await,
+            response.headers().get(ETAG),
+        ) {
+            if server_etag.to_str()? == cached_etag {
+                return Ok(fs::read_to_string(&cache).await?);
+            }
+            bar.map(|bar| {
+                bar.set_message(format!("📥 Downloading updated manifest from {}", &url))
+            });
+            return get_manifest(url, manifest_version.clone(), &cache, client).await;
+        }
+
         if let Some(last_modified_header) = response.headers().get("Last-Modified") {
             let server_last_modified = httpdate::parse_http_date(last_modified_header.to_str()?)?;
 
@@ -134,7 +187,7 @@ async fn manifest_from_cache_or_server(
                     bar.set_message(format!("📥 Downloading updated manifest from {}", &url))
                 });
 
-                return get_manifest(url, manifest_version.clone(), &cache, &client).await;
+                return get_manifest(url, manifest_version.clone(), &cache, client).await;
             }
 
             // Else return the cached manifest.
@@ -146,7 +199,7 @@ async fn manifest_from_cache_or_server(
     // TODO(#337): switch to something that can report progress
     bar.map(|bar| bar.set_message(format!("📥 Downloading manifest from {}", &url)));
 
-    get_manifest(url, manifest_version.clone(), &cache, &client).await
+    get_manifest(url, manifest_version.clone(), &cache, client).await
 }
 
 /// Get the manifest from the server, ignoring the cache.
@@ -159,9 +212,9 @@ pub(crate) async fn manifest_from_server_only(
     let manifest_version = LuaVersion::from(config)?.version_compatibility_str();
     let url = mk_manifest_url(server_url, &manifest_version, config)?;
     let cache = mk_manifest_cache(&url, config).await?;
-    let client = Client::new();
+    let client = config.http_client()?;
     bar.map(|bar| bar.set_message(format!("📥 Downloading manifest from {}", &url)));
-    get_manifest(url, manifest_version.clone(), &cache, &client).await
+    get_manifest(url, manifest_version.clone(), &cache, client).await
 }
 
 fn mk_manifest_url(
@@ -194,6 +247,18 @@ async fn mk_manifest_cache(url: &Url, config: &Config) -> io::Result<PathBuf> {
 #[derive(Clone, Debug)]
 pub(crate) struct ManifestMetadata {
     pub repository: HashMap<PackageName, HashMap<PackageVersion, Vec<RemotePackageType>>>,
+    /// The Lua modules and executables of each package version,
+    /// for manifests that list them.
+    pub contents: HashMap<PackageName, HashMap<PackageVersion, ManifestPackageContents>>,
+    /// The download count of each package, for servers that publish them.
+    pub downloads: HashMap<PackageName, u64>,
+}
+
+/// The Lua modules and executables a package version provides, according to a manifest.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ManifestPackageContents {
+    pub modules: Vec<String>,
+    pub commands: Vec<String>,
 }
 
 impl<'de> serde::Deserialize<'de> for ManifestMetadata {
@@ -223,8 +288,18 @@ impl ManifestMetadata {
 
         lua.load(manifest).exec()?;
 
+        let globals = lua.globals();
         let intermediate = IntermediateManifest {
-            repository: lua.from_value(lua.globals().get("repository")?)?,
+            repository: lua.from_value(globals.get("repository")?)?,
+            modules: lua
+                .from_value::<Option<_>>(globals.get("modules")?)?
+                .unwrap_or_default(),
+            commands: lua
+                .from_value::<Option<_>>(globals.get("commands")?)?
+                .unwrap_or_default(),
+            downloads: lua
+                .from_value::<Option<_>>(globals.get("downloads")?)?
+                .unwrap_or_default(),
         };
         let manifest = Self::from_intermediate(intermediate);
 
@@ -235,17 +310,21 @@ impl ManifestMetadata {
         self.repository.contains_key(rock_name)
     }
 
-    pub fn latest_match(
+    /// Select a version that satisfies the requirement according to the `strategy`.
+    /// Equal versions are ordered by rock type (binary > rockspec > src)
+    /// and then by their version string, so the result is deterministic.
+    pub fn best_match(
         &self,
         lua_package_req: &PackageReq,
         filter: Option<RemotePackageTypeFilterSpec>,
+        strategy: ResolutionStrategy,
     ) -> Option<(PackageSpec, RemotePackageType)> {
         let filter = filter.unwrap_or_default();
         if !self.has_rock(lua_package_req.name()) {
             return None;
         }
 
-        let (version, rock_type) = self.repository[lua_package_req.name()]
+        let candidates = self.repository[lua_package_req.name()]
             .iter()
             .filter(|(version, _)| lua_package_req.version_req().matches(version))
             .flat_map(|(version, rock_types)| {
@@ -261,13 +340,25 @@ impl ManifestMetadata {
                         None
                     }
                 })
-            })
-            .max_by(
-                |(version_a, type_a), (version_b, type_b)| match version_a.cmp(version_b) {
-                    Ordering::Equal => type_a.cmp(type_b),
-                    ordering => ordering,
-                },
-            )?;
+            });
+        let compare_versions = |version_a: &PackageVersion, version_b: &PackageVersion| {
+            version_a
+                .cmp(version_b)
+                .then_with(|| version_a.to_string().cmp(&version_b.to_string()))
+        };
+        let (version, rock_type) = match strategy {
+            ResolutionStrategy::Highest => {
+                candidates.max_by(|(version_a, type_a), (version_b, type_b)| {
+                    compare_versions(version_a, version_b).then_with(|| type_a.cmp(type_b))
+                })
+            }
+            ResolutionStrategy::Minimal => {
+                candidates.min_by(|(version_a, type_a), (version_b, type_b)| {
+                    // Prefer the best rock type for the lowest version
+                    compare_versions(version_a, version_b).then_with(|| type_b.cmp(type_a))
+                })
+            }
+        }?;
 
         Some((
             PackageSpec::new(lua_package_req.name().clone(), version.clone()),
@@ -275,33 +366,179 @@ impl ManifestMetadata {
         ))
     }
 
+    /// Whether a package version ships executables, according to the manifest.
+    pub fn has_commands(&self, rock_name: &PackageName, version: &PackageVersion) -> bool {
+        self.contents
+            .get(rock_name)
+            .and_then(|versions| versions.get(version))
+            .is_some_and(|contents| !contents.commands.is_empty())
+    }
+
+    /// The package versions that provide a Lua module, if the manifest lists them.
+    pub fn module_providers(&self, module: &str) -> Vec<(&PackageName, &PackageVersion)> {
+        self.contents
+            .iter()
+            .flat_map(|(name, versions)| {
+                versions
+                    .iter()
+                    .filter(|(_, contents)| contents.modules.iter().any(|m| m == module))
+                    .map(move |(version, _)| (name, version))
+            })
+            .collect()
+    }
+
+    /// How popular a package is: its download count if the manifest lists download counts,
+    /// or else its number of published versions.
+    pub fn popularity(&self, rock_name: &PackageName) -> Option<u64> {
+        let versions = self.repository.get(rock_name)?;
+        if self.downloads.is_empty() {
+            Some(versions.len() as u64)
+        } else {
+            Some(self.downloads.get(rock_name).copied().unwrap_or_default())
+        }
+    }
+
+    /// Packages whose names are within a small edit distance of `rock_name`
+    /// and that are much more popular, i.e. packages that `rock_name` may be typosquatting.
+    pub fn similar_popular_packages(&self, rock_name: &PackageName) -> Vec<&PackageName> {
+        let Some(popularity) = self.popularity(rock_name) else {
+            return Vec::new();
+        };
+        let name = rock_name.to_string();
+        let max_distance = match name.chars().count() {
+            0..TYPOSQUAT_MIN_NAME_LENGTH => return Vec::new(),
+            len if len <= 8 => 1,
+            _ => 2,
+        };
+        self.repository
+            .keys()
+            .filter(|other| *other != rock_name)
+            .filter(|other| {
+                self.popularity(other).is_some_and(|other_popularity| {
+                    other_popularity >= popularity.max(1) * TYPOSQUAT_POPULARITY_FACTOR
+                })
+            })
+            .filter(|other| edit_distance(&name, &other.to_string()) <= max_distance)
+            .sorted()
+            .collect_vec()
+    }
+
     /// Construct a `ManifestMetadata` from an intermediate representation,
     /// silently skipping entries for versions we don't know how to parse.
     fn from_intermediate(intermediate: IntermediateManifest) -> Self {
+        let mut contents: HashMap<PackageName, HashMap<PackageVersion, ManifestPackageContents>> =
+            HashMap::new();
         let repository = intermediate
             .repository
             .into_iter()
             .map(|(name, package_map)| {
-                (
-                    name,
-                    package_map
-                        .into_iter()
-                        .filter_map(|(version_str, entries)| {
-                            let version = PackageVersion::parse(version_str.as_str()).ok()?;
-                            let entries = entries
-                                .into_iter()
-                                .filter_map(|entry| RemotePackageType::try_from(entry).ok())
-                                .collect_vec();
-                            Some((version, entries))
-                        })
-                        .collect(),
-                )
+                let package_map = package_map
+                    .into_iter()
+                    .filter_map(|(version_str, entries)| {
+                        let version = PackageVersion::parse(version_str.as_str()).ok()?;
+                        for entry in &entries {
+                            if !entry.modules.is_empty() || !entry.commands.is_empty() {
+                                let package_contents = contents
+                                    .entry(name.clone())
+                                    .or_default()
+                                    .entry(version.clone())
+                                    .or_default();
+                                package_contents
+                                    .modules
+                                    .extend(entry.modules.keys().cloned());
+                                package_contents
+                                    .commands
+                                    .extend(entry.commands.keys().cloned());
+                            }
+                        }
+                        let entries = entries
+                            .into_iter()
+                            .filter_map(|entry| RemotePackageType::try_from(entry).ok())
+                            .collect_vec();
+                        Some((version, entries))
+                    })
+                    .collect();
+                (name, package_map)
             })
             .collect();
-        Self { repository }
+        // The top-level `modules` and `commands` map to lists of `<name>/<version>`.
+        let top_level = |entries: HashMap<String, Vec<String>>| {
+            entries.into_iter().flat_map(|(entry, packages)| {
+                packages.into_iter().filter_map(move |package| {
+                    let (name, version) = package.split_once('/')?;
+                    Some((
+                        entry.clone(),
+                        PackageName::new(name.to_string()),
+                        PackageVersion::parse(version).ok()?,
+                    ))
+                })
+            })
+        };
+        for (module, name, version) in top_level(intermediate.modules) {
+            contents
+                .entry(name)
+                .or_default()
+                .entry(version)
+                .or_default()
+                .modules
+                .push(module);
+        }
+        for (command, name, version) in top_level(intermediate.commands) {
+            contents
+                .entry(name)
+                .or_default()
+                .entry(version)
+                .or_default()
+                .commands
+                .push(command);
+        }
+        for package_contents in contents.values_mut().flat_map(HashMap::values_mut) {
+            package_contents.modules.sort();
+            package_contents.modules.dedup();
+            package_contents.commands.sort();
+            package_contents.commands.dedup();
+        }
+        Self {
+            repository,
+            contents,
+            downloads: intermediate.downloads,
+        }
     }
 }
 
+/// Names shorter than this are too likely to be similar by coincidence.
+const TYPOSQUAT_MIN_NAME_LENGTH: usize = 4;
+
+/// How many times more popular a similarly named package must be
+/// for a package to be considered a possible typosquat.
+const TYPOSQUAT_POPULARITY_FACTOR: u64 = 10;
+
+/// The number of insertions, deletions, substitutions and transpositions of adjacent characters
+/// needed to turn `a` into `b`.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect_vec();
+    let b = b.chars().collect_vec();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            distances[i][j] = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distances[i][j] = distances[i][j].min(distances[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct Manifest {
     server_url: Url,
@@ -342,13 +579,15 @@ impl Manifest {
         &self.metadata
     }
 
-    /// Find a package that matches the requirement, returning the latest match
+    /// Find a package that matches the requirement,
+    /// selecting among the matches according to the `strategy`.
     pub fn find(
         &self,
         package_req: &PackageReq,
         filter: Option<RemotePackageTypeFilterSpec>,
+        strategy: ResolutionStrategy,
     ) -> Option<RemotePackage> {
-        match self.metadata().latest_match(package_req, filter) {
+        match self.metadata().best_match(package_req, filter, strategy) {
             None => None,
             Some((package, package_type)) => {
                 let remote_source = match package_type {
@@ -373,7 +612,7 @@ struct UnsupportedArchitectureError;
 impl TryFrom<ManifestRockEntry> for RemotePackageType {
     type Error = UnsupportedArchitectureError;
     fn try_from(
-        ManifestRockEntry { arch }: ManifestRockEntry,
+        ManifestRockEntry { arch, .. }: ManifestRockEntry,
     ) -> Result<Self, UnsupportedArchitectureError> {
         match arch.as_str() {
             "rockspec" => Ok(RemotePackageType::Rockspec),
@@ -391,6 +630,12 @@ impl TryFrom<ManifestRockEntry> for RemotePackageType {
 struct ManifestRockEntry {
     /// e.g. "linux-x86_64", "rockspec", "src", ...
     pub arch: String,
+    /// The Lua modules the rock provides, as listed in a tree's manifest.
+    #[serde(default)]
+    pub modules: HashMap<String, IgnoredAny>,
+    /// The executables the rock provides, as listed in a tree's manifest.
+    #[serde(default)]
+    pub commands: HashMap<String, IgnoredAny>,
 }
 
 /// Intermediate implementation for deserializing
@@ -398,6 +643,15 @@ struct ManifestRockEntry {
 struct IntermediateManifest {
     /// The key of each package's HashMap is the version string
     repository: HashMap<PackageName, HashMap<String, Vec<ManifestRockEntry>>>,
+    /// Lua modules, mapped to the `<name>/<version>` of the packages that provide them
+    #[serde(default)]
+    modules: HashMap<String, Vec<String>>,
+    /// Executables, mapped to the `<name>/<version>` of the packages that provide them
+    #[serde(default)]
+    commands: HashMap<String, Vec<String>>,
+    /// Download counts, which only some servers publish
+    #[serde(default)]
+    downloads: HashMap<PackageName, u64>,
 }
 
 /// Given a URL to a zip file, create a URL to the same file without the .zip extension
@@ -449,6 +703,7 @@ mod tests {
         url_str.pop();
         let config = ConfigBuilder::new()
             .unwrap()
+            .allow_insecure_servers(Some(true))
             .cache_dir(Some(cache_dir))
             .lua_version(Some(crate::config::LuaVersion::LuaJIT))
             .build()
@@ -472,6 +727,7 @@ mod tests {
 
         let config = ConfigBuilder::new()
             .unwrap()
+            .allow_insecure_servers(Some(true))
             .cache_dir(Some(cache_dir))
             .lua_version(Some(crate::config::LuaVersion::Lua51))
             .build()
@@ -502,6 +758,7 @@ mod tests {
         let _metadata = fs::metadata(&cache).await.unwrap();
         let config = ConfigBuilder::new()
             .unwrap()
+            .allow_insecure_servers(Some(true))
             .cache_dir(Some(cache_dir.to_path_buf()))
             .lua_version(Some(crate::config::LuaVersion::Lua51))
             .build()
@@ -516,6 +773,62 @@ mod tests {
         assert_eq!(result, manifest_content);
     }
 
+    #[tokio::test]
+    pub async fn manifest_etag() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::path("/manifest-5.1.zip"))
+                .times(1..)
+                .respond_with(
+                    status_code(200)
+                        .append_header("ETag", "\"manifest-etag\"")
+                        .append_header("Last-Modified", "Sat, 20 Jan 2124 13:14:12 GMT")
+                        .body(
+                            std::fs::read(format!(
+                                "{}/resources/test/manifest-5.1.zip",
+                                env!("CARGO_MANIFEST_DIR")
+                            ))
+                            .unwrap(),
+                        ),
+                ),
+        );
+        let mut url_str = server.url_str(""); // Remove trailing "/"
+        url_str.pop();
+        let server_url = Url::parse(&url_str).unwrap();
+        let cache_dir = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .allow_insecure_servers(Some(true))
+            .cache_dir(Some(cache_dir.to_path_buf()))
+            .lua_version(Some(crate::config::LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let manifest = manifest_from_cache_or_server(&server_url, &config, &Progress::NoProgress)
+            .await
+            .unwrap();
+        let url = mk_manifest_url(&server_url, "5.1", &config).unwrap();
+        let cache = mk_manifest_cache(&url, &config).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(etag_file(&cache)).await.unwrap(),
+            "\"manifest-etag\""
+        );
+
+        // The server's manifest is newer, but its ETag matches the cached one.
+        fs::write(&cache, "cached").await.unwrap();
+        let result = manifest_from_cache_or_server(&server_url, &config, &Progress::NoProgress)
+            .await
+            .unwrap();
+        assert_eq!(result, "cached");
+
+        fs::write(etag_file(&cache), "\"outdated-etag\"")
+            .await
+            .unwrap();
+        let result = manifest_from_cache_or_server(&server_url, &config, &Progress::NoProgress)
+            .await
+            .unwrap();
+        assert_eq!(result, manifest);
+    }
+
     #[tokio::test]
     pub async fn parse_metadata_from_empty_manifest() {
         let manifest = "
@@ -543,6 +856,175 @@ mod tests {
         let metadata = ManifestMetadata::new(&manifest).unwrap();
 
         let package_req: PackageReq = "30log > 1.3.0".parse().unwrap();
-        assert!(metadata.latest_match(&package_req, None).is_none());
+        assert!(metadata
+            .best_match(&package_req, None, ResolutionStrategy::Highest)
+            .is_none());
+    }
+
+    #[test]
+    fn parse_manifest_contents() {
+        let manifest = r#"
+            commands = {
+                ["foo-cli"] = { "foo/1.0.0-1" },
+            }
+            modules = {
+                ["lpeg.re"] = { "lpeg/1.1.0-1", "lpeg/1.0.2-1" },
+            }
+            repository = {
+                foo = {
+                    ["1.0.0-1"] = { { arch = "rockspec" } },
+                    ["2.0.0-1"] = { { arch = "rockspec" } },
+                },
+                lpeg = {
+                    ["1.0.2-1"] = { { arch = "rockspec" } },
+                    ["1.1.0-1"] = {
+                        {
+                            arch = "installed",
+                            modules = { lpeg = "lpeg.so", ["lpeg.re"] = "re.lua" },
+                            commands = {},
+                        },
+                    },
+                },
+            }
+            "#
+        .to_string();
+        let metadata = ManifestMetadata::new(&manifest).unwrap();
+        let foo = PackageName::new("foo".into());
+        assert!(metadata.has_commands(&foo, &"1.0.0-1".parse().unwrap()));
+        assert!(!metadata.has_commands(&foo, &"2.0.0-1".parse().unwrap()));
+        let providers = metadata
+            .module_providers("lpeg.re")
+            .into_iter()
+            .map(|(name, version)| format!("{name}@{version}"))
+            .sorted()
+            .collect_vec();
+        assert_eq!(providers, vec!["lpeg@1.0.2-1", "lpeg@1.1.0-1"]);
+        assert_eq!(metadata.module_providers("lpeg").len(), 1);
+        assert!(metadata.module_providers("foo").is_empty());
+    }
+
+    #[test]
+    fn similar_popular_packages() {
+        let manifest = r#"
+            repository = {
+                lpeg = {
+                    ["1.0.0-1"] = { { arch = "rockspec" } },
+                    ["1.0.1-1"] = { { arch = "rockspec" } },
+                    ["1.0.2-1"] = { { arch = "rockspec" } },
+                    ["1.1.0-1"] = { { arch = "rockspec" } },
+                },
+                lpge = { ["1.0.0-1"] = { { arch = "rockspec" } } },
+                lpegx = { ["1.0.0-1"] = { { arch = "rockspec" } } },
+                penlight = { ["1.0.0-1"] = { { arch = "rockspec" } } },
+                penlihgt = { ["1.0.0-1"] = { { arch = "rockspec" } } },
+            }
+            downloads = {
+                lpeg = 100000,
+                lpge = 10,
+                lpegx = 50000,
+                penlight = 500,
+                penlihgt = 100,
+            }
+            "#
+        .to_string();
+        let metadata = ManifestMetadata::new(&manifest).unwrap();
+        let similar = |name: &str| {
+            metadata
+                .similar_popular_packages(&PackageName::new(name.into()))
+                .into_iter()
+                .map(|name| name.to_string())
+                .collect_vec()
+        };
+        assert_eq!(similar("lpge"), vec!["lpeg"]);
+        assert!(similar("lpeg").is_empty());
+        assert!(similar("lpegx").is_empty());
+        // Not popular enough for the typo to be suspicious
+        assert!(similar("penlihgt").is_empty());
+        assert!(similar("unknown").is_empty());
+
+        let metadata = test_metadata();
+        assert_eq!(
+            metadata.popularity(&PackageName::new("foo".into())),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("lpeg", "lpeg"), 0);
+        assert_eq!(edit_distance("lpeg", "lpge"), 1);
+        assert_eq!(edit_distance("lpeg", "lpegx"), 1);
+        assert_eq!(edit_distance("penlight", "penlihgt"), 1);
+        assert_eq!(edit_distance("penlight", "penlite"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    fn test_metadata() -> ManifestMetadata {
+        let manifest = r#"
+            repository = {
+                foo = {
+                    ["1.0.0-1"] = { { arch = "src" }, { arch = "rockspec" } },
+                    ["1.1.0-1"] = { { arch = "rockspec" }, { arch = "all" } },
+                    ["2.0.0-1"] = { { arch = "rockspec" } },
+                },
+            }
+            "#
+        .to_string();
+        ManifestMetadata::new(&manifest).unwrap()
+    }
+
+    #[test]
+    fn best_match_highest() {
+        let metadata = test_metadata();
+        let package_req: PackageReq = "foo >= 1.0.0, < 2.0.0".parse().unwrap();
+        let (package, rock_type) = metadata
+            .best_match(&package_req, None, ResolutionStrategy::Highest)
+            .unwrap();
+        assert_eq!(package.version().to_string(), "1.1.0-1");
+        assert_eq!(rock_type, RemotePackageType::Binary);
+    }
+
+    #[test]
+    fn best_match_minimal() {
+        let metadata = test_metadata();
+        let package_req: PackageReq = "foo >= 1.0.0".parse().unwrap();
+        let (package, rock_type) = metadata
+            .best_match(&package_req, None, ResolutionStrategy::Minimal)
+            .unwrap();
+        assert_eq!(package.version().to_string(), "1.0.0-1");
+        assert_eq!(rock_type, RemotePackageType::Rockspec);
+
+        let package_req: PackageReq = "foo > 1.0.0".parse().unwrap();
+        let (package, rock_type) = metadata
+            .best_match(&package_req, None, ResolutionStrategy::Minimal)
+            .unwrap();
+        assert_eq!(package.version().to_string(), "1.1.0-1");
+        assert_eq!(rock_type, RemotePackageType::Binary);
+    }
+
+    #[test]
+    fn best_match_is_deterministic() {
+        let manifest = r#"
+            repository = {
+                foo = {
+                    ["1.0-1"] = { { arch = "rockspec" } },
+                    ["1.0.0-1"] = { { arch = "rockspec" } },
+                },
+            }
+            "#
+        .to_string();
+        let package_req: PackageReq = "foo".parse().unwrap();
+        let best_match = |strategy| {
+            ManifestMetadata::new(&manifest)
+                .unwrap()
+                .best_match(&package_req, None, strategy)
+                .map(|(package, _)| package.version().to_string())
+                .unwrap()
+        };
+        // Equal versions are ordered by their version string.
+        for _ in 0..10 {
+            assert_eq!(best_match(ResolutionStrategy::Highest), "1.0.0-1");
+            assert_eq!(best_match(ResolutionStrategy::Minimal), "1.0-1");
+        }
     }
 }
@@ -0,0 +1,122 @@
+use std::{error::Error, sync::Arc};
+
+use crate::{lockfile::LocalPackage, package::PackageSpec};
+
+/// Callbacks on the progress of an install, build or sync, for tools that embed lux
+/// and want to collect their own metrics without parsing progress bars or logs.
+///
+/// All callbacks do nothing by default, so implementors only need to override
+/// the ones they are interested in.
+/// Callbacks may be invoked concurrently from multiple tasks.
+pub trait OperationObserver: Send + Sync {
+    /// A package requirement has been resolved to a package that will be installed.
+    fn package_resolved(&self, _package: &PackageSpec) {}
+
+    /// A package's sources (or its packed binary rock) are available locally.
+    fn package_fetched(&self, _package: &PackageSpec) {}
+
+    /// A package has been built and its files have been installed into the tree.
+    fn package_built(&self, _package: &LocalPackage) {}
+
+    /// A package has been installed and added to the tree's lockfile.
+    fn package_installed(&self, _package: &LocalPackage) {}
+
+    /// Building or installing a package failed.
+    fn package_failed(&self, _package: &PackageSpec, _error: &dyn Error) {}
+}
+
+impl<T: OperationObserver + ?Sized> OperationObserver for Arc<T> {
+    fn package_resolved(&self, package: &PackageSpec) {
+        self.as_ref().package_resolved(package)
+    }
+
+    fn package_fetched(&self, package: &PackageSpec) {
+        self.as_ref().package_fetched(package)
+    }
+
+    fn package_built(&self, package: &LocalPackage) {
+        self.as_ref().package_built(package)
+    }
+
+    fn package_installed(&self, package: &LocalPackage) {
+        self.as_ref().package_installed(package)
+    }
+
+    fn package_failed(&self, package: &PackageSpec, error: &dyn Error) {
+        self.as_ref().package_failed(package, error)
+    }
+}
+
+/// Notify the observer, if there is one.
+impl<T: OperationObserver> OperationObserver for Option<T> {
+    fn package_resolved(&self, package: &PackageSpec) {
+        if let Some(observer) = self {
+            observer.package_resolved(package)
+        }
+    }
+
+    fn package_fetched(&self, package: &PackageSpec) {
+        if let Some(observer) = self {
+            observer.package_fetched(package)
+        }
+    }
+
+    fn package_built(&self, package: &LocalPackage) {
+        if let Some(observer) = self {
+            observer.package_built(package)
+        }
+    }
+
+    fn package_installed(&self, package: &LocalPackage) {
+        if let Some(observer) = self {
+            observer.package_installed(package)
+        }
+    }
+
+    fn package_failed(&self, package: &PackageSpec, error: &dyn Error) {
+        if let Some(observer) = self {
+            observer.package_failed(package, error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, sync::Mutex};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingObserver(Mutex<Vec<String>>);
+
+    impl OperationObserver for RecordingObserver {
+        fn package_resolved(&self, package: &PackageSpec) {
+            self.0.lock().unwrap().push(format!("resolved {package}"));
+        }
+
+        fn package_failed(&self, package: &PackageSpec, error: &dyn Error) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("failed {package}: {error}"));
+        }
+    }
+
+    #[test]
+    fn notify_shared_observer() {
+        let recording = Arc::new(RecordingObserver::default());
+        let observer: Option<Arc<dyn OperationObserver>> = Some(recording.clone());
+        let package = PackageSpec::parse("foo".into(), "1.0.0-1".into()).unwrap();
+        observer.package_resolved(&package);
+        observer.package_fetched(&package);
+        observer.package_failed(&package, &io::Error::other("oops"));
+        None::<Arc<dyn OperationObserver>>.package_resolved(&package);
+        assert_eq!(
+            *recording.0.lock().unwrap(),
+            vec![
+                format!("resolved {package}"),
+                format!("failed {package}: oops"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+};
+
+use bon::Builder;
+use itertools::Itertools;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    config::{Config, LuaVersion, LuaVersionUnset},
+    package::{PackageName, PackageVersion},
+    project::{Project, ProjectTreeError},
+    tree::{Tree, TreeError},
+};
+
+/// The comment on the second line of generated shims,
+/// which tells them apart from other files in the shim directory.
+const SHIM_MARKER: &str = "generated by lux";
+
+/// The tree an executable is installed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BinaryLocation {
+    ProjectTree,
+    UserTree,
+}
+
+impl Display for BinaryLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ProjectTree => "project tree",
+            Self::UserTree => "user tree",
+        }
+        .fmt(f)
+    }
+}
+
+/// An executable that a package installed in a tree.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledBinary {
+    /// The name of the executable.
+    pub name: String,
+    /// The executable, which may be a wrapper script.
+    pub path: PathBuf,
+    /// The Lua script that the wrapper script runs, if the executable is wrapped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unwrapped: Option<PathBuf>,
+    pub package: PackageName,
+    pub version: PackageVersion,
+    pub location: BinaryLocation,
+}
+
+impl InstalledBinary {
+    pub fn is_wrapped(&self) -> bool {
+        self.unwrapped.is_some()
+    }
+}
+
+/// Lists the executables installed in the project and user trees,
+/// and generates shims for them.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Binaries<'a> {
+    #[builder(start_fn)]
+    config: &'a Config,
+    /// Also list the executables in the project tree,
+    /// which take precedence over the user tree.
+    project: Option<&'a Project>,
+}
+
+impl<State> BinariesBuilder<'_, State>
+where
+    State: binaries_builder::IsComplete,
+{
+    /// List the installed executables, in priority order.
+    pub fn list(self) -> Result<Vec<InstalledBinary>, BinariesError> {
+        list_binaries(self._build())
+    }
+
+    /// Write a shim for each installed executable to `dir`, which can be added to the `PATH`,
+    /// e.g. with direnv's `PATH_add`.
+    /// Shims that were generated before and whose executables are no longer installed are removed.
+    /// Returns the paths of the shims.
+    pub fn write_shims(self, dir: &Path) -> Result<Vec<PathBuf>, BinariesError> {
+        let binaries = list_binaries(self._build())?;
+        write_shims(&binaries, dir)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BinariesError {
+    #[error("IO operation failed: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    ProjectTree(#[from] ProjectTreeError),
+    #[error(transparent)]
+    LuaVersionUnset(#[from] LuaVersionUnset),
+}
+
+fn list_binaries(binaries: Binaries<'_>) -> Result<Vec<InstalledBinary>, BinariesError> {
+    let config = binaries.config;
+    let mut result = Vec::new();
+    let lua_version = match binaries.project {
+        Some(project) => {
+            let project_tree = project.tree(config)?;
+            result.extend(tree_binaries(&project_tree, BinaryLocation::ProjectTree)?);
+            project_tree.version().clone()
+        }
+        None => LuaVersion::from(config)?.clone(),
+    };
+    let user_tree = config.user_tree(lua_version)?;
+    result.extend(tree_binaries(&user_tree, BinaryLocation::UserTree)?);
+    Ok(result)
+}
+
+fn tree_binaries(
+    tree: &Tree,
+    location: BinaryLocation,
+) -> Result<Vec<InstalledBinary>, BinariesError> {
+    let lockfile = tree.lockfile()?;
+    Ok(lockfile
+        .rocks()
+        .values()
+        .filter(|package| lockfile.is_entrypoint(&package.id()))
+        .sorted_by(|a, b| a.name().cmp(b.name()))
+        .flat_map(|package| {
+            package
+                .spec
+                .binaries()
+                .into_iter()
+                .filter_map(|binary| binary.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .filter_map(|name| {
+                    let path = bin_path(tree, &name)?;
+                    let unwrapped = Some(tree.unwrapped_bin().join(&name))
+                        .filter(|unwrapped| unwrapped.is_file());
+                    Some(InstalledBinary {
+                        name,
+                        path,
+                        unwrapped,
+                        package: package.name().clone(),
+                        version: package.version().clone(),
+                        location,
+                    })
+                })
+                .collect_vec()
+        })
+        .collect_vec())
+}
+
+/// The executable in the tree's bin directory, which may be a wrapper script.
+fn bin_path(tree: &Tree, name: &str) -> Option<PathBuf> {
+    [
+        tree.bin().join(name),
+        tree.bin().join(format!("{name}.bat")),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+}
+
+fn write_shims(binaries: &[InstalledBinary], dir: &Path) -> Result<Vec<PathBuf>, BinariesError> {
+    std::fs::create_dir_all(dir)?;
+    let mut names = HashSet::new();
+    let shims: Vec<PathBuf> = binaries
+        .iter()
+        // Executables with higher priority shadow those with the same name
+        .filter(|binary| names.insert(binary.name.clone()))
+        .map(|binary| {
+            let shim = dir.join(shim_file_name(&binary.name));
+            std::fs::write(&shim, shim_content(&binary.path))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&shim, std::fs::Permissions::from_mode(0o755))?;
+            }
+            Ok::<_, io::Error>(shim)
+        })
+        .try_collect()?;
+    remove_stale_shims(dir, &shims)?;
+    Ok(shims)
+}
+
+fn remove_stale_shims(dir: &Path, shims: &[PathBuf]) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file()
+            && !shims.contains(&path)
+            && std::fs::read_to_string(&path).is_ok_and(|content| {
+                content
+                    .lines()
+                    .nth(1)
+                    .is_some_and(|line| line.trim_end().ends_with(SHIM_MARKER))
+            })
+        {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_family = "unix")]
+fn shim_file_name(name: &str) -> String {
+    name.to_string()
+}
+
+#[cfg(target_family = "windows")]
+fn shim_file_name(name: &str) -> String {
+    format!("{}.bat", name.trim_end_matches(".bat"))
+}
+
+#[cfg(target_family = "unix")]
+fn shim_content(executable: &Path) -> String {
+    format!(
+        "#!/bin/sh\n# {SHIM_MARKER}\nexec \"{}\" \"$@\"\n",
+        executable.display()
+    )
+}
+
+#[cfg(target_family = "windows")]
+fn shim_content(executable: &Path) -> String {
+    format!(
+        "@echo off\r\nrem {SHIM_MARKER}\r\n\"{}\" %*\r\n",
+        executable.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigBuilder;
+    use assert_fs::prelude::PathCopy;
+
+    #[test]
+    fn list_and_shim_binaries() {
+        let tree_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/sample-tree");
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.copy_from(&tree_path, &["**"]).unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        std::fs::create_dir_all(tree.unwrapped_bin()).unwrap();
+        std::fs::write(tree.unwrapped_bin().join("json2lua"), "print('json2lua')").unwrap();
+        let json2lua = tree.bin().join(shim_file_name("json2lua"));
+        std::fs::write(&json2lua, "").unwrap();
+
+        let binaries = Binaries::new(&config).list().unwrap();
+        assert_eq!(binaries.len(), 1);
+        assert_eq!(binaries[0].name, "json2lua");
+        assert_eq!(binaries[0].path, json2lua);
+        assert_eq!(binaries[0].package.to_string(), "lua-cjson");
+        assert_eq!(binaries[0].location, BinaryLocation::UserTree);
+        assert!(binaries[0].is_wrapped());
+
+        let shim_dir = temp.path().join("shims");
+        std::fs::create_dir_all(&shim_dir).unwrap();
+        let stale_shim = shim_dir.join(shim_file_name("lua2json"));
+        std::fs::write(&stale_shim, shim_content(&tree.bin().join("lua2json"))).unwrap();
+        let other_file = shim_dir.join("README");
+        std::fs::write(&other_file, "not a shim").unwrap();
+        let shims = Binaries::new(&config).write_shims(&shim_dir).unwrap();
+        assert_eq!(shims, vec![shim_dir.join(shim_file_name("json2lua"))]);
+        assert!(std::fs::read_to_string(&shims[0])
+            .unwrap()
+            .contains(&json2lua.display().to_string()));
+        assert!(!stale_shim.exists());
+        assert!(other_file.exists());
+    }
+}
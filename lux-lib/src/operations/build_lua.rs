@@ -9,8 +9,10 @@ use crate::{
     build::{external_dependency::ExternalDependencyInfo, utils},
     config::{external_deps::ExternalDependencySearchConfig, Config, LuaVersion},
     hash::HasIntegrity,
+    lua_installation::LUA_HEADERS,
     lua_rockspec::ExternalDependencySpec,
     operations::{self, UnpackError},
+    package::{HasModRev, PackageVersion},
     progress::{Progress, ProgressBar},
 };
 use bon::Builder;
@@ -41,6 +43,17 @@ pub struct BuildLua<'a> {
     install_dir: &'a Path,
     config: &'a Config,
     progress: &'a Progress<ProgressBar>,
+    /// The exact Lua release to build, e.g. `5.1.5`.
+    /// Defaults to the latest release of the `lua_version` that Lux knows the hash of.
+    /// Ignored for LuaJIT.
+    version: Option<&'a PackageVersion>,
+    /// The expected hash of the Lua sources of the `version`.
+    /// If not set, the sources of a release Lux doesn't know the hash of are not verified.
+    integrity: Option<&'a Integrity>,
+    /// Only install the C headers, e.g. for building C rocks
+    /// against a Lua interpreter that already exists.
+    #[builder(default)]
+    headers_only: bool,
 }
 
 #[derive(Debug, Error)]
@@ -74,14 +87,19 @@ pub enum BuildLuaError {
 }
 
 impl<State: build_lua_builder::State + build_lua_builder::IsComplete> BuildLuaBuilder<'_, State> {
-    pub async fn build(self) -> Result<(), BuildLuaError> {
+    /// Returns the hash of the Lua sources,
+    /// or `None` for LuaJIT, which is built from a git checkout.
+    pub async fn build(self) -> Result<Option<Integrity>, BuildLuaError> {
         let args = self._build();
         let lua_version = args.lua_version;
         match lua_version {
             LuaVersion::Lua51 | LuaVersion::Lua52 | LuaVersion::Lua53 | LuaVersion::Lua54 => {
-                do_build_lua(args).await
+                Ok(Some(do_build_lua(args).await?))
+            }
+            LuaVersion::LuaJIT | LuaVersion::LuaJIT52 => {
+                do_build_luajit(args).await?;
+                Ok(None)
             }
-            LuaVersion::LuaJIT | LuaVersion::LuaJIT52 => do_build_luajit(args).await,
         }
     }
 }
@@ -106,6 +124,11 @@ async fn do_build_luajit(args: BuildLua<'_>) -> Result<(), BuildLuaError> {
         let (object, _) = repo.revparse_ext(&format!("v{LUAJIT_MM_VERSION}"))?;
         repo.checkout_tree(&object, None)?;
     }
+    if args.headers_only {
+        progress
+            .map(|p| p.set_message(format!("💻 Installing Luajit {LUAJIT_MM_VERSION} headers")));
+        return install_headers(&build_dir.join("src"), args.install_dir).await;
+    }
     if cfg!(target_env = "msvc") {
         do_build_luajit_msvc(args, &build_dir).await
     } else {
@@ -279,7 +302,7 @@ async fn do_build_luajit_msvc(args: BuildLua<'_>, build_dir: &Path) -> Result<()
     Ok(())
 }
 
-async fn do_build_lua(args: BuildLua<'_>) -> Result<(), BuildLuaError> {
+async fn do_build_lua(args: BuildLua<'_>) -> Result<Integrity, BuildLuaError> {
     let lua_version = args.lua_version;
     let progress = args.progress;
 
@@ -287,13 +310,23 @@ async fn do_build_lua(args: BuildLua<'_>) -> Result<(), BuildLuaError> {
         .expect("failed to create lua_installation temp directory")
         .into_path();
 
-    let (source_integrity, pkg_version): (Integrity, &str) = match lua_version {
+    let (known_integrity, known_version): (Integrity, &str) = match lua_version {
         LuaVersion::Lua51 => (LUA51_HASH.parse().unwrap(), LUA51_VERSION),
         LuaVersion::Lua52 => (LUA52_HASH.parse().unwrap(), LUA52_VERSION),
         LuaVersion::Lua53 => (LUA53_HASH.parse().unwrap(), LUA53_VERSION),
         LuaVersion::Lua54 => (LUA54_HASH.parse().unwrap(), LUA54_VERSION),
         LuaVersion::LuaJIT | LuaVersion::LuaJIT52 => unreachable!(),
     };
+    let pkg_version = args
+        .version
+        .map(|version| version.to_modrev_string())
+        .unwrap_or(known_version.to_string());
+    let source_integrity = if pkg_version == known_version {
+        Some(known_integrity)
+    } else {
+        args.integrity.cloned()
+    };
+    let pkg_version = pkg_version.as_str();
 
     let file_name = format!("lua-{pkg_version}.tar.gz");
 
@@ -303,7 +336,11 @@ async fn do_build_lua(args: BuildLua<'_>) -> Result<(), BuildLuaError> {
 
     progress.map(|p| p.set_message(format!("📥 Downloading {}", &source_url)));
 
-    let response = reqwest::get(source_url)
+    let response = args
+        .config
+        .http_client()?
+        .get(source_url)
+        .send()
         .await?
         .error_for_status()?
         .bytes()
@@ -311,22 +348,28 @@ async fn do_build_lua(args: BuildLua<'_>) -> Result<(), BuildLuaError> {
 
     let hash = response.hash()?;
 
-    if hash.matches(&source_integrity).is_none() {
-        return Err(BuildLuaError::SourceIntegrityMismatch {
-            expected: source_integrity,
-            actual: hash,
-        });
+    if let Some(source_integrity) = source_integrity {
+        if hash.matches(&source_integrity).is_none() {
+            return Err(BuildLuaError::SourceIntegrityMismatch {
+                expected: source_integrity,
+                actual: hash,
+            });
+        }
     }
 
     let cursor = Cursor::new(response);
     let mime_type = infer::get(cursor.get_ref()).map(|file_type| file_type.mime_type());
     operations::unpack::unpack(mime_type, cursor, true, file_name, &build_dir, progress).await?;
 
-    if cfg!(target_env = "msvc") {
-        do_build_lua_msvc(args, &build_dir, lua_version, pkg_version).await
+    if args.headers_only {
+        progress.map(|p| p.set_message(format!("💻 Installing Lua {pkg_version} headers")));
+        install_headers(&build_dir.join("src"), args.install_dir).await?;
+    } else if cfg!(target_env = "msvc") {
+        do_build_lua_msvc(args, &build_dir, lua_version, pkg_version).await?;
     } else {
-        do_build_lua_unix(args, &build_dir, lua_version, pkg_version).await
+        do_build_lua_unix(args, &build_dir, lua_version, pkg_version).await?;
     }
+    Ok(hash)
 }
 
 async fn do_build_lua_unix(
@@ -521,15 +564,15 @@ async fn do_build_lua_msvc(
     Ok(())
 }
 
+async fn install_headers(src_dir: &Path, install_dir: &Path) -> Result<(), BuildLuaError> {
+    let include_dir = install_dir.join("include");
+    fs::create_dir_all(&include_dir).await?;
+    copy_includes(src_dir, &include_dir).await?;
+    Ok(())
+}
+
 async fn copy_includes(src_dir: &Path, include_dir: &Path) -> Result<(), io::Error> {
-    for f in &[
-        "lauxlib.h",
-        "lua.h",
-        "luaconf.h",
-        "luajit.h",
-        "lualib.h",
-        "lua.hpp",
-    ] {
+    for f in LUA_HEADERS {
         let src_file = src_dir.join(f);
         if src_file.is_file() {
             fs::copy(src_file, include_dir.join(f)).await?;
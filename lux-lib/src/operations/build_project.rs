@@ -1,18 +1,25 @@
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
 use bon::Builder;
 use itertools::Itertools;
 use thiserror::Error;
 
 use crate::{
-    build::{Build, BuildBehaviour, BuildError},
+    build::{
+        compile_commands::CompileCommands, manifest::BuildManifest, Build, BuildBehaviour,
+        BuildError,
+    },
     config::Config,
     lockfile::LocalPackage,
     lua_installation::{LuaInstallation, LuaInstallationError},
     luarocks::luarocks_installation::{LuaRocksError, LuaRocksInstallError, LuaRocksInstallation},
     progress::{MultiProgress, Progress},
-    project::{project_toml::LocalProjectTomlValidationError, Project, ProjectTreeError},
+    project::{
+        constraints::ConstraintCatalogError, project_toml::LocalProjectTomlValidationError,
+        Project, ProjectError, ProjectTreeError,
+    },
     rockspec::Rockspec,
+    timings::Timings,
     tree::{self, TreeError},
 };
 
@@ -42,8 +49,16 @@ pub enum BuildProjectError {
     SyncBuildDependencies(SyncError),
     #[error("error building project:\n{0}")]
     Build(#[from] BuildError),
+    #[error("error updating the build manifest:\n{0}")]
+    BuildManifest(io::Error),
+    #[error(transparent)]
+    ConstraintCatalog(#[from] ConstraintCatalogError),
+    #[error(transparent)]
+    Project(#[from] ProjectError),
 }
 
+const BUILD_MANIFEST_FILE: &str = "build-manifest.json";
+
 #[derive(Builder)]
 #[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
 pub struct BuildProject<'a> {
@@ -61,6 +76,27 @@ pub struct BuildProject<'a> {
 
     #[builder(default = MultiProgress::new_arc())]
     progress: Arc<Progress<MultiProgress>>,
+
+    /// Record the time spent in each build phase
+    timings: Option<Timings>,
+
+    /// Record the commands that compile the project's C sources,
+    /// and those of dependencies that are built from source, if configured
+    compile_commands: Option<CompileCommands>,
+
+    /// Rebuild all modules, even if their sources haven't changed
+    #[builder(default)]
+    force: bool,
+
+    /// Symlink the project's Lua sources into the tree instead of copying them,
+    /// so that edits are picked up without rebuilding
+    #[builder(default)]
+    dev_link: bool,
+
+    /// Skip syncing the dependencies with the lockfile,
+    /// because they are known to be in sync.
+    #[builder(default)]
+    synced: bool,
 }
 
 impl<State: build_project_builder::State + build_project_builder::IsComplete>
@@ -92,12 +128,46 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
             .collect_vec();
 
         let build_tree = project.build_tree(config)?;
-        let lua =
-            LuaInstallation::new_from_config(config, &progress.map(|progress| progress.new_bar()))
-                .await?;
+        let bar = progress.map(|progress| progress.new_bar());
+        let lua = match project.toml().pinned_lua_version() {
+            Some(pinned_version) if args.no_lock => {
+                LuaInstallation::new_pinned(&pinned_version, None, config, &bar)
+                    .await?
+                    .0
+            }
+            Some(pinned_version) => {
+                let lockfile = project.lockfile()?;
+                let (lua, locked) =
+                    LuaInstallation::new_pinned(&pinned_version, lockfile.lua(), config, &bar)
+                        .await?;
+                if lockfile.lua() != Some(&locked) {
+                    lockfile.write_guard().set_lua(Some(locked));
+                }
+                lua
+            }
+            None => {
+                if !args.no_lock {
+                    if let Some(lockfile) = project
+                        .try_lockfile()?
+                        .filter(|lockfile| lockfile.lua().is_some())
+                    {
+                        lockfile.write_guard().set_lua(None);
+                    }
+                }
+                LuaInstallation::new_from_config(config, &bar).await?
+            }
+        };
         let luarocks = LuaRocksInstallation::new(config, build_tree.clone())?;
 
         if args.no_lock {
+            let (dependencies, build_dependencies) =
+                match project.constraint_catalog(config).await? {
+                    Some(catalog) => (
+                        catalog.apply(dependencies)?.0,
+                        catalog.apply(build_dependencies)?.0,
+                    ),
+                    None => (dependencies, build_dependencies),
+                };
             let dependencies_to_install = dependencies
                 .into_iter()
                 .filter(|dep| {
@@ -113,6 +183,9 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
                     .pin(*dep.pin())
                     .opt(*dep.opt())
                     .maybe_source(dep.source().clone())
+                    .variables(dep.variables().clone())
+                    .maybe_debug_symbols(dep.debug_symbols())
+                    .maybe_build_env(dep.build_env().cloned())
                     .build()
                 })
                 .collect();
@@ -120,7 +193,10 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
             Install::new(config)
                 .packages(dependencies_to_install)
                 .project(project)?
+                .check_conflicts(false)
                 .progress(progress.clone())
+                .maybe_timings(args.timings.clone())
+                .maybe_compile_commands(args.compile_commands.clone())
                 .install()
                 .await
                 .map_err(BuildProjectError::InstallDependencies)?;
@@ -140,6 +216,9 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
                     .pin(*dep.pin())
                     .opt(*dep.opt())
                     .maybe_source(dep.source().clone())
+                    .variables(dep.variables().clone())
+                    .maybe_debug_symbols(dep.debug_symbols())
+                    .maybe_build_env(dep.build_env().cloned())
                     .build()
                 })
                 .collect_vec();
@@ -150,26 +229,41 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
                 Install::new(config)
                     .packages(build_dependencies_to_install)
                     .tree(build_tree)
+                    .check_conflicts(false)
                     .progress(progress.clone())
+                    .maybe_timings(args.timings.clone())
                     .install()
                     .await
                     .map_err(BuildProjectError::InstallBuildDependencies)?;
             }
-        } else {
+        } else if !args.synced {
             Sync::new(project, config)
                 .progress(progress.clone())
+                .maybe_timings(args.timings.clone())
+                .maybe_compile_commands(args.compile_commands.clone())
                 .sync_dependencies()
                 .await
                 .map_err(BuildProjectError::SyncDependencies)?;
 
             Sync::new(project, config)
                 .progress(progress.clone())
+                .maybe_timings(args.timings.clone())
                 .sync_build_dependencies()
                 .await
                 .map_err(BuildProjectError::SyncBuildDependencies)?;
         }
 
         if !args.only_deps {
+            let manifest_path = project_tree.root().join(BUILD_MANIFEST_FILE);
+            let manifest = BuildManifest::from_project_files(project.root(), config)
+                .map_err(BuildProjectError::BuildManifest)?;
+            let project_changes = if args.force {
+                None
+            } else {
+                BuildManifest::load(&manifest_path)
+                    .and_then(|previous| manifest.changes_since(&previous))
+            };
+
             let package = Build::new()
                 .rockspec(&project_toml)
                 .lua(&lua)
@@ -178,9 +272,18 @@ impl<State: build_project_builder::State + build_project_builder::IsComplete>
                 .config(config)
                 .progress(&progress.map(|p| p.new_bar()))
                 .behaviour(BuildBehaviour::Force)
+                .maybe_timings(args.timings.clone())
+                .maybe_project_changes(project_changes)
+                .maybe_dev_link_root(args.dev_link.then(|| project.root().as_ref()))
+                .maybe_compile_commands(args.compile_commands.clone())
+                .source_root(project.root().as_ref())
                 .build()
                 .await?;
 
+            manifest
+                .write(&manifest_path)
+                .map_err(BuildProjectError::BuildManifest)?;
+
             let lockfile = project_tree.lockfile()?;
             let dependencies = lockfile
                 .rocks()
@@ -0,0 +1,333 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use bon::Builder;
+use itertools::Itertools;
+use thiserror::Error;
+use walkdir::WalkDir;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::{
+    config::Config,
+    lockfile::{LocalPackage, LocalPackageId},
+    project::{Project, ProjectTreeError},
+    tree::{Tree, TreeError},
+};
+
+/// The directory in a zip bundle that contains the Lua modules.
+const ZIP_MODULES_DIR: &str = "lua";
+/// The loader script in a zip bundle.
+const ZIP_LOADER: &str = "init.lua";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BundleFormat {
+    /// A single Lua file, which registers all modules in `package.preload`.
+    #[default]
+    Lua,
+    /// A zip archive with the Lua modules and an `init.lua` loader,
+    /// which adds them to the `package.path`.
+    Zip,
+}
+
+/// Bundles a built project and its pure Lua dependencies into a single distributable
+/// Lua file or zip archive, for environments that can't install rocks,
+/// like OpenResty or applications that embed Lua.
+///
+/// Packages that contain C modules can't be bundled.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Bundle<'a> {
+    #[builder(start_fn)]
+    dest_dir: PathBuf,
+    #[builder(start_fn)]
+    project: &'a Project,
+    /// The project package, as installed in the project tree.
+    #[builder(start_fn)]
+    package: LocalPackage,
+
+    config: &'a Config,
+
+    #[builder(default)]
+    format: BundleFormat,
+
+    /// The module to `require` when the bundle is loaded.
+    /// The bundle returns the module's value.
+    /// If not set, loading the bundle only makes the modules available to `require`.
+    entrypoint: Option<String>,
+}
+
+impl<State> BundleBuilder<'_, State>
+where
+    State: bundle_builder::State + bundle_builder::IsComplete,
+{
+    /// Write the bundle, returning its path.
+    pub fn bundle(self) -> Result<PathBuf, BundleError> {
+        do_bundle(self._build())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BundleError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Walkdir(#[from] walkdir::Error),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    ProjectTree(#[from] ProjectTreeError),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error("package {0} not found in the project tree's lockfile")]
+    MissingDependency(LocalPackageId),
+    #[error(
+        "cannot bundle {package}, because it contains the C module {module}.\n\
+        Only pure Lua packages can be bundled."
+    )]
+    CModule { package: String, module: String },
+    #[error("the module {module} is provided by both {package} and {other_package}")]
+    DuplicateModule {
+        module: String,
+        package: String,
+        other_package: String,
+    },
+    #[error("the entrypoint module {0} is not provided by the project or its dependencies")]
+    EntrypointNotFound(String),
+}
+
+/// A Lua module in the tree.
+#[derive(Debug)]
+struct BundledModule {
+    /// The module name, as passed to `require`.
+    name: String,
+    /// The path relative to the package's `src` directory.
+    relative_path: PathBuf,
+    path: PathBuf,
+}
+
+fn do_bundle(args: Bundle<'_>) -> Result<PathBuf, BundleError> {
+    let package = &args.package;
+    let tree = args.project.tree(args.config)?;
+    let lockfile = tree.lockfile()?;
+
+    let mut packages = vec![package.clone()];
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<LocalPackageId> = package.dependencies().into_iter().cloned().collect();
+    while let Some(id) = queue.pop_front() {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        let dependency = lockfile
+            .get(&id)
+            .ok_or_else(|| BundleError::MissingDependency(id.clone()))?;
+        queue.extend(dependency.dependencies().into_iter().cloned());
+        packages.push(dependency.clone());
+    }
+
+    let modules = collect_modules(&tree, &packages)?;
+    if let Some(entrypoint) = &args.entrypoint {
+        if !modules.iter().any(|module| &module.name == entrypoint) {
+            return Err(BundleError::EntrypointNotFound(entrypoint.clone()));
+        }
+    }
+
+    let header = format!(
+        "Bundled by Lux from {}",
+        packages
+            .iter()
+            .map(|package| format!("{}@{}", package.name(), package.version()))
+            .join(", ")
+    );
+    let file_name = format!("{}-{}.bundle", package.name(), package.version());
+    match args.format {
+        BundleFormat::Lua => {
+            let sources = modules
+                .iter()
+                .map(|module| Ok((module.name.clone(), std::fs::read_to_string(&module.path)?)))
+                .try_collect::<_, Vec<_>, io::Error>()?;
+            let output_path = args.dest_dir.join(format!("{file_name}.lua"));
+            std::fs::write(
+                &output_path,
+                lua_bundle(&header, &sources, args.entrypoint.as_deref()),
+            )?;
+            Ok(output_path)
+        }
+        BundleFormat::Zip => {
+            let output_path = args.dest_dir.join(format!("{file_name}.zip"));
+            let mut zip = ZipWriter::new(File::create(&output_path)?);
+            let options = SimpleFileOptions::default();
+            for module in &modules {
+                zip.start_file(
+                    Path::new(ZIP_MODULES_DIR)
+                        .join(&module.relative_path)
+                        .to_string_lossy(),
+                    options,
+                )?;
+                zip.write_all(&std::fs::read(&module.path)?)?;
+            }
+            zip.start_file(ZIP_LOADER, options)?;
+            zip.write_all(zip_loader(&header, args.entrypoint.as_deref()).as_bytes())?;
+            zip.finish()?;
+            Ok(output_path)
+        }
+    }
+}
+
+/// Collect the Lua modules of the `packages`, rejecting packages with C modules.
+fn collect_modules(
+    tree: &Tree,
+    packages: &[LocalPackage],
+) -> Result<Vec<BundledModule>, BundleError> {
+    let mut providers: HashMap<String, String> = HashMap::new();
+    let mut modules = Vec::new();
+    for package in packages {
+        let package_str = format!("{}@{}", package.name(), package.version());
+        let layout = tree.installed_rock_layout(package)?;
+        if layout.lib.is_dir() {
+            for entry in WalkDir::new(&layout.lib) {
+                let entry = entry?;
+                if entry.file_type().is_file() {
+                    return Err(BundleError::CModule {
+                        package: package_str,
+                        module: entry
+                            .path()
+                            .strip_prefix(&layout.lib)
+                            .unwrap_or(entry.path())
+                            .to_string_lossy()
+                            .to_string(),
+                    });
+                }
+            }
+        }
+        if !layout.src.is_dir() {
+            continue;
+        }
+        // Projects built with `--dev-link` have symlinked sources.
+        for entry in WalkDir::new(&layout.src)
+            .follow_links(true)
+            .sort_by_file_name()
+        {
+            let entry = entry?;
+            let path = entry.path();
+            if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "lua") {
+                continue;
+            }
+            let relative_path = path
+                .strip_prefix(&layout.src)
+                .expect("walkdir entry is not in the root directory")
+                .to_path_buf();
+            let name = module_name(&relative_path);
+            if let Some(other_package) = providers.insert(name.clone(), package_str.clone()) {
+                return Err(BundleError::DuplicateModule {
+                    module: name,
+                    package: package_str,
+                    other_package,
+                });
+            }
+            modules.push(BundledModule {
+                name,
+                relative_path,
+                path: path.to_path_buf(),
+            });
+        }
+    }
+    Ok(modules)
+}
+
+/// The name to `require` a module by, e.g. `foo.bar` for `foo/bar.lua` or `foo/bar/init.lua`.
+fn module_name(relative_path: &Path) -> String {
+    let without_extension = relative_path.with_extension("");
+    let without_init = if without_extension
+        .file_name()
+        .is_some_and(|name| name == "init")
+        && without_extension
+            .parent()
+            .is_some_and(|parent| !parent.as_os_str().is_empty())
+    {
+        without_extension.parent().unwrap().to_path_buf()
+    } else {
+        without_extension
+    };
+    without_init
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .join(".")
+}
+
+/// A Lua script that registers each of the `(module name, source)` pairs in `package.preload`.
+fn lua_bundle(header: &str, modules: &[(String, String)], entrypoint: Option<&str>) -> String {
+    let preloads = modules
+        .iter()
+        .map(|(name, source)| {
+            // A shebang is only valid in the first line of a chunk.
+            let source = match source.strip_prefix("#!") {
+                Some(rest) => rest.split_once('\n').map(|(_, rest)| rest).unwrap_or(""),
+                None => source,
+            };
+            format!("package.preload[{name:?}] = function(...)\n{source}\nend\n")
+        })
+        .join("\n");
+    let footer = match entrypoint {
+        Some(entrypoint) => format!("\nreturn require({entrypoint:?})\n"),
+        None => String::new(),
+    };
+    format!("-- {header}\n\n{preloads}{footer}")
+}
+
+/// A Lua script that adds the modules in the zip bundle's `lua` directory to the `package.path`.
+fn zip_loader(header: &str, entrypoint: Option<&str>) -> String {
+    let footer = match entrypoint {
+        Some(entrypoint) => format!("\nreturn require({entrypoint:?})\n"),
+        None => String::new(),
+    };
+    format!(
+        r#"-- {header}
+-- Load this file with `dofile` to add the bundled modules to the `package.path`.
+
+local root = debug.getinfo(1, "S").source:match("^@(.*)[/\\]") or "."
+package.path = root .. "/{ZIP_MODULES_DIR}/?.lua;" .. root .. "/{ZIP_MODULES_DIR}/?/init.lua;" .. package.path
+{footer}"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+
+    use super::*;
+
+    #[test]
+    fn bundle_module_names() {
+        assert_eq!(module_name(Path::new("foo.lua")), "foo");
+        assert_eq!(module_name(Path::new("foo/bar.lua")), "foo.bar");
+        assert_eq!(module_name(Path::new("foo/init.lua")), "foo");
+        assert_eq!(module_name(Path::new("init.lua")), "init");
+    }
+
+    #[test]
+    fn lua_bundle_requires_modules() {
+        let bundle = lua_bundle(
+            "test",
+            &[
+                (
+                    "foo".into(),
+                    "#!/usr/bin/env lua\nlocal bar = require('foo.bar')\nreturn { value = bar.value + 1 }".into(),
+                ),
+                ("foo.bar".into(), "return { value = 41 } -- no trailing newline".into()),
+            ],
+            Some("foo"),
+        );
+        let lua = Lua::new();
+        let value: i64 = lua
+            .load(&bundle)
+            .eval::<mlua::Table>()
+            .unwrap()
+            .get("value")
+            .unwrap();
+        assert_eq!(value, 42);
+    }
+}
@@ -0,0 +1,55 @@
+use std::future::Future;
+
+use thiserror::Error;
+
+pub use tokio_util::sync::CancellationToken;
+
+#[derive(Error, Debug, Clone, Copy)]
+#[error("operation cancelled")]
+pub struct Cancelled;
+
+/// Run `future` until it completes or `cancellation` is cancelled.
+/// On cancellation, the future is dropped, which kills the child processes it is waiting for
+/// and removes the temporary directories it owns.
+pub(crate) async fn run_cancellable<T, E>(
+    cancellation: Option<CancellationToken>,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E>
+where
+    E: From<Cancelled>,
+{
+    // The operations' futures are large, so we box them to avoid overflowing the stack
+    // when they are nested.
+    let future = Box::pin(future);
+    match cancellation {
+        Some(cancellation) => cancellation
+            .run_until_cancelled(future)
+            .await
+            .unwrap_or(Err(Cancelled.into())),
+        None => future.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancel_pending_future() {
+        let cancellation = CancellationToken::new();
+        let future = run_cancellable(Some(cancellation.clone()), async {
+            std::future::pending::<Result<(), Cancelled>>().await
+        });
+        cancellation.cancel();
+        assert!(matches!(future.await, Err(Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn uncancelled_future_completes() {
+        let result = run_cancellable(Some(CancellationToken::new()), async {
+            Ok::<_, Cancelled>(42)
+        })
+        .await;
+        assert_eq!(result.unwrap(), 42);
+    }
+}
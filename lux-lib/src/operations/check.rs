@@ -0,0 +1,342 @@
+use std::{collections::HashSet, fmt::Display, path::PathBuf};
+
+use bon::Builder;
+use itertools::Itertools;
+use path_slash::PathExt;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    analysis::{require_graph, RequireGraphError},
+    config::Config,
+    package::{PackageName, PackageVersionReq},
+    project::{project_toml::LocalProjectTomlValidationError, Project, ProjectTreeError},
+    rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
+    tree::TreeError,
+    which::ModuleLocation,
+};
+
+/// The `[check]` section of a `lux.toml`.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct CheckSpec {
+    /// Modules that may be required without a declared dependency, e.g. `vim` for Neovim plugins.
+    /// An entry also allows its submodules, so `foo` allows `foo.bar`.
+    #[serde(default)]
+    pub(crate) allow_modules: Vec<String>,
+    /// Dependencies that are never reported as unused, e.g. ones that only provide executables.
+    #[serde(default)]
+    pub(crate) allow_unused: Vec<PackageName>,
+}
+
+impl CheckSpec {
+    fn allows_module(&self, module: &str) -> bool {
+        self.allow_modules.iter().any(|allowed| {
+            module
+                .strip_prefix(allowed.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+    }
+}
+
+/// A dependency section of a `lux.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DependencySection {
+    Dependencies,
+    BuildDependencies,
+    TestDependencies,
+}
+
+impl Display for DependencySection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dependencies => "dependencies",
+            Self::BuildDependencies => "build_dependencies",
+            Self::TestDependencies => "test_dependencies",
+        }
+        .fmt(f)
+    }
+}
+
+/// A dependency hygiene problem in a project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectIssue {
+    /// A module is required from an installed package that is not declared in the `lux.toml`.
+    UndeclaredDependency {
+        module: String,
+        package: PackageName,
+        /// The first file that requires the module, relative to the project root.
+        file: PathBuf,
+        line: usize,
+    },
+    /// A dependency is installed, but none of its modules are required by the project's sources.
+    UnusedDependency(PackageName),
+    /// A package is declared with the same constraint in more than one dependency section.
+    DuplicateDependency {
+        package: PackageName,
+        sections: Vec<DependencySection>,
+    },
+    /// A package is declared with different constraints in more than one dependency section.
+    OverlappingConstraints {
+        package: PackageName,
+        constraints: Vec<(DependencySection, PackageVersionReq)>,
+    },
+}
+
+impl Display for ProjectIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UndeclaredDependency {
+                module,
+                package,
+                file,
+                line,
+            } => write!(
+                f,
+                "{}:{}: undeclared dependency: `{}` is provided by {}, which is not declared in the lux.toml",
+                file.to_slash_lossy(),
+                line,
+                module,
+                package
+            ),
+            Self::UnusedDependency(package) => write!(
+                f,
+                "unused dependency: none of the modules of {package} are required by the project"
+            ),
+            Self::DuplicateDependency { package, sections } => write!(
+                f,
+                "duplicate dependency: {} is declared in {}",
+                package,
+                sections.iter().join(" and ")
+            ),
+            Self::OverlappingConstraints {
+                package,
+                constraints,
+            } => write!(
+                f,
+                "overlapping constraints: {} is declared as {}",
+                package,
+                constraints
+                    .iter()
+                    .map(|(section, req)| format!("`{req}` in {section}"))
+                    .join(" and ")
+            ),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ProjectCheckError {
+    #[error(transparent)]
+    LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
+    #[error(transparent)]
+    ProjectTree(#[from] ProjectTreeError),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    RequireGraph(#[from] RequireGraphError),
+}
+
+/// Checks a project's declared dependencies against the modules its Lua files require.
+///
+/// Required modules are resolved against the installed packages,
+/// so modules of packages that are not installed can't be attributed to a dependency.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct ProjectCheck<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+    #[builder(start_fn)]
+    config: &'a Config,
+}
+
+impl<State: project_check_builder::State> ProjectCheckBuilder<'_, State> {
+    /// Returns the issues found, or an empty list if the project's dependencies are tidy.
+    pub fn check(self) -> Result<Vec<ProjectIssue>, ProjectCheckError>
+    where
+        State: project_check_builder::IsComplete,
+    {
+        do_check(self._build())
+    }
+}
+
+fn do_check(check: ProjectCheck<'_>) -> Result<Vec<ProjectIssue>, ProjectCheckError> {
+    let project = check.project;
+    let config = check.config;
+    let project_toml = project.toml().into_local()?;
+    let spec = project_toml.check();
+
+    let sections = [
+        (
+            DependencySection::Dependencies,
+            project_toml.dependencies().current_platform(),
+        ),
+        (
+            DependencySection::BuildDependencies,
+            project_toml.build_dependencies().current_platform(),
+        ),
+        (
+            DependencySection::TestDependencies,
+            project_toml.test_dependencies().current_platform(),
+        ),
+    ];
+    let declared: HashSet<&PackageName> = sections
+        .iter()
+        .flat_map(|(_, dependencies)| dependencies.iter().map(|dep| dep.name()))
+        .chain(std::iter::once(project_toml.package()))
+        .collect();
+
+    let graph = require_graph(project, config)?;
+    let mut issues = Vec::new();
+    let mut used = HashSet::new();
+    let mut seen = HashSet::new();
+    for (file, require) in graph
+        .files()
+        .iter()
+        .flat_map(|(file, requires)| requires.iter().map(move |require| (file, require)))
+    {
+        let module = &require.module;
+        if !seen.insert(module) || spec.allows_module(module) {
+            continue;
+        }
+        let matches = graph.modules().get(module).map_or(&[][..], Vec::as_slice);
+        match matches.first() {
+            None => {}
+            Some(first) if first.location == ModuleLocation::ProjectSources => {}
+            Some(first) => {
+                let providers = matches
+                    .iter()
+                    .map(|module_match| &module_match.package)
+                    .filter(|package| declared.contains(package))
+                    .cloned()
+                    .collect_vec();
+                if providers.is_empty() {
+                    issues.push(ProjectIssue::UndeclaredDependency {
+                        module: module.clone(),
+                        package: first.package.clone(),
+                        file: file.clone(),
+                        line: require.line,
+                    });
+                } else {
+                    used.extend(providers);
+                }
+            }
+        }
+    }
+
+    let project_tree = project.tree(config)?;
+    for dependency in project_toml
+        .dependencies()
+        .current_platform()
+        .iter()
+        .sorted_by_key(|dep| dep.name())
+    {
+        if used.contains(dependency.name()) || spec.allow_unused.contains(dependency.name()) {
+            continue;
+        }
+        // We can only tell which modules a dependency provides if it is installed.
+        if project_tree
+            .match_rocks(dependency.package_req())?
+            .is_found()
+        {
+            issues.push(ProjectIssue::UnusedDependency(dependency.name().clone()));
+        }
+    }
+
+    issues.extend(check_constraints(&sections));
+    Ok(issues)
+}
+
+/// Finds packages that are declared in more than one dependency section.
+fn check_constraints(
+    sections: &[(DependencySection, &Vec<LuaDependencySpec>)],
+) -> Vec<ProjectIssue> {
+    sections
+        .iter()
+        .flat_map(|(section, dependencies)| {
+            dependencies
+                .iter()
+                .map(move |dep| (dep.name(), (*section, dep.version_req())))
+        })
+        .into_group_map()
+        .into_iter()
+        .filter(|(_, constraints)| constraints.len() > 1)
+        .sorted_by_key(|(package, _)| *package)
+        .map(|(package, constraints)| {
+            if constraints.iter().map(|(_, req)| req).all_equal() {
+                ProjectIssue::DuplicateDependency {
+                    package: package.clone(),
+                    sections: constraints
+                        .into_iter()
+                        .map(|(section, _)| section)
+                        .collect(),
+                }
+            } else {
+                ProjectIssue::OverlappingConstraints {
+                    package: package.clone(),
+                    constraints: constraints
+                        .into_iter()
+                        .map(|(section, req)| (section, req.clone()))
+                        .collect(),
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::package::PackageReq;
+
+    use super::*;
+
+    #[test]
+    fn allow_modules_with_submodules() {
+        let spec = CheckSpec {
+            allow_modules: vec!["vim".into()],
+            allow_unused: Vec::new(),
+        };
+        assert!(spec.allows_module("vim"));
+        assert!(spec.allows_module("vim.lsp"));
+        assert!(!spec.allows_module("vimscript"));
+    }
+
+    #[test]
+    fn check_duplicate_and_overlapping_constraints() {
+        let dependencies = vec![
+            LuaDependencySpec::from(PackageReq::parse("foo >= 1.0").unwrap()),
+            LuaDependencySpec::from(PackageReq::parse("bar >= 1.0").unwrap()),
+        ];
+        let test_dependencies = vec![
+            LuaDependencySpec::from(PackageReq::parse("foo >= 1.0").unwrap()),
+            LuaDependencySpec::from(PackageReq::parse("bar >= 2.0").unwrap()),
+            LuaDependencySpec::from(PackageReq::parse("busted").unwrap()),
+        ];
+        let issues = check_constraints(&[
+            (DependencySection::Dependencies, &dependencies),
+            (DependencySection::TestDependencies, &test_dependencies),
+        ]);
+        assert_eq!(
+            issues,
+            vec![
+                ProjectIssue::OverlappingConstraints {
+                    package: "bar".into(),
+                    constraints: vec![
+                        (DependencySection::Dependencies, ">=1.0".parse().unwrap()),
+                        (
+                            DependencySection::TestDependencies,
+                            ">=2.0".parse().unwrap()
+                        ),
+                    ],
+                },
+                ProjectIssue::DuplicateDependency {
+                    package: "foo".into(),
+                    sections: vec![
+                        DependencySection::Dependencies,
+                        DependencySection::TestDependencies
+                    ],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Display,
+    sync::Arc,
+};
+
+use bon::Builder;
+use thiserror::Error;
+use which::which;
+
+use crate::{
+    build::{
+        external_dependency::ExternalDependencyInfo, system_package::SystemPackageHint, utils,
+    },
+    config::{Config, LuaVersion, LuaVersionUnset},
+    lua_installation::LuaInstallation,
+    lua_rockspec::{BuildBackendSpec, BuildSpec, ModuleSpec},
+    package::{PackageName, PackageReq, PackageSpec},
+    progress::{MultiProgress, Progress},
+    project::{project_toml::LocalProjectTomlValidationError, Project, ProjectTreeError},
+    remote_package_db::{RemotePackageDB, RemotePackageDBError},
+    rockspec::{lua_dependency::LuaDependencySpec, LuaVersionCompatibility, Rockspec},
+    tree::TreeError,
+    variables::{self, Environment, GetVariableError, HasVariables},
+};
+
+use super::{Download, RemoteRockDownload};
+
+/// A package that would be built.
+#[derive(Debug, Clone)]
+pub struct PlannedBuild {
+    pub package: PackageSpec,
+    /// The build backend, e.g. `builtin` or `make`.
+    pub backend: String,
+    /// Whether the package would be installed to the build tree.
+    pub build_dependency: bool,
+}
+
+impl Display for PlannedBuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.build_dependency {
+            write!(f, "{} ({}, build dependency)", self.package, self.backend)
+        } else {
+            write!(f, "{} ({})", self.package, self.backend)
+        }
+    }
+}
+
+/// A missing prerequisite, or a problem with a rockspec, that would make a build fail.
+#[derive(Debug, Clone)]
+pub enum BuildCheckIssue {
+    /// A dependency could not be resolved, or its rockspec could not be fetched.
+    UnresolvedDependency { package: PackageReq, error: String },
+    /// A package does not support the Lua version it would be built for.
+    UnsupportedLuaVersion {
+        package: PackageSpec,
+        lua_version: LuaVersion,
+    },
+    /// A program that the package's build backend runs is not installed.
+    MissingProgram {
+        package: PackageSpec,
+        backend: String,
+        program: String,
+    },
+    /// An external dependency could not be found.
+    MissingExternalDependency {
+        package: PackageSpec,
+        error: String,
+        hint: Option<SystemPackageHint>,
+    },
+    /// A variable in the package's build spec could not be substituted.
+    UnresolvedVariable { package: PackageSpec, error: String },
+}
+
+impl Display for BuildCheckIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnresolvedDependency { package, error } => {
+                write!(f, "could not resolve {package}: {error}")
+            }
+            Self::UnsupportedLuaVersion {
+                package,
+                lua_version,
+            } => write!(f, "{package} does not support Lua {lua_version}"),
+            Self::MissingProgram {
+                package,
+                backend,
+                program,
+            } => write!(
+                f,
+                "{package}: the {backend} build backend requires {program}, which is not installed"
+            ),
+            Self::MissingExternalDependency {
+                package,
+                error,
+                hint: Some(hint),
+            } => write!(f, "{package}: {error}\n{hint}"),
+            Self::MissingExternalDependency {
+                package,
+                error,
+                hint: None,
+            } => write!(f, "{package}: {error}"),
+            Self::UnresolvedVariable { package, error } => write!(f, "{package}: {error}"),
+        }
+    }
+}
+
+/// What a build would do, without compiling or installing anything.
+#[derive(Debug, Clone, Default)]
+pub struct BuildCheckReport {
+    /// The packages that would be built, with the project last.
+    pub planned: Vec<PlannedBuild>,
+    /// The Lua version that would be installed, if it is not installed yet.
+    pub install_lua: Option<LuaVersion>,
+    pub issues: Vec<BuildCheckIssue>,
+}
+
+#[derive(Error, Debug)]
+pub enum CheckBuildError {
+    #[error(transparent)]
+    LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
+    #[error(transparent)]
+    ProjectTree(#[from] ProjectTreeError),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    LuaVersionUnset(#[from] LuaVersionUnset),
+    #[error("error initialising remote package DB: {0}")]
+    RemotePackageDB(#[from] RemotePackageDBError),
+}
+
+/// Simulates a project build: resolves the dependencies that are not installed
+/// and fetches their rockspecs, then validates the build specs of the dependencies
+/// and of the project, without fetching sources, compiling or installing anything.
+///
+/// Build specs are validated by checking that the programs their build backends run
+/// are installed, probing their external dependencies and substituting their variables.
+/// Dependencies are resolved against the package database, ignoring the project lockfile.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct CheckBuild<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    #[builder(default = MultiProgress::new_arc())]
+    progress: Arc<Progress<MultiProgress>>,
+}
+
+impl<State: check_build_builder::State> CheckBuildBuilder<'_, State> {
+    pub async fn check(self) -> Result<BuildCheckReport, CheckBuildError>
+    where
+        State: check_build_builder::IsComplete,
+    {
+        do_check_build(self._build()).await
+    }
+}
+
+async fn do_check_build(args: CheckBuild<'_>) -> Result<BuildCheckReport, CheckBuildError> {
+    let project = args.project;
+    let config = args.config;
+    let project_toml = project.toml().into_local()?;
+    let project_tree = project.tree(config)?;
+    let build_tree = project.build_tree(config)?;
+    let lua_version = LuaVersion::from(config)?.clone();
+    let lua = LuaInstallation::find(&lua_version, config);
+    let bar = args.progress.map(|p| p.new_bar());
+
+    let mut report = BuildCheckReport {
+        install_lua: lua.is_none().then(|| lua_version.clone()),
+        ..BuildCheckReport::default()
+    };
+
+    let mut queue: VecDeque<(LuaDependencySpec, bool)> = project_toml
+        .dependencies()
+        .current_platform()
+        .iter()
+        .map(|dep| (dep.clone(), false))
+        .chain(
+            project_toml
+                .build_dependencies()
+                .current_platform()
+                .iter()
+                .map(|dep| (dep.clone(), true)),
+        )
+        .collect();
+    let mut seen: HashSet<(PackageName, bool)> = HashSet::new();
+    let mut package_db = None;
+    while let Some((dep, build_dependency)) = queue.pop_front() {
+        let tree = if build_dependency {
+            &build_tree
+        } else {
+            &project_tree
+        };
+        if !seen.insert((dep.name().clone(), build_dependency))
+            || tree.match_rocks(dep.package_req())?.is_found()
+        {
+            continue;
+        }
+        let rockspec = match dep.source() {
+            Some(source) => RemoteRockDownload::from_package_req_and_source_spec(
+                dep.package_req().clone(),
+                source.clone(),
+            )
+            .map(|download| download.rockspec().clone()),
+            None => {
+                if package_db.is_none() {
+                    package_db = Some(RemotePackageDB::from_config(config, &bar).await?);
+                }
+                Download::new(dep.package_req(), config, &bar)
+                    .package_db(package_db.as_ref().expect("package DB was initialised"))
+                    .download_rockspec()
+                    .await
+                    .map(|download| download.rockspec)
+            }
+        };
+        let rockspec = match rockspec {
+            Ok(rockspec) => rockspec,
+            Err(err) => {
+                report.issues.push(BuildCheckIssue::UnresolvedDependency {
+                    package: dep.package_req().clone(),
+                    error: err.to_string(),
+                });
+                continue;
+            }
+        };
+        let dep_config = config.clone().with_variables(dep.variables().clone());
+        report.issues.extend(check_rockspec(
+            &rockspec,
+            &lua_version,
+            lua.as_ref(),
+            &dep_config,
+        ));
+        report.planned.push(PlannedBuild {
+            package: PackageSpec::new(rockspec.package().clone(), rockspec.version().clone()),
+            backend: backend_name(rockspec.build().current_platform()),
+            build_dependency,
+        });
+        // Transitive dependencies of build dependencies are installed to the build tree.
+        queue.extend(
+            rockspec
+                .dependencies()
+                .current_platform()
+                .iter()
+                .map(|dep| (dep.clone(), build_dependency))
+                .chain(
+                    rockspec
+                        .build_dependencies()
+                        .current_platform()
+                        .iter()
+                        .map(|dep| (dep.clone(), true)),
+                ),
+        );
+    }
+
+    report.issues.extend(check_rockspec(
+        &project_toml,
+        &lua_version,
+        lua.as_ref(),
+        config,
+    ));
+    report.planned.push(PlannedBuild {
+        package: PackageSpec::new(
+            project_toml.package().clone(),
+            project_toml.version().clone(),
+        ),
+        backend: backend_name(project_toml.build().current_platform()),
+        build_dependency: false,
+    });
+
+    Ok(report)
+}
+
+/// Validates a rockspec's build spec for the current platform, as far as possible without its sources.
+fn check_rockspec<R: Rockspec>(
+    rockspec: &R,
+    lua_version: &LuaVersion,
+    lua: Option<&LuaInstallation>,
+    config: &Config,
+) -> Vec<BuildCheckIssue> {
+    let package = PackageSpec::new(rockspec.package().clone(), rockspec.version().clone());
+    let build_spec = rockspec.build().current_platform();
+    let backend = backend_name(build_spec);
+    let config = config
+        .clone()
+        .with_package_toolchain(build_spec.toolchain.as_ref());
+    let mut issues = Vec::new();
+
+    if !rockspec.supports_lua_version(lua_version) {
+        issues.push(BuildCheckIssue::UnsupportedLuaVersion {
+            package: package.clone(),
+            lua_version: lua_version.clone(),
+        });
+    }
+
+    let program = match &build_spec.build_backend {
+        Some(BuildBackendSpec::Make(_)) => Some(config.make_cmd()),
+        Some(BuildBackendSpec::CMake(_)) => Some(config.cmake_cmd()),
+        Some(BuildBackendSpec::RustMlua(_)) => Some("cargo".into()),
+        _ => None,
+    };
+    if let Some(program) = program.filter(|program| which(program).is_err()) {
+        issues.push(BuildCheckIssue::MissingProgram {
+            package: package.clone(),
+            backend: backend.clone(),
+            program: format!("`{program}`"),
+        });
+    }
+    if needs_c_compiler(build_spec) && utils::find_c_compiler(&config).is_none() {
+        issues.push(BuildCheckIssue::MissingProgram {
+            package: package.clone(),
+            backend: backend.clone(),
+            program: "a C compiler".into(),
+        });
+    }
+
+    let mut external_dependencies = HashMap::new();
+    let mut missing_external_dependencies = Vec::new();
+    for (name, dep) in rockspec.external_dependencies().current_platform() {
+        match ExternalDependencyInfo::probe(name, dep, config.external_deps()) {
+            Ok(info) => {
+                external_dependencies.insert(name.clone(), info);
+            }
+            Err(err) => {
+                missing_external_dependencies.push(name.clone());
+                issues.push(BuildCheckIssue::MissingExternalDependency {
+                    package: package.clone(),
+                    hint: err
+                        .dependency_name()
+                        .and_then(|name| SystemPackageHint::new(name, config.external_deps())),
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+
+    let build_time_variables = BuildTimeVariables {
+        lua_installed: lua.is_some(),
+        missing_external_dependencies,
+    };
+    let mut substitutions: Vec<&dyn HasVariables> = vec![&build_time_variables];
+    if let Some(lua) = lua {
+        substitutions.push(lua);
+    }
+    substitutions.extend([
+        &external_dependencies as &dyn HasVariables,
+        &Environment {},
+        &config,
+    ]);
+    for value in substituted_values(build_spec) {
+        if let Err(err) = variables::substitute(&substitutions, value) {
+            issues.push(BuildCheckIssue::UnresolvedVariable {
+                package: package.clone(),
+                error: err.to_string(),
+            });
+        }
+    }
+
+    issues
+}
+
+fn backend_name(build_spec: &BuildSpec) -> String {
+    match &build_spec.build_backend {
+        Some(BuildBackendSpec::Builtin(_)) => "builtin".into(),
+        Some(BuildBackendSpec::Make(_)) => "make".into(),
+        Some(BuildBackendSpec::CMake(_)) => "cmake".into(),
+        Some(BuildBackendSpec::Command(_)) => "command".into(),
+        Some(BuildBackendSpec::LuaRock(build_type)) => build_type.clone(),
+        Some(BuildBackendSpec::RustMlua(_)) => "rust-mlua".into(),
+        Some(BuildBackendSpec::TreesitterParser(_)) => "treesitter-parser".into(),
+        Some(BuildBackendSpec::Source) => "source".into(),
+        None => "none".into(),
+    }
+}
+
+/// Whether building the package compiles C sources with the configured C compiler.
+fn needs_c_compiler(build_spec: &BuildSpec) -> bool {
+    let compiles_modules = match &build_spec.build_backend {
+        Some(BuildBackendSpec::Builtin(spec)) => spec.modules.values().any(|module| match module {
+            ModuleSpec::SourcePath(source) => source.extension().is_some_and(|ext| ext == "c"),
+            ModuleSpec::SourcePaths(_) | ModuleSpec::ModulePaths(_) => true,
+        }),
+        Some(BuildBackendSpec::TreesitterParser(spec)) => spec.parser,
+        _ => false,
+    };
+    compiles_modules || !build_spec.install.lib.is_empty()
+}
+
+/// The values in the build spec that are subject to variable substitution.
+fn substituted_values(build_spec: &BuildSpec) -> Vec<&String> {
+    match &build_spec.build_backend {
+        Some(BuildBackendSpec::Make(spec)) => spec
+            .variables
+            .values()
+            .chain(spec.build_variables.values())
+            .chain(spec.install_variables.values())
+            .filter(|value| !value.is_empty())
+            .collect(),
+        Some(BuildBackendSpec::CMake(spec)) => spec.variables.values().collect(),
+        Some(BuildBackendSpec::Command(spec)) => spec
+            .build_command
+            .iter()
+            .chain(spec.install_command.iter())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Variables that are only known once a package is being built,
+/// e.g. its installation prefix, or the paths of a Lua installation that has yet to be installed.
+struct BuildTimeVariables {
+    lua_installed: bool,
+    missing_external_dependencies: Vec<String>,
+}
+
+impl HasVariables for BuildTimeVariables {
+    fn get_variable(&self, input: &str) -> Result<Option<String>, GetVariableError> {
+        let is_build_time_variable = match input {
+            "PREFIX" | "LIBDIR" | "LUADIR" | "BINDIR" | "CONFDIR" | "DOCDIR" => true,
+            "LUA_INCDIR" | "LUA_LIBDIR" | "LUA_BINDIR" | "LUA" | "LUALIB" => !self.lua_installed,
+            _ => input
+                .split_once('_')
+                .is_some_and(|(dep_key, dep_dir_type)| {
+                    matches!(dep_dir_type, "DIR" | "INCDIR" | "LIBDIR" | "BINDIR")
+                        && self
+                            .missing_external_dependencies
+                            .iter()
+                            .any(|name| name == dep_key)
+                }),
+        };
+        Ok(is_build_time_variable.then(String::new))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ConfigBuilder;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn check_build_reports_unresolved_variables() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            temp.join("lux.toml"),
+            r#"
+package = "check-build"
+version = "0.1.0"
+lua = ">=5.1"
+
+[build]
+type = "command"
+build_command = "echo $(PREFIX) $(LUA) $(CHECK_BUILD_UNDEFINED)"
+"#,
+        )
+        .unwrap();
+        let project = Project::from(temp.path()).unwrap().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.join("tree")))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+
+        let report = CheckBuild::new(&project, &config).check().await.unwrap();
+        assert_eq!(report.planned.len(), 1);
+        assert_eq!(report.planned[0].backend, "command");
+        assert_eq!(report.issues.len(), 1);
+        assert!(matches!(
+            &report.issues[0],
+            BuildCheckIssue::UnresolvedVariable { error, .. } if error.contains("CHECK_BUILD_UNDEFINED")
+        ));
+    }
+}
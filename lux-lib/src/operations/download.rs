@@ -6,12 +6,14 @@ use std::{
 
 use bon::Builder;
 use bytes::Bytes;
+use reqwest::Method;
 use thiserror::Error;
 use url::{ParseError, Url};
 
 use crate::{
     config::Config,
     git::GitSource,
+    http::InsecureServerError,
     lockfile::RemotePackageSourceUrl,
     lua_rockspec::{LuaRockspecError, RemoteLuaRockspec, RockSourceSpec},
     luarocks,
@@ -25,12 +27,15 @@ use crate::{
     rockspec::Rockspec,
 };
 
+use super::{run_cancellable, CancellationToken, Cancelled};
+
 /// Builder for a rock downloader.
 pub struct Download<'a> {
     package_req: &'a PackageReq,
     package_db: Option<&'a RemotePackageDB>,
     config: &'a Config,
     progress: &'a Progress<ProgressBar>,
+    cancellation: Option<CancellationToken>,
 }
 
 impl<'a> Download<'a> {
@@ -45,6 +50,7 @@ impl<'a> Download<'a> {
             package_db: None,
             config,
             progress,
+            cancellation: None,
         }
     }
 
@@ -57,59 +63,100 @@ impl<'a> Download<'a> {
         }
     }
 
+    /// Abort the download when cancelled.
+    pub fn cancellation(self, cancellation: CancellationToken) -> Self {
+        Self {
+            cancellation: Some(cancellation),
+            ..self
+        }
+    }
+
     /// Download the package's Rockspec.
     pub async fn download_rockspec(self) -> Result<DownloadedRockspec, SearchAndDownloadError> {
-        match self.package_db {
-            Some(db) => download_rockspec(self.package_req, db, self.progress).await,
-            None => {
-                let db = RemotePackageDB::from_config(self.config, self.progress).await?;
-                download_rockspec(self.package_req, &db, self.progress).await
+        run_cancellable(self.cancellation.clone(), async {
+            match self.package_db {
+                Some(db) => {
+                    download_rockspec(self.package_req, db, self.config, self.progress).await
+                }
+                None => {
+                    let db = RemotePackageDB::from_config(self.config, self.progress).await?;
+                    download_rockspec(self.package_req, &db, self.config, self.progress).await
+                }
             }
-        }
+        })
+        .await
     }
 
     /// Download a `.src.rock` to a file.
     /// `destination_dir` defaults to the current working directory if not set.
+    /// If the file already exists, it is not downloaded again.
     pub async fn download_src_rock_to_file(
         self,
         destination_dir: Option<PathBuf>,
     ) -> Result<DownloadedPackedRock, SearchAndDownloadError> {
-        match self.package_db {
-            Some(db) => {
-                download_src_rock_to_file(self.package_req, destination_dir, db, self.progress)
+        run_cancellable(self.cancellation.clone(), async {
+            match self.package_db {
+                Some(db) => {
+                    download_src_rock_to_file(
+                        self.package_req,
+                        destination_dir,
+                        db,
+                        self.config,
+                        self.progress,
+                    )
                     .await
-            }
-            None => {
-                let db = RemotePackageDB::from_config(self.config, self.progress).await?;
-                download_src_rock_to_file(self.package_req, destination_dir, &db, self.progress)
+                }
+                None => {
+                    let db = RemotePackageDB::from_config(self.config, self.progress).await?;
+                    download_src_rock_to_file(
+                        self.package_req,
+                        destination_dir,
+                        &db,
+                        self.config,
+                        self.progress,
+                    )
                     .await
+                }
             }
-        }
+        })
+        .await
     }
 
     /// Search for a `.src.rock` and download it to memory.
     pub async fn search_and_download_src_rock(
         self,
     ) -> Result<DownloadedPackedRockBytes, SearchAndDownloadError> {
-        match self.package_db {
-            Some(db) => search_and_download_src_rock(self.package_req, db, self.progress).await,
-            None => {
-                let db = RemotePackageDB::from_config(self.config, self.progress).await?;
-                search_and_download_src_rock(self.package_req, &db, self.progress).await
+        run_cancellable(self.cancellation.clone(), async {
+            match self.package_db {
+                Some(db) => {
+                    search_and_download_src_rock(self.package_req, db, self.config, self.progress)
+                        .await
+                }
+                None => {
+                    let db = RemotePackageDB::from_config(self.config, self.progress).await?;
+                    search_and_download_src_rock(self.package_req, &db, self.config, self.progress)
+                        .await
+                }
             }
-        }
+        })
+        .await
     }
 
     pub(crate) async fn download_remote_rock(
         self,
     ) -> Result<RemoteRockDownload, SearchAndDownloadError> {
-        match self.package_db {
-            Some(db) => download_remote_rock(self.package_req, db, self.progress).await,
-            None => {
-                let db = RemotePackageDB::from_config(self.config, self.progress).await?;
-                download_remote_rock(self.package_req, &db, self.progress).await
+        run_cancellable(self.cancellation.clone(), async {
+            match self.package_db {
+                Some(db) => {
+                    download_remote_rock(self.package_req, db, self.config, self.progress).await
+                }
+                None => {
+                    let db = RemotePackageDB::from_config(self.config, self.progress).await?;
+                    download_remote_rock(self.package_req, &db, self.config, self.progress).await
+                }
             }
-        }
+        })
+        .await
     }
 }
 
@@ -125,6 +172,9 @@ pub struct DownloadedPackedRock {
     pub name: PackageName,
     pub version: PackageVersion,
     pub path: PathBuf,
+    /// Whether the rock was already present in the destination directory,
+    /// in which case it was not downloaded again.
+    pub cached: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -198,6 +248,10 @@ impl RemoteRockDownload {
 pub enum DownloadRockspecError {
     #[error("failed to download rockspec: {0}")]
     Request(#[from] reqwest::Error),
+    #[error("failed to parse rockspec URL: {0}")]
+    Url(#[from] ParseError),
+    #[error(transparent)]
+    InsecureServer(#[from] InsecureServerError),
     #[error("failed to convert rockspec response: {0}")]
     ResponseConversion(#[from] FromUtf8Error),
     #[error("error initialising remote package DB: {0}")]
@@ -210,9 +264,10 @@ pub enum DownloadRockspecError {
 async fn download_rockspec(
     package_req: &PackageReq,
     package_db: &RemotePackageDB,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedRockspec, SearchAndDownloadError> {
-    let rockspec = match download_remote_rock(package_req, package_db, progress).await? {
+    let rockspec = match download_remote_rock(package_req, package_db, config, progress).await? {
         RemoteRockDownload::RockspecOnly {
             rockspec_download: rockspec,
         } => rockspec,
@@ -231,6 +286,7 @@ async fn download_rockspec(
 async fn download_remote_rock(
     package_req: &PackageReq,
     package_db: &RemotePackageDB,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<RemoteRockDownload, SearchAndDownloadError> {
     let remote_package = package_db.find(package_req, None, progress)?;
@@ -239,7 +295,14 @@ async fn download_remote_rock(
         RemotePackageSource::LuarocksRockspec(url) => {
             let package = &remote_package.package;
             let rockspec_name = format!("{}-{}.rockspec", package.name(), package.version());
-            let bytes = reqwest::get(format!("{}/{}", &url, rockspec_name))
+            let url = Url::parse(&format!("{}/{}", &url, rockspec_name))
+                .map_err(DownloadRockspecError::Url)?;
+            let bytes = config
+                .http_client()
+                .map_err(DownloadRockspecError::Request)?
+                .server_request(Method::GET, url)
+                .map_err(DownloadRockspecError::InsecureServer)?
+                .send()
                 .await
                 .map_err(DownloadRockspecError::Request)?
                 .error_for_status()
@@ -275,7 +338,7 @@ async fn download_remote_rock(
             } else {
                 url
             };
-            let rock = download_binary_rock(&remote_package.package, url, progress).await?;
+            let rock = download_binary_rock(&remote_package.package, url, config, progress).await?;
             let rockspec = DownloadedRockspec {
                 rockspec: unpack_rockspec(&rock).await?,
                 source: remote_package.source,
@@ -294,7 +357,7 @@ async fn download_remote_rock(
             } else {
                 url.clone()
             };
-            let rock = download_src_rock(&remote_package.package, &url, progress).await?;
+            let rock = download_src_rock(&remote_package.package, &url, config, progress).await?;
             let rockspec = DownloadedRockspec {
                 rockspec: unpack_rockspec(&rock).await?,
                 source: remote_package.source,
@@ -314,6 +377,8 @@ async fn download_remote_rock(
 
 #[derive(Error, Debug)]
 pub enum SearchAndDownloadError {
+    #[error("download cancelled")]
+    Cancelled(#[from] Cancelled),
     #[error(transparent)]
     Search(#[from] SearchError),
     #[error(transparent)]
@@ -345,6 +410,7 @@ pub enum SearchAndDownloadError {
 async fn search_and_download_src_rock(
     package_req: &PackageReq,
     package_db: &RemotePackageDB,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedPackedRockBytes, SearchAndDownloadError> {
     let filter = Some(RemotePackageTypeFilterSpec {
@@ -356,6 +422,7 @@ async fn search_and_download_src_rock(
     Ok(download_src_rock(
         &remote_package.package,
         unsafe { &remote_package.source.url() },
+        config,
         progress,
     )
     .await?)
@@ -365,6 +432,8 @@ async fn search_and_download_src_rock(
 pub enum DownloadSrcRockError {
     #[error("failed to download source rock: {0}")]
     Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    InsecureServer(#[from] InsecureServerError),
     #[error("failed to parse source rock URL: {0}")]
     Parse(#[from] ParseError),
 }
@@ -372,9 +441,10 @@ pub enum DownloadSrcRockError {
 pub(crate) async fn download_src_rock(
     package: &PackageSpec,
     server_url: &Url,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedPackedRockBytes, DownloadSrcRockError> {
-    ArchiveDownload::new(package, server_url, "src.rock", progress)
+    ArchiveDownload::new(package, server_url, "src.rock", config, progress)
         .download()
         .await
 }
@@ -382,10 +452,11 @@ pub(crate) async fn download_src_rock(
 pub(crate) async fn download_binary_rock(
     package: &PackageSpec,
     server_url: &Url,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedPackedRockBytes, DownloadSrcRockError> {
     let ext = format!("{}.rock", luarocks::current_platform_luarocks_identifier());
-    ArchiveDownload::new(package, server_url, &ext, progress)
+    ArchiveDownload::new(package, server_url, &ext, config, progress)
         .fallback_ext("all.rock")
         .download()
         .await
@@ -395,24 +466,46 @@ async fn download_src_rock_to_file(
     package_req: &PackageReq,
     destination_dir: Option<PathBuf>,
     package_db: &RemotePackageDB,
+    config: &Config,
     progress: &Progress<ProgressBar>,
 ) -> Result<DownloadedPackedRock, SearchAndDownloadError> {
     progress.map(|p| p.set_message(format!("📥 Downloading {package_req}")));
 
-    let rock = search_and_download_src_rock(package_req, package_db, progress).await?;
-    let full_rock_name = mk_packed_rock_name(&rock.name, &rock.version, "src.rock");
-    tokio::fs::write(
-        destination_dir
-            .map(|dest| dest.join(&full_rock_name))
-            .unwrap_or_else(|| full_rock_name.clone().into()),
-        &rock.bytes,
-    )
-    .await?;
+    let filter = Some(RemotePackageTypeFilterSpec {
+        rockspec: false,
+        binary: false,
+        src: true,
+    });
+    let remote_package = package_db.find(package_req, filter, progress)?;
+    let package = &remote_package.package;
+    let full_rock_name = mk_packed_rock_name(package.name(), package.version(), "src.rock");
+    let path = destination_dir
+        .map(|dest| dest.join(&full_rock_name))
+        .unwrap_or_else(|| full_rock_name.into());
+
+    // Rocks that were downloaded by a previous run are not downloaded again.
+    let cached = tokio::fs::try_exists(&path).await?;
+    if !cached {
+        let rock = download_src_rock(
+            package,
+            unsafe { &remote_package.source.url() },
+            config,
+            progress,
+        )
+        .await?;
+        // Download to a temporary file first, so that an interrupted download
+        // doesn't leave a truncated rock behind.
+        let mut partial_path = path.clone().into_os_string();
+        partial_path.push(".part");
+        tokio::fs::write(&partial_path, &rock.bytes).await?;
+        tokio::fs::rename(&partial_path, &path).await?;
+    }
 
     Ok(DownloadedPackedRock {
-        name: rock.name.to_owned(),
-        version: rock.version.to_owned(),
-        path: full_rock_name.into(),
+        name: package.name().clone(),
+        version: package.version().clone(),
+        path,
+        cached,
     })
 }
 
@@ -428,6 +521,9 @@ struct ArchiveDownload<'a> {
     #[builder(start_fn)]
     ext: &'a str,
 
+    #[builder(start_fn)]
+    config: &'a Config,
+
     #[builder(start_fn)]
     progress: &'a Progress<ProgressBar>,
 
@@ -444,6 +540,7 @@ where
         let package = args.package;
         let ext = args.ext;
         let server_url = args.server_url;
+        let client = args.config.http_client()?;
         progress.map(|p| {
             p.set_message(format!(
                 "📥 Downloading {}-{}.{}",
@@ -454,7 +551,10 @@ where
         });
         let full_rock_name = mk_packed_rock_name(package.name(), package.version(), ext);
         let url = server_url.join(&full_rock_name)?;
-        let response = reqwest::get(url.clone()).await?;
+        let response = client
+            .server_request(Method::GET, url.clone())?
+            .send()
+            .await?;
         let bytes = if response.status().is_success() {
             response.bytes().await
         } else {
@@ -463,7 +563,9 @@ where
                     let full_rock_name =
                         mk_packed_rock_name(package.name(), package.version(), ext);
                     let url = server_url.join(&full_rock_name)?;
-                    reqwest::get(url.clone())
+                    client
+                        .server_request(Method::GET, url.clone())?
+                        .send()
                         .await?
                         .error_for_status()?
                         .bytes()
@@ -7,6 +7,7 @@ use crate::{
     operations::Install,
     package::{PackageReq, PackageVersionReqError},
     path::{Paths, PathsError},
+    process::{self, ProcessLimits},
     project::{Project, ProjectTreeError},
     remote_package_db::RemotePackageDBError,
     tree::{self, TreeError},
@@ -83,6 +84,16 @@ pub enum ExecError {
     Io(String, io::Error),
 }
 
+impl ExecError {
+    /// The exit code of the command, if it ran and exited with a non-zero exit code.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Self::RunCommandNonZeroExitCode { exit_code, .. } => *exit_code,
+            _ => None,
+        }
+    }
+}
+
 async fn exec(run: Exec<'_>) -> Result<(), ExecError> {
     let lua_version = run
         .project
@@ -111,14 +122,17 @@ async fn exec(run: Exec<'_>) -> Result<(), ExecError> {
         Some(paths.init())
     };
 
-    let status = match Command::new(run.command)
-        .args(run.args)
-        .env("PATH", paths.path_prepended().joined())
-        .env("LUA_INIT", lua_init.unwrap_or_default())
-        .env("LUA_PATH", paths.package_path().joined())
-        .env("LUA_CPATH", paths.package_cpath().joined())
-        .status()
-        .await
+    let status = match process::status(
+        Command::new(run.command)
+            .args(run.args)
+            .env("PATH", paths.path_prepended().joined())
+            .env("LUA_INIT", lua_init.unwrap_or_default())
+            .env("LUA_PATH", paths.package_path().joined())
+            .env("LUA_CPATH", paths.package_cpath().joined()),
+        &ProcessLimits::default(),
+        run.command,
+    )
+    .await
     {
         Ok(status) => Ok(status),
         Err(err) => Err(ExecError::RunCommandFailed {
@@ -131,7 +145,7 @@ async fn exec(run: Exec<'_>) -> Result<(), ExecError> {
     } else {
         Err(ExecError::RunCommandNonZeroExitCode {
             cmd: run.command.to_string(),
-            exit_code: status.code(),
+            exit_code: process::exit_code(&status),
         })
     }
 }
@@ -158,6 +172,7 @@ pub async fn install_command(command: &str, config: &Config) -> Result<(), Insta
     Install::new(config)
         .package(install_spec)
         .tree(tree)
+        .check_conflicts(false)
         .install()
         .await?;
     Ok(())
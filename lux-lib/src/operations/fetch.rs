@@ -201,7 +201,11 @@ async fn do_fetch_src<R: Rockspec>(
         RockSourceSpec::Url(url) => {
             progress.map(|p| p.set_message(format!("📥 Downloading {}", url.to_owned())));
 
-            let response = reqwest::get(url.to_owned())
+            let response = fetch
+                .config
+                .http_client()?
+                .get(url.to_owned())
+                .send()
                 .await?
                 .error_for_status()?
                 .bytes()
@@ -277,7 +281,8 @@ async fn do_fetch_src_rock(
     let dest_dir = fetch.dest_dir;
     let config = fetch.config;
     let progress = fetch.progress;
-    let src_rock = operations::download_src_rock(package, config.server(), progress).await?;
+    let src_rock =
+        operations::download_src_rock(package, config.server(), config, progress).await?;
     let hash = src_rock.bytes.hash()?;
     let cursor = Cursor::new(src_rock.bytes);
     let mime_type = infer::get(cursor.get_ref()).map(|file_type| file_type.mime_type());
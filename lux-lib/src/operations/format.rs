@@ -0,0 +1,269 @@
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+};
+
+use bon::Builder;
+use itertools::Itertools;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::process::Command;
+use walkdir::WalkDir;
+use which::which;
+
+use crate::{
+    config::Config,
+    package::{PackageReq, PackageVersionReqError},
+    progress::{MultiProgress, Progress},
+    project::{project_toml::LocalProjectTomlValidationError, Project, ProjectTreeError},
+    tree::{self, TreeError},
+};
+
+use super::{Install, InstallError, PackageInstallSpec};
+
+#[cfg(target_family = "unix")]
+const LUA_FORMAT_EXE: &str = "lua-format";
+#[cfg(target_family = "windows")]
+const LUA_FORMAT_EXE: &str = "lua-format.exe";
+
+/// The rock that provides the `lua-format` executable.
+const LUA_FORMAT_ROCK: &str = "luaformatter";
+
+/// A formatter that can be run with `lx fmt`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "kebab-case"))]
+#[serde(rename_all = "kebab-case")]
+pub enum Formatter {
+    /// Built into Lux. Configured with a `stylua.toml` or `.stylua.toml`.
+    #[default]
+    Stylua,
+    /// LuaFormatter. Configured with a `.lua-format` file.
+    LuaFormat,
+}
+
+impl Display for Formatter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stylua => write!(f, "stylua"),
+            Self::LuaFormat => write!(f, "lua-format"),
+        }
+    }
+}
+
+/// The `[format]` section of a `lux.toml`.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct FormatSpec {
+    /// The formatter to use. Defaults to `stylua`.
+    #[serde(default)]
+    pub(crate) formatter: Formatter,
+    /// Install the formatter to the project's build tree
+    /// if it is not found on the system.
+    #[serde(default)]
+    pub(crate) install: bool,
+}
+
+impl FormatSpec {
+    pub fn formatter(&self) -> Formatter {
+        self.formatter
+    }
+
+    pub fn install(&self) -> bool {
+        self.install
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FormatError {
+    #[error(transparent)]
+    LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
+    #[error(transparent)]
+    ProjectTree(#[from] ProjectTreeError),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    PackageVersionReq(#[from] PackageVersionReqError),
+    #[error("error installing {LUA_FORMAT_ROCK}:\n{0}")]
+    InstallLuaFormat(#[from] InstallError),
+    #[error("`lua-format` not found.\nInstall it, or set `install = true` in the lux.toml's `[format]` section.")]
+    LuaFormatNotFound,
+    #[error("error formatting {file}: {err}")]
+    Io { file: PathBuf, err: io::Error },
+    #[error("error formatting {file}: {err}")]
+    Stylua {
+        file: PathBuf,
+        err: stylua_lib::Error,
+    },
+    #[error("lua-format failed to format {file}:\n{stderr}")]
+    LuaFormat { file: PathBuf, stderr: String },
+}
+
+/// Formats the project's Lua sources and its `extra.rockspec`.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Format<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// Only format the files in this directory, or this file.
+    workspace_or_file: Option<PathBuf>,
+
+    /// Overrides the `[format]` formatter in the lux.toml.
+    formatter: Option<Formatter>,
+
+    /// Don't write any files, only report the ones that are not formatted.
+    #[builder(default)]
+    check: bool,
+
+    #[builder(default = MultiProgress::new_arc())]
+    progress: Arc<Progress<MultiProgress>>,
+}
+
+impl<State: format_builder::State> FormatBuilder<'_, State> {
+    /// Returns the files that are not formatted.
+    /// Unless in check mode, these files have been formatted.
+    pub async fn format(self) -> Result<Vec<PathBuf>, FormatError>
+    where
+        State: format_builder::IsComplete,
+    {
+        do_format(self._build()).await
+    }
+}
+
+async fn do_format(format: Format<'_>) -> Result<Vec<PathBuf>, FormatError> {
+    let project = format.project;
+    let spec = project.toml().into_local()?.formatting().clone();
+    let formatter = format.formatter.unwrap_or(spec.formatter());
+
+    let files = WalkDir::new(project.root().join("src"))
+        .into_iter()
+        .chain(WalkDir::new(project.root().join("lua")))
+        .chain(WalkDir::new(project.root().join("lib")))
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .filter(|file| file.extension().is_some_and(|ext| ext == "lua"))
+        .chain(Some(project.root().join("extra.rockspec")).filter(|file| file.is_file()))
+        .filter(|file| {
+            format
+                .workspace_or_file
+                .as_ref()
+                .is_none_or(|workspace_or_file| file.starts_with(workspace_or_file))
+        })
+        .collect_vec();
+
+    let mut unformatted = Vec::new();
+    match formatter {
+        Formatter::Stylua => {
+            let stylua_config = stylua_config(project.root());
+            for file in files {
+                let code = std::fs::read_to_string(&file).map_err(io_error(&file))?;
+                let formatted_code = match stylua_lib::format_code(
+                    &code,
+                    stylua_config,
+                    None,
+                    stylua_lib::OutputVerification::Full,
+                ) {
+                    Ok(formatted_code) => formatted_code,
+                    Err(err) => return Err(FormatError::Stylua { file, err }),
+                };
+                if formatted_code != code {
+                    if !format.check {
+                        std::fs::write(&file, formatted_code).map_err(io_error(&file))?;
+                    }
+                    unformatted.push(file);
+                }
+            }
+        }
+        Formatter::LuaFormat => {
+            let lua_format =
+                lua_format_exe(project, format.config, spec.install(), format.progress).await?;
+            let lua_format_config = project.root().join(".lua-format");
+            for file in files {
+                let code = std::fs::read_to_string(&file).map_err(io_error(&file))?;
+                let mut command = Command::new(&lua_format);
+                command.current_dir(project.root().as_path());
+                if lua_format_config.is_file() {
+                    command.arg("-c").arg(&lua_format_config);
+                }
+                let output = command
+                    .arg(&file)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+                    .await
+                    .map_err(io_error(&file))?;
+                if !output.status.success() {
+                    return Err(FormatError::LuaFormat {
+                        file,
+                        stderr: String::from_utf8_lossy(&output.stderr).into(),
+                    });
+                }
+                let formatted_code = String::from_utf8_lossy(&output.stdout);
+                if formatted_code != code {
+                    if !format.check {
+                        std::fs::write(&file, formatted_code.as_ref()).map_err(io_error(&file))?;
+                    }
+                    unformatted.push(file);
+                }
+            }
+        }
+    }
+
+    Ok(unformatted)
+}
+
+fn stylua_config(project_root: &Path) -> stylua_lib::Config {
+    std::fs::read_to_string(project_root.join("stylua.toml"))
+        .or_else(|_| std::fs::read_to_string(project_root.join(".stylua.toml")))
+        .map(|config: String| toml::from_str(&config).unwrap_or_default())
+        .unwrap_or_default()
+}
+
+/// Finds the `lua-format` executable on the system or in the project's build tree,
+/// installing it to the build tree if `install` is set.
+async fn lua_format_exe(
+    project: &Project,
+    config: &Config,
+    install: bool,
+    progress: Arc<Progress<MultiProgress>>,
+) -> Result<PathBuf, FormatError> {
+    if let Ok(lua_format) = which(LUA_FORMAT_EXE) {
+        return Ok(lua_format);
+    }
+    let build_tree = project.build_tree(config)?;
+    let lua_format = build_tree.bin().join(LUA_FORMAT_EXE);
+    if lua_format.is_file() {
+        return Ok(lua_format);
+    }
+    if !install {
+        return Err(FormatError::LuaFormatNotFound);
+    }
+    let luaformatter = PackageReq::new(LUA_FORMAT_ROCK.into(), None)?;
+    if !build_tree.match_rocks(&luaformatter)?.is_found() {
+        Install::new(config)
+            .package(PackageInstallSpec::new(luaformatter, tree::EntryType::Entrypoint).build())
+            .tree(build_tree)
+            .check_conflicts(false)
+            .progress(progress)
+            .install()
+            .await?;
+    }
+    if lua_format.is_file() {
+        Ok(lua_format)
+    } else {
+        Err(FormatError::LuaFormatNotFound)
+    }
+}
+
+fn io_error(file: &Path) -> impl FnOnce(io::Error) -> FormatError + '_ {
+    |err| FormatError::Io {
+        file: file.to_path_buf(),
+        err,
+    }
+}
@@ -0,0 +1,412 @@
+use std::{fmt::Write as _, io, path::PathBuf};
+
+use bon::Builder;
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::{
+    config::LuaVersion,
+    lua_rockspec::TestSpec,
+    project::{project_toml::LocalProjectTomlValidationError, Project},
+    rockspec::{LuaVersionCompatibility, Rockspec},
+};
+
+use super::Linter;
+
+/// The name of the generated luacheck config, relative to the project root.
+pub const LUACHECKRC_FILE_NAME: &str = ".luacheckrc";
+/// The name of the generated selene config, relative to the project root.
+pub const SELENE_CONFIG_FILE_NAME: &str = "selene.toml";
+
+/// Marks the files generated by lux, which may be overwritten when regenerating them.
+const GENERATED_MARKER: &str = "Generated by `lx generate luacheckrc`";
+
+/// The directories that may contain busted specs, if they exist.
+const TEST_DIRS: &[&str] = &["spec", "test", "tests"];
+
+const BUSTED_GLOBALS: &[&str] = &[
+    "describe",
+    "context",
+    "insulate",
+    "expose",
+    "it",
+    "spec",
+    "test",
+    "pending",
+    "before_each",
+    "after_each",
+    "setup",
+    "teardown",
+    "lazy_setup",
+    "lazy_teardown",
+    "strict_setup",
+    "strict_teardown",
+    "finally",
+    "randomize",
+    "assert",
+    "spy",
+    "stub",
+    "mock",
+];
+
+const NGX_GLOBALS: &[&str] = &["ngx", "ndk"];
+
+/// Generates linter configs that declare the globals a project's Lua files may use,
+/// based on the project's supported Lua versions and its dependencies:
+///
+/// - busted's globals in the test directories, if the project is tested with busted.
+/// - Neovim's `vim` global, for Neovim plugins and tests that run with `nlua`.
+/// - OpenResty's globals, if the project depends on `lua-resty-*` packages.
+///
+/// selene can't restrict globals to some directories,
+/// so its config allows busted's globals in all files.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct GenLintConfig<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+
+    /// The linters to generate configs for.
+    /// Defaults to the linters in the project's `[lint]` section.
+    linters: Option<Vec<Linter>>,
+
+    /// Allow Neovim's `vim` global in all files,
+    /// even if no dependency indicates that the project is a Neovim plugin.
+    #[builder(default)]
+    nvim: bool,
+
+    /// Overwrite existing configs that were not generated by lux.
+    #[builder(default)]
+    force: bool,
+}
+
+impl<State> GenLintConfigBuilder<'_, State>
+where
+    State: gen_lint_config_builder::State + gen_lint_config_builder::IsComplete,
+{
+    /// Write the configs, returning the paths that were written to.
+    pub fn generate(self) -> Result<Vec<PathBuf>, GenLintConfigError> {
+        do_generate_lint_config(self._build())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GenLintConfigError {
+    #[error(transparent)]
+    LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
+    #[error("the project does not support any Lua version")]
+    NoSupportedLuaVersion,
+    #[error("{0} was not generated by lux. Use --force to overwrite it.")]
+    NotGenerated(PathBuf),
+    #[error("failed to write {0}:\n{1}")]
+    Write(PathBuf, io::Error),
+}
+
+/// The globals a project's Lua files may use.
+#[derive(Debug, PartialEq)]
+struct LintGlobals {
+    /// The Lua versions the project supports, from oldest to newest.
+    lua_versions: Vec<LuaVersion>,
+    /// Whether Neovim's `vim` global is allowed in all files.
+    nvim: bool,
+    /// Whether OpenResty's globals are allowed in all files.
+    openresty: bool,
+    /// The test directories in which busted's globals are allowed.
+    busted_dirs: Vec<String>,
+    /// Whether Neovim's `vim` global is allowed in the test directories.
+    nlua: bool,
+}
+
+fn do_generate_lint_config(args: GenLintConfig<'_>) -> Result<Vec<PathBuf>, GenLintConfigError> {
+    let project = args.project;
+    let globals = lint_globals(project, args.nvim)?;
+    let linters = args.linters.unwrap_or_else(|| {
+        project
+            .toml()
+            .into_local()
+            .map(|toml| toml.lint().linters())
+            .unwrap_or_default()
+    });
+    let mut files = Vec::new();
+    for linter in linters.into_iter().unique() {
+        match linter {
+            Linter::Luacheck => files.push((
+                LUACHECKRC_FILE_NAME.to_string(),
+                render_luacheckrc(&globals),
+            )),
+            Linter::Selene => files.extend(render_selene_config(&globals)),
+        }
+    }
+    for (file_name, _) in &files {
+        let path = project.root().join(file_name);
+        if !args.force && path.is_file() && !is_generated(&path) {
+            return Err(GenLintConfigError::NotGenerated(path));
+        }
+    }
+    files
+        .into_iter()
+        .map(|(file_name, content)| {
+            let path = project.root().join(file_name);
+            std::fs::write(&path, content)
+                .map_err(|err| GenLintConfigError::Write(path.clone(), err))?;
+            Ok(path)
+        })
+        .try_collect()
+}
+
+fn is_generated(path: &PathBuf) -> bool {
+    std::fs::read_to_string(path).is_ok_and(|content| {
+        content
+            .lines()
+            .next()
+            .is_some_and(|line| line.contains(GENERATED_MARKER))
+    })
+}
+
+fn lint_globals(project: &Project, nvim: bool) -> Result<LintGlobals, GenLintConfigError> {
+    let project_toml = project.toml().into_local()?;
+    let lua_versions = [
+        LuaVersion::Lua51,
+        LuaVersion::Lua52,
+        LuaVersion::Lua53,
+        LuaVersion::Lua54,
+    ]
+    .into_iter()
+    .filter(|lua_version| project.toml().supports_lua_version(lua_version))
+    .collect_vec();
+    if lua_versions.is_empty() {
+        return Err(GenLintConfigError::NoSupportedLuaVersion);
+    }
+    let dependencies = project_toml
+        .dependencies()
+        .current_platform()
+        .iter()
+        .map(|dep| dep.name().to_string())
+        .collect_vec();
+    let test_dependencies = project_toml
+        .test_dependencies()
+        .current_platform()
+        .iter()
+        .map(|dep| dep.name().to_string())
+        .collect_vec();
+    let is_nvim_plugin = dependencies
+        .iter()
+        .any(|name| name.ends_with(".nvim") || name.starts_with("nvim-"));
+    let openresty = dependencies
+        .iter()
+        .any(|name| name == "openresty" || name.starts_with("lua-resty-"));
+    let test_spec = project_toml.test().current_platform();
+    let has_test_dependency = |name: &str| test_dependencies.iter().any(|dep| dep == name);
+    let nlua = matches!(test_spec, TestSpec::BustedNlua(_))
+        || (matches!(test_spec, TestSpec::AutoDetect) && has_test_dependency("nlua"));
+    let busted = nlua
+        || matches!(test_spec, TestSpec::Busted(_))
+        || has_test_dependency("busted")
+        || project.root().join(".busted").is_file();
+    let busted_dirs = if busted {
+        let existing_dirs = TEST_DIRS
+            .iter()
+            .filter(|dir| project.root().join(dir).is_dir())
+            .map(|dir| dir.to_string())
+            .collect_vec();
+        if existing_dirs.is_empty() {
+            vec![TEST_DIRS[0].to_string()]
+        } else {
+            existing_dirs
+        }
+    } else {
+        Vec::new()
+    };
+    Ok(LintGlobals {
+        lua_versions,
+        nvim: nvim || is_nvim_plugin,
+        openresty,
+        busted_dirs,
+        nlua,
+    })
+}
+
+/// The name of a Lua version's standard library in luacheck and selene, e.g. `lua51`.
+fn lua_std(lua_version: &LuaVersion) -> String {
+    format!(
+        "lua{}",
+        lua_version.version_compatibility_str().replace('.', "")
+    )
+}
+
+fn render_luacheckrc(globals: &LintGlobals) -> String {
+    let mut std = match globals.lua_versions.as_slice() {
+        [lua_version] => lua_std(lua_version),
+        // The union of the globals of all Lua versions,
+        // as projects that support several versions usually check which globals exist.
+        _ => "max".to_string(),
+    };
+    if globals.openresty {
+        std.push_str("+ngx_lua");
+    }
+    let mut content = format!(
+        "-- {GENERATED_MARKER} from the project's dependencies.\n\
+         -- Regenerate it after changing the dependencies. To edit it by hand, remove this header.\n\n\
+         std = \"{std}\"\n"
+    );
+    if globals.nvim {
+        content.push_str("globals = { \"vim\" }\n");
+    }
+    for dir in &globals.busted_dirs {
+        let _ = write!(content, "\nfiles[\"{dir}\"] = {{ std = \"+busted\"");
+        if globals.nlua && !globals.nvim {
+            content.push_str(", globals = { \"vim\" }");
+        }
+        content.push_str(" }\n");
+    }
+    content
+}
+
+/// selene's config, and the custom standard libraries it refers to.
+fn render_selene_config(globals: &LintGlobals) -> Vec<(String, String)> {
+    let header = format!(
+        "# {GENERATED_MARKER} from the project's dependencies.\n\
+         # Regenerate it after changing the dependencies. To edit it by hand, remove this header.\n"
+    );
+    let mut custom_stds = Vec::new();
+    if !globals.busted_dirs.is_empty() {
+        custom_stds.push(("busted", BUSTED_GLOBALS));
+    }
+    if globals.nvim || globals.nlua {
+        custom_stds.push(("vim", &["vim"]));
+    }
+    if globals.openresty {
+        custom_stds.push(("ngx", NGX_GLOBALS));
+    }
+    // selene's standard library of a Lua version includes those of the older versions,
+    // so the oldest supported version only allows globals that exist in all of them.
+    let std = std::iter::once(lua_std(&globals.lua_versions[0]))
+        .chain(custom_stds.iter().map(|(name, _)| name.to_string()))
+        .join("+");
+    std::iter::once((
+        SELENE_CONFIG_FILE_NAME.to_string(),
+        format!("{header}\nstd = \"{std}\"\n"),
+    ))
+    .chain(custom_stds.into_iter().map(|(name, std_globals)| {
+        let mut content = format!("{header}---\nglobals:\n");
+        for global in std_globals {
+            let _ = write!(content, "  {global}:\n    any: true\n");
+        }
+        (format!("{name}.yml"), content)
+    }))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild, PathCreateDir};
+
+    use super::*;
+
+    fn write_project(project_root: &assert_fs::TempDir, content: &str) -> Project {
+        project_root.child("lux.toml").write_str(content).unwrap();
+        Project::from(project_root.path()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn lint_globals_from_dependencies() {
+        let project_root = assert_fs::TempDir::new().unwrap();
+        project_root.child("spec").create_dir_all().unwrap();
+        let project = write_project(
+            &project_root,
+            r#"
+package = "my-plugin"
+version = "0.1.0"
+lua = "5.1"
+
+[dependencies]
+"plenary.nvim" = "1.0"
+
+[test_dependencies]
+busted = "2.0"
+nlua = "0.2"
+"#,
+        );
+        let globals = lint_globals(&project, false).unwrap();
+        assert_eq!(
+            globals,
+            LintGlobals {
+                lua_versions: vec![LuaVersion::Lua51],
+                nvim: true,
+                openresty: false,
+                busted_dirs: vec!["spec".into()],
+                nlua: true,
+            }
+        );
+        assert_eq!(
+            render_luacheckrc(&globals),
+            r#"-- Generated by `lx generate luacheckrc` from the project's dependencies.
+-- Regenerate it after changing the dependencies. To edit it by hand, remove this header.
+
+std = "lua51"
+globals = { "vim" }
+
+files["spec"] = { std = "+busted" }
+"#
+        );
+        let selene = render_selene_config(&globals);
+        assert_eq!(
+            selene.iter().map(|(file, _)| file.as_str()).collect_vec(),
+            vec!["selene.toml", "busted.yml", "vim.yml"]
+        );
+        assert!(selene[0].1.ends_with("std = \"lua51+busted+vim\"\n"));
+        assert!(selene[2]
+            .1
+            .ends_with("---\nglobals:\n  vim:\n    any: true\n"));
+    }
+
+    #[test]
+    fn lint_globals_without_dependencies() {
+        let project_root = assert_fs::TempDir::new().unwrap();
+        let project = write_project(
+            &project_root,
+            r#"
+package = "my-server"
+version = "0.1.0"
+lua = ">=5.1"
+
+[dependencies]
+lua-resty-http = "0.17"
+"#,
+        );
+        let globals = lint_globals(&project, false).unwrap();
+        assert_eq!(globals.lua_versions.len(), 4);
+        assert!(!globals.nvim);
+        assert!(globals.openresty);
+        assert!(globals.busted_dirs.is_empty());
+        assert!(render_luacheckrc(&globals).ends_with("std = \"max+ngx_lua\"\n"));
+    }
+
+    #[test]
+    fn keep_handwritten_configs() {
+        let project_root = assert_fs::TempDir::new().unwrap();
+        let project = write_project(
+            &project_root,
+            r#"
+package = "my-project"
+version = "0.1.0"
+lua = "5.4"
+"#,
+        );
+        let luacheckrc = project_root.child(LUACHECKRC_FILE_NAME);
+        luacheckrc.write_str("std = \"lua54\"\n").unwrap();
+        let generate = |force| {
+            GenLintConfig::new(&project)
+                .linters(vec![Linter::Luacheck])
+                .force(force)
+                .generate()
+        };
+        assert!(matches!(
+            generate(false),
+            Err(GenLintConfigError::NotGenerated(_))
+        ));
+        assert_eq!(generate(true).unwrap(), vec![luacheckrc.to_path_buf()]);
+        // Generated configs are regenerated without --force
+        assert_eq!(generate(false).unwrap(), vec![luacheckrc.to_path_buf()]);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use bon::Builder;
+use thiserror::Error;
+
+use crate::project::Project;
+
+const LOADER_TEMPLATE: &str = include_str!("lux_loader.lua");
+const PROJECT_ROOT_PLACEHOLDER: &str = "__LUX_PROJECT_ROOT__";
+
+/// The default name of the generated loader, relative to the project root.
+pub const LOADER_FILE_NAME: &str = "lux-loader.lua";
+
+/// Generates a standalone `lux-loader.lua`, which can be required or set via `LUA_INIT`
+/// by external tooling to find a project's dependencies.
+/// The loader reads the project tree's lockfile at runtime, so it does not need
+/// to be regenerated when the dependencies change.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct GenLoader<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+
+    /// Where to write the loader.
+    /// Defaults to `lux-loader.lua` in the project root.
+    output: Option<PathBuf>,
+
+    /// Locate the project relative to the loader's own path,
+    /// so that the project can be moved to a different location.
+    #[builder(default)]
+    relocatable: bool,
+}
+
+impl<State> GenLoaderBuilder<'_, State>
+where
+    State: gen_loader_builder::State + gen_loader_builder::IsComplete,
+{
+    /// Write the loader, returning the path it was written to.
+    pub fn generate(self) -> Result<PathBuf, GenLoaderError> {
+        do_generate_loader(self._build())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GenLoaderError {
+    #[error("failed to write {0}:\n{1}")]
+    Write(PathBuf, io::Error),
+}
+
+fn do_generate_loader(args: GenLoader<'_>) -> Result<PathBuf, GenLoaderError> {
+    let project_root = args.project.root();
+    let output = args
+        .output
+        .unwrap_or_else(|| project_root.join(LOADER_FILE_NAME));
+    let project_root_expr = if args.relocatable {
+        let loader_dir = output.parent().map(Path::to_path_buf).unwrap_or_default();
+        let relative_project_root =
+            pathdiff::diff_paths(project_root, &loader_dir).unwrap_or(project_root.to_path_buf());
+        relative_project_root_expr(&relative_project_root)
+    } else {
+        quote_lua_string(&project_root.to_string_lossy())
+    };
+    let content = render_loader(&project_root_expr);
+    std::fs::write(&output, content).map_err(|err| GenLoaderError::Write(output.clone(), err))?;
+    Ok(output)
+}
+
+/// Render the loader, with `project_root` being a Lua expression for the project root.
+fn render_loader(project_root: &str) -> String {
+    LOADER_TEMPLATE.replace(PROJECT_ROOT_PLACEHOLDER, project_root)
+}
+
+/// A Lua expression that resolves a path relative to the loader's directory.
+fn relative_project_root_expr(relative_path: &Path) -> String {
+    let relative_path = relative_path.to_string_lossy();
+    let loader_dir = r#"(debug.getinfo(1, "S").source:sub(2):match("^(.*)[/\\]") or ".")"#;
+    if relative_path.is_empty() {
+        loader_dir.to_string()
+    } else {
+        format!(
+            "{} .. package.config:sub(1, 1) .. {}",
+            loader_dir,
+            quote_lua_string(&relative_path)
+        )
+    }
+}
+
+/// Quote a string as a Lua string literal.
+fn quote_lua_string(str: &str) -> String {
+    let escaped = str
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use assert_fs::prelude::*;
+    use mlua::{Lua, Table};
+
+    use super::*;
+
+    fn tree_dir(lua: &Lua) -> String {
+        lua.load("return jit and 'jit' or _VERSION:match('Lua (%d+%.%d+)')")
+            .eval()
+            .unwrap()
+    }
+
+    fn write_lockfile(project_root: &Path, tree_dir: &str, content: &str) {
+        let lockfile = project_root.join(".lux").join(tree_dir).join("lux.lock");
+        std::fs::create_dir_all(lockfile.parent().unwrap()).unwrap();
+        std::fs::write(lockfile, content).unwrap();
+    }
+
+    #[test]
+    fn loader_adds_lockfile_packages_to_path() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let lua = Lua::new();
+        let tree_dir = tree_dir(&lua);
+        write_lockfile(
+            temp.path(),
+            &tree_dir,
+            r#"{
+              "version": "1.0.0",
+              "rocks": {
+                "aaa": { "name": "foo", "version": "1.0.0-1", "dependencies": [], "constraint": null },
+                "bbb": { "name": "foo", "version": "2.0.0-1", "dependencies": [], "constraint": null },
+                "ccc": { "name": "bar", "version": "0.1.0-1", "dependencies": ["aaa"], "constraint": ">=0.1, <0.2" }
+              },
+              "entrypoints": ["bbb", "ccc"]
+            }"#,
+        );
+        let loader = temp.child(LOADER_FILE_NAME);
+        loader
+            .write_str(&render_loader(&quote_lua_string(
+                &temp.path().to_string_lossy(),
+            )))
+            .unwrap();
+        let module: Table = lua.load(loader.path()).eval().unwrap();
+        let packages: Table = module.get("packages").unwrap();
+        let foo: Table = packages.get("foo").unwrap();
+        assert_eq!(foo.get::<String>("version").unwrap(), "2.0.0-1");
+        let package_path: String = lua.load("return package.path").eval().unwrap();
+        let foo_path = temp
+            .path()
+            .join(".lux")
+            .join(&tree_dir)
+            .join("bbb-foo@2.0.0-1")
+            .join("src")
+            .join("?.lua");
+        assert!(package_path.contains(&*foo_path.to_string_lossy()));
+        assert!(package_path.contains("ccc-bar@0.1.0-1"));
+        assert!(!package_path.contains("aaa-foo@1.0.0-1"));
+    }
+
+    #[test]
+    fn loader_strict_version_conflict() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let lua = Lua::new();
+        let tree_dir = tree_dir(&lua);
+        write_lockfile(
+            temp.path(),
+            &tree_dir,
+            r#"{"version":"1.0.0","rocks":{"aaa":{"name":"foo","version":"1.0.0-1"},"bbb":{"name":"foo","version":"2.0.0-1"}},"entrypoints":["aaa","bbb"]}"#,
+        );
+        let loader = render_loader(&quote_lua_string(&temp.path().to_string_lossy()));
+        lua.globals()
+            .set(
+                "os",
+                lua.load("return { getenv = function() return '1' end }")
+                    .eval::<Table>()
+                    .unwrap(),
+            )
+            .unwrap();
+        let err = lua.load(loader).exec().unwrap_err();
+        assert!(err.to_string().contains("multiple versions of foo"));
+    }
+
+    #[test]
+    fn relocatable_loader() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        // The loader uses the `debug` library, which mlua only loads in unsafe mode.
+        let lua = unsafe { Lua::unsafe_new() };
+        let tree_dir = tree_dir(&lua);
+        let project_root = temp.child("project");
+        write_lockfile(
+            project_root.path(),
+            &tree_dir,
+            r#"{"version":"1.0.0","rocks":{"aaa":{"name":"foo","version":"1.0.0-1"}},"entrypoints":["aaa"]}"#,
+        );
+        let loader = project_root.child("scripts").child(LOADER_FILE_NAME);
+        loader
+            .write_str(&render_loader(&relative_project_root_expr(Path::new(".."))))
+            .unwrap();
+        let module: Table = lua.load(loader.path()).eval().unwrap();
+        let package_path: String = lua.load("return package.path").eval().unwrap();
+        assert!(package_path.contains("aaa-foo@1.0.0-1"));
+        let module_root: String = module.get("project_root").unwrap();
+        assert_eq!(
+            Path::new(&module_root).canonicalize().unwrap(),
+            project_root.path().canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn quote_windows_path() {
+        assert_eq!(
+            quote_lua_string(r#"C:\Users\"me""#),
+            r#""C:\\Users\\\"me\"""#
+        );
+    }
+}
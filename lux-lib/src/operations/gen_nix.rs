@@ -0,0 +1,350 @@
+use std::{collections::BTreeMap, fmt::Write as _, io, path::PathBuf};
+
+use bon::Builder;
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::{
+    config::{Config, LuaVersion},
+    lockfile::{
+        LocalPackage, LocalPackageId, LocalPackageLockType, ProjectLockfile, ReadOnly,
+        RemotePackageSourceUrl,
+    },
+    lua_rockspec::LuaVersionError,
+    project::{project_toml::LocalProjectTomlValidationError, Project, ProjectError},
+    remote_package_source::RemotePackageSource,
+    rockspec::Rockspec,
+};
+
+/// The name of the generated derivation, relative to the project root.
+pub const NIX_DERIVATION_FILE_NAME: &str = "default.nix";
+/// The name of the generated flake, relative to the project root.
+pub const NIX_FLAKE_FILE_NAME: &str = "flake.nix";
+
+/// Generates a Nix derivation that builds a project with its locked dependencies.
+///
+/// Each locked package is mapped to a `buildLuarocksPackage` derivation,
+/// with its rockspec and source fetched as fixed-output derivations,
+/// using the hashes recorded in the project's lockfile.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct GenNix<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// Also generate a `flake.nix` that exposes the derivation as the default package.
+    #[builder(default)]
+    flake: bool,
+}
+
+impl<State> GenNixBuilder<'_, State>
+where
+    State: gen_nix_builder::State + gen_nix_builder::IsComplete,
+{
+    /// Write the Nix files, returning the paths that were written to.
+    pub fn generate(self) -> Result<Vec<PathBuf>, GenNixError> {
+        do_generate_nix(self._build())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GenNixError {
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error(transparent)]
+    LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
+    #[error(transparent)]
+    LuaVersion(#[from] LuaVersionError),
+    #[error("failed to generate a rockspec for the project:\n{0}")]
+    Rockspec(String),
+    #[error("failed to write {0}:\n{1}")]
+    Write(PathBuf, io::Error),
+}
+
+fn do_generate_nix(args: GenNix<'_>) -> Result<Vec<PathBuf>, GenNixError> {
+    let project = args.project;
+    let project_toml = project.toml().into_local()?;
+    let lua_version = project.lua_version(args.config)?;
+    let rockspec = project_toml
+        .to_lua_remote_rockspec_string()
+        .map_err(|err| GenNixError::Rockspec(err.to_string()))?;
+    let lockfile = project.lockfile()?;
+
+    let derivation = render_derivation(&project_toml, &rockspec, &lockfile);
+    let derivation_path = project.root().join(NIX_DERIVATION_FILE_NAME);
+    std::fs::write(&derivation_path, derivation)
+        .map_err(|err| GenNixError::Write(derivation_path.clone(), err))?;
+    let mut written = vec![derivation_path];
+
+    if args.flake {
+        let flake = render_flake(project_toml.description().summary.as_deref(), &lua_version);
+        let flake_path = project.root().join(NIX_FLAKE_FILE_NAME);
+        std::fs::write(&flake_path, flake)
+            .map_err(|err| GenNixError::Write(flake_path.clone(), err))?;
+        written.push(flake_path);
+    }
+
+    Ok(written)
+}
+
+fn render_derivation<R: Rockspec>(
+    project: &R,
+    rockspec: &str,
+    lockfile: &ProjectLockfile<ReadOnly>,
+) -> String {
+    let rocks = [LocalPackageLockType::Regular, LocalPackageLockType::Build]
+        .iter()
+        .flat_map(|lock_type| lockfile.rocks(lock_type))
+        .collect::<BTreeMap<_, _>>();
+    let attr_names = rocks
+        .iter()
+        .map(|(id, package)| (*id, attr_name(package)))
+        .collect::<BTreeMap<_, _>>();
+    let entrypoints = |lock_type| {
+        lockfile
+            .rocks(&lock_type)
+            .keys()
+            .filter(|id| lockfile.is_entrypoint(id, &lock_type))
+            .map(|id| dependency_ref(&attr_names, id))
+            .sorted()
+            .dedup()
+            .join(" ")
+    };
+
+    let mut out = String::new();
+    out.push_str("# Generated by `lx generate nix`.\n");
+    out.push_str("# Regenerate it after updating the project's lockfile.\n");
+    out.push_str("{\n  lib,\n  fetchurl,\n  fetchgit,\n  writeText,\n  luaPackages,\n}: let\n");
+    out.push_str("  inherit (luaPackages) buildLuarocksPackage;\n");
+    out.push_str("  deps = lib.fix (self: {\n");
+    // Packages with the same name and version may be locked multiple times
+    // with different constraints, but they share a derivation.
+    let packages = rocks
+        .iter()
+        .map(|(id, package)| (&attr_names[id], *package))
+        .collect::<BTreeMap<_, _>>();
+    for (attr_name, package) in packages {
+        let _ = writeln!(
+            out,
+            "    {} = {};",
+            nix_string(attr_name),
+            indent(&render_package(package, &attr_names), 4)
+        );
+    }
+    out.push_str("  });\n");
+    out.push_str("in\n");
+    let _ = writeln!(
+        out,
+        "  buildLuarocksPackage {{\n    pname = {};\n    version = {};\n    src = ./.;\n    knownRockspec = writeText {} {};\n    nativeBuildInputs = [{}];\n    propagatedBuildInputs = [{}];\n  }}",
+        nix_string(&project.package().to_string()),
+        nix_string(&project.version().to_string()),
+        nix_string(&format!("{}-{}.rockspec", project.package(), project.version())),
+        indent(&nix_indented_string(rockspec), 4),
+        pad_list(&entrypoints(LocalPackageLockType::Build)),
+        pad_list(&entrypoints(LocalPackageLockType::Regular)),
+    );
+    out
+}
+
+fn render_package(
+    package: &LocalPackage,
+    attr_names: &BTreeMap<&LocalPackageId, String>,
+) -> String {
+    let dependencies = package
+        .dependencies()
+        .into_iter()
+        .filter(|id| attr_names.contains_key(id))
+        .map(|id| format!("self.{}", nix_string(&attr_names[id])))
+        .join(" ");
+    format!(
+        "buildLuarocksPackage {{\n  pname = {};\n  version = {};\n  knownRockspec = {};\n  src = {};\n  propagatedBuildInputs = [{}];\n}}",
+        nix_string(&package.name().to_string()),
+        nix_string(&package.version().to_string()),
+        indent(&render_rockspec_fetcher(package), 2),
+        indent(&render_source_fetcher(package), 2),
+        pad_list(&dependencies),
+    )
+}
+
+fn render_rockspec_fetcher(package: &LocalPackage) -> String {
+    let rockspec_name = format!("{}-{}.rockspec", package.name(), package.version());
+    match &package.source {
+        RemotePackageSource::LuarocksRockspec(server)
+        | RemotePackageSource::LuarocksSrcRock(server)
+        | RemotePackageSource::LuarocksBinaryRock(server) => {
+            let url = server
+                .join(&rockspec_name)
+                .map(|url| url.to_string())
+                .unwrap_or_else(|_| format!("{server}/{rockspec_name}"));
+            format!(
+                "(fetchurl {{\n  url = {};\n  hash = {};\n}}).outPath",
+                nix_string(&url),
+                nix_string(&package.hashes().rockspec.to_string())
+            )
+        }
+        RemotePackageSource::RockspecContent(content) => format!(
+            "writeText {} {}",
+            nix_string(&rockspec_name),
+            nix_indented_string(content)
+        ),
+        RemotePackageSource::Local => throw(&format!(
+            "{}@{} was installed from a local rockspec",
+            package.name(),
+            package.version()
+        )),
+        #[cfg(test)]
+        RemotePackageSource::Test => unimplemented!(),
+    }
+}
+
+fn render_source_fetcher(package: &LocalPackage) -> String {
+    let hash = nix_string(&package.hashes().source.to_string());
+    match &package.source_url {
+        Some(RemotePackageSourceUrl::Git { url, checkout_ref }) => format!(
+            "fetchgit {{\n  url = {};\n  rev = {};\n  hash = {hash};\n}}",
+            nix_string(url),
+            nix_string(checkout_ref),
+        ),
+        Some(RemotePackageSourceUrl::Url { url }) => format!(
+            "fetchurl {{\n  url = {};\n  hash = {hash};\n}}",
+            nix_string(url.as_str()),
+        ),
+        Some(RemotePackageSourceUrl::File { path }) => format!(
+            "builtins.path {{\n  path = {};\n}}",
+            nix_string(&path.to_string_lossy())
+        ),
+        None => throw(&format!(
+            "the lockfile does not contain a source URL for {}@{}",
+            package.name(),
+            package.version()
+        )),
+    }
+}
+
+fn render_flake(description: Option<&str>, lua_version: &LuaVersion) -> String {
+    let lua_packages = match lua_version {
+        LuaVersion::Lua51 => "lua51Packages",
+        LuaVersion::Lua52 => "lua52Packages",
+        LuaVersion::Lua53 => "lua53Packages",
+        LuaVersion::Lua54 => "lua54Packages",
+        LuaVersion::LuaJIT | LuaVersion::LuaJIT52 => "luajitPackages",
+    };
+    format!(
+        r#"# Generated by `lx generate nix --flake`.
+{{
+  description = {};
+
+  inputs.nixpkgs.url = "github:NixOS/nixpkgs/nixos-unstable";
+
+  outputs = {{
+    self,
+    nixpkgs,
+  }}: let
+    systems = ["x86_64-linux" "aarch64-linux" "x86_64-darwin" "aarch64-darwin"];
+    forAllSystems = f: nixpkgs.lib.genAttrs systems (system: f nixpkgs.legacyPackages.${{system}});
+  in {{
+    packages = forAllSystems (pkgs: {{
+      default = pkgs.callPackage ./{NIX_DERIVATION_FILE_NAME} {{
+        luaPackages = pkgs.{lua_packages};
+      }};
+    }});
+  }};
+}}
+"#,
+        nix_string(description.unwrap_or_default()),
+    )
+}
+
+/// The Nix attribute name for a locked package.
+fn attr_name(package: &LocalPackage) -> String {
+    format!("{}-{}", package.name(), package.version())
+}
+
+fn dependency_ref(attr_names: &BTreeMap<&LocalPackageId, String>, id: &LocalPackageId) -> String {
+    format!("deps.{}", nix_string(&attr_names[id]))
+}
+
+fn throw(message: &str) -> String {
+    format!("throw {}", nix_string(message))
+}
+
+fn pad_list(items: &str) -> String {
+    if items.is_empty() {
+        String::new()
+    } else {
+        format!(" {items} ")
+    }
+}
+
+/// Indent every line but the first.
+fn indent(str: &str, spaces: usize) -> String {
+    str.replace('\n', &format!("\n{}", " ".repeat(spaces)))
+}
+
+/// A Nix string literal, with `"`, `\` and interpolations escaped.
+pub(crate) fn nix_string(str: &str) -> String {
+    let escaped = str
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${");
+    format!("\"{escaped}\"")
+}
+
+fn nix_indented_string(str: &str) -> String {
+    let escaped = str.replace("''", "'''").replace("${", "''${");
+    format!("''\n{escaped}\n''")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use assert_fs::prelude::PathCopy;
+
+    use super::*;
+
+    #[test]
+    fn nix_derivation_from_lockfile() {
+        let sample_project = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("resources/test/sample-projects/busted-with-lockfile");
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.copy_from(&sample_project, &["**"]).unwrap();
+        // Turn the test dependencies into regular dependencies
+        let lockfile_path = temp.path().join("lux.lock");
+        let mut lockfile: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&lockfile_path).unwrap()).unwrap();
+        let test_dependencies = lockfile
+            .as_object_mut()
+            .unwrap()
+            .remove("test_dependencies")
+            .unwrap();
+        lockfile["dependencies"] = test_dependencies;
+        std::fs::write(&lockfile_path, lockfile.to_string()).unwrap();
+        let project = Project::from(temp.path()).unwrap().unwrap();
+        let project_toml = project.toml().into_local().unwrap();
+        let lockfile = project.lockfile().unwrap();
+        let derivation = render_derivation(&project_toml, "rockspec_format = \"3.0\"", &lockfile);
+        let busted = lockfile
+            .rocks(&LocalPackageLockType::Regular)
+            .values()
+            .find(|package| package.name().to_string() == "busted")
+            .unwrap();
+        assert!(derivation.contains(&format!(
+            "\"busted-{}\" = buildLuarocksPackage {{",
+            busted.version()
+        )));
+        assert!(derivation.contains(&busted.hashes().source.to_string()));
+        assert!(derivation.contains(&format!(
+            "propagatedBuildInputs = [ deps.\"busted-{}\" ];",
+            busted.version()
+        )));
+    }
+
+    #[test]
+    fn nix_string_escapes() {
+        assert_eq!(nix_string("a\"${b}"), r#""a\"\${b}""#);
+        assert_eq!(nix_indented_string("a''${b}"), "''\na'''''${b}\n''");
+    }
+}
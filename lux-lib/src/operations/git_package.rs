@@ -0,0 +1,326 @@
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use bon::Builder;
+use git2::{build::RepoBuilder, Repository};
+use git_url_parse::{GitUrl, GitUrlParseError};
+use itertools::Itertools;
+use tempdir::TempDir;
+use thiserror::Error;
+
+use crate::{
+    build::BuildBehaviour,
+    lockfile::{PinnedState, RemotePackageSourceUrl},
+    lua_rockspec::{DisplayAsLuaKV, LuaRockspecError, RemoteLuaRockspec, RockSourceInternal},
+    package::PackageSpec,
+    progress::{Progress, ProgressBar},
+    project::{
+        project_toml::{ProjectTomlError, RemoteProjectTomlValidationError},
+        Project, ProjectError, PROJECT_TOML,
+    },
+    remote_package_source::RemotePackageSource,
+    rockspec::Rockspec,
+    tree,
+};
+
+use super::{DownloadedRockspec, PackageInstallSpec};
+
+const GIT_PREFIX: &str = "git+";
+
+/// A package in a git repository, e.g. `git+https://github.com/owner/repo`,
+/// optionally at a revision, e.g. `git+https://github.com/owner/repo#v1.0.0`.
+#[derive(Debug, Clone)]
+pub struct GitPackageUrl {
+    url: GitUrl,
+    rev: Option<String>,
+}
+
+impl GitPackageUrl {
+    pub fn url(&self) -> &GitUrl {
+        &self.url
+    }
+
+    pub fn rev(&self) -> Option<&str> {
+        self.rev.as_deref()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ParseGitPackageUrlError {
+    #[error("expected a git URL starting with `git+`, e.g. `git+https://github.com/owner/repo`")]
+    MissingPrefix,
+    #[error(transparent)]
+    GitUrl(#[from] GitUrlParseError),
+}
+
+impl FromStr for GitPackageUrl {
+    type Err = ParseGitPackageUrlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s
+            .strip_prefix(GIT_PREFIX)
+            .ok_or(ParseGitPackageUrlError::MissingPrefix)?;
+        let (url, rev) = match s.rsplit_once('#') {
+            Some((url, rev)) if !rev.is_empty() => (url, Some(rev.to_string())),
+            _ => (s, None),
+        };
+        Ok(Self {
+            url: url.parse()?,
+            rev,
+        })
+    }
+}
+
+impl Display for GitPackageUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{GIT_PREFIX}{}", self.url)?;
+        if let Some(rev) = &self.rev {
+            write!(f, "#{rev}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A package from a git repository, with a rockspec generated from its `lux.toml`
+/// or taken from its rockspec, whose source is pinned to the commit that was checked out.
+#[derive(Debug, Clone)]
+pub struct GitPackage {
+    rockspec: RemoteLuaRockspec,
+    rockspec_content: String,
+    url: String,
+    commit: String,
+}
+
+impl GitPackage {
+    pub fn rockspec(&self) -> &RemoteLuaRockspec {
+        &self.rockspec
+    }
+
+    /// The ID of the commit that the package is built from.
+    pub fn commit(&self) -> &str {
+        &self.commit
+    }
+
+    pub fn package(&self) -> PackageSpec {
+        PackageSpec::new(
+            self.rockspec.package().clone(),
+            self.rockspec.version().clone(),
+        )
+    }
+
+    /// Install the package as an entrypoint.
+    /// The package is always rebuilt, and its lockfile entry records the commit.
+    pub fn into_install_spec(self, pin: PinnedState) -> PackageInstallSpec {
+        let package = self.package().into_package_req();
+        let rockspec_download = DownloadedRockspec {
+            rockspec: self.rockspec,
+            source: RemotePackageSource::RockspecContent(self.rockspec_content),
+            source_url: Some(RemotePackageSourceUrl::Git {
+                url: self.url,
+                checkout_ref: self.commit,
+            }),
+        };
+        PackageInstallSpec::new(package, tree::EntryType::Entrypoint)
+            .build_behaviour(BuildBehaviour::Force)
+            .pin(pin)
+            .rockspec_download(rockspec_download)
+            .build()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FetchGitPackageError {
+    #[error("failed to clone {0}: {1}")]
+    Clone(String, git2::Error),
+    #[error("failed to check out {0}: {1}")]
+    Checkout(String, git2::Error),
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, io::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("no {PROJECT_TOML} or rockspec found in {0}")]
+    NoRockspec(String),
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error(transparent)]
+    RemoteProjectToml(#[from] RemoteProjectTomlValidationError),
+    #[error(transparent)]
+    ProjectToml(#[from] ProjectTomlError),
+    #[error("invalid rockspec in {0}: {1}")]
+    LuaRockspec(String, LuaRockspecError),
+}
+
+/// Clones a git repository and reads the package it contains,
+/// from its `lux.toml` or, if there is none, from its rockspec.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct FetchGitPackage<'a> {
+    #[builder(start_fn)]
+    url: &'a GitPackageUrl,
+    #[builder(start_fn)]
+    progress: &'a Progress<ProgressBar>,
+}
+
+impl<State: fetch_git_package_builder::State> FetchGitPackageBuilder<'_, State> {
+    pub fn fetch(self) -> Result<GitPackage, FetchGitPackageError>
+    where
+        State: fetch_git_package_builder::IsComplete,
+    {
+        do_fetch_git_package(self._build())
+    }
+}
+
+fn do_fetch_git_package(args: FetchGitPackage<'_>) -> Result<GitPackage, FetchGitPackageError> {
+    let url = args.url.url.to_string();
+    args.progress
+        .map(|p| p.set_message(format!("🦠 Cloning {url}")));
+
+    let temp_dir = TempDir::new("lux-git-package")?;
+    let dir = temp_dir.path();
+    // We need the full history, as the version may be generated from git tags.
+    let repo = RepoBuilder::new()
+        .clone(&url, dir)
+        .map_err(|err| FetchGitPackageError::Clone(url.clone(), err))?;
+    let commit = checkout(&repo, args.url.rev())
+        .map_err(|err| FetchGitPackageError::Checkout(args.url.to_string(), err))?;
+
+    let rockspec_content = match Project::from_exact(dir)? {
+        Some(project) => project
+            .toml()
+            .into_remote()?
+            .to_lua_remote_rockspec_string()?,
+        None => {
+            let path = find_rockspec(dir).ok_or(FetchGitPackageError::NoRockspec(url.clone()))?;
+            std::fs::read_to_string(&path).map_err(|err| FetchGitPackageError::Read(path, err))?
+        }
+    };
+    // Pin the source to the commit we checked out.
+    // Later assignments to a rockspec's fields take precedence.
+    let source = RockSourceInternal {
+        url: Some(format!("{GIT_PREFIX}{url}")),
+        tag: Some(commit.clone()),
+        ..RockSourceInternal::default()
+    };
+    let rockspec_content = format!("{rockspec_content}\n{}\n", source.display_lua());
+    let rockspec = RemoteLuaRockspec::new(&rockspec_content)
+        .map_err(|err| FetchGitPackageError::LuaRockspec(url.clone(), err))?;
+
+    args.progress.map(|p| p.finish_and_clear());
+
+    Ok(GitPackage {
+        rockspec,
+        rockspec_content,
+        url,
+        commit,
+    })
+}
+
+/// Check out the `rev`, or the default branch if there is none, returning the commit ID.
+fn checkout(repo: &Repository, rev: Option<&str>) -> Result<String, git2::Error> {
+    if let Some(rev) = rev {
+        let object = repo
+            .revparse_single(rev)
+            .or_else(|_| repo.revparse_single(&format!("origin/{rev}")))?;
+        let commit = object.peel_to_commit()?;
+        repo.checkout_tree(commit.as_object(), None)?;
+        repo.set_head_detached(commit.id())?;
+    }
+    Ok(repo.head()?.peel_to_commit()?.id().to_string())
+}
+
+/// Find a rockspec in the repository's root or in its `rockspecs` directory,
+/// preferring the latest version.
+fn find_rockspec(dir: &Path) -> Option<PathBuf> {
+    [dir.to_path_buf(), dir.join("rockspecs")]
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "rockspec"))
+        .filter_map(|path| {
+            let rockspec = RemoteLuaRockspec::new(&std::fs::read_to_string(&path).ok()?).ok()?;
+            Some((rockspec.version().clone(), path))
+        })
+        .sorted()
+        .next_back()
+        .map(|(_, path)| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Signature;
+
+    use crate::lua_rockspec::RockSourceSpec;
+
+    use super::*;
+
+    fn init_repo(dir: &Path) -> String {
+        let repo = Repository::init(dir).unwrap();
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Signature::now("name", "email").unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "initial", &tree, &[])
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn parse_git_package_url() {
+        let url: GitPackageUrl = "git+https://github.com/owner/repo#v1.0.0".parse().unwrap();
+        assert_eq!(url.rev(), Some("v1.0.0"));
+        assert_eq!(url.to_string(), "git+https://github.com/owner/repo#v1.0.0");
+        let url: GitPackageUrl = "git+https://github.com/owner/repo".parse().unwrap();
+        assert_eq!(url.rev(), None);
+        assert!(matches!(
+            "https://github.com/owner/repo".parse::<GitPackageUrl>(),
+            Err(ParseGitPackageUrlError::MissingPrefix)
+        ));
+    }
+
+    #[test]
+    fn fetch_git_package_from_project_toml() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            temp.join(PROJECT_TOML),
+            r#"
+package = "foo"
+version = "1.0.0"
+lua = ">=5.1"
+
+[dependencies]
+bar = ">=1.0"
+
+[source]
+url = "https://example.com/foo-1.0.0.tar.gz"
+
+[build]
+type = "builtin"
+"#,
+        )
+        .unwrap();
+        let commit = init_repo(temp.path());
+        let url: GitPackageUrl = format!("git+file://{}", temp.path().display())
+            .parse()
+            .unwrap();
+
+        let package = FetchGitPackage::new(&url, &Progress::NoProgress)
+            .fetch()
+            .unwrap();
+        assert_eq!(package.commit(), commit);
+        assert_eq!(package.package().to_string(), "foo 1.0.0-1");
+        let rockspec = package.rockspec();
+        assert_eq!(rockspec.dependencies().current_platform().len(), 1);
+        assert!(matches!(
+            &rockspec.source().current_platform().source_spec,
+            RockSourceSpec::Git(git) if git.checkout_ref.as_deref() == Some(commit.as_str())
+        ));
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bon::Builder;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+use crate::{
+    config::LuaVersion,
+    lua_rockspec::{DisplayLuaKV, DisplayLuaValue, LuaRockspecError, RemoteLuaRockspec},
+    package::{PackageName, PackageVersion},
+    progress::{Progress, ProgressBar},
+    rockspec::{LuaVersionCompatibility, Rockspec},
+};
+
+/// Caches the metadata of indexed rocks, so that unchanged files
+/// don't need to be re-read when regenerating the manifests.
+pub const INDEX_CACHE_FILE: &str = ".lux-index-cache.json";
+
+/// The Lua versions for which luarocks servers provide a `manifest-<version>`.
+const MANIFEST_LUA_VERSIONS: [LuaVersion; 4] = [
+    LuaVersion::Lua51,
+    LuaVersion::Lua52,
+    LuaVersion::Lua53,
+    LuaVersion::Lua54,
+];
+
+/// Generates luarocks-compatible root manifests from a directory of rockspecs and rocks,
+/// so that the directory can be served as a static luarocks server.
+///
+/// Writes a `manifest` with all rocks, as well as a `manifest-<lua version>` per Lua version,
+/// each alongside a zipped variant.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Index<'a> {
+    #[builder(start_fn)]
+    dir: &'a Path,
+    #[builder(start_fn)]
+    progress: &'a Progress<ProgressBar>,
+
+    /// Re-read all rockspecs and rocks, ignoring the index cache.
+    #[builder(default)]
+    force: bool,
+}
+
+impl<State> IndexBuilder<'_, State>
+where
+    State: index_builder::State + index_builder::IsComplete,
+{
+    pub async fn index(self) -> Result<IndexReport, IndexError> {
+        do_index(self._build()).await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum IndexError {
+    #[error("failed to read {0}:\n{1}")]
+    Read(PathBuf, io::Error),
+    #[error("failed to write {0}:\n{1}")]
+    Write(PathBuf, io::Error),
+    #[error("failed to zip {0}:\n{1}")]
+    Zip(PathBuf, zip::result::ZipError),
+    #[error("failed to write the index cache:\n{0}")]
+    Cache(#[from] serde_json::Error),
+}
+
+#[derive(Error, Debug)]
+enum ReadRockError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    Rockspec(#[from] LuaRockspecError),
+    #[error("no rockspec found in the rock's root directory")]
+    MissingRockspec,
+}
+
+#[derive(Debug)]
+pub struct IndexReport {
+    rocks: usize,
+    reindexed: Vec<PathBuf>,
+    written: Vec<PathBuf>,
+}
+
+impl IndexReport {
+    /// The number of rockspecs and rocks in the manifests.
+    pub fn rocks(&self) -> usize {
+        self.rocks
+    }
+
+    /// The rockspecs and rocks that were (re-)read, because they are new or have changed.
+    pub fn reindexed(&self) -> &[PathBuf] {
+        &self.reindexed
+    }
+
+    /// The manifest files that were (re-)written.
+    /// Manifests whose content hasn't changed are not rewritten.
+    pub fn written(&self) -> &[PathBuf] {
+        &self.written
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedRock {
+    name: PackageName,
+    version: PackageVersion,
+    /// e.g. "rockspec", "src", "all" or "linux-x86_64"
+    arch: String,
+    lua_versions: Vec<LuaVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexCacheEntry {
+    modified: SystemTime,
+    len: u64,
+    rock: IndexedRock,
+}
+
+type IndexCache = HashMap<String, IndexCacheEntry>;
+
+async fn do_index(args: Index<'_>) -> Result<IndexReport, IndexError> {
+    let dir = args.dir;
+    let cache_path = dir.join(INDEX_CACHE_FILE);
+    let mut cache: IndexCache = if args.force || !cache_path.is_file() {
+        IndexCache::new()
+    } else {
+        let content = tokio::fs::read_to_string(&cache_path)
+            .await
+            .map_err(|err| IndexError::Read(cache_path.clone(), err))?;
+        // An outdated or corrupt cache is not an error, we just rebuild it.
+        serde_json::from_str(&content).unwrap_or_default()
+    };
+
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .map_err(|err| IndexError::Read(dir.to_path_buf(), err))?;
+    let mut rocks = Vec::new();
+    let mut reindexed = Vec::new();
+    let mut seen = HashSet::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|err| IndexError::Read(dir.to_path_buf(), err))?
+    {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let Some(arch) = rock_arch(&file_name) else {
+            continue;
+        };
+        let metadata = entry
+            .metadata()
+            .await
+            .map_err(|err| IndexError::Read(path.clone(), err))?;
+        let modified = metadata
+            .modified()
+            .map_err(|err| IndexError::Read(path.clone(), err))?;
+        seen.insert(file_name.clone());
+        if let Some(cached) = cache.get(&file_name) {
+            if cached.modified == modified && cached.len == metadata.len() {
+                rocks.push(cached.rock.clone());
+                continue;
+            }
+        }
+        args.progress
+            .map(|b| b.set_message(format!("📖 Reading {file_name}")));
+        let rockspec = match read_rockspec(&path, &arch) {
+            Ok(rockspec) => rockspec,
+            Err(err) => {
+                args.progress.map(|b| {
+                    b.println(format!("⚠️ WARNING: skipping {}: {}", path.display(), err))
+                });
+                cache.remove(&file_name);
+                continue;
+            }
+        };
+        let rock = IndexedRock {
+            name: rockspec.package().clone(),
+            version: rockspec.version().clone(),
+            lua_versions: MANIFEST_LUA_VERSIONS
+                .into_iter()
+                .filter(|lua_version| rockspec.supports_lua_version(lua_version))
+                .collect(),
+            arch,
+        };
+        cache.insert(
+            file_name,
+            IndexCacheEntry {
+                modified,
+                len: metadata.len(),
+                rock: rock.clone(),
+            },
+        );
+        reindexed.push(path);
+        rocks.push(rock);
+    }
+    cache.retain(|file_name, _| seen.contains(file_name));
+
+    let mut written = Vec::new();
+    let manifests = std::iter::once(("manifest".to_string(), None)).chain(
+        MANIFEST_LUA_VERSIONS.iter().map(|lua_version| {
+            (
+                format!("manifest-{}", lua_version.version_compatibility_str()),
+                Some(lua_version),
+            )
+        }),
+    );
+    for (manifest_name, lua_version) in manifests {
+        let content = render_manifest(rocks.iter().filter(|rock| {
+            lua_version.is_none_or(|lua_version| rock.lua_versions.contains(lua_version))
+        }));
+        let manifest_path = dir.join(&manifest_name);
+        let mut zip_path = manifest_path.clone().into_os_string();
+        zip_path.push(".zip");
+        let zip_path = PathBuf::from(zip_path);
+        let is_unchanged = zip_path.is_file()
+            && tokio::fs::read_to_string(&manifest_path)
+                .await
+                .is_ok_and(|existing| existing == content);
+        if is_unchanged {
+            continue;
+        }
+        tokio::fs::write(&manifest_path, &content)
+            .await
+            .map_err(|err| IndexError::Write(manifest_path.clone(), err))?;
+        write_zipped_manifest(&zip_path, &manifest_name, &content)?;
+        written.push(manifest_path);
+        written.push(zip_path);
+    }
+
+    let cache_content = serde_json::to_string(&cache)?;
+    tokio::fs::write(&cache_path, cache_content)
+        .await
+        .map_err(|err| IndexError::Write(cache_path.clone(), err))?;
+
+    Ok(IndexReport {
+        rocks: rocks.len(),
+        reindexed,
+        written,
+    })
+}
+
+/// The luarocks manifest `arch` of a rockspec or rock file,
+/// e.g. `foo-1.0.0-1.linux-x86_64.rock` -> `linux-x86_64`.
+fn rock_arch(file_name: &str) -> Option<String> {
+    if file_name.ends_with(".rockspec") {
+        Some("rockspec".into())
+    } else {
+        let (_, arch) = file_name.strip_suffix(".rock")?.rsplit_once('.')?;
+        Some(arch.into())
+    }
+}
+
+fn read_rockspec(path: &Path, arch: &str) -> Result<RemoteLuaRockspec, ReadRockError> {
+    let content = if arch == "rockspec" {
+        std::fs::read_to_string(path)?
+    } else {
+        let mut archive = ZipArchive::new(File::open(path)?)?;
+        let rockspec_name = archive
+            .file_names()
+            .find(|name| !name.contains('/') && name.ends_with(".rockspec"))
+            .ok_or(ReadRockError::MissingRockspec)?
+            .to_string();
+        let mut content = String::new();
+        archive
+            .by_name(&rockspec_name)?
+            .read_to_string(&mut content)?;
+        content
+    };
+    Ok(RemoteLuaRockspec::new(&content)?)
+}
+
+fn render_manifest<'a>(rocks: impl Iterator<Item = &'a IndexedRock>) -> String {
+    let mut repository: BTreeMap<&PackageName, BTreeMap<&PackageVersion, Vec<&str>>> =
+        BTreeMap::new();
+    for rock in rocks {
+        repository
+            .entry(&rock.name)
+            .or_default()
+            .entry(&rock.version)
+            .or_default()
+            .push(&rock.arch);
+    }
+    let repository = DisplayLuaKV {
+        key: "repository".into(),
+        value: DisplayLuaValue::Table(
+            repository
+                .into_iter()
+                .map(|(name, versions)| DisplayLuaKV {
+                    key: name.to_string(),
+                    value: DisplayLuaValue::Table(
+                        versions
+                            .into_iter()
+                            .map(|(version, arches)| DisplayLuaKV {
+                                key: version.to_string(),
+                                value: DisplayLuaValue::List(
+                                    arches
+                                        .into_iter()
+                                        .sorted_by_key(|arch| match *arch {
+                                            "rockspec" => (0, *arch),
+                                            "src" => (1, *arch),
+                                            _ => (2, *arch),
+                                        })
+                                        .map(|arch| {
+                                            DisplayLuaValue::Table(vec![DisplayLuaKV {
+                                                key: "arch".into(),
+                                                value: DisplayLuaValue::String(arch.into()),
+                                            }])
+                                        })
+                                        .collect(),
+                                ),
+                            })
+                            .collect(),
+                    ),
+                })
+                .collect(),
+        ),
+    };
+    format!("commands = {{}}\nmodules = {{}}\n{repository}\n")
+}
+
+fn write_zipped_manifest(
+    zip_path: &Path,
+    manifest_name: &str,
+    content: &str,
+) -> Result<(), IndexError> {
+    let file =
+        File::create(zip_path).map_err(|err| IndexError::Write(zip_path.to_path_buf(), err))?;
+    let mut zip = ZipWriter::new(file);
+    zip.start_file(manifest_name, SimpleFileOptions::default())
+        .map_err(|err| IndexError::Zip(zip_path.to_path_buf(), err))?;
+    zip.write_all(content.as_bytes())
+        .map_err(|err| IndexError::Write(zip_path.to_path_buf(), err))?;
+    zip.finish()
+        .map_err(|err| IndexError::Zip(zip_path.to_path_buf(), err))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::TempDir;
+
+    use crate::manifest::ManifestMetadata;
+
+    use super::*;
+
+    fn rockspec(name: &str, version: &str, lua: &str) -> String {
+        format!(
+            r#"
+package = "{name}"
+version = "{version}"
+source = {{
+    url = "https://example.com/{name}-{version}.tar.gz",
+}}
+dependencies = {{
+    "lua {lua}",
+}}
+"#
+        )
+    }
+
+    #[tokio::test]
+    async fn index_directory() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.join("foo-1.0.0-1.rockspec"),
+            rockspec("foo", "1.0.0-1", ">= 5.1"),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("lua-bar-2.0.0-1.rockspec"),
+            rockspec("lua-bar", "2.0.0-1", ">= 5.4"),
+        )
+        .unwrap();
+        let rock = File::create(dir.join("foo-1.0.0-1.src.rock")).unwrap();
+        let mut zip = ZipWriter::new(rock);
+        zip.start_file("foo-1.0.0-1.rockspec", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(rockspec("foo", "1.0.0-1", ">= 5.1").as_bytes())
+            .unwrap();
+        zip.finish().unwrap();
+
+        let report = Index::new(dir.path(), &Progress::NoProgress)
+            .index()
+            .await
+            .unwrap();
+        assert_eq!(report.rocks(), 3);
+        assert_eq!(report.reindexed().len(), 3);
+        assert_eq!(report.written().len(), 10);
+
+        let manifest =
+            ManifestMetadata::new(&std::fs::read_to_string(dir.join("manifest-5.1")).unwrap())
+                .unwrap();
+        assert!(manifest.has_rock(&"foo".into()));
+        assert!(!manifest.has_rock(&"lua-bar".into()));
+        let manifest =
+            ManifestMetadata::new(&std::fs::read_to_string(dir.join("manifest-5.4")).unwrap())
+                .unwrap();
+        assert!(manifest.has_rock(&"foo".into()));
+        assert!(manifest.has_rock(&"lua-bar".into()));
+
+        let mut archive =
+            ZipArchive::new(File::open(dir.join("manifest-5.4.zip")).unwrap()).unwrap();
+        let mut zipped = String::new();
+        archive
+            .by_name("manifest-5.4")
+            .unwrap()
+            .read_to_string(&mut zipped)
+            .unwrap();
+        assert_eq!(
+            zipped,
+            std::fs::read_to_string(dir.join("manifest-5.4")).unwrap()
+        );
+
+        let report = Index::new(dir.path(), &Progress::NoProgress)
+            .index()
+            .await
+            .unwrap();
+        assert_eq!(report.rocks(), 3);
+        assert!(report.reindexed().is_empty());
+        assert!(report.written().is_empty());
+
+        std::fs::remove_file(dir.join("lua-bar-2.0.0-1.rockspec")).unwrap();
+        let report = Index::new(dir.path(), &Progress::NoProgress)
+            .index()
+            .await
+            .unwrap();
+        assert_eq!(report.rocks(), 2);
+        assert!(report.reindexed().is_empty());
+        // only the manifests that contained lua-bar are rewritten
+        assert_eq!(report.written().len(), 4);
+    }
+}
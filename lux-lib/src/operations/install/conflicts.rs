@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::{
+    build::utils::c_dylib_extension,
+    lockfile::{LocalPackage, Lockfile, ReadOnly},
+    lua_rockspec::{BuildBackendSpec, LuaModule},
+    package::PackageSpec,
+    rockspec::Rockspec,
+    tree::{self, InstalledFiles, Tree},
+};
+
+use crate::operations::resolve::PackageInstallData;
+
+/// A file that is provided by more than one package.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConflictingFile {
+    /// A Lua module, which would be shadowed by the other package's module.
+    Module(LuaModule),
+    /// A binary in the tree's `bin` directory, which would be overwritten.
+    Binary(PathBuf),
+}
+
+impl Display for ConflictingFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Module(module) => write!(f, "module `{module}`"),
+            Self::Binary(binary) => write!(f, "binary `{}`", binary.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstallConflict {
+    pub file: ConflictingFile,
+    /// The package that already provides the file.
+    pub owner: PackageSpec,
+    /// The package that would overwrite or shadow the file.
+    pub package: PackageSpec,
+}
+
+impl Display for InstallConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is provided by both {} and {}",
+            self.file, self.owner, self.package
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+#[error(
+    "cannot install packages that provide the same files:\n{}\nuse `--allow-overwrite` to install them anyway{}.",
+    .0.iter().map(|conflict| format!("  - {conflict}")).join("\n"),
+    versioned_hint(&.0),
+)]
+pub struct InstallConflicts(pub Vec<InstallConflict>);
+
+impl InstallConflicts {
+    /// Whether all conflicts are between modules, which can be installed side by side.
+    pub fn modules_only(&self) -> bool {
+        modules_only(&self.0)
+    }
+}
+
+fn modules_only(conflicts: &[InstallConflict]) -> bool {
+    conflicts
+        .iter()
+        .all(|conflict| matches!(conflict.file, ConflictingFile::Module(_)))
+}
+
+fn versioned_hint(conflicts: &[InstallConflict]) -> &'static str {
+    if modules_only(conflicts) {
+        ", or `--versioned` to install them side by side"
+    } else {
+        ""
+    }
+}
+
+/// Tracks which package owns each module and binary.
+/// Packages with the same name do not conflict with each other,
+/// as installing a different version of a package is expected to replace its files.
+#[derive(Default)]
+struct FileOwners {
+    owners: HashMap<ConflictingFile, PackageSpec>,
+    conflicts: Vec<InstallConflict>,
+}
+
+impl FileOwners {
+    fn insert(&mut self, file: ConflictingFile, package: &PackageSpec) {
+        match self.owners.get(&file) {
+            Some(owner) if owner.name() != package.name() => {
+                self.conflicts.push(InstallConflict {
+                    file,
+                    owner: owner.clone(),
+                    package: package.clone(),
+                });
+            }
+            Some(_) => {}
+            None => {
+                self.owners.insert(file, package.clone());
+            }
+        }
+    }
+}
+
+/// Check that the packages to install don't provide modules or binaries
+/// that are already provided by installed packages, or by each other.
+pub(crate) fn detect_conflicts<'a>(
+    packages: impl IntoIterator<Item = &'a PackageInstallData>,
+    lockfile: &Lockfile<ReadOnly>,
+    tree: &Tree,
+) -> Result<(), InstallConflicts> {
+    let mut owners = FileOwners::default();
+    for package in lockfile.rocks().values() {
+        let package_spec = package.to_package();
+        let is_entrypoint = lockfile.is_entrypoint(&package.id());
+        for module in installed_modules(tree, package, is_entrypoint) {
+            owners.insert(ConflictingFile::Module(module), &package_spec);
+        }
+        if is_entrypoint {
+            for binary in package.spec.binaries.iter() {
+                owners.insert(ConflictingFile::Binary(binary.clone()), &package_spec);
+            }
+        }
+    }
+    // The installed packages are expected to be conflict-free.
+    // If they aren't, e.g. because they were installed with `--allow-overwrite`,
+    // we don't want to fail installing unrelated packages.
+    owners.conflicts.clear();
+
+    for install_data in packages {
+        let package_spec = install_data.spec.to_package();
+        let rockspec = install_data.downloaded_rock.rockspec();
+        let modules = match &rockspec.build().current_platform().build_backend {
+            Some(BuildBackendSpec::Builtin(spec)) => spec.modules.keys().cloned().collect_vec(),
+            _ => Vec::new(),
+        };
+        for module in modules {
+            owners.insert(ConflictingFile::Module(module), &package_spec);
+        }
+        if install_data.entry_type == tree::EntryType::Entrypoint {
+            for binary in rockspec.binaries().iter() {
+                owners.insert(ConflictingFile::Binary(binary.clone()), &package_spec);
+            }
+        }
+    }
+
+    if owners.conflicts.is_empty() {
+        Ok(())
+    } else {
+        let conflicts = owners
+            .conflicts
+            .into_iter()
+            .sorted_by_key(|conflict| conflict.to_string())
+            .collect_vec();
+        Err(InstallConflicts(conflicts))
+    }
+}
+
+/// The modules provided by an installed package.
+/// Uses the package's recorded installed files if present,
+/// and falls back to scanning its install directory otherwise.
+fn installed_modules(tree: &Tree, package: &LocalPackage, is_entrypoint: bool) -> Vec<LuaModule> {
+    let layout = if is_entrypoint {
+        tree.entrypoint_layout(package)
+    } else {
+        tree.dependency_layout(package)
+    };
+    let files = match InstalledFiles::load(&layout) {
+        Ok(Some(installed_files)) => {
+            let root = tree.root();
+            installed_files
+                .files()
+                .map(|(file, _)| root.join(file))
+                .collect_vec()
+        }
+        _ => [&layout.src, &layout.lib]
+            .into_iter()
+            .flat_map(|dir| WalkDir::new(dir).into_iter().filter_map(Result::ok))
+            .filter(|entry| !entry.file_type().is_dir())
+            .map(|entry| entry.into_path())
+            .collect_vec(),
+    };
+    let lua_modules = module_files(&files, &layout.src, "lua");
+    let lib_modules = module_files(&files, &layout.lib, c_dylib_extension());
+    lua_modules.chain(lib_modules).collect_vec()
+}
+
+fn module_files<'a>(
+    files: &'a [PathBuf],
+    dir: &'a Path,
+    extension: &'a str,
+) -> impl Iterator<Item = LuaModule> + 'a {
+    files
+        .iter()
+        .filter(move |file| file.extension().is_some_and(|ext| ext == extension))
+        .filter_map(move |file| {
+            file.strip_prefix(dir)
+                .ok()
+                .map(|path| LuaModule::from_pathbuf(path.to_path_buf()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_package_name_does_not_conflict() {
+        let mut owners = FileOwners::default();
+        let foo_1 = PackageSpec::new("foo".into(), "1.0.0-1".parse().unwrap());
+        let foo_2 = PackageSpec::new("foo".into(), "2.0.0-1".parse().unwrap());
+        let bar = PackageSpec::new("bar".into(), "1.0.0-1".parse().unwrap());
+        let module = ConflictingFile::Module("foo.util".parse().unwrap());
+        owners.insert(module.clone(), &foo_1);
+        owners.insert(module.clone(), &foo_2);
+        assert!(owners.conflicts.is_empty());
+        owners.insert(module.clone(), &bar);
+        owners.insert(ConflictingFile::Binary("foo".into()), &foo_1);
+        owners.insert(ConflictingFile::Binary("foo".into()), &bar);
+        let err = InstallConflicts(owners.conflicts).to_string();
+        assert!(err.contains("module `foo.util` is provided by both foo 1.0.0-1 and bar 1.0.0-1"));
+        assert!(err.contains("binary `foo` is provided by both foo 1.0.0-1 and bar 1.0.0-1"));
+    }
+}
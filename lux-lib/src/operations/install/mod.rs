@@ -1,7 +1,10 @@
-use std::{collections::HashMap, io, sync::Arc};
+use std::{collections::HashMap, io, sync::Arc, time::Instant};
 
 use crate::{
-    build::{Build, BuildBehaviour, BuildError, RemotePackageSourceSpec, SrcRockSource},
+    build::{
+        compile_commands::CompileCommands, debug_symbols::DebugSymbols, Build, BuildBehaviour,
+        BuildError, RemotePackageSourceSpec, SrcRockSource,
+    },
     config::{Config, LuaVersionUnset},
     lockfile::{
         LocalPackage, LocalPackageId, LockConstraint, Lockfile, OptState, PinnedState, ReadWrite,
@@ -12,14 +15,19 @@ use crate::{
         install_binary_rock::{BinaryRockInstall, InstallBinaryRockError},
         luarocks_installation::{LuaRocksError, LuaRocksInstallError, LuaRocksInstallation},
     },
-    package::{PackageName, PackageNameList},
+    observer::OperationObserver,
+    package::{PackageName, PackageNameList, PackageSpec},
     progress::{MultiProgress, Progress, ProgressBar},
     project::{Project, ProjectTreeError},
     remote_package_db::{RemotePackageDB, RemotePackageDBError, RemotePackageDbIntegrityError},
     rockspec::Rockspec,
-    tree::{self, Tree, TreeError},
+    timings::{RecordTiming, TimingPhase, Timings},
+    tree::{self, ModulePrecedence, Tree, TreeError},
 };
 
+pub use crate::operations::install::conflicts::{
+    ConflictingFile, InstallConflict, InstallConflicts,
+};
 pub use crate::operations::install::spec::PackageInstallSpec;
 
 use bon::Builder;
@@ -29,9 +37,12 @@ use itertools::Itertools;
 use thiserror::Error;
 
 use super::{
-    resolve::get_all_dependencies, DownloadedRockspec, RemoteRockDownload, SearchAndDownloadError,
+    remove::recover_missing_packages, resolve::get_all_dependencies, run_cancellable,
+    CancellationToken, Cancelled, DownloadedRockspec, RemoteRockDownload, RemoveError,
+    SearchAndDownloadError,
 };
 
+mod conflicts;
 pub mod spec;
 
 /// A rocks package installer, providing fine-grained control
@@ -48,6 +59,24 @@ pub struct Install<'a> {
     tree: Tree,
     package_db: Option<RemotePackageDB>,
     progress: Option<Arc<Progress<MultiProgress>>>,
+    /// Record the time spent in each install phase.
+    timings: Option<Timings>,
+    /// Install packages that provide the same modules as other packages side by side,
+    /// deploying stubs that load the module of the package that takes precedence,
+    /// and make the other package's module available under a versioned name.
+    versioned: Option<ModulePrecedence>,
+    /// Record the commands that compile the packages' C sources.
+    compile_commands: Option<CompileCommands>,
+    /// Notified as packages are resolved, fetched, built and installed, or fail to install.
+    observer: Option<Arc<dyn OperationObserver>>,
+    /// Fail if the packages provide modules or binaries
+    /// that are already provided by other packages.
+    /// Enabled by default. Syncs, project builds and installs of the tools that lux runs
+    /// opt out, as they install what the lockfile, project manifest or lux asks for.
+    #[builder(default = true)]
+    check_conflicts: bool,
+    /// Abort the install when cancelled, killing any running builds.
+    cancellation: Option<CancellationToken>,
 }
 
 impl<'a, State> InstallBuilder<'a, State>
@@ -95,46 +124,66 @@ where
     /// Install the packages.
     pub async fn install(self) -> Result<Vec<LocalPackage>, InstallError> {
         let install_built = self._build();
-        let progress = match install_built.progress {
-            Some(p) => p,
-            None => MultiProgress::new_arc(),
-        };
-        let package_db = match install_built.package_db {
-            Some(db) => db,
-            None => {
-                let bar = progress.map(|p| p.new_bar());
-                RemotePackageDB::from_config(install_built.config, &bar).await?
-            }
-        };
-
-        let duplicate_entrypoints = install_built
-            .packages
-            .iter()
-            .filter(|pkg| pkg.entry_type == tree::EntryType::Entrypoint)
-            .map(|pkg| pkg.package.name())
-            .duplicates()
-            .cloned()
-            .collect_vec();
+        let cancellation = install_built.cancellation.clone().unwrap_or_default();
+        run_cancellable(
+            Some(cancellation.clone()),
+            do_install(install_built, cancellation),
+        )
+        .await
+    }
+}
 
-        if !duplicate_entrypoints.is_empty() {
-            return Err(InstallError::DuplicateEntrypoints(PackageNameList::new(
-                duplicate_entrypoints,
-            )));
+async fn do_install(
+    install_built: Install<'_>,
+    cancellation: CancellationToken,
+) -> Result<Vec<LocalPackage>, InstallError> {
+    let progress = match install_built.progress {
+        Some(p) => p,
+        None => MultiProgress::new_arc(),
+    };
+    let package_db = match install_built.package_db {
+        Some(db) => db,
+        None => {
+            let bar = progress.map(|p| p.new_bar());
+            RemotePackageDB::from_config(install_built.config, &bar).await?
         }
+    };
 
-        install_impl(
-            install_built.packages,
-            Arc::new(package_db),
-            install_built.config,
-            &install_built.tree,
-            progress,
-        )
-        .await
+    let duplicate_entrypoints = install_built
+        .packages
+        .iter()
+        .filter(|pkg| pkg.entry_type == tree::EntryType::Entrypoint)
+        .map(|pkg| pkg.package.name())
+        .duplicates()
+        .cloned()
+        .collect_vec();
+
+    if !duplicate_entrypoints.is_empty() {
+        return Err(InstallError::DuplicateEntrypoints(PackageNameList::new(
+            duplicate_entrypoints,
+        )));
     }
+
+    install_impl(
+        install_built.packages,
+        Arc::new(package_db),
+        install_built.config,
+        &install_built.tree,
+        progress,
+        install_built.timings,
+        install_built.versioned,
+        install_built.compile_commands,
+        install_built.observer,
+        install_built.check_conflicts,
+        cancellation,
+    )
+    .await
 }
 
 #[derive(Error, Debug)]
 pub enum InstallError {
+    #[error("install cancelled")]
+    Cancelled(#[from] Cancelled),
     #[error(transparent)]
     SearchAndDownloadError(#[from] SearchAndDownloadError),
     #[error(transparent)]
@@ -163,6 +212,10 @@ pub enum InstallError {
     ProjectTreeError(#[from] ProjectTreeError),
     #[error("cannot install duplicate entrypoints: {0}")]
     DuplicateEntrypoints(PackageNameList),
+    #[error(transparent)]
+    Conflicts(#[from] InstallConflicts),
+    #[error("failed to recover packages missing from the tree: {0}")]
+    Recovery(#[from] RemoveError),
 }
 
 // TODO(vhyrro): This function has too many arguments. Refactor it.
@@ -173,13 +226,47 @@ async fn install_impl(
     config: &Config,
     tree: &Tree,
     progress_arc: Arc<Progress<MultiProgress>>,
+    timings: Option<Timings>,
+    versioned: Option<ModulePrecedence>,
+    compile_commands: Option<CompileCommands>,
+    observer: Option<Arc<dyn OperationObserver>>,
+    check_conflicts: bool,
+    cancellation: CancellationToken,
 ) -> Result<Vec<LocalPackage>, InstallError> {
     let (dep_tx, mut dep_rx) = tokio::sync::mpsc::unbounded_channel();
     let (build_dep_tx, mut build_dep_rx) = tokio::sync::mpsc::unbounded_channel();
 
+    let lock_bar = progress_arc.map(|p| p.new_bar());
+    let _lock = tree.lock(config, &lock_bar).await?;
+    let _build_lock = tree.build_tree(config)?.lock(config, &lock_bar).await?;
+    lock_bar.map(|b| b.finish_and_clear());
+
+    // Packages that were deleted from the tree are reinstalled, unless they were requested.
+    let recovered = recover_missing_packages(tree, config, &progress_arc)
+        .await?
+        .into_iter()
+        .filter(|recovered| {
+            !packages
+                .iter()
+                .any(|pkg| pkg.package.name() == recovered.name())
+        })
+        .map(|recovered| {
+            PackageInstallSpec::new(
+                recovered.clone().into_package_req(),
+                tree::EntryType::Entrypoint,
+            )
+            .pin(recovered.pinned())
+            .opt(recovered.opt())
+            .constraint(recovered.constraint())
+            .build()
+        })
+        .collect_vec();
+    let packages = packages.into_iter().chain(recovered).collect_vec();
+
     let lockfile = tree.lockfile()?;
     let build_lockfile = tree.build_tree(config)?.lockfile()?;
 
+    let resolve_start = Instant::now();
     get_all_dependencies(
         dep_tx,
         build_dep_tx,
@@ -187,10 +274,30 @@ async fn install_impl(
         package_db.clone(),
         Arc::new(lockfile.clone()),
         Arc::new(build_lockfile.clone()),
+        tree.system_tree()
+            .map(|system_tree| system_tree.lockfile().map(Arc::new))
+            .transpose()?,
+        observer.clone(),
         config,
         progress_arc.clone(),
+        cancellation.clone(),
     )
     .await?;
+    timings.record(TimingPhase::Resolve, None, resolve_start);
+
+    let mut all_packages = HashMap::with_capacity(dep_rx.len());
+    while let Some(dep) = dep_rx.recv().await {
+        all_packages.insert(dep.spec.id(), dep);
+    }
+
+    if check_conflicts {
+        match conflicts::detect_conflicts(all_packages.values(), &lockfile, tree) {
+            Ok(()) => {}
+            // Packages that provide the same modules can be installed side by side.
+            Err(conflicts) if versioned.is_some() && conflicts.modules_only() => {}
+            Err(conflicts) => return Err(conflicts.into()),
+        }
+    }
 
     let lua = Arc::new(
         LuaInstallation::new_from_config(config, &progress_arc.map(|progress| progress.new_bar()))
@@ -221,15 +328,16 @@ async fn install_impl(
             .progress(&bar)
             .constraint(build_dep_spec.spec.constraint())
             .behaviour(build_dep_spec.build_behaviour)
+            .variables(build_dep_spec.variables)
+            .maybe_debug_symbols(build_dep_spec.debug_symbols)
+            .maybe_build_env(build_dep_spec.build_env)
+            .maybe_timings(timings.clone())
+            .maybe_observer(observer.clone())
             .build()
             .await
             .map_err(|err| InstallError::BuildDependencyError(package, err))?;
         build_lockfile.add_entrypoint(&pkg);
-    }
-
-    let mut all_packages = HashMap::with_capacity(dep_rx.len());
-    while let Some(dep) = dep_rx.recv().await {
-        all_packages.insert(dep.spec.id(), dep);
+        observer.package_installed(&pkg);
     }
 
     let installed_packages = join_all(all_packages.clone().into_values().map(|install_spec| {
@@ -238,9 +346,13 @@ async fn install_impl(
         let config = config.clone();
         let tree = tree.clone();
         let lua = lua.clone();
+        let timings = timings.clone();
+        let compile_commands = compile_commands.clone();
+        let observer = observer.clone();
 
-        tokio::spawn({
+        tokio::spawn(run_cancellable(Some(cancellation.clone()), {
             async move {
+                let package_spec = install_spec.spec.to_package();
                 let pkg = match downloaded_rock {
                     RemoteRockDownload::RockspecOnly { rockspec_download } => {
                         install_rockspec(
@@ -251,12 +363,18 @@ async fn install_impl(
                             install_spec.pin,
                             install_spec.opt,
                             install_spec.entry_type,
+                            install_spec.variables,
+                            install_spec.debug_symbols,
+                            install_spec.build_env,
                             &lua,
                             &tree,
                             &config,
                             progress_arc,
+                            timings,
+                            compile_commands,
+                            observer.clone(),
                         )
-                        .await?
+                        .await
                     }
                     RemoteRockDownload::BinaryRock {
                         rockspec_download,
@@ -273,8 +391,10 @@ async fn install_impl(
                             &config,
                             &tree,
                             progress_arc,
+                            timings,
+                            observer.clone(),
                         )
-                        .await?
+                        .await
                     }
                     RemoteRockDownload::SrcRock {
                         rockspec_download,
@@ -293,18 +413,34 @@ async fn install_impl(
                             install_spec.pin,
                             install_spec.opt,
                             install_spec.entry_type,
+                            install_spec.variables,
+                            install_spec.debug_symbols,
+                            install_spec.build_env,
                             &lua,
                             &tree,
                             &config,
                             progress_arc,
+                            timings,
+                            compile_commands,
+                            observer.clone(),
                         )
-                        .await?
+                        .await
+                    }
+                };
+                let pkg = match pkg {
+                    Ok(pkg) => pkg,
+                    Err(err) => {
+                        // Build errors have already been reported by the build.
+                        if !matches!(err, InstallError::BuildError(..)) {
+                            observer.package_failed(&package_spec, &err);
+                        }
+                        return Err(err);
                     }
                 };
 
                 Ok::<_, InstallError>((pkg.id(), (pkg, install_spec.entry_type)))
             }
-        })
+        }))
     }))
     .await
     .into_iter()
@@ -346,6 +482,18 @@ async fn install_impl(
         Ok::<_, io::Error>(())
     })?;
 
+    if let Some(precedence) = versioned {
+        let new_packages = installed_packages
+            .values()
+            .map(|(pkg, _)| pkg.clone())
+            .collect_vec();
+        tree.deploy_versioned_modules(&new_packages, precedence)?;
+    }
+
+    installed_packages
+        .values()
+        .for_each(|(pkg, _)| observer.package_installed(pkg));
+
     Ok(installed_packages
         .into_values()
         .map(|(pkg, _)| pkg)
@@ -361,10 +509,16 @@ async fn install_rockspec(
     pin: PinnedState,
     opt: OptState,
     entry_type: tree::EntryType,
+    variables: HashMap<String, String>,
+    debug_symbols: Option<DebugSymbols>,
+    build_env: Option<Vec<String>>,
     lua: &LuaInstallation,
     tree: &Tree,
     config: &Config,
     progress_arc: Arc<Progress<MultiProgress>>,
+    timings: Option<Timings>,
+    compile_commands: Option<CompileCommands>,
+    observer: Option<Arc<dyn OperationObserver>>,
 ) -> Result<LocalPackage, InstallError> {
     let progress = Arc::clone(&progress_arc);
     let rockspec = rockspec_download.rockspec;
@@ -396,6 +550,12 @@ async fn install_rockspec(
         .behaviour(behaviour)
         .source(source)
         .source_spec(source_spec)
+        .variables(variables)
+        .maybe_debug_symbols(debug_symbols)
+        .maybe_build_env(build_env)
+        .maybe_timings(timings)
+        .maybe_compile_commands(compile_commands)
+        .maybe_observer(observer)
         .build()
         .await
         .map_err(|err| InstallError::BuildError(package, err))?;
@@ -417,16 +577,21 @@ async fn install_binary_rock(
     config: &Config,
     tree: &Tree,
     progress_arc: Arc<Progress<MultiProgress>>,
+    timings: Option<Timings>,
+    observer: Option<Arc<dyn OperationObserver>>,
 ) -> Result<LocalPackage, InstallError> {
     let progress = Arc::clone(&progress_arc);
     let rockspec = rockspec_download.rockspec;
     let package = rockspec.package().clone();
+    let install_start = Instant::now();
     let bar = progress.map(|p| {
         p.add(ProgressBar::from(format!(
             "💻 Installing {} (pre-built)",
             &package,
         )))
     });
+    let package_spec = PackageSpec::new(rockspec.package().clone(), rockspec.version().clone());
+    observer.package_fetched(&package_spec);
     let pkg = BinaryRockInstall::new(
         &rockspec,
         rockspec_download.source,
@@ -444,6 +609,8 @@ async fn install_binary_rock(
     .await
     .map_err(|err| InstallError::InstallBinaryRockError(package, err))?;
 
+    timings.record(TimingPhase::Install, Some(&package_spec), install_start);
+
     bar.map(|b| b.finish_and_clear());
 
     Ok(pkg)
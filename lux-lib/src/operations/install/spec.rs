@@ -1,9 +1,12 @@
+use std::collections::HashMap;
+
 use bon::Builder;
 
 use crate::{
-    build::BuildBehaviour,
+    build::{debug_symbols::DebugSymbols, BuildBehaviour},
     lockfile::{LockConstraint, OptState, PinnedState},
     lua_rockspec::RockSourceSpec,
+    operations::DownloadedRockspec,
     package::PackageReq,
     tree,
 };
@@ -26,4 +29,18 @@ pub struct PackageInstallSpec {
     /// Optional constraint, carried over from a previous install,
     /// e.g. defined in a lockfile.
     pub(crate) constraint: Option<LockConstraint>,
+    /// Build variables that override the config's variables
+    /// when building this package (but not its dependencies).
+    #[builder(default)]
+    pub(crate) variables: HashMap<String, String>,
+    /// What to do with the debug symbols of this package's C modules
+    /// (but not its dependencies'), taking precedence over the config.
+    pub(crate) debug_symbols: Option<DebugSymbols>,
+    /// Environment variables that this package's build processes
+    /// (but not its dependencies') may inherit, in addition to the config's allowlist.
+    pub(crate) build_env: Option<Vec<String>>,
+    /// A rockspec that was fetched beforehand, e.g. from a git repository,
+    /// to install instead of searching the package servers.
+    #[builder(setters(vis = "pub(crate)"))]
+    pub(crate) rockspec_download: Option<DownloadedRockspec>,
 }
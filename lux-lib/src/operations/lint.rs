@@ -0,0 +1,389 @@
+use std::{fmt::Display, io, path::PathBuf, process::Stdio, sync::Arc};
+
+use bon::Builder;
+use itertools::Itertools;
+use path_slash::PathExt;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::process::Command;
+
+use crate::{
+    analysis::lua_files,
+    config::Config,
+    package::{PackageReq, PackageVersionReqError},
+    path::{Paths, PathsError},
+    progress::{MultiProgress, Progress},
+    project::{project_toml::LocalProjectTomlValidationError, Project, ProjectTreeError},
+    tree::{self, TreeError},
+};
+
+use super::{Install, InstallError, PackageInstallSpec};
+
+#[cfg(target_family = "unix")]
+const LUACHECK_EXE: &str = "luacheck";
+#[cfg(target_family = "windows")]
+const LUACHECK_EXE: &str = "luacheck.bat";
+
+const SELENE_EXE: &str = "selene";
+
+/// A linter that can be run with `lx lint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "lowercase"))]
+#[serde(rename_all = "lowercase")]
+pub enum Linter {
+    /// Installed to the project's build tree if missing.
+    Luacheck,
+    /// Must be installed on the system, as it is not distributed as a rock.
+    Selene,
+}
+
+impl Display for Linter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Luacheck => write!(f, "luacheck"),
+            Self::Selene => write!(f, "selene"),
+        }
+    }
+}
+
+/// The `[lint]` section of a `lux.toml`.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct LintSpec {
+    /// The linters to run. Defaults to `luacheck`.
+    #[serde(default)]
+    pub(crate) linters: Option<Vec<Linter>>,
+}
+
+impl LintSpec {
+    pub fn linters(&self) -> Vec<Linter> {
+        self.linters.clone().unwrap_or(vec![Linter::Luacheck])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem reported by a linter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub linter: Linter,
+    /// The file, relative to the project root.
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    /// The linter-specific warning code, e.g. `W211` or `unused_variable`.
+    pub code: String,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {} [{} {}] {}",
+            self.file.to_slash_lossy(),
+            self.line,
+            self.column,
+            self.severity,
+            self.linter,
+            self.code,
+            self.message
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LintError {
+    #[error(transparent)]
+    LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
+    #[error(transparent)]
+    ProjectTree(#[from] ProjectTreeError),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    Paths(#[from] PathsError),
+    #[error(transparent)]
+    PackageVersionReq(#[from] PackageVersionReqError),
+    #[error("error installing luacheck:\n{0}")]
+    InstallLuacheck(#[from] InstallError),
+    #[error("`selene` not found. See https://kampfkarren.github.io/selene/ for installation instructions.")]
+    SeleneNotFound,
+    #[error("failed to run {linter}: {err}")]
+    Io { linter: Linter, err: io::Error },
+    #[error("{linter} failed:\n{stderr}")]
+    LinterFailure { linter: Linter, stderr: String },
+    #[error("error collecting Lua files: {0}")]
+    Walk(#[from] ignore::Error),
+}
+
+/// Runs the project's linters and collects their diagnostics into a single report.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Lint<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// Arguments to pass to each linter after the Lua files to lint.
+    #[builder(field)]
+    args: Vec<String>,
+
+    /// Overrides the `[lint]` linters in the lux.toml.
+    linters: Option<Vec<Linter>>,
+
+    /// Lint files that are ignored by `.gitignore` and similar files.
+    #[builder(default)]
+    no_ignore: bool,
+
+    #[builder(default = MultiProgress::new_arc())]
+    progress: Arc<Progress<MultiProgress>>,
+}
+
+impl<State: lint_builder::State> LintBuilder<'_, State> {
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args(mut self, args: impl IntoIterator<Item: Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map_into());
+        self
+    }
+
+    /// Returns the diagnostics of all linters, sorted by location.
+    pub async fn lint(self) -> Result<Vec<Diagnostic>, LintError>
+    where
+        State: lint_builder::IsComplete,
+    {
+        do_lint(self._build()).await
+    }
+}
+
+async fn do_lint(lint: Lint<'_>) -> Result<Vec<Diagnostic>, LintError> {
+    let project = lint.project;
+    let config = lint.config;
+    let linters = match lint.linters {
+        Some(linters) => linters,
+        None => project.toml().into_local()?.lint().linters(),
+    };
+
+    let args = if lint.no_ignore {
+        vec![".".into()]
+    } else {
+        lua_files(project.root())?
+    }
+    .into_iter()
+    .chain(lint.args)
+    .collect_vec();
+
+    let mut diagnostics = Vec::new();
+    for linter in linters.into_iter().unique() {
+        let linter_diagnostics = match linter {
+            Linter::Luacheck => run_luacheck(project, config, &args, lint.progress.clone()).await?,
+            Linter::Selene => run_selene(project, &args).await?,
+        };
+        diagnostics.extend(linter_diagnostics);
+    }
+
+    Ok(diagnostics
+        .into_iter()
+        .sorted_by(|a, b| {
+            (&a.file, a.line, a.column, a.linter).cmp(&(&b.file, b.line, b.column, b.linter))
+        })
+        .collect_vec())
+}
+
+async fn run_luacheck(
+    project: &Project,
+    config: &Config,
+    args: &[String],
+    progress: Arc<Progress<MultiProgress>>,
+) -> Result<Vec<Diagnostic>, LintError> {
+    let build_tree = project.build_tree(config)?;
+    let luacheck = PackageReq::new("luacheck".into(), None)?;
+    if !build_tree.match_rocks(&luacheck)?.is_found() {
+        Install::new(config)
+            .package(PackageInstallSpec::new(luacheck, tree::EntryType::Entrypoint).build())
+            .tree(build_tree.clone())
+            .check_conflicts(false)
+            .progress(progress)
+            .install()
+            .await?;
+    }
+    let paths = Paths::new(&build_tree)?;
+    let output = Command::new(build_tree.bin().join(LUACHECK_EXE))
+        .current_dir(project.root().as_path())
+        .args(["--formatter", "plain", "--codes", "--no-color"])
+        .args(args)
+        .env("PATH", paths.path_prepended().joined())
+        .env("LUA_PATH", paths.package_path().joined())
+        .env("LUA_CPATH", paths.package_cpath().joined())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|err| LintError::Io {
+            linter: Linter::Luacheck,
+            err,
+        })?;
+    // luacheck exits with 1 if there are warnings and 2 if there are errors.
+    // Anything else is a critical error.
+    if !matches!(output.status.code(), Some(0..=2)) {
+        return Err(LintError::LinterFailure {
+            linter: Linter::Luacheck,
+            stderr: String::from_utf8_lossy(&output.stderr).into(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(parse_luacheck_line)
+        .collect_vec())
+}
+
+/// Parses a line of luacheck's `plain` output, e.g.
+/// `src/foo.lua:1:7: (W211) unused variable 'x'`.
+fn parse_luacheck_line(line: &str) -> Option<Diagnostic> {
+    let (location, rest) = line.split_once(": (")?;
+    let (code, message) = rest.split_once(") ")?;
+    let mut location = location.rsplitn(3, ':');
+    let column = location.next()?.parse().ok()?;
+    let line = location.next()?.parse().ok()?;
+    let file = PathBuf::from(location.next()?);
+    Some(Diagnostic {
+        linter: Linter::Luacheck,
+        file,
+        line,
+        column,
+        severity: if code.starts_with('E') {
+            Severity::Error
+        } else {
+            Severity::Warning
+        },
+        code: code.to_string(),
+        message: message.to_string(),
+    })
+}
+
+async fn run_selene(project: &Project, args: &[String]) -> Result<Vec<Diagnostic>, LintError> {
+    let selene = which::which(SELENE_EXE).map_err(|_| LintError::SeleneNotFound)?;
+    let output = Command::new(selene)
+        .current_dir(project.root().as_path())
+        .args(["--display-style=json2", "--no-summary"])
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|err| LintError::Io {
+            linter: Linter::Selene,
+            err,
+        })?;
+    let diagnostics = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(parse_selene_line)
+        .collect_vec();
+    // selene exits with 1 if there are errors.
+    if !output.status.success() && diagnostics.is_empty() {
+        return Err(LintError::LinterFailure {
+            linter: Linter::Selene,
+            stderr: String::from_utf8_lossy(&output.stderr).into(),
+        });
+    }
+    Ok(diagnostics)
+}
+
+#[derive(Deserialize)]
+struct SeleneDiagnostic {
+    #[serde(rename = "type")]
+    kind: String,
+    severity: String,
+    code: String,
+    message: String,
+    primary_label: SeleneLabel,
+}
+
+#[derive(Deserialize)]
+struct SeleneLabel {
+    filename: PathBuf,
+    span: SeleneSpan,
+}
+
+#[derive(Deserialize)]
+struct SeleneSpan {
+    start_line: usize,
+    start_column: usize,
+}
+
+/// Parses a line of selene's `json2` output.
+/// Lines that aren't diagnostics, like summaries, are skipped.
+fn parse_selene_line(line: &str) -> Option<Diagnostic> {
+    let diagnostic: SeleneDiagnostic = serde_json::from_str(line).ok()?;
+    if diagnostic.kind != "Diagnostic" {
+        return None;
+    }
+    Some(Diagnostic {
+        linter: Linter::Selene,
+        file: diagnostic.primary_label.filename,
+        // selene's lines and columns are 0-based
+        line: diagnostic.primary_label.span.start_line + 1,
+        column: diagnostic.primary_label.span.start_column + 1,
+        severity: if diagnostic.severity == "Error" {
+            Severity::Error
+        } else {
+            Severity::Warning
+        },
+        code: diagnostic.code,
+        message: diagnostic.message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_luacheck_output() {
+        let diagnostic =
+            parse_luacheck_line("src/foo.lua:1:7: (W211) unused variable 'x'").unwrap();
+        assert_eq!(diagnostic.file, PathBuf::from("src/foo.lua"));
+        assert_eq!((diagnostic.line, diagnostic.column), (1, 7));
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!(diagnostic.code, "W211");
+        assert_eq!(diagnostic.message, "unused variable 'x'");
+        let diagnostic =
+            parse_luacheck_line("C:\\foo\\bar.lua:3:1: (E011) expected expression").unwrap();
+        assert_eq!(diagnostic.file, PathBuf::from("C:\\foo\\bar.lua"));
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert!(parse_luacheck_line("Total: 1 warning / 0 errors in 1 file").is_none());
+    }
+
+    #[test]
+    fn parse_selene_output() {
+        let line = r#"{"type":"Diagnostic","severity":"Warning","code":"unused_variable","message":"x is defined, but never used","primary_label":{"filename":"src/foo.lua","span":{"start":6,"start_line":0,"start_column":6,"end":7,"end_line":0,"end_column":7},"message":""},"notes":[],"secondary_labels":[]}"#;
+        let diagnostic = parse_selene_line(line).unwrap();
+        assert_eq!(diagnostic.file, PathBuf::from("src/foo.lua"));
+        assert_eq!((diagnostic.line, diagnostic.column), (1, 7));
+        assert_eq!(diagnostic.code, "unused_variable");
+        assert_eq!(
+            diagnostic.to_string(),
+            "src/foo.lua:1:7: warning [selene unused_variable] x is defined, but never used"
+        );
+        assert!(parse_selene_line(r#"{"type":"Summary","errors":0,"warnings":1}"#).is_none());
+    }
+}
@@ -0,0 +1,245 @@
+-- Generated by `lx generate loader`.
+--
+-- Adds the packages installed in a Lux project's tree to `package.path` and `package.cpath`,
+-- so that programs that aren't run with `lx run` can find the project's dependencies.
+--
+-- Usage:
+--   LUA_INIT=@/path/to/lux-loader.lua lua main.lua
+-- or
+--   require('lux-loader')
+--
+-- If multiple versions of a package are installed, entrypoints take precedence.
+-- Otherwise, a warning is printed to stderr, or an error is raised if `LUX_LOADER_STRICT` is set.
+
+local project_root = __LUX_PROJECT_ROOT__
+
+local dir_sep = package.config:sub(1, 1)
+local is_windows = dir_sep == "\\"
+local lib_extension = is_windows and "dll" or "so"
+
+local function join(...)
+    return table.concat({ ... }, dir_sep)
+end
+
+local function lua_version_dir()
+    if jit then
+        return "jit"
+    end
+    return _VERSION:match("Lua (%d+%.%d+)")
+end
+
+-- A minimal JSON decoder, sufficient for reading a Lux lockfile.
+local function decode_json(str)
+    local pos = 1
+    local escapes = { b = "\b", f = "\f", n = "\n", r = "\r", t = "\t" }
+    local value
+
+    local function skip_whitespace()
+        pos = str:find("[^ \t\r\n]", pos) or #str + 1
+    end
+
+    local function fail(msg)
+        error(("lux-loader: invalid lockfile at position %d: %s"):format(pos, msg))
+    end
+
+    local function string_value()
+        local result = {}
+        pos = pos + 1
+        while true do
+            local c = str:sub(pos, pos)
+            if c == "" then
+                fail("unterminated string")
+            elseif c == '"' then
+                pos = pos + 1
+                return table.concat(result)
+            elseif c == "\\" then
+                local escaped = str:sub(pos + 1, pos + 1)
+                if escaped == "u" then
+                    local code = tonumber(str:sub(pos + 2, pos + 5), 16) or fail("invalid unicode escape")
+                    result[#result + 1] = code < 128 and string.char(code) or "?"
+                    pos = pos + 6
+                else
+                    result[#result + 1] = escapes[escaped] or escaped
+                    pos = pos + 2
+                end
+            else
+                local next_special = str:find('["\\]', pos) or #str + 1
+                result[#result + 1] = str:sub(pos, next_special - 1)
+                pos = next_special
+            end
+        end
+    end
+
+    function value()
+        skip_whitespace()
+        local c = str:sub(pos, pos)
+        if c == "{" then
+            local result = {}
+            pos = pos + 1
+            skip_whitespace()
+            if str:sub(pos, pos) == "}" then
+                pos = pos + 1
+                return result
+            end
+            while true do
+                skip_whitespace()
+                if str:sub(pos, pos) ~= '"' then
+                    fail("expected a key")
+                end
+                local key = string_value()
+                skip_whitespace()
+                if str:sub(pos, pos) ~= ":" then
+                    fail("expected ':'")
+                end
+                pos = pos + 1
+                result[key] = value()
+                skip_whitespace()
+                c = str:sub(pos, pos)
+                pos = pos + 1
+                if c == "}" then
+                    return result
+                elseif c ~= "," then
+                    fail("expected ',' or '}'")
+                end
+            end
+        elseif c == "[" then
+            local result = {}
+            pos = pos + 1
+            skip_whitespace()
+            if str:sub(pos, pos) == "]" then
+                pos = pos + 1
+                return result
+            end
+            while true do
+                result[#result + 1] = value()
+                skip_whitespace()
+                c = str:sub(pos, pos)
+                pos = pos + 1
+                if c == "]" then
+                    return result
+                elseif c ~= "," then
+                    fail("expected ',' or ']'")
+                end
+            end
+        elseif c == '"' then
+            return string_value()
+        elseif str:find("^true", pos) then
+            pos = pos + 4
+            return true
+        elseif str:find("^false", pos) then
+            pos = pos + 5
+            return false
+        elseif str:find("^null", pos) then
+            pos = pos + 4
+            return nil
+        else
+            local number = str:match("^-?%d+%.?%d*[eE]?[-+]?%d*", pos)
+            if not number or number == "" then
+                fail("unexpected character '" .. c .. "'")
+            end
+            pos = pos + #number
+            return tonumber(number)
+        end
+    end
+
+    return value()
+end
+
+local function read_file(path)
+    local file = io.open(path, "r")
+    if not file then
+        return nil
+    end
+    local content = file:read("*a")
+    file:close()
+    return content
+end
+
+local tree_root = join(project_root, ".lux", lua_version_dir())
+local lockfile_content = read_file(join(tree_root, "lux.lock"))
+
+local M = {
+    project_root = project_root,
+    tree_root = tree_root,
+    --- The packages that were added to the paths, keyed by name.
+    packages = {},
+}
+
+if not lockfile_content then
+    return M
+end
+
+local lockfile = decode_json(lockfile_content)
+local rocks = lockfile.rocks or {}
+local entrypoints = {}
+for _, id in ipairs(lockfile.entrypoints or {}) do
+    entrypoints[id] = true
+end
+
+-- Group packages by name, so that we can detect version conflicts.
+local by_name = {}
+for id, rock in pairs(rocks) do
+    by_name[rock.name] = by_name[rock.name] or {}
+    table.insert(by_name[rock.name], {
+        id = id,
+        name = rock.name,
+        version = rock.version,
+        entrypoint = entrypoints[id] or false,
+    })
+end
+
+local strict = os.getenv("LUX_LOADER_STRICT")
+local names = {}
+for name in pairs(by_name) do
+    names[#names + 1] = name
+end
+table.sort(names)
+
+local src_paths = {}
+local lib_paths = {}
+for _, name in ipairs(names) do
+    local candidates = by_name[name]
+    table.sort(candidates, function(a, b)
+        if a.entrypoint ~= b.entrypoint then
+            return a.entrypoint
+        end
+        return a.id < b.id
+    end)
+    local selected = candidates[1]
+    local versions = {}
+    -- An entrypoint takes precedence over dependencies, unless there are multiple entrypoint versions.
+    local conflicts = false
+    for _, candidate in ipairs(candidates) do
+        if candidate.version ~= selected.version and not versions[candidate.version] then
+            versions[candidate.version] = true
+            versions[#versions + 1] = candidate.version
+            conflicts = conflicts or candidate.entrypoint or not selected.entrypoint
+        end
+    end
+    if conflicts then
+        local msg = ("lux-loader: multiple versions of %s are installed (%s, %s). Using %s."):format(
+            name,
+            selected.version,
+            table.concat(versions, ", "),
+            selected.version
+        )
+        if strict then
+            error(msg)
+        end
+        io.stderr:write(msg .. "\n")
+    end
+    local rock_dir = join(tree_root, ("%s-%s@%s"):format(selected.id, selected.name, selected.version))
+    src_paths[#src_paths + 1] = join(rock_dir, "src", "?.lua")
+    src_paths[#src_paths + 1] = join(rock_dir, "src", "?", "init.lua")
+    lib_paths[#lib_paths + 1] = join(rock_dir, "lib", "?." .. lib_extension)
+    M.packages[name] = selected
+end
+
+if #src_paths > 0 then
+    -- Stubs of modules that are provided by more than one package take precedence.
+    table.insert(src_paths, 1, join(tree_root, "stubs", "?.lua"))
+    package.path = table.concat(src_paths, ";") .. ";" .. package.path
+    package.cpath = table.concat(lib_paths, ";") .. ";" .. package.cpath
+end
+
+return M
@@ -0,0 +1,117 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use bon::Builder;
+use itertools::Itertools;
+
+use crate::{
+    config::Config,
+    lua_rockspec::RemoteLuaRockspec,
+    package::{PackageReq, PackageSpec},
+    progress::{MultiProgress, Progress},
+    remote_package_db::RemotePackageDB,
+    rockspec::Rockspec,
+};
+
+use super::{Download, RemoteRockDownload, SearchAndDownloadError};
+
+/// Fetches a package's metadata from the remote package database,
+/// without building or installing anything.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct RemoteMetadata<'a> {
+    #[builder(start_fn)]
+    package: &'a PackageReq,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// Instantiated from the config if not set.
+    package_db: Option<RemotePackageDB>,
+
+    #[builder(default = MultiProgress::new_arc())]
+    progress: Arc<Progress<MultiProgress>>,
+}
+
+impl<State: remote_metadata_builder::State> RemoteMetadataBuilder<'_, State> {
+    /// Fetch and parse the package's rockspec.
+    pub async fn rockspec(self) -> Result<RemoteLuaRockspec, SearchAndDownloadError>
+    where
+        State: remote_metadata_builder::IsComplete,
+    {
+        let args = self._build();
+        let package_db = package_db(&args).await?;
+        fetch_rockspec(args.package, args.config, &package_db, &args.progress).await
+    }
+
+    /// Resolve the package and its transitive dependencies for the current platform,
+    /// sorted by name and version.
+    pub async fn resolve(self) -> Result<Vec<PackageSpec>, SearchAndDownloadError>
+    where
+        State: remote_metadata_builder::IsComplete,
+    {
+        let args = self._build();
+        let package_db = package_db(&args).await?;
+        do_resolve(&args, &package_db).await
+    }
+}
+
+async fn package_db(args: &RemoteMetadata<'_>) -> Result<RemotePackageDB, SearchAndDownloadError> {
+    match &args.package_db {
+        Some(package_db) => Ok(package_db.clone()),
+        None => {
+            let bar = args.progress.map(|p| p.new_bar());
+            let package_db = RemotePackageDB::from_config(args.config, &bar).await?;
+            bar.map(|b| b.finish_and_clear());
+            Ok(package_db)
+        }
+    }
+}
+
+async fn fetch_rockspec(
+    package: &PackageReq,
+    config: &Config,
+    package_db: &RemotePackageDB,
+    progress: &Progress<MultiProgress>,
+) -> Result<RemoteLuaRockspec, SearchAndDownloadError> {
+    let bar = progress.map(|p| p.new_bar());
+    let rockspec = Download::new(package, config, &bar)
+        .package_db(package_db)
+        .download_rockspec()
+        .await?
+        .rockspec;
+    bar.map(|b| b.finish_and_clear());
+    Ok(rockspec)
+}
+
+async fn do_resolve(
+    args: &RemoteMetadata<'_>,
+    package_db: &RemotePackageDB,
+) -> Result<Vec<PackageSpec>, SearchAndDownloadError> {
+    let mut resolved: Vec<PackageSpec> = Vec::new();
+    let mut queue = VecDeque::from([(args.package.clone(), None)]);
+    while let Some((package, source)) = queue.pop_front() {
+        if resolved.iter().any(|spec| package.matches(spec)) {
+            continue;
+        }
+        let rockspec = match source {
+            Some(source) => RemoteRockDownload::from_package_req_and_source_spec(package, source)?
+                .rockspec()
+                .clone(),
+            None => fetch_rockspec(&package, args.config, package_db, &args.progress).await?,
+        };
+        queue.extend(
+            rockspec
+                .dependencies()
+                .current_platform()
+                .iter()
+                .map(|dep| (dep.package_req().clone(), dep.source().clone())),
+        );
+        resolved.push(PackageSpec::new(
+            rockspec.package().clone(),
+            rockspec.version().clone(),
+        ));
+    }
+    Ok(resolved
+        .into_iter()
+        .sorted_by(|a, b| a.name().cmp(b.name()).then(a.version().cmp(b.version())))
+        .collect())
+}
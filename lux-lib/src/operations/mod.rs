@@ -1,14 +1,32 @@
 #![allow(ambiguous_glob_reexports)]
 
+mod binaries;
 mod build_lua;
 mod build_project;
+mod bundle;
+mod cancel;
+mod check;
+mod check_build;
 mod download;
 mod exec;
 mod fetch;
+mod format;
+mod gen_lint_config;
+mod gen_loader;
 mod gen_luarc;
+mod gen_nix;
+mod git_package;
+mod index;
 pub mod install;
+mod lint;
+mod metadata;
+mod nix_prefetch;
+mod nvim_link;
+mod outdated_lockfile;
 mod pack;
+mod pack_image;
 mod pin;
+mod purge;
 mod remove;
 mod resolve;
 mod run;
@@ -17,16 +35,35 @@ mod sync;
 mod test;
 mod unpack;
 mod update;
+mod verify;
 
+pub use binaries::*;
 pub use build_lua::*;
 pub use build_project::*;
+pub use bundle::*;
+pub use cancel::*;
+pub use check::*;
+pub use check_build::*;
 pub use download::*;
 pub use exec::*;
 pub use fetch::*;
+pub use format::*;
+pub use gen_lint_config::*;
+pub use gen_loader::*;
 pub use gen_luarc::*;
+pub use gen_nix::*;
+pub use git_package::*;
+pub use index::*;
 pub use install::*;
+pub use lint::*;
+pub use metadata::*;
+pub use nix_prefetch::*;
+pub use nvim_link::*;
+pub use outdated_lockfile::*;
 pub use pack::*;
+pub use pack_image::*;
 pub use pin::*;
+pub use purge::*;
 pub use remove::*;
 pub use run::*;
 pub use run_lua::*;
@@ -34,3 +71,4 @@ pub use sync::*;
 pub use test::*;
 pub use unpack::*;
 pub use update::*;
+pub use verify::*;
@@ -0,0 +1,190 @@
+use std::{fmt::Display, io, path::PathBuf};
+
+use bon::Builder;
+use serde::Serialize;
+use ssri::Integrity;
+use tempdir::TempDir;
+use thiserror::Error;
+
+use crate::{
+    config::Config,
+    hash::to_nix_base32,
+    lockfile::RemotePackageSourceUrl,
+    package::{PackageName, PackageReq, PackageVersion},
+    progress::{Progress, ProgressBar},
+    rockspec::Rockspec,
+};
+
+use super::{gen_nix::nix_string, Download, FetchSrc, FetchSrcError, SearchAndDownloadError};
+
+/// Resolves a package's source and computes the hashes needed
+/// to fetch it with Nix, e.g. for generating Nix expressions from a flake.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct NixPrefetch<'a> {
+    #[builder(start_fn)]
+    package: &'a PackageReq,
+    #[builder(start_fn)]
+    config: &'a Config,
+    #[builder(start_fn)]
+    progress: &'a Progress<ProgressBar>,
+}
+
+impl<State> NixPrefetchBuilder<'_, State>
+where
+    State: nix_prefetch_builder::State + nix_prefetch_builder::IsComplete,
+{
+    pub async fn prefetch(self) -> Result<NixPrefetchData, NixPrefetchError> {
+        do_prefetch(self._build()).await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum NixPrefetchError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Download(#[from] SearchAndDownloadError),
+    #[error(transparent)]
+    FetchSrc(#[from] FetchSrcError),
+}
+
+/// The Nix fetcher that can be used to fetch a package's source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "fetcher")]
+pub enum NixFetcher {
+    /// `fetchgit`. The hash is computed over the checkout, without the `.git` directory.
+    FetchGit { url: String, rev: String },
+    /// `fetchurl`. The hash is computed over the downloaded file.
+    FetchUrl { url: String },
+    /// A local path.
+    Path { path: PathBuf },
+}
+
+impl From<RemotePackageSourceUrl> for NixFetcher {
+    fn from(source_url: RemotePackageSourceUrl) -> Self {
+        match source_url {
+            RemotePackageSourceUrl::Git { url, checkout_ref } => Self::FetchGit {
+                url,
+                rev: checkout_ref,
+            },
+            RemotePackageSourceUrl::Url { url } => Self::FetchUrl {
+                url: url.to_string(),
+            },
+            RemotePackageSourceUrl::File { path } => Self::Path { path },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NixPrefetchData {
+    pub name: PackageName,
+    pub version: PackageVersion,
+    #[serde(flatten)]
+    pub fetcher: NixFetcher,
+    /// The SRI hash, e.g. `sha256-...`.
+    pub hash: String,
+    /// The sha256 in Nix's base-32 encoding, as used in store paths
+    /// and by `nix-prefetch-url`.
+    pub sha256: String,
+}
+
+impl NixPrefetchData {
+    fn new(
+        name: PackageName,
+        version: PackageVersion,
+        fetcher: NixFetcher,
+        integrity: &Integrity,
+    ) -> Self {
+        Self {
+            name,
+            version,
+            fetcher,
+            hash: integrity.to_string(),
+            sha256: to_nix_base32(integrity),
+        }
+    }
+
+    /// A Nix fetcher expression that can be pasted into a derivation.
+    pub fn nix_expression(&self) -> String {
+        match &self.fetcher {
+            NixFetcher::FetchGit { url, rev } => format!(
+                "fetchgit {{\n  url = {};\n  rev = {};\n  hash = {};\n}}",
+                nix_string(url),
+                nix_string(rev),
+                nix_string(&self.hash)
+            ),
+            NixFetcher::FetchUrl { url } => format!(
+                "fetchurl {{\n  url = {};\n  hash = {};\n}}",
+                nix_string(url),
+                nix_string(&self.hash)
+            ),
+            NixFetcher::Path { path } => format!(
+                "builtins.path {{\n  path = {};\n  sha256 = {};\n}}",
+                nix_string(&path.to_string_lossy()),
+                nix_string(&self.sha256)
+            ),
+        }
+    }
+}
+
+impl Display for NixPrefetchData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}@{}", self.name, self.version)?;
+        writeln!(f, "sha256: {}", self.sha256)?;
+        writeln!(f, "hash: {}", self.hash)?;
+        writeln!(f)?;
+        write!(f, "{}", self.nix_expression())
+    }
+}
+
+async fn do_prefetch(args: NixPrefetch<'_>) -> Result<NixPrefetchData, NixPrefetchError> {
+    let rockspec = Download::new(args.package, args.config, args.progress)
+        .download_rockspec()
+        .await?
+        .rockspec;
+    let temp_dir = TempDir::new(&rockspec.package().to_string())?;
+    let metadata = FetchSrc::new(temp_dir.path(), &rockspec, args.config, args.progress)
+        .fetch_internal()
+        .await?;
+    Ok(NixPrefetchData::new(
+        rockspec.package().clone(),
+        rockspec.version().clone(),
+        metadata.source_url.into(),
+        &metadata.hash,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::hash::HasIntegrity;
+
+    use super::*;
+
+    #[test]
+    fn nix_base32_empty_sha256() {
+        let integrity = Bytes::new().hash().unwrap();
+        let data = NixPrefetchData::new(
+            "foo".into(),
+            "1.0.0-1".parse().unwrap(),
+            NixFetcher::FetchUrl {
+                url: "https://example.com/foo.tar.gz".into(),
+            },
+            &integrity,
+        );
+        assert_eq!(
+            data.sha256,
+            "0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73"
+        );
+        assert_eq!(
+            data.hash,
+            "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+        assert_eq!(
+            data.nix_expression(),
+            "fetchurl {\n  url = \"https://example.com/foo.tar.gz\";\n  hash = \"sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=\";\n}"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use bon::Builder;
+use thiserror::Error;
+
+use crate::{
+    config::tree::NVIM_PACK_ROOT,
+    lockfile::{LocalPackage, OptState},
+    tree::{Tree, TreeError},
+};
+
+/// Runtime directories that Neovim searches for in a package.
+const NVIM_RUNTIME_DIRS: &[&str] = &[
+    "after", "autoload", "colors", "compiler", "doc", "ftdetect", "ftplugin", "indent", "keymap",
+    "lsp", "parser", "plugin", "queries", "rplugin", "spell", "syntax",
+];
+
+/// Links the runtime files of installed rocks into a Neovim
+/// `site/pack/lux/{start,opt}` package structure.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct NvimLink<'a> {
+    #[builder(start_fn)]
+    tree: &'a Tree,
+
+    /// The `site` directory to link into.
+    /// Defaults to the `site` directory in the tree's root.
+    site_dir: Option<PathBuf>,
+}
+
+impl<State> NvimLinkBuilder<'_, State>
+where
+    State: nvim_link_builder::State + nvim_link_builder::IsComplete,
+{
+    /// Link the installed rocks, returning the package directories that were linked.
+    pub fn link(self) -> Result<Vec<PathBuf>, NvimLinkError> {
+        do_link(self._build())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum NvimLinkError {
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error("failed to link {src} to {dest}: {err}")]
+    Link {
+        src: PathBuf,
+        dest: PathBuf,
+        err: io::Error,
+    },
+    #[error("cannot link to {0}: a file or directory that is not a symlink already exists")]
+    DestinationExists(PathBuf),
+    #[error("failed to create directory {dir}: {err}")]
+    CreateDir { dir: PathBuf, err: io::Error },
+}
+
+fn do_link(args: NvimLink<'_>) -> Result<Vec<PathBuf>, NvimLinkError> {
+    let tree = args.tree;
+    let pack_dir = match args.site_dir {
+        Some(site_dir) => site_dir.join("pack").join("lux"),
+        None => tree.root().join(NVIM_PACK_ROOT),
+    };
+    let lockfile = tree.lockfile()?;
+    lockfile
+        .rocks()
+        .values()
+        .filter_map(|package| match link_package(tree, package, &pack_dir) {
+            Ok(true) => Some(Ok(pack_dir_for(&pack_dir, package))),
+            Ok(false) => None,
+            Err(err) => Some(Err(err)),
+        })
+        .collect()
+}
+
+fn pack_dir_for(pack_dir: &Path, package: &LocalPackage) -> PathBuf {
+    let start_or_opt = match package.spec.opt {
+        OptState::Required => "start",
+        OptState::Optional => "opt",
+    };
+    pack_dir.join(start_or_opt).join(package.name().to_string())
+}
+
+/// Returns `false` if the package has no runtime files to link.
+fn link_package(
+    tree: &Tree,
+    package: &LocalPackage,
+    pack_dir: &Path,
+) -> Result<bool, NvimLinkError> {
+    let layout = tree.installed_rock_layout(package)?;
+    let dest_dir = pack_dir_for(pack_dir, package);
+    let mut links = Vec::new();
+    if has_entries(&layout.src) {
+        links.push((layout.src.clone(), dest_dir.join("lua")));
+    }
+    // With the `--nvim` layout, the runtime files are already in the pack directory.
+    if layout.etc != dest_dir {
+        links.extend(
+            NVIM_RUNTIME_DIRS
+                .iter()
+                .map(|dir| (layout.etc.join(dir), dest_dir.join(dir)))
+                .filter(|(src, _)| src.is_dir()),
+        );
+    }
+    if links.is_empty() {
+        return Ok(false);
+    }
+    std::fs::create_dir_all(&dest_dir).map_err(|err| NvimLinkError::CreateDir {
+        dir: dest_dir.clone(),
+        err,
+    })?;
+    for (src, dest) in links {
+        link_dir(&src, &dest)?;
+    }
+    Ok(true)
+}
+
+fn has_entries(dir: &Path) -> bool {
+    std::fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some())
+}
+
+fn link_dir(src: &Path, dest: &Path) -> Result<(), NvimLinkError> {
+    let to_link_error = |err| NvimLinkError::Link {
+        src: src.to_path_buf(),
+        dest: dest.to_path_buf(),
+        err,
+    };
+    if let Ok(metadata) = dest.symlink_metadata() {
+        if !metadata.is_symlink() {
+            return Err(NvimLinkError::DestinationExists(dest.to_path_buf()));
+        }
+        remove_symlink(dest).map_err(to_link_error)?;
+    }
+    symlink_dir(src, dest).map_err(to_link_error)
+}
+
+#[cfg(unix)]
+fn symlink_dir(src: &Path, dest: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(src, dest)
+}
+
+#[cfg(windows)]
+fn symlink_dir(src: &Path, dest: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_dir(src, dest)
+}
+
+#[cfg(unix)]
+fn remove_symlink(path: &Path) -> io::Result<()> {
+    std::fs::remove_file(path)
+}
+
+#[cfg(windows)]
+fn remove_symlink(path: &Path) -> io::Result<()> {
+    std::fs::remove_dir(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::PathCopy;
+    use std::path::PathBuf;
+
+    use crate::config::{ConfigBuilder, LuaVersion};
+
+    use super::*;
+
+    #[test]
+    fn link_installed_rocks() {
+        let tree_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/sample-tree");
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.copy_from(&tree_path, &["**"]).unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        let lockfile = tree.lockfile().unwrap();
+        let neorg = lockfile
+            .rocks()
+            .values()
+            .find(|package| package.name().to_string() == "neorg")
+            .unwrap();
+        let layout = tree.installed_rock_layout(neorg).unwrap();
+        std::fs::create_dir_all(layout.etc.join("plugin")).unwrap();
+        std::fs::write(layout.etc.join("plugin").join("neorg.lua"), "").unwrap();
+        std::fs::write(layout.src.join("neorg.lua"), "").unwrap();
+
+        let site_dir = temp.join("site");
+        let linked = NvimLink::new(&tree)
+            .site_dir(site_dir.clone())
+            .link()
+            .unwrap();
+        let neorg_dir = site_dir.join("pack/lux/start/neorg");
+        assert!(linked.contains(&neorg_dir));
+        assert!(neorg_dir.join("plugin/neorg.lua").is_file());
+        assert!(neorg_dir.join("lua/neorg.lua").is_file());
+
+        // Linking is idempotent
+        NvimLink::new(&tree).site_dir(site_dir).link().unwrap();
+        assert!(neorg_dir.join("plugin/neorg.lua").is_file());
+    }
+}
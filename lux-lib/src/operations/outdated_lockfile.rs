@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use bon::Builder;
+use itertools::Itertools;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    config::{Config, LuaVersion, LuaVersionUnset},
+    lockfile::{LocalPackage, LocalPackageLockType},
+    package::{PackageName, PackageVersion},
+    progress::{MultiProgress, Progress},
+    project::{Project, ProjectError},
+    remote_package_db::{RemotePackageDB, RemotePackageDBError},
+    tree::TreeError,
+};
+
+use super::{updatable_packages, update_target};
+
+#[derive(Error, Debug)]
+pub enum OutdatedLockfileError {
+    #[error("error initialising remote package DB: {0}")]
+    RemotePackageDB(#[from] RemotePackageDBError),
+    #[error("error loading project: {0}")]
+    Project(#[from] ProjectError),
+    #[error(transparent)]
+    LuaVersionUnset(#[from] LuaVersionUnset),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+}
+
+/// A locked package that `lx update` would update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LockfileUpdate {
+    name: PackageName,
+    /// The lockfile section the package is locked in, e.g. `"test_dependencies"`.
+    dependencies: &'static str,
+    /// The locked version.
+    locked: PackageVersion,
+    /// The version `lx update` would update to.
+    update: PackageVersion,
+    /// The constraint the package was locked with.
+    constraint: String,
+}
+
+impl LockfileUpdate {
+    pub fn name(&self) -> &PackageName {
+        &self.name
+    }
+
+    pub fn dependencies(&self) -> &str {
+        self.dependencies
+    }
+
+    pub fn locked(&self) -> &PackageVersion {
+        &self.locked
+    }
+
+    pub fn update(&self) -> &PackageVersion {
+        &self.update
+    }
+
+    pub fn constraint(&self) -> &str {
+        &self.constraint
+    }
+}
+
+/// Checks whether `lx update` would change the lockfile, by comparing the
+/// locked packages against the remote manifests, without downloading any sources.
+/// Checks the project lockfile if in a project, otherwise the user tree's lockfile.
+///
+/// Like `lx update`, this skips pinned packages and packages with git or local sources.
+/// Changes to the `lux.toml` that `lx update` would sync are not taken into account.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct OutdatedLockfile<'a> {
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// Instantiated from the config if not set.
+    package_db: Option<RemotePackageDB>,
+
+    #[builder(default = MultiProgress::new_arc())]
+    progress: Arc<Progress<MultiProgress>>,
+}
+
+impl<State: outdated_lockfile_builder::State> OutdatedLockfileBuilder<'_, State> {
+    /// Returns the locked packages that `lx update` would update,
+    /// sorted by lockfile section and name.
+    pub async fn check(self) -> Result<Vec<LockfileUpdate>, OutdatedLockfileError>
+    where
+        State: outdated_lockfile_builder::IsComplete,
+    {
+        let args = self._build();
+        let package_db = match args.package_db {
+            Some(db) => db,
+            None => {
+                let bar = args.progress.map(|p| p.new_bar());
+                let db = RemotePackageDB::from_config(args.config, &bar).await?;
+                bar.map(|b| b.finish_and_clear());
+                db
+            }
+        };
+        let updates = match Project::current()? {
+            Some(project) => {
+                let lockfile = project.lockfile()?;
+                [
+                    (LocalPackageLockType::Regular, "dependencies"),
+                    (LocalPackageLockType::Build, "build_dependencies"),
+                    (LocalPackageLockType::Test, "test_dependencies"),
+                ]
+                .into_iter()
+                .flat_map(|(lock_type, dependencies)| {
+                    lockfile_updates(
+                        lockfile.rocks(&lock_type).values(),
+                        dependencies,
+                        args.config,
+                        &package_db,
+                    )
+                })
+                .collect_vec()
+            }
+            None => {
+                let tree = args
+                    .config
+                    .user_tree(LuaVersion::from(args.config)?.clone())?;
+                let lockfile = tree.lockfile()?;
+                lockfile_updates(
+                    lockfile.rocks().values(),
+                    "dependencies",
+                    args.config,
+                    &package_db,
+                )
+            }
+        };
+        Ok(updates)
+    }
+}
+
+fn lockfile_updates<'a>(
+    rocks: impl IntoIterator<Item = &'a LocalPackage>,
+    dependencies: &'static str,
+    config: &Config,
+    package_db: &RemotePackageDB,
+) -> Vec<LockfileUpdate> {
+    updatable_packages(rocks, false)
+        .into_iter()
+        .filter_map(|(package, constraint)| {
+            update_target(&package, &constraint, config, package_db).map(|update| LockfileUpdate {
+                name: package.name().clone(),
+                dependencies,
+                locked: package.version().clone(),
+                update,
+                constraint: constraint.version_req().to_string(),
+            })
+        })
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .collect_vec()
+}
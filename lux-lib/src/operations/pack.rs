@@ -1,3 +1,4 @@
+use crate::build::provenance::{self, PROVENANCE_FILE_NAME};
 use crate::build::utils;
 use crate::build::utils::c_dylib_extension;
 use crate::lockfile::LocalPackage;
@@ -42,6 +43,11 @@ pub struct Pack {
     tree: Tree,
     #[builder(start_fn)]
     package: LocalPackage,
+
+    /// Write an in-toto provenance statement for the packed rock
+    /// to `<rock>.intoto.json`, alongside the rock.
+    #[builder(default)]
+    attest: bool,
 }
 
 impl<State> PackBuilder<State>
@@ -59,6 +65,7 @@ pub enum PackError {
     Zip(#[from] zip::result::ZipError),
     Io(#[from] io::Error),
     Walkdir(#[from] walkdir::Error),
+    Json(#[from] serde_json::Error),
     #[error("expected a `package.rockspec` in the package root.")]
     MissingRockspec,
 }
@@ -101,6 +108,10 @@ async fn do_pack(args: Pack) -> Result<PathBuf, PackError> {
     let packed_rockspec_name = format!("{}-{}.rockspec", &package.name(), &package.version());
     let renamed_rockspec_entry = temp_dir.join(packed_rockspec_name);
     tokio::fs::copy(layout.rockspec_path(), &renamed_rockspec_entry).await?;
+    if let Some(provenance) = package.provenance() {
+        let provenance_json = serde_json::to_string_pretty(provenance)?;
+        tokio::fs::write(temp_dir.join(PROVENANCE_FILE_NAME), provenance_json).await?;
+    }
     let root_entries = add_rock_entries(&mut zip, &temp_dir, "".into())?;
     let mut bin_entries = HashMap::new();
     for relative_binary_path in package.spec.binaries() {
@@ -140,6 +151,13 @@ async fn do_pack(args: Pack) -> Result<PathBuf, PackError> {
     let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
     zip.start_file("rock_manifest", options)?;
     zip.write_all(manifest_str.as_bytes())?;
+    zip.finish()?;
+    if args.attest {
+        let attestation = provenance::attestation(&package, &output_path)?;
+        let mut attestation_path = output_path.clone().into_os_string();
+        attestation_path.push(".intoto.json");
+        tokio::fs::write(attestation_path, attestation).await?;
+    }
     Ok(output_path)
 }
 
@@ -0,0 +1,626 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use bon::Builder;
+use bytes::Bytes;
+use flate2::{write::GzEncoder, Compression};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tar::{EntryType, Header, HeaderMode};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::{
+    build::utils::{c_dylib_extension, project_files},
+    config::Config,
+    lockfile::{LocalPackage, LocalPackageId},
+    lua_installation::LuaInstallation,
+    project::{project_toml::LocalProjectTomlValidationError, Project, ProjectTreeError},
+    tree::TreeError,
+};
+
+mod registry;
+
+pub use registry::{ImageReference, RegistryError};
+
+/// Where the Lua interpreter is installed in the image.
+const CONTAINER_LUA_BIN: &str = "/opt/lux/bin/lua";
+/// Where the project tree is mirrored in the image.
+const CONTAINER_TREE_ROOT: &str = "/opt/lux/tree";
+/// Where the project's sources are copied to in the image.
+/// This is the working directory, so that `[run]` arguments
+/// can be specified relative to the project root, like with `lx run`.
+const CONTAINER_PROJECT_ROOT: &str = "/opt/lux/project";
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+const OCI_LAYOUT_VERSION: &str = "1.0.0";
+const MEDIA_TYPE_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+const MEDIA_TYPE_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+const MEDIA_TYPE_CONFIG: &str = "application/vnd.oci.image.config.v1+json";
+const MEDIA_TYPE_LAYER: &str = "application/vnd.oci.image.layer.v1.tar+gzip";
+const MEDIA_TYPE_DOCKER_LAYER: &str = "application/vnd.docker.image.rootfs.diff.tar.gzip";
+
+/// Exports a built project as an OCI image archive,
+/// which can be loaded with `docker load` or `podman load`.
+///
+/// The image consists of the following layers, on top of an optional base image:
+///   - The Lua interpreter.
+///   - The project's runtime dependencies.
+///   - The project package and its sources.
+///
+/// The entrypoint is derived from the `[run]` section of the project's `lux.toml`.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct PackImage<'a> {
+    #[builder(start_fn)]
+    dest_dir: PathBuf,
+    #[builder(start_fn)]
+    project: &'a Project,
+    /// The project package, as installed in the project tree.
+    #[builder(start_fn)]
+    package: LocalPackage,
+
+    config: &'a Config,
+    lua: &'a LuaInstallation,
+
+    /// An image to layer the project on top of.
+    /// Without a base image, the image contains only the Lua interpreter and the project tree,
+    /// so the interpreter and any C modules must not depend on shared libraries.
+    base_image: Option<ImageReference>,
+}
+
+impl<State> PackImageBuilder<'_, State>
+where
+    State: pack_image_builder::State + pack_image_builder::IsComplete,
+{
+    /// Write the image archive, returning its path.
+    pub async fn pack(self) -> Result<PathBuf, PackImageError> {
+        do_pack_image(self._build()).await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PackImageError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Walkdir(#[from] walkdir::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Toml(#[from] LocalProjectTomlValidationError),
+    #[error(transparent)]
+    ProjectTree(#[from] ProjectTreeError),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error("failed to pull base image: {0}")]
+    Registry(#[from] RegistryError),
+    #[error("container images can only be created on Linux, but the current platform is {0}")]
+    UnsupportedPlatform(String),
+    #[error("no Lua binary found for the Lua installation")]
+    LuaBinaryNotFound,
+    #[error("package {0} not found in the project tree's lockfile")]
+    MissingDependency(LocalPackageId),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImageManifest {
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub config: Descriptor,
+    pub layers: Vec<Descriptor>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct ImageConfig {
+    pub architecture: String,
+    pub os: String,
+    #[serde(default)]
+    pub config: ContainerConfig,
+    pub rootfs: RootFs,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct ContainerConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entrypoint: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct RootFs {
+    #[serde(rename = "type")]
+    pub fs_type: String,
+    pub diff_ids: Vec<String>,
+}
+
+impl Default for RootFs {
+    fn default() -> Self {
+        Self {
+            fs_type: "layers".into(),
+            diff_ids: Vec::new(),
+        }
+    }
+}
+
+/// A compressed image layer.
+struct Layer {
+    descriptor: Descriptor,
+    /// The digest of the uncompressed layer.
+    diff_id: String,
+    blob: Bytes,
+}
+
+/// Builds a deterministic, gzip-compressed layer tarball.
+struct LayerBuilder {
+    tar: tar::Builder<Vec<u8>>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl LayerBuilder {
+    fn new() -> Self {
+        let mut tar = tar::Builder::new(Vec::new());
+        tar.mode(HeaderMode::Deterministic);
+        Self {
+            tar,
+            dirs: HashSet::new(),
+        }
+    }
+
+    /// Add a file from the host at an absolute path in the image,
+    /// following symlinks, e.g. for projects built with `--dev-link`.
+    fn append_file(&mut self, src: &Path, dest: &Path) -> io::Result<()> {
+        let dest = relative_to_image_root(dest);
+        self.append_parents(&dest)?;
+        self.tar.append_path_with_name(src, dest)
+    }
+
+    /// Recursively add a directory from the host at an absolute path in the image.
+    fn append_dir_all(&mut self, src: &Path, dest: &Path) -> Result<(), PackImageError> {
+        for entry in WalkDir::new(src).follow_links(true) {
+            let entry = entry?;
+            let relative_path = entry
+                .path()
+                .strip_prefix(src)
+                .expect("walkdir entry is not in the root directory");
+            let target = dest.join(relative_path);
+            if entry.file_type().is_dir() {
+                self.append_dir(&relative_to_image_root(&target))?;
+            } else {
+                self.append_file(entry.path(), &target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn append_parents(&mut self, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => self.append_dir(parent),
+            _ => Ok(()),
+        }
+    }
+
+    fn append_dir(&mut self, path: &Path) -> io::Result<()> {
+        if path.as_os_str().is_empty() || self.dirs.contains(path) {
+            return Ok(());
+        }
+        self.append_parents(path)?;
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        header.set_mtime(0);
+        self.tar.append_data(&mut header, path, io::empty())?;
+        self.dirs.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<Layer> {
+        let tar = self.tar.into_inner()?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar)?;
+        let blob = Bytes::from(encoder.finish()?);
+        Ok(Layer {
+            descriptor: Descriptor {
+                media_type: MEDIA_TYPE_LAYER.into(),
+                digest: sha256_digest(&blob),
+                size: blob.len() as u64,
+            },
+            diff_id: sha256_digest(&tar),
+            blob,
+        })
+    }
+}
+
+async fn do_pack_image(args: PackImage<'_>) -> Result<PathBuf, PackImageError> {
+    if std::env::consts::OS != "linux" {
+        return Err(PackImageError::UnsupportedPlatform(
+            std::env::consts::OS.into(),
+        ));
+    }
+    let architecture = oci_architecture();
+    let lua_bin = args
+        .lua
+        .bin
+        .as_ref()
+        .ok_or(PackImageError::LuaBinaryNotFound)?;
+    let project = args.project;
+    let package = &args.package;
+    let tree = project.tree(args.config)?;
+    let lockfile = tree.lockfile()?;
+
+    let base_image = match args.base_image {
+        Some(image) => Some(registry::pull(image, "linux", architecture, args.config).await?),
+        None => None,
+    };
+
+    let tree_root = tree.root();
+    let container_tree_root = PathBuf::from(CONTAINER_TREE_ROOT);
+    let container_package_root = |package: &LocalPackage| {
+        let package_root = tree.root_for(package);
+        container_tree_root.join(
+            package_root
+                .strip_prefix(&tree_root)
+                .expect("package is not installed in the tree"),
+        )
+    };
+
+    let mut dependencies = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<LocalPackageId> = package.dependencies().into_iter().cloned().collect();
+    while let Some(id) = queue.pop_front() {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        let dependency = lockfile
+            .get(&id)
+            .ok_or_else(|| PackImageError::MissingDependency(id.clone()))?;
+        queue.extend(dependency.dependencies().into_iter().cloned());
+        dependencies.push(dependency.clone());
+    }
+    dependencies.sort_by_key(|dependency| dependency.id());
+
+    let mut runtime_layer = LayerBuilder::new();
+    runtime_layer.append_file(lua_bin, Path::new(CONTAINER_LUA_BIN))?;
+
+    let mut dependencies_layer = LayerBuilder::new();
+    for dependency in &dependencies {
+        dependencies_layer.append_dir_all(
+            &tree.root_for(dependency),
+            &container_package_root(dependency),
+        )?;
+    }
+
+    let mut project_layer = LayerBuilder::new();
+    project_layer.append_dir_all(&tree.root_for(package), &container_package_root(package))?;
+    let project_root = project.root().to_path_buf();
+    for file in project_files(&project_root) {
+        let relative_path = file
+            .strip_prefix(&project_root)
+            .expect("project file is not in the project root");
+        project_layer.append_file(
+            &file,
+            &Path::new(CONTAINER_PROJECT_ROOT).join(relative_path),
+        )?;
+    }
+
+    let package_roots = std::iter::once(package)
+        .chain(dependencies.iter())
+        .map(container_package_root)
+        .collect_vec();
+    let lua_path = package_roots
+        .iter()
+        .flat_map(|root| {
+            let src = root.join("src");
+            [src.join("?.lua"), src.join("?").join("init.lua")]
+        })
+        .map(|path| path.display().to_string())
+        .chain(std::iter::once(";".into()))
+        .join(";");
+    let lua_cpath = package_roots
+        .iter()
+        .map(|root| root.join("lib").join(format!("?.{}", c_dylib_extension())))
+        .map(|path| path.display().to_string())
+        .chain(std::iter::once(";".into()))
+        .join(";");
+
+    let (mut config, mut layers) = match base_image {
+        Some(image) => (
+            image.config,
+            image
+                .layers
+                .into_iter()
+                .map(|(mut descriptor, blob)| {
+                    if descriptor.media_type == MEDIA_TYPE_DOCKER_LAYER {
+                        descriptor.media_type = MEDIA_TYPE_LAYER.into();
+                    }
+                    (descriptor, blob)
+                })
+                .collect_vec(),
+        ),
+        None => (
+            ImageConfig {
+                architecture: architecture.into(),
+                os: "linux".into(),
+                ..ImageConfig::default()
+            },
+            Vec::new(),
+        ),
+    };
+    for layer in [
+        runtime_layer.finish()?,
+        dependencies_layer.finish()?,
+        project_layer.finish()?,
+    ] {
+        config.rootfs.diff_ids.push(layer.diff_id);
+        layers.push((layer.descriptor, layer.blob));
+    }
+
+    let mut env = config
+        .config
+        .env
+        .take()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|var| !var.starts_with("LUA_PATH=") && !var.starts_with("LUA_CPATH="))
+        .collect_vec();
+    let path = match env.iter().position(|var| var.starts_with("PATH=")) {
+        Some(i) => env.remove(i)["PATH=".len()..].to_string(),
+        None => DEFAULT_PATH.into(),
+    };
+    let lua_bin_dir = Path::new(CONTAINER_LUA_BIN)
+        .parent()
+        .expect("lua binary has no parent directory");
+    env.push(format!("PATH={}:{}", lua_bin_dir.display(), path));
+    env.push(format!("LUA_PATH={lua_path}"));
+    env.push(format!("LUA_CPATH={lua_cpath}"));
+    config.config.env = Some(env);
+    config.config.working_dir = Some(CONTAINER_PROJECT_ROOT.into());
+    config.config.entrypoint = Some(entrypoint(project)?);
+    config.config.cmd = None;
+
+    let image_name = format!("{}:{}", package.name(), package.version());
+    let output_path =
+        args.dest_dir
+            .join(format!("{}-{}.oci.tar", package.name(), package.version()));
+    write_image(&output_path, &image_name, &config, &layers)?;
+    Ok(output_path)
+}
+
+/// The entrypoint, derived from the `[run]` section of the `lux.toml`.
+/// Defaults to the Lua interpreter.
+fn entrypoint(project: &Project) -> Result<Vec<String>, PackImageError> {
+    let toml = project.toml().into_local()?;
+    let run_spec = toml.run().map(|run| run.current_platform());
+    let command = run_spec
+        .and_then(|run_spec| run_spec.command.as_ref())
+        .map(|command| command.to_string())
+        .unwrap_or(CONTAINER_LUA_BIN.into());
+    let args = run_spec
+        .and_then(|run_spec| run_spec.args.as_ref())
+        .map(|args| args.iter().cloned().collect_vec())
+        .unwrap_or_default();
+    Ok(std::iter::once(command).chain(args).collect_vec())
+}
+
+/// Write an OCI image layout to a tarball.
+/// We also add a Docker `manifest.json`, so that the image can be loaded with `docker load`.
+fn write_image(
+    output_path: &Path,
+    image_name: &str,
+    config: &ImageConfig,
+    layers: &[(Descriptor, Bytes)],
+) -> Result<(), PackImageError> {
+    let mut archive = ImageArchive::new(File::create(output_path)?);
+
+    let config_json = serde_json::to_vec(config)?;
+    let config_descriptor = archive.append_blob(MEDIA_TYPE_CONFIG, &config_json)?;
+    for (descriptor, blob) in layers {
+        archive.append_blob(&descriptor.media_type, blob)?;
+    }
+    let manifest = ImageManifest {
+        schema_version: 2,
+        media_type: Some(MEDIA_TYPE_MANIFEST.into()),
+        config: config_descriptor.clone(),
+        layers: layers
+            .iter()
+            .map(|(descriptor, _)| descriptor.clone())
+            .collect_vec(),
+    };
+    let manifest_descriptor =
+        archive.append_blob(MEDIA_TYPE_MANIFEST, &serde_json::to_vec(&manifest)?)?;
+
+    let index = serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": MEDIA_TYPE_INDEX,
+        "manifests": [{
+            "mediaType": manifest_descriptor.media_type,
+            "digest": manifest_descriptor.digest,
+            "size": manifest_descriptor.size,
+            "annotations": {
+                "org.opencontainers.image.ref.name": image_name,
+            },
+        }],
+    });
+    archive.append_file("index.json", &serde_json::to_vec(&index)?)?;
+    archive.append_file(
+        "oci-layout",
+        &serde_json::to_vec(&serde_json::json!({ "imageLayoutVersion": OCI_LAYOUT_VERSION }))?,
+    )?;
+    let docker_manifest = serde_json::json!([{
+        "Config": blob_path(&config_descriptor.digest),
+        "RepoTags": [image_name],
+        "Layers": layers
+            .iter()
+            .map(|(descriptor, _)| blob_path(&descriptor.digest))
+            .collect_vec(),
+    }]);
+    archive.append_file("manifest.json", &serde_json::to_vec(&docker_manifest)?)?;
+    archive.finish()?;
+    Ok(())
+}
+
+struct ImageArchive {
+    tar: tar::Builder<File>,
+    blobs: HashSet<String>,
+}
+
+impl ImageArchive {
+    fn new(file: File) -> Self {
+        let mut tar = tar::Builder::new(file);
+        tar.mode(HeaderMode::Deterministic);
+        Self {
+            tar,
+            blobs: HashSet::new(),
+        }
+    }
+
+    fn append_blob(&mut self, media_type: &str, content: &[u8]) -> io::Result<Descriptor> {
+        let digest = sha256_digest(content);
+        if self.blobs.insert(digest.clone()) {
+            self.append_file(&blob_path(&digest), content)?;
+        }
+        Ok(Descriptor {
+            media_type: media_type.into(),
+            digest,
+            size: content.len() as u64,
+        })
+    }
+
+    fn append_file(&mut self, path: &str, content: &[u8]) -> io::Result<()> {
+        let mut header = Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        self.tar.append_data(&mut header, path, content)
+    }
+
+    fn finish(self) -> io::Result<()> {
+        self.tar.into_inner()?.flush()
+    }
+}
+
+fn blob_path(digest: &str) -> String {
+    format!("blobs/{}", digest.replacen(':', "/", 1))
+}
+
+fn sha256_digest(content: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(content)))
+}
+
+fn relative_to_image_root(path: &Path) -> PathBuf {
+    path.strip_prefix("/").unwrap_or(path).to_path_buf()
+}
+
+/// The host architecture, as named by the OCI image spec.
+fn oci_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "x86" => "386",
+        "aarch64" => "arm64",
+        "powerpc64" => "ppc64le",
+        "loongarch64" => "loong64",
+        arch => arch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::Read};
+
+    use assert_fs::prelude::*;
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn write_loadable_image() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let src = temp.child("src");
+        src.child("foo")
+            .child("init.lua")
+            .write_str("return {}")
+            .unwrap();
+        let mut layer = LayerBuilder::new();
+        layer
+            .append_dir_all(src.path(), Path::new("/opt/lux/tree/foo/src"))
+            .unwrap();
+        let layer = layer.finish().unwrap();
+        let config = ImageConfig {
+            architecture: "amd64".into(),
+            os: "linux".into(),
+            rootfs: RootFs {
+                diff_ids: vec![layer.diff_id.clone()],
+                ..RootFs::default()
+            },
+            ..ImageConfig::default()
+        };
+        let output = temp.child("foo-1.0.0-1.oci.tar");
+        write_image(
+            output.path(),
+            "foo:1.0.0-1",
+            &config,
+            &[(layer.descriptor.clone(), layer.blob.clone())],
+        )
+        .unwrap();
+
+        let mut archive = tar::Archive::new(File::open(output.path()).unwrap());
+        let entries: HashMap<String, Vec<u8>> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().display().to_string();
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content).unwrap();
+                (path, content)
+            })
+            .collect();
+        assert!(entries.contains_key("oci-layout"));
+        for (path, content) in entries
+            .iter()
+            .filter(|(path, _)| path.starts_with("blobs/"))
+        {
+            assert_eq!(&blob_path(&sha256_digest(content)), path);
+        }
+        let docker_manifest: serde_json::Value =
+            serde_json::from_slice(&entries["manifest.json"]).unwrap();
+        assert_eq!(docker_manifest[0]["RepoTags"][0], "foo:1.0.0-1");
+        let layer_path = docker_manifest[0]["Layers"][0].as_str().unwrap();
+        let mut layer_tar = Vec::new();
+        GzDecoder::new(entries[layer_path].as_slice())
+            .read_to_end(&mut layer_tar)
+            .unwrap();
+        assert_eq!(sha256_digest(&layer_tar), layer.diff_id);
+        let layer_paths = tar::Archive::new(layer_tar.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect_vec();
+        assert!(layer_paths.contains(&"opt".to_string()));
+        assert!(layer_paths.contains(&"opt/lux/tree/foo/src/foo/init.lua".to_string()));
+    }
+}
@@ -0,0 +1,300 @@
+//! A minimal, read-only OCI distribution client for pulling base images.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use bytes::Bytes;
+use reqwest::{
+    header::{self, HeaderValue},
+    Response, StatusCode,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{config::Config, http::HttpClient};
+
+use super::{Descriptor, ImageConfig, ImageManifest};
+
+const DOCKER_HUB_REGISTRY: &str = "registry-1.docker.io";
+
+const MANIFEST_MEDIA_TYPES: &[&str] = &[
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("invalid image reference: {0}")]
+    InvalidReference(String),
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("failed to parse {what} for {image}: {err}")]
+    Parse {
+        what: &'static str,
+        image: ImageReference,
+        err: serde_json::Error,
+    },
+    #[error("{image} has no manifest for {os}/{architecture}")]
+    NoMatchingPlatform {
+        image: ImageReference,
+        os: String,
+        architecture: String,
+    },
+    #[error("unexpected authentication challenge from {0}")]
+    Authentication(String),
+}
+
+/// A reference to an image in a registry, e.g. `debian:stable-slim` or `ghcr.io/foo/bar@sha256:...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageReference {
+    pub registry: String,
+    pub repository: String,
+    /// A tag or digest.
+    pub reference: String,
+}
+
+impl FromStr for ImageReference {
+    type Err = RegistryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, reference) = match s.split_once('@') {
+            Some((name, digest)) => (name, digest.to_string()),
+            None => match s.rsplit_once(':') {
+                Some((name, tag)) if !tag.contains('/') => (name, tag.to_string()),
+                _ => (s, "latest".to_string()),
+            },
+        };
+        if name.is_empty() || reference.is_empty() {
+            return Err(RegistryError::InvalidReference(s.to_string()));
+        }
+        let (registry, repository) = match name.split_once('/') {
+            Some((registry, repository))
+                if registry.contains('.') || registry.contains(':') || registry == "localhost" =>
+            {
+                (registry.to_string(), repository.to_string())
+            }
+            Some(_) => (DOCKER_HUB_REGISTRY.to_string(), name.to_string()),
+            None => (DOCKER_HUB_REGISTRY.to_string(), format!("library/{name}")),
+        };
+        Ok(Self {
+            registry,
+            repository,
+            reference,
+        })
+    }
+}
+
+impl Display for ImageReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let separator = if self.reference.starts_with("sha256:") {
+            "@"
+        } else {
+            ":"
+        };
+        write!(
+            f,
+            "{}/{}{}{}",
+            self.registry, self.repository, separator, self.reference
+        )
+    }
+}
+
+/// A base image, with its layers downloaded.
+pub(crate) struct PulledImage {
+    pub config: ImageConfig,
+    /// The compressed layers, with their descriptors.
+    pub layers: Vec<(Descriptor, Bytes)>,
+}
+
+#[derive(Deserialize)]
+struct ImageIndex {
+    manifests: Vec<IndexEntry>,
+}
+
+#[derive(Deserialize)]
+struct IndexEntry {
+    digest: String,
+    platform: Option<Platform>,
+}
+
+#[derive(Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(alias = "access_token")]
+    token: String,
+}
+
+struct RegistryClient<'a> {
+    client: &'a HttpClient,
+    image: ImageReference,
+    token: Option<String>,
+}
+
+pub(crate) async fn pull(
+    image: ImageReference,
+    os: &str,
+    architecture: &str,
+    config: &Config,
+) -> Result<PulledImage, RegistryError> {
+    let mut client = RegistryClient {
+        client: config.http_client()?,
+        image,
+        token: None,
+    };
+    let mut manifest_bytes = client
+        .get(
+            &format!("manifests/{}", client.image.reference),
+            MANIFEST_MEDIA_TYPES,
+        )
+        .await?;
+    if let Ok(index) = serde_json::from_slice::<ImageIndex>(&manifest_bytes) {
+        let entry = index
+            .manifests
+            .into_iter()
+            .find(|entry| {
+                entry
+                    .platform
+                    .as_ref()
+                    .is_some_and(|p| p.os == os && p.architecture == architecture)
+            })
+            .ok_or_else(|| RegistryError::NoMatchingPlatform {
+                image: client.image.clone(),
+                os: os.to_string(),
+                architecture: architecture.to_string(),
+            })?;
+        manifest_bytes = client
+            .get(&format!("manifests/{}", entry.digest), MANIFEST_MEDIA_TYPES)
+            .await?;
+    }
+    let manifest: ImageManifest =
+        serde_json::from_slice(&manifest_bytes).map_err(|err| RegistryError::Parse {
+            what: "manifest",
+            image: client.image.clone(),
+            err,
+        })?;
+    let config_bytes = client
+        .get(&format!("blobs/{}", manifest.config.digest), &[])
+        .await?;
+    let config: ImageConfig =
+        serde_json::from_slice(&config_bytes).map_err(|err| RegistryError::Parse {
+            what: "config",
+            image: client.image.clone(),
+            err,
+        })?;
+    let mut layers = Vec::new();
+    for layer in manifest.layers {
+        let bytes = client.get(&format!("blobs/{}", layer.digest), &[]).await?;
+        layers.push((layer, bytes));
+    }
+    Ok(PulledImage { config, layers })
+}
+
+impl RegistryClient<'_> {
+    async fn get(&mut self, path: &str, accept: &[&str]) -> Result<Bytes, RegistryError> {
+        let url = format!(
+            "https://{}/v2/{}/{}",
+            self.image.registry, self.image.repository, path
+        );
+        let mut response = self.send(&url, accept).await?;
+        if response.status() == StatusCode::UNAUTHORIZED && self.token.is_none() {
+            self.token = Some(self.authenticate(&response).await?);
+            response = self.send(&url, accept).await?;
+        }
+        Ok(response.error_for_status()?.bytes().await?)
+    }
+
+    async fn send(&self, url: &str, accept: &[&str]) -> Result<Response, reqwest::Error> {
+        let mut request = self.client.get(url);
+        if !accept.is_empty() {
+            let accept =
+                HeaderValue::from_str(&accept.join(", ")).expect("media types are valid headers");
+            request = request.header(header::ACCEPT, accept);
+        }
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await
+    }
+
+    /// Request an anonymous token, as described by the `WWW-Authenticate` challenge.
+    async fn authenticate(&self, response: &Response) -> Result<String, RegistryError> {
+        let challenge = response
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| RegistryError::Authentication(self.image.registry.clone()))?;
+        let params = parse_challenge(challenge);
+        let realm = params
+            .get("realm")
+            .ok_or_else(|| RegistryError::Authentication(self.image.registry.clone()))?;
+        let scope = params
+            .get("scope")
+            .cloned()
+            .unwrap_or_else(|| format!("repository:{}:pull", self.image.repository));
+        let mut query = vec![("scope", scope)];
+        if let Some(service) = params.get("service") {
+            query.push(("service", service.clone()));
+        }
+        let token: TokenResponse = self
+            .client
+            .get(realm)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(token.token)
+    }
+}
+
+fn parse_challenge(challenge: &str) -> HashMap<String, String> {
+    challenge
+        .split(',')
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| {
+            (
+                key.trim().to_string(),
+                value.trim().trim_matches('"').to_string(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_image_reference() {
+        let image: ImageReference = "debian:stable-slim".parse().unwrap();
+        assert_eq!(image.registry, DOCKER_HUB_REGISTRY);
+        assert_eq!(image.repository, "library/debian");
+        assert_eq!(image.reference, "stable-slim");
+        let image: ImageReference = "ghcr.io/foo/bar".parse().unwrap();
+        assert_eq!(image.registry, "ghcr.io");
+        assert_eq!(image.repository, "foo/bar");
+        assert_eq!(image.reference, "latest");
+        let image: ImageReference = "localhost:5000/foo@sha256:abc".parse().unwrap();
+        assert_eq!(image.registry, "localhost:5000");
+        assert_eq!(image.repository, "foo");
+        assert_eq!(image.reference, "sha256:abc");
+        assert_eq!(image.to_string(), "localhost:5000/foo@sha256:abc");
+    }
+
+    #[test]
+    fn parse_auth_challenge() {
+        let params = parse_challenge(
+            r#"realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/debian:pull""#,
+        );
+        assert_eq!(params["realm"], "https://auth.docker.io/token");
+        assert_eq!(params["service"], "registry.docker.io");
+    }
+}
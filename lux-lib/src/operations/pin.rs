@@ -5,8 +5,9 @@ use itertools::Itertools;
 use thiserror::Error;
 
 use crate::{
-    lockfile::{LocalPackageId, PinnedState},
-    package::PackageSpec,
+    lockfile::{LocalPackageId, LocalPackageLockType, PinnedState},
+    package::{PackageName, PackageSpec},
+    project::{Project, ProjectError},
     tree::{Tree, TreeError},
 };
 
@@ -32,8 +33,14 @@ pub enum PinError {
     Tree(#[from] TreeError),
     #[error("failed to move old package: {0}")]
     MoveItemsFailure(#[from] fs_extra::error::Error),
-    #[error("cannot change pin state of {rock}, because it is not an entrypoint")]
-    NotAnEntrypoint { rock: PackageSpec },
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error("package {0} not found in the project lockfile")]
+    NotInProjectLockfile(PackageName),
+    #[error(
+        "{0} is a direct dependency of the project. Change its pin state in the lux.toml instead"
+    )]
+    DirectDependency(PackageName),
 }
 
 pub fn set_pinned_state(
@@ -47,12 +54,6 @@ pub fn set_pinned_state(
         .ok_or_else(|| PinError::PackageNotFound(package_id.clone()))?
         .clone();
 
-    if !lockfile.is_entrypoint(&package.id()) {
-        return Err(PinError::NotAnEntrypoint {
-            rock: package.to_package(),
-        });
-    }
-
     if pin == package.pinned() {
         return Err(PinError::PinStateUnchanged {
             pin_state: package.pinned(),
@@ -82,11 +83,63 @@ pub fn set_pinned_state(
     fs_extra::move_items(&items, new_root, &CopyOptions::new())?;
 
     lockfile.map_then_flush(|lockfile| {
-        lockfile.remove(&old_package);
-        lockfile.add_entrypoint(&package);
+        lockfile.replace(&old_package.id(), &package);
 
         Ok::<_, io::Error>(())
     })?;
 
     Ok(())
 }
+
+/// Pin or unpin a transitive dependency of a project in the project lockfile,
+/// so that `lx update` doesn't move it.
+/// If the package is installed in `tree`, its pin state is changed there too.
+///
+/// Direct dependencies are pinned in the lux.toml instead.
+pub fn set_project_lockfile_pinned_state(
+    project: &Project,
+    tree: &Tree,
+    package: &PackageName,
+    deps: &LocalPackageLockType,
+    pin: PinnedState,
+) -> Result<(), PinError> {
+    let project_lockfile = project.lockfile()?;
+    let packages = project_lockfile
+        .rocks(deps)
+        .values()
+        .filter(|pkg| pkg.name() == package)
+        .cloned()
+        .collect_vec();
+    if packages.is_empty() {
+        return Err(PinError::NotInProjectLockfile(package.clone()));
+    }
+    if packages
+        .iter()
+        .any(|pkg| project_lockfile.is_entrypoint(&pkg.id(), deps))
+    {
+        return Err(PinError::DirectDependency(package.clone()));
+    }
+    let to_change = packages
+        .iter()
+        .filter(|pkg| pkg.pinned() != pin)
+        .collect_vec();
+    if to_change.is_empty() {
+        return Err(PinError::PinStateUnchanged {
+            pin_state: pin,
+            rock: packages[0].to_package(),
+        });
+    }
+
+    let installed = tree.lockfile()?;
+    let mut project_lockfile = project_lockfile.write_guard();
+    for old_package in to_change {
+        if installed.get(&old_package.id()).is_some() {
+            set_pinned_state(&old_package.id(), tree, pin)?;
+        }
+        let mut package = old_package.clone();
+        package.spec.pinned = pin;
+        project_lockfile.replace(&old_package.id(), &package, deps);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,303 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bon::Builder;
+use itertools::Itertools;
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::{
+    config::Config,
+    lockfile::{LocalPackage, LocalPackageId},
+    package::PackageReq,
+    progress::{MultiProgress, Progress, ProgressBar},
+    tree::{Tree, TreeError, TREE_LOCK_FILE_NAME},
+};
+
+use super::{remove::remove, RemoveError};
+
+/// One of the trees that [`Purge`] can remove packages from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PurgeScope {
+    /// The install tree itself.
+    Tree,
+    /// The tree that test dependencies are installed in.
+    TestTree,
+    /// The tree that build dependencies are installed in.
+    BuildTree,
+}
+
+impl Display for PurgeScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tree => "tree",
+            Self::TestTree => "test tree",
+            Self::BuildTree => "build tree",
+        }
+        .fmt(f)
+    }
+}
+
+/// The packages that were (or, in a dry run, would be) purged from a tree.
+#[derive(Debug, Clone)]
+pub struct PurgedTree {
+    pub scope: PurgeScope,
+    pub root: PathBuf,
+    pub packages: Vec<LocalPackage>,
+    /// The disk space reclaimed, in bytes.
+    pub reclaimed: u64,
+}
+
+/// Removes all packages from an install tree and its test and build trees.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct Purge<'a> {
+    #[builder(start_fn)]
+    tree: &'a Tree,
+    #[builder(start_fn)]
+    config: &'a Config,
+    /// The trees to purge. If empty, all of them are purged.
+    #[builder(default)]
+    scopes: Vec<PurgeScope>,
+    /// Packages to keep, along with their dependencies.
+    #[builder(default)]
+    except: Vec<PackageReq>,
+    /// Only report what would be purged, without removing anything.
+    #[builder(default)]
+    dry_run: bool,
+    progress: Option<Arc<Progress<MultiProgress>>>,
+}
+
+impl<State> PurgeBuilder<'_, State>
+where
+    State: purge_builder::IsComplete,
+{
+    /// Purge the trees, returning what was removed from each of them.
+    pub async fn purge(self) -> Result<Vec<PurgedTree>, PurgeError> {
+        do_purge(self._build()).await
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PurgeError {
+    #[error("IO operation failed: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    Remove(#[from] RemoveError),
+}
+
+async fn do_purge(purge: Purge<'_>) -> Result<Vec<PurgedTree>, PurgeError> {
+    let progress = purge.progress.unwrap_or_else(MultiProgress::new_arc);
+    let scopes = if purge.scopes.is_empty() {
+        vec![
+            PurgeScope::Tree,
+            PurgeScope::TestTree,
+            PurgeScope::BuildTree,
+        ]
+    } else {
+        purge.scopes.into_iter().unique().collect_vec()
+    };
+    // The test and build trees are purged first, as they may be nested in the tree's root.
+    let mut result = Vec::new();
+    for scope in scopes.iter().rev() {
+        let tree = match scope {
+            PurgeScope::Tree => purge.tree.clone(),
+            PurgeScope::TestTree => purge.tree.test_tree(purge.config)?,
+            PurgeScope::BuildTree => purge.tree.build_tree(purge.config)?,
+        };
+        // Nested trees that aren't being purged are left in place.
+        let keep_dirs = match scope {
+            PurgeScope::Tree => purge
+                .tree
+                .nested_tree_dirs()
+                .into_iter()
+                .map(Path::to_path_buf)
+                .collect_vec(),
+            PurgeScope::TestTree | PurgeScope::BuildTree => Vec::new(),
+        };
+        let bar = progress.map(|p| {
+            p.add(ProgressBar::from(format!(
+                "🗑️ Purging the {scope} {}",
+                tree.root().display()
+            )))
+        });
+        let _lock = tree.lock(purge.config, &bar).await?;
+        let purged = purge_tree(
+            *scope,
+            tree.clone(),
+            &purge.except,
+            &keep_dirs,
+            purge.dry_run,
+            &progress,
+        )
+        .await?;
+        bar.map(|b| b.finish_and_clear());
+        result.push(purged);
+    }
+    result.reverse();
+    Ok(result)
+}
+
+async fn purge_tree(
+    scope: PurgeScope,
+    tree: Tree,
+    except: &[PackageReq],
+    keep_dirs: &[PathBuf],
+    dry_run: bool,
+    progress: &Progress<MultiProgress>,
+) -> Result<PurgedTree, PurgeError> {
+    let lockfile = tree.lockfile()?;
+    let kept = kept_packages(lockfile.rocks(), except);
+    let packages = lockfile
+        .rocks()
+        .values()
+        .filter(|package| !kept.contains(&package.id()))
+        .sorted_by(|a, b| a.name().cmp(b.name()))
+        .cloned()
+        .collect_vec();
+    let root = tree.root();
+    let reclaimed = if except.is_empty() {
+        let entries = purged_entries(&root, keep_dirs)?;
+        let reclaimed = entries.iter().map(|entry| dir_size(entry)).sum();
+        if !dry_run {
+            for entry in entries {
+                if entry.is_dir() {
+                    tokio::fs::remove_dir_all(entry).await?;
+                } else {
+                    tokio::fs::remove_file(entry).await?;
+                }
+            }
+        }
+        reclaimed
+    } else if dry_run {
+        packages
+            .iter()
+            .map(|package| dir_size(&tree.root_for(package)))
+            .sum()
+    } else {
+        let size = dir_size(&root);
+        remove(
+            packages.iter().map(LocalPackage::id).collect(),
+            tree,
+            progress,
+        )
+        .await?;
+        size.saturating_sub(dir_size(&root))
+    };
+    Ok(PurgedTree {
+        scope,
+        root,
+        packages,
+        reclaimed,
+    })
+}
+
+/// The packages that match one of the `except` requirements, and their dependencies.
+fn kept_packages(
+    packages: &BTreeMap<LocalPackageId, LocalPackage>,
+    except: &[PackageReq],
+) -> HashSet<LocalPackageId> {
+    let mut kept = HashSet::new();
+    let mut queue = packages
+        .values()
+        .filter(|package| except.iter().any(|req| req.matches(&package.to_package())))
+        .map(LocalPackage::id)
+        .collect_vec();
+    while let Some(id) = queue.pop() {
+        if let Some(package) = packages.get(&id).filter(|_| !kept.contains(&id)) {
+            queue.extend(package.dependencies().into_iter().cloned());
+        }
+        kept.insert(id);
+    }
+    kept
+}
+
+/// The entries of the tree's `root` to remove when purging all of its packages.
+/// The lock file and directories that contain any of the `keep_dirs` are kept.
+fn purged_entries(root: &Path, keep_dirs: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    if !root.is_dir() {
+        return Ok(Vec::new());
+    }
+    std::fs::read_dir(root)?
+        .map_ok(|entry| entry.path())
+        .filter_ok(|path| {
+            path.file_name()
+                .is_none_or(|file_name| file_name != TREE_LOCK_FILE_NAME)
+                && !keep_dirs.iter().any(|dir| dir.starts_with(path))
+        })
+        .try_collect()
+}
+
+/// The total size of the files in `path`, in bytes.
+fn dir_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigBuilder, LuaVersion};
+    use assert_fs::prelude::PathCopy;
+
+    #[tokio::test]
+    async fn purge_except_and_scopes() {
+        let tree_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/sample-tree");
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.copy_from(&tree_path, &["**"]).unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        let test_tree = tree.test_tree(&config).unwrap();
+        std::fs::write(test_tree.root().join("marker"), "test").unwrap();
+        let installed = tree.lockfile().unwrap().rocks().len();
+        assert!(installed > 1);
+
+        let dry_run = Purge::new(&tree, &config)
+            .scopes(vec![PurgeScope::Tree])
+            .except(vec!["neorg".parse().unwrap()])
+            .dry_run(true)
+            .purge()
+            .await
+            .unwrap();
+        assert_eq!(dry_run.len(), 1);
+        assert_eq!(dry_run[0].scope, PurgeScope::Tree);
+        let purged = &dry_run[0].packages;
+        // neorg and its five dependencies are kept
+        assert_eq!(purged.len(), installed - 6);
+        assert!(purged
+            .iter()
+            .all(|package| package.name().to_string() != "neorg"));
+        assert_eq!(tree.lockfile().unwrap().rocks().len(), installed);
+
+        let purged = Purge::new(&tree, &config)
+            .scopes(vec![PurgeScope::Tree])
+            .purge()
+            .await
+            .unwrap();
+        assert_eq!(purged[0].packages.len(), installed);
+        assert!(purged[0].reclaimed > 0);
+        assert!(test_tree.root().join("marker").is_file());
+        assert!(tree.lockfile().unwrap().rocks().is_empty());
+
+        Purge::new(&tree, &config).purge().await.unwrap();
+        assert!(!test_tree.root().join("marker").exists());
+    }
+}
@@ -1,10 +1,12 @@
+use std::collections::HashSet;
 use std::io;
+use std::path::Path;
 use std::sync::Arc;
 
 use crate::config::{LuaVersion, LuaVersionUnset};
 use crate::lockfile::{LocalPackage, LocalPackageId};
 use crate::progress::{MultiProgress, Progress, ProgressBar};
-use crate::tree::TreeError;
+use crate::tree::{InstalledFiles, TreeError, TreeRecovery};
 use crate::{config::Config, tree::Tree};
 use futures::future::join_all;
 use itertools::Itertools;
@@ -71,12 +73,86 @@ impl<'a> Remove<'a> {
         let tree = self
             .config
             .user_tree(LuaVersion::from(self.config)?.clone())?;
+        let lock_bar = progress.map(|p| p.new_bar());
+        let _lock = tree.lock(self.config, &lock_bar).await?;
+        lock_bar.map(|b| b.finish_and_clear());
         remove(self.packages, tree, &Arc::clone(&progress)).await
     }
 }
 
+/// Recover from packages whose install directories are missing from the `tree`,
+/// e.g. because they were deleted manually, reporting each of them.
+/// The stale entries are removed from the lockfile.
+/// If the config's `tree_recovery` is `reinstall`, the packages that depend on a missing
+/// package are removed too, and the entrypoints among the removed packages are returned,
+/// so that the caller can reinstall them along with their dependencies.
+/// The caller must hold the tree's lock.
+pub(crate) async fn recover_missing_packages(
+    tree: &Tree,
+    config: &Config,
+    progress: &Progress<MultiProgress>,
+) -> Result<Vec<LocalPackage>, RemoveError> {
+    let missing = tree.missing_packages()?;
+    if missing.is_empty() {
+        return Ok(Vec::new());
+    }
+    let lockfile = tree.lockfile()?;
+    let mut removed: HashSet<LocalPackageId> = missing.iter().map(LocalPackage::id).collect();
+    if config.tree_recovery() == TreeRecovery::Reinstall {
+        while let Some(dependent) = lockfile.rocks().values().find(|package| {
+            !removed.contains(&package.id())
+                && package
+                    .dependencies()
+                    .into_iter()
+                    .any(|dep| removed.contains(dep))
+        }) {
+            removed.insert(dependent.id());
+        }
+    }
+    let bar = progress.map(|p| p.new_bar());
+    let removed = lockfile
+        .rocks()
+        .values()
+        .filter(|package| removed.contains(&package.id()))
+        .sorted_by(|a, b| a.name().cmp(b.name()))
+        .cloned()
+        .collect_vec();
+    for package in &removed {
+        let reason = if missing.contains(package) {
+            format!("is missing from {}", tree.root().display())
+        } else {
+            "depends on a missing package".into()
+        };
+        let action = match config.tree_recovery() {
+            TreeRecovery::Reinstall => "reinstalling it",
+            TreeRecovery::Prune => "pruning it from the lockfile",
+        };
+        bar.map(|b| {
+            b.println(format!(
+                "🩹 {}@{} {reason}, {action}",
+                package.name(),
+                package.version(),
+            ))
+        });
+    }
+    bar.map(|b| b.finish_and_clear());
+    remove(
+        removed.iter().map(LocalPackage::id).collect(),
+        tree.clone(),
+        progress,
+    )
+    .await?;
+    Ok(match config.tree_recovery() {
+        TreeRecovery::Reinstall => removed
+            .into_iter()
+            .filter(|package| lockfile.is_entrypoint(&package.id()))
+            .collect(),
+        TreeRecovery::Prune => Vec::new(),
+    })
+}
+
 // TODO: Remove dependencies recursively too!
-async fn remove(
+pub(crate) async fn remove(
     package_ids: Vec<LocalPackageId>,
     tree: Tree,
     progress: &Progress<MultiProgress>,
@@ -122,9 +198,52 @@ async fn remove_package(
     });
 
     let rock_layout = tree.installed_rock_layout(&package)?;
-    tokio::fs::remove_dir_all(&rock_layout.etc).await?;
-    tokio::fs::remove_dir_all(&rock_layout.rock_path).await?;
+    match InstalledFiles::load(&rock_layout)? {
+        Some(installed_files) => remove_installed_files(&installed_files, &tree).await?,
+        None => remove_binaries(&package, &tree).await?,
+    }
+    if rock_layout.etc.is_dir() {
+        tokio::fs::remove_dir_all(&rock_layout.etc).await?;
+    }
+    if rock_layout.rock_path.is_dir() {
+        tokio::fs::remove_dir_all(&rock_layout.rock_path).await?;
+    }
+    tree.remove_stubs(&package.id())?;
 
+    bar.map(|p| p.finish_and_clear());
+    Ok(())
+}
+
+/// Remove the files recorded when the package was installed.
+/// Files in shared directories that have been modified since,
+/// e.g. because another package has overwritten them, are left in place.
+async fn remove_installed_files(
+    installed_files: &InstalledFiles,
+    tree: &Tree,
+) -> Result<(), RemoveError> {
+    let root = tree.root();
+    let modified_files = installed_files.modified_files(tree);
+    for (file, _) in installed_files.files() {
+        let path = root.join(file);
+        if path.is_file() && !modified_files.contains(file) {
+            tokio::fs::remove_file(&path).await?;
+            remove_empty_parents(&path, &root).await;
+        }
+    }
+    Ok(())
+}
+
+/// Remove empty parent directories, e.g. from an `etc_root`, up to the tree root.
+async fn remove_empty_parents(path: &Path, root: &Path) {
+    for dir in path.ancestors().skip(1) {
+        if dir == root || !dir.starts_with(root) || tokio::fs::remove_dir(dir).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Fallback for packages that were installed without recording their files.
+async fn remove_binaries(package: &LocalPackage, tree: &Tree) -> Result<(), RemoveError> {
     // Delete the corresponding binaries attached to the current package (located under `{LUX_TREE}/bin/`)
     for relative_binary_path in package.spec.binaries() {
         let binary_file_name = relative_binary_path
@@ -141,7 +260,61 @@ async fn remove_package(
             tokio::fs::remove_file(unwrapped_binary_path).await?;
         }
     }
-
-    bar.map(|p| p.finish_and_clear());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigBuilder;
+    use assert_fs::prelude::PathCopy;
+    use std::path::PathBuf;
+
+    async fn recover_sample_tree(
+        tree_recovery: TreeRecovery,
+    ) -> (assert_fs::TempDir, Tree, Vec<LocalPackage>) {
+        let tree_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/sample-tree");
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.copy_from(&tree_path, &["**"]).unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .tree_recovery(Some(tree_recovery))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        // Only neorg is installed in the sample tree, its dependencies are missing.
+        assert_eq!(tree.missing_packages().unwrap().len(), 10);
+        let recovered = recover_missing_packages(&tree, &config, &MultiProgress::new_arc())
+            .await
+            .unwrap();
+        assert!(tree.missing_packages().unwrap().is_empty());
+        (temp, tree, recovered)
+    }
+
+    #[tokio::test]
+    async fn recover_missing_packages_prune() {
+        let (_temp, tree, recovered) = recover_sample_tree(TreeRecovery::Prune).await;
+        assert!(recovered.is_empty());
+        let lockfile = tree.lockfile().unwrap();
+        let remaining = lockfile.rocks().values().collect_vec();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].name().to_string(), "neorg");
+    }
+
+    #[tokio::test]
+    async fn recover_missing_packages_reinstall() {
+        let (_temp, tree, recovered) = recover_sample_tree(TreeRecovery::Reinstall).await;
+        // neorg is removed too, as it depends on missing packages.
+        assert!(tree.lockfile().unwrap().rocks().is_empty());
+        assert_eq!(
+            recovered
+                .iter()
+                .map(|package| package.name().to_string())
+                .collect_vec(),
+            vec!["lua-cjson", "neorg", "say"]
+        );
+    }
+}
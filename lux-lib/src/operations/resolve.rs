@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use async_recursion::async_recursion;
 use futures::future::join_all;
@@ -6,18 +6,24 @@ use itertools::Itertools;
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
-    build::BuildBehaviour,
+    build::{debug_symbols::DebugSymbols, BuildBehaviour},
     config::Config,
     lockfile::{
         LocalPackageId, LocalPackageSpec, Lockfile, LockfilePermissions, OptState, PinnedState,
+        ReadOnly,
     },
+    observer::OperationObserver,
+    package::PackageSpec,
     progress::{MultiProgress, Progress},
     remote_package_db::RemotePackageDB,
     rockspec::Rockspec,
     tree,
 };
 
-use super::{Download, PackageInstallSpec, RemoteRockDownload, SearchAndDownloadError};
+use super::{
+    run_cancellable, CancellationToken, Download, PackageInstallSpec, RemoteRockDownload,
+    SearchAndDownloadError,
+};
 
 #[derive(Clone, Debug)]
 pub(crate) struct PackageInstallData {
@@ -27,6 +33,9 @@ pub(crate) struct PackageInstallData {
     pub downloaded_rock: RemoteRockDownload,
     pub spec: LocalPackageSpec,
     pub entry_type: tree::EntryType,
+    pub variables: HashMap<String, String>,
+    pub debug_symbols: Option<DebugSymbols>,
+    pub build_env: Option<Vec<String>>,
 }
 
 #[async_recursion]
@@ -38,8 +47,11 @@ pub(crate) async fn get_all_dependencies<P>(
     package_db: Arc<RemotePackageDB>,
     lockfile: Arc<Lockfile<P>>,
     build_lockfile: Arc<Lockfile<P>>,
+    system_lockfile: Option<Arc<Lockfile<ReadOnly>>>,
+    observer: Option<Arc<dyn OperationObserver>>,
     config: &Config,
     progress: Arc<Progress<MultiProgress>>,
+    cancellation: CancellationToken,
 ) -> Result<Vec<LocalPackageId>, SearchAndDownloadError>
 where
     P: LockfilePermissions + Send + Sync + 'static,
@@ -47,7 +59,8 @@ where
     join_all(
         packages
             .into_iter()
-            // Exclude packages that are already installed
+            // Exclude packages that are already installed,
+            // either in the tree or in the read-only system tree
             .filter(
                 |PackageInstallSpec {
                      package,
@@ -55,7 +68,10 @@ where
                      ..
                  }| {
                     *build_behaviour == BuildBehaviour::Force
-                        || lockfile.has_rock(package, None).is_none()
+                        || (lockfile.has_rock(package, None).is_none()
+                            && system_lockfile.as_ref().is_none_or(|system_lockfile| {
+                                system_lockfile.has_rock(package, None).is_none()
+                            }))
                 },
             )
             .map(
@@ -68,6 +84,10 @@ where
                      entry_type,
                      constraint,
                      source,
+                     variables,
+                     debug_symbols,
+                     build_env,
+                     rockspec_download,
                  }| {
                     let config = config.clone();
                     let dependencies_tx = dependencies_tx.clone();
@@ -77,11 +97,18 @@ where
                     let build_dep_progress = Arc::clone(&progress);
                     let lockfile = Arc::clone(&lockfile);
                     let build_lockfile = Arc::clone(&build_lockfile);
+                    let system_lockfile = system_lockfile.clone();
+                    let observer = observer.clone();
+                    let cancellation = cancellation.clone();
 
-                    tokio::spawn(async move {
+                    // Spawned tasks are not dropped with the install,
+                    // so they must observe the cancellation themselves.
+                    tokio::spawn(run_cancellable(Some(cancellation.clone()), async move {
                         let bar = progress.map(|p| p.new_bar());
 
-                        let downloaded_rock = if let Some(source) = source {
+                        let downloaded_rock = if let Some(rockspec_download) = rockspec_download {
+                            RemoteRockDownload::RockspecOnly { rockspec_download }
+                        } else if let Some(source) = source {
                             RemoteRockDownload::from_package_req_and_source_spec(
                                 package.clone(),
                                 source,
@@ -95,6 +122,11 @@ where
 
                         let constraint = constraint.unwrap_or(package.version_req().clone().into());
 
+                        observer.package_resolved(&PackageSpec::new(
+                            downloaded_rock.rockspec().package().clone(),
+                            downloaded_rock.rockspec().version().clone(),
+                        ));
+
                         let rockspec = downloaded_rock.rockspec();
 
                         // NOTE: We don't need to install build dependencies to install binary rocks.
@@ -125,8 +157,11 @@ where
                                 package_db.clone(),
                                 build_lockfile.clone(),
                                 build_lockfile.clone(),
+                                None,
+                                observer.clone(),
                                 &config,
                                 build_dep_progress,
+                                cancellation.clone(),
                             )
                             .await?;
                         }
@@ -165,8 +200,11 @@ where
                             package_db,
                             lockfile,
                             build_lockfile,
+                            system_lockfile,
+                            observer.clone(),
                             &config,
                             progress,
+                            cancellation,
                         )
                         .await?;
 
@@ -188,12 +226,15 @@ where
                             spec: local_spec.clone(),
                             downloaded_rock,
                             entry_type,
+                            variables,
+                            debug_symbols,
+                            build_env,
                         };
 
                         dependencies_tx.send(install_spec).unwrap();
 
                         Ok::<_, SearchAndDownloadError>(local_spec.id())
-                    })
+                    }))
                 },
             ),
     )
@@ -1,19 +1,23 @@
-use std::ops::Deref;
+use std::{borrow::Cow, collections::HashMap, ops::Deref, path::PathBuf};
 
 use bon::Builder;
 use itertools::Itertools;
 use nonempty::NonEmpty;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::Deserialize;
 use thiserror::Error;
 use tokio::process::Command;
 
 use crate::{
-    config::Config,
-    lua_installation::LuaBinary,
+    config::{Config, LuaVersion},
+    lua_installation::openresty::OpenResty,
     lua_rockspec::LuaVersionError,
     operations::run_lua::RunLua,
     path::{Paths, PathsError},
+    process::{self, ProcessLimits},
     project::{project_toml::LocalProjectTomlValidationError, Project, ProjectTreeError},
+    tree::RockLayout,
+    variables::{self, Environment, HasVariables, VariableSubstitutionError},
 };
 
 use super::RunLuaError;
@@ -51,6 +55,19 @@ impl<'de> Deserialize<'de> for RunCommand {
     }
 }
 
+impl JsonSchema for RunCommand {
+    fn schema_name() -> Cow<'static, str> {
+        "RunCommand".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "The command to run the project with, e.g. `nlua`",
+        })
+    }
+}
+
 impl Deref for RunCommand {
     type Target = String;
 
@@ -69,8 +86,23 @@ pub enum RunError {
     ProjectTree(#[from] ProjectTreeError),
     Io(#[from] std::io::Error),
     Paths(#[from] PathsError),
+    VariableSubstitution(#[from] VariableSubstitutionError),
     #[error("No `run` field found in `lux.toml`")]
     NoRunField,
+    #[error("could not find an OpenResty installation.\nSet the `OPENRESTY_PREFIX` variable in your config to the OpenResty prefix, or make sure `resty` is on the PATH.")]
+    OpenRestyNotFound,
+    #[error("running with `resty` requires a project that targets LuaJIT, but it targets {0}")]
+    RestyLuaVersion(LuaVersion),
+}
+
+impl RunError {
+    /// The exit code of the `run` command, if it ran and exited with a non-zero exit code.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Self::RunLua(err) => err.exit_code(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Builder)]
@@ -80,6 +112,9 @@ pub struct Run<'a> {
     args: &'a [String],
     config: &'a Config,
     disable_loader: Option<bool>,
+    /// Run the `[run]` args with OpenResty's `resty`, instead of the `command` or Lua.
+    #[builder(default)]
+    resty: bool,
 }
 
 impl<State> RunBuilder<'_, State>
@@ -99,39 +134,104 @@ where
             .current_platform()
             .clone();
 
-        let mut args = run_spec.args.unwrap_or_default();
+        let layout = project.layout(config)?;
+        let lua_version = project.lua_version(config).ok();
+        let mut args = run_spec.args.unwrap_or_default().try_map(|arg| {
+            substitute_layout_variables(layout.as_ref(), lua_version.as_ref(), &arg)
+        })?;
 
         if !extra_args.is_empty() {
             args.extend(extra_args.iter().cloned());
         }
+        let cwd = match &run_spec.cwd {
+            Some(cwd) => project.root().join(substitute_layout_variables(
+                layout.as_ref(),
+                lua_version.as_ref(),
+                &cwd.to_string_lossy(),
+            )?),
+            None => project.root().to_path_buf(),
+        };
+        let env = substitute_layout_variables_in_env(
+            layout.as_ref(),
+            lua_version.as_ref(),
+            run_spec.env,
+        )?;
+        let spec = ResolvedRunSpec {
+            args,
+            cwd,
+            env,
+            layout,
+        };
         let disable_loader = run.disable_loader.unwrap_or(false);
+        if run.resty {
+            return run_with_resty(project, spec, config).await;
+        }
         match &run_spec.command {
-            Some(command) => {
-                run_with_command(project, command, disable_loader, &args, config).await
-            }
-            None => run_with_local_lua(project, disable_loader, &args, config).await,
+            Some(command) => run_with_command(project, command, disable_loader, spec, config).await,
+            None => run_with_local_lua(project, disable_loader, spec, config).await,
         }
     }
 }
 
+/// A `[run]` spec, with the layout variables substituted.
+struct ResolvedRunSpec {
+    args: NonEmpty<String>,
+    cwd: PathBuf,
+    env: HashMap<String, String>,
+    layout: Option<RockLayout>,
+}
+
+/// Substitutes the `$(PREFIX)`, `$(LUADIR)`, ... variables of the project's layout
+/// and `$(LUA_VERSION)`, falling back to environment variables.
+pub(crate) fn substitute_layout_variables(
+    layout: Option<&RockLayout>,
+    lua_version: Option<&LuaVersion>,
+    input: &str,
+) -> Result<String, VariableSubstitutionError> {
+    let variables: Vec<&dyn HasVariables> = layout
+        .map(|layout| layout as &dyn HasVariables)
+        .into_iter()
+        .chain(lua_version.map(|lua_version| lua_version as &dyn HasVariables))
+        .chain(std::iter::once(&Environment {} as &dyn HasVariables))
+        .collect();
+    variables::substitute(&variables, input)
+}
+
+pub(crate) fn substitute_layout_variables_in_env(
+    layout: Option<&RockLayout>,
+    lua_version: Option<&LuaVersion>,
+    env: HashMap<String, String>,
+) -> Result<HashMap<String, String>, VariableSubstitutionError> {
+    env.into_iter()
+        .map(|(name, value)| {
+            Ok((
+                name,
+                substitute_layout_variables(layout, lua_version, &value)?,
+            ))
+        })
+        .try_collect()
+}
+
 async fn run_with_local_lua(
     project: &Project,
     disable_loader: bool,
-    args: &NonEmpty<String>,
+    spec: ResolvedRunSpec,
     config: &Config,
 ) -> Result<(), RunError> {
-    let version = project.lua_version(config)?;
+    let lua_cmd = project.lua_binary(config)?;
 
     let tree = project.tree(config)?;
-    let args = &args.into_iter().cloned().collect();
+    let args = &spec.args.into_iter().collect();
 
     RunLua::new()
-        .root(project.root())
+        .root(&spec.cwd)
         .tree(&tree)
         .config(config)
-        .lua_cmd(LuaBinary::new(version, config))
+        .lua_cmd(lua_cmd)
         .disable_loader(disable_loader)
         .args(args)
+        .maybe_layout(spec.layout.as_ref())
+        .env(spec.env)
         .run_lua()
         .await?;
 
@@ -142,7 +242,7 @@ async fn run_with_command(
     project: &Project,
     command: &RunCommand,
     disable_loader: bool,
-    args: &NonEmpty<String>,
+    spec: ResolvedRunSpec,
     config: &Config,
 ) -> Result<(), RunError> {
     let tree = project.tree(config)?;
@@ -162,17 +262,49 @@ async fn run_with_command(
         Some(paths.init())
     };
 
-    match Command::new(command.deref())
-        .args(args.into_iter().cloned().collect_vec())
-        .current_dir(project.root().deref())
+    spawn_run_command(command, &paths, lua_init, spec).await
+}
+
+async fn run_with_resty(
+    project: &Project,
+    spec: ResolvedRunSpec,
+    config: &Config,
+) -> Result<(), RunError> {
+    let lua_version = project.lua_version(config)?;
+    if !matches!(lua_version, LuaVersion::LuaJIT) {
+        return Err(RunError::RestyLuaVersion(lua_version));
+    }
+    let openresty = OpenResty::detect(config).ok_or(RunError::OpenRestyNotFound)?;
+    let tree = project.tree(config)?;
+    let mut paths = Paths::new(&tree)?;
+    // Setting `LUA_PATH` overrides OpenResty's default `package.path`,
+    // so we have to add its Lua libraries ourselves.
+    for dir in openresty.lualib_dirs() {
+        paths.append_lualib_dir(&dir);
+    }
+    // nginx does not evaluate `LUA_INIT`, so we can't use the `lux.loader`.
+    spawn_run_command(&openresty.resty().to_string_lossy(), &paths, None, spec).await
+}
+
+async fn spawn_run_command(
+    command: &str,
+    paths: &Paths,
+    lua_init: Option<String>,
+    spec: ResolvedRunSpec,
+) -> Result<(), RunError> {
+    let mut cmd = Command::new(command);
+    cmd.args(spec.args.into_iter().collect_vec())
+        .current_dir(&spec.cwd)
         .env("PATH", paths.path_prepended().joined())
         .env("LUA_INIT", lua_init.unwrap_or_default())
         .env("LUA_PATH", paths.package_path().joined())
-        .env("LUA_CPATH", paths.package_cpath().joined())
-        .status()
-        .await?
-        .code()
-    {
+        .env("LUA_CPATH", paths.package_cpath().joined());
+    if let Some(layout) = &spec.layout {
+        cmd.envs(layout.env_vars());
+    }
+    cmd.envs(spec.env);
+    let status = process::status(&mut cmd, &ProcessLimits::default(), command).await?;
+    match process::exit_code(&status) {
         Some(0) => Ok(()),
         code => Err(RunLuaError::LuaCommandNonZeroExitCode {
             lua_cmd: command.to_string(),
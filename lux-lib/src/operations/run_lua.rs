@@ -7,6 +7,7 @@ use bon::Builder;
 use crate::config::Config;
 
 use std::{
+    collections::HashMap,
     io,
     path::{Path, PathBuf},
 };
@@ -17,8 +18,8 @@ use tokio::process::Command;
 use crate::{
     lua_installation::{LuaBinary, LuaBinaryError},
     path::{Paths, PathsError},
-    tree::Tree,
-    tree::TreeError,
+    process::{self, ProcessLimits},
+    tree::{RockLayout, Tree, TreeError},
 };
 
 #[derive(Error, Debug)]
@@ -43,6 +44,16 @@ pub enum RunLuaError {
     Tree(#[from] TreeError),
 }
 
+impl RunLuaError {
+    /// The exit code of the Lua command, if it ran and exited with a non-zero exit code.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Self::LuaCommandNonZeroExitCode { exit_code, .. } => *exit_code,
+            _ => None,
+        }
+    }
+}
+
 #[derive(Builder)]
 #[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
 pub struct RunLua<'a> {
@@ -56,6 +67,12 @@ pub struct RunLua<'a> {
     disable_loader: Option<bool>,
     lua_init: Option<String>,
     welcome_message: Option<String>,
+    /// Set the `LUX_PREFIX`, `LUX_ETC`, ... environment variables
+    /// to point to this layout's directories.
+    layout: Option<&'a RockLayout>,
+    /// Additional environment variables to set.
+    #[builder(default)]
+    env: HashMap<String, String>,
 }
 
 impl<State> RunLuaBuilder<'_, State>
@@ -107,15 +124,24 @@ To suppress this warning, set the `--no-loader` option.
             loader_init
         );
 
-        let status = match Command::new(&lua_cmd)
+        let mut command = Command::new(&lua_cmd);
+        command
             .current_dir(args.root)
             .args(args.args)
             .env("PATH", paths.path_prepended().joined())
             .env("LUA_PATH", paths.package_path().joined())
             .env("LUA_CPATH", paths.package_cpath().joined())
-            .env("LUA_INIT", lua_init)
-            .status()
-            .await
+            .env("LUA_INIT", lua_init);
+        if let Some(layout) = args.layout {
+            command.envs(layout.env_vars());
+        }
+        command.envs(args.env);
+        let status = match process::status(
+            &mut command,
+            &ProcessLimits::default(),
+            &lua_cmd.to_string_lossy(),
+        )
+        .await
         {
             Ok(status) => Ok(status),
             Err(err) => Err(RunLuaError::LuaCommandFailed {
@@ -128,7 +154,7 @@ To suppress this warning, set the `--no-loader` option.
         } else {
             Err(RunLuaError::LuaCommandNonZeroExitCode {
                 lua_cmd: lua_cmd.to_string_lossy().to_string(),
-                exit_code: status.code(),
+                exit_code: process::exit_code(&status),
             })
         }
     }
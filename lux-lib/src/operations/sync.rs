@@ -1,24 +1,35 @@
 use std::{io, sync::Arc};
 
 use crate::{
-    build::BuildBehaviour,
+    build::{compile_commands::CompileCommands, BuildBehaviour},
     config::Config,
-    lockfile::{LocalPackage, LocalPackageLockType, LockfileIntegrityError},
+    lockfile::{
+        signature::{verify_lockfile_signature, LockfileSignatureError},
+        LocalPackage, LocalPackageLockType, LockfileIntegrityError,
+    },
+    lua_rockspec::{PerPlatform, PlatformIdentifier},
     luarocks::luarocks_installation::LUAROCKS_VERSION,
+    observer::OperationObserver,
     operations::{self, GenLuaRcError},
     package::{PackageName, PackageReq},
     progress::{MultiProgress, Progress},
     project::{
-        project_toml::LocalProjectTomlValidationError, Project, ProjectError, ProjectTreeError,
+        constraints::{CatalogConstraint, ConstraintCatalogError},
+        project_toml::LocalProjectTomlValidationError,
+        Project, ProjectError, ProjectTreeError,
     },
-    rockspec::Rockspec,
+    rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
+    timings::Timings,
     tree::{self, TreeError},
 };
 use bon::{builder, Builder};
 use itertools::Itertools;
 use thiserror::Error;
 
-use super::{Install, InstallError, PackageInstallSpec, Remove, RemoveError};
+use super::{
+    remove::recover_missing_packages, CancellationToken, Install, InstallError, PackageInstallSpec,
+    Remove, RemoveError,
+};
 
 /// A rocks sync builder, for synchronising a tree with a lockfile.
 #[derive(Builder)]
@@ -35,6 +46,14 @@ pub struct Sync<'a> {
     progress: Option<Arc<Progress<MultiProgress>>>,
     /// Whether to validate the integrity of installed packages.
     validate_integrity: Option<bool>,
+    /// Record the time spent in each install phase.
+    timings: Option<Timings>,
+    /// Record the commands that compile the packages' C sources.
+    compile_commands: Option<CompileCommands>,
+    /// Notified as packages are resolved, fetched, built and installed, or fail to install.
+    observer: Option<Arc<dyn OperationObserver>>,
+    /// Abort installing packages when cancelled.
+    cancellation: Option<CancellationToken>,
 }
 
 impl<State> SyncBuilder<'_, State>
@@ -58,10 +77,9 @@ where
     pub async fn sync_test_dependencies(mut self) -> Result<SyncReport, SyncError> {
         let toml = self.project.toml().into_local()?;
         for test_dep in toml
-            .test()
-            .current_platform()
-            .test_dependencies(self.project)
-            .iter()
+            .all_test_specs()
+            .flat_map(|test_spec| test_spec.current_platform().test_dependencies(self.project))
+            .unique_by(|test_dep| test_dep.name().clone())
             .filter(|test_dep| {
                 !toml
                     .test_dependencies()
@@ -69,7 +87,6 @@ where
                     .iter()
                     .any(|dep| dep.name() == test_dep.name())
             })
-            .cloned()
         {
             self.extra_packages.push(test_dep);
         }
@@ -104,6 +121,24 @@ where
 pub struct SyncReport {
     pub(crate) added: Vec<LocalPackage>,
     pub(crate) removed: Vec<LocalPackage>,
+    pub(crate) constrained: Vec<CatalogConstraint>,
+}
+
+impl SyncReport {
+    /// Packages that were installed to sync the tree with the lockfile.
+    pub fn added(&self) -> &[LocalPackage] {
+        &self.added
+    }
+
+    /// Packages that were removed from the tree to sync it with the lockfile.
+    pub fn removed(&self) -> &[LocalPackage] {
+        &self.removed
+    }
+
+    /// Dependencies whose version requirements were constrained by the project's constraint catalog.
+    pub fn constrained(&self) -> &[CatalogConstraint] {
+        &self.constrained
+    }
 }
 
 #[derive(Error, Debug)]
@@ -126,6 +161,10 @@ pub enum SyncError {
     LocalProjectTomlValidationError(#[from] LocalProjectTomlValidationError),
     #[error("failed to generate `.luarc.json`:\n{0}")]
     GenLuaRc(#[from] GenLuaRcError),
+    #[error(transparent)]
+    ConstraintCatalog(#[from] ConstraintCatalogError),
+    #[error(transparent)]
+    LockfileSignature(#[from] LockfileSignatureError),
 }
 
 async fn do_sync(
@@ -139,54 +178,81 @@ async fn do_sync(
     };
     std::fs::create_dir_all(tree.root())?;
 
+    if args.config.require_signed_lockfile() {
+        verify_lockfile_signature(&args.project.lockfile_path(), args.config)?;
+    }
+
+    let progress = args.progress.unwrap_or(MultiProgress::new_arc());
+
+    let lock_bar = progress.map(|p| p.new_bar());
+    let _lock = tree.lock(args.config, &lock_bar).await?;
+    lock_bar.map(|b| b.finish_and_clear());
+
+    // Stale entries of packages that were deleted from the tree are removed,
+    // so that the packages the project lockfile requires are reinstalled below.
+    recover_missing_packages(&tree, args.config, &progress).await?;
+
     let mut project_lockfile = args.project.lockfile()?.write_guard();
     let dest_lockfile = tree.lockfile()?;
 
-    let progress = args.progress.unwrap_or(MultiProgress::new_arc());
+    let toml = args.project.toml().into_local()?;
+    let dependencies = match lock_type {
+        LocalPackageLockType::Regular => toml.dependencies(),
+        LocalPackageLockType::Build => toml.build_dependencies(),
+        LocalPackageLockType::Test => toml.test_dependencies(),
+    };
+    let packages = dependencies
+        .current_platform()
+        .clone()
+        .into_iter()
+        .chain(args.extra_packages.into_iter().map_into())
+        .collect_vec();
 
-    let packages = match lock_type {
-        LocalPackageLockType::Regular => args
-            .project
-            .toml()
-            .into_local()?
-            .dependencies()
-            .current_platform()
-            .clone(),
-        LocalPackageLockType::Build => args
-            .project
-            .toml()
-            .into_local()?
-            .build_dependencies()
-            .current_platform()
-            .clone(),
-        LocalPackageLockType::Test => args
-            .project
-            .toml()
-            .into_local()?
-            .test_dependencies()
-            .current_platform()
-            .clone(),
-    }
-    .into_iter()
-    .chain(args.extra_packages.into_iter().map_into())
-    .collect_vec();
+    let (packages, constrained) = match args.project.constraint_catalog(args.config).await? {
+        Some(catalog) => {
+            let (packages, constrained) = catalog.apply(packages)?;
+            let bar = progress.map(|p| p.new_bar());
+            for constraint in &constrained {
+                bar.map(|b| {
+                    b.println(format!(
+                        "📌 {}: constrained {} to {} by {}",
+                        constraint.name(),
+                        constraint.requirement(),
+                        constraint.resolved(),
+                        catalog.source()
+                    ))
+                });
+            }
+            bar.map(|b| b.finish_and_clear());
+            (packages, constrained)
+        }
+        None => (packages, Vec::new()),
+    };
 
-    let package_sync_spec = project_lockfile.package_sync_spec(&packages, lock_type);
+    // Only the packages required on the current platform are synced,
+    // so that the entries of other platforms are kept in the project lockfile.
+    let platform = PlatformIdentifier::default();
+    let mut platform_lock = project_lockfile
+        .local_pkg_lock(lock_type)
+        .for_platform(&platform);
+
+    let package_sync_spec = platform_lock.package_sync_spec(&packages);
 
     package_sync_spec
         .to_remove
         .iter()
-        .for_each(|pkg| project_lockfile.remove(pkg, lock_type));
+        .for_each(|pkg| platform_lock.remove(pkg));
 
     let mut to_add: Vec<(tree::EntryType, LocalPackage)> = Vec::new();
 
     let mut report = SyncReport {
         added: Vec::new(),
         removed: Vec::new(),
+        constrained,
     };
-    for (id, local_package) in project_lockfile.rocks(lock_type) {
+    for (id, local_package) in platform_lock.rocks() {
         if dest_lockfile.get(id).is_none() {
-            let entry_type = if project_lockfile.is_entrypoint(&local_package.id(), lock_type) {
+            let entry_type = if platform_lock.is_entrypoint(&local_package.id()) {
                 tree::EntryType::Entrypoint
             } else {
                 tree::EntryType::DependencyOnly
@@ -194,8 +260,15 @@ async fn do_sync(
             to_add.push((entry_type, local_package.clone()));
         }
     }
+    // The project's own package is built into its tree by `lx build`.
+    // We keep it, so that incremental builds can reuse its install directory.
+    let is_project_package = |package: &LocalPackage| {
+        matches!(lock_type, LocalPackageLockType::Regular)
+            && package.name() == toml.package()
+            && package.version() == toml.version()
+    };
     for (id, local_package) in dest_lockfile.rocks() {
-        if project_lockfile.get(id, lock_type).is_none() {
+        if platform_lock.get(id).is_none() && !is_project_package(local_package) {
             report.removed.push(local_package.clone());
         }
     }
@@ -209,6 +282,25 @@ async fn do_sync(
                 .pin(pkg.pinned())
                 .opt(pkg.opt())
                 .constraint(pkg.constraint())
+                .variables(
+                    packages
+                        .iter()
+                        .find(|dep| dep.name() == pkg.name())
+                        .map(|dep| dep.variables().clone())
+                        .unwrap_or_default(),
+                )
+                .maybe_debug_symbols(
+                    packages
+                        .iter()
+                        .find(|dep| dep.name() == pkg.name())
+                        .and_then(|dep| dep.debug_symbols()),
+                )
+                .maybe_build_env(
+                    packages
+                        .iter()
+                        .find(|dep| dep.name() == pkg.name())
+                        .and_then(|dep| dep.build_env().cloned()),
+                )
                 .build()
         })
         .collect_vec();
@@ -216,13 +308,18 @@ async fn do_sync(
         .added
         .extend(to_add.iter().map(|(_, pkg)| pkg).cloned());
 
-    let package_db = project_lockfile.local_pkg_lock(lock_type).clone().into();
+    let package_db = platform_lock.clone().into();
 
     Install::new(args.config)
         .package_db(package_db)
         .packages(packages_to_install)
         .tree(tree.clone())
+        .check_conflicts(false)
         .progress(progress.clone())
+        .maybe_timings(args.timings.clone())
+        .maybe_compile_commands(args.compile_commands.clone())
+        .maybe_observer(args.observer.clone())
+        .maybe_cancellation(args.cancellation.clone())
         .install()
         .await?;
 
@@ -251,7 +348,7 @@ async fn do_sync(
         .await?;
 
     dest_lockfile.map_then_flush(|lockfile| -> Result<(), io::Error> {
-        lockfile.sync(project_lockfile.local_pkg_lock(lock_type));
+        lockfile.sync(&platform_lock);
         Ok(())
     })?;
 
@@ -266,6 +363,9 @@ async fn do_sync(
                     .pin(*dep.pin())
                     .opt(*dep.opt())
                     .maybe_source(dep.source.clone())
+                    .variables(dep.variables.clone())
+                    .maybe_debug_symbols(dep.debug_symbols)
+                    .maybe_build_env(dep.build_env.clone())
                     .build()
             })
             .collect();
@@ -273,17 +373,26 @@ async fn do_sync(
         let added = Install::new(args.config)
             .packages(missing_packages)
             .tree(tree.clone())
+            .check_conflicts(false)
             .progress(progress.clone())
+            .maybe_timings(args.timings.clone())
+            .maybe_compile_commands(args.compile_commands.clone())
+            .maybe_observer(args.observer.clone())
+            .maybe_cancellation(args.cancellation.clone())
             .install()
             .await?;
 
         report.added.extend(added);
 
-        // Sync the newly added packages back to the project lockfile
         let dest_lockfile = tree.lockfile()?;
-        project_lockfile.sync(dest_lockfile.local_pkg_lock(), lock_type);
+        platform_lock = dest_lockfile.local_pkg_lock().clone();
     }
 
+    // Sync the packages back to the project lockfile
+    project_lockfile.sync_platform(&platform_lock, lock_type, &platform, |package| {
+        is_platform_specific(dependencies, package)
+    });
+
     operations::GenLuaRc::new()
         .config(args.config)
         .project(args.project)
@@ -293,6 +402,24 @@ async fn do_sync(
     Ok(report)
 }
 
+/// Whether a package is only declared as a dependency on some platforms,
+/// or with different version requirements on different platforms.
+fn is_platform_specific(
+    dependencies: &PerPlatform<Vec<LuaDependencySpec>>,
+    package: &LocalPackage,
+) -> bool {
+    let declarations = std::iter::once(&dependencies.default)
+        .chain(dependencies.per_platform.values())
+        .map(|dependencies| {
+            dependencies
+                .iter()
+                .find(|dep| dep.name() == package.name())
+                .map(|dep| dep.version_req())
+        })
+        .collect_vec();
+    declarations.iter().any(Option::is_some) && !declarations.iter().all_equal()
+}
+
 #[cfg(test)]
 mod tests {
     use super::Sync;
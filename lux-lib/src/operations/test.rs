@@ -1,25 +1,44 @@
-use std::{io, ops::Deref, path::PathBuf, process::Command, sync::Arc};
+use std::{
+    io,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use crate::{
-    build::BuildBehaviour,
-    config::{Config, ConfigError},
-    lua_installation::{LuaBinary, LuaBinaryError},
+    build::{
+        sanitizer::{self, Sanitizer},
+        BuildBehaviour,
+    },
+    config::{Config, ConfigError, LuaVersion},
+    lua_installation::{
+        nlua::{install_nlua, NluaError, NVIM_APPNAME},
+        LuaBinaryError,
+    },
     lua_rockspec::{LuaVersionError, TestSpecError, ValidatedTestSpec},
     package::{PackageName, PackageVersionReqError},
     path::{Paths, PathsError},
+    process::{self, ProcessLimits},
     progress::{MultiProgress, Progress},
     project::{
-        project_toml::LocalProjectTomlValidationError, Project, ProjectError, ProjectTreeError,
+        project_toml::{LocalProjectTomlValidationError, TestSuite, TestSuiteNotFound},
+        Project, ProjectError, ProjectTreeError,
     },
     rockspec::Rockspec,
-    tree::{self, TreeError},
+    tree::{self, TreeError, LOCKFILE_NAME},
+    variables::VariableSubstitutionError,
 };
 use bon::Builder;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
+use tokio::process::Command;
 
 use super::{
-    BuildProject, BuildProjectError, Install, InstallError, PackageInstallSpec, Sync, SyncError,
+    substitute_layout_variables, substitute_layout_variables_in_env, BuildProject,
+    BuildProjectError, Install, InstallError, PackageInstallSpec, Sync, SyncError,
 };
 
 #[cfg(target_family = "unix")]
@@ -27,6 +46,16 @@ const BUSTED_EXE: &str = "busted";
 #[cfg(target_family = "windows")]
 const BUSTED_EXE: &str = "busted.bat";
 
+/// Caches the test environment in the test tree, see [`CachedTestEnv`].
+const TEST_ENV_CACHE_FILE: &str = "test-env.json";
+
+/// Environment variables that are kept in a hermetic test environment,
+/// because processes can't be spawned reliably without them.
+#[cfg(target_family = "unix")]
+const PRESERVED_ENV_VARS: &[&str] = &[];
+#[cfg(target_family = "windows")]
+const PRESERVED_ENV_VARS: &[&str] = &["SYSTEMROOT", "COMSPEC", "PATHEXT", "WINDIR"];
+
 #[derive(Builder)]
 #[builder(start_fn = new, finish_fn(name = _run, vis = ""))]
 pub struct Test<'a> {
@@ -40,6 +69,13 @@ pub struct Test<'a> {
 
     no_lock: Option<bool>,
 
+    /// The test suite to run, e.g. `integration` for `[test.integration]` in the lux.toml.
+    /// Defaults to the `[test].default_suite`, or the `[test]` section itself.
+    suite: Option<String>,
+
+    /// Overrides the `[test].timeout` in the lux.toml.
+    timeout: Option<Duration>,
+
     #[builder(default)]
     env: TestEnv,
     #[builder(default = MultiProgress::new_arc())]
@@ -71,6 +107,12 @@ pub enum TestEnv {
     /// An impure environment in which `HOME` and `XDG` base directories can influence
     /// the test results.
     Impure,
+    /// A scrubbed environment, isolated like [`TestEnv::Pure`], in which only
+    /// the variables set by Lux are present and the locale is fixed to `C`.
+    /// This catches tests that depend on the user's shell, e.g. on rocks
+    /// that are installed globally and found via `LUA_PATH_5_x` or `LUA_INIT`.
+    /// Enabled by `[test].hermetic = true` in the lux.toml, unless the environment is impure.
+    Hermetic,
 }
 
 impl Default for TestEnv {
@@ -91,6 +133,8 @@ pub enum RunTestsError {
     TestFailure,
     #[error("failed to execute `{0}`: {1}")]
     RunCommandFailure(String, io::Error),
+    #[error("{0}")]
+    Timeout(io::Error),
     #[error(transparent)]
     Io(#[from] io::Error),
     #[error(transparent)]
@@ -100,6 +144,8 @@ pub enum RunTestsError {
     #[error(transparent)]
     Tree(#[from] ProjectTreeError),
     #[error(transparent)]
+    Lockfile(#[from] TreeError),
+    #[error(transparent)]
     ProjectTomlValidation(#[from] LocalProjectTomlValidationError),
     #[error("failed to sync dependencies: {0}")]
     Sync(#[from] SyncError),
@@ -109,23 +155,62 @@ pub enum RunTestsError {
     LuaVersion(#[from] LuaVersionError),
     #[error(transparent)]
     LuaBinary(#[from] LuaBinaryError),
+    #[error(transparent)]
+    VariableSubstitution(#[from] VariableSubstitutionError),
+    #[error(transparent)]
+    TestSuiteNotFound(#[from] TestSuiteNotFound),
+    #[error(transparent)]
+    Nlua(#[from] NluaError),
 }
 
 async fn run_tests(test: Test<'_>) -> Result<(), RunTestsError> {
     let rocks = test.project.toml().into_local()?;
+    let suite = rocks.test_suite(test.suite.as_deref())?;
 
-    let test_spec = rocks
-        .test()
+    let test_spec = suite
+        .spec()
         .current_platform()
         .to_validated(&test.project)?;
 
-    let config = test_spec.test_config(test.config)?;
+    let config = test_spec.test_config(test.config, None)?;
+    let config = match &test_spec {
+        ValidatedTestSpec::BustedNlua(_) => {
+            // Installed before syncing, so that busted's wrapper script runs it
+            let nlua = install_nlua(&test.project.test_tree(&config)?, &config)?;
+            test_spec.test_config(test.config, Some(&nlua))?
+        }
+        _ => config,
+    };
 
     let no_lock = test.no_lock.unwrap_or(false);
 
-    if no_lock {
-        ensure_test_dependencies(&test.project, &rocks, &config, test.progress).await?;
+    let test_tree = test.project.test_tree(&config)?;
+    let project_tree = test.project.tree(&config)?;
+    let trees = [
+        Some(&project_tree),
+        Some(&test_tree),
+        project_tree.system_tree(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|tree| tree.root())
+    .collect_vec();
+    let cache_file = test_tree.root().join(TEST_ENV_CACHE_FILE);
+    let lua_version = test.project.lua_version(&config)?;
+    let cached_env = if no_lock {
+        None
     } else {
+        CachedTestEnv::load(
+            &cache_file,
+            &test_env_fingerprint(&test.project, &config, &lua_version, &trees)?,
+        )
+    };
+
+    if no_lock {
+        // Installing without the lockfile can leave the dependencies out of sync with it.
+        let _ = std::fs::remove_file(&cache_file);
+        ensure_test_dependencies(&test.project, &rocks, &suite, &config, test.progress).await?;
+    } else if cached_env.is_none() {
         Sync::new(&test.project, &config)
             .progress(test.progress.clone())
             .sync_test_dependencies()
@@ -135,34 +220,82 @@ async fn run_tests(test: Test<'_>) -> Result<(), RunTestsError> {
     BuildProject::new(&test.project, &config)
         .no_lock(no_lock)
         .only_deps(false)
+        .synced(cached_env.is_some())
         .build()
         .await?;
 
-    let project_tree = test.project.tree(&config)?;
-    let test_tree = test.project.test_tree(&config)?;
-    let mut paths = Paths::new(&project_tree)?;
-    let test_tree_paths = Paths::new(&test_tree)?;
-    paths.prepend(&test_tree_paths);
+    let cached_env = match cached_env {
+        Some(cached_env) => cached_env,
+        None => {
+            let mut paths = Paths::new(&project_tree)?;
+            let test_tree_paths = Paths::new(&test_tree)?;
+            paths.prepend(&test_tree_paths);
+            let env = CachedTestEnv {
+                // The sync may have updated the lockfile
+                fingerprint: test_env_fingerprint(&test.project, &config, &lua_version, &trees)?,
+                trees,
+                path: paths.path_prepended().joined(),
+                lua_path: paths.package_path().joined(),
+                lua_cpath: paths.package_cpath().joined(),
+            };
+            if !no_lock {
+                env.write(&cache_file)?;
+            }
+            env
+        }
+    };
 
     let mut command = match &test_spec {
         ValidatedTestSpec::Busted(_) => Command::new(BUSTED_EXE),
         ValidatedTestSpec::BustedNlua(_) => Command::new(BUSTED_EXE),
         ValidatedTestSpec::Command(spec) => Command::new(spec.command.clone()),
         ValidatedTestSpec::LuaScript(_) => {
-            let lua_version = test.project.lua_version(&config)?;
-            let lua_binary = LuaBinary::new(lua_version, &config);
+            let lua_binary = test.project.lua_binary(&config)?;
             let lua_bin_path: PathBuf = lua_binary.try_into()?;
             Command::new(lua_bin_path)
         }
     };
+    let env = match test.env {
+        TestEnv::Pure if suite.hermetic() => TestEnv::Hermetic,
+        env => env,
+    };
+    if let TestEnv::Hermetic = env {
+        command.env_clear();
+        for var in PRESERVED_ENV_VARS {
+            if let Some(value) = std::env::var_os(var) {
+                command.env(var, value);
+            }
+        }
+        command.env("LANG", "C").env("LC_ALL", "C");
+    }
+    let layout = test.project.layout(&config)?;
+    let cwd = match suite.cwd() {
+        Some(cwd) => test.project.root().join(substitute_layout_variables(
+            layout.as_ref(),
+            Some(&lua_version),
+            &cwd.to_string_lossy(),
+        )?),
+        None => test.project.root().deref().clone(),
+    };
+    let test_args: Vec<String> = test_spec
+        .args()
+        .iter()
+        .map(|arg| substitute_layout_variables(layout.as_ref(), Some(&lua_version), arg))
+        .try_collect()?;
     let mut command = command
-        .current_dir(test.project.root().deref())
-        .args(test_spec.args())
+        .current_dir(cwd)
+        .args(test_args)
         .args(test.args)
-        .env("PATH", paths.path_prepended().joined())
-        .env("LUA_PATH", paths.package_path().joined())
-        .env("LUA_CPATH", paths.package_cpath().joined());
-    if let TestEnv::Pure = test.env {
+        .env("PATH", &cached_env.path)
+        .env("LUA_PATH", &cached_env.lua_path)
+        .env("LUA_CPATH", &cached_env.lua_cpath);
+    if let Some(layout) = &layout {
+        command = command.envs(layout.env_vars());
+    }
+    if let ValidatedTestSpec::BustedNlua(_) = test_spec {
+        command = command.env("NVIM_APPNAME", NVIM_APPNAME);
+    }
+    if let TestEnv::Pure | TestEnv::Hermetic = env {
         // isolate the test runner from the user's own config/data files
         // by initialising empty HOME and XDG base directory paths
         let home = test_tree.root().join("home");
@@ -174,14 +307,34 @@ async fn run_tests(test: Test<'_>) -> Result<(), RunTestsError> {
         std::fs::create_dir_all(&xdg_state_home)?;
         let xdg_data_home = xdg.join("local").join("share");
         std::fs::create_dir_all(&xdg_data_home)?;
+        if let TestEnv::Hermetic = env {
+            let xdg_cache_home = xdg.join("cache");
+            std::fs::create_dir_all(&xdg_cache_home)?;
+            let tmp = home.join("tmp");
+            std::fs::create_dir_all(&tmp)?;
+            command = command
+                .env("XDG_CACHE_HOME", xdg_cache_home)
+                .env("TMPDIR", &tmp)
+                .env("TEMP", &tmp)
+                .env("TMP", tmp);
+        }
         command = command
             .env("HOME", home)
             .env("XDG_CONFIG_HOME", xdg_config_home)
             .env("XDG_STATE_HOME", xdg_state_home)
             .env("XDG_DATA_HOME", xdg_data_home);
     }
-    let status = match command.status() {
+    let sanitizers = instrumented_sanitizers(&test.project.tree(&config)?)?;
+    command = command.envs(sanitizer::runtime_env(&sanitizers, &config).await);
+    command = command.envs(substitute_layout_variables_in_env(
+        layout.as_ref(),
+        Some(&lua_version),
+        suite.env(),
+    )?);
+    let limits = ProcessLimits::test(&config, test.timeout.or(suite.timeout()));
+    let status = match process::status(command, &limits, "tests").await {
         Ok(status) => Ok(status),
+        Err(err) if err.kind() == io::ErrorKind::TimedOut => Err(RunTestsError::Timeout(err)),
         Err(err) => Err(RunTestsError::RunCommandFailure("busted".into(), err)),
     }?;
     if status.success() {
@@ -191,6 +344,90 @@ async fn run_tests(test: Test<'_>) -> Result<(), RunTestsError> {
     }
 }
 
+/// The sanitizers that C modules in the `tree` are instrumented with,
+/// e.g. by `lx build --sanitize address`.
+fn instrumented_sanitizers(tree: &tree::Tree) -> Result<Vec<Sanitizer>, TreeError> {
+    Ok(tree
+        .lockfile()?
+        .rocks()
+        .values()
+        .filter_map(|package| package.provenance())
+        .flat_map(|provenance| provenance.sanitizers())
+        .copied()
+        .unique()
+        .collect())
+}
+
+/// The test environment of a project whose dependencies are in sync with its lockfile.
+/// While the `lux.toml`, `lux.lock` and the config don't change,
+/// and the trees haven't been removed, `lx test` can reuse it
+/// instead of syncing the dependencies and resolving the paths of the test tree.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct CachedTestEnv {
+    fingerprint: String,
+    /// The roots of the trees that the paths point to.
+    #[serde(default)]
+    trees: Vec<PathBuf>,
+    path: String,
+    lua_path: String,
+    lua_cpath: String,
+}
+
+impl CachedTestEnv {
+    /// Loads the cached test environment, if it matches the `fingerprint`
+    /// and its trees and their lockfiles still exist.
+    fn load(cache_file: &Path, fingerprint: &str) -> Option<Self> {
+        let content = std::fs::read_to_string(cache_file).ok()?;
+        serde_json::from_str::<Self>(&content)
+            .ok()
+            .filter(|env| env.fingerprint == fingerprint)
+            .filter(|env| {
+                env.trees
+                    .iter()
+                    .all(|root| root.is_dir() && root.join(LOCKFILE_NAME).is_file())
+            })
+    }
+
+    fn write(&self, cache_file: &Path) -> io::Result<()> {
+        std::fs::write(cache_file, serde_json::to_string(self)?)
+    }
+}
+
+/// Identifies the state of the project's `lux.toml` and `lux.lock`,
+/// and the config and trees that the test environment depends on.
+fn test_env_fingerprint(
+    project: &Project,
+    config: &Config,
+    lua_version: &LuaVersion,
+    trees: &[PathBuf],
+) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(lua_version.to_string());
+    let optional_paths = [config.lua_dir(), config.system_tree()];
+    for path in optional_paths.into_iter().chain(trees.iter().map(Some)) {
+        if let Some(path) = path {
+            hasher.update(path.as_os_str().as_encoded_bytes());
+        }
+        hasher.update([0]);
+    }
+    for (name, value) in config.variables().iter().sorted() {
+        hasher.update(name);
+        hasher.update([0]);
+        hasher.update(value);
+        hasher.update([0]);
+    }
+    for file in [project.toml_path(), project.lockfile_path()] {
+        match std::fs::read(&file) {
+            Ok(content) => hasher.update(content),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        hasher.update([0]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
 #[derive(Error, Debug)]
 #[error("error installing test dependencies: {0}")]
 pub enum InstallTestDependenciesError {
@@ -205,13 +442,14 @@ pub enum InstallTestDependenciesError {
 async fn ensure_test_dependencies(
     project: &Project,
     rockspec: &impl Rockspec,
+    suite: &TestSuite,
     config: &Config,
     progress: Arc<Progress<MultiProgress>>,
 ) -> Result<(), InstallTestDependenciesError> {
     let test_tree = project.test_tree(config)?;
     let rockspec_dependencies = rockspec.test_dependencies().current_platform();
-    let test_dependencies = rockspec
-        .test()
+    let test_dependencies = suite
+        .spec()
         .current_platform()
         .test_dependencies(project)
         .iter()
@@ -257,6 +495,7 @@ async fn ensure_test_dependencies(
                         .pin(*dep.pin())
                         .opt(*dep.opt())
                         .maybe_source(dep.source.clone())
+                        .variables(dep.variables.clone())
                         .build()
                     })
                 }),
@@ -266,6 +505,7 @@ async fn ensure_test_dependencies(
     Install::new(config)
         .packages(test_dependencies)
         .tree(test_tree)
+        .check_conflicts(false)
         .progress(progress.clone())
         .install()
         .await?;
@@ -275,12 +515,9 @@ async fn ensure_test_dependencies(
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::{collections::HashMap, path::Path};
 
-    use crate::{
-        config::{ConfigBuilder, LuaVersion},
-        lua_installation::detect_installed_lua_version,
-    };
+    use crate::{config::ConfigBuilder, lua_installation::detect_installed_lua_version};
 
     use super::*;
     use assert_fs::{prelude::PathCopy, TempDir};
@@ -299,6 +536,94 @@ mod tests {
         run_test(&project_root).await
     }
 
+    #[test]
+    fn cached_test_env_is_invalidated_by_lockfile_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        temp_dir
+            .copy_from(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("resources/test/sample-projects/command-test/"),
+                &["**"],
+            )
+            .unwrap();
+        let project = Project::from(temp_dir.path()).unwrap().unwrap();
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let fingerprint = test_env_fingerprint(&project, &config, &LuaVersion::Lua51, &[]).unwrap();
+        let cache_file = temp_dir.path().join(TEST_ENV_CACHE_FILE);
+        let env = CachedTestEnv {
+            fingerprint: fingerprint.clone(),
+            trees: Vec::new(),
+            path: "path".into(),
+            lua_path: "lua_path".into(),
+            lua_cpath: "lua_cpath".into(),
+        };
+        env.write(&cache_file).unwrap();
+        assert_eq!(CachedTestEnv::load(&cache_file, &fingerprint), Some(env));
+
+        std::fs::write(project.lockfile_path(), "{}").unwrap();
+        let fingerprint = test_env_fingerprint(&project, &config, &LuaVersion::Lua51, &[]).unwrap();
+        assert_eq!(CachedTestEnv::load(&cache_file, &fingerprint), None);
+    }
+
+    #[test]
+    fn cached_test_env_is_invalidated_by_config_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        temp_dir
+            .copy_from(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("resources/test/sample-projects/command-test/"),
+                &["**"],
+            )
+            .unwrap();
+        let project = Project::from(temp_dir.path()).unwrap().unwrap();
+        let fingerprint = |config: &Config, trees: &[PathBuf]| {
+            test_env_fingerprint(&project, config, &LuaVersion::Lua51, trees).unwrap()
+        };
+        let config = ConfigBuilder::new().unwrap().build().unwrap();
+        let tree = temp_dir.path().join("tree");
+        let base = fingerprint(&config, std::slice::from_ref(&tree));
+
+        let other_tree = temp_dir.path().join("other-tree");
+        assert_ne!(base, fingerprint(&config, &[other_tree]));
+        for config in [
+            ConfigBuilder::new()
+                .unwrap()
+                .lua_dir(Some(temp_dir.path().join("lua"))),
+            ConfigBuilder::new()
+                .unwrap()
+                .system_tree(Some(temp_dir.path().join("system"))),
+            ConfigBuilder::new()
+                .unwrap()
+                .variables(Some(HashMap::from_iter([("FOO".into(), "bar".into())]))),
+        ] {
+            let config = config.build().unwrap();
+            assert_ne!(base, fingerprint(&config, std::slice::from_ref(&tree)));
+        }
+    }
+
+    #[test]
+    fn cached_test_env_is_invalidated_by_removed_trees() {
+        let temp_dir = TempDir::new().unwrap();
+        let tree = temp_dir.path().join("tree");
+        std::fs::create_dir_all(&tree).unwrap();
+        std::fs::write(tree.join(LOCKFILE_NAME), "{}").unwrap();
+        let cache_file = temp_dir.path().join(TEST_ENV_CACHE_FILE);
+        let env = CachedTestEnv {
+            fingerprint: "fingerprint".into(),
+            trees: vec![tree.clone()],
+            path: "path".into(),
+            lua_path: "lua_path".into(),
+            lua_cpath: "lua_cpath".into(),
+        };
+        env.write(&cache_file).unwrap();
+        assert_eq!(CachedTestEnv::load(&cache_file, "fingerprint"), Some(env));
+
+        std::fs::remove_file(tree.join(LOCKFILE_NAME)).unwrap();
+        assert_eq!(CachedTestEnv::load(&cache_file, "fingerprint"), None);
+        std::fs::remove_dir_all(&tree).unwrap();
+        assert_eq!(CachedTestEnv::load(&cache_file, "fingerprint"), None);
+    }
+
     async fn run_test(project_root: &Path) {
         let temp_dir = TempDir::new().unwrap();
         temp_dir.copy_from(project_root, &["**"]).unwrap();
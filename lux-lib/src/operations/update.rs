@@ -1,16 +1,20 @@
-use std::{io, sync::Arc};
+use std::{collections::HashSet, io, sync::Arc};
 
 use bon::Builder;
 use itertools::Itertools;
 use thiserror::Error;
 
 use crate::{
-    config::{Config, LuaVersion, LuaVersionUnset},
+    config::{Config, LuaVersion, LuaVersionUnset, ResolutionStrategy},
     lockfile::{
-        LocalPackage, LocalPackageLockType, Lockfile, PinnedState, ProjectLockfile, ReadOnly,
-        ReadWrite,
+        LocalPackage, LocalPackageLockType, LockConstraint, Lockfile, PinnedState, ProjectLockfile,
+        ReadOnly, ReadWrite,
+    },
+    lua_rockspec::PlatformIdentifier,
+    package::{
+        PackageName, PackageReq, PackageSpec, PackageVersion, PackageVersionReq,
+        RockConstraintUnsatisfied,
     },
-    package::{PackageReq, RockConstraintUnsatisfied},
     progress::{MultiProgress, Progress},
     project::{Project, ProjectError, ProjectTreeError},
     remote_package_db::{RemotePackageDB, RemotePackageDBError},
@@ -42,6 +46,14 @@ pub enum UpdateError {
     ProjectTree(#[from] ProjectTreeError),
     #[error("error syncing the project tree: {0}")]
     Sync(#[from] SyncError),
+    #[error("--precise requires exactly one package to update")]
+    PreciseRequiresSinglePackage,
+    #[error("{name} {version} does not satisfy the constraint {constraint}")]
+    PreciseVersionUnsatisfied {
+        name: PackageName,
+        version: PackageVersion,
+        constraint: PackageVersionReq,
+    },
 }
 
 /// A rocks package updater, providing fine-grained control
@@ -68,6 +80,16 @@ pub struct Update<'a> {
     /// Whether to validate the integrity when syncing the project lockfile.
     validate_integrity: Option<bool>,
 
+    /// Update the selected package to exactly this version,
+    /// which must satisfy the package's constraint.
+    /// Requires exactly one package to be selected.
+    precise: Option<PackageVersion>,
+
+    /// Update pinned packages, unpinning them.
+    /// By default, pinned packages are skipped.
+    #[builder(default)]
+    unpin: bool,
+
     package_db: Option<RemotePackageDB>,
 
     #[builder(default = MultiProgress::new_arc())]
@@ -97,6 +119,21 @@ impl<State: update_builder::State> UpdateBuilder<'_, State> {
     {
         let args = self._update();
 
+        if args.precise.is_some()
+            && [
+                &args.packages,
+                &args.build_dependencies,
+                &args.test_dependencies,
+            ]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .count()
+                != 1
+        {
+            return Err(UpdateError::PreciseRequiresSinglePackage);
+        }
+
         let package_db = match &args.package_db {
             Some(db) => db.clone(),
             None => {
@@ -122,6 +159,15 @@ async fn update_project(
     let mut project_lockfile = project.lockfile()?.write_guard();
     let tree = project.tree(args.config)?;
 
+    // If any packages are selected, only the selected packages are updated.
+    let is_selective = args.packages.is_some()
+        || args.build_dependencies.is_some()
+        || args.test_dependencies.is_some();
+    let selection = |packages: &Option<Vec<PackageReq>>| match packages {
+        None if is_selective => Some(Vec::new()),
+        packages => packages.clone(),
+    };
+
     let dep_report = super::Sync::new(&project, args.config)
         .validate_integrity(args.validate_integrity.unwrap_or(false))
         .sync_dependencies()
@@ -132,9 +178,8 @@ async fn update_project(
         &mut project_lockfile,
         LocalPackageLockType::Regular,
         package_db.clone(),
-        args.config,
-        args.progress.clone(),
-        &args.packages,
+        &args,
+        &selection(&args.packages),
     )
     .await?
     .into_iter()
@@ -151,9 +196,8 @@ async fn update_project(
         &mut project_lockfile,
         LocalPackageLockType::Test,
         package_db.clone(),
-        args.config,
-        args.progress.clone(),
-        &args.test_dependencies,
+        &args,
+        &selection(&args.test_dependencies),
     )
     .await?
     .into_iter()
@@ -171,9 +215,8 @@ async fn update_project(
         &mut project_lockfile,
         LocalPackageLockType::Build,
         package_db.clone(),
-        args.config,
-        args.progress.clone(),
-        &args.build_dependencies,
+        &args,
+        &selection(&args.build_dependencies),
     )
     .await?
     .into_iter()
@@ -192,20 +235,33 @@ async fn update_dependency_tree(
     project_lockfile: &mut ProjectLockfile<ReadWrite>,
     lock_type: LocalPackageLockType,
     package_db: RemotePackageDB,
-    config: &Config,
-    progress: Arc<Progress<MultiProgress>>,
+    args: &Update<'_>,
     packages: &Option<Vec<PackageReq>>,
 ) -> Result<Vec<LocalPackage>, UpdateError> {
     let lockfile = tree.lockfile()?;
-    let dependencies = updatable_packages(&lockfile)
+    let dependencies = updatable_packages(lockfile.rocks().values(), args.unpin)
         .into_iter()
         .filter(|pkg| is_included(pkg, packages))
         .collect_vec();
     let updated_lockfile = tree.lockfile()?;
-    let updated_dependencies =
-        update(dependencies, package_db, tree, &lockfile, config, progress).await?;
+    let updated_dependencies = update(dependencies, package_db, tree, &lockfile, args).await?;
     if !updated_dependencies.is_empty() {
-        project_lockfile.sync(updated_lockfile.local_pkg_lock(), &lock_type);
+        // Updated packages stay specific to the current platform if they were before.
+        let platform = PlatformIdentifier::default();
+        let platform_specific = updated_lockfile
+            .rocks()
+            .values()
+            .filter(|package| {
+                project_lockfile.is_platform_entrypoint(package, &lock_type, &platform)
+            })
+            .map(|package| package.name().clone())
+            .collect::<HashSet<_>>();
+        project_lockfile.sync_platform(
+            updated_lockfile.local_pkg_lock(),
+            &lock_type,
+            &platform,
+            |package| platform_specific.contains(package.name()),
+        );
     }
     Ok(updated_dependencies)
 }
@@ -230,19 +286,11 @@ async fn update_install_tree(
         .config
         .user_tree(LuaVersion::from(args.config)?.clone())?;
     let lockfile = tree.lockfile()?;
-    let packages = updatable_packages(&lockfile)
+    let packages = updatable_packages(lockfile.rocks().values(), args.unpin)
         .into_iter()
         .filter(|pkg| is_included(pkg, &args.packages))
         .collect_vec();
-    update(
-        packages,
-        package_db,
-        tree,
-        &lockfile,
-        args.config,
-        args.progress,
-    )
-    .await
+    update(packages, package_db, tree, &lockfile, &args).await
 }
 
 async fn update(
@@ -250,54 +298,87 @@ async fn update(
     package_db: RemotePackageDB,
     tree: Tree,
     lockfile: &Lockfile<ReadOnly>,
-    config: &Config,
-    progress: Arc<Progress<MultiProgress>>,
+    args: &Update<'_>,
 ) -> Result<Vec<LocalPackage>, UpdateError> {
-    let updatable = packages
-        .clone()
-        .into_iter()
-        .filter_map(|(package, constraint)| {
-            match package
-                .to_package()
-                .has_update_with(&constraint, &package_db)
+    let updatable = match &args.precise {
+        Some(version) => {
+            if let Some((package, constraint)) = packages
+                .iter()
+                .find(|(_, constraint)| !constraint.version_req().matches(version))
             {
-                Ok(Some(_)) if package.pinned() == PinnedState::Unpinned => {
-                    Some((package, constraint))
-                }
-                _ => None,
+                return Err(UpdateError::PreciseVersionUnsatisfied {
+                    name: package.name().clone(),
+                    version: version.clone(),
+                    constraint: constraint.version_req().clone(),
+                });
             }
-        })
-        .collect_vec();
+            packages
+                .into_iter()
+                .filter(|(package, _)| package.version() != version)
+                .collect_vec()
+        }
+        None => packages
+            .into_iter()
+            .filter(|(package, constraint)| {
+                update_target(package, constraint, args.config, &package_db).is_some()
+            })
+            .collect_vec(),
+    };
     if updatable.is_empty() {
         Ok(Vec::new())
     } else {
-        Remove::new(config)
+        Remove::new(args.config)
             .packages(updatable.iter().map(|(package, _)| package.id()))
-            .progress(progress.clone())
+            .progress(args.progress.clone())
             .remove()
             .await?;
-        let updated_packages = Install::new(config)
+        let updated_packages = Install::new(args.config)
             .packages(
                 updatable
                     .iter()
-                    .map(|updatable| mk_install_spec(updatable, lockfile))
+                    .map(|updatable| mk_install_spec(updatable, lockfile, args.precise.as_ref()))
                     .collect(),
             )
             .tree(tree)
+            .check_conflicts(false)
             .package_db(package_db)
-            .progress(progress)
+            .progress(args.progress.clone())
             .install()
             .await?;
         Ok(updated_packages)
     }
 }
 
-fn updatable_packages(lockfile: &Lockfile<ReadOnly>) -> Vec<(LocalPackage, PackageReq)> {
-    lockfile
-        .rocks()
-        .values()
+/// The version that `lx update` would update a package to, if any.
+pub(super) fn update_target(
+    package: &LocalPackage,
+    constraint: &PackageReq,
+    config: &Config,
+    package_db: &RemotePackageDB,
+) -> Option<PackageVersion> {
+    match config.resolution_strategy() {
+        ResolutionStrategy::Highest => package
+            .to_package()
+            .has_update_with(constraint, package_db)
+            .ok()
+            .flatten(),
+        // Reinstall packages that are not at the lowest version that satisfies their constraint.
+        ResolutionStrategy::Minimal => package_db
+            .resolve(constraint, None)
+            .map(|resolved| resolved.version().clone())
+            .filter(|version| version != package.version()),
+    }
+}
+
+/// Packages that can be updated, with the constraints they were installed with.
+pub(super) fn updatable_packages<'a>(
+    rocks: impl IntoIterator<Item = &'a LocalPackage>,
+    unpin: bool,
+) -> Vec<(LocalPackage, PackageReq)> {
+    rocks
+        .into_iter()
         .filter(|package| {
-            package.pinned() == PinnedState::Unpinned
+            (unpin || package.pinned() == PinnedState::Unpinned)
                 && match package.source() {
                     RemotePackageSource::LuarocksRockspec(_) => true,
                     RemotePackageSource::LuarocksSrcRock(_) => true,
@@ -310,21 +391,38 @@ fn updatable_packages(lockfile: &Lockfile<ReadOnly>) -> Vec<(LocalPackage, Packa
                     RemotePackageSource::Test => false,
                 }
         })
-        .map(|package| (package.clone(), package.to_package().into_package_req()))
+        .map(|package| {
+            let constraint = PackageReq {
+                name: package.name().clone(),
+                version_req: match package.constraint() {
+                    LockConstraint::Unconstrained => PackageVersionReq::any(),
+                    LockConstraint::Constrained(version_req) => version_req,
+                },
+            };
+            (package.clone(), constraint)
+        })
         .collect_vec()
 }
 
 fn mk_install_spec(
-    (package, req): &(LocalPackage, PackageReq),
+    (package, constraint): &(LocalPackage, PackageReq),
     lockfile: &Lockfile<ReadOnly>,
+    precise: Option<&PackageVersion>,
 ) -> PackageInstallSpec {
     let entry_type = if lockfile.is_entrypoint(&package.id()) {
         tree::EntryType::Entrypoint
     } else {
         tree::EntryType::DependencyOnly
     };
-    PackageInstallSpec::new(req.clone(), entry_type)
+    let package_req = match precise {
+        Some(version) => {
+            PackageSpec::new(package.name().clone(), version.clone()).into_package_req()
+        }
+        None => constraint.clone(),
+    };
+    PackageInstallSpec::new(package_req, entry_type)
         .pin(PinnedState::Unpinned)
         .opt(package.opt())
+        .constraint(package.constraint())
         .build()
 }
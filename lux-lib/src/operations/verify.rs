@@ -0,0 +1,363 @@
+use std::{fmt::Display, io, path::PathBuf};
+
+use bon::Builder;
+use itertools::Itertools;
+use path_slash::PathExt;
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    config::Config,
+    lockfile::LocalPackageLockType,
+    lua_rockspec::{BuildBackendSpec, LuaModule, ModuleSpec, RemoteLuaRockspec, RockSourceSpec},
+    package::{PackageReq, PackageSpec},
+    project::{
+        project_toml::{LocalProjectTomlValidationError, RemoteProjectToml},
+        rockspec_template::{RockspecTemplate, RockspecTemplateError, ROCKSPEC_TEMPLATE},
+        Project, ProjectError,
+    },
+    rockspec::Rockspec,
+};
+
+use super::DependencySection;
+
+/// An inconsistency between a project's `lux.toml`, its generated rockspec,
+/// its lockfile and its sources.
+#[derive(Debug, Clone)]
+pub enum VerifyIssue {
+    /// The `lux.toml` can't be converted to a rockspec, e.g. because its version template
+    /// doesn't resolve.
+    Rockspec(String),
+    /// The generated rockspec can't be parsed.
+    InvalidRockspec(String),
+    /// A rockspec in the project root differs from the one that would be generated.
+    OutdatedRockspec(PathBuf),
+    /// A module of the builtin build backend points to a file that doesn't exist.
+    MissingModuleSource { module: LuaModule, path: PathBuf },
+    /// A file that the build installs doesn't exist.
+    MissingInstallFile { name: String, path: PathBuf },
+    /// A directory that the build copies doesn't exist.
+    MissingCopyDirectory(PathBuf),
+    /// The source URL can't be fetched.
+    UnreachableSource { url: Url, reason: String },
+    /// A dependency is declared in the `lux.toml`, but not locked.
+    UnlockedDependency {
+        package: PackageReq,
+        section: DependencySection,
+    },
+    /// A package is locked, but no longer required by the `lux.toml`'s dependencies.
+    StaleLockedPackage {
+        package: PackageSpec,
+        section: DependencySection,
+    },
+}
+
+impl Display for VerifyIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rockspec(err) => write!(f, "cannot generate a rockspec: {err}"),
+            Self::InvalidRockspec(err) => write!(f, "the generated rockspec is invalid: {err}"),
+            Self::OutdatedRockspec(path) => write!(
+                f,
+                "{}: outdated rockspec: it differs from the one generated from the lux.toml",
+                path.to_slash_lossy()
+            ),
+            Self::MissingModuleSource { module, path } => write!(
+                f,
+                "{}: missing module source: module `{module}` points to a file that does not exist",
+                path.to_slash_lossy()
+            ),
+            Self::MissingInstallFile { name, path } => write!(
+                f,
+                "{}: missing install file: `{name}` points to a file that does not exist",
+                path.to_slash_lossy()
+            ),
+            Self::MissingCopyDirectory(path) => write!(
+                f,
+                "{}: missing copy directory: the directory does not exist",
+                path.to_slash_lossy()
+            ),
+            Self::UnreachableSource { url, reason } => {
+                write!(f, "unreachable source: {url} ({reason})")
+            }
+            Self::UnlockedDependency { package, section } => write!(
+                f,
+                "unlocked dependency: {package} is declared in {section}, but not locked"
+            ),
+            Self::StaleLockedPackage { package, section } => write!(
+                f,
+                "stale lockfile entry: {package} is locked in {section}, but no longer required"
+            ),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ProjectVerifyError {
+    #[error(transparent)]
+    LocalProjectTomlValidation(#[from] LocalProjectTomlValidationError),
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, io::Error),
+    #[error(transparent)]
+    RockspecTemplate(#[from] RockspecTemplateError),
+    #[error(transparent)]
+    HttpClient(#[from] reqwest::Error),
+}
+
+/// Checks that a project's `lux.toml`, its generated rockspec and its lockfile are consistent,
+/// and that the files and source URL they refer to exist, e.g. before publishing the project.
+#[derive(Builder)]
+#[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
+pub struct ProjectVerify<'a> {
+    #[builder(start_fn)]
+    project: &'a Project,
+    #[builder(start_fn)]
+    config: &'a Config,
+
+    /// Send a `HEAD` request to check that the source URL can be fetched.
+    #[builder(default = true)]
+    fetch_source: bool,
+}
+
+impl<State: project_verify_builder::State> ProjectVerifyBuilder<'_, State> {
+    /// Returns the issues found, or an empty list if the project is consistent.
+    pub async fn verify(self) -> Result<Vec<VerifyIssue>, ProjectVerifyError>
+    where
+        State: project_verify_builder::IsComplete,
+    {
+        do_verify(self._build()).await
+    }
+}
+
+async fn do_verify(args: ProjectVerify<'_>) -> Result<Vec<VerifyIssue>, ProjectVerifyError> {
+    let project = args.project;
+    let project_toml = project.toml().into_local()?;
+    let mut issues = verify_files(project, &project_toml);
+
+    match project.toml().into_remote() {
+        Ok(remote_toml) => {
+            issues.extend(verify_rockspec(project, &remote_toml)?);
+            if args.fetch_source {
+                issues.extend(verify_source(&remote_toml, args.config).await?);
+            }
+        }
+        Err(err) => issues.push(VerifyIssue::Rockspec(err.to_string())),
+    }
+
+    if let Some(lockfile) = project.try_lockfile()? {
+        for (section, lock_type, dependencies) in [
+            (
+                DependencySection::Dependencies,
+                LocalPackageLockType::Regular,
+                project_toml.dependencies(),
+            ),
+            (
+                DependencySection::BuildDependencies,
+                LocalPackageLockType::Build,
+                project_toml.build_dependencies(),
+            ),
+            (
+                DependencySection::TestDependencies,
+                LocalPackageLockType::Test,
+                project_toml.test_dependencies(),
+            ),
+        ] {
+            let sync_spec = lockfile
+                .local_pkg_lock(&lock_type)
+                .package_sync_spec(dependencies.current_platform());
+            issues.extend(sync_spec.to_add.into_iter().map(|dep| {
+                VerifyIssue::UnlockedDependency {
+                    package: dep.package_req().clone(),
+                    section,
+                }
+            }));
+            issues.extend(
+                sync_spec
+                    .to_remove
+                    .into_iter()
+                    .sorted_by_key(|package| package.name().clone())
+                    .map(|package| VerifyIssue::StaleLockedPackage {
+                        package: package.to_package(),
+                        section,
+                    }),
+            );
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Checks that the files and directories the build refers to exist.
+fn verify_files(project: &Project, project_toml: &impl Rockspec) -> Vec<VerifyIssue> {
+    let root = project.root();
+    let exists = |path: &PathBuf| root.join(path).exists();
+    let build = project_toml.build().current_platform();
+    let mut issues = Vec::new();
+    if let Some(BuildBackendSpec::Builtin(spec)) = &build.build_backend {
+        for (module, module_spec) in spec
+            .modules
+            .iter()
+            .sorted_by_key(|(module, _)| module.to_string())
+        {
+            let paths = match module_spec {
+                ModuleSpec::SourcePath(path) => vec![path],
+                ModuleSpec::SourcePaths(paths) => paths.iter().collect_vec(),
+                ModuleSpec::ModulePaths(module_paths) => module_paths.sources.iter().collect_vec(),
+            };
+            issues.extend(paths.into_iter().filter(|path| !exists(path)).map(|path| {
+                VerifyIssue::MissingModuleSource {
+                    module: module.clone(),
+                    path: path.clone(),
+                }
+            }));
+        }
+    }
+    let install = &build.install;
+    issues.extend(
+        install
+            .bin
+            .iter()
+            .chain(install.conf.iter())
+            .map(|(name, path)| (name.clone(), path))
+            .chain(
+                install
+                    .lua
+                    .iter()
+                    .chain(install.lib.iter())
+                    .map(|(module, path)| (module.to_string(), path)),
+            )
+            .filter(|(_, path)| !exists(path))
+            .sorted()
+            .map(|(name, path)| VerifyIssue::MissingInstallFile {
+                name,
+                path: path.clone(),
+            }),
+    );
+    issues.extend(
+        build
+            .copy_directories
+            .iter()
+            .filter(|dir| !root.join(dir).is_dir())
+            .map(|dir| VerifyIssue::MissingCopyDirectory(dir.clone())),
+    );
+    issues
+}
+
+/// Checks that the rockspec generated from the `lux.toml` is valid,
+/// and that a rockspec in the project root, if present, is up to date.
+fn verify_rockspec(
+    project: &Project,
+    remote_toml: &RemoteProjectToml,
+) -> Result<Vec<VerifyIssue>, ProjectVerifyError> {
+    let template_path = project.root().join(ROCKSPEC_TEMPLATE);
+    let rockspec = if template_path.is_file() {
+        let template: RockspecTemplate = std::fs::read_to_string(&template_path)
+            .map_err(|err| ProjectVerifyError::Read(template_path, err))?
+            .parse()?;
+        remote_toml.to_lua_remote_rockspec_string_with_template(&template)
+    } else {
+        remote_toml.to_lua_remote_rockspec_string()
+    };
+    let rockspec = match rockspec {
+        Ok(rockspec) => rockspec,
+        Err(err) => return Ok(vec![VerifyIssue::Rockspec(err.to_string())]),
+    };
+    if let Err(err) = RemoteLuaRockspec::new(&rockspec) {
+        return Ok(vec![VerifyIssue::InvalidRockspec(err.to_string())]);
+    }
+    let file_name = format!(
+        "{}-{}.rockspec",
+        remote_toml.package(),
+        remote_toml.version()
+    );
+    let path = project.root().join(&file_name);
+    if path.is_file() {
+        let existing = std::fs::read_to_string(&path)
+            .map_err(|err| ProjectVerifyError::Read(path.clone(), err))?;
+        if existing != rockspec {
+            return Ok(vec![VerifyIssue::OutdatedRockspec(PathBuf::from(
+                file_name,
+            ))]);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Checks that the source URL can be fetched, without downloading it.
+/// Git sources are not checked.
+async fn verify_source(
+    remote_toml: &RemoteProjectToml,
+    config: &Config,
+) -> Result<Vec<VerifyIssue>, ProjectVerifyError> {
+    let url = match &remote_toml.source().current_platform().source_spec {
+        RockSourceSpec::Url(url) if matches!(url.scheme(), "http" | "https") => url.clone(),
+        _ => return Ok(Vec::new()),
+    };
+    let reason = match config.http_client()?.head(url.clone()).send().await {
+        Ok(response) if response.status().is_success() => return Ok(Vec::new()),
+        Ok(response) => response.status().to_string(),
+        Err(err) => err.to_string(),
+    };
+    Ok(vec![VerifyIssue::UnreachableSource { url, reason }])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ConfigBuilder;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn verify_reports_missing_files_and_outdated_rockspec() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            temp.join("lux.toml"),
+            r#"
+package = "verify"
+version = "0.1.0"
+lua = ">=5.1"
+
+[source]
+url = "https://example.com/verify-0.1.0.tar.gz"
+
+[build]
+type = "builtin"
+
+[build.modules]
+verify = "src/verify.lua"
+"verify.missing" = "src/verify/missing.lua"
+
+[build.install.bin]
+verify = "bin/verify"
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir(temp.join("src")).unwrap();
+        std::fs::write(temp.join("src").join("verify.lua"), "return {}").unwrap();
+        std::fs::write(temp.join("verify-0.1.0-1.rockspec"), "package = 'verify'").unwrap();
+        let project = Project::from_exact(temp.path()).unwrap().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.join("tree")))
+            .build()
+            .unwrap();
+
+        let issues = ProjectVerify::new(&project, &config)
+            .fetch_source(false)
+            .verify()
+            .await
+            .unwrap();
+        assert_eq!(issues.len(), 3, "{issues:#?}");
+        assert!(matches!(
+            &issues[0],
+            VerifyIssue::MissingModuleSource { module, .. } if module.to_string() == "verify.missing"
+        ));
+        assert!(matches!(
+            &issues[1],
+            VerifyIssue::MissingInstallFile { name, .. } if name == "verify"
+        ));
+        assert!(matches!(&issues[2], VerifyIssue::OutdatedRockspec(_)));
+    }
+}
@@ -1,13 +1,15 @@
 use itertools::Itertools;
 use mlua::{ExternalResult, FromLua, IntoLua, LuaSerdeExt};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{de, Deserialize, Deserializer, Serialize};
-use std::{cmp::Ordering, fmt::Display, str::FromStr};
+use std::{borrow::Cow, cmp::Ordering, fmt::Display, str::FromStr};
 use thiserror::Error;
 
 mod outdated;
 mod version;
 
 pub use outdated::*;
+pub(crate) use version::HasModRev;
 pub use version::{
     PackageVersion, PackageVersionParseError, PackageVersionReq, PackageVersionReqError,
     VersionReqToVersionError,
@@ -16,7 +18,6 @@ pub use version::{
 use crate::{
     lockfile::RemotePackageSourceUrl,
     lua_rockspec::{DisplayAsLuaKV, DisplayLuaKV, DisplayLuaValue},
-    package::version::HasModRev,
     remote_package_source::RemotePackageSource,
     rockspec::lua_dependency::LuaDependencySpec,
     variables::{GetVariableError, HasVariables},
@@ -416,6 +417,19 @@ impl<'de> Deserialize<'de> for PackageName {
     }
 }
 
+impl JsonSchema for PackageName {
+    fn schema_name() -> Cow<'static, str> {
+        "PackageName".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "A luarocks package name, e.g. `lua-cjson`",
+        })
+    }
+}
+
 impl FromLua for PackageName {
     fn from_lua(
         value: mlua::prelude::LuaValue,
@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     cmp::{self, Ordering},
     fmt::Display,
     str::FromStr,
@@ -7,6 +8,7 @@ use std::{
 use html_escape::decode_html_entities;
 use itertools::Itertools;
 use mlua::{ExternalResult, FromLua, IntoLua};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use semver::{Comparator, Error, Op, Version, VersionReq};
 use serde::{de, Deserialize, Deserializer, Serialize};
 use thiserror::Error;
@@ -498,6 +500,260 @@ impl PackageVersionReq {
     pub fn is_any(&self) -> bool {
         matches!(self, PackageVersionReq::Any)
     }
+
+    /// Returns the version if this requirement only matches a single
+    /// `major.minor.patch` version, e.g. `==5.1.5`.
+    /// Partial requirements, like `==5.1`, match any patch version, so they return `None`.
+    pub fn exact_version(&self) -> Option<PackageVersion> {
+        match self {
+            PackageVersionReq::SemVer(req) => match req.comparators.as_slice() {
+                [comparator] if comparator.op == Op::Exact && comparator.patch.is_some() => {
+                    PackageVersion::try_from(self.clone()).ok()
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the narrowest SemVer requirement that matches all versions
+    /// matched by either `self` or `other`.
+    /// Returns `None` if the requirements can't be merged,
+    /// e.g. if one of them is not a SemVer requirement.
+    pub fn widen(&self, other: &Self) -> Option<Self> {
+        match (self, other) {
+            (PackageVersionReq::SemVer(req), PackageVersionReq::SemVer(other_req)) => {
+                let range = VersionRange::from_req(req)?.hull(VersionRange::from_req(other_req)?);
+                Some(PackageVersionReq::SemVer(range.into_req()))
+            }
+            _ if self == other => Some(self.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns a requirement that only matches versions matched by both `self` and `other`.
+    /// Returns `None` if no version can match both requirements.
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        match (self, other) {
+            (PackageVersionReq::Any, req) | (req, PackageVersionReq::Any) => Some(req.clone()),
+            (PackageVersionReq::SemVer(req), PackageVersionReq::SemVer(other_req)) => {
+                match VersionRange::from_req(req).zip(VersionRange::from_req(other_req)) {
+                    Some((range, other_range)) => {
+                        let range = range.intersect(other_range);
+                        (!range.is_empty()).then(|| PackageVersionReq::SemVer(range.into_req()))
+                    }
+                    None => Some(PackageVersionReq::SemVer(VersionReq {
+                        comparators: req
+                            .comparators
+                            .iter()
+                            .chain(other_req.comparators.iter())
+                            .cloned()
+                            .collect(),
+                    })),
+                }
+            }
+            _ if self == other => Some(self.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// A bound of a [`VersionRange`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct VersionBound {
+    version: Version,
+    inclusive: bool,
+}
+
+impl VersionBound {
+    fn inclusive(version: Version) -> Self {
+        Self {
+            version,
+            inclusive: true,
+        }
+    }
+
+    fn exclusive(version: Version) -> Self {
+        Self {
+            version,
+            inclusive: false,
+        }
+    }
+}
+
+/// The (convex) range of versions matched by a [`VersionReq`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct VersionRange {
+    lower: Option<VersionBound>,
+    upper: Option<VersionBound>,
+}
+
+impl VersionRange {
+    fn from_req(req: &VersionReq) -> Option<Self> {
+        req.comparators
+            .iter()
+            .try_fold(VersionRange::default(), |range, comparator| {
+                Some(range.intersect(VersionRange::from_comparator(comparator)?))
+            })
+    }
+
+    fn from_comparator(comparator: &Comparator) -> Option<Self> {
+        let major = comparator.major;
+        let version = Version {
+            major,
+            minor: comparator.minor.unwrap_or(0),
+            patch: comparator.patch.unwrap_or(0),
+            pre: comparator.pre.clone(),
+            build: semver::BuildMetadata::EMPTY,
+        };
+        // The first version that is not matched by a partial version, e.g. 1.3.0 for 1.2
+        let next_partial = match (comparator.minor, comparator.patch) {
+            (Some(minor), Some(patch)) => Version::new(major, minor, patch + 1),
+            (Some(minor), None) => Version::new(major, minor + 1, 0),
+            (None, _) => Version::new(major + 1, 0, 0),
+        };
+        let is_full = comparator.patch.is_some();
+        let (lower, upper) = match comparator.op {
+            Op::Exact if is_full => (
+                Some(VersionBound::inclusive(version.clone())),
+                Some(VersionBound::inclusive(version)),
+            ),
+            Op::Exact | Op::Wildcard => (
+                Some(VersionBound::inclusive(version)),
+                Some(VersionBound::exclusive(next_partial)),
+            ),
+            Op::Greater if is_full => (Some(VersionBound::exclusive(version)), None),
+            Op::Greater => (Some(VersionBound::inclusive(next_partial)), None),
+            Op::GreaterEq => (Some(VersionBound::inclusive(version)), None),
+            Op::Less => (None, Some(VersionBound::exclusive(version))),
+            Op::LessEq if is_full => (None, Some(VersionBound::inclusive(version))),
+            Op::LessEq => (None, Some(VersionBound::exclusive(next_partial))),
+            Op::Tilde => {
+                let upper = match comparator.minor {
+                    Some(minor) => Version::new(major, minor + 1, 0),
+                    None => Version::new(major + 1, 0, 0),
+                };
+                (
+                    Some(VersionBound::inclusive(version)),
+                    Some(VersionBound::exclusive(upper)),
+                )
+            }
+            Op::Caret => {
+                let upper = match (major, comparator.minor, comparator.patch) {
+                    (0, Some(0), Some(patch)) => Version::new(0, 0, patch + 1),
+                    (0, Some(minor), _) => Version::new(0, minor + 1, 0),
+                    (major, _, _) => Version::new(major + 1, 0, 0),
+                };
+                (
+                    Some(VersionBound::inclusive(version)),
+                    Some(VersionBound::exclusive(upper)),
+                )
+            }
+            _ => return None,
+        };
+        Some(Self { lower, upper })
+    }
+
+    /// The range of versions matched by both `self` and `other`.
+    fn intersect(self, other: Self) -> Self {
+        Self {
+            lower: pick_bound(self.lower, other.lower, Ordering::Greater),
+            upper: pick_bound(self.upper, other.upper, Ordering::Less),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match (&self.lower, &self.upper) {
+            (Some(lower), Some(upper)) => match lower.version.cmp(&upper.version) {
+                Ordering::Greater => true,
+                Ordering::Equal => !(lower.inclusive && upper.inclusive),
+                Ordering::Less => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// The smallest range containing both `self` and `other`.
+    fn hull(self, other: Self) -> Self {
+        Self {
+            lower: self
+                .lower
+                .zip(other.lower)
+                .map(|(a, b)| widest_bound(a, b, Ordering::Less)),
+            upper: self
+                .upper
+                .zip(other.upper)
+                .map(|(a, b)| widest_bound(a, b, Ordering::Greater)),
+        }
+    }
+
+    fn into_req(self) -> VersionReq {
+        let comparator = |op, version: Version| Comparator {
+            op,
+            major: version.major,
+            minor: Some(version.minor),
+            patch: Some(version.patch),
+            pre: version.pre,
+        };
+        match (self.lower, self.upper) {
+            (Some(lower), Some(upper)) if lower == upper && lower.inclusive => VersionReq {
+                comparators: vec![comparator(Op::Exact, lower.version)],
+            },
+            (None, None) => VersionReq::STAR,
+            (lower, upper) => VersionReq {
+                comparators: lower
+                    .map(|bound| {
+                        let op = if bound.inclusive {
+                            Op::GreaterEq
+                        } else {
+                            Op::Greater
+                        };
+                        comparator(op, bound.version)
+                    })
+                    .into_iter()
+                    .chain(upper.map(|bound| {
+                        let op = if bound.inclusive {
+                            Op::LessEq
+                        } else {
+                            Op::Less
+                        };
+                        comparator(op, bound.version)
+                    }))
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Picks the tighter of two optional bounds, where `tighter` is the ordering of the tighter version.
+fn pick_bound(
+    a: Option<VersionBound>,
+    b: Option<VersionBound>,
+    tighter: Ordering,
+) -> Option<VersionBound> {
+    match (a, b) {
+        (Some(a), Some(b)) => match a.version.cmp(&b.version) {
+            Ordering::Equal => Some(VersionBound {
+                inclusive: a.inclusive && b.inclusive,
+                ..a
+            }),
+            ord if ord == tighter => Some(a),
+            _ => Some(b),
+        },
+        (a, b) => a.or(b),
+    }
+}
+
+/// Picks the wider of two bounds, where `wider` is the ordering of the wider version.
+fn widest_bound(a: VersionBound, b: VersionBound, wider: Ordering) -> VersionBound {
+    match a.version.cmp(&b.version) {
+        Ordering::Equal => VersionBound {
+            inclusive: a.inclusive || b.inclusive,
+            ..a
+        },
+        ord if ord == wider => a,
+        _ => b,
+    }
 }
 
 impl Display for PackageVersionReq {
@@ -530,6 +786,19 @@ impl<'de> Deserialize<'de> for PackageVersionReq {
     }
 }
 
+impl JsonSchema for PackageVersionReq {
+    fn schema_name() -> Cow<'static, str> {
+        "PackageVersionReq".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "A version requirement, e.g. `>=1.0`, `~> 2.1` or `dev`",
+        })
+    }
+}
+
 impl FromStr for PackageVersionReq {
     type Err = PackageVersionReqError;
 
@@ -897,4 +1166,72 @@ mod tests {
             "==a144124839f027a2d0a95791936c478d047126fc"
         );
     }
+
+    #[tokio::test]
+    async fn widen_package_version_req() {
+        let widen = |a: &str, b: &str| {
+            PackageVersionReq::parse(a)
+                .unwrap()
+                .widen(&PackageVersionReq::parse(b).unwrap())
+                .map(|req| req.to_string())
+        };
+        assert_eq!(widen("1.0.0", "1.5.0").unwrap(), ">=1.0.0, <=1.5.0");
+        assert_eq!(widen("1.0.0", "1.0.0").unwrap(), "==1.0.0");
+        assert_eq!(widen(">= 1.0, < 2.0", ">= 2.0").unwrap(), ">=1.0.0");
+        assert_eq!(widen("~> 1.2", "~> 2.1").unwrap(), ">=1.2.0, <2.2.0");
+        assert_eq!(widen("< 1.0", ">= 2.0").unwrap(), "*");
+        assert_eq!(
+            widen(">= 1.0, < 1.0.5", "== 1.0.3").unwrap(),
+            ">=1.0.0, <1.0.5"
+        );
+        assert!(widen("1.0.0", "scm").is_none());
+        assert_eq!(widen("scm", "scm").unwrap(), "==scm");
+
+        let widened = widen("~> 1.2", "~> 2.1").unwrap();
+        let req = PackageVersionReq::parse(&widened).unwrap();
+        assert!(req.matches(&"1.9.0".parse().unwrap()));
+        assert!(!req.matches(&"2.2.0".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn intersect_package_version_req() {
+        let intersect = |a: &str, b: &str| {
+            PackageVersionReq::parse(a)
+                .unwrap()
+                .intersect(&PackageVersionReq::parse(b).unwrap())
+                .map(|req| req.to_string())
+        };
+        assert_eq!(intersect(">= 1.0.0", "< 2.0.0").unwrap(), ">=1.0.0, <2.0.0");
+        assert_eq!(
+            intersect(">= 1.0.0, < 3.0.0", "~> 2.1").unwrap(),
+            ">=2.1.0, <2.2.0"
+        );
+        assert_eq!(intersect("1.5.0", ">= 1.0.0").unwrap(), "==1.5.0");
+        assert!(intersect("< 1.0.0", ">= 2.0.0").is_none());
+        assert!(intersect("< 1.0.0", "> 1.0.0").is_none());
+        assert!(intersect("1.0.0", "scm").is_none());
+        assert_eq!(
+            PackageVersionReq::Any
+                .intersect(&PackageVersionReq::parse("scm").unwrap())
+                .unwrap()
+                .to_string(),
+            "==scm"
+        );
+    }
+
+    #[tokio::test]
+    async fn exact_package_version_req() {
+        let exact = |req: &str| {
+            PackageVersionReq::parse(req)
+                .unwrap()
+                .exact_version()
+                .map(|version| version.to_modrev_string())
+        };
+        assert_eq!(exact("=5.1.5").unwrap(), "5.1.5");
+        assert_eq!(exact("== 5.4.8").unwrap(), "5.4.8");
+        assert!(exact("==5.1").is_none());
+        assert!(exact(">= 5.1.5").is_none());
+        assert!(exact(">= 5.1.5, < 5.2.0").is_none());
+        assert!(exact("scm").is_none());
+    }
 }
@@ -1,7 +1,12 @@
 use itertools::Itertools;
 use path_slash::PathBufExt;
 use serde::Serialize;
-use std::{env, fmt::Display, path::PathBuf, str::FromStr};
+use std::{
+    env,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use thiserror::Error;
 
 use crate::{
@@ -42,16 +47,43 @@ impl Paths {
     }
 
     pub fn new(tree: &Tree) -> Result<Self, PathsError> {
-        let mut paths = tree
+        let mut paths = Self::from_tree(tree)?;
+
+        // Packages installed in the tree take precedence over those in the system tree.
+        if let Some(system_tree) = tree.system_tree() {
+            paths.append(&Self::from_tree(system_tree)?);
+        }
+
+        if let Some(lib_path) = tree.version().lux_lib_dir() {
+            paths.prepend(&Paths {
+                version: tree.version().clone(),
+                src: <_>::default(),
+                bin: <_>::default(),
+                lib: PackagePath(vec![lib_path.join(".so")]),
+            });
+        }
+
+        Ok(paths)
+    }
+
+    fn from_tree(tree: &Tree) -> Result<Self, PathsError> {
+        // Stubs of modules that are provided by more than one package
+        // take precedence over the packages' own modules.
+        let mut default = Self::default(tree);
+        let stubs = tree.stubs();
+        if stubs.is_dir() {
+            default.src.0.push(stubs.join("?.lua"));
+        }
+        let paths = tree
             .list()?
-            .into_iter()
-            .flat_map(|(_, packages)| {
+            .into_values()
+            .flat_map(|packages| {
                 packages
                     .into_iter()
                     .map(|package| tree.installed_rock_layout(&package))
                     .collect_vec()
             })
-            .try_fold(Self::default(tree), |mut paths, package| {
+            .try_fold(default, |mut paths, package| {
                 let package = package?;
                 paths.src.0.push(package.src.join("?.lua"));
                 paths.src.0.push(package.src.join("?").join("init.lua"));
@@ -62,16 +94,6 @@ impl Paths {
                 paths.bin.0.push(package.bin);
                 Ok::<Paths, TreeError>(paths)
             })?;
-
-        if let Some(lib_path) = tree.version().lux_lib_dir() {
-            paths.prepend(&Paths {
-                version: tree.version().clone(),
-                src: <_>::default(),
-                bin: <_>::default(),
-                lib: PackagePath(vec![lib_path.join(".so")]),
-            });
-        }
-
         Ok(paths)
     }
 
@@ -119,11 +141,27 @@ impl Paths {
         path
     }
 
+    /// Append the `package.path` and `package.cpath` entries for a directory
+    /// that contains both Lua and C modules, like OpenResty's `lualib`.
+    pub(crate) fn append_lualib_dir(&mut self, dir: &Path) {
+        self.src.0.push(dir.join("?.lua"));
+        self.src.0.push(dir.join("?").join("init.lua"));
+        self.lib
+            .0
+            .push(dir.join(format!("?.{}", c_dylib_extension())));
+    }
+
     pub fn prepend(&mut self, other: &Self) {
         self.src.prepend(&other.src);
         self.lib.prepend(&other.lib);
         self.bin.prepend(&other.bin);
     }
+
+    fn append(&mut self, other: &Self) {
+        self.src.0.extend(other.src.0.iter().cloned());
+        self.lib.0.extend(other.lib.0.iter().cloned());
+        self.bin.0.extend(other.bin.0.iter().cloned());
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Default, Serialize, Clone)]
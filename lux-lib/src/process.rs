@@ -0,0 +1,572 @@
+//! Run child processes in the foreground, so that they behave as if they had been run directly.
+//!
+//! While waiting for the child, signals that would otherwise terminate lux
+//! (and orphan the child) are forwarded to the child's process group.
+//! On Windows, the child is assigned to a job object, which terminates the child's
+//! process tree if lux exits.
+//!
+//! Build and test processes can also be subject to [`ProcessLimits`].
+//! If a process exceeds its timeout, its whole process tree is killed.
+//! Build processes can also be restricted to an allowlist of inherited environment variables.
+
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    future::Future,
+    io,
+    process::{ExitStatus, Output},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::{Child, Command},
+};
+
+use crate::config::Config;
+
+/// Environment variables that build processes always inherit if their environment is scrubbed,
+/// because compilers and build tools may not run without them.
+const BASE_ENV_ALLOWLIST: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "TERM",
+    "LANG",
+    "LC_*",
+    "TZ",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "SOURCE_DATE_EPOCH",
+    "PKG_CONFIG_PATH",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+    "NIX_*",
+    // Windows
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "PROGRAMDATA",
+    "PROGRAMFILES",
+    "PROGRAMFILES(X86)",
+    "NUMBER_OF_PROCESSORS",
+    "PROCESSOR_ARCHITECTURE",
+    "INCLUDE",
+    "LIB",
+    "LIBPATH",
+    "VCINSTALLDIR",
+    "VSCMD_*",
+];
+
+/// Resource limits for spawned build and test processes.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProcessLimits {
+    timeout: Option<Duration>,
+    /// The maximum memory, in MiB.
+    memory: Option<u64>,
+    cpu_time: Option<Duration>,
+    /// The environment variables that a build process may inherit,
+    /// in addition to the [`BASE_ENV_ALLOWLIST`].
+    /// If `None`, it inherits the whole environment.
+    env_allowlist: Option<Vec<String>>,
+}
+
+impl ProcessLimits {
+    /// The limits for build steps, as configured.
+    pub(crate) fn build(config: &Config) -> Self {
+        Self {
+            timeout: config.build_timeout().cloned(),
+            memory: config.process_memory_limit(),
+            cpu_time: config.process_cpu_time_limit().cloned(),
+            env_allowlist: config.build_env_allowlist().cloned(),
+        }
+    }
+
+    /// The limits for a test run, with the given `timeout`.
+    /// Test runs inherit the whole environment.
+    pub(crate) fn test(config: &Config, timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            env_allowlist: None,
+            ..Self::build(config)
+        }
+    }
+
+    fn deadline(&self) -> impl Future<Output = ()> {
+        let timeout = self.timeout;
+        async move {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        }
+    }
+}
+
+/// A child process spawned with [`spawn`].
+pub(crate) struct LimitedChild {
+    child: Child,
+    timeout: Option<Duration>,
+    #[cfg(windows)]
+    _job: sys::Job,
+}
+
+impl LimitedChild {
+    /// Wait for the child to exit and collect its output,
+    /// passing each line of its piped stdout and stderr to `on_line` as soon as it is written.
+    /// If the child exceeds its timeout, its process tree is killed,
+    /// and an error naming the `phase` that timed out is returned.
+    pub(crate) async fn wait_with_output_streamed<F>(
+        mut self,
+        phase: &str,
+        on_line: F,
+    ) -> io::Result<Output>
+    where
+        F: Fn(&[u8]),
+    {
+        let stdout = self.child.stdout.take();
+        let stderr = self.child.stderr.take();
+        #[cfg(unix)]
+        let pid = self.child.id();
+        let child = &mut self.child;
+        let output = async {
+            let (stdout, stderr, status) = tokio::try_join!(
+                read_lines(stdout, &on_line),
+                read_lines(stderr, &on_line),
+                child.wait()
+            )?;
+            Ok(Output {
+                status,
+                stdout,
+                stderr,
+            })
+        };
+        let Some(timeout) = self.timeout else {
+            return output.await;
+        };
+        match tokio::time::timeout(timeout, output).await {
+            Ok(output) => output,
+            Err(_) => {
+                // The child itself is killed on drop.
+                // On Windows, dropping the job kills the rest of the tree.
+                #[cfg(unix)]
+                if let Some(pid) = pid {
+                    sys::kill_process_group(pid, libc::SIGKILL);
+                }
+                Err(timed_out(phase, timeout))
+            }
+        }
+    }
+}
+
+/// Read `reader` to the end, passing each line to `on_line`.
+async fn read_lines<R, F>(reader: Option<R>, on_line: &F) -> io::Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+    F: Fn(&[u8]),
+{
+    let mut output = Vec::new();
+    if let Some(reader) = reader {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line).await? > 0 {
+            on_line(&line);
+            output.append(&mut line);
+        }
+    }
+    Ok(output)
+}
+
+/// Spawn a build process, subject to `limits`.
+/// Unlike [`status`], this does not forward signals,
+/// as build processes are not expected to be interactive.
+pub(crate) fn spawn(command: &mut Command, limits: &ProcessLimits) -> io::Result<LimitedChild> {
+    command.kill_on_drop(true);
+    if let Some(allowlist) = &limits.env_allowlist {
+        scrub_env(command, allowlist);
+    }
+    #[cfg(unix)]
+    sys::apply_limits(command, limits, limits.timeout.is_some());
+    let child = command.spawn()?;
+    #[cfg(windows)]
+    let job = sys::Job::new(limits).and_then(|job| job.assign(&child).map(|_| job))?;
+    Ok(LimitedChild {
+        child,
+        timeout: limits.timeout,
+        #[cfg(windows)]
+        _job: job,
+    })
+}
+
+/// Spawn `command` and wait for it to finish, forwarding termination signals to it.
+/// If the child exceeds the `limits`' timeout, its process tree is killed,
+/// and an error naming the `phase` that timed out is returned.
+pub(crate) async fn status(
+    command: &mut Command,
+    limits: &ProcessLimits,
+    phase: &str,
+) -> io::Result<ExitStatus> {
+    // Kill the child if we are cancelled while waiting for it.
+    command.kill_on_drop(true);
+    sys::status(command, limits).await.and_then(|status| {
+        status.ok_or_else(|| timed_out(phase, limits.timeout.unwrap_or_default()))
+    })
+}
+
+/// Remove the inherited environment variables that are not allowlisted from the `command`'s
+/// environment. Variables that were set explicitly on the `command` are kept.
+fn scrub_env(command: &mut Command, allowlist: &[String]) {
+    let explicit: HashSet<OsString> = command
+        .as_std()
+        .get_envs()
+        .map(|(name, _)| name.to_os_string())
+        .collect();
+    for (name, _) in std::env::vars_os() {
+        if !explicit.contains(&name) && !is_env_allowed(&name, allowlist) {
+            command.env_remove(name);
+        }
+    }
+}
+
+fn is_env_allowed(name: &OsStr, allowlist: &[String]) -> bool {
+    let Some(name) = name.to_str() else {
+        return false;
+    };
+    BASE_ENV_ALLOWLIST
+        .iter()
+        .copied()
+        .chain(allowlist.iter().map(String::as_str))
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => env_name_matches(name.get(..prefix.len()), prefix),
+            None => env_name_matches(Some(name), pattern),
+        })
+}
+
+/// Environment variable names are case-insensitive on Windows.
+fn env_name_matches(name: Option<&str>, pattern: &str) -> bool {
+    name.is_some_and(|name| {
+        if cfg!(windows) {
+            name.eq_ignore_ascii_case(pattern)
+        } else {
+            name == pattern
+        }
+    })
+}
+
+/// The exit code to propagate for a child's exit status.
+/// On Unix, a child that was terminated by a signal is reported as `128 + signal`,
+/// like shells do.
+pub fn exit_code(status: &ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status
+            .code()
+            .or_else(|| status.signal().map(|signal| 128 + signal))
+    }
+    #[cfg(not(unix))]
+    {
+        status.code()
+    }
+}
+
+fn timed_out(phase: &str, timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("{phase} timed out after {timeout:?}"),
+    )
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::{
+        io::{self, IsTerminal},
+        process::ExitStatus,
+    };
+
+    use tokio::{
+        process::Command,
+        signal::unix::{signal, SignalKind},
+    };
+
+    use super::ProcessLimits;
+
+    /// Returns `None` if the process timed out.
+    pub(super) async fn status(
+        command: &mut Command,
+        limits: &ProcessLimits,
+    ) -> io::Result<Option<ExitStatus>> {
+        // If we are attached to a terminal, the child stays in our (foreground) process group,
+        // so that it can read from the terminal. The terminal then delivers SIGINT to the
+        // whole group, including the child, and we only need to stay alive until it exits.
+        // Otherwise, or if we may have to kill it, the child gets its own process group,
+        // so that signals reach any processes it spawns.
+        let own_process_group = !io::stdin().is_terminal() || limits.timeout.is_some();
+        apply_limits(command, limits, own_process_group);
+        let mut sigint = signal(SignalKind::interrupt())?;
+        let mut sigterm = signal(SignalKind::terminate())?;
+        let mut child = command.spawn()?;
+        let Some(pid) = child.id() else {
+            return child.wait().await.map(Some);
+        };
+        let deadline = limits.deadline();
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                status = child.wait() => return status.map(Some),
+                _ = sigint.recv() => {
+                    if own_process_group {
+                        kill_process_group(pid, libc::SIGINT);
+                    }
+                }
+                _ = sigterm.recv() => {
+                    if own_process_group {
+                        kill_process_group(pid, libc::SIGTERM);
+                    } else {
+                        // SAFETY: This only sends a signal. If the child has already exited,
+                        // it fails with ESRCH, which we can ignore, as `wait` will return.
+                        unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+                    }
+                }
+                _ = &mut deadline => {
+                    kill_process_group(pid, libc::SIGKILL);
+                    child.wait().await?;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    pub(super) fn kill_process_group(pid: u32, signal: libc::c_int) {
+        // SAFETY: This only sends a signal. If the process group no longer exists,
+        // it fails with ESRCH, which we can ignore.
+        unsafe { libc::killpg(pid as libc::pid_t, signal) };
+    }
+
+    pub(super) fn apply_limits(
+        command: &mut Command,
+        limits: &ProcessLimits,
+        own_process_group: bool,
+    ) {
+        if own_process_group {
+            command.process_group(0);
+        }
+        let memory = limits.memory.map(|mib| mib.saturating_mul(1024 * 1024));
+        let cpu_time = limits.cpu_time.map(|cpu_time| cpu_time.as_secs().max(1));
+        if memory.is_none() && cpu_time.is_none() {
+            return;
+        }
+        // SAFETY: `setrlimit` is async-signal-safe and the closure does not allocate.
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(memory) = memory {
+                    let limit = libc::rlimit {
+                        rlim_cur: memory as libc::rlim_t,
+                        rlim_max: memory as libc::rlim_t,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                if let Some(cpu_time) = cpu_time {
+                    let limit = libc::rlimit {
+                        rlim_cur: cpu_time as libc::rlim_t,
+                        rlim_max: cpu_time as libc::rlim_t,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::{io, mem, process::ExitStatus, ptr};
+
+    use tokio::process::{Child, Command};
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        System::JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+            SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+            JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOB_OBJECT_LIMIT_PROCESS_MEMORY,
+            JOB_OBJECT_LIMIT_PROCESS_TIME,
+        },
+    };
+
+    use super::ProcessLimits;
+
+    /// Returns `None` if the process timed out.
+    pub(super) async fn status(
+        command: &mut Command,
+        limits: &ProcessLimits,
+    ) -> io::Result<Option<ExitStatus>> {
+        let mut child = command.spawn()?;
+        // The job is closed when it is dropped, which terminates the child's process tree
+        // if we exit before it does.
+        let job = Job::new(limits).and_then(|job| job.assign(&child).map(|_| job))?;
+        let deadline = limits.deadline();
+        tokio::pin!(deadline);
+        loop {
+            // Ctrl+C is delivered to every process attached to the console, including the child,
+            // so we only need to stay alive until it exits.
+            tokio::select! {
+                status = child.wait() => return status.map(Some),
+                _ = tokio::signal::ctrl_c() => {}
+                _ = &mut deadline => {
+                    drop(job);
+                    child.wait().await?;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    pub(super) struct Job(HANDLE);
+
+    impl Job {
+        pub(super) fn new(limits: &ProcessLimits) -> io::Result<Self> {
+            // SAFETY: A null name and security attributes create an anonymous job object
+            // with the default security descriptor.
+            let handle = unsafe { CreateJobObjectW(ptr::null(), ptr::null()) };
+            if handle.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let job = Self(handle);
+            // SAFETY: JOBOBJECT_EXTENDED_LIMIT_INFORMATION is a plain C struct,
+            // for which all zeroes is a valid value.
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { mem::zeroed() };
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            if let Some(memory) = limits.memory {
+                info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+                info.ProcessMemoryLimit = memory.saturating_mul(1024 * 1024) as usize;
+            }
+            if let Some(cpu_time) = limits.cpu_time {
+                info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_TIME;
+                // In 100-nanosecond ticks
+                info.BasicLimitInformation.PerProcessUserTimeLimit =
+                    (cpu_time.as_nanos() / 100).min(i64::MAX as u128) as i64;
+            }
+            // SAFETY: `info` is valid for the given size and information class.
+            let ok = unsafe {
+                SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const _,
+                    mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(job)
+        }
+
+        pub(super) fn assign(&self, child: &Child) -> io::Result<()> {
+            let Some(process) = child.raw_handle() else {
+                // The child has already exited.
+                return Ok(());
+            };
+            // SAFETY: Both handles are valid for the duration of the call.
+            if unsafe { AssignProcessToJobObject(self.0, process as HANDLE) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // SAFETY: The handle was created by `CreateJobObjectW` and is only closed here.
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn propagate_exit_code() {
+        let limits = ProcessLimits::default();
+        let exit_status = status(Command::new("sh").arg("-c").arg("exit 3"), &limits, "sh")
+            .await
+            .unwrap();
+        assert_eq!(exit_code(&exit_status), Some(3));
+        let exit_status = status(
+            Command::new("sh").arg("-c").arg("kill -TERM $$"),
+            &limits,
+            "sh",
+        )
+        .await
+        .unwrap();
+        assert_eq!(exit_code(&exit_status), Some(128 + libc::SIGTERM));
+    }
+
+    #[tokio::test]
+    async fn kill_process_tree_on_timeout() {
+        let limits = ProcessLimits {
+            timeout: Some(Duration::from_millis(200)),
+            ..ProcessLimits::default()
+        };
+        let err = status(
+            Command::new("sh").arg("-c").arg("sleep 10 & wait"),
+            &limits,
+            "tests",
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(err.to_string(), "tests timed out after 200ms");
+        let err = spawn(Command::new("sh").arg("-c").arg("sleep 10"), &limits)
+            .unwrap()
+            .wait_with_output_streamed("make build", |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn scrub_build_env() {
+        std::env::set_var("LUX_TEST_SCRUB_SECRET", "secret");
+        std::env::set_var("LUX_TEST_SCRUB_ALLOWED", "allowed");
+        let limits = ProcessLimits {
+            env_allowlist: Some(vec!["LUX_TEST_SCRUB_ALLOW*".into()]),
+            ..ProcessLimits::default()
+        };
+        let output = spawn(
+            Command::new("sh")
+                .arg("-c")
+                .arg("echo \"$LUX_TEST_SCRUB_SECRET:$LUX_TEST_SCRUB_ALLOWED:$LUX_TEST_SCRUB_EXPLICIT\"")
+                .env("LUX_TEST_SCRUB_EXPLICIT", "explicit")
+                .stdout(std::process::Stdio::piped()),
+            &limits,
+        )
+        .unwrap()
+        .wait_with_output_streamed("sh", |_| {})
+        .await
+        .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            ":allowed:explicit"
+        );
+        assert!(is_env_allowed(OsStr::new("PATH"), &[]));
+        assert!(is_env_allowed(OsStr::new("LC_ALL"), &[]));
+        assert!(!is_env_allowed(OsStr::new("AWS_SECRET_ACCESS_KEY"), &[]));
+    }
+}
@@ -1,4 +1,11 @@
-use std::{borrow::Cow, sync::Arc, time::Duration};
+use std::{
+    borrow::Cow,
+    io::IsTerminal,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use indicatif::ProgressDrawTarget;
 
 mod private {
     pub trait HasProgress {}
@@ -34,13 +41,57 @@ where
     }
 }
 
+/// How progress is reported to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgressMode {
+    /// Animated progress bars.
+    #[default]
+    Interactive,
+    /// Plain log lines on stderr, e.g. when the output is not a terminal.
+    Plain,
+    /// No progress or status messages.
+    Quiet,
+}
+
+static PROGRESS_MODE: OnceLock<ProgressMode> = OnceLock::new();
+
+impl ProgressMode {
+    /// `Quiet` if `quiet` is set, `Plain` if stdout or stderr is not a terminal,
+    /// and `Interactive` otherwise.
+    pub fn detect(quiet: bool) -> Self {
+        if quiet {
+            Self::Quiet
+        } else if std::io::stdout().is_terminal() && std::io::stderr().is_terminal() {
+            Self::Interactive
+        } else {
+            Self::Plain
+        }
+    }
+
+    /// Use this mode for all progress bars created by this process.
+    /// Has no effect if a mode has already been set.
+    pub fn init(self) {
+        let _ = PROGRESS_MODE.set(self);
+    }
+
+    /// The mode set with [`ProgressMode::init`], or `Interactive` if none was set.
+    pub fn current() -> Self {
+        PROGRESS_MODE.get().copied().unwrap_or_default()
+    }
+}
+
 // WARNING: Don't implement `Clone` for this.
 pub struct MultiProgress(indicatif::MultiProgress);
 pub struct ProgressBar(indicatif::ProgressBar);
 
 impl MultiProgress {
     pub fn new() -> Self {
-        Self(indicatif::MultiProgress::new())
+        match ProgressMode::current() {
+            ProgressMode::Interactive => Self(indicatif::MultiProgress::new()),
+            ProgressMode::Plain | ProgressMode::Quiet => Self(
+                indicatif::MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+            ),
+        }
     }
 
     pub fn new_arc() -> Arc<Progress<MultiProgress>> {
@@ -71,11 +122,15 @@ impl Default for MultiProgress {
 
 impl ProgressBar {
     pub fn new() -> Self {
-        let bar =
-            indicatif::ProgressBar::new_spinner().with_finish(indicatif::ProgressFinish::AndClear);
-        bar.enable_steady_tick(Duration::from_millis(100));
-
-        Self(bar)
+        match ProgressMode::current() {
+            ProgressMode::Interactive => {
+                let bar = indicatif::ProgressBar::new_spinner()
+                    .with_finish(indicatif::ProgressFinish::AndClear);
+                bar.enable_steady_tick(Duration::from_millis(100));
+                Self(bar)
+            }
+            ProgressMode::Plain | ProgressMode::Quiet => Self(indicatif::ProgressBar::hidden()),
+        }
     }
 
     pub fn into_raw(self) -> indicatif::ProgressBar {
@@ -86,6 +141,8 @@ impl ProgressBar {
     where
         M: Into<Cow<'static, str>>,
     {
+        let message = message.into();
+        log_plain(&message);
         self.0.set_message(message)
     }
 
@@ -101,13 +158,19 @@ impl ProgressBar {
     where
         M: AsRef<str>,
     {
-        self.0.println(message)
+        match ProgressMode::current() {
+            ProgressMode::Interactive => self.0.println(message),
+            ProgressMode::Plain => eprintln!("{}", message.as_ref()),
+            ProgressMode::Quiet => {}
+        }
     }
 
     pub fn finish_with_message<M>(&self, message: M)
     where
         M: Into<Cow<'static, str>>,
     {
+        let message = message.into();
+        log_plain(&message);
         self.0.finish_with_message(message)
     }
 
@@ -124,10 +187,19 @@ impl Default for ProgressBar {
 
 impl From<String> for ProgressBar {
     fn from(message: String) -> Self {
+        log_plain(&message);
         Self(Self::new().0.with_message(message))
     }
 }
 
+/// In `Plain` mode, status messages are printed as log lines,
+/// as there are no progress bars to display them.
+fn log_plain(message: &str) {
+    if ProgressMode::current() == ProgressMode::Plain && !message.trim().is_empty() {
+        eprintln!("{}", message.trim());
+    }
+}
+
 impl From<u64> for ProgressBar {
     fn from(position: u64) -> Self {
         let new = Self::new();
@@ -0,0 +1,256 @@
+use std::{borrow::Cow, collections::HashMap, fmt::Display, io, path::PathBuf};
+
+use itertools::Itertools;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    config::Config,
+    package::{PackageName, PackageVersionReq},
+    rockspec::lua_dependency::LuaDependencySpec,
+};
+
+use super::ProjectRoot;
+
+#[derive(Error, Debug)]
+pub enum ConstraintCatalogError {
+    #[error("failed to read the constraints file {0}:\n{1}")]
+    Io(PathBuf, io::Error),
+    #[error("failed to fetch the constraints file from {0}:\n{1}")]
+    Request(Url, reqwest::Error),
+    #[error("error parsing the constraints file {0}:\n{1}")]
+    Toml(String, toml::de::Error),
+    #[error("the requirement {name}{requirement} conflicts with the constraint {name}{constraint} from {catalog}")]
+    Conflict {
+        name: PackageName,
+        requirement: PackageVersionReq,
+        constraint: PackageVersionReq,
+        catalog: String,
+    },
+}
+
+/// Where to load a constraint catalog from,
+/// as specified by the `constraints` field of the lux.toml.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintCatalogSource {
+    Url(Url),
+    /// A path, relative to the project root
+    Path(PathBuf),
+}
+
+impl<'de> Deserialize<'de> for ConstraintCatalogSource {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let source = String::deserialize(deserializer)?;
+        Ok(match Url::parse(&source) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Self::Url(url),
+            _ => Self::Path(source.into()),
+        })
+    }
+}
+
+impl JsonSchema for ConstraintCatalogSource {
+    fn schema_name() -> Cow<'static, str> {
+        "ConstraintCatalogSource".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "A URL or a path, relative to the project root",
+        })
+    }
+}
+
+impl Display for ConstraintCatalogSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Url(url) => url.fmt(f),
+            Self::Path(path) => path.display().fmt(f),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ConstraintCatalogFile {
+    #[serde(default)]
+    constraints: HashMap<PackageName, PackageVersionReq>,
+}
+
+/// A catalog of version constraints that can be shared across projects, e.g. by an organization.
+/// Each project dependency's version requirement is intersected with its constraint in the catalog.
+///
+/// ```toml
+/// [constraints]
+/// busted = ">= 2.0.0, < 3.0.0"
+/// lua-cjson = "2.1.0"
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConstraintCatalog {
+    source: String,
+    constraints: HashMap<PackageName, PackageVersionReq>,
+}
+
+/// A dependency whose version requirement was constrained by a [`ConstraintCatalog`].
+#[derive(Debug, Clone)]
+pub struct CatalogConstraint {
+    name: PackageName,
+    requirement: PackageVersionReq,
+    constraint: PackageVersionReq,
+    resolved: PackageVersionReq,
+}
+
+impl CatalogConstraint {
+    pub fn name(&self) -> &PackageName {
+        &self.name
+    }
+
+    /// The version requirement of the project.
+    pub fn requirement(&self) -> &PackageVersionReq {
+        &self.requirement
+    }
+
+    /// The constraint from the catalog.
+    pub fn constraint(&self) -> &PackageVersionReq {
+        &self.constraint
+    }
+
+    /// The intersection of the requirement and the constraint.
+    pub fn resolved(&self) -> &PackageVersionReq {
+        &self.resolved
+    }
+}
+
+impl ConstraintCatalog {
+    pub(crate) async fn load(
+        source: &ConstraintCatalogSource,
+        project_root: &ProjectRoot,
+        config: &Config,
+    ) -> Result<Self, ConstraintCatalogError> {
+        let content = match source {
+            ConstraintCatalogSource::Url(url) => async {
+                config
+                    .http_client()?
+                    .get(url.clone())
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await
+            }
+            .await
+            .map_err(|err| ConstraintCatalogError::Request(url.clone(), err))?,
+            ConstraintCatalogSource::Path(path) => {
+                let path = project_root.join(path);
+                tokio::fs::read_to_string(&path)
+                    .await
+                    .map_err(|err| ConstraintCatalogError::Io(path, err))?
+            }
+        };
+        Self::parse(source.to_string(), &content)
+    }
+
+    pub fn parse(source: String, content: &str) -> Result<Self, ConstraintCatalogError> {
+        let file: ConstraintCatalogFile = toml::from_str(content)
+            .map_err(|err| ConstraintCatalogError::Toml(source.clone(), err))?;
+        Ok(Self {
+            source,
+            constraints: file.constraints,
+        })
+    }
+
+    /// The URL or path the catalog was loaded from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn get(&self, name: &PackageName) -> Option<&PackageVersionReq> {
+        self.constraints.get(name)
+    }
+
+    /// Intersect the version requirements of `dependencies` with the catalog's constraints.
+    /// Returns the constrained dependencies, along with the constraints that were applied.
+    pub fn apply(
+        &self,
+        dependencies: Vec<LuaDependencySpec>,
+    ) -> Result<(Vec<LuaDependencySpec>, Vec<CatalogConstraint>), ConstraintCatalogError> {
+        let mut applied = Vec::new();
+        let dependencies = dependencies
+            .into_iter()
+            .map(|mut dep| {
+                let Some(constraint) = self.get(dep.name()) else {
+                    return Ok(dep);
+                };
+                let requirement = dep.version_req().clone();
+                let resolved = requirement.intersect(constraint).ok_or_else(|| {
+                    ConstraintCatalogError::Conflict {
+                        name: dep.name().clone(),
+                        requirement: requirement.clone(),
+                        constraint: constraint.clone(),
+                        catalog: self.source.clone(),
+                    }
+                })?;
+                if resolved != requirement {
+                    dep.package_req.version_req = resolved.clone();
+                    applied.push(CatalogConstraint {
+                        name: dep.name().clone(),
+                        requirement,
+                        constraint: constraint.clone(),
+                        resolved,
+                    });
+                }
+                Ok(dep)
+            })
+            .try_collect()?;
+        Ok((dependencies, applied))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::package::PackageReq;
+
+    use super::*;
+
+    #[test]
+    fn apply_constraint_catalog() {
+        let catalog = ConstraintCatalog::parse(
+            "lux-constraints.toml".into(),
+            r#"
+            [constraints]
+            busted = ">= 2.0.0, < 3.0.0"
+            lua-cjson = "2.1.0"
+            "#,
+        )
+        .unwrap();
+        let dependencies = vec![
+            PackageReq::new("busted".into(), Some(">= 1.0.0".into()))
+                .unwrap()
+                .into(),
+            PackageReq::new("lua-cjson".into(), Some("2.1.0".into()))
+                .unwrap()
+                .into(),
+            PackageReq::new("penlight".into(), None).unwrap().into(),
+        ];
+        let (dependencies, applied): (Vec<LuaDependencySpec>, _) =
+            catalog.apply(dependencies).unwrap();
+        assert_eq!(dependencies[0].version_req().to_string(), ">=2.0.0, <3.0.0");
+        assert_eq!(dependencies[1].version_req().to_string(), "==2.1.0");
+        assert!(dependencies[2].version_req().is_any());
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].name(), &"busted".into());
+        assert_eq!(applied[0].requirement().to_string(), ">=1.0.0");
+
+        let dependencies = vec![PackageReq::new("lua-cjson".into(), Some("2.0.0".into()))
+            .unwrap()
+            .into()];
+        assert!(matches!(
+            catalog.apply(dependencies),
+            Err(ConstraintCatalogError::Conflict { .. })
+        ));
+    }
+}
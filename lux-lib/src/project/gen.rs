@@ -1,10 +1,12 @@
 use std::{
+    borrow::Cow,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
 use ::serde::Deserialize;
 use git2::Repository;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::Serialize;
 use thiserror::Error;
 
@@ -16,7 +18,7 @@ use crate::{
 
 use super::ProjectRoot;
 
-#[derive(Debug, PartialEq, Deserialize, Serialize, Clone, Default)]
+#[derive(Debug, PartialEq, Deserialize, JsonSchema, Serialize, Clone, Default)]
 /// Template for generating a remote rockspec source
 ///
 /// Variables that can be substituted in each of the fields:
@@ -180,6 +182,19 @@ impl RockSourceTemplate {
 #[derive(Debug, PartialEq, Deserialize, Serialize, Clone, Default)]
 pub(crate) struct PackageVersionTemplate(Option<PackageVersion>);
 
+impl JsonSchema for PackageVersionTemplate {
+    fn schema_name() -> Cow<'static, str> {
+        "PackageVersionTemplate".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "The package version. If not set, it is generated from the git tags",
+        })
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum GenerateVersionError {
     #[error("error generating version from git repository metadata:\n{0}")]
@@ -0,0 +1,106 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::{config::LuaVersion, package::PackageVersion};
+
+/// The file in which other Lua version managers record a project's Lua version.
+pub const LUA_VERSION_FILE: &str = ".lua-version";
+
+#[derive(Error, Debug)]
+pub enum LuaVersionFileError {
+    #[error("error reading {0}:\n{1}")]
+    Io(PathBuf, io::Error),
+    #[error("{0}: unsupported Lua version '{1}'. Expected e.g. '5.4', '5.1.5' or 'luajit'.")]
+    UnsupportedLuaVersion(PathBuf, String),
+}
+
+/// Read the Lua version from the `.lua-version` file in `root`, if there is one.
+pub(crate) fn read_lua_version_file(
+    root: &Path,
+) -> Result<Option<LuaVersion>, LuaVersionFileError> {
+    let path = root.join(LUA_VERSION_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content =
+        std::fs::read_to_string(&path).map_err(|err| LuaVersionFileError::Io(path.clone(), err))?;
+    let version = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or_default();
+    parse_lua_version(version)
+        .map(Some)
+        .ok_or_else(|| LuaVersionFileError::UnsupportedLuaVersion(path, version.to_string()))
+}
+
+/// Parse the version formats that Lua version managers use,
+/// e.g. `5.4`, `5.1.5`, `lua-5.4.6`, `luajit` or `luajit-2.1.0-beta3`.
+fn parse_lua_version(version: &str) -> Option<LuaVersion> {
+    let version = version.to_lowercase();
+    if let Some(luajit_version) = version.strip_prefix("luajit") {
+        return Some(match luajit_version.trim_start_matches(['-', '@']) {
+            "52" | "5.2" => LuaVersion::LuaJIT52,
+            _ => LuaVersion::LuaJIT,
+        });
+    }
+    let version = version
+        .strip_prefix("lua")
+        .map_or(version.as_str(), |version| {
+            version.trim_start_matches(['-', '@'])
+        });
+    LuaVersion::from_str(version).ok().or_else(|| {
+        PackageVersion::parse(version)
+            .ok()
+            .and_then(|version| LuaVersion::from_version(version).ok())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+
+    use super::*;
+
+    #[test]
+    fn parse_lua_version_formats() {
+        for (version, expected) in [
+            ("5.1", Some(LuaVersion::Lua51)),
+            ("5.4.6", Some(LuaVersion::Lua54)),
+            ("lua-5.3.6", Some(LuaVersion::Lua53)),
+            ("lua5.2", Some(LuaVersion::Lua52)),
+            ("luajit", Some(LuaVersion::LuaJIT)),
+            ("LuaJIT-2.1.0-beta3", Some(LuaVersion::LuaJIT)),
+            ("jit52", Some(LuaVersion::LuaJIT52)),
+            ("5.5", None),
+            ("system", None),
+        ] {
+            assert_eq!(parse_lua_version(version), expected, "{version}");
+        }
+    }
+
+    #[test]
+    fn read_lua_version_files() {
+        let root = assert_fs::TempDir::new().unwrap();
+        assert!(read_lua_version_file(root.path()).unwrap().is_none());
+
+        let file = root.child(LUA_VERSION_FILE);
+        file.write_str("# managed by hererocks\n\n 5.1.5 \n")
+            .unwrap();
+        assert_eq!(
+            read_lua_version_file(root.path()).unwrap(),
+            Some(LuaVersion::Lua51)
+        );
+
+        file.write_str("system\n").unwrap();
+        assert!(matches!(
+            read_lua_version_file(root.path()),
+            Err(LuaVersionFileError::UnsupportedLuaVersion(_, version)) if version == "system"
+        ));
+    }
+}
@@ -1,18 +1,21 @@
+use constraints::{ConstraintCatalog, ConstraintCatalogError};
 use itertools::Itertools;
 use lets_find_up::{find_up_with, FindUpKind, FindUpOptions};
+use lua_version_file::read_lua_version_file;
 use mlua::{ExternalResult, UserData};
 use path_slash::PathBufExt;
 use project_toml::{
     LocalProjectTomlValidationError, PartialProjectToml, RemoteProjectTomlValidationError,
 };
 use std::{
+    fmt::Display,
     io,
     ops::Deref,
     path::{Path, PathBuf},
     str::FromStr,
 };
 use thiserror::Error;
-use toml_edit::{DocumentMut, Item};
+use toml_edit::{DocumentMut, InlineTable, Item, Value};
 
 use crate::{
     build,
@@ -20,6 +23,7 @@ use crate::{
     git::{self, shorthand::GitUrlShorthand, utils::GitError},
     lockfile::{LockfileError, ProjectLockfile, ReadOnly},
     lua::lua_runtime,
+    lua_installation::{LuaBinary, LuaInstallation},
     lua_rockspec::{
         LocalLuaRockspec, LuaRockspecError, LuaVersionError, PartialLuaRockspec,
         PartialRockspecError, RemoteLuaRockspec,
@@ -30,20 +34,28 @@ use crate::{
         lua_dependency::{DependencyType, LuaDependencySpec, LuaDependencyType},
         LuaVersionCompatibility,
     },
-    tree::{Tree, TreeError},
+    tree::{RockLayout, Tree, TreeError},
 };
 use crate::{
     lockfile::PinnedState,
-    package::{PackageName, PackageReq},
+    package::{PackageName, PackageReq, PackageVersionReq},
 };
 
+pub mod constraints;
 pub(crate) mod gen;
+mod lua_version_file;
 pub mod project_toml;
+pub mod rockspec_template;
+mod substitution;
+mod unknown_fields;
 
+pub use lua_version_file::{LuaVersionFileError, LUA_VERSION_FILE};
 pub use project_toml::PROJECT_TOML;
+pub use unknown_fields::UnknownField;
 
 pub const EXTRA_ROCKSPEC: &str = "extra.rockspec";
 pub(crate) const LUX_DIR_NAME: &str = ".lux";
+const PROFILES_DIR_NAME: &str = "profiles";
 const LUARC: &str = ".luarc.json";
 const EMMYRC: &str = ".emmyrc.json";
 
@@ -92,6 +104,62 @@ pub enum ProjectEditError {
     ExpectedString(toml_edit::Value),
     #[error(transparent)]
     GitUrlShorthandParse(#[from] git::shorthand::ParseError),
+    #[error("aborted adding dependencies, because {0}")]
+    DependencyConflict(DependencyConflict),
+    #[error("cannot widen the version requirements, because {0}")]
+    IncompatibleVersionReqs(DependencyConflict),
+}
+
+/// A dependency that is already in the lux.toml, with a different version requirement
+/// than the one that is being added.
+#[derive(Debug, Clone)]
+pub struct DependencyConflict {
+    name: PackageName,
+    existing: PackageVersionReq,
+    requested: PackageVersionReq,
+}
+
+impl DependencyConflict {
+    pub fn name(&self) -> &PackageName {
+        &self.name
+    }
+
+    /// The version requirement that is currently in the lux.toml.
+    pub fn existing(&self) -> &PackageVersionReq {
+        &self.existing
+    }
+
+    /// The version requirement that is being added.
+    pub fn requested(&self) -> &PackageVersionReq {
+        &self.requested
+    }
+
+    /// A version requirement that matches both the existing and the requested versions,
+    /// if they can be merged.
+    pub fn widened(&self) -> Option<PackageVersionReq> {
+        self.existing.widen(&self.requested)
+    }
+}
+
+impl Display for DependencyConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` is already a dependency with version requirement `{}`, but `{}` was requested",
+            self.name, self.existing, self.requested
+        )
+    }
+}
+
+/// How to resolve a [`DependencyConflict`] when adding dependencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyConflictResolution {
+    /// Widen the existing version requirement, so that it also matches the requested versions.
+    Widen,
+    /// Replace the existing version requirement with the requested one.
+    Replace,
+    /// Abort without editing the lux.toml.
+    Abort,
 }
 
 #[derive(Error, Debug)]
@@ -194,7 +262,9 @@ impl UserData for Project {
                 let package_db = RemotePackageDB::from_config(&config, &Progress::NoProgress)
                     .await
                     .into_lua_err()?;
-                this.add(deps, &package_db).await.into_lua_err()
+                this.add(deps, &package_db, |_| DependencyConflictResolution::Replace)
+                    .await
+                    .into_lua_err()
             },
         );
 
@@ -319,6 +389,13 @@ impl Project {
         self.root.join(PROJECT_TOML)
     }
 
+    /// The fields in the `lux.toml` that lux doesn't know, e.g. misspelled ones,
+    /// which are ignored when loading the project.
+    pub fn unknown_fields(&self) -> Result<Vec<UnknownField>, ProjectError> {
+        let content = std::fs::read_to_string(self.toml_path())?;
+        Ok(unknown_fields::unknown_fields(&content))
+    }
+
     /// Get the `.luarc.json` or `.emmyrc.json` path.
     pub(crate) fn luarc_path(&self) -> PathBuf {
         let luarc_path = self.root.join(LUARC);
@@ -393,16 +470,27 @@ impl Project {
         self.lua_version_tree(self.lua_version(config)?, config)
     }
 
+    /// The root of the project's trees, with a separate directory for each build profile.
+    /// If the config has a project tree root, e.g. a staging directory, that is used instead.
+    fn tree_root_dir(&self, config: &Config) -> PathBuf {
+        if let Some(root) = config.project_tree_root() {
+            return root.to_path_buf();
+        }
+        match config.profile() {
+            Some(profile) => self
+                .default_tree_root_dir()
+                .join(PROFILES_DIR_NAME)
+                .join(profile),
+            None => self.default_tree_root_dir(),
+        }
+    }
+
     pub(crate) fn lua_version_tree(
         &self,
         lua_version: LuaVersion,
         config: &Config,
     ) -> Result<Tree, ProjectTreeError> {
-        Ok(Tree::new(
-            self.default_tree_root_dir(),
-            lua_version,
-            config,
-        )?)
+        Ok(Tree::new(self.tree_root_dir(config), lua_version, config)?)
     }
 
     pub fn test_tree(&self, config: &Config) -> Result<Tree, ProjectTreeError> {
@@ -413,59 +501,201 @@ impl Project {
         Ok(self.tree(config)?.build_tree(config)?)
     }
 
+    /// The `RockLayout` of the project's package in the project tree.
+    /// Returns `None` if the project has not been built yet.
+    pub fn layout(&self, config: &Config) -> Result<Option<RockLayout>, ProjectTreeError> {
+        let tree = self.tree(config)?;
+        let lockfile = tree.lockfile()?;
+        Ok(lockfile
+            .rocks()
+            .values()
+            .filter(|package| {
+                lockfile.is_entrypoint(&package.id()) && package.name() == self.toml().package()
+            })
+            .max_by_key(|package| package.version())
+            .map(|package| tree.entrypoint_layout(package)))
+    }
+
     pub fn lua_version(&self, config: &Config) -> Result<LuaVersion, LuaVersionError> {
         self.toml().lua_version_matches(config)
     }
 
+    /// The Lua version to use for the project if the config doesn't set one:
+    /// The Lua version of the release the lux.toml pins, if any,
+    /// or the Lua version in the project's `.lua-version` file,
+    /// which other Lua version managers use.
+    pub fn default_lua_version(&self) -> Result<Option<LuaVersion>, LuaVersionFileError> {
+        match self
+            .toml()
+            .pinned_lua_version()
+            .and_then(|version| LuaVersion::from_version(version).ok())
+        {
+            Some(lua_version) => Ok(Some(lua_version)),
+            None => read_lua_version_file(&self.root),
+        }
+    }
+
+    /// The Lua interpreter to run the project with.
+    /// If the project pins an exact Lua version, this is the pinned interpreter,
+    /// unless the `LUA` variable is overridden in the config.
+    pub fn lua_binary(&self, config: &Config) -> Result<LuaBinary, LuaVersionError> {
+        let lua_version = self.lua_version(config)?;
+        let pinned_bin = self
+            .toml()
+            .pinned_lua_version()
+            .filter(|_| !config.variables().contains_key("LUA"))
+            .and_then(|pinned_version| LuaInstallation::find_pinned(&pinned_version, config))
+            .and_then(|lua| lua.bin().map(Path::to_path_buf));
+        Ok(match pinned_bin {
+            Some(bin) => LuaBinary::from(bin),
+            None => LuaBinary::new(lua_version, config),
+        })
+    }
+
+    /// Load the constraint catalog specified by the `constraints` field of the lux.toml, if any.
+    pub async fn constraint_catalog(
+        &self,
+        config: &Config,
+    ) -> Result<Option<ConstraintCatalog>, ConstraintCatalogError> {
+        match &self.toml().constraints {
+            Some(source) => Ok(Some(
+                ConstraintCatalog::load(source, &self.root, config).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Add dependencies to the lux.toml.
+    /// If a dependency is already present with a different version requirement,
+    /// `resolve_conflict` decides whether to widen or replace its version requirement,
+    /// or to abort without editing the lux.toml.
     pub async fn add(
         &mut self,
         dependencies: DependencyType<PackageReq>,
         package_db: &RemotePackageDB,
+        resolve_conflict: impl FnMut(&DependencyConflict) -> DependencyConflictResolution,
+    ) -> Result<(), ProjectEditError> {
+        let dependencies = match dependencies {
+            DependencyType::Regular(deps) => {
+                DependencyType::Regular(deps.into_iter().map_into().collect())
+            }
+            DependencyType::Build(deps) => {
+                DependencyType::Build(deps.into_iter().map_into().collect())
+            }
+            DependencyType::Test(deps) => {
+                DependencyType::Test(deps.into_iter().map_into().collect())
+            }
+            DependencyType::External(deps) => DependencyType::External(deps),
+        };
+        self.add_all(vec![dependencies], package_db, resolve_conflict)
+            .await
+    }
+
+    /// Add dependencies to several dependency tables of the lux.toml at once,
+    /// marking them as pinned or optional if specified.
+    /// The lux.toml is only written if all dependencies could be added.
+    pub async fn add_all(
+        &mut self,
+        dependencies: Vec<DependencyType<LuaDependencySpec>>,
+        package_db: &RemotePackageDB,
+        mut resolve_conflict: impl FnMut(&DependencyConflict) -> DependencyConflictResolution,
     ) -> Result<(), ProjectEditError> {
         let mut project_toml =
             toml_edit::DocumentMut::from_str(&tokio::fs::read_to_string(self.toml_path()).await?)?;
 
         prepare_dependency_tables(&mut project_toml);
-        let table = match dependencies {
-            DependencyType::Regular(_) => &mut project_toml["dependencies"],
-            DependencyType::Build(_) => &mut project_toml["build_dependencies"],
-            DependencyType::Test(_) => &mut project_toml["test_dependencies"],
-            DependencyType::External(_) => &mut project_toml["external_dependencies"],
-        };
+        for dependencies in dependencies {
+            let table = match dependencies {
+                DependencyType::Regular(_) => &mut project_toml["dependencies"],
+                DependencyType::Build(_) => &mut project_toml["build_dependencies"],
+                DependencyType::Test(_) => &mut project_toml["test_dependencies"],
+                DependencyType::External(_) => &mut project_toml["external_dependencies"],
+            };
 
-        match dependencies {
-            DependencyType::Regular(ref deps)
-            | DependencyType::Build(ref deps)
-            | DependencyType::Test(ref deps) => {
-                for dep in deps {
-                    let dep_version_str = if dep.version_req().is_any() {
-                        package_db
-                            .latest_version(dep.name())
-                            // This condition should never be reached, as the package should
-                            // have been found in the database or an error should have been
-                            // reported prior.
-                            // Still worth making an error message for this in the future,
-                            // though.
-                            .expect("unable to query latest version for package")
-                            .to_string()
-                    } else {
-                        dep.version_req().to_string()
-                    };
-                    table[dep.name().to_string()] = toml_edit::value(dep_version_str);
-                }
-            }
-            DependencyType::External(ref deps) => {
-                for (name, dep) in deps {
-                    if let Some(path) = &dep.header {
-                        table[name]["header"] = toml_edit::value(path.to_slash_lossy().to_string());
+            match dependencies {
+                DependencyType::Regular(ref deps)
+                | DependencyType::Build(ref deps)
+                | DependencyType::Test(ref deps) => {
+                    for dep in deps {
+                        let dep_version_str = if dep.version_req().is_any() {
+                            package_db
+                                .latest_version(dep.name())
+                                // This condition should never be reached, as the package should
+                                // have been found in the database or an error should have been
+                                // reported prior.
+                                // Still worth making an error message for this in the future,
+                                // though.
+                                .expect("unable to query latest version for package")
+                                .to_string()
+                        } else {
+                            dep.version_req().to_string()
+                        };
+                        let name = dep.name().to_string();
+                        let existing = table.get(&name).and_then(|item| {
+                            item.as_str()
+                                .or_else(|| item.get("version").and_then(Item::as_str))
+                                .and_then(|version_req| PackageVersionReq::parse(version_req).ok())
+                        });
+                        let requested = PackageVersionReq::parse(&dep_version_str).ok();
+                        match existing.zip(requested) {
+                            // Keep the existing entry, which may have other fields.
+                            Some((existing, requested)) if existing == requested => {}
+                            Some((existing, requested)) => {
+                                let conflict = DependencyConflict {
+                                    name: dep.name().clone(),
+                                    existing,
+                                    requested,
+                                };
+                                match resolve_conflict(&conflict) {
+                                    DependencyConflictResolution::Widen => {
+                                        let widened = conflict.widened().ok_or_else(|| {
+                                            ProjectEditError::IncompatibleVersionReqs(
+                                                conflict.clone(),
+                                            )
+                                        })?;
+                                        set_dependency_version(
+                                            &mut table[&name],
+                                            widened.to_string(),
+                                        );
+                                    }
+                                    DependencyConflictResolution::Replace => {
+                                        let entry = &mut table[&name];
+                                        set_dependency_version(entry, dep_version_str);
+                                        // The requested version is from the package database.
+                                        if let Some(entry) = entry.as_table_like_mut() {
+                                            entry.remove("git");
+                                            entry.remove("rev");
+                                        }
+                                    }
+                                    DependencyConflictResolution::Abort => {
+                                        return Err(ProjectEditError::DependencyConflict(conflict));
+                                    }
+                                }
+                            }
+                            None => table[&name] = toml_edit::value(dep_version_str),
+                        }
+                        if dep.pin().as_bool() {
+                            set_dependency_flag(&mut table[&name], "pin", true);
+                        }
+                        if dep.opt().as_bool() {
+                            set_dependency_flag(&mut table[&name], "opt", true);
+                        }
                     }
-                    if let Some(path) = &dep.library {
-                        table[name]["library"] =
-                            toml_edit::value(path.to_slash_lossy().to_string());
+                }
+                DependencyType::External(ref deps) => {
+                    for (name, dep) in deps {
+                        if let Some(path) = &dep.header {
+                            table[name]["header"] =
+                                toml_edit::value(path.to_slash_lossy().to_string());
+                        }
+                        if let Some(path) = &dep.library {
+                            table[name]["library"] =
+                                toml_edit::value(path.to_slash_lossy().to_string());
+                        }
                     }
                 }
-            }
-        };
+            };
+        }
 
         let toml_content = project_toml.to_string();
         tokio::fs::write(self.toml_path(), &toml_content).await?;
@@ -550,6 +780,30 @@ impl Project {
         Ok(())
     }
 
+    /// Sort the dependency tables of the lux.toml alphabetically,
+    /// keeping the comments attached to each dependency.
+    pub async fn sort_dependencies(&mut self) -> Result<(), ProjectEditError> {
+        let mut project_toml =
+            toml_edit::DocumentMut::from_str(&tokio::fs::read_to_string(self.toml_path()).await?)?;
+
+        for key in [
+            "dependencies",
+            "build_dependencies",
+            "test_dependencies",
+            "external_dependencies",
+        ] {
+            if let Some(table) = project_toml.get_mut(key).and_then(Item::as_table_like_mut) {
+                table.sort_values();
+            }
+        }
+
+        let toml_content = project_toml.to_string();
+        tokio::fs::write(self.toml_path(), &toml_content).await?;
+        self.toml = PartialProjectToml::new(&toml_content, self.root.clone())?;
+
+        Ok(())
+    }
+
     pub async fn upgrade(
         &mut self,
         dependencies: LuaDependencyType<PackageName>,
@@ -577,40 +831,30 @@ impl Project {
                             .to_string())
                     };
                 for dep in deps {
-                    let mut dep_item = table[dep.to_string()].clone();
-                    match &dep_item {
-                        Item::Value(_) => {
-                            let dep_version_str = latest_rock_version_str(dep)?;
-                            table[dep.to_string()] = toml_edit::value(dep_version_str);
-                        }
-                        Item::Table(tbl) => {
-                            if tbl.contains_key("git") {
-                                let git_value = tbl
-                                    .get("git")
-                                    .expect("expected 'git' field")
-                                    .clone()
-                                    .into_value()
-                                    .map_err(ProjectEditError::ExpectedValue)?;
-                                let git_url_str = git_value
-                                    .as_str()
-                                    .ok_or(ProjectEditError::ExpectedString(git_value.clone()))?;
-                                let shorthand: GitUrlShorthand = git_url_str.parse()?;
-                                let latest_rev =
-                                    git::utils::latest_semver_tag_or_commit_sha(&shorthand.into())?;
-                                let key = if tbl.contains_key("rev") {
-                                    "rev".to_string()
-                                } else {
-                                    "version".to_string()
-                                };
-                                dep_item[key] = toml_edit::value(latest_rev);
-                                table[dep.to_string()] = dep_item;
+                    let entry = &mut table[dep.to_string()];
+                    if entry.is_none() {
+                        continue;
+                    }
+                    match entry.get("git") {
+                        Some(git) => {
+                            let git_value = git
+                                .clone()
+                                .into_value()
+                                .map_err(ProjectEditError::ExpectedValue)?;
+                            let git_url_str = git_value
+                                .as_str()
+                                .ok_or(ProjectEditError::ExpectedString(git_value.clone()))?;
+                            let shorthand: GitUrlShorthand = git_url_str.parse()?;
+                            let latest_rev =
+                                git::utils::latest_semver_tag_or_commit_sha(&shorthand.into())?;
+                            let key = if entry.get("rev").is_some() {
+                                "rev"
                             } else {
-                                let dep_version_str = latest_rock_version_str(dep)?;
-                                dep_item["version".to_string()] = toml_edit::value(dep_version_str);
-                                table[dep.to_string()] = dep_item;
-                            }
+                                "version"
+                            };
+                            set_entry_value(entry, key, latest_rev);
                         }
-                        _ => {}
+                        None => set_dependency_version(entry, latest_rock_version_str(dep)?),
                     }
                 }
             }
@@ -713,23 +957,9 @@ impl Project {
             | LuaDependencyType::Build(ref deps)
             | LuaDependencyType::Test(ref deps) => {
                 for dep in deps {
-                    let mut dep_item = table[dep.to_string()].clone();
-                    match dep_item {
-                        version @ Item::Value(_) => match &pin {
-                            PinnedState::Unpinned => {}
-                            PinnedState::Pinned => {
-                                let mut dep_entry = toml_edit::table().into_table().unwrap();
-                                dep_entry.set_implicit(true);
-                                dep_entry["version"] = version;
-                                dep_entry["pin"] = toml_edit::value(true);
-                                table[dep.to_string()] = toml_edit::Item::Table(dep_entry);
-                            }
-                        },
-                        Item::Table(_) => {
-                            dep_item["pin".to_string()] = toml_edit::value(pin.as_bool());
-                            table[dep.to_string()] = dep_item;
-                        }
-                        _ => {}
+                    let entry = &mut table[dep.to_string()];
+                    if !entry.is_none() {
+                        set_dependency_flag(entry, "pin", pin.as_bool());
                     }
                 }
             }
@@ -747,6 +977,63 @@ impl Project {
     }
 }
 
+/// Replaces a value, keeping its formatting and comments.
+fn replace_value(value: &mut Value, new_value: impl Into<Value>) {
+    let decor = value.decor().clone();
+    *value = new_value.into();
+    *value.decor_mut() = decor;
+}
+
+/// Sets a field of a dependency entry table, keeping its formatting and comments.
+fn set_entry_value(entry: &mut Item, key: &str, value: impl Into<Value>) {
+    if let Some(old_value) = entry.get_mut(key).and_then(Item::as_value_mut) {
+        replace_value(old_value, value);
+    } else if let Some(table) = entry.as_inline_table_mut() {
+        let mut value = value.into();
+        // Keep the padding before the closing brace at the end.
+        if let Some((_, last_value)) = table.iter_mut().last() {
+            if let Some(suffix) = last_value.decor().suffix().cloned() {
+                last_value.decor_mut().set_suffix("");
+                value.decor_mut().set_suffix(suffix);
+            }
+        }
+        table.insert(key, value);
+    } else if let Some(table) = entry.as_table_like_mut() {
+        table.insert(key, Item::Value(value.into()));
+    }
+}
+
+/// Sets the version of a dependency entry, which may be a version string or a table,
+/// keeping its formatting and comments.
+fn set_dependency_version(entry: &mut Item, version: String) {
+    if entry.is_table_like() {
+        set_entry_value(entry, "version", version);
+    } else if let Some(old_version) = entry.as_value_mut() {
+        replace_value(old_version, version);
+    } else {
+        *entry = toml_edit::value(version);
+    }
+}
+
+/// Sets a boolean field of a dependency entry, like `pin` or `opt`.
+/// A version string is converted to an inline table, so that the entry
+/// keeps its position and comments.
+fn set_dependency_flag(entry: &mut Item, key: &str, flag: bool) {
+    if let Some(version) = entry.as_value_mut().filter(|value| value.is_str()) {
+        if !flag {
+            return;
+        }
+        let mut table = InlineTable::new();
+        table.insert("version", Value::from(version.as_str().unwrap_or_default()));
+        let decor = version.decor().clone();
+        *version = Value::InlineTable(table);
+        *version.decor_mut() = decor;
+    }
+    if flag || entry.get(key).is_some() {
+        set_entry_value(entry, key, flag);
+    }
+}
+
 fn prepare_dependency_tables(project_toml: &mut DocumentMut) {
     if !project_toml.contains_table("dependencies") {
         let mut table = toml_edit::table().into_table().unwrap();
@@ -784,6 +1071,8 @@ mod tests {
 
     use super::*;
     use crate::{
+        config::ConfigBuilder,
+        lockfile::OptState,
         lua_rockspec::ExternalDependencySpec,
         manifest::{Manifest, ManifestMetadata},
         package::PackageReq,
@@ -813,16 +1102,25 @@ mod tests {
             .add(
                 DependencyType::Regular(add_dependencies.clone()),
                 &package_db,
+                |_| DependencyConflictResolution::Replace,
             )
             .await
             .unwrap();
 
         project
-            .add(DependencyType::Build(add_dependencies.clone()), &package_db)
+            .add(
+                DependencyType::Build(add_dependencies.clone()),
+                &package_db,
+                |_| DependencyConflictResolution::Replace,
+            )
             .await
             .unwrap();
         project
-            .add(DependencyType::Test(add_dependencies.clone()), &package_db)
+            .add(
+                DependencyType::Test(add_dependencies.clone()),
+                &package_db,
+                |_| DependencyConflictResolution::Replace,
+            )
             .await
             .unwrap();
 
@@ -836,6 +1134,7 @@ mod tests {
                     },
                 )])),
                 &package_db,
+                |_| DependencyConflictResolution::Replace,
             )
             .await
             .unwrap();
@@ -870,6 +1169,203 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_add_conflicting_dependencies() {
+        let sample_project: PathBuf = "resources/test/sample-projects/no-build-spec/".into();
+        let project_root = assert_fs::TempDir::new().unwrap();
+        project_root.copy_from(&sample_project, &["**"]).unwrap();
+        let project_root: PathBuf = project_root.path().into();
+        let mut project = Project::from(&project_root).unwrap().unwrap();
+
+        let test_manifest_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/manifest-5.1");
+        let content = String::from_utf8(std::fs::read(&test_manifest_path).unwrap()).unwrap();
+        let metadata = ManifestMetadata::new(&content).unwrap();
+        let package_db = Manifest::new(Url::parse("https://example.com").unwrap(), metadata).into();
+        let busted = |version: &str| {
+            DependencyType::Regular(vec![
+                PackageReq::new("busted".into(), Some(version.into())).unwrap()
+            ])
+        };
+        let busted_version_req = |project: &Project| {
+            project
+                .toml()
+                .into_remote()
+                .unwrap()
+                .dependencies()
+                .current_platform()
+                .first()
+                .unwrap()
+                .version_req()
+                .to_string()
+        };
+
+        project
+            .add(busted(">= 1.0.0, < 2.0.0"), &package_db, |_| {
+                panic!("unexpected conflict")
+            })
+            .await
+            .unwrap();
+        project
+            .add(busted(">= 1.0.0, < 2.0.0"), &package_db, |_| {
+                panic!("unexpected conflict")
+            })
+            .await
+            .unwrap();
+
+        let err = project
+            .add(busted(">= 2.0.0, < 3.0.0"), &package_db, |conflict| {
+                assert_eq!(conflict.name().to_string(), "busted");
+                DependencyConflictResolution::Abort
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProjectEditError::DependencyConflict(_)));
+        assert_eq!(busted_version_req(&project), ">=1.0.0, <2.0.0");
+
+        project
+            .add(busted(">= 2.0.0, < 3.0.0"), &package_db, |_| {
+                DependencyConflictResolution::Widen
+            })
+            .await
+            .unwrap();
+        assert_eq!(busted_version_req(&project), ">=1.0.0, <3.0.0");
+
+        project
+            .add(busted("== 2.1.0"), &package_db, |_| {
+                DependencyConflictResolution::Replace
+            })
+            .await
+            .unwrap();
+        assert_eq!(busted_version_req(&project), "==2.1.0");
+
+        let err = project
+            .add(busted("scm"), &package_db, |_| {
+                DependencyConflictResolution::Widen
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProjectEditError::IncompatibleVersionReqs(_)));
+    }
+
+    #[tokio::test]
+    async fn test_add_all_dependencies() {
+        let sample_project: PathBuf = "resources/test/sample-projects/no-build-spec/".into();
+        let project_root = assert_fs::TempDir::new().unwrap();
+        project_root.copy_from(&sample_project, &["**"]).unwrap();
+        let project_root: PathBuf = project_root.path().into();
+        let mut project = Project::from(&project_root).unwrap().unwrap();
+
+        let test_manifest_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/manifest-5.1");
+        let content = String::from_utf8(std::fs::read(&test_manifest_path).unwrap()).unwrap();
+        let metadata = ManifestMetadata::new(&content).unwrap();
+        let package_db = Manifest::new(Url::parse("https://example.com").unwrap(), metadata).into();
+        let busted = LuaDependencySpec::from(
+            PackageReq::new("busted".into(), Some(">= 1.0.0".into())).unwrap(),
+        );
+
+        project
+            .add_all(
+                vec![
+                    DependencyType::Build(vec![busted.clone().with_opt(OptState::Optional)]),
+                    DependencyType::Test(vec![busted.clone().with_pin(PinnedState::Pinned)]),
+                ],
+                &package_db,
+                |_| panic!("unexpected conflict"),
+            )
+            .await
+            .unwrap();
+
+        let project = Project::from(&project_root).unwrap().unwrap();
+        let validated_toml = project.toml().into_remote().unwrap();
+        assert!(validated_toml.dependencies().current_platform().is_empty());
+        let build_dependencies = validated_toml.build_dependencies().current_platform();
+        assert_eq!(build_dependencies.len(), 1);
+        assert_eq!(build_dependencies[0].version_req(), busted.version_req());
+        assert_eq!(build_dependencies[0].opt(), &OptState::Optional);
+        assert_eq!(build_dependencies[0].pin(), &PinnedState::Unpinned);
+        let test_dependencies = validated_toml.test_dependencies().current_platform();
+        assert_eq!(test_dependencies.len(), 1);
+        assert_eq!(test_dependencies[0].opt(), &OptState::Required);
+        assert_eq!(test_dependencies[0].pin(), &PinnedState::Pinned);
+    }
+
+    #[tokio::test]
+    async fn test_edits_preserve_formatting() {
+        let sample_project: PathBuf = "resources/test/sample-projects/formatted/".into();
+        let project_root = assert_fs::TempDir::new().unwrap();
+        project_root.copy_from(&sample_project, &["**"]).unwrap();
+        let project_root: PathBuf = project_root.path().into();
+        let mut project = Project::from(&project_root).unwrap().unwrap();
+
+        let test_manifest_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/manifest-5.1");
+        let content = String::from_utf8(std::fs::read(&test_manifest_path).unwrap()).unwrap();
+        let metadata = ManifestMetadata::new(&content).unwrap();
+        let package_db: RemotePackageDB =
+            Manifest::new(Url::parse("https://example.com").unwrap(), metadata).into();
+
+        project
+            .add_all(
+                vec![DependencyType::Regular(vec![
+                    LuaDependencySpec::from(
+                        PackageReq::new("busted".into(), Some(">= 2.0.0".into())).unwrap(),
+                    )
+                    .with_pin(PinnedState::Pinned),
+                    PackageReq::new("penlight".into(), Some("1.5.0".into()))
+                        .unwrap()
+                        .into(),
+                ])],
+                &package_db,
+                |_| DependencyConflictResolution::Widen,
+            )
+            .await
+            .unwrap();
+        project
+            .set_pinned_state(
+                LuaDependencyType::Regular(vec!["lua-cjson".into()]),
+                PinnedState::Pinned,
+            )
+            .await
+            .unwrap();
+        project.sort_dependencies().await.unwrap();
+
+        let toml_content = std::fs::read_to_string(project.toml_path()).unwrap();
+        assert_eq!(
+            toml_content,
+            r#"package = "sample-project"
+version = "0.1.0"
+lua = ">=5.1"
+
+[source]
+url = "https://github.com/nvim-neorocks/luarocks-stub"
+
+[dependencies]
+busted = { version = ">=1.0.0", opt = true, pin = true } # tests only
+# Needed for the JSON encoder
+lua-cjson    = { version = "2.1.0", pin = true }  # keep in sync with the C API
+penlight = "==1.5.0"
+
+[build]
+type = "builtin"
+"#
+        );
+
+        project
+            .upgrade(
+                LuaDependencyType::Regular(vec!["lua-cjson".into()]),
+                &package_db,
+            )
+            .await
+            .unwrap();
+        let latest_version = package_db.latest_version(&"lua-cjson".into()).unwrap();
+        let toml_content = std::fs::read_to_string(project.toml_path()).unwrap();
+        assert!(toml_content.contains(&format!(
+            r#"lua-cjson    = {{ version = "{latest_version}", pin = true }}  # keep in sync with the C API"#
+        )));
+    }
+
     #[tokio::test]
     async fn test_remove_dependencies() {
         let sample_project: PathBuf = "resources/test/sample-projects/dependencies/".into();
@@ -926,6 +1422,114 @@ mod tests {
         test_pin_unpin_dependencies(PinnedState::Unpinned).await
     }
 
+    #[test]
+    fn test_build_profiles() {
+        let project_root = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            project_root.join(PROJECT_TOML),
+            r#"
+package = "profiles"
+version = "0.1.0"
+lua = "5.1"
+
+[profile.dev]
+cflags = ["-O0", "-g3"]
+"#,
+        )
+        .unwrap();
+        let project = Project::from(project_root.path()).unwrap().unwrap();
+        let dev = project.toml().profile("dev").unwrap();
+        assert_eq!(dev.cflags(), &["-O0".to_string(), "-g3".to_string()]);
+        let release = project.toml().profile("release").unwrap();
+        assert_eq!(release.cflags(), &["-O2".to_string()]);
+        assert!(project.toml().profile("asan").is_none());
+
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .lua_version(Some(LuaVersion::Lua51))
+            .toolchain(Some("clang".into()))
+            .build()
+            .unwrap();
+        let default_tree = project.tree(&config).unwrap();
+        let config = config.with_profile("dev".into(), &dev);
+        let toolchain = config.toolchain().unwrap();
+        assert_eq!(toolchain.cc(), Some("clang"));
+        assert_eq!(toolchain.cflags(), dev.cflags());
+        let dev_tree = project.tree(&config).unwrap();
+        assert_ne!(dev_tree.root(), default_tree.root());
+        assert!(dev_tree.root().starts_with(
+            project
+                .root()
+                .join(LUX_DIR_NAME)
+                .join("profiles")
+                .join("dev")
+        ));
+    }
+
+    #[test]
+    fn test_default_lua_version() {
+        let project_root = assert_fs::TempDir::new().unwrap();
+        let project_toml = |lua: &str| {
+            std::fs::write(
+                project_root.join(PROJECT_TOML),
+                format!(
+                    r#"
+package = "versioned"
+version = "0.1.0"
+lua = "{lua}"
+"#
+                ),
+            )
+            .unwrap();
+            Project::from(project_root.path()).unwrap().unwrap()
+        };
+        assert_eq!(project_toml(">=5.1").default_lua_version().unwrap(), None);
+        std::fs::write(project_root.join(LUA_VERSION_FILE), "5.3.6\n").unwrap();
+        assert_eq!(
+            project_toml(">=5.1").default_lua_version().unwrap(),
+            Some(LuaVersion::Lua53)
+        );
+        assert_eq!(
+            project_toml("=5.1.5").default_lua_version().unwrap(),
+            Some(LuaVersion::Lua51)
+        );
+    }
+
+    #[test]
+    fn test_project_tree_root() {
+        let project_root = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            project_root.join(PROJECT_TOML),
+            r#"
+package = "staged"
+version = "0.1.0"
+lua = "5.1"
+"#,
+        )
+        .unwrap();
+        let project = Project::from(project_root.path()).unwrap().unwrap();
+        let out_tree = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap()
+            .with_project_tree_root(out_tree.to_path_buf());
+        let tree = project.tree(&config).unwrap();
+        assert!(tree.root().starts_with(out_tree.path()));
+        assert!(project
+            .test_tree(&config)
+            .unwrap()
+            .root()
+            .starts_with(out_tree.path()));
+        assert!(project
+            .build_tree(&config)
+            .unwrap()
+            .root()
+            .starts_with(out_tree.path()));
+        assert!(!project.root().join(LUX_DIR_NAME).exists());
+    }
+
     async fn test_pin_unpin_dependencies(pin: PinnedState) {
         let sample_project: PathBuf = "resources/test/sample-projects/dependencies/".into();
         let project_root = assert_fs::TempDir::new().unwrap();
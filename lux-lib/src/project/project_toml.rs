@@ -1,8 +1,10 @@
 //! Structs and utilities for `lux.toml`
 
+use crate::build::debug_symbols::DebugSymbols;
 use crate::git::shorthand::GitUrlShorthand;
 use crate::git::GitSource;
 use crate::hash::HasIntegrity;
+use crate::lockfile::LocalPackageLockType;
 use crate::lockfile::OptState;
 use crate::lockfile::PinnedState;
 use crate::lua_rockspec::DeploySpec;
@@ -11,27 +13,33 @@ use crate::lua_rockspec::LocalRockSource;
 use crate::lua_rockspec::LuaRockspecError;
 use crate::lua_rockspec::RemoteLuaRockspec;
 use crate::lua_rockspec::RockSourceSpec;
-use crate::operations::RunCommand;
+use crate::operations::{CheckSpec, FormatSpec, LintSpec, RunCommand};
 use crate::package::PackageNameList;
+use crate::project::rockspec_template::RockspecTemplate;
 use crate::rockspec::lua_dependency::LuaDependencySpec;
 use std::io;
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use itertools::Itertools;
 use mlua::ExternalResult;
 use mlua::UserData;
 use nonempty::NonEmpty;
+use schemars::{generate::SchemaSettings, transform::RecursiveTransform, JsonSchema, Schema};
 use serde::de;
 use serde::{Deserialize, Deserializer};
 use ssri::Integrity;
 use thiserror::Error;
 
 use crate::{
-    config::{Config, LuaVersion},
+    config::{profile::BuildProfile, Config, LuaVersion},
     lua_rockspec::{
         BuildSpec, BuildSpecInternal, BuildSpecInternalError, DisplayAsLuaKV, ExternalDependencies,
         ExternalDependencySpec, FromPlatformOverridable, LuaVersionError, PartialLuaRockspec,
-        PerPlatform, PlatformIdentifier, PlatformSupport, PlatformValidationError,
+        PartialOverride, PerPlatform, PlatformIdentifier, PlatformSupport, PlatformValidationError,
         RemoteRockSource, RockDescription, RockSourceError, RockspecFormat, TestSpec,
         TestSpecDecodeError, TestSpecInternal,
     },
@@ -42,15 +50,17 @@ use crate::{
     rockspec::{LuaVersionCompatibility, Rockspec},
 };
 
+use super::constraints::ConstraintCatalogSource;
 use super::gen::GenerateSourceError;
 use super::gen::RockSourceTemplate;
 use super::r#gen::GenerateVersionError;
 use super::r#gen::PackageVersionTemplate;
+use super::substitution::substitute_project_variables;
 use super::ProjectRoot;
 
 pub const PROJECT_TOML: &str = "lux.toml";
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(untagged)]
 #[allow(clippy::large_enum_variant)] // This is ok because it's just a Deserialize helper
 enum DependencyEntry {
@@ -58,7 +68,7 @@ enum DependencyEntry {
     Detailed(DependencyTableEntry),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 struct DependencyTableEntry {
     version: PackageVersionReq,
     #[serde(default)]
@@ -69,6 +79,12 @@ struct DependencyTableEntry {
     git: Option<GitUrlShorthand>,
     #[serde(default)]
     rev: Option<String>,
+    #[serde(default)]
+    variables: Option<HashMap<String, String>>,
+    #[serde(default)]
+    debug_symbols: Option<DebugSymbols>,
+    #[serde(default)]
+    build_env: Option<Vec<String>>,
 }
 
 fn parse_map_to_dependency_vec_opt<'de, D>(
@@ -120,6 +136,9 @@ where
                             opt: OptState::from(entry.opt.unwrap_or(false)),
                             pin: PinnedState::from(entry.pin.unwrap_or(false)),
                             source,
+                            variables: entry.variables.unwrap_or_default(),
+                            debug_symbols: entry.debug_symbols,
+                            build_env: entry.build_env,
                         })
                     }
                 })
@@ -164,6 +183,10 @@ pub enum LocalProjectTomlValidationError {
     GenerateVersion(#[from] GenerateVersionError),
 }
 
+#[derive(Debug, Error)]
+#[error("no test suite named {0}. Add a `[test.{0}]` section to the lux.toml.")]
+pub struct TestSuiteNotFound(String);
+
 #[derive(Debug, Error)]
 pub enum RemoteProjectTomlValidationError {
     #[error("error generating rockspec source:\n{0}")]
@@ -177,7 +200,8 @@ pub enum RemoteProjectTomlValidationError {
 /// The `lux.toml` file.
 /// The only required fields are `package` and `build`, which are required to build a project using `lux build`.
 /// The rest of the fields are optional, but are required to build a rockspec.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[schemars(title = "lux.toml")]
 pub struct PartialProjectToml {
     pub(crate) package: PackageName,
     #[serde(default, rename = "version")]
@@ -188,25 +212,45 @@ pub struct PartialProjectToml {
     #[serde(default)]
     pub(crate) run: Option<RunSpec>,
     #[serde(default)]
+    pub(crate) lint: Option<LintSpec>,
+    #[serde(default)]
+    pub(crate) check: Option<CheckSpec>,
+    #[serde(default)]
+    pub(crate) format: Option<FormatSpec>,
+    #[serde(default)]
     pub(crate) lua: Option<PackageVersionReq>,
     #[serde(default)]
     pub(crate) description: Option<RockDescription>,
     #[serde(default)]
+    #[schemars(with = "Option<HashMap<String, bool>>")]
     pub(crate) supported_platforms: Option<HashMap<PlatformIdentifier, bool>>,
     #[serde(default, deserialize_with = "parse_map_to_dependency_vec_opt")]
+    #[schemars(with = "Option<HashMap<String, DependencyEntry>>")]
     pub(crate) dependencies: Option<Vec<LuaDependencySpec>>,
     #[serde(default, deserialize_with = "parse_map_to_dependency_vec_opt")]
+    #[schemars(with = "Option<HashMap<String, DependencyEntry>>")]
     pub(crate) build_dependencies: Option<Vec<LuaDependencySpec>>,
     #[serde(default)]
     pub(crate) external_dependencies: Option<HashMap<String, ExternalDependencySpec>>,
     #[serde(default, deserialize_with = "parse_map_to_dependency_vec_opt")]
+    #[schemars(with = "Option<HashMap<String, DependencyEntry>>")]
     pub(crate) test_dependencies: Option<Vec<LuaDependencySpec>>,
     #[serde(default, rename = "source")]
     pub(crate) source_template: RockSourceTemplate,
     #[serde(default)]
-    pub(crate) test: Option<TestSpecInternal>,
+    pub(crate) test: Option<ProjectTestSpecInternal>,
     #[serde(default)]
     pub(crate) deploy: Option<DeploySpec>,
+    /// A catalog of version constraints that apply to the project's dependencies.
+    #[serde(default)]
+    pub(crate) constraints: Option<ConstraintCatalogSource>,
+    /// Build profiles, which take precedence over the built-in `dev` and `release` profiles.
+    #[serde(default, rename = "profile")]
+    pub(crate) profiles: Option<HashMap<String, BuildProfile>>,
+    /// What to do with the debug symbols of the C modules built for this project,
+    /// including its dependencies.
+    #[serde(default)]
+    pub(crate) debug_symbols: Option<DebugSymbols>,
 
     /// Used to bind the project TOML to a project root
     #[serde(skip, default = "ProjectRoot::new")]
@@ -237,11 +281,71 @@ impl HasIntegrity for PartialProjectToml {
     }
 }
 
+/// The JSON schema of the `lux.toml` format,
+/// which editors can use for completion and validation, e.g. via taplo.
+pub fn project_toml_schema() -> serde_json::Value {
+    SchemaSettings::draft07()
+        .with(|settings| {
+            // This has to run before draft 7's transforms, which turn `$ref`s with siblings into `allOf`s.
+            settings.transforms.insert(
+                0,
+                Box::new(RecursiveTransform(remove_null as fn(&mut Schema))),
+            )
+        })
+        .into_generator()
+        .into_root_schema_for::<PartialProjectToml>()
+        .to_value()
+}
+
+/// Remove the `null` alternatives and defaults that are generated for `Option`s,
+/// as TOML has no `null`.
+fn remove_null(schema: &mut Schema) {
+    fn contains_null(value: &serde_json::Value) -> bool {
+        match value {
+            serde_json::Value::Null => true,
+            serde_json::Value::Array(values) => values.iter().any(contains_null),
+            serde_json::Value::Object(map) => map.values().any(contains_null),
+            _ => false,
+        }
+    }
+    let Some(object) = schema.as_object_mut() else {
+        return;
+    };
+    if object.get("default").is_some_and(contains_null) {
+        object.remove("default");
+    }
+    if let Some(serde_json::Value::Array(types)) = object.get_mut("type") {
+        types.retain(|ty| ty != "null");
+        if let [ty] = types.as_slice() {
+            let ty = ty.clone();
+            object.insert("type".into(), ty);
+        }
+    }
+    if let Some(serde_json::Value::Array(any_of)) = object.get_mut("anyOf") {
+        any_of.retain(|schema| schema.get("type").is_none_or(|ty| ty != "null"));
+        if let [serde_json::Value::Object(schema)] = any_of.as_slice() {
+            let schema = schema.clone();
+            object.remove("anyOf");
+            object.extend(schema);
+        }
+    }
+}
+
 impl PartialProjectToml {
     pub(crate) fn new(str: &str, project_root: ProjectRoot) -> Result<Self, toml::de::Error> {
+        let project_toml: Self = toml::from_str(str)?;
+        // Deserializing from a `toml::Value` loses the error spans,
+        // so we only do so if there may be variables to substitute.
+        let project_toml = if str.contains("$(") {
+            let mut table: toml::Table = toml::from_str(str)?;
+            substitute_project_variables(&mut table, &project_root).map_err(de::Error::custom)?;
+            toml::Value::Table(table).try_into()?
+        } else {
+            project_toml
+        };
         Ok(Self {
             project_root,
-            ..toml::from_str(str)?
+            ..project_toml
         })
     }
 
@@ -289,6 +393,30 @@ impl PartialProjectToml {
             ));
         }
 
+        let test = project_toml.test.clone().unwrap_or_default();
+        let test_suites = test
+            .suites
+            .iter()
+            .map(|(name, suite)| {
+                let internal = test.spec.apply_overrides(&suite.spec).expect("infallible");
+                Ok((name.clone(), TestSuite::new(Some(name.clone()), internal)?))
+            })
+            .try_collect::<_, BTreeMap<_, _>, TestSpecDecodeError>()?;
+        // The test suites share the test tree, so their test dependencies are installed together.
+        let mut test_dependencies = project_toml.test_dependencies.clone().unwrap_or_default();
+        for dep in test
+            .suites
+            .values()
+            .flat_map(|suite| suite.test_dependencies.iter().flatten())
+        {
+            if !test_dependencies
+                .iter()
+                .any(|test_dep| test_dep.name() == dep.name())
+            {
+                test_dependencies.push(dep.clone());
+            }
+        }
+
         let validated = LocalProjectToml {
             internal: project_toml.clone(),
 
@@ -302,6 +430,9 @@ impl PartialProjectToml {
                 .ok_or(LocalProjectTomlValidationError::NoLuaVersion)?,
             description: project_toml.description.unwrap_or_default(),
             run: project_toml.run.map(PerPlatform::new),
+            lint: project_toml.lint.clone().unwrap_or_default(),
+            check: project_toml.check.clone().unwrap_or_default(),
+            format: project_toml.format.clone().unwrap_or_default(),
             supported_platforms: PlatformSupport::parse(
                 &project_toml
                     .supported_platforms
@@ -325,11 +456,11 @@ impl PartialProjectToml {
             external_dependencies: PerPlatform::new(
                 project_toml.external_dependencies.unwrap_or_default(),
             ),
-            test_dependencies: PerPlatform::new(project_toml.test_dependencies.unwrap_or_default()),
-            test: PerPlatform::new(TestSpec::from_platform_overridable(
-                project_toml.test.clone().unwrap_or_default(),
-            )?),
-            build: PerPlatform::new(BuildSpec::from_internal_spec(project_toml.build.clone())?),
+            test_dependencies: PerPlatform::new(test_dependencies),
+            test: PerPlatform::new(TestSpec::from_platform_overridable(test.spec.clone())?),
+            test_suites,
+            default_test_suite: test.default_suite,
+            build: project_toml.build.clone().into_per_platform()?,
             deploy: PerPlatform::new(project_toml.deploy.clone().unwrap_or_default()),
             rockspec_format: project_toml.rockspec_format.clone(),
 
@@ -399,6 +530,38 @@ impl PartialProjectToml {
         self.version_template.try_generate(&self.project_root)
     }
 
+    /// The exact Lua release the project is pinned to, e.g. `lua = "=5.1.5"`.
+    pub fn pinned_lua_version(&self) -> Option<PackageVersion> {
+        self.lua.as_ref().and_then(|lua| lua.exact_version())
+    }
+
+    /// The build profile named `name`, either from the project's `[profile.<name>]` section
+    /// or one of the built-in `dev` and `release` profiles.
+    pub fn profile(&self, name: &str) -> Option<BuildProfile> {
+        self.profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(name))
+            .cloned()
+            .or_else(|| BuildProfile::builtin(name))
+    }
+
+    /// What to do with the debug symbols of the C modules built for this project, if set.
+    pub fn debug_symbols(&self) -> Option<DebugSymbols> {
+        self.debug_symbols
+    }
+
+    /// Whether `name` is declared in the given dependencies section.
+    pub fn declares_dependency(&self, deps: &LocalPackageLockType, name: &PackageName) -> bool {
+        match deps {
+            LocalPackageLockType::Regular => &self.dependencies,
+            LocalPackageLockType::Test => &self.test_dependencies,
+            LocalPackageLockType::Build => &self.build_dependencies,
+        }
+        .iter()
+        .flatten()
+        .any(|dep| dep.name() == name)
+    }
+
     /// Merge the `ProjectToml` struct with an unvalidated `LuaRockspec`.
     /// The final merged struct can then be validated.
     pub fn merge(self, other: PartialLuaRockspec) -> Self {
@@ -422,6 +585,9 @@ impl PartialProjectToml {
                 .or(self.lua),
             build: other.build.unwrap_or(self.build),
             run: self.run,
+            lint: self.lint,
+            check: self.check,
+            format: self.format,
             description: other.description.or(self.description),
             supported_platforms: other
                 .supported_platforms
@@ -439,9 +605,19 @@ impl PartialProjectToml {
             test_dependencies: other.test_dependencies.or(self.test_dependencies),
             external_dependencies: other.external_dependencies.or(self.external_dependencies),
             source_template: self.source_template,
-            test: other.test.or(self.test),
+            test: match (other.test, self.test) {
+                (Some(spec), Some(test)) => Some(ProjectTestSpecInternal { spec, ..test }),
+                (Some(spec), None) => Some(ProjectTestSpecInternal {
+                    spec,
+                    ..ProjectTestSpecInternal::default()
+                }),
+                (None, test) => test,
+            },
             deploy: other.deploy.or(self.deploy),
             rockspec_format: other.rockspec_format.or(self.rockspec_format),
+            constraints: self.constraints,
+            profiles: self.profiles,
+            debug_symbols: self.debug_symbols,
 
             // Keep the project root the same, as it is not part of the lua rockspec
             project_root: self.project_root,
@@ -490,35 +666,108 @@ impl LuaVersionCompatibility for PartialProjectToml {
     fn supports_lua_version(&self, lua_version: &LuaVersion) -> bool {
         self.lua
             .as_ref()
-            .is_none_or(|lua| lua.matches(&lua_version.as_version()))
+            .is_none_or(|lua| lua_version.satisfies(lua))
     }
 
     fn lua_version(&self) -> Option<LuaVersion> {
-        for (possibility, version) in [
-            ("5.4.0", LuaVersion::Lua54),
-            ("5.3.0", LuaVersion::Lua53),
-            ("5.2.0", LuaVersion::Lua52),
-            ("5.1.0", LuaVersion::Lua51),
-        ] {
-            if self
-                .lua
-                .as_ref()
-                .is_none_or(|lua| lua.matches(&possibility.parse().unwrap()))
-            {
-                return Some(version);
-            }
-        }
-        None
+        [
+            LuaVersion::Lua54,
+            LuaVersion::Lua53,
+            LuaVersion::Lua52,
+            LuaVersion::Lua51,
+        ]
+        .into_iter()
+        .find(|version| self.lua.as_ref().is_none_or(|lua| version.satisfies(lua)))
     }
 }
 
 // TODO(vhyrro): Move this struct into a different directory.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct RunSpec {
     /// The command to execute when running the project
     pub(crate) command: Option<RunCommand>,
     /// Arguments to pass to the command
+    #[schemars(with = "Option<Vec<String>>")]
     pub(crate) args: Option<NonEmpty<String>>,
+    /// The working directory, relative to the project root
+    pub(crate) cwd: Option<PathBuf>,
+    /// Environment variables to set
+    #[serde(default)]
+    pub(crate) env: HashMap<String, String>,
+}
+
+/// The `[test]` section of a `lux.toml`,
+/// which may contain named test suites, e.g. `[test.integration]`.
+/// Suites cannot be named after the `[test]` section's fields, e.g. `script` or `env`.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[schemars(rename = "TestSpec")]
+pub(crate) struct ProjectTestSpecInternal {
+    #[serde(flatten)]
+    pub(crate) spec: TestSpecInternal,
+    /// The suite to run if none is selected.
+    #[serde(default)]
+    pub(crate) default_suite: Option<String>,
+    #[serde(flatten)]
+    pub(crate) suites: BTreeMap<String, TestSuiteInternal>,
+}
+
+/// A named test suite, which inherits the settings of the `[test]` section.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[schemars(rename = "TestSuite")]
+pub(crate) struct TestSuiteInternal {
+    #[serde(flatten)]
+    pub(crate) spec: TestSpecInternal,
+    /// Test dependencies of this suite, in addition to the `test_dependencies`.
+    #[serde(default, deserialize_with = "parse_map_to_dependency_vec_opt")]
+    #[schemars(with = "Option<HashMap<String, DependencyEntry>>")]
+    pub(crate) test_dependencies: Option<Vec<LuaDependencySpec>>,
+}
+
+/// A test suite, with the settings of the `[test]` section applied.
+#[derive(Clone, Debug)]
+pub struct TestSuite {
+    name: Option<String>,
+    spec: PerPlatform<TestSpec>,
+    internal: TestSpecInternal,
+}
+
+impl TestSuite {
+    fn new(name: Option<String>, internal: TestSpecInternal) -> Result<Self, TestSpecDecodeError> {
+        Ok(Self {
+            name,
+            spec: PerPlatform::new(TestSpec::from_platform_overridable(internal.clone())?),
+            internal,
+        })
+    }
+
+    /// The name of the suite, or `None` for the `[test]` section itself.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn spec(&self) -> &PerPlatform<TestSpec> {
+        &self.spec
+    }
+
+    /// The maximum time the test suite may take.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.internal.timeout.map(Duration::from_secs)
+    }
+
+    /// The working directory of the tests, relative to the project root.
+    pub fn cwd(&self) -> Option<&Path> {
+        self.internal.cwd.as_deref()
+    }
+
+    /// Environment variables to set when running the tests.
+    pub fn env(&self) -> HashMap<String, String> {
+        self.internal.env.clone().unwrap_or_default()
+    }
+
+    /// Whether to run the tests in a scrubbed environment.
+    pub fn hermetic(&self) -> bool {
+        self.internal.hermetic.unwrap_or(false)
+    }
 }
 
 /// The `lux.toml` file, after being properly deserialized.
@@ -531,6 +780,9 @@ pub struct LocalProjectToml {
     lua: PackageVersionReq,
     rockspec_format: Option<RockspecFormat>,
     run: Option<PerPlatform<RunSpec>>,
+    lint: LintSpec,
+    check: CheckSpec,
+    format: FormatSpec,
     description: RockDescription,
     supported_platforms: PlatformSupport,
     dependencies: PerPlatform<Vec<LuaDependencySpec>>,
@@ -538,6 +790,8 @@ pub struct LocalProjectToml {
     external_dependencies: PerPlatform<HashMap<String, ExternalDependencySpec>>,
     test_dependencies: PerPlatform<Vec<LuaDependencySpec>>,
     test: PerPlatform<TestSpec>,
+    test_suites: BTreeMap<String, TestSuite>,
+    default_test_suite: Option<String>,
     build: PerPlatform<BuildSpec>,
     deploy: PerPlatform<DeploySpec>,
 
@@ -553,6 +807,77 @@ impl LocalProjectToml {
         self.run.as_ref()
     }
 
+    pub fn lint(&self) -> &LintSpec {
+        &self.lint
+    }
+
+    pub fn check(&self) -> &CheckSpec {
+        &self.check
+    }
+
+    pub fn formatting(&self) -> &FormatSpec {
+        &self.format
+    }
+
+    /// The maximum time the test suite may take, set by `[test].timeout`.
+    pub fn test_timeout(&self) -> Option<Duration> {
+        self.test_spec_internal()
+            .and_then(|test| test.timeout)
+            .map(Duration::from_secs)
+    }
+
+    /// The working directory of the tests, relative to the project root, set by `[test].cwd`.
+    pub fn test_cwd(&self) -> Option<&Path> {
+        self.test_spec_internal()
+            .and_then(|test| test.cwd.as_deref())
+    }
+
+    /// Environment variables to set when running the tests, set by `[test].env`.
+    pub fn test_env(&self) -> HashMap<String, String> {
+        self.test_spec_internal()
+            .and_then(|test| test.env.clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether to run the tests in a scrubbed environment, set by `[test].hermetic`.
+    pub fn test_hermetic(&self) -> bool {
+        self.test_spec_internal()
+            .and_then(|test| test.hermetic)
+            .unwrap_or(false)
+    }
+
+    fn test_spec_internal(&self) -> Option<&TestSpecInternal> {
+        self.internal.test.as_ref().map(|test| &test.spec)
+    }
+
+    /// The names of the test suites, e.g. `integration` for `[test.integration]`.
+    pub fn test_suite_names(&self) -> impl Iterator<Item = &str> {
+        self.test_suites.keys().map(String::as_str)
+    }
+
+    /// The test suite with the given name.
+    /// If no name is given, this is the `[test].default_suite`, if set,
+    /// and the `[test]` section itself otherwise.
+    pub fn test_suite(&self, name: Option<&str>) -> Result<TestSuite, TestSuiteNotFound> {
+        match name.or(self.default_test_suite.as_deref()) {
+            Some(name) => self
+                .test_suites
+                .get(name)
+                .cloned()
+                .ok_or_else(|| TestSuiteNotFound(name.to_string())),
+            None => Ok(TestSuite {
+                name: None,
+                spec: self.test.clone(),
+                internal: self.test_spec_internal().cloned().unwrap_or_default(),
+            }),
+        }
+    }
+
+    /// The `[test]` section and all named test suites.
+    pub(crate) fn all_test_specs(&self) -> impl Iterator<Item = &PerPlatform<TestSpec>> {
+        std::iter::once(&self.test).chain(self.test_suites.values().map(|suite| &suite.spec))
+    }
+
     /// Convert this project TOML to a Lua rockspec.
     /// Fails if there is no valid project root or if there are off-spec dependencies.
     pub fn to_lua_rockspec(&self) -> Result<LocalLuaRockspec, LuaRockspecError> {
@@ -732,7 +1057,7 @@ version = "{}""#,
         template.push(source.display_lua());
 
         if let Some(ref test) = self.internal.test {
-            template.push(test.display_lua());
+            template.push(test.spec.display_lua());
         }
 
         template.push(self.internal.build.display_lua());
@@ -847,6 +1172,32 @@ impl Rockspec for RemoteProjectToml {
     }
 
     fn to_lua_remote_rockspec_string(&self) -> Result<String, Self::Error> {
+        let fields = self.rockspec_fields()?;
+        let (starter, template) = fields.split_at(3);
+        let starter = starter
+            .iter()
+            .filter_map(|(_, value)| value.as_deref())
+            .join("\n");
+        let unformatted_code = std::iter::once(format!("\n{starter}"))
+            .chain(template.iter().filter_map(|(_, value)| value.clone()))
+            .join("\n\n");
+        Ok(format_lua_code(unformatted_code))
+    }
+}
+
+impl RemoteProjectToml {
+    /// Converts the rockspec to a string that can be uploaded to a luarocks server,
+    /// with its fields laid out according to the `template`.
+    pub fn to_lua_remote_rockspec_string_with_template(
+        &self,
+        template: &RockspecTemplate,
+    ) -> Result<String, ProjectTomlError> {
+        Ok(format_lua_code(template.render(&self.rockspec_fields()?)))
+    }
+
+    /// The fields of the rockspec, with their Lua code, or `None` if they aren't set,
+    /// in the order they are written in by default.
+    fn rockspec_fields(&self) -> Result<Vec<(&'static str, Option<String>)>, ProjectTomlError> {
         let project_root = &self.local.internal.project_root;
         let version = self
             .local
@@ -854,90 +1205,123 @@ impl Rockspec for RemoteProjectToml {
             .version_template
             .try_generate(project_root)?;
 
-        let starter = format!(
-            r#"
-rockspec_format = "{}"
-package = "{}"
-version = "{}""#,
-            self.local.rockspec_format.as_ref().unwrap_or(&"3.0".into()),
-            self.local.package,
-            &version
-        );
-
-        let mut template = Vec::new();
-
-        if self.local.description != RockDescription::default() {
-            template.push(self.local.description.display_lua());
-        }
-
-        if self.local.supported_platforms != PlatformSupport::default() {
-            template.push(self.local.supported_platforms.display_lua());
-        }
-
-        {
-            let mut dependencies = self.local.internal.dependencies.clone().unwrap_or_default();
-            dependencies.insert(
-                0,
-                PackageReq {
-                    name: "lua".into(),
-                    version_req: self.local.lua.clone(),
-                }
-                .into(),
-            );
-            template.push(Dependencies(&dependencies).display_lua());
-        }
-
-        match self.local.internal.build_dependencies {
-            Some(ref build_dependencies) if !build_dependencies.is_empty() => {
-                template.push(BuildDependencies(build_dependencies).display_lua());
-            }
-            _ => {}
-        }
-
-        match self.local.internal.external_dependencies {
-            Some(ref external_dependencies) if !external_dependencies.is_empty() => {
-                template.push(ExternalDependencies(external_dependencies).display_lua());
-            }
-            _ => {}
-        }
-
-        match self.local.internal.test_dependencies {
-            Some(ref test_dependencies) if !test_dependencies.is_empty() => {
-                template.push(TestDependencies(test_dependencies).display_lua());
+        let mut dependencies = self.local.internal.dependencies.clone().unwrap_or_default();
+        dependencies.insert(
+            0,
+            PackageReq {
+                name: "lua".into(),
+                version_req: self.local.lua.clone(),
             }
-            _ => {}
-        }
+            .into(),
+        );
 
         let source = self.local.internal.source_template.try_generate(
             project_root,
             &self.local.internal.package,
             &version,
         )?;
-        template.push(source.display_lua());
-
-        if let Some(ref test) = self.local.internal.test {
-            template.push(test.display_lua());
-        }
 
-        if let Some(ref deploy) = self.local.internal.deploy {
-            template.push(deploy.display_lua());
-        }
-
-        template.push(self.local.internal.build.display_lua());
+        Ok(vec![
+            (
+                "rockspec_format",
+                Some(format!(
+                    r#"rockspec_format = "{}""#,
+                    self.local.rockspec_format.as_ref().unwrap_or(&"3.0".into())
+                )),
+            ),
+            (
+                "package",
+                Some(format!(r#"package = "{}""#, self.local.package)),
+            ),
+            ("version", Some(format!(r#"version = "{}""#, &version))),
+            (
+                "description",
+                (self.local.description != RockDescription::default())
+                    .then(|| self.local.description.display_lua().to_string()),
+            ),
+            (
+                "supported_platforms",
+                (self.local.supported_platforms != PlatformSupport::default())
+                    .then(|| self.local.supported_platforms.display_lua().to_string()),
+            ),
+            (
+                "dependencies",
+                Some(Dependencies(&dependencies).display_lua().to_string()),
+            ),
+            (
+                "build_dependencies",
+                self.local
+                    .internal
+                    .build_dependencies
+                    .as_ref()
+                    .filter(|build_dependencies| !build_dependencies.is_empty())
+                    .map(|build_dependencies| {
+                        BuildDependencies(build_dependencies)
+                            .display_lua()
+                            .to_string()
+                    }),
+            ),
+            (
+                "external_dependencies",
+                self.local
+                    .internal
+                    .external_dependencies
+                    .as_ref()
+                    .filter(|external_dependencies| !external_dependencies.is_empty())
+                    .map(|external_dependencies| {
+                        ExternalDependencies(external_dependencies)
+                            .display_lua()
+                            .to_string()
+                    }),
+            ),
+            (
+                "test_dependencies",
+                self.local
+                    .internal
+                    .test_dependencies
+                    .as_ref()
+                    .filter(|test_dependencies| !test_dependencies.is_empty())
+                    .map(|test_dependencies| {
+                        TestDependencies(test_dependencies)
+                            .display_lua()
+                            .to_string()
+                    }),
+            ),
+            ("source", Some(source.display_lua().to_string())),
+            (
+                "test",
+                self.local
+                    .internal
+                    .test
+                    .as_ref()
+                    .map(|test| test.spec.display_lua().to_string()),
+            ),
+            (
+                "deploy",
+                self.local
+                    .internal
+                    .deploy
+                    .as_ref()
+                    .map(|deploy| deploy.display_lua().to_string()),
+            ),
+            (
+                "build",
+                Some(self.local.internal.build.display_lua().to_string()),
+            ),
+        ])
+    }
+}
 
-        let unformatted_code = std::iter::once(starter)
-            .chain(template.into_iter().map(|kv| kv.to_string()))
-            .join("\n\n");
-        let result = match stylua_lib::format_code(
-            &unformatted_code,
-            stylua_lib::Config::default(),
-            None,
-            stylua_lib::OutputVerification::Full,
-        ) {
-            Ok(formatted_code) => formatted_code,
-            Err(_) => unformatted_code,
-        };
-        Ok(result)
+/// Format Lua code with stylua, falling back to the unformatted code if that fails.
+fn format_lua_code(unformatted_code: String) -> String {
+    match stylua_lib::format_code(
+        &unformatted_code,
+        stylua_lib::Config::default(),
+        None,
+        stylua_lib::OutputVerification::Full,
+    ) {
+        Ok(formatted_code) => formatted_code,
+        Err(_) => unformatted_code,
     }
 }
 
@@ -1023,7 +1407,11 @@ impl UserData for RemoteProjectToml {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{
+        path::{Path, PathBuf},
+        str::FromStr,
+        time::Duration,
+    };
 
     use assert_fs::prelude::{PathChild, PathCopy, PathCreateDir};
     use git2::{Repository, RepositoryInitOptions};
@@ -1031,14 +1419,110 @@ mod tests {
     use url::Url;
 
     use crate::{
+        build::debug_symbols::DebugSymbols,
+        config::LuaVersion,
         git::GitSource,
-        lua_rockspec::{PartialLuaRockspec, PerPlatform, RemoteLuaRockspec, RockSourceSpec},
+        lua_rockspec::{
+            BuildBackendSpec, BuildSpec, LuaModule, ModuleSpec, PartialLuaRockspec, PerPlatform,
+            PlatformIdentifier, RemoteLuaRockspec, RockSourceSpec, TestSpec,
+        },
+        operations::Formatter,
         project::{Project, ProjectRoot},
-        rockspec::{lua_dependency::LuaDependencySpec, Rockspec},
+        rockspec::{lua_dependency::LuaDependencySpec, LuaVersionCompatibility, Rockspec},
     };
 
     use super::PartialProjectToml;
 
+    #[test]
+    fn pinned_lua_version() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "=5.1.5"
+        "#;
+        let project = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        assert_eq!(project.pinned_lua_version(), Some("5.1.5".parse().unwrap()));
+        assert!(project.supports_lua_version(&LuaVersion::Lua51));
+        assert!(!project.supports_lua_version(&LuaVersion::Lua54));
+        assert!(!project.supports_lua_version(&LuaVersion::LuaJIT));
+        assert_eq!(project.lua_version(), Some(LuaVersion::Lua51));
+
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+        "#;
+        let project = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        assert!(project.pinned_lua_version().is_none());
+        assert!(project.supports_lua_version(&LuaVersion::Lua51));
+        assert!(project.supports_lua_version(&LuaVersion::LuaJIT));
+    }
+
+    #[test]
+    fn project_toml_variable_substitution() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [run]
+        args = ["$(PACKAGE)-$(VERSION)", "$(LUADIR)"]
+
+        [external_dependencies.FOO]
+        header = "$(PROJECT_ROOT)/include/foo.h"
+
+        [build]
+        type = "make"
+
+        [build.build_variables]
+        CFLAGS = "-I$(PROJECT_ROOT)/include $(CFLAGS)"
+        "#;
+        let project_root = ProjectRoot(PathBuf::from("/project"));
+        let project = PartialProjectToml::new(project_toml, project_root.clone())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        let run_args = project
+            .run()
+            .unwrap()
+            .current_platform()
+            .args
+            .clone()
+            .unwrap();
+        assert_eq!(
+            run_args.into_iter().collect::<Vec<_>>(),
+            vec!["my-package-1.0.0".to_string(), "$(LUADIR)".to_string()]
+        );
+        let header = project
+            .external_dependencies()
+            .current_platform()
+            .get("FOO")
+            .unwrap()
+            .header
+            .clone();
+        assert_eq!(header, Some(PathBuf::from("/project/include/foo.h")));
+        match &project.build().current_platform().build_backend {
+            Some(BuildBackendSpec::Make(spec)) => assert_eq!(
+                spec.build_variables.get("CFLAGS").unwrap(),
+                "-I/project/include $(CFLAGS)"
+            ),
+            _ => panic!("expected a make build backend"),
+        }
+
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [external_dependencies.FOO]
+        header = "$(LUX_UNDEFINED_VARIABLE)/foo.h"
+        "#;
+        let err = PartialProjectToml::new(project_toml, project_root).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("undefined variable $(LUX_UNDEFINED_VARIABLE)"));
+    }
+
     #[test]
     fn project_toml_parsing() {
         let project_toml = r#"
@@ -1497,6 +1981,349 @@ mod tests {
             .unwrap_err();
     }
 
+    #[test]
+    fn project_toml_with_dependency_variables() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [dependencies]
+        foo = "1.0"
+
+        [dependencies.luaossl]
+        version = "20250929"
+
+        [dependencies.luaossl.variables]
+        OPENSSL_DIR = "/opt/openssl"
+        CRYPTO_LIBDIR = "/opt/openssl/lib"
+        "#;
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        let dependencies = project_toml.dependencies().current_platform();
+        let luaossl = dependencies
+            .iter()
+            .find(|dep| dep.name() == &"luaossl".into())
+            .unwrap();
+        assert_eq!(
+            luaossl.variables().get("OPENSSL_DIR"),
+            Some(&"/opt/openssl".to_string())
+        );
+        assert_eq!(
+            luaossl.variables().get("CRYPTO_LIBDIR"),
+            Some(&"/opt/openssl/lib".to_string())
+        );
+        let foo = dependencies
+            .iter()
+            .find(|dep| dep.name() == &"foo".into())
+            .unwrap();
+        assert!(foo.variables().is_empty());
+    }
+
+    #[test]
+    fn project_toml_with_debug_symbols() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+        debug_symbols = "split"
+
+        [dependencies]
+        foo = "1.0"
+
+        [dependencies.luaossl]
+        version = "20250929"
+        debug_symbols = "keep"
+
+        [profile.release]
+        debug_symbols = "strip"
+        "#;
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        assert_eq!(project_toml.debug_symbols(), Some(DebugSymbols::Split));
+        assert_eq!(
+            project_toml.profile("release").unwrap().debug_symbols(),
+            Some(DebugSymbols::Strip)
+        );
+        assert_eq!(project_toml.profile("dev").unwrap().debug_symbols(), None);
+        let project_toml = project_toml.into_local().unwrap();
+        let dependencies = project_toml.dependencies().current_platform();
+        let luaossl = dependencies
+            .iter()
+            .find(|dep| dep.name() == &"luaossl".into())
+            .unwrap();
+        assert_eq!(luaossl.debug_symbols(), Some(DebugSymbols::Keep));
+        let foo = dependencies
+            .iter()
+            .find(|dep| dep.name() == &"foo".into())
+            .unwrap();
+        assert_eq!(foo.debug_symbols(), None);
+    }
+
+    #[test]
+    fn project_toml_with_test_timeout() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [test]
+        type = "busted"
+        timeout = 600
+        "#;
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        assert_eq!(project_toml.test_timeout(), Some(Duration::from_secs(600)));
+        assert!(!project_toml.test_hermetic());
+    }
+
+    #[test]
+    fn project_toml_with_cwd_and_env() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [run]
+        args = ["main.lua"]
+        cwd = "app"
+        env = { FOO = "$(CONFDIR)" }
+
+        [test]
+        type = "busted"
+        cwd = "spec"
+        env = { BAR = "bar" }
+        "#;
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        let run_spec = project_toml.run().unwrap().current_platform();
+        assert_eq!(run_spec.cwd, Some(PathBuf::from("app")));
+        assert_eq!(run_spec.env["FOO"], "$(CONFDIR)");
+        assert_eq!(project_toml.test_cwd(), Some(Path::new("spec")));
+        assert_eq!(project_toml.test_env()["BAR"], "bar");
+    }
+
+    #[test]
+    fn project_toml_with_toolchain() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [build]
+        type = "builtin"
+
+        [build.toolchain]
+        cc = "clang"
+        cflags = ["-fsanitize=address"]
+        ldflags = ["-fsanitize=address"]
+        "#;
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        let toolchain = project_toml
+            .build()
+            .current_platform()
+            .toolchain
+            .clone()
+            .unwrap();
+        assert_eq!(toolchain.cc(), Some("clang"));
+        assert_eq!(toolchain.cxx(), None);
+        assert_eq!(toolchain.cflags(), ["-fsanitize=address"]);
+        assert_eq!(toolchain.ldflags(), ["-fsanitize=address"]);
+    }
+
+    #[test]
+    fn project_toml_with_build_platform_overrides() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [source]
+        url = "https://example.com"
+
+        [build]
+        type = "builtin"
+
+        [build.modules.foo]
+        sources = ["csrc/foo.c"]
+        defines = ["FOO_PLATFORM=unknown"]
+
+        [build.platforms.windows.modules.foo]
+        defines = ["FOO_PLATFORM=windows"]
+
+        [build.platforms.unix.modules.foo]
+        defines = ["FOO_PLATFORM=unix"]
+
+        [build.platforms.linux.modules.foo]
+        defines = ["FOO_PLATFORM=linux"]
+        "#;
+
+        let defines = |build: &PerPlatform<BuildSpec>, platform: PlatformIdentifier| match &build
+            .get(&platform)
+            .build_backend
+        {
+            Some(BuildBackendSpec::Builtin(spec)) => {
+                match &spec.modules[&LuaModule::from_str("foo").unwrap()] {
+                    ModuleSpec::ModulePaths(paths) => {
+                        assert_eq!(paths.sources, vec![PathBuf::from("csrc/foo.c")]);
+                        paths.defines.clone()
+                    }
+                    module_spec => panic!("unexpected module spec: {module_spec:?}"),
+                }
+            }
+            backend => panic!("unexpected build backend: {backend:?}"),
+        };
+        let define = |value: &str| vec![("FOO_PLATFORM".to_string(), Some(value.to_string()))];
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default()).unwrap();
+        let local_project_toml = project_toml.into_local().unwrap();
+        let build = local_project_toml.build();
+        assert_eq!(
+            defines(build, PlatformIdentifier::Unknown("solaris".into())),
+            define("unknown")
+        );
+        assert_eq!(
+            defines(build, PlatformIdentifier::Windows),
+            define("windows")
+        );
+        assert_eq!(defines(build, PlatformIdentifier::FreeBSD), define("unix"));
+        assert_eq!(defines(build, PlatformIdentifier::Linux), define("linux"));
+
+        // The overrides are preserved in the generated rockspec
+        let rockspec = project_toml
+            .into_remote()
+            .unwrap()
+            .to_lua_rockspec()
+            .unwrap();
+        assert_eq!(rockspec.build(), build);
+    }
+
+    #[test]
+    fn project_toml_with_test_suites() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [test]
+        type = "busted"
+        timeout = 600
+        default_suite = "unit"
+
+        [test.unit]
+        flags = ["--run=unit"]
+
+        [test.integration]
+        type = "command"
+        command = "make"
+        timeout = 1200
+
+        [test.integration.test_dependencies]
+        foo = "1.0"
+
+        [test.legacy]
+        type = "command"
+        lua_script = "test.lua"
+        "#;
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        assert_eq!(
+            project_toml.test_suite_names().collect::<Vec<_>>(),
+            vec!["integration", "legacy", "unit"]
+        );
+
+        let unit = project_toml.test_suite(None).unwrap();
+        assert_eq!(unit.name(), Some("unit"));
+        assert!(matches!(
+            unit.spec().current_platform(),
+            TestSpec::Busted(spec) if spec.flags == vec!["--run=unit".to_string()]
+        ));
+        assert_eq!(unit.timeout(), Some(Duration::from_secs(600)));
+
+        let integration = project_toml.test_suite(Some("integration")).unwrap();
+        assert!(matches!(
+            integration.spec().current_platform(),
+            TestSpec::Command(spec) if spec.command == "make"
+        ));
+        assert_eq!(integration.timeout(), Some(Duration::from_secs(1200)));
+        assert!(project_toml
+            .test_dependencies()
+            .current_platform()
+            .iter()
+            .any(|dep| dep.name() == &"foo".into()));
+
+        assert!(project_toml.test_suite(Some("e2e")).is_err());
+        assert_eq!(project_toml.test_timeout(), Some(Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn project_toml_with_hermetic_tests() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [test]
+        type = "busted"
+        hermetic = true
+        "#;
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        assert!(project_toml.test_hermetic());
+    }
+
+    #[test]
+    fn project_toml_with_format_spec() {
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+        "#;
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        assert_eq!(project_toml.formatting().formatter(), Formatter::Stylua);
+        assert!(!project_toml.formatting().install());
+
+        let project_toml = r#"
+        package = "my-package"
+        version = "1.0.0"
+        lua = "5.1"
+
+        [format]
+        formatter = "lua-format"
+        install = true
+        "#;
+
+        let project_toml = PartialProjectToml::new(project_toml, ProjectRoot::default())
+            .unwrap()
+            .into_local()
+            .unwrap();
+        assert_eq!(project_toml.formatting().formatter(), Formatter::LuaFormat);
+        assert!(project_toml.formatting().install());
+    }
+
     #[test]
     fn project_toml_with_invalid_run_command() {
         for command in ["lua", "lua5.1", "lua5.2", "lua5.3", "lua5.4", "luajit"] {
@@ -1706,4 +2533,22 @@ mod tests {
         }
         assert_eq!(source.unpack_dir, Some("lux-1.0.0".into()));
     }
+
+    /// The JSON schema that editors use must be kept in sync with the `lux.toml` format.
+    /// To regenerate it, run this test with `UPDATE_SCHEMA=1`.
+    #[test]
+    fn project_toml_schema_is_up_to_date() {
+        let schema_path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/lux-toml.schema.json");
+        let schema = serde_json::to_string_pretty(&super::project_toml_schema()).unwrap() + "\n";
+        if std::env::var("UPDATE_SCHEMA").is_ok() {
+            std::fs::write(&schema_path, &schema).unwrap();
+        }
+        let expected = std::fs::read_to_string(&schema_path).unwrap_or_default();
+        assert!(
+            expected == schema,
+            "{} is out of date. Regenerate it with `UPDATE_SCHEMA=1 cargo test -p lux-lib project_toml_schema`",
+            schema_path.display()
+        );
+    }
 }
@@ -0,0 +1,151 @@
+use std::str::FromStr;
+
+use itertools::Itertools;
+use thiserror::Error;
+
+/// The name of the file in a project's root that, if present,
+/// is used as the template for generated rockspecs.
+pub const ROCKSPEC_TEMPLATE: &str = "rockspec.template";
+
+/// The fields of a generated rockspec, in the order they are written in by default.
+pub const ROCKSPEC_FIELDS: [&str; 13] = [
+    "rockspec_format",
+    "package",
+    "version",
+    "description",
+    "supported_platforms",
+    "dependencies",
+    "build_dependencies",
+    "external_dependencies",
+    "test_dependencies",
+    "source",
+    "test",
+    "deploy",
+    "build",
+];
+
+/// A template that controls the layout of generated rockspecs,
+/// so that projects can enforce the ordering of fields and add comments.
+///
+/// The rockspec's fields are inserted with `{{field}}` placeholders, e.g.:
+///
+/// ```lua
+/// -- Maintained by the platform team.
+/// {{package}}
+/// {{version}}
+/// {{rockspec_format}}
+///
+/// {{source}}
+/// {{build}}
+/// ```
+///
+/// Placeholders of fields that aren't set are removed,
+/// and fields that are set, but not in the template, are appended to the end.
+#[derive(Debug, Clone)]
+pub struct RockspecTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Text(String),
+    Field(String),
+}
+
+#[derive(Debug, Error)]
+pub enum RockspecTemplateError {
+    #[error("unknown rockspec field in template: {field}. Allowed fields: {allowed}", field = ._0, allowed = ROCKSPEC_FIELDS.join(", "))]
+    UnknownField(String),
+    #[error("unclosed placeholder in rockspec template: {0}")]
+    UnclosedPlaceholder(String),
+}
+
+impl FromStr for RockspecTemplate {
+    type Err = RockspecTemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            segments.push(Segment::Text(rest[..start].to_string()));
+            let placeholder = &rest[start + 2..];
+            let end = placeholder.find("}}").ok_or_else(|| {
+                RockspecTemplateError::UnclosedPlaceholder(
+                    rest[start..].lines().next().unwrap_or_default().to_string(),
+                )
+            })?;
+            let field = placeholder[..end].trim();
+            if !ROCKSPEC_FIELDS.contains(&field) {
+                return Err(RockspecTemplateError::UnknownField(field.to_string()));
+            }
+            segments.push(Segment::Field(field.to_string()));
+            rest = &placeholder[end + 2..];
+        }
+        segments.push(Segment::Text(rest.to_string()));
+        Ok(Self { segments })
+    }
+}
+
+impl RockspecTemplate {
+    /// Render the template with the rockspec's `fields`, which map the field names
+    /// to their Lua code, or `None` if they aren't set.
+    pub(crate) fn render(&self, fields: &[(&str, Option<String>)]) -> String {
+        let mut result = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.as_str(),
+                Segment::Field(name) => fields
+                    .iter()
+                    .find(|(field, _)| field == name)
+                    .and_then(|(_, value)| value.as_deref())
+                    .unwrap_or_default(),
+            })
+            .collect::<String>();
+        let remaining = fields
+            .iter()
+            .filter(|(field, _)| !self.contains(field))
+            .filter_map(|(_, value)| value.as_deref())
+            .join("\n\n");
+        if !remaining.is_empty() {
+            result = format!("{}\n\n{remaining}\n", result.trim_end());
+        }
+        result
+    }
+
+    fn contains(&self, field: &str) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Field(name) if name == field))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_rockspec_template() {
+        let template: RockspecTemplate = "-- header\n{{ package }}\n{{version}}\n{{description}}\n"
+            .parse()
+            .unwrap();
+        let fields = [
+            ("package", Some("package = \"foo\"".to_string())),
+            ("version", Some("version = \"1.0.0-1\"".to_string())),
+            ("description", None),
+            ("build", Some("build = {}".to_string())),
+        ];
+        assert_eq!(
+            template.render(&fields),
+            "-- header\npackage = \"foo\"\nversion = \"1.0.0-1\"\n\nbuild = {}\n"
+        );
+        assert!(matches!(
+            "{{ packag }}".parse::<RockspecTemplate>(),
+            Err(RockspecTemplateError::UnknownField(field)) if field == "packag"
+        ));
+        assert!(matches!(
+            "{{ package".parse::<RockspecTemplate>(),
+            Err(RockspecTemplateError::UnclosedPlaceholder(_))
+        ));
+    }
+}
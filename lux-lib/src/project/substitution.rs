@@ -0,0 +1,128 @@
+use serde::Deserialize;
+use toml::{Table, Value};
+
+use crate::{
+    package::HasModRev,
+    variables::{
+        self, Deferred, Environment, GetVariableError, HasVariables, VariableSubstitutionError,
+    },
+};
+
+use super::{gen::PackageVersionTemplate, ProjectRoot};
+
+/// The `lux.toml` fields in which the project's variables are substituted,
+/// and whether they are substituted again later, e.g. when building the project.
+/// Variables that are unknown when loading the `lux.toml` are left as they are
+/// for the later substitution, which also has access to environment variables.
+const FIELDS: &[(&str, &[&str], bool)] = &[
+    (
+        "build",
+        &["variables", "build_variables", "install_variables"],
+        true,
+    ),
+    ("external_dependencies", &["header", "library"], false),
+    ("test", &["flags", "cwd", "env"], true),
+    ("run", &["args", "cwd", "env"], true),
+];
+
+/// The variables of a project that can be used in its `lux.toml`:
+///
+/// - `$(PROJECT_ROOT)`: The project's root directory
+/// - `$(PACKAGE)`: The package name
+/// - `$(VERSION)`: The package version, which may be generated from git tags
+struct ProjectVariables<'a> {
+    project_root: &'a ProjectRoot,
+    package: Option<&'a str>,
+    version: Option<&'a Value>,
+}
+
+impl HasVariables for ProjectVariables<'_> {
+    fn get_variable(&self, input: &str) -> Result<Option<String>, GetVariableError> {
+        Ok(match input {
+            "PROJECT_ROOT" => Some(self.project_root.to_string_lossy().to_string()),
+            "PACKAGE" => self.package.map(str::to_string),
+            "VERSION" => {
+                let template = match self.version {
+                    Some(version) => PackageVersionTemplate::deserialize(version.clone())
+                        .map_err(GetVariableError::new)?,
+                    None => PackageVersionTemplate::default(),
+                };
+                let version = template
+                    .try_generate(self.project_root)
+                    .map_err(GetVariableError::new)?;
+                Some(version.to_modrev_string())
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Substitute the project's variables in the `lux.toml` fields that support them.
+/// Environment variables are substituted in fields that are not substituted again later.
+pub(crate) fn substitute_project_variables(
+    toml: &mut Table,
+    project_root: &ProjectRoot,
+) -> Result<(), VariableSubstitutionError> {
+    let project_variables = ProjectVariables {
+        project_root,
+        package: toml.get("package").and_then(Value::as_str),
+        version: toml.get("version"),
+    };
+    let mut substituted = Vec::new();
+    for (section, fields, deferred) in FIELDS {
+        if let Some(value) = toml.get(*section) {
+            let mut value = value.clone();
+            let variables: [&dyn HasVariables; 2] = if *deferred {
+                [&project_variables, &Deferred]
+            } else {
+                [&project_variables, &Environment {}]
+            };
+            substitute_fields(&mut value, fields, &variables)?;
+            substituted.push((*section, value));
+        }
+    }
+    for (section, value) in substituted {
+        toml.insert(section.to_string(), value);
+    }
+    Ok(())
+}
+
+/// Substitute the variables in the `fields` of a table, or of its nested tables,
+/// e.g. platform overrides.
+fn substitute_fields(
+    value: &mut Value,
+    fields: &[&str],
+    variables: &[&dyn HasVariables],
+) -> Result<(), VariableSubstitutionError> {
+    if let Value::Table(table) = value {
+        for (key, value) in table.iter_mut() {
+            if fields.contains(&key.as_str()) {
+                substitute_strings(value, variables)?;
+            } else {
+                substitute_fields(value, fields, variables)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn substitute_strings(
+    value: &mut Value,
+    variables: &[&dyn HasVariables],
+) -> Result<(), VariableSubstitutionError> {
+    match value {
+        Value::String(str) => *str = variables::substitute(variables, str)?,
+        Value::Array(values) => {
+            for value in values {
+                substitute_strings(value, variables)?;
+            }
+        }
+        Value::Table(table) => {
+            for (_, value) in table.iter_mut() {
+                substitute_strings(value, variables)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
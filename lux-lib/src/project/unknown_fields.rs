@@ -0,0 +1,390 @@
+use std::fmt::Display;
+
+use toml_edit::{Document, Item};
+
+use crate::manifest::edit_distance;
+
+/// The fields of a `lux.toml` table, which are used to detect unknown fields, e.g. typos,
+/// which would otherwise be ignored silently when deserializing.
+#[derive(Clone, Copy)]
+enum Schema {
+    /// A value whose fields aren't checked, e.g. a table of variables.
+    Any,
+    /// A table with known fields.
+    /// Tables with other names are checked against the fallback schema if there is one,
+    /// e.g. the named test suites in the `[test]` table.
+    Table(&'static [(&'static str, Schema)], Option<&'static Schema>),
+    /// A table with arbitrary keys, e.g. package names, whose values have the same schema.
+    Map(&'static Schema),
+}
+
+use Schema::{Any, Map, Table};
+
+const DEPENDENCIES: Schema = Map(&Table(
+    &[
+        ("version", Any),
+        ("opt", Any),
+        ("pin", Any),
+        ("git", Any),
+        ("rev", Any),
+        ("variables", Any),
+        ("debug_symbols", Any),
+        ("build_env", Any),
+    ],
+    None,
+));
+
+/// The fields of the `[build]` table, followed by the given extra fields.
+macro_rules! build_fields {
+    ($($extra:expr),*) => {
+        &[
+            ("type", Any),
+            ("modules", Any),
+            ("makefile", Any),
+            ("build_target", Any),
+            ("build_pass", Any),
+            ("install_target", Any),
+            ("install_pass", Any),
+            ("build_variables", Any),
+            ("install_variables", Any),
+            ("variables", Any),
+            ("cmake", Any),
+            ("build_command", Any),
+            ("install_command", Any),
+            (
+                "install",
+                Table(
+                    &[("lua", Any), ("lib", Any), ("conf", Any), ("bin", Any)],
+                    None,
+                ),
+            ),
+            ("copy_directories", Any),
+            ("patches", Any),
+            ("target_path", Any),
+            ("default_features", Any),
+            ("include", Any),
+            ("features", Any),
+            ("lang", Any),
+            ("parser", Any),
+            ("generate", Any),
+            ("generate_from_grammar", Any),
+            ("location", Any),
+            ("queries", Any),
+            ("toolchain", Any),
+            $($extra),*
+        ]
+    };
+}
+
+/// A `[build.platforms.<platform>]` override, which can't contain further overrides.
+const BUILD_PLATFORM_OVERRIDE: Schema = Table(build_fields!(), None);
+
+const BUILD: Schema = Table(
+    build_fields!(("platforms", Map(&BUILD_PLATFORM_OVERRIDE))),
+    None,
+);
+
+const TEST_SUITE: Schema = Table(
+    &[
+        ("type", Any),
+        ("flags", Any),
+        ("command", Any),
+        ("script", Any),
+        ("lua_script", Any),
+        ("timeout", Any),
+        ("hermetic", Any),
+        ("cwd", Any),
+        ("env", Any),
+        ("test_dependencies", DEPENDENCIES),
+    ],
+    None,
+);
+
+const TEST: Schema = Table(
+    &[
+        ("type", Any),
+        ("flags", Any),
+        ("command", Any),
+        ("script", Any),
+        ("lua_script", Any),
+        ("timeout", Any),
+        ("hermetic", Any),
+        ("cwd", Any),
+        ("env", Any),
+        ("default_suite", Any),
+    ],
+    Some(&TEST_SUITE),
+);
+
+const LUX_TOML: Schema = Table(
+    &[
+        ("package", Any),
+        ("version", Any),
+        ("build", BUILD),
+        ("rockspec_format", Any),
+        (
+            "run",
+            Table(
+                &[("command", Any), ("args", Any), ("cwd", Any), ("env", Any)],
+                None,
+            ),
+        ),
+        ("lint", Table(&[("linters", Any)], None)),
+        (
+            "check",
+            Table(&[("allow_modules", Any), ("allow_unused", Any)], None),
+        ),
+        (
+            "format",
+            Table(&[("formatter", Any), ("install", Any)], None),
+        ),
+        ("lua", Any),
+        (
+            "description",
+            Table(
+                &[
+                    ("summary", Any),
+                    ("detailed", Any),
+                    ("license", Any),
+                    ("homepage", Any),
+                    ("issues_url", Any),
+                    ("maintainer", Any),
+                    ("labels", Any),
+                ],
+                None,
+            ),
+        ),
+        ("supported_platforms", Any),
+        ("dependencies", DEPENDENCIES),
+        ("build_dependencies", DEPENDENCIES),
+        (
+            "external_dependencies",
+            Map(&Table(&[("header", Any), ("library", Any)], None)),
+        ),
+        ("test_dependencies", DEPENDENCIES),
+        (
+            "source",
+            Table(
+                &[
+                    ("url", Any),
+                    ("dev", Any),
+                    ("file", Any),
+                    ("dir", Any),
+                    ("tag", Any),
+                ],
+                None,
+            ),
+        ),
+        ("test", TEST),
+        (
+            "deploy",
+            Table(
+                &[("wrap_bin_scripts", Any), ("generate_helptags", Any)],
+                None,
+            ),
+        ),
+        ("constraints", Any),
+        (
+            "profile",
+            Map(&Table(
+                &[("cflags", Any), ("ldflags", Any), ("debug_symbols", Any)],
+                None,
+            )),
+        ),
+        ("debug_symbols", Any),
+    ],
+    None,
+);
+
+/// A field in a `lux.toml` that lux doesn't know, e.g. because it is misspelled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownField {
+    path: String,
+    location: Option<(usize, usize)>,
+    suggestion: Option<String>,
+}
+
+impl UnknownField {
+    /// The dotted path of the field, e.g. `build.instal`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The line and column of the field's key, starting at 1.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.location
+    }
+
+    /// The known field whose name is closest to the unknown field's, if any is close enough.
+    pub fn suggestion(&self) -> Option<&str> {
+        self.suggestion.as_deref()
+    }
+}
+
+impl Display for UnknownField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown field `{}`", self.path)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{suggestion}`?)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the fields in the content of a `lux.toml` that lux doesn't know.
+/// If the content can't be parsed, no fields are reported,
+/// as the parse error is reported when deserializing the `lux.toml`.
+pub(crate) fn unknown_fields(content: &str) -> Vec<UnknownField> {
+    let mut unknown = Vec::new();
+    if let Ok(document) = Document::parse(content) {
+        check_fields(document.as_item(), LUX_TOML, "", content, &mut unknown);
+    }
+    unknown
+}
+
+fn check_fields(
+    item: &Item,
+    schema: Schema,
+    path: &str,
+    content: &str,
+    unknown: &mut Vec<UnknownField>,
+) {
+    let Some(table) = item.as_table_like() else {
+        return;
+    };
+    let field_path = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+    for (key, item) in table.iter() {
+        match schema {
+            Any => {}
+            Map(schema) => check_fields(item, *schema, &field_path(key), content, unknown),
+            Table(fields, fallback) => {
+                match (fields.iter().find(|(field, _)| *field == key), fallback) {
+                    (Some((_, schema)), _) => {
+                        check_fields(item, *schema, &field_path(key), content, unknown)
+                    }
+                    (None, Some(schema)) if item.is_table_like() => {
+                        check_fields(item, *schema, &field_path(key), content, unknown)
+                    }
+                    (None, _) => unknown.push(UnknownField {
+                        path: field_path(key),
+                        location: table
+                            .key(key)
+                            .and_then(|key| key.span())
+                            .map(|span| location(content, span.start)),
+                        suggestion: suggestion(key, fields),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+/// The line and column of a byte offset, starting at 1.
+fn location(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit_once('\n')
+        .map_or(before, |(_, line)| line)
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// The known field that is closest to `key`, allowing about one typo for every three characters.
+fn suggestion(key: &str, fields: &[(&str, Schema)]) -> Option<String> {
+    let max_distance = (key.chars().count() / 3).max(1);
+    fields
+        .iter()
+        .map(|(field, _)| (edit_distance(key, field), *field))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_unknown_fields() {
+        let content = r#"
+package = "foo"
+version = "1.0.0"
+lua = ">=5.1"
+
+[build_dependences]
+bar = "1.0"
+
+[dependencies]
+baz = { version = "1.0", optional = true }
+
+[build]
+type = "builtin"
+instal = { bin = { foo = "bin/foo" } }
+
+[test]
+type = "busted"
+
+[test.integration]
+flags = ["--tags=integration"]
+scrip = "test.lua"
+
+[variables]
+FOO = "bar"
+"#;
+        let unknown = unknown_fields(content);
+        assert_eq!(
+            unknown
+                .iter()
+                .map(|field| (field.path(), field.location(), field.suggestion()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "build_dependences",
+                    Some((6, 2)),
+                    Some("build_dependencies")
+                ),
+                ("dependencies.baz.optional", Some((10, 26)), None),
+                ("build.instal", Some((14, 1)), Some("install")),
+                ("test.integration.scrip", Some((21, 1)), Some("script")),
+                ("variables", Some((23, 2)), None),
+            ]
+        );
+        assert_eq!(
+            unknown[0].to_string(),
+            "unknown field `build_dependences` (did you mean `build_dependencies`?)"
+        );
+    }
+
+    #[test]
+    fn no_unknown_fields() {
+        let content = r#"
+package = "foo"
+version = "1.0.0"
+
+[dependencies]
+bar = "1.0"
+
+[build]
+type = "builtin"
+
+[build.install.bin]
+foo = "bin/foo"
+
+[build.platforms.windows.modules.foo]
+defines = ["FOO_WINDOWS"]
+
+[profile.dev]
+cflags = ["-O0"]
+"#;
+        assert!(unknown_fields(content).is_empty());
+    }
+}
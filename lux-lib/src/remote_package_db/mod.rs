@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use crate::{
-    config::{Config, ConfigError},
+    config::{Config, ConfigError, ResolutionStrategy},
     lockfile::{LocalPackageLock, LockfileIntegrityError},
     manifest::{Manifest, ManifestError},
     package::{
@@ -19,7 +19,8 @@ pub struct RemotePackageDB(Impl);
 
 #[derive(Clone, Debug)]
 enum Impl {
-    LuarocksManifests(Vec<Manifest>),
+    /// Manifests in order of precedence, with the strategy for selecting among matching versions.
+    LuarocksManifests(Vec<Manifest>, ResolutionStrategy),
     Lock(LocalPackageLock),
 }
 
@@ -64,20 +65,39 @@ impl RemotePackageDB {
             manifests.push(manifest);
         }
         manifests.push(Manifest::from_config(config.server().clone(), config, progress).await?);
-        Ok(Self(Impl::LuarocksManifests(manifests)))
+        Ok(Self(Impl::LuarocksManifests(
+            manifests,
+            config.resolution_strategy(),
+        )))
     }
 
-    /// Find a remote package that matches the requirement, returning the latest match.
+    /// Find a remote package that matches the requirement,
+    /// selecting among the matches according to the config's [`ResolutionStrategy`].
+    /// The first server that has a match takes precedence.
     pub(crate) fn find(
         &self,
         package_req: &PackageReq,
         filter: Option<RemotePackageTypeFilterSpec>,
         progress: &Progress<ProgressBar>,
+    ) -> Result<RemotePackage, SearchError> {
+        let strategy = match &self.0 {
+            Impl::LuarocksManifests(_, strategy) => *strategy,
+            Impl::Lock(_) => ResolutionStrategy::default(),
+        };
+        self.find_with_strategy(package_req, filter, strategy, progress)
+    }
+
+    fn find_with_strategy(
+        &self,
+        package_req: &PackageReq,
+        filter: Option<RemotePackageTypeFilterSpec>,
+        strategy: ResolutionStrategy,
+        progress: &Progress<ProgressBar>,
     ) -> Result<RemotePackage, SearchError> {
         match &self.0 {
-            Impl::LuarocksManifests(manifests) => match manifests.iter().find_map(|manifest| {
+            Impl::LuarocksManifests(manifests, _) => match manifests.iter().find_map(|manifest| {
                 progress.map(|p| p.set_message(format!("🔎 Searching {}", &manifest.server_url())));
-                manifest.find(package_req, filter.clone())
+                manifest.find(package_req, filter.clone(), strategy)
             }) {
                 Some(package) => Ok(package),
                 None => Err(SearchError::RockNotFound(package_req.clone())),
@@ -100,7 +120,7 @@ impl RemotePackageDB {
     /// Search for all packages that match the requirement.
     pub fn search(&self, package_req: &PackageReq) -> Vec<(&PackageName, Vec<&PackageVersion>)> {
         match &self.0 {
-            Impl::LuarocksManifests(manifests) => manifests
+            Impl::LuarocksManifests(manifests, _) => manifests
                 .iter()
                 .flat_map(|manifest| {
                     manifest
@@ -127,8 +147,8 @@ impl RemotePackageDB {
                 .collect(),
             Impl::Lock(lockfile) => lockfile
                 .rocks()
-                .iter()
-                .filter_map(|(_, package)| {
+                .values()
+                .filter_map(|package| {
                     // NOTE: This doesn't group packages by name, but we don't care for now,
                     // as we shouldn't need to use this function with a lockfile.
                     let name = package.name();
@@ -142,17 +162,127 @@ impl RemotePackageDB {
         }
     }
 
+    /// Search for all packages that match the requirement and ship executables.
+    /// Only manifests that list the packages' commands are taken into account.
+    pub fn search_binaries(
+        &self,
+        package_req: &PackageReq,
+    ) -> Vec<(&PackageName, Vec<&PackageVersion>)> {
+        match &self.0 {
+            Impl::LuarocksManifests(manifests, _) => manifests
+                .iter()
+                .flat_map(|manifest| {
+                    let metadata = manifest.metadata();
+                    metadata
+                        .repository
+                        .iter()
+                        .filter(|(name, _)| {
+                            name.to_string().contains(&package_req.name().to_string())
+                        })
+                        .filter_map(|(name, elements)| {
+                            let versions = elements
+                                .keys()
+                                .filter(|version| {
+                                    package_req.version_req().matches(version)
+                                        && metadata.has_commands(name, version)
+                                })
+                                .sorted_by(|a, b| Ord::cmp(b, a))
+                                .collect_vec();
+                            (!versions.is_empty()).then_some((name, versions))
+                        })
+                })
+                .collect(),
+            Impl::Lock(lockfile) => lockfile
+                .rocks()
+                .values()
+                .filter_map(|package| {
+                    let name = package.name();
+                    if name.to_string().contains(&package_req.name().to_string())
+                        && package_req.version_req().matches(package.version())
+                        && !package.spec.binaries().is_empty()
+                    {
+                        Some((name, vec![package.version()]))
+                    } else {
+                        None
+                    }
+                })
+                .collect_vec(),
+        }
+    }
+
+    /// Search for all packages that provide a Lua module, e.g. `lpeg.re`.
+    /// Only manifests that list the packages' modules are taken into account.
+    pub fn search_module(&self, module: &str) -> Vec<(&PackageName, Vec<&PackageVersion>)> {
+        match &self.0 {
+            Impl::LuarocksManifests(manifests, _) => manifests
+                .iter()
+                .flat_map(|manifest| {
+                    manifest
+                        .metadata()
+                        .module_providers(module)
+                        .into_iter()
+                        .into_group_map()
+                        .into_iter()
+                        .map(|(name, versions)| {
+                            (
+                                name,
+                                versions
+                                    .into_iter()
+                                    .sorted_by(|a, b| Ord::cmp(b, a))
+                                    .collect_vec(),
+                            )
+                        })
+                })
+                .collect(),
+            // The lockfile doesn't record the packages' modules.
+            Impl::Lock(_) => Vec::new(),
+        }
+    }
+
+    /// Much more popular packages with a name similar to `package_name`,
+    /// which may indicate that `package_name` is typosquatting them.
+    /// Only the manifest the package is published in is taken into account.
+    pub fn similar_popular_packages(&self, package_name: &PackageName) -> Vec<&PackageName> {
+        match &self.0 {
+            Impl::LuarocksManifests(manifests, _) => manifests
+                .iter()
+                .flat_map(|manifest| manifest.metadata().similar_popular_packages(package_name))
+                .unique()
+                .collect_vec(),
+            // Lockfile dependencies have already been vetted when they were added.
+            Impl::Lock(_) => Vec::new(),
+        }
+    }
+
     /// Find the latest version for a package by name.
     pub(crate) fn latest_version(&self, rock_name: &PackageName) -> Option<PackageVersion> {
         self.latest_match(&rock_name.clone().into(), None)
             .map(|result| result.version().clone())
     }
 
-    /// Find the latest package that matches the requirement.
+    /// Find the latest package that matches the requirement,
+    /// regardless of the resolution strategy.
     pub fn latest_match(
         &self,
         package_req: &PackageReq,
         filter: Option<RemotePackageTypeFilterSpec>,
+    ) -> Option<PackageSpec> {
+        match self.find_with_strategy(
+            package_req,
+            filter,
+            ResolutionStrategy::Highest,
+            &Progress::NoProgress,
+        ) {
+            Ok(result) => Some(result.package),
+            Err(_) => None,
+        }
+    }
+
+    /// Find the package that the resolver selects for the requirement.
+    pub fn resolve(
+        &self,
+        package_req: &PackageReq,
+        filter: Option<RemotePackageTypeFilterSpec>,
     ) -> Option<PackageSpec> {
         match self.find(package_req, filter, &Progress::NoProgress) {
             Ok(result) => Some(result.package),
@@ -175,6 +305,30 @@ impl UserData for RemotePackageDB {
                 })
                 .collect::<HashMap<_, _>>())
         });
+        methods.add_method("search_binaries", |_, this, package_req: PackageReq| {
+            Ok(this
+                .search_binaries(&package_req)
+                .into_iter()
+                .map(|(package_name, versions)| {
+                    (
+                        package_name.clone(),
+                        versions.into_iter().cloned().collect_vec(),
+                    )
+                })
+                .collect::<HashMap<_, _>>())
+        });
+        methods.add_method("search_module", |_, this, module: String| {
+            Ok(this
+                .search_module(&module)
+                .into_iter()
+                .map(|(package_name, versions)| {
+                    (
+                        package_name.clone(),
+                        versions.into_iter().cloned().collect_vec(),
+                    )
+                })
+                .collect::<HashMap<_, _>>())
+        });
         methods.add_method("latest_match", |_, this, package_req| {
             Ok(this.latest_match(&package_req, None))
         });
@@ -183,7 +337,10 @@ impl UserData for RemotePackageDB {
 
 impl From<Manifest> for RemotePackageDB {
     fn from(manifest: Manifest) -> Self {
-        Self(Impl::LuarocksManifests(vec![manifest]))
+        Self(Impl::LuarocksManifests(
+            vec![manifest],
+            ResolutionStrategy::default(),
+        ))
     }
 }
 
@@ -5,6 +5,7 @@ use serde::{Deserialize, Deserializer};
 use thiserror::Error;
 
 use crate::{
+    build::debug_symbols::DebugSymbols,
     lockfile::{OptState, PinnedState},
     lua_rockspec::{
         ExternalDependencySpec, PartialOverride, PerPlatform, PlatformOverridable, RockSourceSpec,
@@ -24,6 +25,12 @@ pub struct LuaDependencySpec {
     pub(crate) pin: PinnedState,
     pub(crate) opt: OptState,
     pub(crate) source: Option<RockSourceSpec>,
+    /// Build variables that apply only when building this package.
+    pub(crate) variables: HashMap<String, String>,
+    /// What to do with the debug symbols of this package's C modules, if set.
+    pub(crate) debug_symbols: Option<DebugSymbols>,
+    /// Environment variables that this package's build processes may inherit, if set.
+    pub(crate) build_env: Option<Vec<String>>,
 }
 
 impl LuaDependencySpec {
@@ -39,6 +46,15 @@ impl LuaDependencySpec {
     pub fn source(&self) -> &Option<RockSourceSpec> {
         &self.source
     }
+    pub fn variables(&self) -> &HashMap<String, String> {
+        &self.variables
+    }
+    pub fn debug_symbols(&self) -> Option<DebugSymbols> {
+        self.debug_symbols
+    }
+    pub fn build_env(&self) -> Option<&Vec<String>> {
+        self.build_env.as_ref()
+    }
     pub fn into_package_req(self) -> PackageReq {
         self.package_req
     }
@@ -51,6 +67,12 @@ impl LuaDependencySpec {
     pub fn matches(&self, package: &PackageSpec) -> bool {
         self.package_req.matches(package)
     }
+    pub fn with_pin(self, pin: PinnedState) -> Self {
+        Self { pin, ..self }
+    }
+    pub fn with_opt(self, opt: OptState) -> Self {
+        Self { opt, ..self }
+    }
 }
 
 impl From<PackageName> for LuaDependencySpec {
@@ -60,6 +82,9 @@ impl From<PackageName> for LuaDependencySpec {
             pin: PinnedState::default(),
             opt: OptState::default(),
             source: None,
+            variables: HashMap::default(),
+            debug_symbols: None,
+            build_env: None,
         }
     }
 }
@@ -71,6 +96,9 @@ impl From<PackageReq> for LuaDependencySpec {
             pin: PinnedState::default(),
             opt: OptState::default(),
             source: None,
+            variables: HashMap::default(),
+            debug_symbols: None,
+            build_env: None,
         }
     }
 }
@@ -85,6 +113,9 @@ impl FromStr for LuaDependencySpec {
             pin: PinnedState::default(),
             opt: OptState::default(),
             source: None,
+            variables: HashMap::default(),
+            debug_symbols: None,
+            build_env: None,
         })
     }
 }
@@ -140,6 +171,9 @@ impl FromLua for LuaDependencySpec {
             pin: PinnedState::default(),
             opt: OptState::default(),
             source: None,
+            variables: HashMap::default(),
+            debug_symbols: None,
+            build_env: None,
         })
     }
 }
@@ -155,6 +189,9 @@ impl<'de> Deserialize<'de> for LuaDependencySpec {
             pin: PinnedState::default(),
             opt: OptState::default(),
             source: None,
+            variables: HashMap::default(),
+            debug_symbols: None,
+            build_env: None,
         })
     }
 }
@@ -114,21 +114,18 @@ impl<T: Rockspec> LuaVersionCompatibility for T {
     }
 
     fn supports_lua_version(&self, lua_version: &LuaVersion) -> bool {
-        self.lua().matches(&lua_version.as_version())
+        lua_version.satisfies(self.lua())
     }
 
     fn lua_version(&self) -> Option<LuaVersion> {
-        for (possibility, version) in [
-            ("5.4.0", LuaVersion::Lua54),
-            ("5.3.0", LuaVersion::Lua53),
-            ("5.2.0", LuaVersion::Lua52),
-            ("5.1.0", LuaVersion::Lua51),
-        ] {
-            if self.lua().matches(&possibility.parse().unwrap()) {
-                return Some(version);
-            }
-        }
-        None
+        [
+            LuaVersion::Lua54,
+            LuaVersion::Lua53,
+            LuaVersion::Lua52,
+            LuaVersion::Lua51,
+        ]
+        .into_iter()
+        .find(|version| version.satisfies(self.lua()))
     }
 }
 
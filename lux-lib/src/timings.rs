@@ -0,0 +1,220 @@
+//! Local build metrics, for identifying bottlenecks in large dependency trees.
+//! Nothing is ever sent anywhere.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::{build::compiler_cache::CompilerCacheStats, package::PackageSpec};
+
+/// The number of packages to show in the summary.
+const SLOWEST_PACKAGES_LIMIT: usize = 10;
+
+/// A phase of a build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimingPhase {
+    /// Resolving and downloading rockspecs and packed rocks.
+    Resolve,
+    /// Fetching a package's source.
+    Fetch,
+    /// Compiling a package.
+    Compile,
+    /// Installing a package into the tree.
+    Install,
+}
+
+impl Display for TimingPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Resolve => "resolve",
+            Self::Fetch => "fetch",
+            Self::Compile => "compile",
+            Self::Install => "install",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    pub phase: TimingPhase,
+    /// The package (`<name>@<version>`), if this is a per-package phase.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    pub seconds: f64,
+}
+
+/// A structured report of the time spent in each phase of a build.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimingsReport {
+    pub timings: Vec<PhaseTiming>,
+    /// The compiler cache hits and misses during the build, if a compiler cache is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compiler_cache: Option<CompilerCacheStats>,
+}
+
+impl TimingsReport {
+    /// Total time spent in each phase, summed over all packages.
+    pub fn phase_totals(&self) -> Vec<(TimingPhase, f64)> {
+        self.timings
+            .iter()
+            .into_group_map_by(|timing| timing.phase)
+            .into_iter()
+            .map(|(phase, timings)| (phase, timings.iter().map(|t| t.seconds).sum()))
+            .sorted_by_key(|(phase, _)| *phase as u8)
+            .collect()
+    }
+
+    /// Packages sorted by the total time spent building them, slowest first.
+    pub fn slowest_packages(&self) -> Vec<(String, HashMap<TimingPhase, f64>)> {
+        self.timings
+            .iter()
+            .filter_map(|timing| timing.package.as_ref().map(|pkg| (pkg, timing)))
+            .into_group_map_by(|(pkg, _)| (*pkg).clone())
+            .into_iter()
+            .map(|(pkg, timings)| {
+                let phases = timings.iter().fold(HashMap::new(), |mut acc, (_, t)| {
+                    *acc.entry(t.phase).or_insert(0.0) += t.seconds;
+                    acc
+                });
+                (pkg, phases)
+            })
+            .sorted_by(|(_, a), (_, b)| b.values().sum::<f64>().total_cmp(&a.values().sum::<f64>()))
+            .collect()
+    }
+
+    /// Write the report as JSON, creating the parent directory if necessary.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)
+    }
+}
+
+impl Display for TimingsReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<30} {:>10}", "Phase", "Time (s)")?;
+        for (phase, seconds) in self.phase_totals() {
+            writeln!(f, "{:<30} {:>10.2}", phase.to_string(), seconds)?;
+        }
+        if let Some(compiler_cache) = &self.compiler_cache {
+            writeln!(f)?;
+            writeln!(f, "{compiler_cache}")?;
+        }
+        let slowest = self.slowest_packages();
+        if slowest.is_empty() {
+            return Ok(());
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<30} {:>10} {:>10} {:>10} {:>10}",
+            "Package", "Total (s)", "Fetch", "Compile", "Install"
+        )?;
+        for (package, phases) in slowest.into_iter().take(SLOWEST_PACKAGES_LIMIT) {
+            let get = |phase| phases.get(&phase).copied().unwrap_or_default();
+            writeln!(
+                f,
+                "{:<30} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+                package,
+                phases.values().sum::<f64>(),
+                get(TimingPhase::Fetch),
+                get(TimingPhase::Compile),
+                get(TimingPhase::Install),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A handle for recording build timings.
+/// Cloning it is cheap, and all clones record into the same report.
+#[derive(Debug, Clone, Default)]
+pub struct Timings(Arc<Mutex<TimingsReport>>);
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, phase: TimingPhase, package: Option<&PackageSpec>, start: Instant) {
+        self.push(phase, package, start.elapsed());
+    }
+
+    fn push(&self, phase: TimingPhase, package: Option<&PackageSpec>, duration: Duration) {
+        self.0
+            .lock()
+            .expect("timings lock poisoned")
+            .timings
+            .push(PhaseTiming {
+                phase,
+                package: package.map(|pkg| format!("{}@{}", pkg.name(), pkg.version())),
+                seconds: duration.as_secs_f64(),
+            });
+    }
+
+    /// Record the compiler cache statistics of the build.
+    pub fn set_compiler_cache_stats(&self, stats: CompilerCacheStats) {
+        self.0.lock().expect("timings lock poisoned").compiler_cache = Some(stats);
+    }
+
+    pub fn report(&self) -> TimingsReport {
+        self.0.lock().expect("timings lock poisoned").clone()
+    }
+}
+
+/// Record the time since `start` if timings are enabled.
+pub(crate) trait RecordTiming {
+    fn record(&self, phase: TimingPhase, package: Option<&PackageSpec>, start: Instant);
+}
+
+impl RecordTiming for Option<Timings> {
+    fn record(&self, phase: TimingPhase, package: Option<&PackageSpec>, start: Instant) {
+        if let Some(timings) = self {
+            timings.record(phase, package, start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings_report_summary() {
+        let timings = Timings::new();
+        let foo = PackageSpec::parse("foo".into(), "1.0.0-1".into()).unwrap();
+        let bar = PackageSpec::parse("bar".into(), "2.0.0-1".into()).unwrap();
+        timings.push(TimingPhase::Resolve, None, Duration::from_secs(1));
+        timings.push(TimingPhase::Fetch, Some(&foo), Duration::from_secs(1));
+        timings.push(TimingPhase::Compile, Some(&foo), Duration::from_secs(2));
+        timings.push(TimingPhase::Compile, Some(&bar), Duration::from_secs(5));
+        let report = timings.report();
+        assert_eq!(
+            report.phase_totals(),
+            vec![
+                (TimingPhase::Resolve, 1.0),
+                (TimingPhase::Fetch, 1.0),
+                (TimingPhase::Compile, 7.0),
+            ]
+        );
+        let slowest = report.slowest_packages();
+        assert_eq!(slowest[0].0, "bar@2.0.0-1");
+        assert_eq!(slowest[1].0, "foo@1.0.0-1");
+        assert_eq!(slowest[1].1.get(&TimingPhase::Compile), Some(&2.0));
+        let json = serde_json::to_string(&report).unwrap();
+        let parsed: TimingsReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.timings.len(), 4);
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use ssri::Integrity;
+use walkdir::WalkDir;
+
+use crate::hash::HasIntegrity;
+
+use super::{RockLayout, Tree};
+
+const INSTALLED_FILES_NAME: &str = "installed_files.json";
+
+/// The files installed by a package, with their hashes.
+/// Paths are relative to the tree root, so that files placed in shared directories,
+/// like the tree's `bin` directory or an `etc_root`, can be attributed to their package.
+///
+/// This is written to `installed_files.json` in the package's install directory.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledFiles {
+    files: BTreeMap<PathBuf, Integrity>,
+}
+
+impl InstalledFiles {
+    /// Collect the files installed to a rock layout.
+    /// `binaries` are the names of the binaries installed to the tree's `bin` directory.
+    pub(crate) fn collect<'a>(
+        tree: &Tree,
+        layout: &RockLayout,
+        binaries: impl IntoIterator<Item = &'a PathBuf>,
+    ) -> io::Result<Self> {
+        let manifest_path = Self::path(layout);
+        let mut files = BTreeMap::new();
+        let mut dirs = vec![layout.rock_path.clone()];
+        if !layout.etc.starts_with(&layout.rock_path) {
+            dirs.push(layout.etc.clone());
+        }
+        for dir in dirs.iter().filter(|dir| dir.is_dir()) {
+            for entry in WalkDir::new(dir) {
+                let entry = entry.map_err(io::Error::other)?;
+                if entry.file_type().is_dir() || entry.path() == manifest_path {
+                    continue;
+                }
+                files.insert(entry.path().to_path_buf(), entry.path().hash()?);
+            }
+        }
+        for binary in binaries {
+            let Some(file_name) = binary.file_name() else {
+                continue;
+            };
+            let mut candidates = vec![
+                tree.bin().join(file_name),
+                tree.unwrapped_bin().join(file_name),
+            ];
+            if cfg!(target_family = "windows") {
+                candidates.push(
+                    tree.bin()
+                        .join(format!("{}.bat", file_name.to_string_lossy())),
+                );
+            }
+            for file in candidates.into_iter().filter(|file| file.is_file()) {
+                let hash = file.hash()?;
+                files.insert(file, hash);
+            }
+        }
+        let root = tree.root();
+        let files = files
+            .into_iter()
+            .map(|(file, hash)| {
+                let relative_path = file
+                    .strip_prefix(&root)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(file);
+                (relative_path, hash)
+            })
+            .collect();
+        Ok(Self { files })
+    }
+
+    /// Load the installed files of a package.
+    /// Returns `None` if the package was installed before lux recorded its files.
+    pub fn load(layout: &RockLayout) -> io::Result<Option<Self>> {
+        let path = Self::path(layout);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    pub(crate) fn write(&self, layout: &RockLayout) -> io::Result<()> {
+        std::fs::write(Self::path(layout), serde_json::to_string_pretty(self)?)
+    }
+
+    /// The installed files, relative to the tree root, with their hashes.
+    pub fn files(&self) -> impl Iterator<Item = (&PathBuf, &Integrity)> {
+        self.files.iter()
+    }
+
+    /// The installed files that have been modified or deleted since they were installed,
+    /// relative to the tree root.
+    pub fn modified_files(&self, tree: &Tree) -> Vec<PathBuf> {
+        let root = tree.root();
+        self.files
+            .iter()
+            .filter(|(file, hash)| {
+                let path = root.join(file);
+                !path.is_file() || path.hash().ok().as_ref() != Some(*hash)
+            })
+            .map(|(file, _)| file.clone())
+            .collect()
+    }
+
+    fn path(layout: &RockLayout) -> PathBuf {
+        layout.rock_path.join(INSTALLED_FILES_NAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use assert_fs::prelude::*;
+
+    use crate::{
+        config::{ConfigBuilder, LuaVersion},
+        lockfile::{LocalPackage, LocalPackageHashes, LockConstraint},
+        package::PackageSpec,
+        remote_package_source::RemotePackageSource,
+        rockspec::RockBinaries,
+    };
+
+    use super::*;
+
+    #[test]
+    fn collect_installed_files() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        let hash = "sha256-uBr6qNv1DqQvTsS5xd8ulK3Ob3gr0lZPA9ISbR8enlQ="
+            .parse::<Integrity>()
+            .unwrap();
+        let package = LocalPackage::from(
+            &PackageSpec::parse("foo".into(), "1.0.0-1".into()).unwrap(),
+            LockConstraint::Unconstrained,
+            RockBinaries::default(),
+            RemotePackageSource::Test,
+            None,
+            LocalPackageHashes {
+                rockspec: hash.clone(),
+                source: hash,
+            },
+        );
+        let layout = tree.entrypoint(&package).unwrap();
+        fs::write(layout.src.join("foo.lua"), "return {}").unwrap();
+        fs::write(tree.bin().join("foo"), "#!/bin/sh").unwrap();
+        fs::write(tree.bin().join("bar"), "#!/bin/sh").unwrap();
+        let installed_files =
+            InstalledFiles::collect(&tree, &layout, &[PathBuf::from("bin/foo")]).unwrap();
+        installed_files.write(&layout).unwrap();
+        let installed_files = InstalledFiles::load(&layout).unwrap().unwrap();
+        let files = installed_files
+            .files()
+            .map(|(file, _)| file.clone())
+            .collect::<Vec<_>>();
+        let rock_dir = layout.rock_path.strip_prefix(tree.root()).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                rock_dir.join("src").join("foo.lua"),
+                PathBuf::from("bin/foo")
+            ]
+        );
+        assert!(installed_files.modified_files(&tree).is_empty());
+        temp.child(tree.root().join("bin/foo"))
+            .write_str("modified")
+            .unwrap();
+        assert_eq!(
+            installed_files.modified_files(&tree),
+            vec![PathBuf::from("bin/foo")]
+        );
+    }
+}
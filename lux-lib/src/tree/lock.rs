@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use fs4::fs_std::FileExt;
+use thiserror::Error;
+
+use crate::progress::{Progress, ProgressBar};
+
+pub(crate) const TREE_LOCK_FILE_NAME: &str = ".lux-tree.lock";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Error, Debug)]
+pub enum TreeLockError {
+    #[error("failed to lock the install tree {0}:\n{1}")]
+    Io(PathBuf, io::Error),
+    #[error("timed out after {}s waiting for the lock on the install tree {path} held by {holder}.\nIf no other lux process is running, the lock may be stale: remove {} and try again.", .timeout.as_secs(), .path.join(TREE_LOCK_FILE_NAME).display())]
+    Timeout {
+        path: PathBuf,
+        holder: String,
+        timeout: Duration,
+    },
+}
+
+/// The lock files held by this process, with the number of [`TreeLock`]s referring to each.
+/// Tree locks are re-entrant within a process, so that nested operations on the same tree
+/// (e.g. a sync that installs packages) don't wait for themselves.
+fn held_locks() -> &'static Mutex<HashMap<PathBuf, (File, usize)>> {
+    static HELD_LOCKS: OnceLock<Mutex<HashMap<PathBuf, (File, usize)>>> = OnceLock::new();
+    HELD_LOCKS.get_or_init(Mutex::default)
+}
+
+/// An advisory lock on an install tree, which prevents concurrent lux processes
+/// from corrupting its lockfile.
+/// The lock is released when dropped.
+#[derive(Debug)]
+pub struct TreeLock {
+    lock_file: PathBuf,
+}
+
+impl TreeLock {
+    /// Lock the tree with the root directory `dir`, waiting for at most `timeout`
+    /// if another process holds the lock.
+    /// A `timeout` of zero means waiting for as long as it takes.
+    pub(crate) async fn acquire(
+        dir: &Path,
+        timeout: Duration,
+        progress: &Progress<ProgressBar>,
+    ) -> Result<Self, TreeLockError> {
+        let err = |err| TreeLockError::Io(dir.to_path_buf(), err);
+        std::fs::create_dir_all(dir).map_err(err)?;
+        let lock_file = dir.join(TREE_LOCK_FILE_NAME);
+        if let Some(lock) = Self::reenter(&lock_file) {
+            return Ok(lock);
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_file)
+            .map_err(err)?;
+        let start = Instant::now();
+        let mut waiting = false;
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => break,
+                Err(lock_err) if lock_err.kind() == fs4::lock_contended_error().kind() => {
+                    // Another task of this process may have locked the tree in the meantime.
+                    if let Some(lock) = Self::reenter(&lock_file) {
+                        return Ok(lock);
+                    }
+                    let holder = lock_holder(&mut file);
+                    if !timeout.is_zero() && start.elapsed() >= timeout {
+                        return Err(TreeLockError::Timeout {
+                            path: dir.to_path_buf(),
+                            holder,
+                            timeout,
+                        });
+                    }
+                    if !waiting {
+                        waiting = true;
+                        progress.map(|p| {
+                            p.set_message(format!(
+                                "🔒 Waiting for the lock on {}, held by {holder}",
+                                dir.display()
+                            ))
+                        });
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                Err(lock_err) => return Err(err(lock_err)),
+            }
+        }
+
+        // Record who holds the lock, so that waiting processes can report it.
+        file.set_len(0).map_err(err)?;
+        file.rewind().map_err(err)?;
+        write!(file, "{}", std::process::id()).map_err(err)?;
+
+        held_locks()
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(lock_file.clone(), (file, 1));
+        Ok(Self { lock_file })
+    }
+
+    /// Share the lock on `lock_file` if this process already holds it.
+    fn reenter(lock_file: &Path) -> Option<Self> {
+        let mut held_locks = held_locks().lock().unwrap_or_else(|err| err.into_inner());
+        let (_, count) = held_locks.get_mut(lock_file)?;
+        *count += 1;
+        Some(Self {
+            lock_file: lock_file.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for TreeLock {
+    fn drop(&mut self) {
+        let mut held_locks = held_locks().lock().unwrap_or_else(|err| err.into_inner());
+        if let Some((file, count)) = held_locks.get_mut(&self.lock_file) {
+            *count -= 1;
+            if *count == 0 {
+                let _ = file.set_len(0);
+                let _ = FileExt::unlock(file);
+                held_locks.remove(&self.lock_file);
+            }
+        }
+    }
+}
+
+/// Describe the process that holds a tree lock, detecting holders that are no longer running.
+fn lock_holder(file: &mut File) -> String {
+    let mut content = String::new();
+    let pid = file
+        .rewind()
+        .and_then(|_| file.read_to_string(&mut content))
+        .ok()
+        .and_then(|_| content.trim().parse::<u32>().ok());
+    match pid {
+        Some(pid) if !is_running(pid) => {
+            format!("process {pid}, which is no longer running (stale lock)")
+        }
+        Some(pid) => format!("process {pid}"),
+        None => "another process".into(),
+    }
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // Signal 0 only checks whether the process exists.
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn tree_lock_is_reentrant() {
+        let temp = TempDir::new().unwrap();
+        let lock_file = temp.path().join(TREE_LOCK_FILE_NAME);
+        let lock = TreeLock::acquire(temp.path(), Duration::from_secs(1), &Progress::NoProgress)
+            .await
+            .unwrap();
+        let nested = TreeLock::acquire(temp.path(), Duration::from_secs(1), &Progress::NoProgress)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&lock_file).unwrap(),
+            std::process::id().to_string()
+        );
+
+        // Another open file description can't take the lock while it is held.
+        let other = File::open(&lock_file).unwrap();
+        assert!(other.try_lock_exclusive().is_err());
+        drop(nested);
+        assert!(other.try_lock_exclusive().is_err());
+        drop(lock);
+        other.try_lock_exclusive().unwrap();
+
+        // Locking times out while another holder has the lock.
+        assert!(matches!(
+            TreeLock::acquire(
+                temp.path(),
+                Duration::from_millis(200),
+                &Progress::NoProgress
+            )
+            .await,
+            Err(TreeLockError::Timeout { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn waiting_for_a_tree_lock_does_not_block_other_trees() {
+        let tree_a = TempDir::new().unwrap();
+        let tree_b = TempDir::new().unwrap();
+        std::fs::write(tree_b.path().join(TREE_LOCK_FILE_NAME), "").unwrap();
+        let other = File::open(tree_b.path().join(TREE_LOCK_FILE_NAME)).unwrap();
+        other.try_lock_exclusive().unwrap();
+
+        let waiting = tokio::spawn(async move {
+            TreeLock::acquire(tree_b.path(), Duration::ZERO, &Progress::NoProgress)
+                .await
+                .map(|_| ())
+        });
+
+        // Locking and releasing another tree doesn't wait for the pending lock.
+        let lock = tokio::time::timeout(
+            Duration::from_secs(5),
+            TreeLock::acquire(tree_a.path(), Duration::from_secs(1), &Progress::NoProgress),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        drop(lock);
+        assert!(!waiting.is_finished());
+
+        FileExt::unlock(&other).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+}
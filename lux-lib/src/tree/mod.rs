@@ -2,18 +2,33 @@ use crate::{
     build::utils::format_path,
     config::{tree::RockLayoutConfig, Config, LuaVersion},
     lockfile::{LocalPackage, LocalPackageId, Lockfile, LockfileError, OptState, ReadOnly},
-    package::PackageReq,
+    lua_rockspec::LuaModule,
+    package::{PackageReq, PackageSpec},
+    progress::{Progress, ProgressBar},
     variables::{GetVariableError, HasVariables},
 };
-use std::{io, path::PathBuf};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
 
 use itertools::Itertools;
 use mlua::{ExternalResult, IntoLua};
 use thiserror::Error;
 
+mod installed_files;
 mod list;
+mod lock;
+mod recovery;
+mod versioned;
+
+pub use installed_files::InstalledFiles;
+pub(crate) use lock::TREE_LOCK_FILE_NAME;
+pub use lock::{TreeLock, TreeLockError};
+pub use recovery::TreeRecovery;
+pub use versioned::{versioned_module_name, ModulePrecedence};
 
-const LOCKFILE_NAME: &str = "lux.lock";
+pub(crate) const LOCKFILE_NAME: &str = "lux.lock";
 
 /// A tree is a collection of files where installed rocks are located.
 ///
@@ -25,6 +40,7 @@ const LOCKFILE_NAME: &str = "lux.lock";
 /// - /rocks/<lua-version>/<rock>/lib - shared libraries (.so files)
 /// - /rocks/<lua-version>/<rock>/src - library code for the rock
 /// - /bin - binary files produced by various rocks
+/// - /stubs - stubs for modules that are provided by more than one rock
 
 #[derive(Clone, Debug)]
 pub struct Tree {
@@ -38,6 +54,8 @@ pub struct Tree {
     test_tree_dir: PathBuf,
     /// The root of this tree's build dependency tree.
     build_tree_dir: PathBuf,
+    /// A read-only tree, whose packages are available in addition to this tree's.
+    system_tree: Option<Box<Tree>>,
 }
 
 #[derive(Debug, Error)]
@@ -46,6 +64,10 @@ pub enum TreeError {
     Io(#[from] io::Error),
     #[error(transparent)]
     Lockfile(#[from] LockfileError),
+    #[error(transparent)]
+    Lock(#[from] TreeLockError),
+    #[error("{1} does not provide the module `{0}`")]
+    ModuleNotFound(LuaModule, PackageSpec),
 }
 
 /// Change-agnostic way of referencing various paths for a rock.
@@ -84,6 +106,22 @@ impl RockLayout {
     pub fn rockspec_path(&self) -> PathBuf {
         self.rock_path.join("package.rockspec")
     }
+
+    /// Environment variables pointing to the layout's directories.
+    /// These are set by `lx run`, `lx test` and `lx shell` for the project's package,
+    /// so that it can locate its resources at runtime.
+    pub fn env_vars(&self) -> [(&'static str, &Path); 7] {
+        [
+            ("LUX_PREFIX", &self.rock_path),
+            ("LUX_ETC", &self.etc),
+            ("LUX_LIB", &self.lib),
+            ("LUX_SRC", &self.src),
+            ("LUX_BIN", &self.bin),
+            ("LUX_CONF", &self.conf),
+            ("LUX_DOC", &self.doc),
+        ]
+        .map(|(name, path)| (name, path.as_path()))
+    }
 }
 
 impl HasVariables for RockLayout {
@@ -123,7 +161,35 @@ impl Tree {
         let version_dir = root.join(version.to_string());
         let test_tree_dir = version_dir.join("test_dependencies");
         let build_tree_dir = version_dir.join("build_dependencies");
-        Self::new_with_paths(root, test_tree_dir, build_tree_dir, version, config)
+        let system_tree = match config.system_tree() {
+            Some(system_root) if system_root != &root => {
+                Self::read_only(system_root.clone(), version.clone())?.map(Box::new)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            system_tree,
+            ..Self::new_with_paths(root, test_tree_dir, build_tree_dir, version, config)?
+        })
+    }
+
+    /// Open an existing tree without modifying it.
+    /// Returns `None` if the tree has no lockfile for `version`.
+    fn read_only(root: PathBuf, version: LuaVersion) -> Result<Option<Self>, TreeError> {
+        let version_dir = root.join(version.to_string());
+        let lockfile_path = version_dir.join(LOCKFILE_NAME);
+        if !lockfile_path.is_file() {
+            return Ok(None);
+        }
+        let lockfile = Lockfile::load(lockfile_path, None)?;
+        Ok(Some(Self {
+            root_parent: root,
+            version,
+            entrypoint_layout: lockfile.entrypoint_layout,
+            test_tree_dir: version_dir.join("test_dependencies"),
+            build_tree_dir: version_dir.join("build_dependencies"),
+            system_tree: None,
+        }))
     }
 
     fn new_with_paths(
@@ -156,6 +222,7 @@ impl Tree {
             entrypoint_layout: rock_layout_config,
             test_tree_dir,
             build_tree_dir,
+            system_tree: None,
         })
     }
 
@@ -187,8 +254,30 @@ impl Tree {
         self.bin().join("unwrapped")
     }
 
+    /// The read-only system tree layered below this tree, if any.
+    pub fn system_tree(&self) -> Option<&Tree> {
+        self.system_tree.as_deref()
+    }
+
+    /// The tree in which an installed package is located:
+    /// either this tree or, if the package isn't installed here, the read-only system tree.
+    pub fn tree_for(&self, package: &LocalPackageId) -> Result<&Tree, TreeError> {
+        match &self.system_tree {
+            Some(system_tree) if self.lockfile()?.get(package).is_none() => Ok(system_tree),
+            _ => Ok(self),
+        }
+    }
+
+    /// Find the installed packages matching `req`.
+    /// If there are none in this tree, the read-only system tree is searched.
+    /// Use [`Tree::tree_for`] to find out which tree a matching package is installed in.
     pub fn match_rocks(&self, req: &PackageReq) -> Result<RockMatches, TreeError> {
         let mut found_packages = self.lockfile()?.find_rocks(req);
+        if found_packages.is_empty() {
+            if let Some(system_tree) = &self.system_tree {
+                return system_tree.match_rocks(req);
+            }
+        }
         Ok(match found_packages.len() {
             0 => RockMatches::NotFound(req.clone()),
             1 => RockMatches::Single(found_packages.pop().unwrap()),
@@ -299,11 +388,27 @@ impl Tree {
         )?)
     }
 
+    /// Lock this tree for modification, waiting for up to the configured `lock_timeout`
+    /// if another process holds the lock.
+    pub async fn lock(
+        &self,
+        config: &Config,
+        progress: &Progress<ProgressBar>,
+    ) -> Result<TreeLock, TreeError> {
+        Ok(TreeLock::acquire(&self.root(), config.lock_timeout(), progress).await?)
+    }
+
     /// Get this tree's lockfile path.
     pub fn lockfile_path(&self) -> PathBuf {
         self.root().join(LOCKFILE_NAME)
     }
 
+    /// The directories of the test and build trees,
+    /// which may be nested in this tree's root.
+    pub(crate) fn nested_tree_dirs(&self) -> [&Path; 2] {
+        [&self.test_tree_dir, &self.build_tree_dir]
+    }
+
     /// The tree in which to install test dependencies
     pub fn test_tree(&self, config: &Config) -> Result<Self, TreeError> {
         let test_tree_dir = self.test_tree_dir.clone();
@@ -419,7 +524,7 @@ mod tests {
         package::{PackageName, PackageSpec, PackageVersion},
         remote_package_source::RemotePackageSource,
         rockspec::RockBinaries,
-        tree::RockLayout,
+        tree::{RockLayout, RockMatches},
         variables,
     };
 
@@ -596,4 +701,44 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn system_tree_overlay() {
+        let tree_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/sample-tree");
+
+        let system_temp = assert_fs::TempDir::new().unwrap();
+        system_temp.copy_from(&tree_path, &["**"]).unwrap();
+        let system_tree_path = system_temp.to_path_buf();
+        let user_temp = assert_fs::TempDir::new().unwrap();
+
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(user_temp.to_path_buf()))
+            .system_tree(Some(system_tree_path.clone()))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        let system_tree = tree.system_tree().unwrap();
+        assert_eq!(system_tree.root(), system_tree_path.join("5.1"));
+        // The system tree is never written to.
+        assert!(!system_tree_path.join(".gitignore").exists());
+
+        let neorg = match tree.match_rocks(&"neorg".parse().unwrap()).unwrap() {
+            RockMatches::Single(id) => id,
+            matches => panic!("expected a single match, got {matches:?}"),
+        };
+        assert_eq!(tree.tree_for(&neorg).unwrap().root(), system_tree.root());
+        assert!(tree.lockfile().unwrap().get(&neorg).is_none());
+
+        let paths = crate::path::Paths::new(&tree).unwrap();
+        assert!(paths
+            .package_path()
+            .joined()
+            .contains(&system_tree.root().display().to_string()));
+
+        // Without a lockfile for the Lua version, there is no system tree to layer.
+        let tree = config.user_tree(LuaVersion::Lua54).unwrap();
+        assert!(tree.system_tree().is_none());
+    }
 }
@@ -0,0 +1,74 @@
+use std::{fmt::Display, str::FromStr};
+
+use mlua::{ExternalResult, FromLua, IntoLua};
+use serde::{Deserialize, Serialize};
+
+use crate::lockfile::LocalPackage;
+
+use super::{Tree, TreeError};
+
+/// What to do with packages in a tree's lockfile whose install directories are missing,
+/// e.g. because they were deleted manually.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeRecovery {
+    /// Reinstall the missing packages.
+    #[default]
+    Reinstall,
+    /// Remove the stale entries from the lockfile.
+    Prune,
+}
+
+impl FromStr for TreeRecovery {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "reinstall" => Ok(Self::Reinstall),
+            "prune" => Ok(Self::Prune),
+            _ => Err("unrecognized tree recovery. Allowed values: 'reinstall', 'prune'.".into()),
+        }
+    }
+}
+
+impl Display for TreeRecovery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Reinstall => "reinstall",
+            Self::Prune => "prune",
+        })
+    }
+}
+
+impl FromLua for TreeRecovery {
+    fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
+        let recovery_str: String = FromLua::from_lua(value, lua)?;
+        Self::from_str(&recovery_str).into_lua_err()
+    }
+}
+
+impl IntoLua for TreeRecovery {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        self.to_string().into_lua(lua)
+    }
+}
+
+impl Tree {
+    /// The packages in the lockfile whose install directories are missing from the tree.
+    pub fn missing_packages(&self) -> Result<Vec<LocalPackage>, TreeError> {
+        let lockfile = self.lockfile()?;
+        Ok(lockfile
+            .rocks()
+            .values()
+            .filter(|package| {
+                let layout = if lockfile.is_entrypoint(&package.id()) {
+                    self.entrypoint_layout(package)
+                } else {
+                    self.dependency_layout(package)
+                };
+                !layout.rock_path.is_dir()
+            })
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use path_slash::PathBufExt;
+use walkdir::WalkDir;
+
+use crate::{
+    build::utils::c_dylib_extension,
+    lockfile::{LocalPackage, LocalPackageId},
+    lua_rockspec::LuaModule,
+    package::PackageSpec,
+};
+
+use super::{RockLayout, Tree, TreeError};
+
+const STUBS_DIR_NAME: &str = "stubs";
+
+/// The first line of each stub is this prefix, followed by the ID of the package the stub loads from.
+const STUB_HEADER: &str = "-- Generated by lux for ";
+
+/// Which package's module is loaded by `require`
+/// when two packages that provide the same module are installed side by side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum ModulePrecedence {
+    /// The module of the package that is being installed.
+    #[default]
+    New,
+    /// The module of the package that is already installed.
+    Installed,
+}
+
+/// The name under which a package's module is available
+/// if another package's module takes precedence,
+/// e.g. `foo_1_0_0_1-bar` for the module `bar` of `foo 1.0.0-1`.
+///
+/// This is the naming scheme of luarocks' versioned deploys.
+/// Lua ignores the part up to the hyphen when looking up a C module's `luaopen_` function.
+pub fn versioned_module_name(package: &PackageSpec, module: &LuaModule) -> LuaModule {
+    let name_version = format!("{}_{}", package.name(), package.version()).replace(['-', '.'], "_");
+    format!("{name_version}-{module}")
+        .parse()
+        .expect("a LuaModule can be parsed from any string")
+}
+
+impl Tree {
+    /// The directory containing the stubs of modules that are provided by more than one package.
+    /// It takes precedence over the packages' own source directories in the `package.path`.
+    pub fn stubs(&self) -> PathBuf {
+        self.root().join(STUBS_DIR_NAME)
+    }
+
+    /// Deploy the modules of newly installed packages that are also provided
+    /// by other packages in the tree side by side:
+    /// a stub loads the module of the package that takes precedence,
+    /// and the other package's module is available under its versioned name.
+    pub(crate) fn deploy_versioned_modules(
+        &self,
+        new_packages: &[LocalPackage],
+        precedence: ModulePrecedence,
+    ) -> Result<(), TreeError> {
+        let lockfile = self.lockfile()?;
+        for package in new_packages {
+            let layout = self.installed_rock_layout(package)?;
+            for module in package_modules(&layout) {
+                for other in lockfile.rocks().values() {
+                    if other.name() == package.name()
+                        || module_file(&self.installed_rock_layout(other)?, &module).is_none()
+                    {
+                        continue;
+                    }
+                    let (preferred, versioned) = match precedence {
+                        ModulePrecedence::New => (package, other),
+                        ModulePrecedence::Installed => (other, package),
+                    };
+                    self.deploy_stub(preferred, &module, &module)?;
+                    self.deploy_stub(
+                        versioned,
+                        &module,
+                        &versioned_module_name(&versioned.to_package(), &module),
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a stub that loads the `module` of an installed `package` when `as_module` is required.
+    pub(crate) fn deploy_stub(
+        &self,
+        package: &LocalPackage,
+        module: &LuaModule,
+        as_module: &LuaModule,
+    ) -> Result<PathBuf, TreeError> {
+        let layout = self.installed_rock_layout(package)?;
+        let stub = self.stubs().join(as_module.to_lua_path());
+        let stub_dir = stub.parent().unwrap_or(&stub).to_path_buf();
+        let loader = match module_file(&layout, module) {
+            Some(ModuleFile::Lua(path)) => {
+                format!("assert(loadfile({}))", relative_path_expr(&stub_dir, &path))
+            }
+            Some(ModuleFile::Lib(path)) => format!(
+                "assert(package.loadlib({}, \"luaopen_{}\"))",
+                relative_path_expr(&stub_dir, &path),
+                module.as_str().replace('.', "_")
+            ),
+            None => {
+                return Err(TreeError::ModuleNotFound(
+                    module.clone(),
+                    package.to_package(),
+                ))
+            }
+        };
+        std::fs::create_dir_all(&stub_dir)?;
+        std::fs::write(
+            &stub,
+            format!(
+                "{STUB_HEADER}{}\n-- Loads the module `{module}` of {}@{}\nreturn {loader}(...)\n",
+                package.id(),
+                package.name(),
+                package.version(),
+            ),
+        )?;
+        Ok(stub)
+    }
+
+    /// Remove the stubs that load the modules of a package.
+    pub(crate) fn remove_stubs(&self, package: &LocalPackageId) -> io::Result<()> {
+        let stubs = self.stubs();
+        let header = format!("{STUB_HEADER}{package}");
+        let package_stubs = WalkDir::new(&stubs)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                std::fs::read_to_string(path)
+                    .is_ok_and(|content| content.lines().next() == Some(header.as_str()))
+            })
+            .collect::<Vec<_>>();
+        for stub in package_stubs {
+            std::fs::remove_file(&stub)?;
+            for dir in stub.ancestors().skip(1) {
+                if !dir.starts_with(&stubs) || std::fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+enum ModuleFile {
+    Lua(PathBuf),
+    Lib(PathBuf),
+}
+
+/// The file that provides a module in a package's layout, if any.
+fn module_file(layout: &RockLayout, module: &LuaModule) -> Option<ModuleFile> {
+    [module.to_lua_path(), module.to_lua_init_path()]
+        .into_iter()
+        .map(|path| layout.src.join(path))
+        .find(|path| path.is_file())
+        .map(ModuleFile::Lua)
+        .or_else(|| {
+            let path = layout.lib.join(module.to_lib_path());
+            path.is_file().then_some(ModuleFile::Lib(path))
+        })
+}
+
+/// The Lua and C modules installed in a package's layout.
+fn package_modules(layout: &RockLayout) -> Vec<LuaModule> {
+    [(&layout.src, "lua"), (&layout.lib, c_dylib_extension())]
+        .into_iter()
+        .flat_map(|(dir, extension)| {
+            WalkDir::new(dir)
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(move |entry| {
+                    entry.file_type().is_file()
+                        && entry.path().extension().is_some_and(|ext| ext == extension)
+                })
+                .filter_map(move |entry| {
+                    entry
+                        .path()
+                        .strip_prefix(dir)
+                        .ok()
+                        .map(|path| LuaModule::from_pathbuf(path.to_path_buf()))
+                })
+        })
+        .collect()
+}
+
+/// A Lua expression for `path`, relative to the directory of the stub that is being run,
+/// so that the tree can be moved to a different location.
+fn relative_path_expr(stub_dir: &Path, path: &Path) -> String {
+    let relative_path = pathdiff::diff_paths(path, stub_dir).unwrap_or(path.to_path_buf());
+    format!(
+        r#"(debug.getinfo(1, "S").source:match("^@(.*)[/\\]") or ".") .. "/{}""#,
+        relative_path
+            .to_slash_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+    use ssri::Integrity;
+
+    use crate::{
+        config::{ConfigBuilder, LuaVersion},
+        lockfile::{LocalPackageHashes, LockConstraint},
+        remote_package_source::RemotePackageSource,
+        rockspec::RockBinaries,
+    };
+
+    use super::*;
+
+    #[test]
+    fn versioned_module_names() {
+        let foo = PackageSpec::parse("foo".into(), "1.0.0-1".into()).unwrap();
+        assert_eq!(
+            versioned_module_name(&foo, &"bar.baz".parse().unwrap()).as_str(),
+            "foo_1_0_0_1-bar.baz"
+        );
+    }
+
+    fn install_package(tree: &Tree, name: &str, modules: &[&str]) -> LocalPackage {
+        let hash = "sha256-uBr6qNv1DqQvTsS5xd8ulK3Ob3gr0lZPA9ISbR8enlQ="
+            .parse::<Integrity>()
+            .unwrap();
+        let package = LocalPackage::from(
+            &PackageSpec::parse(name.into(), "1.0.0-1".into()).unwrap(),
+            LockConstraint::Unconstrained,
+            RockBinaries::default(),
+            RemotePackageSource::LuarocksRockspec("https://luarocks.org/".parse().unwrap()),
+            None,
+            LocalPackageHashes {
+                rockspec: hash.clone(),
+                source: hash,
+            },
+        );
+        let layout = tree.entrypoint(&package).unwrap();
+        tree.lockfile()
+            .unwrap()
+            .write_guard()
+            .add_entrypoint(&package);
+        for module in modules {
+            let path = layout.src.join(format!("{module}.lua"));
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, format!("return '{name}'")).unwrap();
+        }
+        package
+    }
+
+    #[test]
+    fn deploy_versioned_modules() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        install_package(&tree, "foo", &["shared", "foo"]);
+        let bar = install_package(&tree, "bar", &["shared"]);
+        tree.deploy_versioned_modules(&[bar], ModulePrecedence::New)
+            .unwrap();
+
+        // The stubs use the `debug` library, which mlua only loads in unsafe mode.
+        let lua = unsafe { Lua::unsafe_new() };
+        lua.load(format!(
+            "package.path = {:?}",
+            tree.stubs().join("?.lua").to_slash_lossy()
+        ))
+        .exec()
+        .unwrap();
+        let require = |module: &str| -> String {
+            lua.load(format!("return require('{module}')"))
+                .eval()
+                .unwrap()
+        };
+        assert_eq!(require("shared"), "bar");
+        assert_eq!(require("foo_1_0_0_1-shared"), "foo");
+        assert!(!tree.stubs().join("foo.lua").exists());
+    }
+
+    #[test]
+    fn deploy_and_remove_stubs() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        let package = install_package(&tree, "foo", &[]);
+        let layout = tree.installed_rock_layout(&package).unwrap();
+        std::fs::create_dir_all(layout.src.join("bar")).unwrap();
+        std::fs::write(layout.src.join("bar").join("init.lua"), "return ...").unwrap();
+
+        let module: LuaModule = "bar".parse().unwrap();
+        let as_module = versioned_module_name(&package.to_package(), &module);
+        let stub = tree.deploy_stub(&package, &module, &as_module).unwrap();
+        assert_eq!(stub, tree.stubs().join("foo_1_0_0_1-bar.lua"));
+
+        // The stubs use the `debug` library, which mlua only loads in unsafe mode.
+        let lua = unsafe { Lua::unsafe_new() };
+        lua.load(format!(
+            "package.path = {:?}",
+            tree.stubs().join("?.lua").to_slash_lossy()
+        ))
+        .exec()
+        .unwrap();
+        let loaded: String = lua
+            .load(format!("return require('{as_module}')"))
+            .eval()
+            .unwrap();
+        assert_eq!(loaded, as_module.as_str());
+
+        tree.remove_stubs(&package.id()).unwrap();
+        assert!(!stub.exists());
+        assert!(!tree.stubs().exists());
+    }
+}
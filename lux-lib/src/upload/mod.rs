@@ -1,16 +1,14 @@
 use std::env;
 
+use crate::http::InsecureServerError;
 use crate::package::PackageVersion;
 use crate::project::project_toml::RemoteProjectTomlValidationError;
 use crate::rockspec::Rockspec;
 use crate::TOOL_VERSION;
 use crate::{config::Config, project::Project};
 
-use reqwest::StatusCode;
-use reqwest::{
-    multipart::{Form, Part},
-    Client,
-};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Method, StatusCode};
 use serde::Deserialize;
 use serde_enum_str::Serialize_enum_str;
 use thiserror::Error;
@@ -75,6 +73,8 @@ pub enum ToolCheckError {
     ParseError(#[from] url::ParseError),
     #[error(transparent)]
     Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    InsecureServer(#[from] InsecureServerError),
     #[error("`lux` is out of date with {0}'s expected tool version! `lux` is at version {TOOL_VERSION}, server is at {server_version}", server_version = _1.version)]
     ToolOutdated(String, VersionCheckResponse),
 }
@@ -85,6 +85,8 @@ pub enum UserCheckError {
     ParseError(#[from] url::ParseError),
     #[error(transparent)]
     Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    InsecureServer(#[from] InsecureServerError),
     #[error("invalid API key provided")]
     UserNotFound,
     #[error("server {0} responded with error status: {1}")]
@@ -98,6 +100,8 @@ pub enum RockCheckError {
     ParseError(#[from] url::ParseError),
     #[error(transparent)]
     Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    InsecureServer(#[from] InsecureServerError),
 }
 
 #[derive(Error, Debug)]
@@ -107,6 +111,7 @@ pub enum UploadError {
     ParseError(#[from] url::ParseError),
     Lua(#[from] mlua::Error),
     Request(#[from] reqwest::Error),
+    InsecureServer(#[from] InsecureServerError),
     #[error("server {0} responded with error status: {1}")]
     Server(Url, StatusCode),
     #[error("client error when requesting {0}\nStatus code: {1}")]
@@ -232,7 +237,7 @@ async fn upload_from_project(
     #[cfg(not(target_env = "msvc"))] protocol: SignatureProtocol,
     config: &Config,
 ) -> Result<(), UploadError> {
-    let client = Client::builder().https_only(true).build()?;
+    let client = config.http_client()?;
 
     let rockspec = project.toml().into_remote()?;
 
@@ -240,11 +245,11 @@ async fn upload_from_project(
         return Err(UploadError::UnsupportedVersion(ver.to_string()));
     }
 
-    helpers::ensure_tool_version(&client, config.server()).await?;
-    helpers::ensure_user_exists(&client, api_key, config.server()).await?;
+    helpers::ensure_tool_version(client, config.server()).await?;
+    helpers::ensure_user_exists(client, api_key, config.server()).await?;
 
     if helpers::rock_exists(
-        &client,
+        client,
         api_key,
         rockspec.package(),
         rockspec.version(),
@@ -299,7 +304,10 @@ async fn upload_from_project(
     };
 
     let response = client
-        .post(unsafe { helpers::url_for_method(config.server(), api_key, "upload")? })
+        .server_request(Method::POST, unsafe {
+            helpers::url_for_method(config.server(), api_key, "upload")?
+        })?
+        .redact_url()
         .multipart(multipart)
         .send()
         .await?;
@@ -316,10 +324,10 @@ async fn upload_from_project(
 
 mod helpers {
     use super::*;
+    use crate::http::HttpClient;
     use crate::package::{PackageName, PackageVersion};
     use crate::upload::RockCheckError;
     use crate::upload::{ToolCheckError, UserCheckError};
-    use reqwest::Client;
     use url::Url;
 
     /// WARNING: This function is unsafe,
@@ -339,12 +347,12 @@ mod helpers {
     }
 
     pub(crate) async fn ensure_tool_version(
-        client: &Client,
+        client: &HttpClient,
         server_url: &Url,
     ) -> Result<(), ToolCheckError> {
         let url = server_url.join("api/tool_version")?;
         let response: VersionCheckResponse = client
-            .post(url)
+            .server_request(Method::POST, url)?
             .json(&("current", TOOL_VERSION))
             .send()
             .await?
@@ -362,12 +370,15 @@ mod helpers {
     }
 
     pub(crate) async fn ensure_user_exists(
-        client: &Client,
+        client: &HttpClient,
         api_key: &ApiKey,
         server_url: &Url,
     ) -> Result<(), UserCheckError> {
         let response = client
-            .get(unsafe { url_for_method(server_url, api_key, "status")? })
+            .server_request(Method::GET, unsafe {
+                url_for_method(server_url, api_key, "status")?
+            })?
+            .redact_url()
             .send()
             .await?;
         let status = response.status();
@@ -381,14 +392,17 @@ mod helpers {
     }
 
     pub(crate) async fn rock_exists(
-        client: &Client,
+        client: &HttpClient,
         api_key: &ApiKey,
         name: &PackageName,
         version: &PackageVersion,
         server: &Url,
     ) -> Result<bool, RockCheckError> {
         Ok(client
-            .get(unsafe { url_for_method(server, api_key, "check_rockspec")? })
+            .server_request(Method::GET, unsafe {
+                url_for_method(server, api_key, "check_rockspec")?
+            })?
+            .redact_url()
             .query(&(
                 ("package", name.to_string()),
                 ("version", version.to_string()),
@@ -3,7 +3,7 @@ use thiserror::Error;
 
 #[derive(Error, Debug, Clone)]
 pub enum VariableSubstitutionError {
-    #[error("unable to substitute variables {0:#?}")]
+    #[error("unable to substitute variables:\n{}", .0.join("\n"))]
     SubstitutionError(Vec<String>),
     #[error("variable expansion recursion limit (100) reached")]
     RecursionLimit,
@@ -35,6 +35,16 @@ impl HasVariables for Environment {
     }
 }
 
+/// Leaves all variables as they are, so that they can be substituted
+/// in a later pass, e.g. when building a package.
+pub(crate) struct Deferred;
+
+impl HasVariables for Deferred {
+    fn get_variable(&self, input: &str) -> Result<Option<String>, GetVariableError> {
+        Ok(Some(format!("$({input})")))
+    }
+}
+
 fn parser<'a>(
     variables: &'a [&'a dyn HasVariables],
 ) -> impl Parser<'a, &'a str, String, chumsky::extra::Err<Rich<'a, char>>> {
@@ -62,10 +72,7 @@ fn parser<'a>(
                     })?
                     .into_iter()
                     .find_map(|v| v)
-                    .ok_or(Rich::custom(
-                        span,
-                        format!("could not expand variable $({s})"),
-                    ))
+                    .ok_or(Rich::custom(span, format!("undefined variable $({s})")))
             })
             .or(none_of("$)").repeated().at_least(1).collect::<String>())
             .repeated()
@@ -170,6 +177,23 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn undefined_variable_error() {
+        let err = substitute(&[&TestVariables], "-I$(UNDEFINED_VAR)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unable to substitute variables:\nundefined variable $(UNDEFINED_VAR)"
+        );
+    }
+
+    #[test]
+    fn deferred_variables() {
+        assert_eq!(
+            substitute(&[&TestVariables, &Deferred], "$(TEST_VAR) $(LATER)").unwrap(),
+            "foo $(LATER)".to_string()
+        );
+    }
+
     #[test]
     fn substitute_with_empty_string() {
         assert_eq!(
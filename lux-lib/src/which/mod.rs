@@ -1,16 +1,26 @@
-use std::{io, path::PathBuf};
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+};
 
 use bon::{builder, Builder};
 use itertools::Itertools;
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::{
     config::{Config, LuaVersion, LuaVersionUnset},
+    lockfile::LocalPackage,
     lua_rockspec::LuaModule,
-    package::PackageReq,
-    tree::TreeError,
+    package::{PackageName, PackageReq, PackageVersion},
+    project::{Project, ProjectTreeError},
+    tree::{Tree, TreeError},
 };
 
+/// Directories in a project that may contain Lua sources.
+const PROJECT_SOURCE_DIRS: &[&str] = &["src", "lua", "lib"];
+
 /// A rocks module finder.
 #[derive(Builder)]
 #[builder(start_fn = new, finish_fn(name = _build, vis = ""))]
@@ -21,6 +31,13 @@ pub struct Which<'a> {
     config: &'a Config,
     #[builder(field)]
     packages: Vec<PackageReq>,
+    /// Also search the project's sources and its project, test and build trees,
+    /// which take precedence over the user tree.
+    project: Option<&'a Project>,
+    /// Search for an executable named like the module instead of a module.
+    /// Wrapped Lua scripts resolve to the unwrapped scripts they run.
+    #[builder(default)]
+    executable: bool,
 }
 
 impl<State> WhichBuilder<'_, State>
@@ -37,7 +54,26 @@ where
         self
     }
 
+    /// Find the module that would be loaded.
     pub fn search(self) -> Result<PathBuf, WhichError>
+    where
+        State: which_builder::IsComplete,
+    {
+        let which = self._build();
+        let not_found = if which.executable {
+            WhichError::ExecutableNotFound(which.module.to_string())
+        } else {
+            WhichError::ModuleNotFound(which.module.clone())
+        };
+        do_search(which)?
+            .into_iter()
+            .next()
+            .map(|module_match| module_match.path)
+            .ok_or(not_found)
+    }
+
+    /// Find every match for the module, in priority order.
+    pub fn search_all(self) -> Result<Vec<ModuleMatch>, WhichError>
     where
         State: which_builder::IsComplete,
     {
@@ -52,15 +88,147 @@ pub enum WhichError {
     #[error(transparent)]
     Tree(#[from] TreeError),
     #[error(transparent)]
+    ProjectTree(#[from] ProjectTreeError),
+    #[error(transparent)]
     LuaVersionUnset(#[from] LuaVersionUnset),
     #[error("lua module {0} not found.")]
     ModuleNotFound(LuaModule),
+    #[error("executable {0} not found.")]
+    ExecutableNotFound(String),
+}
+
+/// Where a module was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModuleLocation {
+    ProjectSources,
+    ProjectTree,
+    TestTree,
+    BuildTree,
+    UserTree,
+}
+
+impl Display for ModuleLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ProjectSources => "project sources",
+            Self::ProjectTree => "project tree",
+            Self::TestTree => "test tree",
+            Self::BuildTree => "build tree",
+            Self::UserTree => "user tree",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleMatch {
+    pub path: PathBuf,
+    pub location: ModuleLocation,
+    /// The package providing the module.
+    /// For project sources, this is the project's package.
+    pub package: PackageName,
+    /// The version of the package, if it is installed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<PackageVersion>,
+    /// The package's documentation directory, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc: Option<PathBuf>,
+}
+
+impl ModuleMatch {
+    /// Returns the matches that are shadowed by the first (highest priority) match,
+    /// i.e. those that are provided by a different package.
+    pub fn shadowed(matches: &[Self]) -> Vec<&Self> {
+        match matches.split_first() {
+            Some((first, rest)) => rest
+                .iter()
+                .filter(|module_match| module_match.package != first.package)
+                .collect_vec(),
+            None => Vec::new(),
+        }
+    }
 }
 
-fn do_search(which: Which<'_>) -> Result<PathBuf, WhichError> {
+impl ModuleMatch {
+    /// The package providing the module, e.g. `foo@1.0.0-1`.
+    pub fn owner(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{}", self.package, version),
+            None => self.package.to_string(),
+        }
+    }
+}
+
+impl Display for ModuleMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}, {})",
+            self.path.display(),
+            self.owner(),
+            self.location
+        )
+    }
+}
+
+fn do_search(which: Which<'_>) -> Result<Vec<ModuleMatch>, WhichError> {
     let config = which.config;
-    let lua_version = LuaVersion::from(config)?;
-    let tree = config.user_tree(lua_version.clone())?;
+    let mut matches = Vec::new();
+    let lua_version = match which.project {
+        Some(project) => {
+            let project_tree = project.tree(config)?;
+            if which.packages.is_empty() && !which.executable {
+                let package = project.toml().package();
+                matches.extend(
+                    search_project_sources(project.root(), &which.module).map(|path| ModuleMatch {
+                        path,
+                        location: ModuleLocation::ProjectSources,
+                        package: package.clone(),
+                        version: None,
+                        doc: None,
+                    }),
+                );
+            }
+            matches.extend(search_tree(
+                &project_tree,
+                ModuleLocation::ProjectTree,
+                &which,
+            )?);
+            matches.extend(search_tree(
+                &project.test_tree(config)?,
+                ModuleLocation::TestTree,
+                &which,
+            )?);
+            matches.extend(search_tree(
+                &project.build_tree(config)?,
+                ModuleLocation::BuildTree,
+                &which,
+            )?);
+            project_tree.version().clone()
+        }
+        None => LuaVersion::from(config)?.clone(),
+    };
+    let tree = config.user_tree(lua_version)?;
+    matches.extend(search_tree(&tree, ModuleLocation::UserTree, &which)?);
+    Ok(matches)
+}
+
+fn search_project_sources(root: &Path, module: &LuaModule) -> Option<PathBuf> {
+    PROJECT_SOURCE_DIRS.iter().find_map(|dir| {
+        let dir = root.join(dir);
+        [module.to_lua_path(), module.to_lua_init_path()]
+            .into_iter()
+            .map(|path| dir.join(path))
+            .find(|path| path.is_file())
+    })
+}
+
+fn search_tree(
+    tree: &Tree,
+    location: ModuleLocation,
+    which: &Which<'_>,
+) -> Result<Vec<ModuleMatch>, WhichError> {
     let lockfile = tree.lockfile()?;
     let local_packages = if which.packages.is_empty() {
         lockfile
@@ -82,33 +250,56 @@ fn do_search(which: Which<'_>) -> Result<PathBuf, WhichError> {
             })
             .collect_vec()
     };
-    local_packages
+    Ok(local_packages
         .into_iter()
+        .sorted()
         .filter_map(|pkg| {
             let rock_layout = tree.installed_rock_layout(&pkg).ok()?;
-            let lib_path = rock_layout.lib.join(which.module.to_lib_path());
-            if lib_path.is_file() {
-                return Some(lib_path);
-            }
-            let lua_path = rock_layout.src.join(which.module.to_lua_path());
-            if lua_path.is_file() {
-                return Some(lua_path);
-            }
-            let lua_path = rock_layout.src.join(which.module.to_lua_init_path());
-            if lua_path.is_file() {
-                return Some(lua_path);
-            }
-            None
+            let path = if which.executable {
+                search_executable(tree, &pkg, &which.module)
+            } else {
+                let lib_path = rock_layout.lib.join(which.module.to_lib_path());
+                let lua_path = rock_layout.src.join(which.module.to_lua_path());
+                let lua_init_path = rock_layout.src.join(which.module.to_lua_init_path());
+                [lib_path, lua_path, lua_init_path]
+                    .into_iter()
+                    .find(|path| path.is_file())
+            }?;
+            Some(ModuleMatch {
+                path,
+                location,
+                package: pkg.name().clone(),
+                version: Some(pkg.version().clone()),
+                doc: Some(rock_layout.doc).filter(|doc| doc.is_dir()),
+            })
+        })
+        .collect_vec())
+}
+
+/// The executable named `name` that `package` installed,
+/// resolved through the unwrapped bin directory for wrapped Lua scripts.
+fn search_executable(tree: &Tree, package: &LocalPackage, name: &LuaModule) -> Option<PathBuf> {
+    package
+        .spec
+        .binaries()
+        .into_iter()
+        .filter_map(|binary| binary.file_name())
+        .find(|file_name| file_name.to_string_lossy() == name.as_str())
+        .and_then(|file_name| {
+            [
+                tree.unwrapped_bin().join(file_name),
+                tree.bin().join(file_name),
+            ]
+            .into_iter()
+            .find(|path| path.is_file())
         })
-        .next()
-        .ok_or(WhichError::ModuleNotFound(which.module))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::config::{ConfigBuilder, LuaVersion};
-    use assert_fs::prelude::PathCopy;
+    use assert_fs::prelude::{FileWriteStr, PathChild, PathCopy};
     use std::{path::PathBuf, str::FromStr};
 
     #[tokio::test]
@@ -160,4 +351,81 @@ mod test {
             .search();
         assert!(matches!(result, Err(WhichError::ModuleNotFound(_))));
     }
+
+    #[test]
+    fn test_which_executable() {
+        let tree_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/sample-tree");
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.copy_from(&tree_path, &["**"]).unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(temp.to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+        let tree = config.user_tree(LuaVersion::Lua51).unwrap();
+        let unwrapped = tree.unwrapped_bin().join("json2lua");
+        std::fs::create_dir_all(tree.unwrapped_bin()).unwrap();
+        std::fs::write(&unwrapped, "print('json2lua')").unwrap();
+        std::fs::write(tree.bin().join("json2lua"), "#!/bin/sh").unwrap();
+
+        let matches = Which::new(LuaModule::from_str("json2lua").unwrap(), &config)
+            .executable(true)
+            .search_all()
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path, unwrapped);
+        assert_eq!(matches[0].owner(), "lua-cjson@2.1.0-1");
+        let result = Which::new(LuaModule::from_str("foo.bar").unwrap(), &config)
+            .executable(true)
+            .search();
+        assert!(matches!(result, Err(WhichError::ExecutableNotFound(_))));
+    }
+
+    #[test]
+    fn test_which_project_sources_shadow_user_tree() {
+        let tree_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/test/sample-tree");
+        let temp = assert_fs::TempDir::new().unwrap();
+        let user_tree = temp.child("tree");
+        user_tree.copy_from(&tree_path, &["**"]).unwrap();
+        let project_root = temp.child("project");
+        project_root
+            .child("lux.toml")
+            .write_str(
+                r#"
+package = "my-project"
+version = "0.1.0"
+lua = "5.1"
+
+[build]
+type = "builtin"
+"#,
+            )
+            .unwrap();
+        project_root
+            .child("src/foo/bar.lua")
+            .write_str("return {}")
+            .unwrap();
+        let project = Project::from(project_root.path()).unwrap().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .user_tree(Some(user_tree.to_path_buf()))
+            .lua_version(Some(LuaVersion::Lua51))
+            .build()
+            .unwrap();
+
+        let matches = Which::new(LuaModule::from_str("foo.bar").unwrap(), &config)
+            .project(&project)
+            .search_all()
+            .unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].location, ModuleLocation::ProjectSources);
+        assert_eq!(matches[0].package.to_string(), "my-project");
+        assert_eq!(matches[1].location, ModuleLocation::UserTree);
+        let shadowed = ModuleMatch::shadowed(&matches);
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed[0].location, ModuleLocation::UserTree);
+    }
 }
@@ -1,6 +1,7 @@
 use assert_fs::{assert::PathAssert, prelude::PathChild};
 use lux_lib::{
     config::{ConfigBuilder, LuaVersion},
+    lua_installation::LuaInstallation,
     operations::BuildLua,
     progress::{MultiProgress, Progress},
 };
@@ -108,3 +109,31 @@ async fn test_build_luajit() {
         luajit_lib.assert(predicate::path::is_file());
     }
 }
+
+#[tokio::test]
+async fn test_install_lua_headers() {
+    let progress = MultiProgress::new();
+    for lua_version in [LuaVersion::Lua51, LuaVersion::Lua54, LuaVersion::LuaJIT] {
+        let lua_dir = assert_fs::TempDir::new().unwrap();
+        let config = ConfigBuilder::new()
+            .unwrap()
+            .lua_dir(Some(lua_dir.to_path_buf()))
+            .lua_version(Some(lua_version.clone()))
+            .build()
+            .unwrap();
+        let bar = Progress::Progress(progress.new_bar());
+        let lua = LuaInstallation::install_headers(&lua_version, &config, &bar)
+            .await
+            .unwrap();
+        assert!(lua.is_headers_only());
+        for header in ["lua.h", "luaconf.h", "lauxlib.h", "lualib.h"] {
+            lua_dir
+                .child("include")
+                .child(header)
+                .assert(predicate::path::is_file());
+        }
+        lua_dir.child("bin").assert(predicate::path::missing());
+        lua_dir.child("lib").assert(predicate::path::missing());
+        assert!(lua.headers().iter().any(|header| header.ends_with("lua.h")));
+    }
+}
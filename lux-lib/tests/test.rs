@@ -84,23 +84,21 @@ async fn non_regression_lockfile_corruption() {
     assert_eq!(lockfile_before_test, lockfile_after_test);
 }
 
-#[cfg(target_os = "linux")]
 #[tokio::test]
 async fn run_busted_nlua_test() {
     run_busted_nlua_test_impl(false).await
 }
 
-#[cfg(target_os = "linux")]
 #[tokio::test]
 async fn run_busted_nlua_test_no_lock() {
     run_busted_nlua_test_impl(true).await
 }
 
-// NOTE: The busted-nlua test backend is currently broken on macOS and Windows.
-// On macOS, it appears that Neovim segfaults when `require`ing `lfs` (luafilesystem).
-// Investigation is needed on Windows.
-#[cfg(target_os = "linux")]
 async fn run_busted_nlua_test_impl(no_lock: bool) {
+    if which::which("nvim").is_err() {
+        println!("Skipping test because Neovim is not installed");
+        return;
+    }
     let project_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("resources/test/sample-projects/busted-nlua/");
     let temp_dir = assert_fs::TempDir::new().unwrap();
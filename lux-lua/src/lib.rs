@@ -5,6 +5,7 @@ use mlua::prelude::*;
 mod config;
 mod loader;
 mod operations;
+mod paths;
 mod project;
 
 #[cfg_attr(not(feature = "test"), mlua::lua_module)]
@@ -33,6 +34,7 @@ fn lux(lua: &Lua) -> LuaResult<LuaTable> {
     exports.set("config", config::config(lua)?)?;
     exports.set("project", project::project(lua)?)?;
     exports.set("operations", operations::operations(lua)?)?;
+    exports.set("paths", lua.create_function(paths::paths)?)?;
 
     Ok(exports)
 }
@@ -4,14 +4,32 @@
 use std::collections::HashMap;
 
 use lux_lib::{
-    config::Config,
+    config::{Config, LuaVersion},
+    lockfile::LocalPackage,
     lua::lua_runtime,
-    package::{PackageName, PackageVersion},
-    progress::Progress,
+    operations::{CancellationToken, Install, PackageInstallSpec, Purge, PurgeScope},
+    package::{PackageName, PackageReq, PackageVersion},
+    progress::{MultiProgress, Progress},
     remote_package_db::RemotePackageDB,
+    tree,
 };
 use mlua::prelude::*;
 
+/// A token for cancelling long-running operations, like `install`.
+/// Cancelling an install aborts its running builds.
+#[derive(Clone, FromLua)]
+struct LuaCancellationToken(CancellationToken);
+
+impl LuaUserData for LuaCancellationToken {
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("cancel", |_, this, ()| {
+            this.0.cancel();
+            Ok(())
+        });
+        methods.add_method("is_cancelled", |_, this, ()| Ok(this.0.is_cancelled()));
+    }
+}
+
 pub fn operations(lua: &Lua) -> mlua::Result<LuaTable> {
     let table = lua.create_table()?;
 
@@ -24,9 +42,121 @@ pub fn operations(lua: &Lua) -> mlua::Result<LuaTable> {
         })?,
     )?;
 
+    table.set(
+        "cancellation_token",
+        lua.create_function(|_, ()| Ok(LuaCancellationToken(CancellationToken::new())))?,
+    )?;
+
+    table.set(
+        "install",
+        lua.create_async_function(
+            |_,
+             (packages, config, cancellation): (
+                Vec<String>,
+                Config,
+                Option<LuaCancellationToken>,
+            )| async move {
+                let _runtime = lua_runtime().enter();
+
+                install(packages, &config, cancellation).await
+            },
+        )?,
+    )?;
+
+    table.set(
+        "purge",
+        lua.create_async_function(
+            |lua, (config, opts): (Config, Option<LuaTable>)| async move {
+                let _runtime = lua_runtime().enter();
+
+                purge(&lua, &config, opts).await
+            },
+        )?,
+    )?;
+
     Ok(table)
 }
 
+/// Purge the user tree. Takes the same options as `lx purge`:
+/// `{ tree = bool, test_tree = bool, build_tree = bool, except = { packages }, dry_run = bool }`.
+/// Returns a table for each purged tree, with its `scope`, `root`, `packages`
+/// and the `reclaimed` disk space in bytes.
+async fn purge(lua: &Lua, config: &Config, opts: Option<LuaTable>) -> mlua::Result<LuaTable> {
+    let opts = match opts {
+        Some(opts) => opts,
+        None => lua.create_table()?,
+    };
+    let scopes = [
+        ("tree", PurgeScope::Tree),
+        ("test_tree", PurgeScope::TestTree),
+        ("build_tree", PurgeScope::BuildTree),
+    ]
+    .into_iter()
+    .filter_map(|(key, scope)| match opts.get::<Option<bool>>(key) {
+        Ok(Some(true)) => Some(Ok(scope)),
+        Ok(_) => None,
+        Err(err) => Some(Err(err)),
+    })
+    .collect::<mlua::Result<Vec<_>>>()?;
+    let except = opts
+        .get::<Option<Vec<String>>>("except")?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|package| package.parse().into_lua_err())
+        .collect::<mlua::Result<Vec<PackageReq>>>()?;
+    let tree = config
+        .user_tree(LuaVersion::from(config).into_lua_err()?.clone())
+        .into_lua_err()?;
+
+    let purged = Purge::new(&tree, config)
+        .scopes(scopes)
+        .except(except)
+        .dry_run(opts.get::<Option<bool>>("dry_run")?.unwrap_or(false))
+        .progress(MultiProgress::new_arc())
+        .purge()
+        .await
+        .into_lua_err()?;
+    lua.create_sequence_from(
+        purged
+            .into_iter()
+            .map(|purged| {
+                let table = lua.create_table()?;
+                table.set("scope", purged.scope.to_string())?;
+                table.set("root", purged.root)?;
+                table.set("packages", purged.packages)?;
+                table.set("reclaimed", purged.reclaimed)?;
+                Ok(table)
+            })
+            .collect::<mlua::Result<Vec<_>>>()?,
+    )
+}
+
+async fn install(
+    packages: Vec<String>,
+    config: &Config,
+    cancellation: Option<LuaCancellationToken>,
+) -> mlua::Result<Vec<LocalPackage>> {
+    let packages = packages
+        .into_iter()
+        .map(|package| {
+            let package: PackageReq = package.parse().into_lua_err()?;
+            Ok(PackageInstallSpec::new(package, tree::EntryType::Entrypoint).build())
+        })
+        .collect::<mlua::Result<Vec<_>>>()?;
+    let tree = config
+        .user_tree(LuaVersion::from(config).into_lua_err()?.clone())
+        .into_lua_err()?;
+
+    Install::new(config)
+        .packages(packages)
+        .tree(tree)
+        .progress(MultiProgress::new_arc())
+        .maybe_cancellation(cancellation.map(|cancellation| cancellation.0))
+        .install()
+        .await
+        .into_lua_err()
+}
+
 async fn search(
     query: String,
     config: &Config,
@@ -41,3 +171,53 @@ async fn search(
         .map(|(name, versions)| (name.clone(), versions.into_iter().cloned().collect()))
         .collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use mlua::prelude::*;
+
+    #[test]
+    fn lua_api_test_cancel_install() {
+        let lua = Lua::new();
+        let tree = assert_fs::TempDir::new().unwrap();
+
+        lua.globals().set("lux", crate::lux(&lua).unwrap()).unwrap();
+        lua.globals().set("tree", tree.path()).unwrap();
+
+        lua.load(
+            r#"
+            local config = lux.config.builder():lua_version("5.1"):user_tree(tree):build()
+            local token = lux.operations.cancellation_token()
+            assert(not token:is_cancelled(), "token should not be cancelled")
+            token:cancel()
+            assert(token:is_cancelled(), "token should be cancelled")
+            local ok, err = pcall(lux.operations.install, { "foo" }, config, token)
+            assert(not ok, "cancelled install should fail")
+            assert(tostring(err):find("install cancelled"), tostring(err))
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn lua_api_test_purge_dry_run() {
+        let lua = Lua::new();
+        let tree = assert_fs::TempDir::new().unwrap();
+
+        lua.globals().set("lux", crate::lux(&lua).unwrap()).unwrap();
+        lua.globals().set("tree", tree.path()).unwrap();
+
+        lua.load(
+            r#"
+            local config = lux.config.builder():lua_version("5.1"):user_tree(tree):build()
+            local purged = lux.operations.purge(config, { test_tree = true, dry_run = true })
+            assert(#purged == 1, "only the test tree should be purged")
+            assert(purged[1].scope == "test tree", purged[1].scope)
+            assert(#purged[1].packages == 0, "the test tree should be empty")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+use mlua::{Lua, Table};
+
+/// `lux.paths()` returns the install directories of the project's package,
+/// which `lx run`, `lx test` and `lx shell` expose via environment variables:
+///
+/// | key      | environment variable |
+/// |----------|----------------------|
+/// | `prefix` | `LUX_PREFIX`         |
+/// | `etc`    | `LUX_ETC`            |
+/// | `lib`    | `LUX_LIB`            |
+/// | `src`    | `LUX_SRC`            |
+/// | `bin`    | `LUX_BIN`            |
+/// | `conf`   | `LUX_CONF`           |
+/// | `doc`    | `LUX_DOC`            |
+///
+/// Keys whose environment variable is not set are `nil`.
+///
+/// ```lua
+/// local conf = lux.paths().conf
+/// ```
+pub fn paths(lua: &Lua, _: ()) -> mlua::Result<Table> {
+    let table = lua.create_table()?;
+    for (key, var) in [
+        ("prefix", "LUX_PREFIX"),
+        ("etc", "LUX_ETC"),
+        ("lib", "LUX_LIB"),
+        ("src", "LUX_SRC"),
+        ("bin", "LUX_BIN"),
+        ("conf", "LUX_CONF"),
+        ("doc", "LUX_DOC"),
+    ] {
+        if let Some(value) = std::env::var_os(var) {
+            table.set(key, value.to_string_lossy())?;
+        }
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use mlua::prelude::*;
+
+    #[test]
+    fn lua_api_test_paths() {
+        let lua = Lua::new();
+        std::env::set_var("LUX_CONF", "/tmp/lux/etc/conf");
+
+        lua.globals().set("lux", crate::lux(&lua).unwrap()).unwrap();
+
+        lua.load(
+            r#"
+            local paths = lux.paths()
+            assert(paths.conf == "/tmp/lux/etc/conf", "conf should be set")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+}
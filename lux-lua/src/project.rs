@@ -1,7 +1,12 @@
 use std::path::PathBuf;
 
-use lux_lib::project::Project;
-use mlua::{ExternalResult, Lua, Table};
+use lux_lib::{
+    analysis::{self, RequireGraph},
+    config::Config,
+    lockfile::{DependencyGraph, LocalPackageLockType},
+    project::Project,
+};
+use mlua::{ExternalError, ExternalResult, Lua, Table, UserDataRef};
 
 pub fn project(lua: &Lua) -> mlua::Result<Table> {
     let table = lua.create_table()?;
@@ -21,9 +26,78 @@ pub fn project(lua: &Lua) -> mlua::Result<Table> {
         lua.create_function(|_, path: PathBuf| Project::from(path).into_lua_err())?,
     )?;
 
+    table.set("dependency_graph", lua.create_function(dependency_graph)?)?;
+    table.set("require_graph", lua.create_function(require_graph)?)?;
+
     Ok(table)
 }
 
+/// `lux.project.dependency_graph(project?, deps?)` returns the packages in the project's lockfile
+/// and the dependencies between them:
+///
+/// ```lua
+/// {
+///   nodes = { { id = "...", name = "foo", version = "1.0.0-1", pinned = false, entrypoint = true } },
+///   edges = { { from = "<id of foo>", to = "<id of a dependency of foo>" } },
+/// }
+/// ```
+///
+/// `project` defaults to the current project.
+/// `deps` is one of `"regular"` (the default), `"build"` or `"test"`.
+fn dependency_graph(
+    _: &Lua,
+    (project, deps): (Option<UserDataRef<Project>>, Option<String>),
+) -> mlua::Result<DependencyGraph> {
+    let deps = match deps.as_deref() {
+        None | Some("regular") => LocalPackageLockType::Regular,
+        Some("build") => LocalPackageLockType::Build,
+        Some("test") => LocalPackageLockType::Test,
+        Some(deps) => {
+            return Err(format!(
+                "invalid dependency type '{deps}'. Expected 'regular', 'build' or 'test'."
+            )
+            .into_lua_err())
+        }
+    };
+    let lockfile = match project {
+        Some(project) => project.lockfile(),
+        None => Project::current()
+            .into_lua_err()?
+            .ok_or_else(|| "no project found".into_lua_err())?
+            .lockfile(),
+    }
+    .into_lua_err()?;
+    Ok(lockfile.dependency_graph(&deps))
+}
+
+/// `lux.project.require_graph(config, project?)` returns the modules the project's Lua files
+/// require, and every match for each module, in priority order:
+///
+/// ```lua
+/// {
+///   files = { ["src/foo.lua"] = { { module = "bar", line = 1 } } },
+///   modules = { bar = { { path = "...", location = "project-tree", package = "bar", version = "1.0.0-1" } } },
+/// }
+/// ```
+///
+/// `project` defaults to the current project.
+/// Builtin modules, like `string`, are not resolved.
+fn require_graph(
+    _: &Lua,
+    (config, project): (Config, Option<UserDataRef<Project>>),
+) -> mlua::Result<RequireGraph> {
+    match project {
+        Some(project) => analysis::require_graph(&project, &config),
+        None => analysis::require_graph(
+            &Project::current()
+                .into_lua_err()?
+                .ok_or_else(|| "no project found".into_lua_err())?,
+            &config,
+        ),
+    }
+    .into_lua_err()
+}
+
 #[cfg(test)]
 mod tests {
     use assert_fs::{assert::PathAssert, prelude::PathChild, TempDir};
@@ -82,6 +156,122 @@ type = "builtin"
         std::env::set_current_dir(old_cwd).unwrap();
     }
 
+    #[test]
+    fn lua_api_test_require_graph() {
+        let (project, lua) = create_fake_project();
+        let tree = assert_fs::TempDir::new().unwrap();
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::write(
+            project.join("src/main.lua"),
+            "local foo = require('foo')\nlocal str = require('string')\n",
+        )
+        .unwrap();
+        lua.globals().set("tree", tree.path()).unwrap();
+
+        lua.load(
+            r#"
+            local config = lux.config.builder():lua_version("5.1"):user_tree(tree):build()
+            local project = lux.project.new(project_location)
+            local graph = lux.project.require_graph(config, project)
+            local requires = graph.files["src/main.lua"]
+            assert(#requires == 2, "main.lua should require 2 modules")
+            assert(requires[1].module == "foo", "main.lua should require foo")
+            assert(requires[1].line == 1, "foo should be required on line 1")
+            assert(#graph.modules.foo == 0, "foo should not be found")
+            assert(graph.modules.string == nil, "builtin modules should not be resolved")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn lua_api_test_dependency_graph() {
+        let (project, lua) = create_fake_project();
+        std::fs::write(
+            project.join("lux.lock"),
+            r#"
+        {
+          "version": "1.0.0",
+          "dependencies": {
+            "rocks": {
+              "4e9592a499c9ced4f8ce366db9db7d9c0dd1424ea8d4c8c16c1550ea3a61a696": {
+                "name": "say",
+                "version": "1.4.1-3",
+                "pinned": false,
+                "opt": false,
+                "dependencies": [],
+                "constraint": ">=1.4.0",
+                "binaries": [],
+                "source": "luarocks_rockspec+https://luarocks.org/",
+                "source_url": {
+                  "type": "git",
+                  "url": "https://github.com/lunarmodules/say.git",
+                  "ref": "v1.4.1"
+                },
+                "hashes": {
+                  "rockspec": "sha256-WFKt1iWeyjO9A8SG0KUX8tkS9JvMqoVM8CKBUguuK0Y=",
+                  "source": "sha256-IjNkK1leVtYgbEjUqguVMjbdW+0BHAOCE0pazrVuF50="
+                }
+              },
+              "acdfde00d122aac481c18c906d483478bb536741beb025becc11782a075d125b": {
+                "name": "luassert",
+                "version": "1.9.0-1",
+                "pinned": false,
+                "opt": false,
+                "dependencies": [
+                  "4e9592a499c9ced4f8ce366db9db7d9c0dd1424ea8d4c8c16c1550ea3a61a696"
+                ],
+                "constraint": null,
+                "binaries": [],
+                "source": "luarocks_rockspec+https://luarocks.org/",
+                "source_url": {
+                  "type": "git",
+                  "url": "https://github.com/lunarmodules/luassert.git",
+                  "ref": "v1.9.0"
+                },
+                "hashes": {
+                  "rockspec": "sha256-rTPvF/GK/jMnH/q4wbwTCGBFELWh+JcvHeOCFAbIf64=",
+                  "source": "sha256-jjdB95Vr5iVsh5T7E84WwZMW6/5H2k2R/ny2VBs2l3I="
+                }
+              }
+            },
+            "entrypoints": [
+              "acdfde00d122aac481c18c906d483478bb536741beb025becc11782a075d125b"
+            ]
+          }
+        }
+"#,
+        )
+        .unwrap();
+
+        lua.load(
+            r#"
+            local project = lux.project.new(project_location)
+            local graph = lux.project.dependency_graph(project)
+            assert(#graph.nodes == 2, "graph should have 2 nodes")
+            assert(#graph.edges == 1, "graph should have 1 edge")
+
+            local nodes = {}
+            for _, node in ipairs(graph.nodes) do
+                nodes[node.id] = node
+            end
+            local edge = graph.edges[1]
+            assert(nodes[edge.from].name == "luassert", "luassert should depend on say")
+            assert(nodes[edge.from].entrypoint, "luassert should be an entrypoint")
+            assert(nodes[edge.to].name == "say", "luassert should depend on say")
+            assert(nodes[edge.to].version == "1.4.1-3", "say version should be correct")
+            assert(not nodes[edge.to].entrypoint, "say should not be an entrypoint")
+            assert(not nodes[edge.to].pinned, "say should not be pinned")
+
+            assert(#lux.project.dependency_graph(project, "test").nodes == 0, "test graph should be empty")
+            assert(not pcall(lux.project.dependency_graph, project, "invalid"), "invalid dependency type should error")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
     #[test]
     fn lua_api_test_project() {
         let (project, lua) = create_fake_project();
@@ -119,7 +119,7 @@
         '';
 
         postInstall = ''
-          installManPage target/dist/lx.1
+          installManPage target/dist/*.1
           installShellCompletion target/dist/lx.{bash,fish} --zsh target/dist/_lx
         '';
 
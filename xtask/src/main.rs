@@ -1,14 +1,12 @@
 use std::{
-    env,
-    fs::{self, File},
+    env, fs,
     path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
 use clap::{CommandFactory, ValueEnum};
 use clap_complete::{generate_to, Shell};
-use clap_mangen::Man;
-use lux_cli::Cli;
+use lux_cli::{gen_man::generate_man_pages, Cli};
 
 type DynError = Box<dyn std::error::Error>;
 
@@ -115,13 +113,7 @@ fn build(release: bool) -> Result<(), DynError> {
 }
 
 fn dist_man() -> Result<(), DynError> {
-    fs::create_dir_all(dist_dir())?;
-
-    let cmd = &mut Cli::command();
-
-    Man::new(cmd.clone())
-        .render(&mut File::create(dist_dir().join("lx.1")).unwrap())
-        .unwrap();
+    generate_man_pages(&dist_dir())?;
     Ok(())
 }
 